@@ -0,0 +1,32 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Records the commit opsops is built from for `opsops info` and
+/// `opsops --version --json`. Builds outside a git checkout, e.g. in the nix
+/// sandbox, can pass it as OPSOPS_BUILD_HASH.
+fn main() {
+    println!("cargo:rerun-if-env-changed=OPSOPS_BUILD_HASH");
+    let git = Path::new(".git");
+    if let Ok(head) = std::fs::read_to_string(git.join("HEAD")) {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        if let Some(reference) = head.trim().strip_prefix("ref: ")
+            && git.join(reference).exists()
+        {
+            println!("cargo:rerun-if-changed=.git/{}", reference);
+        }
+    }
+
+    let hash = std::env::var("OPSOPS_BUILD_HASH").ok().or_else(|| {
+        let output = Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    });
+    if let Some(hash) = hash.filter(|h| !h.is_empty()) {
+        println!("cargo:rustc-env=OPSOPS_BUILD_HASH={}", hash);
+    }
+}
@@ -0,0 +1,766 @@
+# Messages of opsops, see src/util/i18n.rs. Every catalog has the same
+# messages in the same order.
+
+## Shared by the commands
+
+invalid-utf8-path = Ungültiges UTF-8 im Pfad:
+file-not-found = Datei nicht gefunden:
+sops-not-installed = 'sops' ist nicht installiert oder nicht im PATH.
+install-it-first = Bitte zuerst installieren.
+age-key-failed = Age-Schlüssel konnte nicht geholt werden:
+sops-launch-failed = sops konnte nicht gestartet werden:
+file-unchanged = Die Datei wurde nicht geändert.
+exit-code = Exit-Code: { $status }
+no-sops-config = .sops.yaml wurde nicht gefunden.
+no-project-root = Das Projektverzeichnis konnte nicht bestimmt werden.
+outside-project = Die Datei muss im Verzeichnis mit der .sops.yaml liegen.
+read-failed = { $path } konnte nicht gelesen werden: { $error }
+write-failed = { $path } konnte nicht geschrieben werden: { $error }
+delete-failed = { $path } konnte nicht gelöscht werden: { $error }
+remove-failed = { $path } konnte nicht entfernt werden: { $error }
+create-failed = { $path } konnte nicht angelegt werden: { $error }
+decrypt-path-failed = { $path } konnte nicht entschlüsselt werden: { $error }
+sops-config-read-failed = .sops.yaml konnte nicht gelesen werden: { $error }
+sops-config-update-failed = .sops.yaml konnte nicht aktualisiert werden: { $error }
+audit-log-failed = Das Audit-Log konnte nicht geschrieben werden: { $error }
+age-key-get-failed = Der Age-Schlüssel konnte nicht geholt werden: { $error }
+create-dir-in-failed = In { $path } konnte kein Verzeichnis angelegt werden: { $error }
+start-failed = '{ $path }' konnte nicht gestartet werden: { $error }
+removed = { $path } entfernt
+restrict-failed = Die Rechte von { $path } konnten nicht eingeschränkt werden: { $error }
+wrote = { $path } geschrieben
+temp-dir-failed = Es konnte kein temporäres Verzeichnis angelegt werden: { $error }
+public-key-failed = Der öffentliche Schlüssel konnte nicht abgeleitet werden: { $error }
+exists-pass-force = { $path } existiert bereits, --force überschreibt die Datei
+not-encrypted = { $path } ist keine verschlüsselte Datei
+created-for-rule = { $path } angelegt, verschlüsselt für die Empfänger seiner Erstellungsregel
+sops-config-error = Fehler beim Lesen der sops-Datei:
+no-encrypted-files = Keine verschlüsselten Dateien gefunden.
+sops-exited = sops wurde beendet mit { $status }
+temp-file-failed = Die temporäre Datei konnte nicht angelegt werden: { $error }
+tracking-failed = Die Liste der entschlüsselten Kopien konnte nicht aktualisiert werden: { $error }
+schema-validate-failed = Die Prüfung gegen das Schema ist fehlgeschlagen:
+secret-validated = Secret { $secret } mit dem Age-Schlüssel aus 1Password geprüft
+secret-applied = Secret { $secret } mit dem Age-Schlüssel aus 1Password angewendet
+skipped = { $path } übersprungen: { $error }
+config-read-failed = Die Konfiguration konnte nicht gelesen werden:
+config-write-failed = Die Konfiguration konnte nicht geschrieben werden:
+op-vaults-failed = Die Tresore konnten nicht abgerufen werden.
+op-no-vaults = Keine Tresore gefunden.
+op-items-failed = Die Einträge konnten nicht abgerufen werden.
+op-no-items = Keine Einträge gefunden.
+op-choose-vault = Wähle einen Tresor
+op-choose-item = Wähle einen Eintrag
+op-choose-field = Wähle ein Feld
+no-files-to-search = Keine verschlüsselten Dateien zum Durchsuchen.
+none = keine
+already-exists = { $path } existiert bereits
+expired-no-access = Abgelaufene Empfänger haben keinen Zugriff mehr
+opsops-config-read-failed = Fehler beim Lesen von .opsops.yaml:
+opsops-config-write-failed = .opsops.yaml konnte nicht geschrieben werden:
+registry-empty = Das Mitgliederverzeichnis ist leer.
+registry-sync-first = Führe zuerst 'opsops registry sync' aus.
+registry-read-failed = Fehler beim Lesen des Verzeichnisses:
+unknown = unbekannt
+user-config = Benutzerkonfig.
+file-outside-project = Die Datei liegt außerhalb des Verzeichnisses mit der .sops.yaml.
+rule-number = Regel #{ $number }
+not-found = nicht gefunden
+output-write-failed = Die Ausgabe konnte nicht geschrieben werden: { $error }
+invalid-regex = Ungültiger regulärer Ausdruck:
+error-prefix = Fehler:
+record-failed = Aufzeichnung nach { $path } nicht möglich: { $error }
+chdir-failed = Wechsel nach { $path } nicht möglich: { $error }
+agent-unsupported = Der Agent braucht Unix-Sockets, die diese Plattform nicht hat
+docs-generated-man = Man-Page erzeugt unter: { $path }
+docs-generated-completions = Fish-Vervollständigungen erzeugt unter: { $path }
+docs-to-install = Zur Installation:
+
+## encrypt
+
+refusing-to-encrypt = Verschlüsseln verweigert für
+allow-protected-hint = Mit --allow-protected trotzdem verschlüsseln.
+encrypting-protected = { $path } wird verschlüsselt, obwohl { $reason } (--allow-protected)
+add-escrow-hint = Füge ihn der Regel in .sops.yaml hinzu, z. B. mit 'opsops target-keys'.
+stale-copy-aborted = Abgebrochen. Übernimm zuerst die Änderungen von upstream in die Klartext-Kopie.
+encrypting-to = 🔐 Verschlüssele nach
+encrypt-note-failed = Fehler beim Verschlüsseln der Notiz:
+encrypted-note = Notiz erfolgreich verschlüsselt
+encrypted-file = Datei erfolgreich mit SOPS verschlüsselt
+encrypt-failed = Fehler beim Verschlüsseln der Datei.
+encrypt-diff-notes = --diff fasst keine Notizen zusammen, nur YAML-, JSON- und dotenv-Dateien.
+encrypt-summary-failed = Die Verschlüsselung kann nicht zusammengefasst werden: { $error }
+encrypt-looks-like-mistake = { $path }: es sieht nach einem Versehen aus
+encrypt-guardrails-hint = Setze guardrails.severity in .opsops.yaml auf warning, um es trotzdem zu verschlüsseln
+encrypt-schema-mismatch = { $path }: es passt nicht zu { $schema }
+encrypt-stale-copy = { $source } hat sich geändert, nachdem { $path } daraus entschlüsselt wurde (z. B. hat jemand im Team Änderungen gepusht).
+encrypt-show-diff = Strukturellen Vergleich zeigen
+encrypt-anyway = Trotzdem verschlüsseln und die Änderungen verwerfen
+encrypt-abort = Abbrechen
+encrypt-what-now = Was möchtest du tun?
+encrypt-diff-unsupported = Nur YAML- und JSON-Dateien können strukturell verglichen werden.
+encrypt-no-differences = Keine Unterschiede in Schlüsseln oder Werten.
+encrypt-would-change = Das Verschlüsseln von { $path } würde diese Schlüssel im Original ändern:
+
+## decrypt
+
+decrypting = 🔓 Entschlüssele
+decrypt-note-failed = Fehler beim Entschlüsseln der Notiz:
+decrypted-note = Notiz erfolgreich entschlüsselt
+decrypted-file = Datei erfolgreich mit SOPS entschlüsselt
+decrypt-failed = Fehler beim Entschlüsseln der Datei.
+decrypt-not-ignored = { $path } wird von git nicht ignoriert, der Klartext könnte committet werden. Füge es zu .gitignore hinzu.
+decrypt-tracking-failed = Die entschlüsselte Kopie konnte nicht vermerkt werden: { $error }
+decrypt-includes-in-note = --resolve-includes wird für Markdown-Notizen nicht unterstützt
+decrypt-includes-plaintext = { $path } ist nicht verschlüsselt, gib es mit 'opsops read --resolve-includes' aus
+decrypt-output-newer = { $output } ist neuer als { $path } und enthält vielleicht Änderungen oder die Entschlüsselung eines anderen Terminals. Mit --unique-output wird an einen eigenen Pfad entschlüsselt.
+decrypt-skipping-in-place = Überspringe { $count } Datei(en) ohne .enc-Endung oder decrypt_outputs:-Eintrag, sie würden an Ort und Stelle entschlüsselt
+decrypt-nothing = Nichts zu entschlüsseln
+
+## rm
+rm-not-encrypted = { $path } ist keine verschlüsselte Datei, lösche sie mit rm
+rm-record-removal = { $error }. Führe git rm --cached { $name } aus, um das Löschen einzutragen.
+rm-deleted = { $name } gelöscht
+rm-shredded-copy = Die Klartextkopie { $path } wurde überschrieben und gelöscht
+rm-shred-failed = Die Klartextkopie { $path } konnte nicht überschrieben werden: { $error }
+rm-remove-rules = Die { $count } Regel(n) für { $name } aus der .sops.yaml entfernen?
+rm-kept-rules = Die Regel(n) für { $name } bleiben in der .sops.yaml, --yes entfernt sie
+rm-removed-rules = { $count } Regel(n) aus der .sops.yaml entfernt
+rm-undo = Rückgängig machen mit { $command }
+rm-access-kept = Löschen entzieht keinen Zugriff: { $count } Empfänger konnten { $name } entschlüsseln und haben eventuell Kopien. Tausche die enthaltenen Geheimnisse aus, falls das wichtig ist: { $recipients }
+rm-access-kept-in-history = Löschen entzieht keinen Zugriff: { $count } Empfänger konnten { $name } entschlüsseln, die Git-Historie enthält die Datei noch, und sie haben eventuell Kopien. Tausche die enthaltenen Geheimnisse aus, falls das wichtig ist: { $recipients }
+
+## kubeconfig
+kubeconfig-not-a-kubeconfig = { $path } sieht nicht wie eine Kubeconfig aus, sie hat keine clusters
+kubeconfig-starting-shell = Starte { $shell } mit KUBECONFIG={ $path }, beim Beenden wird die Kubeconfig gelöscht
+kubeconfig-wiped = { $path } gelöscht
+kubeconfig-wipe-failed = { $path } konnte nicht gelöscht werden: { $error }
+kubeconfig-wipe-failed-by-hand = { $path } konnte nicht gelöscht werden: { $error }. Lösche die Datei von Hand.
+kubeconfig-not-written-by-use = { $path } wurde nicht von 'opsops kubeconfig use' geschrieben
+
+## teardown
+teardown-heading = 🧹 Entferne opsops aus
+teardown-found-encrypted = { $count } verschlüsselte Datei(en) in diesem Projekt gefunden.
+teardown-decrypt-all = Alle an Ort und Stelle entschlüsseln? Dabei landen Geheimnisse im Klartext auf der Festplatte
+teardown-type-decrypt = Zum Bestätigen 'decrypt' eingeben
+teardown-not-confirmed = Nicht bestätigt, die Dateien bleiben verschlüsselt
+teardown-no-hooks = Keine Git-Hooks von opsops installiert
+teardown-removed-hook = Git-Hook { $path } entfernt
+teardown-removed-git-config = Git-Konfiguration { $key } entfernt
+teardown-kept-backups = { $path } bis auf die Backups in { $backups } entfernt, 'opsops restore <file>' stellt Dateien wieder her
+teardown-complete = Entfernen abgeschlossen.
+teardown-still-encrypted = Git-Hooks und Zustand bleiben, einige Dateien sind noch verschlüsselt
+
+## agent
+agent-stopped = Agent beendet
+agent-not-running = Es läuft kein Agent
+agent-listening = Agent lauscht auf
+agent-clients-need = Clients brauchen { $setting }
+agent-configure-failed = Der Socket konnte nicht eingerichtet werden: { $error }
+agent-accept-failed = Die Verbindung konnte nicht angenommen werden: { $error }
+agent-keys-wiped = Agent beendet, Schlüssel aus dem Speicher gelöscht
+agent-already-running = Auf { $path } läuft bereits ein Agent
+agent-remove-stale-failed = Der veraltete Socket { $path } konnte nicht entfernt werden: { $error }
+agent-bind-failed = { $path } konnte nicht gebunden werden: { $error }
+
+## argocd
+argocd-add-patches = Füge die Patches der Kustomization hinzu, die Argo CD installiert:
+
+## bench
+bench-heading = ⏱  Messe opsops
+bench-no-key = Massenverschlüsselung übersprungen, kein Age-Schlüssel verfügbar
+bench-rule-matching = Regelabgleich
+bench-files-rounds = ({ $files } Dateien, { $rounds } Durchläufe)
+bench-synthetic-files-failed = Die Testdateien konnten nicht angelegt werden: { $error }
+bench-key-retrieval = Schlüsselabruf
+bench-rounds = ({ $rounds } Durchläufe)
+bench-skipping-key-retrieval = Schlüsselabruf übersprungen: { $error }
+bench-bulk-encryption = Massenverschlüsselung
+bench-files-workers = ({ $files } Dateien, { $workers } Worker)
+bench-no-sops = Massenverschlüsselung übersprungen, sops ist nicht installiert
+bench-no-samples = keine Messwerte
+bench-agent-not-running = läuft nicht, starte ihn mit 'opsops agent'
+
+## ci
+ci-include-gitlab = Binde sie in der .gitlab-ci.yml ein:
+ci-token-github = Lege ein Token eines 1Password-Dienstkontos als Repository-Secret OP_SERVICE_ACCOUNT_TOKEN an
+ci-token-gitlab = Lege ein Token eines 1Password-Dienstkontos als maskierte CI/CD-Variable OP_SERVICE_ACCOUNT_TOKEN an
+ci-passphrase-github = Lege die Passphrase als Repository-Secret OPSOPS_PASSPHRASE an
+ci-passphrase-gitlab = Lege die Passphrase als maskierte CI/CD-Variable OPSOPS_PASSPHRASE an
+
+## completions
+completions-install-unsupported = Vervollständigungen für { $shell } können nicht installiert werden. Füge stattdessen dies deinem Profil hinzu: opsops completions { $shell } | Out-String | Invoke-Expression
+completions-up-to-date = Die { $shell }-Vervollständigungen in { $path } sind aktuell
+completions-installed = { $shell }-Vervollständigungen nach { $path } installiert
+completions-add-line = '{ $line }' zu { $path } hinzufügen?
+completions-added = Vervollständigungen zu { $path } hinzugefügt
+completions-load-them = Um sie zu laden, füge diese Zeile zu { $path } hinzu:
+completions-no-shell = Die Shell konnte nicht erkannt werden.
+completions-name-shell = Gib sie an, z. B. 'opsops completions zsh'.
+completions-write-failed = Die Vervollständigungen konnten nicht geschrieben werden: { $error }
+completions-no-home = Das Home-Verzeichnis konnte nicht ermittelt werden
+
+## cp
+cp-names-no-value = { $location } benennt keinen Wert, verwende { $syntax }
+cp-copied = { $source } nach { $destination } kopiert
+
+## diff
+diff-unchanged = { $path } ist seit { $rev } unverändert
+diff-plaintext-unchanged = Der Klartext von { $path } ist seit { $rev } unverändert
+diff-working-tree = Arbeitsverzeichnis
+
+## doctor
+doctor-found-sops = sops gefunden: { $path } { $version }
+doctor-no-sops = sops ist nicht installiert oder nicht im PATH. Bitte installiere sops.
+doctor-found-op = 1Password CLI (op) gefunden: { $path } { $version }
+doctor-no-op = 1Password CLI (op) ist nicht installiert oder nicht im PATH. Bitte installiere op.
+doctor-host-key = { $host } passt zum host_keys-Eintrag '{ $pattern }', sein Schlüssel kommt aus { $identity }
+doctor-passphrase-key = Der Age-Schlüssel wird aus einer Passphrase abgeleitet ({ $key })
+doctor-no-item = Keine 1Password-Referenz in .opsops.yaml gefunden. Richte sie mit 'opsops init' ein.
+doctor-item-found = 1Password-Eintrag in .opsops.yaml gefunden: { $item }
+doctor-private-key = Privater Schlüssel geholt: { $key }
+doctor-no-identity = Der Age-Schlüssel enthält keine Identität
+doctor-your-public-key = Dein öffentlicher Schlüssel ist: { $key }
+doctor-your-public-keys = Deine öffentlichen Schlüssel sind:
+doctor-rules-without-age = Regeln ohne Age-Schlüssel:
+doctor-rule-item = - Regel #{ $number }: { $pattern }
+doctor-no-matching-key = Kein passender öffentlicher Schlüssel in .sops.yaml gefunden.
+doctor-matching-key = Passender öffentlicher Schlüssel gefunden: { $key }
+doctor-matching-keys = { $count } von { $total } Identitäten passen zu Empfängern in .sops.yaml: { $keys }
+doctor-patterns-ok = Alle path_regex-Muster sind verankert oder maskiert
+doctor-also-matches = passt auch auf { $path }
+doctor-exact-match = für eine exakte Übereinstimmung: { $pattern }
+doctor-plain-path = Regel #{ $number } path_regex '{ $pattern }' ist ein einfacher Pfad: '.' passt auf jedes Zeichen und das Muster passt überall in einem Pfad
+doctor-invalid-escrow = Ungültiger escrow_recipient: { $error }
+doctor-escrow-ok = Alle Regeln verschlüsseln für den Treuhand-Empfänger { $key }
+doctor-escrow-missing = Regeln verschlüsseln nicht für den Treuhand-Empfänger { $key }
+doctor-expiry-read-failed = recipient_expiry konnte nicht gelesen werden: { $error }
+doctor-expired-item = - { $recipient } abgelaufen nach { $date }: { $rules } Regel(n), { $files } Datei(en)
+doctor-expire-sweep = Entferne sie mit 'opsops key expire-sweep' und rotiere die Datenschlüssel
+doctor-expired-access = Abgelaufene Empfänger können noch Geheimnisse entschlüsseln
+doctor-destinations-ok = { $count } Zielregel(n) sind gültig
+doctor-invalid-destinations = Ungültige destination_rules
+doctor-recorded-key = Schlüssel von { $project } in { $path } vermerkt
+doctor-key-unique = Kein anderes Projekt im Schlüsselverzeichnis verwendet diesen Schlüssel
+doctor-leaked-key = Ein geleakter Schlüssel legt sie alle offen, verwende einen Schlüssel pro Projekt ('opsops generate-age-key')
+doctor-key-shared = Der Age-Schlüssel dieses Projekts schützt auch { $count } andere(s) Projekt(e)
+doctor-scan-failed = Das Repository konnte nicht nach privaten Schlüsseln durchsucht werden: { $error }
+doctor-no-committed-keys = Keine privaten Schlüssel in versionierten Dateien oder der Historie gefunden (letzte { $count } Commits)
+doctor-finding-in-commit = - { $path } enthält { $kind } (Commit { $commit })
+doctor-finding = - { $path } enthält { $kind }
+doctor-remediate = Zur Behebung:
+doctor-remediate-rotate = 1. Rotiere den Schlüssel: erzeuge mit 'opsops generate-age-key' einen neuen und verschlüssele alle Dateien neu
+doctor-remediate-history = 2. Entferne den Schlüssel aus der Historie, z. B. mit 'git filter-repo' oder BFG, und pushe dann mit --force
+doctor-remediate-reclone = 3. Bitte alle Mitwirkenden, das Repository neu zu klonen
+doctor-committed-keys = HOHE SCHWERE: Private Schlüssel sind in diesem Repository committet!
+
+## drift
+drift-none = Alle { $count } Datei(en) entsprechen ihren Regeln.
+drift-unresolved = { $count } von { $total } Datei(en) weichen von ihren Regeln ab.
+
+## edit
+edit-create-hint = (--create legt sie an)
+edit-creating = 📝 Lege Datei an:
+edit-opening = 📝 Öffne Datei zum Bearbeiten:
+edit-create-failed = Die Datei konnte nicht angelegt werden:
+edit-saved = Datei erfolgreich bearbeitet und gespeichert.
+edit-not-created = Nichts gespeichert, nicht angelegt:
+edit-failed = Fehler beim Bearbeiten der Datei.
+edit-schema-decrypt-failed = { $path } konnte zur Prüfung nicht entschlüsselt werden: { $error }
+edit-schema-mismatch = { $path } wurde gespeichert, passt aber nicht zu { $schema }:
+edit-again = Behebe es mit einem erneuten 'opsops edit'.
+edit-note-failed = Fehler beim Bearbeiten der Notiz:
+edit-editor-exited = Der Editor wurde beendet mit { $status }
+edit-read-note-failed = Die bearbeitete Notiz konnte nicht gelesen werden: { $error }
+
+## escrow
+escrow-not-set = Kein escrow_recipient in .opsops.yaml gesetzt.
+escrow-add-key = Füge zuerst den öffentlichen Schlüssel deiner Treuhand-Identität hinzu.
+escrow-rule = Regel #{ $number } { $pattern }
+escrow-unrecoverable = { $count } Regeln oder Dateien sind nicht für den Treuhand-Empfänger verschlüsselt.
+escrow-add-to-rules = Füge ihn den Regeln hinzu und verschlüssele die Dateien neu.
+escrow-recoverable = Alles ist durch den Treuhand-Empfänger wiederherstellbar
+
+## export
+export-parse-failed = Die entschlüsselte Datei konnte nicht gelesen werden: { $error }
+export-table-failed = Export als Tabelle nicht möglich:
+export-exported = { $path } nach { $output } exportiert
+export-not-a-map = Nur Dokumente mit Schlüsseln auf oberster Ebene können exportiert werden
+export-no-values = { $path } enthält keine Werte zum Exportieren
+export-op-failed = Export nach 1Password nicht möglich:
+export-update-failed = { $item } in { $vault } konnte nicht aktualisiert werden: { $error }
+export-updated = { $count } Feld(er) von { $item } in { $vault } aus { $path } aktualisiert
+export-removed-fields-stay = Seit dem letzten Export aus der Datei entfernte Felder bleiben im Eintrag.
+export-exported-fields = { $count } Feld(er) von { $path } nach { $item } in { $vault } exportiert
+
+## flux
+flux-none = Keine Flux-Kustomizations gefunden.
+flux-kustomization = Kustomization { $name } ({ $path })
+flux-decrypts = { $kustomization } entschlüsselt mit sops über das Secret { $secret }
+flux-no-secret-ref = { $kustomization } verwendet sops, hat aber kein decryption.secretRef
+flux-needs-secret = Flux braucht den Age-Schlüssel in einem Secret, siehe 'opsops flux create-secret'
+flux-other-provider = { $kustomization } entschlüsselt mit dem Provider '{ $provider }' statt mit sops
+flux-no-decryption = { $kustomization } hat keine Entschlüsselung eingerichtet
+flux-add-provider = Füge spec.decryption.provider: sops hinzu, wenn sie verschlüsselte Dateien ausrollt
+flux-no-kubectl = kubectl nicht gefunden, der Vergleich mit den Secrets im Cluster entfällt
+flux-secret-read-failed = Das Secret { $secret } konnte nicht gelesen werden: { $error }
+flux-create-secret = Lege es mit 'opsops flux create-secret --name { $name } --namespace { $namespace }' an
+flux-secret-matches = Das Secret { $secret } enthält den Schlüssel aus 1Password
+flux-secret-differs = Das Secret { $secret } enthält einen anderen Age-Schlüssel als 1Password
+flux-update-secret = Aktualisiere es mit 'opsops flux create-secret --name { $name } --namespace { $namespace }'
+
+## generate-age-key
+key-public-label = 🔑 Öffentlich:
+key-private-label = 🔐 Privat:
+key-recovery-label = 📝 Wiederherstellung:
+key-recovery-hint = Die Wiederherstellungsphrase stellt den Schlüssel mit 'opsops key recover --mnemonic' wieder her. Schreib sie auf und bewahre sie offline auf.
+key-save-in-op = Möchtest du diesen Schlüssel in 1Password speichern?
+key-choose-vault = Wähle einen 1Password-Tresor für den Eintrag
+key-choose-item-name = Wähle einen Namen für den 1Password-Eintrag
+key-save-securely = Denk daran, diesen Schlüssel an einem sicheren Ort zu speichern!
+key-add-to-sops-config = Vergiss nicht, den öffentlichen Schlüssel zu .sops.yaml hinzuzufügen!
+
+## grep
+grep-invalid-pattern = Ungültiges Muster: { $error }
+
+## help
+help-guides = Anleitungen:
+help-read-guide = Lies eine mit 'opsops help <anleitung>', Manpages mit 'opsops man [befehl]'.
+help-unknown = Kein Befehl und keine Anleitung namens '{ $name }'. Anleitungen: { $guides }
+
+## import
+import-refusing-to-overwrite = Überschreiben verweigert:
+import-invalid-table = Ungültige Tabelle
+import-encrypt-failed = Der Import konnte nicht verschlüsselt werden:
+import-imported-entries = { $count } Einträge aus { $path } nach { $output } importiert
+import-plaintext-left = { $path } enthält noch den Klartext, lösche es, sobald du den Import geprüft hast.
+import-pass-path = Gib mit --path die Zuordnung an, unter die die Felder kommen, oder wähle Felder mit --field
+import-duplicate-label = Der Eintrag hat mehrere Felder namens '{ $label }', wähle sie mit --field
+import-no-field = Der Eintrag hat kein Feld '{ $label }' mit einem Wert
+import-ambiguous-label = Der Eintrag hat mehrere Felder namens '{ $label }', lies eines über seine op://-Referenz
+import-single-field = { $reference } benennt ein einzelnes Feld, --field wählt Felder eines ganzen Eintrags
+import-invalid-reference = '{ $reference }' muss die Form op://<vault>/<item>[/<section>]/<field> oder op://<vault>/<item> haben
+import-imported-op = { $paths } aus 1Password nach { $path } importiert
+
+## info
+info-version = Version
+info-build = Build
+info-target = Ziel
+info-tools = Werkzeuge
+info-configuration = Konfiguration
+info-project-root = Projektwurzel
+info-key = Schlüssel
+info-key-source = (aus { $source })
+
+## init
+init-failed = Die Initialisierung ist fehlgeschlagen:
+init-no-reference = ⚠️  Keine 1Password-Referenz in .opsops.yaml eingerichtet.
+init-nothing-to-do = .sops.yaml existiert. Nichts zu tun.
+init-missing = .sops.yaml fehlt.
+init-create-basic = Möchtest du eine einfache .sops.yaml anlegen?
+init-create-failed = Die Konfigurationsdatei konnte nicht angelegt werden:
+init-created-basic = Einfache .sops.yaml angelegt.
+init-create-manually = Bitte lege eine .sops.yaml von Hand nach dieser Anleitung an: https://github.com/getsops/sops#using-sops-yaml-conf-to-select-kms-pgp-and-age-for-new-files
+init-already-exists = .sops.yaml existiert bereits
+init-created = .sops.yaml mit { $count } Regel(n) angelegt, die für { $key } verschlüsseln
+init-wrote-reference = 1Password-Referenz { $reference } geschrieben
+init-pass-reference = Keine 1Password-Referenz eingerichtet, gib den Schlüssel als op://... an oder verwende --op-item
+init-wrote-passphrase = Passphrase-Einstellungen in .opsops.yaml geschrieben
+init-passphrase-only = Ohne die Passphrase lässt sich nichts entschlüsseln. Verschiebe den Schlüssel mit 'opsops key upgrade' nach 1Password, sobald das Projekt wächst.
+init-assign-key = Möchtest du einen Age-Schlüssel aus 1Password zuweisen?
+init-writing-reference = 🔐 Schreibe 1Password-Referenz in die Konfiguration: { $reference }
+init-read-or-create-failed = Die Konfiguration konnte nicht gelesen oder angelegt werden: { $error }
+init-updated-reference = .opsops.yaml erfolgreich mit der 1Password-Referenz aktualisiert.
+init-not-a-key = '{ $field }' enthält keinen geheimen Age-Schlüssel:
+init-choose-another = Ein anderes Feld wählen?
+
+## inspect
+inspect-expired = { $date } (abgelaufen)
+inspect-expires-soon = { $date } (innerhalb von { $days } Tagen)
+inspect-not-a-value = { $location } ist kein einzelner Wert
+inspect-no-pem = { $location } enthält kein PEM-Zertifikat und keinen Schlüssel
+inspect-certificate = Zertifikat
+inspect-private-key = Privater Schlüssel
+inspect-public-key = Öffentlicher Schlüssel
+inspect-block-of = ({ $number } von { $count })
+inspect-subject = Subjekt
+inspect-issuer = Aussteller
+inspect-not-before = nicht vor
+inspect-expires = läuft ab
+inspect-key = Schlüssel
+inspect-no-certificates = Keine Zertifikate in { $count } verschlüsselten Datei(en).
+
+## list-config
+list-no-config = Fehler: Keine SOPS-Konfigurationsdatei gefunden.
+list-parse-failed = YAML konnte nicht gelesen werden:
+list-includes-failed = Die Einbindungen konnten nicht aufgelöst werden:
+list-item = Zugewiesener 1Password-Eintrag:
+list-rules = Regeln:
+list-rule = 🔹 Regel #
+list-from = 📎 Aus:
+list-file-pattern = 📂 Dateimuster:
+list-age-keys = 🔑 Age-Schlüssel:
+list-age-key = 🔑 Age-Schlüssel:
+list-invalid-pattern = Ungültiges path_regex
+list-no-matches = Passt auf keine Datei in diesem Projekt
+list-matching-files = 📄 Passende Dateien:
+list-and-more = ... und { $count } weitere
+list-overlaps = Dateien, auf die mehrere Regeln passen (sops nimmt die erste):
+list-destinations = Ziele:
+list-destination = 🔸 Ziel #
+list-publishes-to = 📤 Veröffentlicht nach:
+list-reencrypted-for = 🔑 Neu verschlüsselt für:
+list-used-by-sops = Diese Konfiguration wird beim Verschlüsseln mit SOPS verwendet.
+
+## man
+man-no-subcommand = '{ $command }' hat keinen Unterbefehl '{ $name }'
+man-render-failed = Die Manpage konnte nicht erzeugt werden: { $error }
+man-write-failed = Die Manpage konnte nicht geschrieben werden: { $error }
+
+## meta
+meta-not-encrypted = { $path } hat keine sops-Metadaten, es ist nicht verschlüsselt
+meta-sops-version = sops-Version
+meta-last-modified = zuletzt geändert
+meta-present = vorhanden
+meta-missing = fehlt
+meta-encrypted-values = verschlüsselte Werte
+meta-recipients = Empfänger
+meta-other = andere
+meta-key-group = Schlüsselgruppe { $number }
+meta-shamir-threshold = Shamir-Schwelle
+
+## mv
+mv-not-encrypted = { $path } ist keine verschlüsselte Datei, verschiebe sie mit mv
+mv-outside-project = Beide Pfade müssen im Verzeichnis mit der .sops.yaml liegen.
+mv-failed = { $path } konnte nicht nach { $destination } verschoben werden: { $error }
+mv-config-failed = .sops.yaml konnte nicht aktualisiert werden, nichts wurde verschoben: { $error }
+mv-record-move = { $error }. Vermerke das Verschieben mit git add -A.
+mv-moved = { $path } nach { $destination } verschoben
+mv-updated-rules = { $count } Regel(n) in .sops.yaml aktualisiert
+mv-no-rule = Keine Erstellungsregel deckt { $path } mehr ab. Füge mit { $command } eine hinzu, bevor du es bearbeitest.
+mv-other-rule = { $path } fällt jetzt unter Regel #{ $number } ({ $pattern }), Änderungen werden mit ihren Schlüsseln verschlüsselt
+mv-every-file = jede Datei
+
+## key
+key-recovery-phrase = Wiederherstellungsphrase
+key-recovery-read-failed = Die Wiederherstellungsphrase konnte nicht gelesen werden:
+key-recovery-invalid = Ungültige Wiederherstellungsphrase:
+key-recovered-recipient = Der wiederhergestellte Schlüssel ist ein Empfänger in .sops.yaml
+key-recovered-not-recipient = Der wiederhergestellte Schlüssel ist in keiner Regel in .sops.yaml Empfänger
+key-skipped-key-groups = Regel für '{ $pattern }' übersprungen, sie verwendet Schlüsselgruppen. Füge den Schlüssel von Hand einer Gruppe hinzu.
+key-added-members = { $members } zu .sops.yaml hinzugefügt
+key-run-drift-fix = Verschlüssele vorhandene Dateien mit 'opsops drift --fix' neu für sie
+key-none-expired = Kein Empfänger ist abgelaufen.
+key-expired-after = { $recipient } abgelaufen nach { $date }
+key-expired-rule = Regel #{ $number }: { $pattern }
+key-removed-expired = { $count } abgelaufene(n) Empfänger aus .sops.yaml entfernt
+key-access-again = { $recipient } hat wieder Zugriff
+key-sweep-incomplete = Einige Dateien gewähren abgelaufenen Empfängern noch Zugriff. Führe die Bereinigung erneut aus, um sie zu wiederholen.
+key-no-passphrase = Dieses Projekt verwendet keinen Passphrase-Schlüssel, nichts umzustellen.
+key-pass-reference = Gib die 1Password-Referenz des neuen Schlüssels mit --op-item op://... an
+key-new-key-failed = Der neue Schlüssel konnte nicht geholt werden:
+key-derive-failed = Der Passphrase-Schlüssel konnte nicht abgeleitet werden:
+key-replaced-passphrase = Passphrase-Schlüssel in .sops.yaml durch { $key } ersetzt
+key-still-passphrase = noch für den Passphrase-Schlüssel verschlüsselt
+key-not-encrypted-to = nicht für { $key } verschlüsselt
+key-upgrade-incomplete = Einige Dateien sind noch für den Passphrase-Schlüssel verschlüsselt. Führe 'opsops key upgrade' erneut aus, um sie zu wiederholen.
+key-upgraded = Das Projekt verwendet jetzt den Schlüssel in 1Password: { $reference }
+key-keep-passphrase = Ältere Commits sind noch für den Passphrase-Schlüssel verschlüsselt, bewahre die Passphrase auf, solange sie wichtig sind
+
+## new
+new-pass-key = Gib den Schlüssel, für den verschlüsselt wird, mit --from-key <age public key|op://...> an oder verwende --passphrase.
+new-created-repository = git-Repository in { $path } angelegt
+new-encrypt-failed = { $path } konnte nicht verschlüsselt werden (sops wurde beendet mit { $status }). Entferne es vor dem Commit.
+new-installed-hook = pre-commit-Hook installiert
+new-hook-failed = Der pre-commit-Hook konnte nicht installiert werden: { $error }
+new-not-repository-root = { $path } ist nicht die Wurzel des Repositorys, verschiebe .github/workflows/secrets.yml dorthin, damit CI es findet
+new-created = Projekt angelegt!
+new-next-steps = Nächste Schritte:
+new-has-config = { $path } hat bereits eine .sops.yaml
+new-refusing-to-overwrite = Vorhandene Dateien in { $path } werden nicht überschrieben: { $files }
+
+## paths
+paths-not-created = (noch nicht angelegt)
+paths-user = Benutzer
+paths-config-dir = Konfig.-Verz.
+paths-key-registry = Schlüsselverz.
+paths-cache-dir = Cache-Verz.
+paths-reference-cache = Referenz-Cache
+paths-state-dir = Zustandsverz.
+paths-agent-socket = Agent-Socket
+paths-project = Projekt
+paths-root = Wurzel
+paths-state = Zustand
+paths-locks = Sperren
+
+## preview
+preview-already-encrypted = ist bereits verschlüsselt.
+preview-no-rule = Keine Erstellungsregel passt auf
+
+## publish
+publish-not-encrypted = ist nicht verschlüsselt.
+publish-invalid-destinations = Ungültige destination_rules in .sops.yaml:
+publish-no-rule = Keine Zielregel passt auf
+publish-publishing = Veröffentliche { $path } nach { $destination }
+publish-published = Datei erfolgreich mit SOPS veröffentlicht
+publish-failed = Fehler beim Veröffentlichen der Datei.
+
+## read
+read-file-failed = Datei konnte nicht gelesen werden:
+read-note-options = --format, --extract, --redact und --resolve-includes werden für Markdown-Notizen nicht unterstützt:
+
+## registry
+registry-no-source = Keine Quelle für das Verzeichnis.
+registry-no-source-hint = Gib eine an (op://<vault>/<note> oder <git url>[#<path>]) oder setze member_registry in der .opsops.yaml.
+registry-syncing = Synchronisiere das Mitgliederverzeichnis von { $source }
+registry-sync-failed = Das Verzeichnis konnte nicht synchronisiert werden:
+registry-new-key = (neuer Schlüssel)
+registry-synced = { $count } Mitglied(er) nach { $path } synchronisiert
+registry-write-failed = Das Verzeichnis konnte nicht geschrieben werden:
+
+## replay
+replay-start-failed = Start fehlgeschlagen: { $error }
+replay-exit = Exit { $code }
+replay-killed = durch ein Signal beendet
+replay-took = { $outcome } in { $duration }
+replay-recorded = Sitzung aufgezeichnet
+replay-command = Befehl
+replay-platform = Plattform
+replay-variables = Variablen
+replay-external-commands = Externe Befehle
+replay-errors = Fehler
+replay-exited = Beendet mit { $code } nach { $duration }
+replay-exited-early = Vorzeitig beendet, der Exit-Code wurde nicht aufgezeichnet
+
+## restore
+restore-no-backups = Keine Sicherungen gefunden für
+restore-no-backup-at = Keine Sicherung von { $path } zu { $at }.
+restore-list-hint = Liste sie mit --list auf.
+restore-backed-up = Aktuelle Version nach { $path } gesichert
+restore-failed = { $path } konnte nicht wiederhergestellt werden: { $error }
+restore-restored = { $path } aus der Sicherung vom { $timestamp } wiederhergestellt
+
+## scan
+scan-no-repository = Nicht in einem Git-Repository.
+scan-failed = Das Repository konnte nicht durchsucht werden:
+scan-clean = Keine privaten Schlüssel in versionierten Dateien oder im Verlauf gefunden (letzte { $history } Commits)
+scan-committed = Private Schlüssel sind eingecheckt. Tausche sie aus und entferne sie aus dem Verlauf, siehe 'opsops doctor'.
+
+## serve
+serve-stdin-failed = Von stdin konnte nicht gelesen werden: { $error }
+
+## set
+set-value = Wert
+set-read-failed = Der Wert konnte nicht gelesen werden:
+set-generated = { $target } erzeugt
+set-done = { $target } gesetzt
+
+## set-key
+set-key-not-found = Datei nicht gefunden.
+set-key-unsupported = Nur YAML- und JSON-Dateien werden unterstützt.
+set-key-no-extension = Die Datei hat keine Endung. Nur YAML- und JSON-Dateien werden unterstützt.
+set-key-no-public-key = Aus dem Age-Schlüssel konnte kein öffentlicher Schlüssel abgeleitet werden.
+set-key-regex-failed = Fehler beim Ermitteln des regulären Ausdrucks:
+set-key-updated = .sops.yaml erfolgreich aktualisiert
+set-key-next-step = Du kannst die Datei jetzt verschlüsseln mit:
+set-key-conditional-pattern = Verwende encrypted_regex '{ $pattern }' aus rule_conditions in der .opsops.yaml
+set-key-pattern-all = Alle Werte (ganze Datei verschlüsseln)
+set-key-pattern-kubernetes = Kubernetes (Felder data, stringData, password, ingress, token)
+set-key-pattern-talos = Talos-Konfigurationsgeheimnisse (secrets-Abschnitte, Zertifikate, Schlüssel)
+set-key-pattern-common = Übliche sensible Daten (Passwörter, Tokens, Schlüssel, Zugangsdaten)
+set-key-pattern-custom = Eigenes Muster (eigenen regulären Ausdruck angeben)
+set-key-choose-pattern = Was soll in dieser Datei verschlüsselt werden?
+set-key-enter-pattern =
+    Gib einen regulären Ausdruck für die zu verschlüsselnden Schlüssel ein
+    Beispiel: ^(password|api_key|secret)
+set-key-use-pattern = Dieses Muster verwenden?
+
+## setup
+setup-title = 🧭 opsops wird eingerichtet
+setup-install-missing = Installiere die fehlenden Werkzeuge und führe 'opsops setup' erneut aus.
+setup-signed-in = Bei 1Password angemeldet
+setup-signing-in = Nicht bei 1Password angemeldet, führe 'op signin' aus...
+setup-sign-in-failed = Die Anmeldung bei 1Password ist fehlgeschlagen. Führe 'op signin' aus und versuche es erneut.
+setup-age-key-failed = Der Age-Schlüssel konnte nicht gelesen werden:
+setup-public-key = Verwende den öffentlichen Schlüssel:
+setup-wrote-user-config = Benutzerkonfiguration nach { $path } geschrieben
+setup-user-config-failed = Die Benutzerkonfiguration konnte nicht geschrieben werden: { $error }
+setup-complete = Einrichtung abgeschlossen!
+setup-next-step = Verschlüssele deine erste Datei mit:
+setup-found-tool = { $tool } gefunden: { $path }
+setup-missing-tool = { $tool } ist nicht installiert oder nicht im PATH.
+setup-install-hint = Installiere es mit:
+setup-generate-key = Einen neuen Age-Schlüssel erzeugen und in 1Password speichern
+setup-existing-key = Einen vorhandenen Age-Schlüssel aus 1Password verwenden
+setup-choose-key = Welchen Age-Schlüssel soll dieses Projekt verwenden?
+setup-stored-key = Neuen Age-Schlüssel in 1Password gespeichert
+setup-no-template = Keine (ich lege Regeln selbst mit 'opsops target-keys' an)
+setup-choose-template = Welche Dateien sollen verschlüsselt werden?
+setup-rule-exists = Es gibt bereits eine Regel für '{ $pattern }'. Trotzdem eine weitere hinzufügen?
+setup-keeping-rules = Vorhandene Regeln bleiben erhalten
+setup-wrote-sops-config = .sops.yaml geschrieben
+setup-no-repository = Kein Git-Repository, Git-Hooks werden übersprungen
+setup-install-hook = Einen pre-commit-Hook installieren, der das Einchecken privater Schlüssel verhindert?
+setup-hook-installed = pre-commit-Hook installiert
+setup-hook-exists = Der pre-commit-Hook ist bereits installiert
+
+## stats
+stats-secrets = Geheimnisse
+stats-encrypted-files = verschl. Dateien
+stats-creation-rules = Erstellungsregeln
+stats-recipients = Empfänger
+stats-oldest-rotation = älteste Rotation
+stats-newest-rotation = neueste Rotation
+stats-files-per-rule = Dateien pro Regel
+stats-every-file = (jede Datei)
+stats-rule = Regel #{ $number }
+stats-no-rule = keine Regel deckt sie ab, siehe `opsops drift`
+stats-expired = { $count } Wert(e) sind abgelaufen.
+stats-value-expiry = Ablauf von Werten
+stats-with-date = mit Datum
+stats-due = { $count } ({ $due } abgelaufen oder fällig innerhalb von { $days } Tagen)
+stats-encrypted = verschlüsselt
+stats-unreadable-expiry = (__expires-Schlüssel aus encrypted_regex heraushalten)
+stats-least-recently-rotated = Am längsten nicht rotiert
+stats-largest = Am größten
+
+## sync
+sync-not-a-value = { $target } ist kein einzelner Wert
+sync-no-entries = Keine sync-Einträge in der .opsops.yaml, nichts zu tun.
+sync-in-sync = synchron
+sync-differs = weicht ab
+sync-missing-in-file = fehlt in der Datei
+sync-failed = fehlgeschlagen: { $error }
+sync-drifted = { $drifted } von { $count } Einträgen weichen ab, { $pull } kopiert 1Password in die Dateien, { $push } in die andere Richtung
+sync-everything-in-sync = Alles ist synchron.
+sync-updated-values = { $count } Wert(e) in { $path } aktualisiert
+sync-updated-field = { $reference } aktualisiert
+sync-update-failed = { $reference } konnte nicht aktualisiert werden: { $error }
+sync-nothing-to-push-to = { $target } existiert nicht, nichts nach { $reference } zu übertragen
+sync-nothing-to-push = Nichts zu übertragen.
+
+## talos
+talos-generate-first = Erzeuge sie zuerst mit 'talosctl gen secrets'.
+talos-rule-failed = Die Regel konnte nicht hinzugefügt werden:
+talos-added-rule = Talos-Regel für { $path } zur .sops.yaml hinzugefügt
+talos-no-talosctl = 'talosctl' ist nicht installiert oder nicht im PATH.
+talos-applied = { $path } angewendet
+talos-run-failed = talosctl konnte nicht ausgeführt werden:
+talos-plaintext = Klartext
+talos-none-found = Keine Talos-Maschinenkonfigurationen oder Secrets-Bundles gefunden.
+talos-plaintext-configs = { $count } Talos-Konfiguration(en) enthalten Geheimnisse im Klartext.
+talos-encrypt-them = Verschlüssele sie mit 'opsops encrypt'.
+talos-all-encrypted = Alle { $count } Talos-Konfiguration(en) sind verschlüsselt
+
+## verify
+verify-no-schemas = Keine Schemas an Erstellungsregeln in der .opsops.yaml.
+verify-no-files = Keine Datei wird von einer Regel mit Schema abgedeckt.
+verify-not-encrypted = nicht verschlüsselt
+verify-decrypt-failed = Entschlüsselung fehlgeschlagen: { $error }
+verify-failed = { $failed } von { $count } Datei(en) haben die Prüfung nicht bestanden.
+verify-all-match = Alle { $count } Datei(en) sind verschlüsselt und passen zu ihrem Schema
+verify-all-encrypted = Alle { $count } Datei(en) sind verschlüsselt
+verify-read-failed = Lesen fehlgeschlagen: { $error }
+verify-not-sops = nicht mit sops verschlüsselt
+verify-missing = fehlt
+verify-extra = zusätzlich
+verify-recipients-failed = { $failed } von { $count } Datei(en) sind nicht genau für die { $keys } angegebenen Schlüssel verschlüsselt.
+verify-recipients-ok = Alle { $count } Datei(en) sind genau für die { $keys } angegebenen Schlüssel verschlüsselt
+
+## read-only
+read-only-record = Im Nur-Lese-Modus wird keine Sitzung aufgezeichnet
+read-only-create-a-1password-item = Im Nur-Lese-Modus verweigert: ein 1Password-Element anlegen
+read-only-edit-sops-yaml = Im Nur-Lese-Modus verweigert: .sops.yaml bearbeiten
+read-only-remove-recipients = Im Nur-Lese-Modus verweigert: Empfänger entfernen
+read-only-upgrade-the-key = Im Nur-Lese-Modus verweigert: den Schlüssel aktualisieren
+read-only-update-the-member-registry = Im Nur-Lese-Modus verweigert: das Mitgliederverzeichnis aktualisieren
+read-only-decrypt-files = Im Nur-Lese-Modus verweigert: Dateien entschlüsseln
+read-only-write-decrypted-kubeconfigs = Im Nur-Lese-Modus verweigert: entschlüsselte kubeconfigs schreiben
+read-only-delete-kubeconfigs = Im Nur-Lese-Modus verweigert: kubeconfigs löschen
+read-only-edit-files = Im Nur-Lese-Modus verweigert: Dateien bearbeiten
+read-only-encrypt-files = Im Nur-Lese-Modus verweigert: Dateien verschlüsseln
+read-only-start-or-stop-the-agent = Im Nur-Lese-Modus verweigert: den Agenten starten oder stoppen
+read-only-run-benchmarks = Im Nur-Lese-Modus verweigert: Benchmarks ausführen
+read-only-write-reports = Im Nur-Lese-Modus verweigert: Berichte schreiben
+read-only-initialize-a-project = Im Nur-Lese-Modus verweigert: ein Projekt initialisieren
+read-only-create-a-project = Im Nur-Lese-Modus verweigert: ein Projekt anlegen
+read-only-set-up-a-project = Im Nur-Lese-Modus verweigert: ein Projekt einrichten
+read-only-tear-down-a-project = Im Nur-Lese-Modus verweigert: ein Projekt abbauen
+read-only-bootstrap-argo-cd = Im Nur-Lese-Modus verweigert: Argo CD einrichten
+read-only-apply-secrets = Im Nur-Lese-Modus verweigert: Secrets anwenden
+read-only-apply-machine-configs = Im Nur-Lese-Modus verweigert: Maschinenkonfigurationen anwenden
+read-only-update-keys = Im Nur-Lese-Modus verweigert: Schlüssel aktualisieren
+read-only-publish-files = Im Nur-Lese-Modus verweigert: Dateien veröffentlichen
+read-only-import-files = Im Nur-Lese-Modus verweigert: Dateien importieren
+read-only-write-files = Im Nur-Lese-Modus verweigert: Dateien schreiben
+read-only-write-1password-items = Im Nur-Lese-Modus verweigert: 1Password-Elemente schreiben
+read-only-restore-backups = Im Nur-Lese-Modus verweigert: Sicherungen wiederherstellen
+read-only-move-files = Im Nur-Lese-Modus verweigert: Dateien verschieben
+read-only-sync-secrets = Im Nur-Lese-Modus verweigert: Geheimnisse synchronisieren
+read-only-copy-secrets = Im Nur-Lese-Modus verweigert: Geheimnisse kopieren
+read-only-write-secrets = Im Nur-Lese-Modus verweigert: Geheimnisse schreiben
+read-only-delete-files = Im Nur-Lese-Modus verweigert: Dateien löschen
+read-only-change-creation-rules = Im Nur-Lese-Modus verweigert: Erstellungsregeln ändern
+read-only-write-documentation = Im Nur-Lese-Modus verweigert: Dokumentation schreiben
+read-only-install-completions = Im Nur-Lese-Modus verweigert: Vervollständigungen installieren
+read-only-write-sops-yaml = Im Nur-Lese-Modus verweigert: .sops.yaml schreiben
+read-only-write-opsops-yaml = Im Nur-Lese-Modus verweigert: .opsops.yaml schreiben
+
+## Shared by the helpers
+open-failed = { $path } konnte nicht geöffnet werden: { $error }
+config-not-found = Konfigurationsdatei nicht gefunden:
+op-variable-empty = Die Umgebungsvariable { $variable } ist gesetzt, aber leer
+op-variable-invalid-uid = Die Umgebungsvariable { $variable } ist keine gültige UID: { $value }
+op-no-invoking-user = Der aufrufende Benutzer aus { $variable } wurde nicht gefunden
+op-no-impersonation = { $variable } gefunden, aber opsops wurde ohne das Feature impersonation gebaut, op läuft als aktueller Benutzer
+op-create-failed = Das Element konnte nicht in 1Password angelegt werden
+op-json-failed = JSON konnte nicht gelesen werden:
+op-rate-limited = 1Password hat die Anfrage gedrosselt, neuer Versuch in { $seconds }s
+pick-no-files = Kein Pfad angegeben und keine Datei in diesem Verzeichnis passt auf eine Regel in der .sops.yaml.
+pick-pass-file = Übergib die Datei für { $action } als Argument.
+pick-no-terminal = Kein Pfad angegeben und kein Terminal, um eine Datei auszuwählen.
+env-key-hidden = { $variables } in der Umgebung gefunden, aber opsops verwendet den Schlüssel des Projekts und verbirgt es vor sops. Übergib --prefer-env-key, um es stattdessen zu verwenden, oder --prefer-op-key, um diesen Hinweis abzuschalten
+project-root-fallback = Projektwurzel nicht gefunden, verwende das aktuelle Verzeichnis. Bitte nutze die Option --sops-file
+notification-failed = Die { $target }-Benachrichtigung konnte nicht gesendet werden: { $error }
+migrated-config = 🔧 Projektkonfiguration migriert: { $description }
+invalid-private-key = Ungültiges Format des privaten Schlüssels:
+key-fd-failed = Der Age-Schlüssel konnte nicht über einen Dateideskriptor übergeben werden ({ $error }), verwende SOPS_AGE_KEY
+preview-would-encrypt = { $encrypted } von { $count } Werten würden verschlüsselt
+summary-encrypted = { $count } Wert(e) verschlüsselt:
+summary-still-encrypted = Bereits verschlüsselt ({ $count }):
+summary-plaintext = Von der Regel im Klartext belassen ({ $count }):
+summary-data-key-reused = Datenschlüssel: aus dem früheren Chiffretext übernommen
+summary-data-key-generated = Datenschlüssel: neu erzeugt
+bulk-ok = ok
+bulk-skipped = übersprungen
+bulk-resumed = ok (früherer Lauf)
+bulk-totals = { $succeeded } erfolgreich, { $failed } fehlgeschlagen, { $skipped } übersprungen
+bulk-totals-resumed = , { $resumed } von einem früheren Lauf abgeschlossen
+prompt-enter-number = Gib eine Zahl von 1 bis { $count } ein
+prompt-or-filter =  oder Text, um die Liste einzugrenzen
+prompt-nothing-for = , nichts für { $default }
+prompt-nothing-to-cancel = , nichts zum Abbrechen
+prompt-answer-required = Eine Antwort ist erforderlich.
+prompt-chose = { $item } gewählt
+prompt-out-of-range = { $number } ist keine Zahl von 1 bis { $count }.
+prompt-not-a-number = '{ $answer }' ist keine Zahl.
+prompt-no-match = Nichts passt auf '{ $answer }', zeige alles.
+prompt-yes-or-no = Gib yes oder no ein, nichts für { $default }:
+prompt-answer-yes-or-no = Bitte antworte mit yes oder no.
+passphrase = Passphrase
+passphrase-repeat = Passphrase wiederholen
+passphrase-mismatch = Die Passphrasen stimmen nicht überein
+perf-heading = Benötigte Zeit (--profile-perf):
+perf-total = gesamt
+perf-opsops = opsops selbst
+perf-slowest = Am langsamsten:
+
+## Advice beneath errors, see src/util/advice.rs
+
+advice-no-creation-rule = Lege eine Creation Rule dafür an: opsops target-keys { $file }
+advice-key-not-recipient = Dein Schlüssel ist kein Empfänger dieser Datei. Passende Schlüssel zeigt: opsops doctor
+advice-mac-mismatch = Die Datei wurde außerhalb von sops geändert. Zurückrollen mit: opsops restore { $file }
+advice-op-signed-out = Bei 1Password anmelden: op signin
+advice-op-rate-limited = Verteile 1Password-Aufrufe mit op_rate_limit in der Benutzerkonfiguration, siehe opsops paths
+advice-sops-missing = Fehlende Werkzeuge installieren: opsops setup
+advice-init = Projekt einrichten: opsops init
@@ -0,0 +1,766 @@
+# Messages of opsops, see src/util/i18n.rs. Every catalog has the same
+# messages in the same order.
+
+## Shared by the commands
+
+invalid-utf8-path = Invalid UTF-8 in path:
+file-not-found = File not found:
+sops-not-installed = 'sops' is not installed or not in PATH.
+install-it-first = Please install it first.
+age-key-failed = Failed to get Age key:
+sops-launch-failed = Failed to launch sops:
+file-unchanged = File has not changed.
+exit-code = Exit code: { $status }
+no-sops-config = Could not find .sops.yaml.
+no-project-root = Could not determine project root.
+outside-project = The file has to be inside the directory containing .sops.yaml.
+read-failed = Failed to read { $path }: { $error }
+write-failed = Failed to write { $path }: { $error }
+delete-failed = Failed to delete { $path }: { $error }
+remove-failed = Failed to remove { $path }: { $error }
+create-failed = Failed to create { $path }: { $error }
+decrypt-path-failed = Failed to decrypt { $path }: { $error }
+sops-config-read-failed = Failed to read .sops.yaml: { $error }
+sops-config-update-failed = Failed to update .sops.yaml: { $error }
+audit-log-failed = Failed to write the audit log: { $error }
+age-key-get-failed = Failed to get the age key: { $error }
+create-dir-in-failed = Failed to create a directory in { $path }: { $error }
+start-failed = Failed to start '{ $path }': { $error }
+removed = Removed { $path }
+restrict-failed = Failed to restrict { $path }: { $error }
+wrote = Wrote { $path }
+temp-dir-failed = Couldn't create a temporary directory: { $error }
+public-key-failed = Couldn't derive the public key: { $error }
+exists-pass-force = { $path } already exists, pass --force to overwrite it
+not-encrypted = { $path } is not an encrypted file
+created-for-rule = Created { $path }, encrypted for the recipients of its creation rule
+sops-config-error = Error reading sops file:
+no-encrypted-files = No encrypted files found.
+sops-exited = sops exited with { $status }
+temp-file-failed = Failed to create a temporary file: { $error }
+tracking-failed = Couldn't update the decrypted copy tracking: { $error }
+schema-validate-failed = Failed to validate against the schema:
+secret-validated = Validated Secret { $secret } with the age key from 1Password
+secret-applied = Applied Secret { $secret } with the age key from 1Password
+skipped = Skipped { $path }: { $error }
+config-read-failed = Failed to read config:
+config-write-failed = Failed to write config:
+op-vaults-failed = Failed to retrieve vaults.
+op-no-vaults = No vaults found.
+op-items-failed = Failed to retrieve items.
+op-no-items = No items found.
+op-choose-vault = Choose a Vault
+op-choose-item = Choose an Item
+op-choose-field = Choose a Field
+no-files-to-search = No encrypted files to search.
+none = none
+already-exists = { $path } already exists
+expired-no-access = Expired recipients no longer have access
+opsops-config-read-failed = Error reading .opsops.yaml:
+opsops-config-write-failed = Failed to write .opsops.yaml:
+registry-empty = The member registry is empty.
+registry-sync-first = Run 'opsops registry sync' first.
+registry-read-failed = Error reading the registry:
+unknown = unknown
+user-config = user config
+file-outside-project = File is outside of the directory containing .sops.yaml.
+rule-number = Rule #{ $number }
+not-found = not found
+output-write-failed = Failed to write output: { $error }
+invalid-regex = Invalid regex:
+error-prefix = Error:
+record-failed = Cannot record to { $path }: { $error }
+chdir-failed = Cannot change to { $path }: { $error }
+agent-unsupported = The agent needs unix sockets, which this platform doesn't have
+docs-generated-man = Generated man page at: { $path }
+docs-generated-completions = Generated Fish completions at: { $path }
+docs-to-install = To install:
+
+## encrypt
+
+refusing-to-encrypt = Refusing to encrypt
+allow-protected-hint = Pass --allow-protected to encrypt it anyway.
+encrypting-protected = Encrypting { $path } although { $reason } (--allow-protected)
+add-escrow-hint = Add it to the rule in .sops.yaml, e.g. with 'opsops target-keys'.
+stale-copy-aborted = Aborted. Merge the upstream changes into the plaintext copy first.
+encrypting-to = 🔐 Encrypting to
+encrypt-note-failed = Error while encrypting the note:
+encrypted-note = Successfully encrypted the note
+encrypted-file = Successfully encrypted file to with SOPS
+encrypt-failed = Error while encrypting the file.
+encrypt-diff-notes = --diff doesn't summarize notes, only YAML, JSON and dotenv files.
+encrypt-summary-failed = Can't summarize the encryption: { $error }
+encrypt-looks-like-mistake = { $path }: it looks like a mistake
+encrypt-guardrails-hint = Set guardrails.severity to warning in .opsops.yaml to encrypt it anyway
+encrypt-schema-mismatch = { $path }: it doesn't match { $schema }
+encrypt-stale-copy = { $source } changed after { $path } was decrypted from it (e.g. a teammate pushed changes).
+encrypt-show-diff = Show a structural diff
+encrypt-anyway = Encrypt anyway, discarding the upstream changes
+encrypt-abort = Abort
+encrypt-what-now = What do you want to do?
+encrypt-diff-unsupported = Only YAML and JSON files can be compared structurally.
+encrypt-no-differences = No differences in keys or values.
+encrypt-would-change = Encrypting { $path } would change these keys upstream:
+
+## decrypt
+
+decrypting = 🔓 Decrypting
+decrypt-note-failed = Error while decrypting the note:
+decrypted-note = Successfully decrypted the note
+decrypted-file = Successfully decrypted file with SOPS
+decrypt-failed = Error while decrypting the file.
+decrypt-not-ignored = { $path } is not ignored by git, its plaintext could be committed. Add it to .gitignore.
+decrypt-tracking-failed = Couldn't track the decrypted copy: { $error }
+decrypt-includes-in-note = --resolve-includes is not supported for Markdown notes
+decrypt-includes-plaintext = { $path } is not encrypted, print it with 'opsops read --resolve-includes'
+decrypt-output-newer = { $output } is newer than { $path }, it may hold edits or another terminal's decryption. Pass --unique-output to decrypt to a path of its own.
+decrypt-skipping-in-place = Skipping { $count } file(s) without a .enc extension or a decrypt_outputs: entry, they would be decrypted in place
+decrypt-nothing = Nothing to decrypt
+
+## rm
+rm-not-encrypted = { $path } is not an encrypted file, delete it with rm
+rm-record-removal = { $error }. Run git rm --cached { $name } to record it.
+rm-deleted = Deleted { $name }
+rm-shredded-copy = Shredded the plaintext copy { $path }
+rm-shred-failed = Failed to shred the plaintext copy { $path }: { $error }
+rm-remove-rules = Remove the { $count } rule(s) written for { $name } from .sops.yaml?
+rm-kept-rules = Kept the rule(s) for { $name } in .sops.yaml, pass --yes to remove them
+rm-removed-rules = Removed { $count } rule(s) from .sops.yaml
+rm-undo = Undo with { $command }
+rm-access-kept = Deleting doesn't revoke access: { $count } recipient(s) could decrypt { $name } and may keep copies. Rotate the secrets it held if that matters: { $recipients }
+rm-access-kept-in-history = Deleting doesn't revoke access: { $count } recipient(s) could decrypt { $name }, git history still has it, and may keep copies. Rotate the secrets it held if that matters: { $recipients }
+
+## kubeconfig
+kubeconfig-not-a-kubeconfig = { $path } doesn't look like a kubeconfig, it has no clusters
+kubeconfig-starting-shell = Starting { $shell } with KUBECONFIG={ $path }, exit it to wipe the kubeconfig
+kubeconfig-wiped = Wiped { $path }
+kubeconfig-wipe-failed = Failed to wipe { $path }: { $error }
+kubeconfig-wipe-failed-by-hand = Failed to wipe { $path }: { $error }. Delete it by hand.
+kubeconfig-not-written-by-use = { $path } wasn't written by 'opsops kubeconfig use'
+
+## teardown
+teardown-heading = 🧹 Tearing down opsops in
+teardown-found-encrypted = Found { $count } encrypted file(s) in this project.
+teardown-decrypt-all = Decrypt all of them in place? This writes plaintext secrets to disk
+teardown-type-decrypt = Type 'decrypt' to confirm
+teardown-not-confirmed = Confirmation not given, leaving files encrypted
+teardown-no-hooks = No opsops git hooks installed
+teardown-removed-hook = Removed git hook { $path }
+teardown-removed-git-config = Removed git config { $key }
+teardown-kept-backups = Removed { $path } except the backups in { $backups }, 'opsops restore <file>' rolls files back
+teardown-complete = Teardown complete.
+teardown-still-encrypted = Leaving git hooks and state in place, some files are still encrypted
+
+## agent
+agent-stopped = Agent stopped
+agent-not-running = No agent is running
+agent-listening = Agent listening on
+agent-clients-need = Clients need { $setting }
+agent-configure-failed = Failed to configure socket: { $error }
+agent-accept-failed = Failed to accept connection: { $error }
+agent-keys-wiped = Agent stopped, keys wiped from memory
+agent-already-running = An agent is already running on { $path }
+agent-remove-stale-failed = Failed to remove stale socket { $path }: { $error }
+agent-bind-failed = Failed to bind { $path }: { $error }
+
+## argocd
+argocd-add-patches = Add the patches to the kustomization that installs Argo CD:
+
+## bench
+bench-heading = ⏱  Benchmarking opsops
+bench-no-key = Skipping bulk encryption, no age key available
+bench-rule-matching = Rule matching
+bench-files-rounds = ({ $files } files, { $rounds } rounds)
+bench-synthetic-files-failed = Couldn't create synthetic files: { $error }
+bench-key-retrieval = Key retrieval
+bench-rounds = ({ $rounds } rounds)
+bench-skipping-key-retrieval = Skipping key retrieval: { $error }
+bench-bulk-encryption = Bulk encryption
+bench-files-workers = ({ $files } files, { $workers } workers)
+bench-no-sops = Skipping bulk encryption, sops is not installed
+bench-no-samples = no samples
+bench-agent-not-running = not running, start it with 'opsops agent'
+
+## ci
+ci-include-gitlab = Include it from .gitlab-ci.yml:
+ci-token-github = Add a 1Password service account token as the OP_SERVICE_ACCOUNT_TOKEN repository secret
+ci-token-gitlab = Add a 1Password service account token as the masked OP_SERVICE_ACCOUNT_TOKEN CI/CD variable
+ci-passphrase-github = Add the passphrase as the OPSOPS_PASSPHRASE repository secret
+ci-passphrase-gitlab = Add the passphrase as the masked OPSOPS_PASSPHRASE CI/CD variable
+
+## completions
+completions-install-unsupported = Installing { $shell } completions isn't supported. Add this to your profile instead: opsops completions { $shell } | Out-String | Invoke-Expression
+completions-up-to-date = The { $shell } completions at { $path } are up to date
+completions-installed = Installed { $shell } completions to { $path }
+completions-add-line = Add '{ $line }' to { $path }?
+completions-added = Added the completions to { $path }
+completions-load-them = To load them, add this line to { $path }:
+completions-no-shell = Could not detect your shell.
+completions-name-shell = Name it, e.g. 'opsops completions zsh'.
+completions-write-failed = Failed to write the completions: { $error }
+completions-no-home = Could not determine the home directory
+
+## cp
+cp-names-no-value = { $location } names no value, use { $syntax }
+cp-copied = Copied { $source } to { $destination }
+
+## diff
+diff-unchanged = { $path } is unchanged since { $rev }
+diff-plaintext-unchanged = The plaintext of { $path } is unchanged since { $rev }
+diff-working-tree = working tree
+
+## doctor
+doctor-found-sops = Found sops: { $path } { $version }
+doctor-no-sops = sops is not installed or not found in PATH. Please install sops.
+doctor-found-op = Found 1Password CLI (op): { $path } { $version }
+doctor-no-op = 1Password CLI (op) is not installed or not found in PATH. Please install op.
+doctor-host-key = { $host } matches the host_keys entry '{ $pattern }', its key comes from { $identity }
+doctor-passphrase-key = The age key is derived from a passphrase ({ $key })
+doctor-no-item = No 1Password reference found in .opsops.yaml. Run 'opsops init' to configure.
+doctor-item-found = 1Password item found in .opsops.yaml: { $item }
+doctor-private-key = Got private key: { $key }
+doctor-no-identity = The age key contains no identity
+doctor-your-public-key = Your public key is: { $key }
+doctor-your-public-keys = Your public keys are:
+doctor-rules-without-age = Rules without age keys:
+doctor-rule-item = - Rule #{ $number }: { $pattern }
+doctor-no-matching-key = No matching public key found in .sops.yaml config.
+doctor-matching-key = Found matching public key: { $key }
+doctor-matching-keys = { $count } of { $total } identities match recipients in .sops.yaml: { $keys }
+doctor-patterns-ok = All path_regex patterns are anchored or escaped
+doctor-also-matches = also matches { $path }
+doctor-exact-match = use for an exact match: { $pattern }
+doctor-plain-path = Rule #{ $number } path_regex '{ $pattern }' is a plain path: '.' matches any character and it matches anywhere in a path
+doctor-invalid-escrow = Invalid escrow_recipient: { $error }
+doctor-escrow-ok = All rules encrypt to the escrow recipient { $key }
+doctor-escrow-missing = Rules don't encrypt to the escrow recipient { $key }
+doctor-expiry-read-failed = Couldn't read recipient_expiry: { $error }
+doctor-expired-item = - { $recipient } expired after { $date }: { $rules } rule(s), { $files } file(s)
+doctor-expire-sweep = Run 'opsops key expire-sweep' to remove them and rotate the data keys
+doctor-expired-access = Expired recipients can still decrypt secrets
+doctor-destinations-ok = { $count } destination rule(s) are valid
+doctor-invalid-destinations = Invalid destination_rules
+doctor-recorded-key = Recorded the key of { $project } in { $path }
+doctor-key-unique = No other project in the key registry uses this key
+doctor-leaked-key = A leaked key exposes all of them, use one key per project ('opsops generate-age-key')
+doctor-key-shared = The age key of this project also protects { $count } other project(s)
+doctor-scan-failed = Couldn't scan repository for private keys: { $error }
+doctor-no-committed-keys = No private keys found in tracked files or history (last { $count } commits)
+doctor-finding-in-commit = - { $path } contains { $kind } (commit { $commit })
+doctor-finding = - { $path } contains { $kind }
+doctor-remediate = To remediate:
+doctor-remediate-rotate = 1. Rotate the key: generate a new one with 'opsops generate-age-key' and re-encrypt all files
+doctor-remediate-history = 2. Remove the key from history, e.g. with 'git filter-repo' or BFG, then force-push
+doctor-remediate-reclone = 3. Ask all collaborators to re-clone the repository
+doctor-committed-keys = HIGH SEVERITY: Private keys are committed to this repository!
+
+## drift
+drift-none = All { $count } file(s) match their rules.
+drift-unresolved = { $count } of { $total } file(s) drifted from their rules.
+
+## edit
+edit-create-hint = (--create to create it)
+edit-creating = 📝 Creating file:
+edit-opening = 📝 Opening file for editing:
+edit-create-failed = Failed to create the file:
+edit-saved = File edited and saved successfully.
+edit-not-created = Nothing saved, not created:
+edit-failed = Error while editing the file.
+edit-schema-decrypt-failed = Couldn't decrypt { $path } to validate it: { $error }
+edit-schema-mismatch = { $path } was saved but doesn't match { $schema }:
+edit-again = Run 'opsops edit' again to fix it.
+edit-note-failed = Error while editing the note:
+edit-editor-exited = The editor exited with { $status }
+edit-read-note-failed = Failed to read the edited note: { $error }
+
+## escrow
+escrow-not-set = No escrow_recipient set in .opsops.yaml.
+escrow-add-key = Add the public key of your escrow identity first.
+escrow-rule = rule #{ $number } { $pattern }
+escrow-unrecoverable = { $count } rules or files are not encrypted to the escrow recipient.
+escrow-add-to-rules = Add it to the rules and re-encrypt the files.
+escrow-recoverable = Everything is recoverable by the escrow recipient
+
+## export
+export-parse-failed = Failed to parse the decrypted file: { $error }
+export-table-failed = Can't export as a table:
+export-exported = Exported { $path } to { $output }
+export-not-a-map = Only documents with keys at the top level can be exported
+export-no-values = { $path } holds no values to export
+export-op-failed = Can't export to 1Password:
+export-update-failed = Failed to update { $item } in { $vault }: { $error }
+export-updated = Updated { $count } field(s) of { $item } in { $vault } from { $path }
+export-removed-fields-stay = Fields removed from the file since the last export stay in the item.
+export-exported-fields = Exported { $count } field(s) of { $path } to { $item } in { $vault }
+
+## flux
+flux-none = No Flux Kustomizations found.
+flux-kustomization = Kustomization { $name } ({ $path })
+flux-decrypts = { $kustomization } decrypts with sops using Secret { $secret }
+flux-no-secret-ref = { $kustomization } uses sops but has no decryption.secretRef
+flux-needs-secret = Flux needs the age key in a Secret, see 'opsops flux create-secret'
+flux-other-provider = { $kustomization } decrypts with provider '{ $provider }' instead of sops
+flux-no-decryption = { $kustomization } has no decryption configured
+flux-add-provider = Add spec.decryption.provider: sops if it deploys encrypted files
+flux-no-kubectl = kubectl not found, skipping the comparison with the cluster Secrets
+flux-secret-read-failed = Couldn't read Secret { $secret }: { $error }
+flux-create-secret = Create it with 'opsops flux create-secret --name { $name } --namespace { $namespace }'
+flux-secret-matches = Secret { $secret } holds the key from 1Password
+flux-secret-differs = Secret { $secret } holds a different age key than 1Password
+flux-update-secret = Update it with 'opsops flux create-secret --name { $name } --namespace { $namespace }'
+
+## generate-age-key
+key-public-label = 🔑 Public Key:
+key-private-label = 🔐 Private Key:
+key-recovery-label = 📝 Recovery:
+key-recovery-hint = The recovery phrase restores the key with 'opsops key recover --mnemonic'. Write it down and keep it offline.
+key-save-in-op = Would you like to save this key in 1Password?
+key-choose-vault = Choose a 1Password vault to store the item
+key-choose-item-name = Choose a name for the 1Password item
+key-save-securely = Remember to save this key in a secure location!
+key-add-to-sops-config = Don't forget to add the public key to .sops.yaml!
+
+## grep
+grep-invalid-pattern = Invalid pattern: { $error }
+
+## help
+help-guides = Guides:
+help-read-guide = Read one with 'opsops help <guide>', man pages with 'opsops man [command]'.
+help-unknown = No command or guide named '{ $name }'. Guides: { $guides }
+
+## import
+import-refusing-to-overwrite = Refusing to overwrite
+import-invalid-table = Invalid table
+import-encrypt-failed = Failed to encrypt the import:
+import-imported-entries = Imported { $count } entries from { $path } into { $output }
+import-plaintext-left = { $path } still holds the plaintext, delete it once you checked the import.
+import-pass-path = Pass --path for the mapping the fields go under, or pick fields with --field
+import-duplicate-label = The item has several fields labeled '{ $label }', pick them with --field
+import-no-field = The item has no field '{ $label }' with a value
+import-ambiguous-label = The item has several fields labeled '{ $label }', read one with its op:// reference
+import-single-field = { $reference } names a single field, --field picks fields of a whole item
+import-invalid-reference = '{ $reference }' must have the form op://<vault>/<item>[/<section>]/<field> or op://<vault>/<item>
+import-imported-op = Imported { $paths } from 1Password into { $path }
+
+## info
+info-version = version
+info-build = build
+info-target = target
+info-tools = Tools
+info-configuration = Configuration
+info-project-root = project root
+info-key = key
+info-key-source = (from { $source })
+
+## init
+init-failed = Failed to initialize:
+init-no-reference = ⚠️  No 1Password reference configured in .opsops.yaml.
+init-nothing-to-do = .sops.yaml file exists. No action needed.
+init-missing = .sops.yaml is missing.
+init-create-basic = Would you like to create a basic .sops.yaml file?
+init-create-failed = Failed to create config file:
+init-created-basic = Created basic .sops.yaml file.
+init-create-manually = Please create a .sops.yaml file manually following the guide at: https://github.com/getsops/sops#using-sops-yaml-conf-to-select-kms-pgp-and-age-for-new-files
+init-already-exists = .sops.yaml already exists
+init-created = Created .sops.yaml with { $count } rule(s) encrypting to { $key }
+init-wrote-reference = Wrote 1Password reference { $reference }
+init-pass-reference = No 1Password reference configured, pass the key as op://... or use --op-item
+init-wrote-passphrase = Wrote the passphrase settings to .opsops.yaml
+init-passphrase-only = Nothing can be decrypted without the passphrase. Move the key to 1Password with 'opsops key upgrade' once the project grows.
+init-assign-key = Would you like to assign an age key from 1Password?
+init-writing-reference = 🔐 Writing 1Password reference to config: { $reference }
+init-read-or-create-failed = Failed to read or create config: { $error }
+init-updated-reference = Successfully updated .opsops.yaml with 1Password reference.
+init-not-a-key = '{ $field }' doesn't hold an age secret key:
+init-choose-another = Choose another field?
+
+## inspect
+inspect-expired = { $date } (expired)
+inspect-expires-soon = { $date } (within { $days } days)
+inspect-not-a-value = { $location } is not a single value
+inspect-no-pem = { $location } holds no PEM certificate or key
+inspect-certificate = Certificate
+inspect-private-key = Private key
+inspect-public-key = Public key
+inspect-block-of = ({ $number } of { $count })
+inspect-subject = subject
+inspect-issuer = issuer
+inspect-not-before = not before
+inspect-expires = expires
+inspect-key = key
+inspect-no-certificates = No certificates in { $count } encrypted file(s).
+
+## list-config
+list-no-config = Error: No SOPS configuration file found.
+list-parse-failed = Failed to parse YAML:
+list-includes-failed = Failed to resolve includes:
+list-item = Assigned 1Password item:
+list-rules = Rules:
+list-rule = 🔹 Rule #
+list-from = 📎 From:
+list-file-pattern = 📂 File pattern:
+list-age-keys = 🔑 Age Keys:
+list-age-key = 🔑 Age Key:
+list-invalid-pattern = Invalid path_regex
+list-no-matches = Matches no files in this project
+list-matching-files = 📄 Matching files:
+list-and-more = ... and { $count } more
+list-overlaps = Files matched by multiple rules (sops uses the first match):
+list-destinations = Destinations:
+list-destination = 🔸 Destination #
+list-publishes-to = 📤 Publishes to:
+list-reencrypted-for = 🔑 Re-encrypted for:
+list-used-by-sops = This configuration will be used when encrypting files with SOPS.
+
+## man
+man-no-subcommand = '{ $command }' has no subcommand '{ $name }'
+man-render-failed = Failed to render the man page: { $error }
+man-write-failed = Failed to write the man page: { $error }
+
+## meta
+meta-not-encrypted = { $path } has no sops metadata, it isn't encrypted
+meta-sops-version = sops version
+meta-last-modified = last modified
+meta-present = present
+meta-missing = missing
+meta-encrypted-values = encrypted values
+meta-recipients = Recipients
+meta-other = other
+meta-key-group = key group { $number }
+meta-shamir-threshold = shamir threshold
+
+## mv
+mv-not-encrypted = { $path } is not an encrypted file, move it with mv
+mv-outside-project = Both paths have to be inside the directory containing .sops.yaml.
+mv-failed = Failed to move { $path } to { $destination }: { $error }
+mv-config-failed = Failed to update .sops.yaml, nothing was moved: { $error }
+mv-record-move = { $error }. Run git add -A to record the move.
+mv-moved = Moved { $path } to { $destination }
+mv-updated-rules = Updated { $count } rule(s) in .sops.yaml
+mv-no-rule = No creation rule covers { $path } anymore. Add one with { $command } before editing it.
+mv-other-rule = { $path } is now covered by rule #{ $number } ({ $pattern }), edits will be encrypted with its keys
+mv-every-file = every file
+
+## key
+key-recovery-phrase = Recovery phrase
+key-recovery-read-failed = Failed to read the recovery phrase:
+key-recovery-invalid = Invalid recovery phrase:
+key-recovered-recipient = The recovered key is a recipient in .sops.yaml
+key-recovered-not-recipient = The recovered key is not a recipient of any rule in .sops.yaml
+key-skipped-key-groups = Skipped the rule for '{ $pattern }', it uses key groups. Add the key to a group by hand.
+key-added-members = Added { $members } to .sops.yaml
+key-run-drift-fix = Run 'opsops drift --fix' to re-encrypt existing files for them
+key-none-expired = No recipient has expired.
+key-expired-after = { $recipient } expired after { $date }
+key-expired-rule = rule #{ $number }: { $pattern }
+key-removed-expired = Removed { $count } expired recipient(s) from .sops.yaml
+key-access-again = { $recipient } has access again
+key-sweep-incomplete = Some files still grant expired recipients access. Run the sweep again to retry them.
+key-no-passphrase = This project doesn't use a passphrase key, nothing to upgrade.
+key-pass-reference = Pass the 1Password reference of the new key with --op-item op://...
+key-new-key-failed = Failed to get the new key:
+key-derive-failed = Failed to derive the passphrase key:
+key-replaced-passphrase = Replaced the passphrase key with { $key } in .sops.yaml
+key-still-passphrase = still encrypted to the passphrase key
+key-not-encrypted-to = not encrypted to { $key }
+key-upgrade-incomplete = Some files are still encrypted to the passphrase key. Run 'opsops key upgrade' again to retry them.
+key-upgraded = The project now uses the key in 1Password: { $reference }
+key-keep-passphrase = Older commits are still encrypted to the passphrase key, keep the passphrase while they matter
+
+## new
+new-pass-key = Pass the key to encrypt to with --from-key <age public key|op://...>, or use --passphrase.
+new-created-repository = Created a git repository in { $path }
+new-encrypt-failed = Failed to encrypt { $path } (sops exited with { $status }). Remove it before committing.
+new-installed-hook = Installed the pre-commit hook
+new-hook-failed = Couldn't install the pre-commit hook: { $error }
+new-not-repository-root = { $path } is not the repository root, move .github/workflows/secrets.yml there for CI to pick it up
+new-created = Project created!
+new-next-steps = Next steps:
+new-has-config = { $path } already has a .sops.yaml
+new-refusing-to-overwrite = Refusing to overwrite existing files in { $path }: { $files }
+
+## paths
+paths-not-created = (not created yet)
+paths-user = User
+paths-config-dir = config dir
+paths-key-registry = key registry
+paths-cache-dir = cache dir
+paths-reference-cache = reference cache
+paths-state-dir = state dir
+paths-agent-socket = agent socket
+paths-project = Project
+paths-root = root
+paths-state = state
+paths-locks = locks
+
+## preview
+preview-already-encrypted = is already encrypted.
+preview-no-rule = No creation rule matches
+
+## publish
+publish-not-encrypted = is not encrypted.
+publish-invalid-destinations = Invalid destination_rules in .sops.yaml:
+publish-no-rule = No destination rule matches
+publish-publishing = Publishing { $path } to { $destination }
+publish-published = Successfully published file with SOPS
+publish-failed = Error while publishing the file.
+
+## read
+read-file-failed = Failed to read file:
+read-note-options = --format, --extract, --redact and --resolve-includes are not supported for Markdown notes:
+
+## registry
+registry-no-source = No registry source.
+registry-no-source-hint = Pass one (op://<vault>/<note> or <git url>[#<path>]) or set member_registry in .opsops.yaml.
+registry-syncing = Syncing the member registry from { $source }
+registry-sync-failed = Failed to sync the registry:
+registry-new-key = (new key)
+registry-synced = Synced { $count } member(s) to { $path }
+registry-write-failed = Failed to write the registry:
+
+## replay
+replay-start-failed = failed to start: { $error }
+replay-exit = exit { $code }
+replay-killed = killed by a signal
+replay-took = { $outcome } in { $duration }
+replay-recorded = Session recorded
+replay-command = command
+replay-platform = platform
+replay-variables = variables
+replay-external-commands = External commands
+replay-errors = Errors
+replay-exited = Exited with { $code } after { $duration }
+replay-exited-early = Exited early, the exit code wasn't recorded
+
+## restore
+restore-no-backups = No backups found for
+restore-no-backup-at = No backup of { $path } at { $at }.
+restore-list-hint = List them with --list.
+restore-backed-up = Backed up the current version to { $path }
+restore-failed = Failed to restore { $path }: { $error }
+restore-restored = Restored { $path } from the backup taken at { $timestamp }
+
+## scan
+scan-no-repository = Not inside a git repository.
+scan-failed = Failed to scan the repository:
+scan-clean = No private keys found in tracked files or history (last { $history } commits)
+scan-committed = Private keys are committed. Rotate them and remove them from history, see 'opsops doctor'.
+
+## serve
+serve-stdin-failed = Failed to read from stdin: { $error }
+
+## set
+set-value = Value
+set-read-failed = Failed to read the value:
+set-generated = Generated { $target }
+set-done = Set { $target }
+
+## set-key
+set-key-not-found = File not found.
+set-key-unsupported = Only YAML and JSON files are supported.
+set-key-no-extension = File has no extension. Only YAML and JSON files are supported.
+set-key-no-public-key = Could not extract public key from the age key.
+set-key-regex-failed = Error getting regex:
+set-key-updated = Successfully updated .sops.yaml
+set-key-next-step = You can now encrypt your file with:
+set-key-conditional-pattern = Using encrypted_regex '{ $pattern }' from rule_conditions in .opsops.yaml
+set-key-pattern-all = All values (encrypt entire file)
+set-key-pattern-kubernetes = Kubernetes (data, stringData, password, ingress, token fields)
+set-key-pattern-talos = Talos configuration secrets (secrets sections, certs, keys)
+set-key-pattern-common = Common sensitive data (passwords, tokens, keys, credentials)
+set-key-pattern-custom = Custom pattern (provide your own regex)
+set-key-choose-pattern = What do you want to encrypt in this file?
+set-key-enter-pattern =
+    Enter your regex pattern to match keys you want to encrypt
+    Example: ^(password|api_key|secret)
+set-key-use-pattern = Use this pattern?
+
+## setup
+setup-title = 🧭 Setting up opsops
+setup-install-missing = Install the missing tools and run 'opsops setup' again.
+setup-signed-in = Signed in to 1Password
+setup-signing-in = Not signed in to 1Password, running 'op signin'...
+setup-sign-in-failed = Signing in to 1Password failed. Run 'op signin' and try again.
+setup-age-key-failed = Couldn't read the age key:
+setup-public-key = Using public key:
+setup-wrote-user-config = Wrote user config to { $path }
+setup-user-config-failed = Couldn't write user config: { $error }
+setup-complete = Setup complete!
+setup-next-step = Encrypt your first file with:
+setup-found-tool = Found { $tool }: { $path }
+setup-missing-tool = { $tool } is not installed or not in PATH.
+setup-install-hint = Install it with:
+setup-generate-key = Generate a new age key and store it in 1Password
+setup-existing-key = Use an existing age key from 1Password
+setup-choose-key = Which age key should this project use?
+setup-stored-key = Stored new age key in 1Password
+setup-no-template = None (I'll add rules myself with 'opsops target-keys')
+setup-choose-template = Which files should be encrypted?
+setup-rule-exists = A rule for '{ $pattern }' already exists. Add another one anyway?
+setup-keeping-rules = Keeping existing rules
+setup-wrote-sops-config = Wrote .sops.yaml
+setup-no-repository = Not a git repository, skipping git hooks
+setup-install-hook = Install a pre-commit hook that blocks committing private keys?
+setup-hook-installed = Installed pre-commit hook
+setup-hook-exists = Pre-commit hook is already installed
+
+## stats
+stats-secrets = Secrets
+stats-encrypted-files = encrypted files
+stats-creation-rules = creation rules
+stats-recipients = recipients
+stats-oldest-rotation = oldest rotation
+stats-newest-rotation = newest rotation
+stats-files-per-rule = Files per rule
+stats-every-file = (every file)
+stats-rule = rule #{ $number }
+stats-no-rule = no rule covers them, see `opsops drift`
+stats-expired = { $count } value(s) have expired.
+stats-value-expiry = Value expiry
+stats-with-date = with a date
+stats-due = { $count } ({ $due } expired or due within { $days } days)
+stats-encrypted = encrypted
+stats-unreadable-expiry = (keep __expires keys out of encrypted_regex)
+stats-least-recently-rotated = Least recently rotated
+stats-largest = Largest
+
+## sync
+sync-not-a-value = { $target } is not a single value
+sync-no-entries = No sync entries in .opsops.yaml, nothing to do.
+sync-in-sync = in sync
+sync-differs = differs
+sync-missing-in-file = missing in file
+sync-failed = failed: { $error }
+sync-drifted = { $drifted } of { $count } entries drifted, { $pull } copies 1Password into the files, { $push } the other way
+sync-everything-in-sync = Everything is in sync.
+sync-updated-values = Updated { $count } value(s) in { $path }
+sync-updated-field = Updated { $reference }
+sync-update-failed = Failed to update { $reference }: { $error }
+sync-nothing-to-push-to = { $target } doesn't exist, nothing to push to { $reference }
+sync-nothing-to-push = Nothing to push.
+
+## talos
+talos-generate-first = Generate it with 'talosctl gen secrets' first.
+talos-rule-failed = Failed to add a rule:
+talos-added-rule = Added a Talos rule for { $path } to .sops.yaml
+talos-no-talosctl = 'talosctl' is not installed or not in PATH.
+talos-applied = Applied { $path }
+talos-run-failed = Failed to run talosctl:
+talos-plaintext = plaintext
+talos-none-found = No Talos machine configs or secrets bundles found.
+talos-plaintext-configs = { $count } Talos config(s) contain plaintext secrets.
+talos-encrypt-them = Encrypt them with 'opsops encrypt'.
+talos-all-encrypted = All { $count } Talos config(s) are encrypted
+
+## verify
+verify-no-schemas = No schemas attached to creation rules in .opsops.yaml.
+verify-no-files = No files are covered by a rule with a schema.
+verify-not-encrypted = not encrypted
+verify-decrypt-failed = failed to decrypt: { $error }
+verify-failed = { $failed } of { $count } file(s) failed verification.
+verify-all-match = All { $count } file(s) are encrypted and match their schema
+verify-all-encrypted = All { $count } file(s) are encrypted
+verify-read-failed = failed to read: { $error }
+verify-not-sops = not encrypted with sops
+verify-missing = missing
+verify-extra = extra
+verify-recipients-failed = { $failed } of { $count } file(s) are not encrypted to exactly the { $keys } given key(s).
+verify-recipients-ok = All { $count } file(s) are encrypted to exactly the { $keys } given key(s)
+
+## read-only
+read-only-record = Refusing to record a session in read-only mode
+read-only-create-a-1password-item = Refusing to create a 1Password item in read-only mode
+read-only-edit-sops-yaml = Refusing to edit .sops.yaml in read-only mode
+read-only-remove-recipients = Refusing to remove recipients in read-only mode
+read-only-upgrade-the-key = Refusing to upgrade the key in read-only mode
+read-only-update-the-member-registry = Refusing to update the member registry in read-only mode
+read-only-decrypt-files = Refusing to decrypt files in read-only mode
+read-only-write-decrypted-kubeconfigs = Refusing to write decrypted kubeconfigs in read-only mode
+read-only-delete-kubeconfigs = Refusing to delete kubeconfigs in read-only mode
+read-only-edit-files = Refusing to edit files in read-only mode
+read-only-encrypt-files = Refusing to encrypt files in read-only mode
+read-only-start-or-stop-the-agent = Refusing to start or stop the agent in read-only mode
+read-only-run-benchmarks = Refusing to run benchmarks in read-only mode
+read-only-write-reports = Refusing to write reports in read-only mode
+read-only-initialize-a-project = Refusing to initialize a project in read-only mode
+read-only-create-a-project = Refusing to create a project in read-only mode
+read-only-set-up-a-project = Refusing to set up a project in read-only mode
+read-only-tear-down-a-project = Refusing to tear down a project in read-only mode
+read-only-bootstrap-argo-cd = Refusing to bootstrap Argo CD in read-only mode
+read-only-apply-secrets = Refusing to apply Secrets in read-only mode
+read-only-apply-machine-configs = Refusing to apply machine configs in read-only mode
+read-only-update-keys = Refusing to update keys in read-only mode
+read-only-publish-files = Refusing to publish files in read-only mode
+read-only-import-files = Refusing to import files in read-only mode
+read-only-write-files = Refusing to write files in read-only mode
+read-only-write-1password-items = Refusing to write 1Password items in read-only mode
+read-only-restore-backups = Refusing to restore backups in read-only mode
+read-only-move-files = Refusing to move files in read-only mode
+read-only-sync-secrets = Refusing to sync secrets in read-only mode
+read-only-copy-secrets = Refusing to copy secrets in read-only mode
+read-only-write-secrets = Refusing to write secrets in read-only mode
+read-only-delete-files = Refusing to delete files in read-only mode
+read-only-change-creation-rules = Refusing to change creation rules in read-only mode
+read-only-write-documentation = Refusing to write documentation in read-only mode
+read-only-install-completions = Refusing to install completions in read-only mode
+read-only-write-sops-yaml = Refusing to write .sops.yaml in read-only mode
+read-only-write-opsops-yaml = Refusing to write .opsops.yaml in read-only mode
+
+## Shared by the helpers
+open-failed = Failed to open { $path }: { $error }
+config-not-found = Config file not found:
+op-variable-empty = Environment variable { $variable } is set but empty
+op-variable-invalid-uid = Environment variable { $variable } is not a valid uid: { $value }
+op-no-invoking-user = Couldn't find invoking user from { $variable }
+op-no-impersonation = Found { $variable }, but opsops was built without the impersonation feature, op runs as the current user
+op-create-failed = Failed to create item in 1Password
+op-json-failed = Failed to parse JSON:
+op-rate-limited = 1Password rate limited the request, retrying in { $seconds }s
+pick-no-files = No path given and no files in this directory match a rule in .sops.yaml.
+pick-pass-file = Pass the file to { $action } as an argument.
+pick-no-terminal = No path given and no terminal to pick a file from.
+env-key-hidden = Found { $variables } in the environment, but opsops uses the project's key and hides it from sops. Pass --prefer-env-key to use it instead, or --prefer-op-key to silence this
+project-root-fallback = Couldn't find project root, falling back to current directory. Please use the --sops-file flag
+notification-failed = Failed to send the { $target } notification: { $error }
+migrated-config = 🔧 Migrated project config: { $description }
+invalid-private-key = Invalid private key format:
+key-fd-failed = Couldn't pass the age key via a file descriptor ({ $error }), using SOPS_AGE_KEY
+preview-would-encrypt = { $encrypted } of { $count } values would be encrypted
+summary-encrypted = Encrypted { $count } value(s):
+summary-still-encrypted = Already encrypted ({ $count }):
+summary-plaintext = Left in plaintext by the rule ({ $count }):
+summary-data-key-reused = Data key: reused from the earlier ciphertext
+summary-data-key-generated = Data key: freshly generated
+bulk-ok = ok
+bulk-skipped = skipped
+bulk-resumed = ok (earlier run)
+bulk-totals = { $succeeded } succeeded, { $failed } failed, { $skipped } skipped
+bulk-totals-resumed = , { $resumed } finished by an earlier run
+prompt-enter-number = Enter a number from 1 to { $count }
+prompt-or-filter = , or text to narrow down the list
+prompt-nothing-for = , nothing for { $default }
+prompt-nothing-to-cancel = , nothing to cancel
+prompt-answer-required = An answer is required.
+prompt-chose = Chose { $item }
+prompt-out-of-range = { $number } is not a number from 1 to { $count }.
+prompt-not-a-number = '{ $answer }' is not a number.
+prompt-no-match = Nothing matches '{ $answer }', showing everything.
+prompt-yes-or-no = Type yes or no, nothing for { $default }:
+prompt-answer-yes-or-no = Please answer yes or no.
+passphrase = Passphrase
+passphrase-repeat = Repeat the passphrase
+passphrase-mismatch = The passphrases don't match
+perf-heading = Time spent (--profile-perf):
+perf-total = total
+perf-opsops = opsops itself
+perf-slowest = Slowest:
+
+## Advice beneath errors, see src/util/advice.rs
+
+advice-no-creation-rule = Add a creation rule for it: opsops target-keys { $file }
+advice-key-not-recipient = Your key is not a recipient of this file. See which keys match: opsops doctor
+advice-mac-mismatch = The file was modified outside of sops. Roll it back: opsops restore { $file }
+advice-op-signed-out = Sign in to 1Password: op signin
+advice-op-rate-limited = Space out 1Password calls with op_rate_limit in the user config, see opsops paths
+advice-sops-missing = Install the missing tools: opsops setup
+advice-init = Set up the project: opsops init
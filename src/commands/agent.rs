@@ -0,0 +1,154 @@
+use colored::Colorize;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::io::FromRawFd;
+use std::os::unix::net::UnixListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::{
+    GlobalContext,
+    util::{
+        agent::{self, KeyCache, SOCKET_ENV, handle_connection, socket_path},
+        i18n::{failed_to, tr, tr_args},
+        op_key::read_key_from_op,
+        print_status::{print_error, print_info, print_success},
+    },
+};
+
+/// First file descriptor passed by systemd socket activation
+const SD_LISTEN_FDS_START: i32 = 3;
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_shutdown(_: libc::c_int) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+/// Runs the key agent in the foreground, or stops a running one
+pub fn agent(_context: &GlobalContext, stop: bool) {
+    if stop {
+        if agent::stop() {
+            print_success(tr("agent-stopped"));
+        } else {
+            print_error(tr("agent-not-running"));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let (listener, owns_socket) = match activated_listener() {
+        Some(listener) => (listener, false),
+        None => match bind_listener() {
+            Ok(listener) => (listener, true),
+            Err(e) => {
+                print_error(e);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            request_shutdown as *const () as libc::sighandler_t,
+        );
+        libc::signal(
+            libc::SIGTERM,
+            request_shutdown as *const () as libc::sighandler_t,
+        );
+    }
+
+    let path = socket_path();
+    print_success(format!(
+        "{} {}",
+        tr("agent-listening").green(),
+        path.display()
+    ));
+    if std::env::var_os(SOCKET_ENV).is_some() {
+        print_info(tr_args(
+            "agent-clients-need",
+            &[(
+                "setting",
+                format!("{}={}", SOCKET_ENV, path.display()).into(),
+            )],
+        ));
+    }
+
+    if let Err(e) = listener.set_nonblocking(true) {
+        print_error(tr_args(
+            "agent-configure-failed",
+            &[("error", e.to_string().into())],
+        ));
+        std::process::exit(1);
+    }
+
+    let mut cache = KeyCache::new();
+    while !SHUTDOWN.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let _ = stream.set_nonblocking(false);
+                if handle_connection(stream, &mut cache, &read_key_from_op) {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => {
+                print_error(tr_args(
+                    "agent-accept-failed",
+                    &[("error", e.to_string().into())],
+                ));
+                break;
+            }
+        }
+    }
+
+    // Dropping the cache zeroes and unlocks all identities
+    drop(cache);
+    if owns_socket {
+        let _ = fs::remove_file(&path);
+    }
+    print_info(tr("agent-keys-wiped"));
+}
+
+/// Takes over a listening socket passed by systemd socket activation
+fn activated_listener() -> Option<UnixListener> {
+    let pid: u32 = std::env::var("LISTEN_PID").ok()?.parse().ok()?;
+    let fds: i32 = std::env::var("LISTEN_FDS").ok()?.parse().ok()?;
+    if pid != std::process::id() || fds < 1 {
+        return None;
+    }
+    Some(unsafe { UnixListener::from_raw_fd(SD_LISTEN_FDS_START) })
+}
+
+/// Creates the socket in a private directory, replacing a stale one
+fn bind_listener() -> Result<UnixListener, String> {
+    let path = socket_path();
+
+    if agent::is_running() {
+        return Err(tr_args(
+            "agent-already-running",
+            &[("path", path.display().to_string().into())],
+        ));
+    }
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| failed_to("agent-remove-stale-failed", &path, e))?;
+    }
+    if let Some(dir) = path.parent() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .map_err(|e| failed_to("create-failed", dir, e))?;
+    }
+
+    let listener =
+        UnixListener::bind(&path).map_err(|e| failed_to("agent-bind-failed", &path, e))?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+        .map_err(|e| failed_to("restrict-failed", &path, e))?;
+
+    Ok(listener)
+}
@@ -0,0 +1,93 @@
+use age::secrecy::SecretString;
+use colored::Colorize;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{
+    GlobalContext,
+    util::{
+        argocd::{AGE_KEYS_FILE, argocd_cm_patch, repo_server_patch},
+        i18n::{failed_to, tr, tr_args},
+        kubectl::{apply_manifest, secret_manifest},
+        op_key::get_age_key_from_1password,
+        print_status::{print_error, print_info, print_success},
+    },
+};
+
+pub struct BootstrapOptions {
+    pub namespace: String,
+    pub secret_name: String,
+    pub ksops_image: String,
+    /// Where the repo-server and argocd-cm patches are written
+    pub patch_dir: PathBuf,
+    pub dry_run: bool,
+}
+
+/// Applies the Secret holding the age key from 1Password and writes the patches
+/// Argo CD needs to decrypt with KSOPS. The key only ever goes to kubectl's stdin.
+pub fn bootstrap(options: BootstrapOptions, context: &GlobalContext) {
+    let key: SecretString = match get_age_key_from_1password(context) {
+        Ok(key) => key,
+        Err(e) => {
+            print_error(format!("{} {}", tr("age-key-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let manifest = secret_manifest(
+        &options.secret_name,
+        &options.namespace,
+        AGE_KEYS_FILE,
+        &key,
+    );
+    match apply_manifest(manifest, options.dry_run) {
+        Ok(status) if status.success() => print_success(tr_args(
+            if options.dry_run {
+                "secret-validated"
+            } else {
+                "secret-applied"
+            },
+            &[(
+                "secret",
+                format!("{}/{}", options.namespace, options.secret_name).into(),
+            )],
+        )),
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    }
+
+    let patches = [
+        (
+            "argocd-repo-server-patch.yaml",
+            repo_server_patch(
+                &options.namespace,
+                &options.secret_name,
+                &options.ksops_image,
+            ),
+        ),
+        ("argocd-cm-patch.yaml", argocd_cm_patch(&options.namespace)),
+    ];
+    if let Err(e) = fs::create_dir_all(&options.patch_dir) {
+        print_error(failed_to("create-failed", &options.patch_dir, e));
+        std::process::exit(1);
+    }
+    for (name, contents) in &patches {
+        let path = options.patch_dir.join(name);
+        if let Err(e) = fs::write(&path, contents) {
+            print_error(failed_to("write-failed", &path, e));
+            std::process::exit(1);
+        }
+        print_success(tr_args(
+            "wrote",
+            &[("path", path.display().to_string().into())],
+        ));
+    }
+
+    print_info(tr("argocd-add-patches"));
+    println!("\npatches:");
+    for (name, _) in &patches {
+        println!("  - path: {}", options.patch_dir.join(name).display());
+    }
+}
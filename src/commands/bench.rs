@@ -0,0 +1,361 @@
+use age::secrecy::{ExposeSecret, SecretString};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+use crate::{
+    GlobalContext,
+    util::{
+        agent,
+        config_include::load_effective_config,
+        i18n::{tr, tr_args},
+        op_key::{extract_public_key, read_key_from_op, resolve_op_reference},
+        print_status::{print_error, print_info, print_warning},
+        rule_match::{RuleMatcher, relative_path},
+        rule_templates::TEMPLATES,
+        sops_command::SopsCommandBuilder,
+        sops_config::write_config,
+        sops_files::walk_files,
+        sops_structs::{CreationRule, SopsConfig},
+    },
+};
+
+/// Synthetic files are spread over this many directories
+const SYNTHETIC_DIRS: usize = 50;
+
+/// Fastest, median and mean duration of a set of runs
+#[derive(Debug, PartialEq)]
+struct Timing {
+    min: Duration,
+    median: Duration,
+    mean: Duration,
+}
+
+impl Timing {
+    fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        let total: Duration = samples.iter().sum();
+        Some(Timing {
+            min: samples[0],
+            median: samples[samples.len() / 2],
+            mean: total / samples.len() as u32,
+        })
+    }
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn report(label: &str, timing: Option<Timing>) {
+    match timing {
+        Some(t) => println!(
+            "  {:28} min {:>10}  median {:>10}  mean {:>10}",
+            label,
+            ms(t.min),
+            ms(t.median),
+            ms(t.mean)
+        ),
+        None => println!("  {:28} {}", label, tr("bench-no-samples").dimmed()),
+    }
+}
+
+/// Runs `f` `rounds` times and collects the duration of every successful run
+fn measure<F: FnMut() -> bool>(rounds: usize, mut f: F) -> Option<Timing> {
+    let samples = (0..rounds)
+        .filter_map(|_| {
+            let start = Instant::now();
+            f().then(|| start.elapsed())
+        })
+        .collect();
+    Timing::from_samples(samples)
+}
+
+/// Measures the performance sensitive paths of opsops on this machine
+pub fn bench(context: &GlobalContext, files: usize, rounds: usize) {
+    println!("{}\n", tr("bench-heading").bold());
+
+    bench_rule_matching(context, files, rounds);
+    let key = bench_key_retrieval(context, rounds);
+    match key {
+        Some(key) => bench_encryption(context, &key, rounds),
+        None => print_warning(tr("bench-no-key")),
+    }
+}
+
+/// Walks and matches a synthetic repository of `files` files against the project's rules
+fn bench_rule_matching(context: &GlobalContext, files: usize, rounds: usize) {
+    print_info(format!(
+        "{} {}",
+        tr("bench-rule-matching").cyan(),
+        tr_args(
+            "bench-files-rounds",
+            &[("files", files.into()), ("rounds", rounds.into())],
+        )
+    ));
+
+    let dir = match TempDir::new() {
+        Ok(d) => d,
+        Err(e) => {
+            print_error(tr_args(
+                "temp-dir-failed",
+                &[("error", e.to_string().into())],
+            ));
+            return;
+        }
+    };
+    if let Err(e) = write_synthetic_repo(dir.path(), files) {
+        print_error(tr_args(
+            "bench-synthetic-files-failed",
+            &[("error", e.to_string().into())],
+        ));
+        return;
+    }
+
+    // Use the project's rules if there are any, the setup templates otherwise
+    let rules = load_effective_config(context)
+        .map(|c| c.config.creation_rules)
+        .ok()
+        .filter(|r| !r.is_empty())
+        .unwrap_or_else(|| {
+            TEMPLATES
+                .iter()
+                .map(|t| CreationRule {
+                    path_regex: Some(t.path_regex.to_string()),
+                    ..Default::default()
+                })
+                .collect()
+        });
+
+    let mut paths = Vec::new();
+    report(
+        "walk files",
+        measure(rounds, || {
+            paths = walk_files(dir.path())
+                .iter()
+                .map(|p| relative_path(dir.path(), p))
+                .collect();
+            true
+        }),
+    );
+    report(
+        &format!("match {} rules", rules.len()),
+        measure(rounds, || {
+            let matcher = RuleMatcher::new(&rules);
+            paths.iter().all(|p| {
+                let _ = matcher.first_match(p);
+                true
+            })
+        }),
+    );
+    println!();
+}
+
+fn write_synthetic_repo(root: &Path, files: usize) -> std::io::Result<()> {
+    let extensions = ["yaml", "json", "env", "toml", "md"];
+    for i in 0..files {
+        let dir = root
+            .join(format!("team-{}", i % SYNTHETIC_DIRS))
+            .join("config");
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join(format!("file-{}.{}", i, extensions[i % extensions.len()])),
+            "",
+        )?;
+    }
+    Ok(())
+}
+
+/// Times reading the key from 1Password directly and through a running agent
+fn bench_key_retrieval(context: &GlobalContext, rounds: usize) -> Option<SecretString> {
+    print_info(format!(
+        "{} {}",
+        tr("bench-key-retrieval").cyan(),
+        tr_args("bench-rounds", &[("rounds", rounds.into())])
+    ));
+
+    let reference = match resolve_op_reference(context) {
+        Ok(r) => r,
+        Err(e) => {
+            print_warning(tr_args(
+                "bench-skipping-key-retrieval",
+                &[("error", e.to_string().into())],
+            ));
+            println!();
+            return None;
+        }
+    };
+
+    let mut key = None;
+    report(
+        "1Password (uncached)",
+        measure(rounds, || match read_key_from_op(&reference) {
+            Ok(k) => {
+                key = Some(k);
+                true
+            }
+            Err(_) => false,
+        }),
+    );
+
+    if agent::is_running() {
+        report(
+            "agent (cached)",
+            measure(rounds, || agent::request_key(&reference).is_some()),
+        );
+    } else {
+        println!(
+            "  {:28} {}",
+            "agent (cached)",
+            tr("bench-agent-not-running").dimmed()
+        );
+    }
+    println!();
+
+    key
+}
+
+/// Encrypts a batch of files one after another and then in parallel
+fn bench_encryption(context: &GlobalContext, key: &SecretString, rounds: usize) {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let batch = workers * 4;
+    print_info(format!(
+        "{} {}",
+        tr("bench-bulk-encryption").cyan(),
+        tr_args(
+            "bench-files-workers",
+            &[("files", batch.into()), ("workers", workers.into())],
+        )
+    ));
+
+    if which::which("sops").is_err() {
+        print_warning(tr("bench-no-sops"));
+        return;
+    }
+    let public_key = match extract_public_key(key.expose_secret()) {
+        Ok(k) => k,
+        Err(e) => {
+            print_error(tr_args(
+                "public-key-failed",
+                &[("error", e.to_string().into())],
+            ));
+            return;
+        }
+    };
+
+    let dir = match TempDir::new() {
+        Ok(d) => d,
+        Err(e) => {
+            print_error(tr_args(
+                "temp-dir-failed",
+                &[("error", e.to_string().into())],
+            ));
+            return;
+        }
+    };
+    let bench_context = GlobalContext {
+        sops_file: Some(dir.path().join(".sops.yaml").to_string_lossy().into()),
+        opitem: None,
+        age_key_env: context.age_key_env,
+        chdir: context.chdir.clone(),
+        read_only: context.read_only,
+        key_preference: context.key_preference,
+    };
+    let config = SopsConfig {
+        creation_rules: vec![CreationRule {
+            path_regex: Some(".*".to_string()),
+            age: Some(public_key),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    if let Err(e) = write_config(&config, &bench_context) {
+        print_error(e);
+        return;
+    }
+
+    let inputs: Vec<_> = (0..batch)
+        .map(|i| dir.path().join(format!("secret-{}.yaml", i)))
+        .collect();
+    for input in &inputs {
+        if let Err(e) = fs::write(input, "password: hunter2\ntoken: abc123\n") {
+            print_error(tr_args(
+                "bench-synthetic-files-failed",
+                &[("error", e.to_string().into())],
+            ));
+            return;
+        }
+    }
+
+    let encrypt = |input: &Path| {
+        SopsCommandBuilder::new(&bench_context)
+            .arg("--encrypt")
+            .arg("--output")
+            .arg_path(input.with_extension("enc.yaml"))
+            .arg_path(input)
+            .with_age_key_value(key)
+            ._output()
+            .is_ok_and(|o| o.status.success())
+    };
+
+    let sequential = measure(rounds, || inputs.iter().all(|i| encrypt(i)));
+    let parallel = measure(rounds, || {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = inputs
+                .chunks(inputs.len().div_ceil(workers))
+                .map(|chunk| scope.spawn(move || chunk.iter().all(|i| encrypt(i))))
+                .collect();
+            handles.into_iter().all(|h| h.join().unwrap_or(false))
+        })
+    });
+
+    let throughput = |timing: &Option<Timing>| {
+        timing.as_ref().map_or("-".to_string(), |t| {
+            format!("{:.1} files/s", batch as f64 / t.median.as_secs_f64())
+        })
+    };
+    let (sequential_rate, parallel_rate) = (throughput(&sequential), throughput(&parallel));
+    report("sequential", sequential);
+    report("parallel", parallel);
+    println!(
+        "  {:28} sequential {}, parallel {}",
+        "throughput", sequential_rate, parallel_rate
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Timing, measure};
+
+    #[test]
+    fn test_timing_from_samples() {
+        let samples = [30, 10, 20, 40].map(Duration::from_millis).to_vec();
+        assert_eq!(
+            Timing::from_samples(samples),
+            Some(Timing {
+                min: Duration::from_millis(10),
+                median: Duration::from_millis(30),
+                mean: Duration::from_millis(25),
+            })
+        );
+        assert_eq!(Timing::from_samples(Vec::new()), None);
+    }
+
+    #[test]
+    fn test_measure_skips_failed_runs() {
+        let mut run = 0;
+        let timing = measure(4, || {
+            run += 1;
+            run % 2 == 0
+        });
+        assert!(timing.is_some());
+        assert_eq!(measure(3, || false), None);
+    }
+}
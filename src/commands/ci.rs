@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    GlobalContext,
+    util::{
+        ci_pipeline::{CiKey, CiProvider, CiSettings, pipeline},
+        i18n::{failed_to, tr, tr_args},
+        opsops_config::read_opsops_config,
+        passphrase_key::KeyProvider,
+        print_status::{print_error, print_info, print_success},
+        sops_config::config_dir,
+        sops_files::find_encrypted_files,
+    },
+};
+
+fn fail(message: impl Into<String>) -> ! {
+    print_error(message.into());
+    std::process::exit(1);
+}
+
+/// What the pipeline is customized to: the key the project decrypts with, a
+/// file to decrypt and whether there are schemas to validate
+fn settings(root: &Path, context: &GlobalContext) -> Result<CiSettings, String> {
+    let config = read_opsops_config(context)?.unwrap_or_default();
+    let key = match config.keyprovider {
+        KeyProvider::Passphrase => Some(CiKey::Passphrase),
+        KeyProvider::OnePassword => context
+            .opitem
+            .clone()
+            .or(config.onepassworditem)
+            .map(CiKey::OnePassword),
+    };
+    let sample_file = find_encrypted_files(root).into_iter().find_map(|file| {
+        file.strip_prefix(root)
+            .ok()
+            .map(|relative| relative.to_string_lossy().into_owned())
+    });
+    Ok(CiSettings {
+        key,
+        sample_file,
+        schemas: !config.schemas.is_empty(),
+    })
+}
+
+/// Writes a pipeline for `provider` that checks the project's secrets and, if
+/// it has a key, decrypts them with a 1Password service account or the
+/// passphrase
+pub fn generate(
+    provider: CiProvider,
+    output: Option<PathBuf>,
+    force: bool,
+    context: &GlobalContext,
+) {
+    let root = config_dir(context).unwrap_or_else(|| context.working_dir());
+    let settings = settings(&root, context).unwrap_or_else(|e| fail(e));
+    let path = output.unwrap_or_else(|| root.join(provider.default_path()));
+    if path.exists() && !force {
+        fail(tr_args(
+            "exists-pass-force",
+            &[("path", path.display().to_string().into())],
+        ));
+    }
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).unwrap_or_else(|e| fail(failed_to("create-failed", dir, e)));
+    }
+    fs::write(&path, pipeline(provider, &settings))
+        .unwrap_or_else(|e| fail(failed_to("write-failed", &path, e)));
+    print_success(tr_args(
+        "wrote",
+        &[("path", path.display().to_string().into())],
+    ));
+
+    if provider == CiProvider::Gitlab {
+        let relative = path.strip_prefix(&root).unwrap_or(&path);
+        print_info(format!(
+            "{}\n  include:\n    - local: {}",
+            tr("ci-include-gitlab"),
+            relative.display()
+        ));
+    }
+    match (&settings.key, provider) {
+        (Some(CiKey::OnePassword(_)), CiProvider::Github) => print_info(tr("ci-token-github")),
+        (Some(CiKey::OnePassword(_)), CiProvider::Gitlab) => print_info(tr("ci-token-gitlab")),
+        (Some(CiKey::Passphrase), CiProvider::Github) => print_info(tr("ci-passphrase-github")),
+        (Some(CiKey::Passphrase), CiProvider::Gitlab) => print_info(tr("ci-passphrase-gitlab")),
+        (None, _) => {}
+    }
+}
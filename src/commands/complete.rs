@@ -0,0 +1,119 @@
+use clap::ValueEnum;
+use std::path::Path;
+
+use crate::{
+    GlobalContext,
+    util::{
+        file_picker::{quiet_config, rule_matched_files},
+        reference_cache::cached_references,
+    },
+};
+
+/// What `__complete` should list candidates for
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CompletionKind {
+    /// 1Password references for `--op-item`
+    OpItem,
+    /// Project files matched by a creation rule
+    File,
+}
+
+/// References from the project config first, then recently used ones
+fn op_item_candidates(context: &GlobalContext) -> Vec<(String, &'static str)> {
+    let mut candidates = Vec::new();
+    if let Some(config) = quiet_config(context)
+        && !config.onepassworditem.is_empty()
+    {
+        candidates.push((config.onepassworditem, "from .opsops.yaml"));
+    }
+    for reference in cached_references() {
+        if !candidates.iter().any(|(c, _)| *c == reference) {
+            candidates.push((reference, "recently used"));
+        }
+    }
+    candidates
+}
+
+/// Files a creation rule applies to
+fn file_candidates(context: &GlobalContext, cwd: &Path) -> Vec<(String, &'static str)> {
+    rule_matched_files(context, cwd)
+        .into_iter()
+        .map(|path| (path.to_string_lossy().to_string(), "matches a rule"))
+        .collect()
+}
+
+fn filter_candidates(
+    candidates: Vec<(String, &'static str)>,
+    prefix: &str,
+) -> Vec<(String, &'static str)> {
+    candidates
+        .into_iter()
+        .filter(|(value, _)| value.starts_with(prefix))
+        .collect()
+}
+
+/// Prints completion candidates as `value<TAB>description`, one per line
+pub fn complete(kind: CompletionKind, prefix: &str, context: &GlobalContext) {
+    let candidates = match kind {
+        CompletionKind::OpItem => op_item_candidates(context),
+        CompletionKind::File => file_candidates(context, &context.working_dir()),
+    };
+
+    for (value, description) in filter_candidates(candidates, prefix) {
+        println!("{}\t{}", value, description);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::{file_candidates, filter_candidates};
+    use crate::GlobalContext;
+
+    #[test]
+    fn test_file_candidates_only_include_rule_matches() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join(".sops.yaml"),
+            "creation_rules:\n- path_regex: ^k8s/.*\\.yaml$\nonepassworditem: op://V/I/F\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("k8s")).unwrap();
+        fs::write(dir.path().join("k8s/secret.yaml"), "").unwrap();
+        fs::write(dir.path().join("k8s/notes.md"), "").unwrap();
+        fs::write(dir.path().join("readme.yaml"), "").unwrap();
+
+        let context = GlobalContext {
+            sops_file: Some(dir.path().join(".sops.yaml").to_string_lossy().into()),
+            opitem: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
+        };
+
+        let values = |cwd| -> Vec<String> {
+            file_candidates(&context, cwd)
+                .into_iter()
+                .map(|(v, _)| v)
+                .collect()
+        };
+        assert_eq!(values(dir.path()), vec!["k8s/secret.yaml"]);
+        assert_eq!(values(&dir.path().join("k8s")), vec!["secret.yaml"]);
+    }
+
+    #[test]
+    fn test_filter_candidates_by_prefix() {
+        let candidates = vec![
+            ("op://Infra/age/key".to_string(), "from .opsops.yaml"),
+            ("op://Personal/age/key".to_string(), "recently used"),
+        ];
+        assert_eq!(
+            filter_candidates(candidates, "op://P"),
+            vec![("op://Personal/age/key".to_string(), "recently used")]
+        );
+    }
+}
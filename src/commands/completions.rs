@@ -0,0 +1,283 @@
+use clap_complete::{Shell, generate};
+use colored::Colorize;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+
+use crate::util::{
+    dirs::{home, xdg_config_home, xdg_data_home},
+    i18n::{failed_to, tr, tr_args},
+    print_status::{print_error, print_info, print_success},
+    prompts::confirm,
+};
+
+/// Fish hooks asking `opsops __complete` for candidates clap can't know statically
+const FISH_DYNAMIC_COMPLETIONS: &str = r#"
+# Dynamic completions
+complete -c opsops -l op-item -x -a "(opsops __complete op-item (commandline -ct) 2>/dev/null)"
+complete -c opsops -n "__fish_seen_subcommand_from edit encrypt decrypt read target-keys" -f -a "(opsops __complete file (commandline -ct) 2>/dev/null)"
+"#;
+
+/// The completion script of `command` for `shell`
+pub fn completion_script(shell: Shell, command: clap::Command) -> Vec<u8> {
+    // The bash generator splits subcommand paths at "__" and panics on the
+    // hidden `__complete`, which is never offered anyway
+    let mut command = command.mut_subcommand("__complete", |c| c.name("complete-data"));
+    let mut script = Vec::new();
+    generate(shell, &mut command, "opsops", &mut script);
+    if shell == Shell::Fish {
+        script.extend_from_slice(FISH_DYNAMIC_COMPLETIONS.as_bytes());
+    }
+    script
+}
+
+/// Base directories the install locations are derived from
+struct ShellDirs {
+    home: PathBuf,
+    config_home: PathBuf,
+    data_home: PathBuf,
+    /// `$ZDOTDIR`, where zsh reads `.zshrc` from instead of the home directory
+    zdotdir: Option<PathBuf>,
+}
+
+impl ShellDirs {
+    fn from_env() -> Option<Self> {
+        Some(ShellDirs {
+            home: home()?,
+            config_home: xdg_config_home()?,
+            data_home: xdg_data_home()?,
+            zdotdir: std::env::var_os("ZDOTDIR")
+                .map(PathBuf::from)
+                .filter(|p| p.is_absolute()),
+        })
+    }
+}
+
+/// A line that has to be added to a shell's startup file to load the script
+#[derive(Debug, PartialEq)]
+struct RcLine {
+    file: PathBuf,
+    line: String,
+}
+
+/// Where the completions of `shell` go
+#[derive(Debug, PartialEq)]
+struct InstallTarget {
+    file: PathBuf,
+    /// `None` for shells that pick up the file on their own
+    rc: Option<RcLine>,
+}
+
+fn install_target(shell: Shell, dirs: &ShellDirs) -> Result<InstallTarget, String> {
+    match shell {
+        // Loaded on demand by bash-completion
+        Shell::Bash => Ok(InstallTarget {
+            file: dirs.data_home.join("bash-completion/completions/opsops"),
+            rc: None,
+        }),
+        Shell::Fish => Ok(InstallTarget {
+            file: dirs.config_home.join("fish/completions/opsops.fish"),
+            rc: None,
+        }),
+        Shell::Zsh => {
+            let file = dirs.data_home.join("zsh/site-functions/_opsops");
+            Ok(InstallTarget {
+                rc: Some(RcLine {
+                    file: dirs.zdotdir.as_ref().unwrap_or(&dirs.home).join(".zshrc"),
+                    line: format!("source '{}'", file.display()),
+                }),
+                file,
+            })
+        }
+        Shell::Elvish => Ok(InstallTarget {
+            file: dirs.config_home.join("elvish/lib/opsops.elv"),
+            rc: Some(RcLine {
+                file: dirs.config_home.join("elvish/rc.elv"),
+                line: "use opsops".to_string(),
+            }),
+        }),
+        _ => Err(tr_args(
+            "completions-install-unsupported",
+            &[("shell", shell.to_string().into())],
+        )),
+    }
+}
+
+/// Whether the startup file already loads the completions
+fn has_line(contents: &str, line: &str) -> bool {
+    contents.lines().any(|l| l.trim() == line)
+}
+
+/// Appends the line to the startup file, unless it is there already
+fn add_rc_line(rc: &RcLine) -> Result<bool, String> {
+    let contents = fs::read_to_string(&rc.file).unwrap_or_default();
+    if has_line(&contents, &rc.line) {
+        return Ok(false);
+    }
+    if let Some(dir) = rc.file.parent() {
+        fs::create_dir_all(dir).map_err(|e| failed_to("create-failed", dir, e))?;
+    }
+    let separator = if contents.is_empty() || contents.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&rc.file)
+        .and_then(|mut file| write!(file, "{}\n# opsops completions\n{}\n", separator, rc.line))
+        .map_err(|e| failed_to("write-failed", &rc.file, e))?;
+    Ok(true)
+}
+
+/// Writes the script where `shell` looks for completions. Startup files are
+/// only edited after asking, without a terminal the line to add is printed.
+fn install(shell: Shell, script: &[u8]) -> Result<(), String> {
+    let dirs = ShellDirs::from_env().ok_or_else(|| tr("completions-no-home"))?;
+    let target = install_target(shell, &dirs)?;
+
+    if fs::read(&target.file).is_ok_and(|current| current == script) {
+        print_info(tr_args(
+            "completions-up-to-date",
+            &[
+                ("shell", shell.to_string().into()),
+                ("path", target.file.display().to_string().into()),
+            ],
+        ));
+    } else {
+        if let Some(dir) = target.file.parent() {
+            fs::create_dir_all(dir).map_err(|e| failed_to("create-failed", dir, e))?;
+        }
+        fs::write(&target.file, script).map_err(|e| failed_to("write-failed", &target.file, e))?;
+        print_success(tr_args(
+            "completions-installed",
+            &[
+                ("shell", shell.to_string().into()),
+                ("path", target.file.display().to_string().into()),
+            ],
+        ));
+    }
+
+    let Some(rc) = target.rc else {
+        return Ok(());
+    };
+    if has_line(&fs::read_to_string(&rc.file).unwrap_or_default(), &rc.line) {
+        return Ok(());
+    }
+    let consent = std::io::stdin().is_terminal()
+        && confirm(
+            &tr_args(
+                "completions-add-line",
+                &[
+                    ("line", rc.line.as_str().into()),
+                    ("path", rc.file.display().to_string().into()),
+                ],
+            ),
+            true,
+        )
+        .unwrap_or(false);
+    if consent && add_rc_line(&rc)? {
+        print_success(tr_args(
+            "completions-added",
+            &[("path", rc.file.display().to_string().into())],
+        ));
+    } else {
+        print_info(format!(
+            "{}\n  {}",
+            tr_args(
+                "completions-load-them",
+                &[("path", rc.file.display().to_string().into())],
+            ),
+            rc.line
+        ));
+    }
+    Ok(())
+}
+
+/// Prints the completion script, or installs it with `--install`. The shell
+/// defaults to the one in `$SHELL`.
+pub fn completions(shell: Option<Shell>, install_script: bool, command: clap::Command) {
+    let Some(shell) = shell.or_else(Shell::from_env) else {
+        print_error(format!(
+            "{} {}",
+            tr("completions-no-shell").red(),
+            tr("completions-name-shell").dimmed()
+        ));
+        std::process::exit(1);
+    };
+    let script = completion_script(shell, command);
+
+    if !install_script {
+        if let Err(e) = std::io::stdout().write_all(&script) {
+            print_error(tr_args(
+                "completions-write-failed",
+                &[("error", e.to_string().into())],
+            ));
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Err(e) = install(shell, &script) {
+        print_error(e.red());
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap_complete::Shell;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    use super::{InstallTarget, RcLine, ShellDirs, add_rc_line, install_target};
+
+    fn dirs(zdotdir: Option<&str>) -> ShellDirs {
+        ShellDirs {
+            home: PathBuf::from("/home/me"),
+            config_home: PathBuf::from("/home/me/.config"),
+            data_home: PathBuf::from("/home/me/.local/share"),
+            zdotdir: zdotdir.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn test_install_targets() {
+        assert_eq!(
+            install_target(Shell::Fish, &dirs(None)).unwrap(),
+            InstallTarget {
+                file: PathBuf::from("/home/me/.config/fish/completions/opsops.fish"),
+                rc: None
+            }
+        );
+        assert_eq!(
+            install_target(Shell::Zsh, &dirs(Some("/home/me/.zsh"))).unwrap(),
+            InstallTarget {
+                file: PathBuf::from("/home/me/.local/share/zsh/site-functions/_opsops"),
+                rc: Some(RcLine {
+                    file: PathBuf::from("/home/me/.zsh/.zshrc"),
+                    line: "source '/home/me/.local/share/zsh/site-functions/_opsops'".to_string()
+                })
+            }
+        );
+        assert!(install_target(Shell::PowerShell, &dirs(None)).is_err());
+    }
+
+    #[test]
+    fn test_add_rc_line_is_idempotent() {
+        let dir = TempDir::new().unwrap();
+        let rc = RcLine {
+            file: dir.path().join(".zshrc"),
+            line: "source '/tmp/_opsops'".to_string(),
+        };
+        fs::write(&rc.file, "autoload -Uz compinit && compinit").unwrap();
+
+        assert!(add_rc_line(&rc).unwrap());
+        assert!(!add_rc_line(&rc).unwrap());
+        assert_eq!(
+            fs::read_to_string(&rc.file).unwrap(),
+            "autoload -Uz compinit && compinit\n\n# opsops completions\nsource '/tmp/_opsops'\n"
+        );
+    }
+}
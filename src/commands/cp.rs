@@ -0,0 +1,89 @@
+use colored::Colorize;
+use std::path::PathBuf;
+
+use crate::{
+    GlobalContext,
+    util::{
+        document::{extract, parse_document},
+        encrypted_insert::{check_post_processable, insert_values},
+        i18n::{failed_to, tr_args},
+        op_key::get_age_key_from_1password,
+        print_status::{print_error, print_success, print_warning},
+        sops_command::decrypt_in_memory,
+        sops_files::{is_sops_encrypted_file, sops_file_type},
+    },
+};
+
+fn fail(message: impl Into<String>) -> ! {
+    print_error(message.into());
+    std::process::exit(1);
+}
+
+/// Splits `<file>#<key path>` at the last `#`
+fn split_location(location: &str) -> (PathBuf, Option<&str>) {
+    match location.rsplit_once('#') {
+        Some((file, path)) if !path.is_empty() => (PathBuf::from(file), Some(path)),
+        Some((file, _)) => (PathBuf::from(file), None),
+        None => (PathBuf::from(location), None),
+    }
+}
+
+/// Copies the value or subtree at `<src>#<path>` into `<dst>#<path>`. Both files
+/// are decrypted in memory with the key fetched once, only the destination is
+/// encrypted again and the plaintext never touches the disk. The key path of
+/// the destination defaults to the one of the source, a destination that
+/// doesn't exist yet is created under its creation rule.
+pub fn cp(source: String, destination: String, force: bool, context: &GlobalContext) {
+    let (source_file, Some(source_path)) = split_location(&source) else {
+        fail(tr_args(
+            "cp-names-no-value",
+            &[
+                ("location", source.as_str().into()),
+                ("syntax", "<file>#<key.path>".yellow().to_string().into()),
+            ],
+        ));
+    };
+    let (destination_file, destination_path) = split_location(&destination);
+    let destination_path = destination_path.unwrap_or(source_path);
+    for file in [&source_file, &destination_file] {
+        if let Err(e) = check_post_processable(file) {
+            fail(e);
+        }
+    }
+    if !is_sops_encrypted_file(&source_file) {
+        fail(tr_args(
+            "not-encrypted",
+            &[("path", source_file.display().to_string().into())],
+        ));
+    }
+
+    let key = get_age_key_from_1password(context)
+        .unwrap_or_else(|e| fail(tr_args("age-key-get-failed", &[("error", e.into())])));
+    let content = decrypt_in_memory(&source_file, Some(&key), context)
+        .unwrap_or_else(|e| fail(failed_to("decrypt-path-failed", &source_file, e)));
+    let document =
+        parse_document(&content, sops_file_type(&source_file)).unwrap_or_else(|e| fail(e));
+    let value = extract(&document, source_path)
+        .unwrap_or_else(|e| fail(format!("{}: {}", source_file.display(), e)))
+        .clone();
+
+    let values = vec![(destination_path.to_string(), value)];
+    let created =
+        insert_values(&destination_file, values, force, &key, context).unwrap_or_else(|e| fail(e));
+    print_success(tr_args(
+        "cp-copied",
+        &[
+            ("source", source.as_str().into()),
+            (
+                "destination",
+                format!("{}#{}", destination_file.display(), destination_path).into(),
+            ),
+        ],
+    ));
+    if created {
+        print_warning(tr_args(
+            "created-for-rule",
+            &[("path", destination_file.display().to_string().into())],
+        ));
+    }
+}
@@ -1,63 +1,310 @@
 use crate::GlobalContext;
-use crate::util::print_status::{print_error, print_info, print_success};
+use crate::commands::read::{ReadOptions, read};
+use crate::util::advice::advise_sops_failure;
+use crate::util::bulk::run_bulk;
+use crate::util::decrypted_copies::record_decryption;
+use crate::util::dirs::runtime_dir;
+use crate::util::document::render_document;
+use crate::util::document_includes::read_with_includes;
+use crate::util::file_picker::pick_file;
+use crate::util::find_project_root::find_project_root;
+use crate::util::formatting::{Formatting, resolve_formatting};
+use crate::util::git_index::could_be_committed;
+use crate::util::i18n::{failed_to, tr, tr_args};
+use crate::util::markdown::{decrypt_note, is_markdown};
+use crate::util::op_key::get_age_key_from_1password;
+use crate::util::output_destinations::{default_output, output_for};
+use crate::util::output_format::OutputFormat;
+use crate::util::output_permissions::{apply_permissions, prepare_output, resolve_permissions};
+use crate::util::print_status::{print_error, print_info, print_success, print_warning};
 use crate::util::sops_command::SopsCommandBuilder;
+use crate::util::sops_files::{find_encrypted_files, is_sops_encrypted_file, sops_file_type};
 use crate::util::sops_status::is_file_unchanged_status;
 use colored::Colorize;
-use std::ffi::OsString;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Rewrites the decrypted file at `path` with sorted keys if configured
+fn canonicalize_file(path: &Path, file_type: &str, formatting: &Formatting) -> Result<(), String> {
+    let mut content = fs::read(path).map_err(|e| failed_to("read-failed", path, e))?;
+    let canonical = formatting.canonicalize(&content, file_type);
+    content.zeroize();
+    match canonical? {
+        Some(canonical) => {
+            fs::write(path, &*canonical).map_err(|e| failed_to("write-failed", path, e))
+        }
+        None => Ok(()),
+    }
+}
+
+/// A path named like `output` in a fresh directory of its own under the
+/// runtime directory, so concurrent decryptions never write the same file
+fn unique_output(output: &Path) -> Result<PathBuf, String> {
+    let parent = runtime_dir().join("decrypted");
+    fs::create_dir_all(&parent).map_err(|e| failed_to("create-failed", &parent, e))?;
+    let dir = tempfile::Builder::new()
+        .prefix("decrypt-")
+        .tempdir_in(&parent)
+        .map_err(|e| failed_to("create-dir-in-failed", &parent, e))?
+        .keep();
+    Ok(dir.join(output.file_name().unwrap_or(OsStr::new("decrypted"))))
+}
+
+/// Whether `output` was written after `source` was, by an edit or another
+/// decryption since
+fn newer_than_source(output: &Path, source: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    matches!((modified(output), modified(source)), (Some(o), Some(s)) if o > s)
+}
+
+/// Creates the directories of `output`, warning if it is a `decrypt_outputs:`
+/// destination git doesn't ignore
+fn prepare_destination(output: &Path, source: &str) -> Result<(), String> {
+    if let Some(dir) = output.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| failed_to("create-failed", dir, e))?;
+    }
+    if output != Path::new(default_output(source)) && could_be_committed(output) {
+        print_warning(tr_args(
+            "decrypt-not-ignored",
+            &[("path", output.display().to_string().into())],
+        ));
+    }
+    Ok(())
+}
+
+/// Writes `source` with its includes resolved to `output`
+fn write_with_includes(
+    source: &Path,
+    output: &Path,
+    file_type: &str,
+    formatting: &Formatting,
+    context: &GlobalContext,
+) -> Result<(), String> {
+    let mut document = read_with_includes(source, context)?;
+    formatting.apply(&mut document);
+    let mut rendered = Zeroizing::new(render_document(&document, file_type)?);
+    if !rendered.ends_with('\n') {
+        rendered.push('\n');
+    }
+    fs::write(output, rendered.as_bytes()).map_err(|e| failed_to("write-failed", output, e))
+}
+
+/// Reports a finished decryption and remembers where the plaintext copy of
+/// `source` went
+fn report_decrypted(output_path: &str, source: &str, unique: bool, context: &GlobalContext) {
+    if unique {
+        println!("{}", output_path);
+        return;
+    }
+    print_success(tr("decrypted-file").green());
+    // Remember the source of the plaintext copy to detect upstream changes on encrypt
+    if output_path != source
+        && let Some(root) = find_project_root(context)
+        && let Err(e) = record_decryption(&root, Path::new(output_path), Path::new(source))
+    {
+        print_warning(tr_args(
+            "decrypt-tracking-failed",
+            &[("error", e.to_string().into())],
+        ));
+    }
+}
+
+/// Decrypts a file using SOPS with the Age key from 1Password. With
+/// `unique` the plaintext goes to a new path per invocation, printed
+/// alone on stdout, instead of next to the file. With `resolve_includes`
+/// the files it includes are decrypted and inlined.
+pub fn decrypt(
+    path: Option<OsString>,
+    mode: Option<String>,
+    owner: Option<String>,
+    unique: bool,
+    formatting: Formatting,
+    resolve_includes: bool,
+    context: &GlobalContext,
+) {
+    // Without a path, let the user pick one of the files matched by a rule
+    let path = path.unwrap_or_else(|| pick_file(context, "decrypt"));
 
-/// Decrypts a file using SOPS with the Age key from 1Password
-pub fn decrypt(path: OsString, context: &GlobalContext) {
     // Convert the path from OsString to String
     let path_str = match path.into_string() {
         Ok(p) => p,
         Err(os) => {
-            print_error(format!("{} {:?}", "Invalid UTF-8 in path:".red(), os));
+            print_error(format!("{} {:?}", tr("invalid-utf8-path").red(), os));
             std::process::exit(1);
         }
     };
 
     // Check if the file exists
     if !Path::new(&path_str).is_file() {
-        print_error(format!("{} {}", "File not found:".red(), path_str));
+        print_error(format!("{} {}", tr("file-not-found").red(), path_str));
         std::process::exit(1);
     }
 
+    if resolve_includes && is_markdown(Path::new(&path_str)) {
+        print_error(tr("decrypt-includes-in-note").red());
+        std::process::exit(1);
+    }
+    // The output would replace the includes of a plaintext file with their content
+    if resolve_includes && !is_sops_encrypted_file(Path::new(&path_str)) {
+        print_error(tr_args(
+            "decrypt-includes-plaintext",
+            &[("path", path_str.as_str().into())],
+        ));
+        std::process::exit(1);
+    }
+
+    // Nothing may be written, so the plaintext goes to stdout like with `read`
+    if context.read_only {
+        let options = ReadOptions {
+            format: OutputFormat::Text,
+            extract: None,
+            redact: false,
+            formatting,
+            resolve_includes,
+        };
+        read(Some(path_str.into()), options, context);
+        return;
+    }
+
     // Ensure sops is installed
     if which::which("sops").is_err() {
         print_error(format!(
             "{} {}",
-            "'sops' is not installed or not in PATH.".red(),
-            "Please install it first.".dimmed()
+            tr("sops-not-installed").red(),
+            tr("install-it-first").dimmed()
         ));
         std::process::exit(1);
     }
 
-    // Create the decrypted output path - remove .enc extension if it exists, otherwise add .dec
-    let output_path = if path_str.ends_with(".enc") {
-        path_str[..path_str.len() - 4].to_string()
+    // Where `decrypt_outputs:` maps the file, or next to it without a .enc extension
+    let default_output = match output_for(&path_str, context) {
+        Ok(output) => output.to_string_lossy().into_owned(),
+        Err(e) => {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    };
+
+    // Plaintext is only readable by its owner unless configured otherwise
+    let permissions = match resolve_permissions(
+        Path::new(&default_output),
+        mode.as_deref(),
+        owner.as_deref(),
+        context,
+    ) {
+        Ok(p) => p,
+        Err(e) => {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    };
+
+    let output_path = if unique {
+        match unique_output(Path::new(&default_output)) {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(e) => {
+                print_error(e.red());
+                std::process::exit(1);
+            }
+        }
     } else {
-        path_str.to_string()
+        if default_output != path_str
+            && newer_than_source(Path::new(&default_output), Path::new(&path_str))
+        {
+            print_warning(tr_args(
+                "decrypt-output-newer",
+                &[
+                    ("output", default_output.as_str().into()),
+                    ("path", path_str.as_str().into()),
+                ],
+            ));
+        }
+        if let Err(e) = prepare_destination(Path::new(&default_output), &path_str) {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+        default_output
     };
+    if let Err(e) = prepare_output(Path::new(&output_path)) {
+        print_error(e.red());
+        std::process::exit(1);
+    }
+
+    // stdout only carries the path of a unique output
+    if !unique {
+        println!(
+            "{} {} -> {}",
+            tr("decrypting").green(),
+            path_str,
+            output_path
+        );
+    }
 
-    println!(
-        "{} {} -> {}",
-        "🔓 Decrypting".green(),
-        path_str,
-        output_path
-    );
+    // Notes only have their front matter and secret blocks encrypted
+    if is_markdown(Path::new(&output_path)) {
+        let result = std::fs::read_to_string(&path_str)
+            .map_err(|e| failed_to("read-failed", Path::new(&path_str), e))
+            .and_then(|contents| decrypt_note(&contents, Path::new(&path_str), context))
+            .and_then(|mut note| {
+                let written = std::fs::write(&output_path, &note)
+                    .map_err(|e| failed_to("write-failed", Path::new(&output_path), e));
+                note.zeroize();
+                written
+            })
+            .and_then(|_| apply_permissions(Path::new(&output_path), &permissions));
+        if let Err(e) = result {
+            print_error(format!("{} {}", tr("decrypt-note-failed").red(), e));
+            std::process::exit(1);
+        }
+        if unique {
+            println!("{}", output_path);
+        } else {
+            print_success(tr("decrypted-note").green());
+        }
+        return;
+    }
+
+    let formatting = match resolve_formatting(&formatting, context) {
+        Ok(formatting) => formatting,
+        Err(e) => {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    };
+    let file_type = sops_file_type(Path::new(&path_str));
+
+    if resolve_includes {
+        let result = write_with_includes(
+            Path::new(&path_str),
+            Path::new(&output_path),
+            file_type,
+            &formatting,
+            context,
+        )
+        .and_then(|_| apply_permissions(Path::new(&output_path), &permissions));
+        if let Err(e) = result {
+            print_error(format!("{} {}", tr("decrypt-failed").red(), e));
+            std::process::exit(1);
+        }
+        report_decrypted(&output_path, &path_str, unique, context);
+        return;
+    }
 
     // Create a SOPS command with the Age key from 1Password
     let sops_command = match SopsCommandBuilder::new(context)
         .arg("--decrypt")
+        .args(formatting.sops_args(file_type))
         .arg("--output")
-        .arg(&output_path)
-        .arg(&path_str)
+        .arg_path(&output_path)
+        .arg_path(&path_str)
         .with_age_key()
     {
         Ok(cmd) => cmd,
         Err(e) => {
-            print_error(format!("{} {}", "Failed to get Age key:".red(), e));
+            print_error(format!("{} {}", tr("age-key-failed").red(), e));
             std::process::exit(1);
         }
     };
@@ -65,29 +312,113 @@ pub fn decrypt(path: OsString, context: &GlobalContext) {
     // Run the command
     match sops_command.status() {
         Ok(status) if status.success() => {
-            print_success(format!(
-                "{}",
-                "Successfully decrypted file with SOPS".green()
-            ));
+            let result = canonicalize_file(Path::new(&output_path), file_type, &formatting)
+                .and_then(|_| apply_permissions(Path::new(&output_path), &permissions));
+            if let Err(e) = result {
+                print_error(e.red());
+                std::process::exit(1);
+            }
+            report_decrypted(&output_path, &path_str, unique, context);
         }
         Ok(status) if is_file_unchanged_status(&status) => {
-            print_info(format!(
-                "{} {}",
-                "File has not changed.".blue(),
-                output_path
-            ));
+            print_info(format!("{} {}", tr("file-unchanged").blue(), output_path));
         }
         Ok(status) => {
             print_error(format!(
-                "{} Exit code: {}",
-                "Error while decrypting the file.".red(),
-                status
+                "{} {}",
+                tr("decrypt-failed").red(),
+                tr_args("exit-code", &[("status", status.to_string().into())])
             ));
+            advise_sops_failure(&status, Path::new(&path_str), context);
             std::process::exit(status.code().unwrap_or(1));
         }
         Err(e) => {
-            print_error(format!("{} {:?}", "Failed to launch sops:".red(), e));
+            print_error(format!("{} {:?}", tr("sops-launch-failed").red(), e));
             std::process::exit(1);
         }
     }
 }
+
+/// Decrypts every encrypted file of the project to where `decrypt_outputs:`
+/// maps it, or next to it if it has a `.enc` extension. Files that would be
+/// decrypted in place are left alone.
+pub fn decrypt_all(formatting: Formatting, fail_fast: bool, context: &GlobalContext) {
+    fn fail(message: String) -> ! {
+        print_error(message.red());
+        std::process::exit(1);
+    }
+
+    let Some(root) = find_project_root(context) else {
+        fail(tr("no-project-root"));
+    };
+    let formatting = resolve_formatting(&formatting, context).unwrap_or_else(|e| fail(e));
+
+    let mut outputs = BTreeMap::new();
+    let mut in_place = 0;
+    for file in find_encrypted_files(&root) {
+        let output = output_for(&file.to_string_lossy(), context).unwrap_or_else(|e| fail(e));
+        if output == file {
+            in_place += 1;
+        } else {
+            outputs.insert(file, output);
+        }
+    }
+    if in_place > 0 {
+        print_info(tr_args(
+            "decrypt-skipping-in-place",
+            &[("count", in_place.into())],
+        ));
+    }
+    if outputs.is_empty() {
+        print_info(tr("decrypt-nothing"));
+        return;
+    }
+
+    // Fetch the key once instead of once per file
+    let age_key = get_age_key_from_1password(context)
+        .unwrap_or_else(|e| fail(format!("{} {}", tr("age-key-failed"), e)));
+
+    let files: Vec<PathBuf> = outputs.keys().cloned().collect();
+    let report = run_bulk(&files, "decrypt", fail_fast, |file| {
+        let output = &outputs[file];
+        prepare_destination(output, &file.to_string_lossy())?;
+        let permissions = resolve_permissions(output, None, None, context)?;
+        prepare_output(output)?;
+        if is_markdown(file) {
+            let contents =
+                fs::read_to_string(file).map_err(|e| failed_to("read-failed", file, e))?;
+            let mut note = decrypt_note(&contents, file, context)?;
+            let written =
+                fs::write(output, &note).map_err(|e| failed_to("write-failed", output, e));
+            note.zeroize();
+            written?;
+        } else {
+            let file_type = sops_file_type(file);
+            let result = SopsCommandBuilder::new(context)
+                .arg("--decrypt")
+                .args(formatting.sops_args(file_type))
+                .arg("--output")
+                .arg_path(output)
+                .arg_path(file)
+                .with_age_key_value(&age_key)
+                ._output()
+                .map_err(|e| format!("{} {}", tr("sops-launch-failed"), e))?;
+            if !result.status.success() {
+                let stderr = String::from_utf8_lossy(&result.stderr);
+                return Err(stderr
+                    .trim()
+                    .lines()
+                    .last()
+                    .unwrap_or("sops failed")
+                    .to_string());
+            }
+            canonicalize_file(output, file_type, &formatting)?;
+        }
+        apply_permissions(output, &permissions)?;
+        record_decryption(&root, output, file)
+    });
+    report.print_summary();
+    if report.exit_code() != 0 {
+        std::process::exit(report.exit_code());
+    }
+}
@@ -0,0 +1,141 @@
+use age::secrecy::SecretString;
+use colored::Colorize;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::{
+    GlobalContext,
+    util::{
+        diff_render::{
+            DiffRenderer, configured_renderer, line_changes, render_inline, render_side_by_side,
+            run_external,
+        },
+        git_index::committed_content,
+        i18n::{failed_to, tr, tr_args},
+        markdown::{decrypt_note, is_markdown},
+        op_key::get_age_key_from_1password,
+        print_status::{print_error, print_info},
+        sops_command::SopsCommandBuilder,
+        sops_files::{is_sops_encrypted, sops_file_type},
+    },
+};
+
+/// The plaintext of `contents`, a version of the file at `path`. Versions
+/// committed before the file was encrypted are returned as they are.
+fn plaintext(
+    contents: &[u8],
+    path: &Path,
+    age_key: &SecretString,
+    context: &GlobalContext,
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    let text = String::from_utf8_lossy(contents);
+    if !is_sops_encrypted(&text) {
+        return Ok(Zeroizing::new(contents.to_vec()));
+    }
+    if is_markdown(path) {
+        return decrypt_note(&text, path, context).map(|note| Zeroizing::new(note.into_bytes()));
+    }
+    let file_type = sops_file_type(path);
+    let output = SopsCommandBuilder::new(context)
+        .arg("--decrypt")
+        .arg("--input-type")
+        .arg(file_type)
+        .arg("--output-type")
+        .arg(file_type)
+        .arg("--filename-override")
+        .arg_path(path)
+        .arg("/dev/stdin")
+        .with_age_key_value(age_key)
+        .output_with_input(contents)
+        .map_err(|e| format!("{} {}", tr("sops-launch-failed"), e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(Zeroizing::new(output.stdout))
+}
+
+/// Shows how the plaintext of the file at `path` changed since the revision
+/// `rev`, decrypting both versions in memory. `renderer` overrides the one of
+/// the user config, see [`crate::util::diff_render`].
+pub fn diff(path: OsString, rev: String, renderer: Option<String>, context: &GlobalContext) {
+    let path = Path::new(&path);
+    let renderer = match configured_renderer(renderer.as_deref()) {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    };
+    let versions = committed_content(path, &rev).and_then(|old| {
+        let new = fs::read(path).map_err(|e| failed_to("read-failed", path, e))?;
+        Ok((old, new))
+    });
+    let (old, new) = match versions {
+        Ok(versions) => versions,
+        Err(e) => {
+            print_error(e);
+            std::process::exit(1);
+        }
+    };
+    if old == new {
+        print_info(tr_args(
+            "diff-unchanged",
+            &[
+                ("path", path.display().to_string().into()),
+                ("rev", rev.as_str().into()),
+            ],
+        ));
+        return;
+    }
+
+    let age_key = match get_age_key_from_1password(context) {
+        Ok(key) => key,
+        Err(e) => {
+            print_error(format!("{} {}", tr("age-key-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let plaintexts = plaintext(&old, path, &age_key, context)
+        .and_then(|old| Ok((old, plaintext(&new, path, &age_key, context)?)));
+    let (old, new) = match plaintexts {
+        Ok(plaintexts) => plaintexts,
+        Err(e) => {
+            print_error(failed_to("decrypt-path-failed", path, e));
+            std::process::exit(1);
+        }
+    };
+    if old == new {
+        print_info(tr_args(
+            "diff-plaintext-unchanged",
+            &[
+                ("path", path.display().to_string().into()),
+                ("rev", rev.as_str().into()),
+            ],
+        ));
+        return;
+    }
+
+    if let DiffRenderer::External(command) = &renderer {
+        let file_name = path
+            .file_name()
+            .map_or("file".into(), |name| name.to_string_lossy());
+        if let Err(e) = run_external(command, &file_name, &old, &new) {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+        return;
+    }
+    let (old, new) = (String::from_utf8_lossy(&old), String::from_utf8_lossy(&new));
+    let changes = line_changes(&old, &new);
+    let rendered = Zeroizing::new(match renderer {
+        DiffRenderer::SideBySide => render_side_by_side(&changes, None),
+        _ => render_inline(
+            &changes,
+            &format!("{} ({})", path.display(), rev),
+            &format!("{} ({})", path.display(), tr("diff-working-tree")),
+        ),
+    });
+    print!("{}", *rendered);
+}
@@ -1,178 +1,565 @@
 use crate::{
     GlobalContext,
     util::{
-        op_key::{extract_public_key, get_age_key_from_1password},
-        print_status::{print_error, print_success, print_warning},
-        sops_config::read_or_create_config,
+        check_report::{CheckReport, FailOn},
+        config_include::load_effective_config,
+        destinations::destination_problems,
+        environment::detect_tool,
+        escrow::{escrow_recipient, rule_recipients, rules_missing_escrow},
+        find_project_root::find_project_root,
+        host_keys::{HostIdentity, current_host_identity},
+        i18n::{failed_to, tr, tr_args},
+        key_registry::{project_id, read_key_registry, write_key_registry},
+        op_key::{
+            get_age_key_from_1password, mask_key, public_keys, validate_age_recipients,
+            with_fingerprint,
+        },
+        passphrase_key::passphrase_settings,
+        print_status::{print_error, print_info},
+        recipient_expiry::{expired_recipients, remaining_access},
+        rule_match::{RuleMatcher, relative_path, unescaped_path_suggestion},
+        secret_scan::{HISTORY_DEPTH, scan_repository},
+        sops_config::config_dir,
+        sops_files::walk_files,
+        sops_structs::{CreationRule, DestinationRule},
     },
 };
-use colored::Colorize;
-
-pub fn doctor(context: &GlobalContext) {
-    match which::which("sops") {
-        Ok(path) => {
-            let version = std::process::Command::new(&path)
-                .arg("--version")
-                .output()
-                .ok()
-                .and_then(|o| String::from_utf8(o.stdout).ok())
-                .map(|out| out.lines().next().unwrap_or("unknown").to_string())
-                .unwrap_or_else(|| "unknown".to_string());
-            print_success(format!(
-                "{} {} {}",
-                "Found sops:".green(),
-                path.display(),
-                version.trim().dimmed()
-            ));
-        }
-        Err(_) => {
-            print_error(format!(
-                "{}",
-                "sops is not installed or not found in PATH. Please install sops.".red()
-            ));
+use age::secrecy::ExposeSecret;
+use git2::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How `opsops doctor` reports its checks
+pub struct DoctorOptions {
+    /// Exit non-zero when a check fails instead of only printing it
+    pub ci: bool,
+    pub fail_on: FailOn,
+    /// Compare the key with the other projects in the key registry
+    pub org: bool,
+    pub junit: Option<PathBuf>,
+    pub json: Option<PathBuf>,
+}
+
+pub fn doctor(options: DoctorOptions, context: &GlobalContext) {
+    // CI logs don't render escape codes
+    if options.ci {
+        colored::control::set_override(false);
+    }
+
+    let mut report = CheckReport::default();
+    run_checks(&mut report, options.org, context);
+
+    if let Some(path) = &options.json {
+        write_report(path, report.to_json());
+    }
+    if let Some(path) = &options.junit {
+        write_report(path, Ok(report.to_junit("opsops doctor", options.fail_on)));
+    }
+
+    if options.ci && report.failed(options.fail_on) {
+        std::process::exit(1);
+    }
+}
+
+fn write_report(path: &Path, contents: Result<String, String>) {
+    let result = contents.and_then(|contents| {
+        fs::write(path, contents).map_err(|e| failed_to("write-failed", path, e))
+    });
+    if let Err(e) = result {
+        print_error(e);
+        std::process::exit(1);
+    }
+}
+
+/// Runs the checks in order, stopping at the first one the rest depend on
+fn run_checks(report: &mut CheckReport, org: bool, context: &GlobalContext) {
+    let sops = detect_tool("sops");
+    match &sops.path {
+        Some(path) => report.pass(
+            "sops",
+            tr_args(
+                "doctor-found-sops",
+                &[
+                    ("path", path.display().to_string().into()),
+                    ("version", sops.version_or_unknown().into()),
+                ],
+            ),
+        ),
+        None => {
+            report.fail("sops", tr("doctor-no-sops"), Vec::new());
             return;
         }
     }
-    match which::which("op") {
-        Ok(path) => {
-            let version = std::process::Command::new(&path)
-                .arg("--version")
-                .output()
-                .ok()
-                .and_then(|o| String::from_utf8(o.stdout).ok())
-                .unwrap_or_else(|| "unknown".to_string());
-            print_success(format!(
-                "{} {} {}",
-                "Found 1Password CLI (op):".green(),
-                path.display(),
-                version.trim().dimmed()
-            ));
+    // Projects in passphrase mode don't need 1Password
+    let passphrase = match passphrase_settings(context) {
+        Ok(settings) => settings,
+        Err(err) => {
+            report.fail("keyprovider", err, Vec::new());
+            return;
+        }
+    };
+    // Hosts with a key file of their own don't need 1Password either
+    let host = match current_host_identity(context) {
+        Ok(host) => host,
+        Err(err) => {
+            report.fail("host_keys", err, Vec::new());
+            return;
         }
-        Err(_) => {
-            print_error(format!(
-                "{}",
-                "1Password CLI (op) is not installed or not found in PATH. Please install op."
-                    .red()
-            ));
+    };
+    let key_file = host
+        .as_ref()
+        .is_some_and(|host| matches!(host.identity, HostIdentity::KeyFile(_)));
+    let op = detect_tool("op");
+    match &op.path {
+        Some(path) => report.pass(
+            "op",
+            tr_args(
+                "doctor-found-op",
+                &[
+                    ("path", path.display().to_string().into()),
+                    ("version", op.version_or_unknown().into()),
+                ],
+            ),
+        ),
+        None if passphrase.is_some() || key_file => {}
+        None => {
+            report.fail("op", tr("doctor-no-op"), Vec::new());
             return;
         }
     }
 
-    let config = match read_or_create_config(context) {
-        Ok(c) => c,
+    let config = match load_effective_config(context) {
+        Ok(c) => c.config,
         Err(err) => {
-            print_error(format!("{} {}", "Error reading sops file: ".red(), err));
+            report.fail(
+                "config",
+                tr_args("sops-config-read-failed", &[("error", err.into())]),
+                Vec::new(),
+            );
             return;
         }
     };
-    // Check if onepassworditem is set
-    if config.onepassworditem.is_empty() {
-        print_error(format!(
-            "{}",
-            "No 1Password reference found in .sops.yaml. Run 'opsops init' to configure.".red()
-        ));
+    check_rule_patterns(report, &config.creation_rules, context);
+    check_escrow(report, &config.creation_rules, context);
+    check_expired_recipients(report, &config.creation_rules, context);
+    check_destinations(report, &config.destination_rules);
+
+    if let Some(host) = &host {
+        report.pass(
+            "host_keys",
+            tr_args(
+                "doctor-host-key",
+                &[
+                    ("host", host.hostname.as_str().into()),
+                    ("pattern", host.pattern.as_str().into()),
+                    ("identity", host.identity.to_string().into()),
+                ],
+            ),
+        );
+    } else if let Some(settings) = &passphrase {
+        report.pass(
+            "keyprovider",
+            tr_args(
+                "doctor-passphrase-key",
+                &[("key", with_fingerprint(&settings.public_key).into())],
+            ),
+        );
+    } else if config.onepassworditem.is_empty() {
+        // Check if onepassworditem is set
+        report.fail("onepassworditem", tr("doctor-no-item"), Vec::new());
         return;
     } else {
-        print_success(format!(
-            "{} {}\n",
-            "1Password item found in .sops.yaml:".green(),
-            config.onepassworditem
-        ));
+        report.pass(
+            "onepassworditem",
+            tr_args(
+                "doctor-item-found",
+                &[("item", config.onepassworditem.as_str().into())],
+            ),
+        );
     }
 
     let age = match get_age_key_from_1password(context) {
         Ok(it) => it,
         Err(err) => {
-            print_error(format!("{} {}", "Couldn't get age key:".red(), err));
+            report.fail(
+                "private_key",
+                tr_args("age-key-get-failed", &[("error", err.into())]),
+                Vec::new(),
+            );
             return;
         }
     };
+    report.pass(
+        "private_key",
+        tr_args("doctor-private-key", &[("key", mask_key(&age).into())]),
+    );
 
-    // Create a copy of age before moving it
-    let age_copy = age.clone();
-    let mut hiddenkey = age_copy;
-    let stars = "*".repeat(hiddenkey.len() - 22);
-    hiddenkey.replace_range(15..=(hiddenkey.len() - 8), &stars);
-    print_success(format!("{} {}", "Got private key:".green(), hiddenkey));
-
-    // Parse the private key into an Identity
-    let derived_public_key = match extract_public_key(&age) {
-        Ok(k) => k,
+    // The field may hold a whole keys.txt with several identities
+    let identities = match public_keys(age.expose_secret()) {
+        Ok(k) if !k.is_empty() => k,
+        Ok(_) => {
+            report.fail("public_key", tr("doctor-no-identity"), Vec::new());
+            return;
+        }
         Err(err) => {
-            print_error(format!("{}{}", "Error getting public key: \n".red(), err));
+            report.fail(
+                "public_key",
+                tr_args("public-key-failed", &[("error", err.into())]),
+                Vec::new(),
+            );
             return;
         }
     };
 
     // Get public keys from config
-    let mut found = false;
+    let mut recipients = Vec::new();
     let mut rules_without_age = Vec::new();
-
-    // Check single keys in creation rules and collect rules without age keys
     for (i, rule) in config.creation_rules.iter().enumerate() {
-        let mut rule_has_keys = false;
-
-        // Check direct age key
-        if let Some(key) = &rule.age {
-            rule_has_keys = true;
-            if derived_public_key == *key {
-                print_success(format!("{} {}", "Found matching public key:".green(), key));
-                found = true;
-                break;
-            }
+        let keys = rule_recipients(rule);
+        // If this rule has no age keys at all, record it
+        if keys.is_empty() {
+            rules_without_age.push(i);
         }
+        recipients.extend(keys);
+    }
+    let matching: Vec<&String> = identities
+        .iter()
+        .filter(|key| recipients.contains(key))
+        .collect();
 
-        // Check key groups
-        for key_group in &rule.key_groups {
-            if !key_group.age.is_empty() {
-                rule_has_keys = true;
-                for key in &key_group.age {
-                    if derived_public_key == *key {
-                        print_success(format!(
-                            "{} {}",
-                            "Found matching public key in .sops.yaml:".green(),
-                            key
-                        ));
-                        found = true;
-                        break;
-                    }
+    match matching.as_slice() {
+        [] => {
+            let mut details = match identities.as_slice() {
+                [key] => vec![tr_args(
+                    "doctor-your-public-key",
+                    &[("key", with_fingerprint(key).into())],
+                )],
+                keys => {
+                    let mut details = vec![tr("doctor-your-public-keys")];
+                    details.extend(
+                        keys.iter()
+                            .map(|key| format!("- {}", with_fingerprint(key))),
+                    );
+                    details
+                }
+            };
+            if !rules_without_age.is_empty() {
+                details.push(tr("doctor-rules-without-age"));
+                for i in rules_without_age {
+                    let path_regex = match &config.creation_rules[i].path_regex {
+                        Some(regex) => regex.as_str(),
+                        None => "<no path_regex>",
+                    };
+                    details.push(tr_args(
+                        "doctor-rule-item",
+                        &[("number", i.into()), ("pattern", path_regex.into())],
+                    ));
                 }
             }
-            if found {
-                break;
-            }
+            report.fail("public_key", tr("doctor-no-matching-key"), details);
         }
+        [key] if identities.len() == 1 => report.pass(
+            "public_key",
+            tr_args(
+                "doctor-matching-key",
+                &[("key", with_fingerprint(key).into())],
+            ),
+        ),
+        keys => report.pass(
+            "public_key",
+            tr_args(
+                "doctor-matching-keys",
+                &[
+                    ("count", keys.len().into()),
+                    ("total", identities.len().into()),
+                    (
+                        "keys",
+                        keys.iter()
+                            .map(|key| with_fingerprint(key))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                            .into(),
+                    ),
+                ],
+            ),
+        ),
+    }
+    let derived_public_key = matching.first().copied().unwrap_or(&identities[0]);
 
-        // If this rule has no age keys at all, record it
-        if !rule_has_keys {
-            rules_without_age.push(i);
-        }
+    if org {
+        check_key_reuse(report, derived_public_key, context);
+    }
+    check_committed_keys(report, age.expose_secret(), context);
+}
 
-        if found {
-            break;
-        }
+/// Warns about rules whose `path_regex` is an unescaped file path, listing the
+/// other files they accidentally cover
+fn check_rule_patterns(report: &mut CheckReport, rules: &[CreationRule], context: &GlobalContext) {
+    let suggestions: Vec<_> = rules
+        .iter()
+        .enumerate()
+        .filter_map(|(i, rule)| {
+            let path_regex = rule.path_regex.as_deref()?;
+            unescaped_path_suggestion(path_regex).map(|exact| (i, path_regex, exact))
+        })
+        .collect();
+    if suggestions.is_empty() {
+        report.pass("rule_patterns", tr("doctor-patterns-ok"));
+        return;
     }
 
-    if !found {
-        print_error(format!(
-            "{}",
-            "No matching public key found in .sops.yaml config.".red()
+    let files: Vec<String> = match config_dir(context) {
+        Some(root) => walk_files(&root)
+            .iter()
+            .map(|path| relative_path(&root, path))
+            .collect(),
+        None => Vec::new(),
+    };
+    let matcher = RuleMatcher::new(rules);
+
+    for (i, path_regex, exact) in suggestions {
+        let literal = path_regex.trim_start_matches("./");
+        let mut details: Vec<String> = files
+            .iter()
+            .filter(|f| *f != literal && matcher.matches(i, f) == Some(true))
+            .map(|file| tr_args("doctor-also-matches", &[("path", file.as_str().into())]))
+            .collect();
+        details.push(tr_args(
+            "doctor-exact-match",
+            &[("pattern", exact.as_str().into())],
         ));
-        print_warning(format!(
-            "{}",
-            format!("  Your public key is: {}", derived_public_key).yellow()
+        report.warn(
+            "rule_patterns",
+            tr_args(
+                "doctor-plain-path",
+                &[("number", (i + 1).into()), ("pattern", path_regex.into())],
+            ),
+            details,
+        );
+    }
+}
+
+/// Fails when rules don't encrypt to the escrow recipient from .opsops.yaml
+fn check_escrow(report: &mut CheckReport, rules: &[CreationRule], context: &GlobalContext) {
+    let Some(escrow) = escrow_recipient(context) else {
+        return;
+    };
+    if let Err(err) = validate_age_recipients(&escrow) {
+        report.fail(
+            "escrow",
+            tr_args("doctor-invalid-escrow", &[("error", err.into())]),
+            Vec::new(),
+        );
+        return;
+    }
+
+    let missing = rules_missing_escrow(rules, &escrow);
+    if missing.is_empty() {
+        report.pass(
+            "escrow",
+            tr_args(
+                "doctor-escrow-ok",
+                &[("key", with_fingerprint(&escrow).into())],
+            ),
+        );
+        return;
+    }
+    let details = missing
+        .into_iter()
+        .map(|i| {
+            tr_args(
+                "doctor-rule-item",
+                &[
+                    ("number", (i + 1).into()),
+                    (
+                        "pattern",
+                        rules[i]
+                            .path_regex
+                            .as_deref()
+                            .unwrap_or("<no path_regex>")
+                            .into(),
+                    ),
+                ],
+            )
+        })
+        .collect();
+    report.fail(
+        "escrow",
+        tr_args(
+            "doctor-escrow-missing",
+            &[("key", with_fingerprint(&escrow).into())],
+        ),
+        details,
+    );
+}
+
+/// Warns about recipients past their `recipient_expiry` date that rules or
+/// encrypted files still grant access
+fn check_expired_recipients(
+    report: &mut CheckReport,
+    rules: &[CreationRule],
+    context: &GlobalContext,
+) {
+    let expired = match expired_recipients(context) {
+        Ok(expired) => expired,
+        Err(err) => {
+            report.warn(
+                "recipient_expiry",
+                tr_args("doctor-expiry-read-failed", &[("error", err.into())]),
+                Vec::new(),
+            );
+            return;
+        }
+    };
+    let Some(root) = config_dir(context).filter(|_| !expired.is_empty()) else {
+        return;
+    };
+
+    let mut details = Vec::new();
+    for entry in &expired {
+        let access = remaining_access(&entry.recipient, rules, &root);
+        if access.is_empty() {
+            continue;
+        }
+        details.push(tr_args(
+            "doctor-expired-item",
+            &[
+                ("recipient", entry.describe().into()),
+                ("date", entry.expires.to_string().into()),
+                ("rules", access.rules.len().into()),
+                ("files", access.files.len().into()),
+            ],
         ));
+    }
+    if details.is_empty() {
+        report.pass("recipient_expiry", tr("expired-no-access"));
+        return;
+    }
+    details.push(tr("doctor-expire-sweep"));
+    report.warn("recipient_expiry", tr("doctor-expired-access"), details);
+}
 
-        // Print rules without age keys
-        if !rules_without_age.is_empty() {
-            print_warning(format!("{}", "  Rules without age keys:".yellow()));
-            for i in rules_without_age {
-                let path_regex = match &config.creation_rules[i].path_regex {
-                    Some(regex) => regex.as_str(),
-                    None => "<no path_regex>",
-                };
-                eprintln!("  - Rule #{}: {}", i, path_regex);
-            }
+/// Fails on `destination_rules` that `sops publish` can't use
+fn check_destinations(report: &mut CheckReport, rules: &[DestinationRule]) {
+    if rules.is_empty() {
+        return;
+    }
+    let problems = destination_problems(rules);
+    if problems.is_empty() {
+        report.pass(
+            "destinations",
+            tr_args("doctor-destinations-ok", &[("count", rules.len().into())]),
+        );
+    } else {
+        report.fail("destinations", tr("doctor-invalid-destinations"), problems);
+    }
+}
+
+/// Warns when the key of this project also protects unrelated projects in the
+/// key registry, and records this project in it
+fn check_key_reuse(report: &mut CheckReport, public_key: &str, context: &GlobalContext) {
+    let Some(root) = find_project_root(context) else {
+        return;
+    };
+    let project = project_id(&root);
+    let mut registry = match read_key_registry() {
+        Ok(r) => r,
+        Err(e) => {
+            report.warn("key_reuse", e, Vec::new());
+            return;
+        }
+    };
+
+    if !context.read_only && registry.projects.get(&project).map(String::as_str) != Some(public_key)
+    {
+        registry
+            .projects
+            .insert(project.clone(), public_key.to_string());
+        match write_key_registry(&registry) {
+            Ok(path) => print_info(tr_args(
+                "doctor-recorded-key",
+                &[
+                    ("project", project.as_str().into()),
+                    ("path", path.display().to_string().into()),
+                ],
+            )),
+            Err(e) => report.warn("key_reuse", e, Vec::new()),
+        }
+    }
+
+    let sharing = registry.projects_sharing(&project, public_key);
+    if sharing.is_empty() {
+        report.pass("key_reuse", tr("doctor-key-unique"));
+        return;
+    }
+    let mut details: Vec<String> = sharing.iter().map(|p| format!("- {}", p)).collect();
+    details.push(tr("doctor-leaked-key"));
+    report.warn(
+        "key_reuse",
+        tr_args("doctor-key-shared", &[("count", sharing.len().into())]),
+        details,
+    );
+}
+
+/// Searches tracked files and recent git history for committed private keys
+fn check_committed_keys(report: &mut CheckReport, age_key: &str, context: &GlobalContext) {
+    let repo = match find_project_root(context).and_then(|root| Repository::open(root).ok()) {
+        Some(repo) => repo,
+        // Not a git repository, nothing to scan
+        None => return,
+    };
+
+    let findings = match scan_repository(&repo, Some(age_key), HISTORY_DEPTH) {
+        Ok(f) => f,
+        Err(err) => {
+            report.warn(
+                "committed_keys",
+                tr_args("doctor-scan-failed", &[("error", err.to_string().into())]),
+                Vec::new(),
+            );
+            return;
         }
+    };
+
+    if findings.is_empty() {
+        report.pass(
+            "committed_keys",
+            tr_args(
+                "doctor-no-committed-keys",
+                &[("count", HISTORY_DEPTH.into())],
+            ),
+        );
+        return;
     }
+
+    let mut details: Vec<String> = findings
+        .iter()
+        .map(|finding| match finding.commit {
+            Some(commit) => tr_args(
+                "doctor-finding-in-commit",
+                &[
+                    ("path", finding.path.as_str().into()),
+                    ("kind", finding.kind.to_string().into()),
+                    ("commit", commit.to_string()[..8].to_string().into()),
+                ],
+            ),
+            None => tr_args(
+                "doctor-finding",
+                &[
+                    ("path", finding.path.as_str().into()),
+                    ("kind", finding.kind.to_string().into()),
+                ],
+            ),
+        })
+        .collect();
+    details.extend(
+        [
+            "doctor-remediate",
+            "doctor-remediate-rotate",
+            "doctor-remediate-history",
+            "doctor-remediate-reclone",
+        ]
+        .map(tr),
+    );
+    report.fail("committed_keys", tr("doctor-committed-keys"), details);
 }
@@ -0,0 +1,186 @@
+use colored::Colorize;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    GlobalContext,
+    util::{
+        bulk::run_resumable,
+        config_include::load_effective_config,
+        drift::{Drift, file_drift},
+        i18n::{failed_to, tr, tr_args},
+        journal::Journal,
+        notifications::{NotifyEvent, notify},
+        op_key::get_age_key_from_1password,
+        print_status::{print_error, print_info, print_success},
+        rule_match::{first_matching_rule, project_relative_path, relative_path},
+        sops_command::SopsCommandBuilder,
+        sops_config::config_dir,
+        sops_files::{find_encrypted_files, is_sops_encrypted_file},
+        sops_metadata::{file_recipients, read_metadata},
+    },
+};
+
+/// Lists encrypted files whose recipients or encrypted keys no longer match
+/// the creation rule covering them. With `fix`, runs `sops updatekeys` on the
+/// files where that resolves the drift, resuming an interrupted earlier run.
+pub fn drift(paths: Vec<OsString>, fix: bool, context: &GlobalContext) {
+    let Some(root) = config_dir(context) else {
+        print_error(tr("no-sops-config"));
+        std::process::exit(1);
+    };
+    let rules = match load_effective_config(context) {
+        Ok(c) => c.config.creation_rules,
+        Err(e) => {
+            print_error(format!("{} {}", tr("sops-config-error").red(), e));
+            std::process::exit(1);
+        }
+    };
+
+    let files: Vec<PathBuf> = if paths.is_empty() {
+        find_encrypted_files(&root)
+    } else {
+        paths
+            .into_iter()
+            .map(PathBuf::from)
+            .filter(|p| is_sops_encrypted_file(p))
+            .collect()
+    };
+    if files.is_empty() {
+        print_info(tr("no-encrypted-files"));
+        return;
+    }
+
+    let check = |file: &Path| {
+        let contents =
+            std::fs::read_to_string(file).map_err(|e| failed_to("read-failed", file, e))?;
+        let rule = project_relative_path(&root, file)
+            .and_then(|relative| first_matching_rule(&rules, &relative))
+            .map(|index| &rules[index]);
+        let metadata = read_metadata(&contents).unwrap_or_default();
+        Ok::<_, String>(file_drift(&metadata, rule))
+    };
+
+    let mut drifted = Vec::new();
+    for file in &files {
+        let drift = match check(file) {
+            Ok(drift) => drift,
+            Err(e) => {
+                print_error(e.red());
+                std::process::exit(1);
+            }
+        };
+
+        let name = relative_path(&root, file);
+        if drift.is_empty() {
+            println!("{} {}", "✓".green(), name);
+            continue;
+        }
+        println!("{} {}", "✗".red(), name);
+        for d in &drift {
+            println!("    {}", d);
+        }
+        drifted.push((file, drift));
+    }
+
+    if drifted.is_empty() {
+        if fix {
+            // Nothing left of an interrupted run
+            Journal::open(&root, "drift-fix", "updatekeys").finish();
+        }
+        print_success(tr_args("drift-none", &[("count", files.len().into())]));
+        return;
+    }
+
+    println!();
+    let mut unresolved = 0;
+    let mut to_update = Vec::new();
+    for (file, drift) in &drifted {
+        let name = relative_path(&root, file);
+        let updatekeys = drift.iter().any(Drift::fixed_by_updatekeys);
+        if updatekeys && fix {
+            to_update.push(file.to_path_buf());
+        } else if updatekeys {
+            println!("  sops updatekeys {}", name);
+        }
+
+        if drift.contains(&Drift::NoRule) {
+            println!("  {}: add a creation rule covering it or delete it", name);
+        } else if drift.iter().any(|d| !d.fixed_by_updatekeys()) {
+            println!(
+                "  {}: decrypt and encrypt it again to apply the rule's key selection",
+                name
+            );
+        }
+        if !fix || drift.iter().any(|d| !d.fixed_by_updatekeys()) {
+            unresolved += 1;
+        }
+    }
+
+    if !to_update.is_empty() {
+        // Only asks 1Password if anything is left to update
+        let mut age_key = None;
+        let report = run_resumable(
+            &root,
+            &to_update,
+            "rekey",
+            Journal::open(&root, "drift-fix", "updatekeys"),
+            |file| match check(file)?.iter().find(|d| d.fixed_by_updatekeys()) {
+                Some(d) => Err(d.to_string()),
+                None => Ok(()),
+            },
+            |file| {
+                let key =
+                    age_key.get_or_insert_with(|| match get_age_key_from_1password(context) {
+                        Ok(key) => key,
+                        Err(e) => {
+                            print_error(format!("{} {}", tr("age-key-failed").red(), e));
+                            std::process::exit(1);
+                        }
+                    });
+                let status = SopsCommandBuilder::new(context)
+                    .arg("updatekeys")
+                    .arg("--yes")
+                    .arg_path(file)
+                    .with_age_key_value(key)
+                    .status()
+                    .map_err(|e| format!("{} {}", tr("sops-launch-failed"), e))?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(tr_args(
+                        "sops-exited",
+                        &[("status", status.to_string().into())],
+                    ))
+                }
+            },
+        );
+        report.print_summary();
+        unresolved += report.failed();
+        if report.succeeded() > 0 {
+            let recipients: Vec<String> = to_update
+                .iter()
+                .filter_map(|file| std::fs::read_to_string(file).ok())
+                .flat_map(|contents| file_recipients(&contents))
+                .collect();
+            notify(
+                NotifyEvent::Rekey,
+                &root,
+                report.succeeded(),
+                &recipients,
+                context,
+            );
+        }
+    }
+
+    if unresolved > 0 {
+        print_error(
+            tr_args(
+                "drift-unresolved",
+                &[("count", unresolved.into()), ("total", files.len().into())],
+            )
+            .red(),
+        );
+        std::process::exit(1);
+    }
+}
@@ -1,24 +1,56 @@
 use crate::GlobalContext;
-use crate::util::print_status::{print_error, print_info, print_success};
-use crate::util::sops_command::SopsCommandBuilder;
+use crate::util::advice::advise_sops_failure;
+use crate::util::backups::backup_or_exit;
+use crate::util::editor::{EditorSettings, editor_settings};
+use crate::util::file_lock::lock_file;
+use crate::util::file_picker::pick_file;
+use crate::util::file_scaffold::scaffold;
+use crate::util::i18n::{failed_to, tr, tr_args};
+use crate::util::json_schema::{SchemaIndex, validate_content};
+use crate::util::markdown::{decrypt_note, encrypt_note, is_markdown};
+use crate::util::print_status::{print_error, print_info, print_success, print_warning};
+use crate::util::session_record;
+use crate::util::sops_command::{SopsCommandBuilder, decrypt_in_memory, run_sops_on_buffer};
+use crate::util::sops_files::sops_file_type;
 use crate::util::sops_status::is_file_unchanged_status;
 use colored::Colorize;
 use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use zeroize::Zeroize;
+
+/// Entry point for the `edit` command. With `create`, a path that doesn't
+/// exist yet is started from [`scaffold`] and encrypted when saved.
+pub fn edit(
+    path: Option<OsString>,
+    create: bool,
+    force: bool,
+    tmpdir: Option<String>,
+    sandbox: bool,
+    context: &GlobalContext,
+) {
+    // Without a path, let the user pick one of the files matched by a rule
+    let path = path.unwrap_or_else(|| pick_file(context, "edit"));
 
-/// Entry point for the `edit` command.
-pub fn edit(path: OsString, context: &GlobalContext) {
     // Convert the path from OsString to String
     let path_str = match path.into_string() {
         Ok(p) => p,
         Err(os) => {
-            print_error(format!("{} {:?}", "Invalid UTF-8 in path:".red(), os));
+            print_error(format!("{} {:?}", tr("invalid-utf8-path").red(), os));
             std::process::exit(1);
         }
     };
 
-    // Check if the file exists
-    if !std::path::Path::new(&path_str).is_file() {
-        print_error(format!("{} {}", "File not found:".red(), path_str));
+    // Check if the file exists, or may be created
+    let creating = create && !Path::new(&path_str).exists();
+    if !creating && !Path::new(&path_str).is_file() {
+        print_error(format!(
+            "{} {} {}",
+            tr("file-not-found").red(),
+            path_str,
+            tr("edit-create-hint").dimmed()
+        ));
         std::process::exit(1);
     }
 
@@ -26,22 +58,89 @@ pub fn edit(path: OsString, context: &GlobalContext) {
     if which::which("sops").is_err() {
         print_error(format!(
             "{} {}",
-            "'sops' is not installed or not in PATH.".red(),
-            "Please install it first.".dimmed()
+            tr("sops-not-installed").red(),
+            tr("install-it-first").dimmed()
         ));
         std::process::exit(1);
     }
 
-    println!("{} {}", "📝 Opening file for editing:".green(), path_str);
+    // Where the plaintext goes and what may access it while editing
+    let settings = match editor_settings(tmpdir, sandbox) {
+        Ok(s) => s,
+        Err(e) => {
+            print_error(format!("{}", e.red()));
+            std::process::exit(1);
+        }
+    };
+
+    // What a new file starts with, its directories have to exist for the lock
+    let plaintext = creating
+        .then(|| start_new_file(&path_str, context))
+        .flatten();
+
+    // Keep other opsops processes from writing the file at the same time
+    let _lock = match lock_file(Path::new(&path_str), force) {
+        Ok((lock, warning)) => {
+            if let Some(warning) = warning {
+                print_warning(format!("{} (--force)", warning));
+            }
+            lock
+        }
+        Err(e) => {
+            print_error(format!("{}", e.red()));
+            std::process::exit(1);
+        }
+    };
+
+    if creating {
+        println!("{} {}", tr("edit-creating").green(), path_str);
+    } else {
+        // sops rewrites the file in place, keep the ciphertext for `opsops restore`
+        backup_or_exit(Path::new(&path_str), context);
+        println!("{} {}", tr("edit-opening").green(), path_str);
+    }
+
+    if is_markdown(Path::new(&path_str)) {
+        edit_note(&path_str, &settings, plaintext, context);
+        return;
+    }
+
+    // sops only creates files from its own example, so encrypt the scaffold first
+    let scaffolded = plaintext.is_some();
+    if let Some(mut plaintext) = plaintext {
+        let result = run_sops_on_buffer(
+            "--encrypt",
+            sops_file_type(Path::new(&path_str)),
+            Path::new(&path_str),
+            &plaintext,
+            context,
+        )
+        .and_then(|ciphertext| {
+            fs::write(&path_str, ciphertext)
+                .map_err(|e| failed_to("write-failed", Path::new(&path_str), e))
+        });
+        plaintext.zeroize();
+        if let Err(e) = result {
+            print_error(format!("{} {}", tr("edit-create-failed").red(), e));
+            std::process::exit(1);
+        }
+    }
+    // Nothing was saved, so the file shouldn't have been created either
+    let discard_scaffold = || {
+        if scaffolded {
+            let _ = fs::remove_file(&path_str);
+        }
+    };
 
     // Create a SOPS command with the Age key from 1Password
-    let sops_command = match SopsCommandBuilder::new(context)
-        .arg(&path_str)
-        .with_age_key()
-    {
+    let mut builder = SopsCommandBuilder::new(context).env("SOPS_EDITOR", &settings.editor);
+    if let Some(tmpdir) = &settings.tmpdir {
+        builder = builder.env("TMPDIR", tmpdir);
+    }
+    let sops_command = match builder.arg_path(&path_str).with_age_key() {
         Ok(cmd) => cmd,
         Err(e) => {
-            print_error(format!("{} {}", "Failed to get Age key:".red(), e));
+            print_error(format!("{} {}", tr("age-key-failed").red(), e));
             std::process::exit(1);
         }
     };
@@ -49,22 +148,186 @@ pub fn edit(path: OsString, context: &GlobalContext) {
     // Run the command
     match sops_command.status() {
         Ok(status) if status.success() => {
-            print_success(format!("{}", "File edited and saved successfully.".green()));
+            print_success(format!("{}", tr("edit-saved").green()));
+            check_schema_after_edit(&path_str, context);
         }
         Ok(status) if is_file_unchanged_status(&status) => {
-            print_info(format!("{}", "File has not changed.".blue()));
+            discard_scaffold();
+            if creating {
+                print_info(format!("{} {}", tr("edit-not-created").blue(), path_str));
+            } else {
+                print_info(format!("{}", tr("file-unchanged").blue()));
+            }
         }
         Ok(status) => {
+            discard_scaffold();
             print_error(format!(
-                "{} Exit code: {}",
-                "Error while editing the file.".red(),
-                status
+                "{} {}",
+                tr("edit-failed").red(),
+                tr_args("exit-code", &[("status", status.to_string().into())])
             ));
+            advise_sops_failure(&status, Path::new(&path_str), context);
             std::process::exit(status.code().unwrap_or(1));
         }
         Err(e) => {
-            print_error(format!("{} {:?}", "Failed to launch sops:".red(), e));
+            discard_scaffold();
+            print_error(format!("{} {:?}", tr("sops-launch-failed").red(), e));
             std::process::exit(1);
         }
     }
 }
+
+/// Creates the directories of a new file and returns what to start it with,
+/// `None` to leave that to sops
+fn start_new_file(path: &str, context: &GlobalContext) -> Option<String> {
+    let fail = |message: String| -> ! {
+        print_error(format!("{} {}", tr("edit-create-failed").red(), message));
+        std::process::exit(1);
+    };
+    if let Some(dir) = Path::new(path)
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        && let Err(e) = fs::create_dir_all(dir)
+    {
+        fail(failed_to("create-failed", dir, e));
+    }
+    scaffold(Path::new(path), context).unwrap_or_else(|e| fail(e))
+}
+
+/// Reports if the saved file doesn't match the schema of its rule. sops has
+/// already written it, so all that's left is to fail loudly.
+fn check_schema_after_edit(path: &str, context: &GlobalContext) {
+    let schema = match SchemaIndex::load(context) {
+        Ok(index) => index.and_then(|i| i.schema_for(Path::new(path))),
+        Err(e) => {
+            print_error(format!("{} {}", tr("schema-validate-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let Some(schema) = schema else {
+        return;
+    };
+    let content = match decrypt_in_memory(Path::new(path), None, context) {
+        Ok(plaintext) => plaintext,
+        Err(e) => {
+            print_warning(tr_args(
+                "edit-schema-decrypt-failed",
+                &[("path", path.into()), ("error", e.to_string().into())],
+            ));
+            return;
+        }
+    };
+    let result = validate_content(&schema, Path::new(path), &content);
+    drop(content);
+    match result {
+        Ok(errors) if !errors.is_empty() => {
+            print_error(format!(
+                "{} {}",
+                tr_args(
+                    "edit-schema-mismatch",
+                    &[
+                        ("path", path.into()),
+                        ("schema", schema.display().to_string().into()),
+                    ],
+                )
+                .red(),
+                tr("edit-again").dimmed()
+            ));
+            for error in errors {
+                eprintln!("  {}", error);
+            }
+            std::process::exit(1);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            print_error(format!("{} {}", tr("schema-validate-failed").red(), e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// sops can't edit notes, so decrypt them to a private temporary file, open
+/// the editor sops would use and encrypt the result if it changed. A new note
+/// starts from `scaffold` instead.
+fn edit_note(
+    path: &str,
+    settings: &EditorSettings,
+    scaffold: Option<String>,
+    context: &GlobalContext,
+) {
+    let exit_with = |e: String| -> ! {
+        print_error(format!("{} {}", tr("edit-note-failed").red(), e));
+        std::process::exit(1);
+    };
+
+    let creating = scaffold.is_some();
+    let read = || {
+        fs::read_to_string(path)
+            .map_err(|e| failed_to("read-failed", Path::new(&path), e))
+            .and_then(|contents| decrypt_note(&contents, Path::new(path), context))
+    };
+    let mut plaintext = match scaffold.map_or_else(read, Ok) {
+        Ok(note) => note,
+        Err(e) => exit_with(e),
+    };
+    // Created with mode 0600, and removed when dropped
+    let mut temp = tempfile::Builder::new();
+    temp.prefix("opsops-").suffix(".md");
+    let temp = match settings
+        .tmpdir
+        .as_ref()
+        .map_or_else(|| temp.tempfile(), |dir| temp.tempfile_in(dir))
+        .and_then(|temp| fs::write(temp.path(), &plaintext).map(|_| temp))
+    {
+        Ok(temp) => temp,
+        Err(e) => exit_with(tr_args(
+            "temp-file-failed",
+            &[("error", e.to_string().into())],
+        )),
+    };
+
+    let editor = &settings.editor;
+    let mut words = editor.split_whitespace();
+    let status = session_record::status(
+        Command::new(words.next().unwrap_or("vim"))
+            .args(words)
+            .arg(temp.path()),
+    );
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => exit_with(tr_args(
+            "edit-editor-exited",
+            &[("status", status.to_string().into())],
+        )),
+        Err(e) => exit_with(failed_to("start-failed", Path::new(editor), e)),
+    }
+
+    let mut edited = match fs::read_to_string(temp.path()) {
+        Ok(edited) => edited,
+        Err(e) => exit_with(tr_args(
+            "edit-read-note-failed",
+            &[("error", e.to_string().into())],
+        )),
+    };
+    let unchanged = edited == plaintext;
+    plaintext.zeroize();
+    let result = if unchanged {
+        Ok(false)
+    } else {
+        encrypt_note(&edited, Path::new(path), context).and_then(|note| {
+            fs::write(path, note)
+                .map(|_| true)
+                .map_err(|e| failed_to("write-failed", Path::new(&path), e))
+        })
+    };
+    edited.zeroize();
+    // Overwrite the plaintext before the temporary file is removed
+    let _ = fs::write(temp.path(), "");
+
+    match result {
+        Ok(true) => print_success(format!("{}", tr("edit-saved").green())),
+        Ok(false) if creating => print_info(format!("{} {}", tr("edit-not-created").blue(), path)),
+        Ok(false) => print_info(format!("{}", tr("file-unchanged").blue())),
+        Err(e) => exit_with(e),
+    }
+}
@@ -1,53 +1,189 @@
 use crate::GlobalContext;
-use crate::util::print_status::{print_error, print_info, print_success};
-use crate::util::sops_command::SopsCommandBuilder;
+use crate::util::advice::advise_sops_failure;
+use crate::util::backups::backup_or_exit;
+use crate::util::decrypted_copies::{KeyChange, changed_source, forget, structural_diff};
+use crate::util::document::parse_document;
+use crate::util::encrypted_keys::{EncryptionState, compare_encryption, encryption_state};
+use crate::util::escrow::missing_escrow_reason;
+use crate::util::file_lock::lock_file;
+use crate::util::file_picker::pick_file;
+use crate::util::find_project_root::find_project_root;
+use crate::util::formatting::{Formatting, resolve_formatting};
+use crate::util::guardrails::{Severity, check_guardrails};
+use crate::util::i18n::{failed_to, tr, tr_args};
+use crate::util::json_schema::check_rule_schema;
+use crate::util::markdown::{encrypt_note, is_markdown};
+use crate::util::print_status::{print_error, print_info, print_success, print_warning};
+use crate::util::prompts::select;
+use crate::util::protected_files::protected_reason;
+use crate::util::sops_command::{SopsCommandBuilder, decrypt_in_memory};
+use crate::util::sops_files::{is_sops_encrypted_file, sops_file_type};
 use crate::util::sops_status::is_file_unchanged_status;
 use colored::Colorize;
 use std::ffi::OsString;
 use std::path::Path;
+use zeroize::Zeroize;
+
+/// Encrypts a file using SOPS with the Age key from 1Password. `diff` prints
+/// which keys were newly encrypted, which the rule left in plaintext and
+/// whether the data key was reused. `formatting` holds `--indent`.
+pub fn encrypt(
+    path: Option<OsString>,
+    force: bool,
+    allow_protected: bool,
+    diff: bool,
+    formatting: Formatting,
+    context: &GlobalContext,
+) {
+    // Without a path, let the user pick one of the files matched by a rule
+    let path = path.unwrap_or_else(|| pick_file(context, "encrypt"));
 
-/// Encrypts a file using SOPS with the Age key from 1Password
-pub fn encrypt(path: OsString, context: &GlobalContext) {
     // Convert the path from OsString to String
     let path_str = match path.into_string() {
         Ok(p) => p,
         Err(os) => {
-            print_error(format!("{} {:?}", "Invalid UTF-8 in path:".red(), os));
+            print_error(format!("{} {:?}", tr("invalid-utf8-path").red(), os));
             std::process::exit(1);
         }
     };
 
     // Check if the file exists
     if !Path::new(&path_str).is_file() {
-        print_error(format!("{} {}", "File not found:".red(), path_str));
+        print_error(format!("{} {}", tr("file-not-found").red(), path_str));
+        std::process::exit(1);
+    }
+
+    // Encrypting the config or git's own files would brick the project
+    if let Some(reason) = protected_reason(Path::new(&path_str), context) {
+        if !allow_protected {
+            print_error(format!(
+                "{} {}: {}. {}",
+                tr("refusing-to-encrypt").red(),
+                path_str,
+                reason,
+                tr("allow-protected-hint").dimmed()
+            ));
+            std::process::exit(1);
+        }
+        print_warning(tr_args(
+            "encrypting-protected",
+            &[
+                ("path", path_str.as_str().into()),
+                ("reason", reason.into()),
+            ],
+        ));
+    }
+
+    // Files must stay recoverable by the escrow identity, no override for that
+    if let Some(reason) = missing_escrow_reason(Path::new(&path_str), context) {
+        print_error(format!(
+            "{} {}: {}. {}",
+            tr("refusing-to-encrypt").red(),
+            path_str,
+            reason,
+            tr("add-escrow-hint").dimmed()
+        ));
         std::process::exit(1);
     }
 
+    // Catch structural mistakes, like missing secrets, before they get deployed
+    if !is_markdown(Path::new(&path_str)) && !is_sops_encrypted_file(Path::new(&path_str)) {
+        check_guardrails_or_exit(&path_str, context);
+        check_schema_or_exit(&path_str, context);
+    }
+
     // Ensure sops is installed
     if which::which("sops").is_err() {
         print_error(format!(
             "{} {}",
-            "'sops' is not installed or not in PATH.".red(),
-            "Please install it first.".dimmed()
+            tr("sops-not-installed").red(),
+            tr("install-it-first").dimmed()
         ));
         std::process::exit(1);
     }
 
+    // Keep other opsops processes from writing the file at the same time
+    let _lock = match lock_file(Path::new(&path_str), force) {
+        Ok((lock, warning)) => {
+            if let Some(warning) = warning {
+                print_warning(format!("{} (--force)", warning));
+            }
+            lock
+        }
+        Err(e) => {
+            print_error(format!("{}", e.red()));
+            std::process::exit(1);
+        }
+    };
+
+    // Warn before clobbering upstream changes to the encrypted original of this copy
+    let root = find_project_root(context);
+    if let Some(root) = &root
+        && let Some(source) = changed_source(root, Path::new(&path_str))
+        && !confirm_stale_copy(&path_str, &source, context)
+    {
+        print_info(tr("stale-copy-aborted"));
+        std::process::exit(1);
+    }
+
     let output_path = path_str.to_string();
 
-    print_info(format!("{} {}", "🔐 Encrypting to".green(), path_str));
+    // Encrypting in place replaces the file, keep it if it already was ciphertext
+    backup_or_exit(Path::new(&output_path), context);
+
+    print_info(format!("{} {}", tr("encrypting-to").green(), path_str));
+
+    // Notes keep their prose readable, only front matter and secret blocks are encrypted
+    if is_markdown(Path::new(&path_str)) {
+        let result = std::fs::read_to_string(&path_str)
+            .map_err(|e| failed_to("read-failed", Path::new(&path_str), e))
+            .and_then(|contents| encrypt_note(&contents, Path::new(&path_str), context))
+            .and_then(|note| {
+                std::fs::write(&output_path, note)
+                    .map_err(|e| failed_to("write-failed", Path::new(&output_path), e))
+            });
+        if let Err(e) = result {
+            print_error(format!("{} {}", tr("encrypt-note-failed").red(), e));
+            std::process::exit(1);
+        }
+        print_success(tr("encrypted-note").green());
+        if diff {
+            print_warning(tr("encrypt-diff-notes"));
+        }
+        if let Some(root) = &root
+            && let Err(e) = forget(root, Path::new(&path_str))
+        {
+            print_warning(tr_args(
+                "tracking-failed",
+                &[("error", e.to_string().into())],
+            ));
+        }
+        return;
+    }
+
+    // Only the shape of the input is kept for the summary, never its values
+    let before = diff.then(|| read_encryption_state(Path::new(&path_str)));
+
+    let formatting = match resolve_formatting(&formatting, context) {
+        Ok(formatting) => formatting,
+        Err(e) => {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    };
 
     // Create a SOPS command with the Age key from 1Password
     let sops_command = match SopsCommandBuilder::new(context)
         .arg("--encrypt")
+        .args(formatting.sops_args(sops_file_type(Path::new(&path_str))))
         .arg("--output")
-        .arg(&output_path)
-        .arg(&path_str)
+        .arg_path(&output_path)
+        .arg_path(&path_str)
         .with_age_key()
     {
         Ok(cmd) => cmd,
         Err(e) => {
-            print_error(format!("{} {}", "Failed to get Age key:".red(), e));
+            print_error(format!("{} {}", tr("age-key-failed").red(), e));
             std::process::exit(1);
         }
     };
@@ -55,29 +191,204 @@ pub fn encrypt(path: OsString, context: &GlobalContext) {
     // Run the command
     match sops_command.status() {
         Ok(status) if status.success() => {
-            print_success(format!(
-                "{}",
-                "Successfully encrypted file to with SOPS".green()
-            ));
+            print_success(tr("encrypted-file").green());
+            if let Some(before) = &before {
+                print_encryption_summary(before, Path::new(&output_path));
+            }
+            if let Some(root) = &root
+                && let Err(e) = forget(root, Path::new(&path_str))
+            {
+                print_warning(tr_args(
+                    "tracking-failed",
+                    &[("error", e.to_string().into())],
+                ));
+            }
         }
         Ok(status) if is_file_unchanged_status(&status) => {
             print_info(format!(
                 "{} {}",
-                "ℹ️ File has not changed.".blue(),
+                format!("ℹ️ {}", tr("file-unchanged")).blue(),
                 output_path
             ));
         }
         Ok(status) => {
             print_error(format!(
-                "{} Exit code: {}",
-                "Error while encrypting the file.".red(),
-                status
+                "{} {}",
+                tr("encrypt-failed").red(),
+                tr_args("exit-code", &[("status", status.to_string().into())])
             ));
+            advise_sops_failure(&status, Path::new(&path_str), context);
             std::process::exit(status.code().unwrap_or(1));
         }
         Err(e) => {
-            print_error(format!("{} {:?}", "Failed to launch sops:".red(), e));
+            print_error(format!("{} {:?}", tr("sops-launch-failed").red(), e));
             std::process::exit(1);
         }
     }
 }
+
+/// The encryption state of the document at `path`, see [`encryption_state`]
+fn read_encryption_state(path: &Path) -> Result<EncryptionState, String> {
+    let mut content = std::fs::read(path).map_err(|e| failed_to("read-failed", path, e))?;
+    let document = parse_document(&content, sops_file_type(path));
+    content.zeroize();
+    Ok(encryption_state(&document?))
+}
+
+/// Prints what encrypting changed, comparing `before` with the file sops wrote
+fn print_encryption_summary(before: &Result<EncryptionState, String>, output: &Path) {
+    let summary = before.clone().and_then(|before| {
+        read_encryption_state(output).map(|after| compare_encryption(&before, &after))
+    });
+    match summary {
+        Ok(summary) => summary.print(),
+        Err(e) => print_warning(tr_args(
+            "encrypt-summary-failed",
+            &[("error", e.to_string().into())],
+        )),
+    }
+}
+
+/// Warns about a file that looks like a mistake, or exits if its rule refuses those
+fn check_guardrails_or_exit(path: &str, context: &GlobalContext) {
+    let mut content = match std::fs::read(path) {
+        Ok(c) => c,
+        Err(e) => {
+            print_error(failed_to("read-failed", Path::new(&path), e));
+            std::process::exit(1);
+        }
+    };
+    let result = check_guardrails(&content, sops_file_type(Path::new(path)), context);
+    content.zeroize();
+    match result {
+        Ok((_, findings)) if findings.is_empty() => {}
+        Ok((Severity::Error, findings)) => {
+            print_error(format!(
+                "{} {}",
+                tr("refusing-to-encrypt").red(),
+                tr_args("encrypt-looks-like-mistake", &[("path", path.into())])
+            ));
+            for finding in findings {
+                eprintln!("  {}", finding);
+            }
+            eprintln!("{}", tr("encrypt-guardrails-hint").dimmed());
+            std::process::exit(1);
+        }
+        Ok((Severity::Warning, findings)) => {
+            for finding in findings {
+                print_warning(format!("{}: {}", path, finding));
+            }
+        }
+        Err(e) => {
+            print_error(format!("{}", e.red()));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Exits if the plaintext at `path` doesn't match the schema of its rule
+fn check_schema_or_exit(path: &str, context: &GlobalContext) {
+    let mut content = match std::fs::read(path) {
+        Ok(c) => c,
+        Err(e) => {
+            print_error(failed_to("read-failed", Path::new(&path), e));
+            std::process::exit(1);
+        }
+    };
+    let result = check_rule_schema(Path::new(path), &content, context);
+    content.zeroize();
+    match result {
+        Ok(Some((schema, errors))) if !errors.is_empty() => {
+            print_error(format!(
+                "{} {}",
+                tr("refusing-to-encrypt").red(),
+                tr_args(
+                    "encrypt-schema-mismatch",
+                    &[
+                        ("path", path.into()),
+                        ("schema", schema.display().to_string().into()),
+                    ],
+                )
+            ));
+            for error in errors {
+                eprintln!("  {}", error);
+            }
+            std::process::exit(1);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            print_error(format!("{} {}", tr("schema-validate-failed").red(), e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Asks what to do about a plaintext copy whose encrypted source changed since it was
+/// decrypted. Returns whether to go ahead and encrypt.
+fn confirm_stale_copy(path: &str, source: &Path, context: &GlobalContext) -> bool {
+    print_warning(
+        tr_args(
+            "encrypt-stale-copy",
+            &[
+                ("source", source.display().to_string().into()),
+                ("path", path.into()),
+            ],
+        )
+        .yellow(),
+    );
+
+    let options = [
+        tr("encrypt-show-diff"),
+        tr("encrypt-anyway"),
+        tr("encrypt-abort"),
+    ];
+    loop {
+        let selection = match select(&tr("encrypt-what-now"), &options, 0) {
+            Ok(s) => s,
+            // Not interactive, never clobber silently
+            Err(_) => return false,
+        };
+        match selection {
+            0 => show_structural_diff(path, source, context),
+            1 => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Prints which keys differ between the upstream version and the plaintext copy
+fn show_structural_diff(path: &str, source: &Path, context: &GlobalContext) {
+    let upstream = match decrypt_in_memory(source, None, context) {
+        Ok(plaintext) => plaintext,
+        Err(e) => {
+            print_error(failed_to("decrypt-path-failed", source, e));
+            return;
+        }
+    };
+
+    let parse = |bytes: &[u8]| serde_yaml::from_slice::<serde_yaml::Value>(bytes).ok();
+    let (Some(upstream), Some(local)) = (
+        parse(&upstream),
+        std::fs::read(path).ok().as_deref().and_then(parse),
+    ) else {
+        print_warning(tr("encrypt-diff-unsupported"));
+        return;
+    };
+
+    let changes = structural_diff(&upstream, &local);
+    if changes.is_empty() {
+        print_info(tr("encrypt-no-differences"));
+        return;
+    }
+    println!(
+        "{}",
+        tr_args("encrypt-would-change", &[("path", path.into())])
+    );
+    for change in changes {
+        match change {
+            KeyChange::Added(key) => println!("  {} {}", "+".green(), key),
+            KeyChange::Removed(key) => println!("  {} {}", "-".red(), key),
+            KeyChange::Changed(key) => println!("  {} {}", "~".yellow(), key),
+        }
+    }
+}
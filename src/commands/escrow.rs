@@ -0,0 +1,89 @@
+use colored::Colorize;
+use std::fs;
+
+use crate::{
+    GlobalContext,
+    util::{
+        escrow::{escrow_recipient, rules_missing_escrow},
+        file_picker::quiet_config,
+        find_project_root::find_project_root,
+        i18n::{tr, tr_args},
+        op_key::validate_age_recipients,
+        print_status::{print_error, print_success},
+        rule_match::relative_path,
+        sops_files::find_encrypted_files,
+        sops_metadata::file_recipients,
+    },
+};
+
+/// Proves every encrypted file in the project can be recovered with the escrow
+/// identity: all rules encrypt to the escrow recipient and so does the metadata
+/// of every file. Exits with 1 otherwise.
+pub fn verify(context: &GlobalContext) {
+    let Some(escrow) = escrow_recipient(context) else {
+        print_error(format!(
+            "{} {}",
+            tr("escrow-not-set").red(),
+            tr("escrow-add-key").dimmed()
+        ));
+        std::process::exit(1);
+    };
+    if let Err(e) = validate_age_recipients(&escrow) {
+        print_error(tr_args("doctor-invalid-escrow", &[("error", e.into())]).red());
+        std::process::exit(1);
+    }
+    let Some(root) = find_project_root(context) else {
+        print_error(tr("no-project-root"));
+        std::process::exit(1);
+    };
+
+    let mut unrecoverable = 0;
+
+    let rules = quiet_config(context)
+        .map(|c| c.creation_rules)
+        .unwrap_or_default();
+    for i in rules_missing_escrow(&rules, &escrow) {
+        unrecoverable += 1;
+        println!(
+            "{} {}",
+            "✗".red(),
+            tr_args(
+                "escrow-rule",
+                &[
+                    ("number", (i + 1).into()),
+                    (
+                        "pattern",
+                        rules[i]
+                            .path_regex
+                            .as_deref()
+                            .unwrap_or("<no path_regex>")
+                            .into(),
+                    ),
+                ],
+            )
+        );
+    }
+
+    for file in find_encrypted_files(&root) {
+        let recipients = fs::read_to_string(&file)
+            .map(|c| file_recipients(&c))
+            .unwrap_or_default();
+        let name = relative_path(&root, &file);
+        if recipients.contains(&escrow) {
+            println!("{} {}", "✓".green(), name);
+        } else {
+            unrecoverable += 1;
+            println!("{} {}", "✗".red(), name);
+        }
+    }
+
+    if unrecoverable > 0 {
+        print_error(format!(
+            "{} {}",
+            tr_args("escrow-unrecoverable", &[("count", unrecoverable.into())]).red(),
+            tr("escrow-add-to-rules").dimmed()
+        ));
+        std::process::exit(1);
+    }
+    print_success(format!("{} {}", tr("escrow-recoverable").green(), escrow));
+}
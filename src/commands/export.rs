@@ -0,0 +1,231 @@
+use colored::Colorize;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use zeroize::Zeroize;
+
+use crate::{
+    GlobalContext,
+    util::{
+        csv_table::{delimiter_for, render_table, yaml_to_table},
+        decrypted_copies::flatten,
+        document::{parse_document, scalar_text},
+        i18n::{failed_to, tr, tr_args},
+        op::{OpCategory, OpItem, OpItemField, item_category, op_item_create, op_item_edit},
+        output_permissions::{apply_permissions, prepare_output, resolve_permissions},
+        print_status::{print_error, print_info, print_success},
+        sops_command::decrypt_in_memory,
+        sops_files::sops_file_type,
+    },
+};
+
+/// Converts an encrypted map of entries, like `import csv` creates, back into
+/// a CSV or TSV table, printed unless `output` is given
+pub fn csv(path: OsString, output: Option<OsString>, key_column: String, context: &GlobalContext) {
+    let file = Path::new(&path);
+    let decrypted = match decrypt_in_memory(file, None, context) {
+        Ok(plaintext) => plaintext,
+        Err(e) => {
+            print_error(failed_to("decrypt-path-failed", file, &e));
+            std::process::exit(e.code);
+        }
+    };
+
+    let delimiter = output
+        .as_deref()
+        .map_or(',', |o| delimiter_for(Path::new(o)));
+    let table = serde_yaml::from_slice(&decrypted)
+        .map_err(|e| tr_args("export-parse-failed", &[("error", e.to_string().into())]))
+        .and_then(|document| yaml_to_table(&document, &key_column))
+        .map(|rows| render_table(&rows, delimiter));
+    drop(decrypted);
+    let mut table = match table {
+        Ok(t) => t,
+        Err(e) => {
+            print_error(format!("{} {}", tr("export-table-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+
+    let result = match &output {
+        Some(output) => write_private(Path::new(output), &table, context),
+        None => io::stdout()
+            .write_all(table.as_bytes())
+            .map_err(|e| tr_args("output-write-failed", &[("error", e.to_string().into())])),
+    };
+    table.zeroize();
+    match (result, output) {
+        (Ok(()), Some(output)) => print_success(tr_args(
+            "export-exported",
+            &[
+                ("path", file.display().to_string().into()),
+                ("output", Path::new(&output).display().to_string().into()),
+            ],
+        )),
+        (Ok(()), None) => {}
+        (Err(e), _) => {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Writes plaintext with the permissions decrypted files get
+fn write_private(path: &Path, contents: &str, context: &GlobalContext) -> Result<(), String> {
+    let permissions = resolve_permissions(path, None, None, context)?;
+    prepare_output(path)?;
+    fs::write(path, contents).map_err(|e| failed_to("write-failed", path, e))?;
+    apply_permissions(path, &permissions)
+}
+
+/// The leaves of `document` as concealed fields. Top level values are fields of
+/// their own, the leaves of each subtree go into a section named after it,
+/// labeled with their key path below it.
+fn item_fields(document: &Value) -> Result<Vec<OpItemField>, String> {
+    let Value::Mapping(map) = document else {
+        return Err(tr("export-not-a-map"));
+    };
+    let field = |section: Option<String>, label: String, value: &Value| {
+        scalar_text(value)
+            .filter(|_| !value.is_null())
+            .map(|value| OpItemField {
+                section,
+                field: label,
+                field_type: Some("password".to_string()),
+                value,
+            })
+    };
+    let mut fields = Vec::new();
+    for (key, value) in map {
+        let key = scalar_text(key).unwrap_or_default();
+        if matches!(value, Value::Mapping(_) | Value::Sequence(_)) {
+            let mut leaves = BTreeMap::new();
+            flatten(value, String::new(), &mut leaves);
+            fields.extend(
+                leaves
+                    .iter()
+                    .filter_map(|(path, leaf)| field(Some(key.clone()), path.clone(), leaf)),
+            );
+        } else {
+            fields.extend(field(None, key, value));
+        }
+    }
+    Ok(fields)
+}
+
+/// Mirrors an encrypted file into a 1Password item, creating it or updating its
+/// fields, for teams browsing their secrets in 1Password. Fields that were
+/// removed from the file stay in the item.
+pub fn op(path: OsString, vault: String, item: Option<String>, context: &GlobalContext) {
+    let file = Path::new(&path);
+    let title = item.unwrap_or_else(|| {
+        file.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let decrypted = match decrypt_in_memory(file, None, context) {
+        Ok(plaintext) => plaintext,
+        Err(e) => {
+            print_error(failed_to("decrypt-path-failed", file, &e));
+            std::process::exit(e.code);
+        }
+    };
+    let fields = parse_document(&decrypted, sops_file_type(file)).and_then(|d| item_fields(&d));
+    drop(decrypted);
+    let fields = match fields {
+        Ok(fields) if !fields.is_empty() => fields,
+        Ok(_) => {
+            print_error(tr_args(
+                "export-no-values",
+                &[("path", file.display().to_string().into())],
+            ));
+            std::process::exit(1);
+        }
+        Err(e) => {
+            print_error(format!("{} {}", tr("export-op-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+
+    let count = fields.len();
+    let item = OpItem {
+        vault: vault.clone(),
+        title: title.clone(),
+        category: OpCategory::SecureNote,
+        fields,
+    };
+    if item_category(&title, &vault).is_ok() {
+        if let Err(e) = op_item_edit(&item) {
+            print_error(tr_args(
+                "export-update-failed",
+                &[
+                    ("item", title.as_str().into()),
+                    ("vault", vault.as_str().into()),
+                    ("error", e.to_string().into()),
+                ],
+            ));
+            std::process::exit(1);
+        }
+        print_success(tr_args(
+            "export-updated",
+            &[
+                ("count", count.into()),
+                ("item", title.as_str().into()),
+                ("vault", vault.as_str().into()),
+                ("path", file.display().to_string().into()),
+            ],
+        ));
+        print_info(tr("export-removed-fields-stay"));
+    } else {
+        if !op_item_create(item) {
+            std::process::exit(1);
+        }
+        print_success(tr_args(
+            "export-exported-fields",
+            &[
+                ("count", count.into()),
+                ("path", file.display().to_string().into()),
+                ("item", title.as_str().into()),
+                ("vault", vault.as_str().into()),
+            ],
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::item_fields;
+
+    #[test]
+    fn test_item_fields_sections_per_subtree() {
+        let document = serde_yaml::from_str(
+            "api_key: abc\ndb:\n  user: app\n  primary:\n    password: hunter2\nhosts:\n- a\nempty: null\n",
+        )
+        .unwrap();
+        let fields: Vec<(Option<String>, String, String)> = item_fields(&document)
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.section, f.field, f.value))
+            .collect();
+        let field = |section: Option<&str>, label: &str, value: &str| {
+            (
+                section.map(str::to_string),
+                label.to_string(),
+                value.to_string(),
+            )
+        };
+        assert_eq!(
+            fields,
+            [
+                field(None, "api_key", "abc"),
+                field(Some("db"), "primary.password", "hunter2"),
+                field(Some("db"), "user", "app"),
+                field(Some("hosts"), "[0]", "a"),
+            ]
+        );
+        assert!(item_fields(&serde_yaml::from_str("- a").unwrap()).is_err());
+    }
+}
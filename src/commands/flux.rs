@@ -0,0 +1,230 @@
+use age::secrecy::{ExposeSecret, SecretString};
+use colored::Colorize;
+use std::collections::BTreeSet;
+use std::process::Command;
+use zeroize::Zeroize;
+
+use crate::{
+    GlobalContext,
+    util::{
+        check_report::{CheckReport, FailOn},
+        find_project_root::find_project_root,
+        flux::{age_key_from_secret, decryption_secret_manifest, find_kustomizations},
+        i18n::{failed_to, tr, tr_args},
+        kubectl::{apply_manifest, kubectl},
+        op_key::{extract_public_key, get_age_key_from_1password},
+        print_status::{print_error, print_info, print_success},
+        rule_match::relative_path,
+        session_record,
+    },
+};
+
+/// Checks that Flux Kustomizations decrypt with sops and that the Secrets they
+/// read the age key from hold the key stored in 1Password
+pub fn check(context: &GlobalContext) {
+    let Some(root) = find_project_root(context) else {
+        print_error(tr("no-project-root"));
+        std::process::exit(1);
+    };
+    let kustomizations = find_kustomizations(&root);
+    if kustomizations.is_empty() {
+        print_info(tr("flux-none"));
+        return;
+    }
+
+    let mut report = CheckReport::default();
+    let mut secrets = BTreeSet::new();
+    for k in &kustomizations {
+        let label = tr_args(
+            "flux-kustomization",
+            &[
+                ("name", k.name.as_str().into()),
+                ("path", relative_path(&root, &k.file).into()),
+            ],
+        );
+        match (k.provider.as_deref(), &k.secret_name) {
+            (Some("sops"), Some(secret)) => {
+                report.pass(
+                    "kustomization",
+                    tr_args(
+                        "flux-decrypts",
+                        &[
+                            ("kustomization", label.as_str().into()),
+                            ("secret", format!("{}/{}", k.namespace, secret).into()),
+                        ],
+                    ),
+                );
+                secrets.insert((k.namespace.clone(), secret.clone()));
+            }
+            (Some("sops"), None) => report.fail(
+                "kustomization",
+                tr_args(
+                    "flux-no-secret-ref",
+                    &[("kustomization", label.as_str().into())],
+                ),
+                vec![tr("flux-needs-secret")],
+            ),
+            (Some(other), _) => report.warn(
+                "kustomization",
+                tr_args(
+                    "flux-other-provider",
+                    &[
+                        ("kustomization", label.as_str().into()),
+                        ("provider", other.into()),
+                    ],
+                ),
+                Vec::new(),
+            ),
+            (None, _) => report.warn(
+                "kustomization",
+                tr_args(
+                    "flux-no-decryption",
+                    &[("kustomization", label.as_str().into())],
+                ),
+                vec![tr("flux-add-provider")],
+            ),
+        }
+    }
+
+    if !secrets.is_empty() {
+        check_secrets(&mut report, &secrets, context);
+    }
+    if report.failed(FailOn::Error) {
+        std::process::exit(1);
+    }
+}
+
+/// Compares the age key in each referenced Secret with the one in 1Password
+fn check_secrets(
+    report: &mut CheckReport,
+    secrets: &BTreeSet<(String, String)>,
+    context: &GlobalContext,
+) {
+    let Ok(kubectl) = kubectl() else {
+        report.warn("decryption_secret", tr("flux-no-kubectl"), Vec::new());
+        return;
+    };
+    let expected = match get_age_key_from_1password(context)
+        .and_then(|key| extract_public_key(key.expose_secret()).map_err(str::to_string))
+    {
+        Ok(public_key) => public_key,
+        Err(e) => {
+            report.fail(
+                "decryption_secret",
+                tr_args("age-key-get-failed", &[("error", e.into())]),
+                Vec::new(),
+            );
+            return;
+        }
+    };
+
+    for (namespace, name) in secrets {
+        let output = session_record::output(Command::new(&kubectl).args([
+            "get",
+            "secret",
+            name,
+            "--namespace",
+            namespace,
+            "-o",
+            "json",
+        ]));
+        let mut json = match output {
+            Ok(output) if output.status.success() => output.stdout,
+            Ok(output) => {
+                report.fail(
+                    "decryption_secret",
+                    tr_args(
+                        "flux-secret-read-failed",
+                        &[
+                            ("secret", format!("{}/{}", namespace, name).into()),
+                            (
+                                "error",
+                                String::from_utf8_lossy(&output.stderr).trim().into(),
+                            ),
+                        ],
+                    ),
+                    vec![tr_args(
+                        "flux-create-secret",
+                        &[
+                            ("name", name.as_str().into()),
+                            ("namespace", namespace.as_str().into()),
+                        ],
+                    )],
+                );
+                continue;
+            }
+            Err(e) => {
+                report.fail(
+                    "decryption_secret",
+                    failed_to("start-failed", &kubectl, e),
+                    Vec::new(),
+                );
+                return;
+            }
+        };
+        let actual = age_key_from_secret(&String::from_utf8_lossy(&json))
+            .and_then(|key| extract_public_key(key.expose_secret()).map_err(str::to_string));
+        json.zeroize();
+
+        match actual {
+            Ok(public_key) if public_key == expected => report.pass(
+                "decryption_secret",
+                tr_args(
+                    "flux-secret-matches",
+                    &[("secret", format!("{}/{}", namespace, name).into())],
+                ),
+            ),
+            Ok(public_key) => report.fail(
+                "decryption_secret",
+                tr_args(
+                    "flux-secret-differs",
+                    &[("secret", format!("{}/{}", namespace, name).into())],
+                ),
+                vec![
+                    format!("Secret:    {}", public_key),
+                    format!("1Password: {}", expected),
+                    tr_args(
+                        "flux-update-secret",
+                        &[
+                            ("name", name.as_str().into()),
+                            ("namespace", namespace.as_str().into()),
+                        ],
+                    ),
+                ],
+            ),
+            Err(e) => report.fail(
+                "decryption_secret",
+                format!("Secret {}/{}: {}", namespace, name, e),
+                Vec::new(),
+            ),
+        }
+    }
+}
+
+/// Creates or updates the Secret Flux decrypts with, straight from 1Password
+pub fn create_secret(name: String, namespace: String, dry_run: bool, context: &GlobalContext) {
+    let key: SecretString = match get_age_key_from_1password(context) {
+        Ok(key) => key,
+        Err(e) => {
+            print_error(format!("{} {}", tr("age-key-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let manifest = decryption_secret_manifest(&name, &namespace, &key);
+
+    match apply_manifest(manifest, dry_run) {
+        Ok(status) if status.success() => print_success(tr_args(
+            if dry_run {
+                "secret-validated"
+            } else {
+                "secret-applied"
+            },
+            &[("secret", format!("{}/{}", namespace, name).into())],
+        )),
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    }
+}
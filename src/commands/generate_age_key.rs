@@ -1,68 +1,100 @@
 use age::{secrecy::ExposeSecret, x25519};
 use colored::Colorize;
-use dialoguer::{Confirm, Input, theme::ColorfulTheme};
+use std::io::IsTerminal;
 
 use crate::{
     GlobalContext,
     util::{
+        i18n::tr,
+        mnemonic::to_mnemonic,
         op::{OpCategory, OpItem, OpItemField, op_item_create},
-        print_status::print_info,
+        op_key::fingerprint,
+        print_status::{print_error, print_info},
+        prompts::{confirm, input},
+        user_config::read_user_config,
     },
 };
 
-pub fn generate_age_key(_context: &GlobalContext) {
+pub fn generate_age_key(mnemonic: bool, _context: &GlobalContext) {
     let key = x25519::Identity::generate();
+    present_key(&key, mnemonic);
+}
+
+/// Prints a key pair and offers to store it in 1Password. With `mnemonic` the
+/// recovery phrase is printed and stored as well.
+pub fn present_key(key: &x25519::Identity, mnemonic: bool) {
     let pubkey = key.to_public();
+    let phrase = if mnemonic {
+        match to_mnemonic(key) {
+            Ok(phrase) => Some(phrase),
+            Err(e) => {
+                print_error(e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
 
     let label_width = 17;
 
     println!(
-        "{} {}",
-        format!("{:width$}", "🔑 Public Key:", width = label_width)
+        "{} {} {}",
+        format!("{:width$}", tr("key-public-label"), width = label_width)
             .yellow()
             .bold(),
-        pubkey.to_string().cyan()
+        pubkey.to_string().cyan(),
+        format!("[{}]", fingerprint(&pubkey.to_string())).dimmed()
     );
 
     println!(
         "{} {}",
-        format!("{:width$}", "🔐 Private Key:", width = label_width)
+        format!("{:width$}", tr("key-private-label"), width = label_width)
             .red()
             .bold(),
         key.to_string().expose_secret()
     );
 
-    if Confirm::with_theme(&ColorfulTheme::default())
-        .with_prompt("Would you like to save this key in 1Password?")
-        .default(false)
-        .interact()
-        .unwrap()
-    {
-        let vault = Input::with_theme(&ColorfulTheme::default())
-            .with_prompt("Choose a 1Password vault to store the item")
-            .default("Personal".to_string())
-            .interact_text()
-            .unwrap();
-        let name = Input::with_theme(&ColorfulTheme::default())
-            .with_prompt("Choose a name for the 1Password item")
-            .interact_text()
-            .unwrap();
-        save_to_op(&key, name, vault);
-    } else {
+    if let Some(phrase) = &phrase {
         println!(
-            "{}",
-            "Remember to save this key in a secure location!".dimmed()
+            "{} {}",
+            format!("{:width$}", tr("key-recovery-label"), width = label_width)
+                .red()
+                .bold(),
+            phrase.as_str()
         );
+        println!("{}", tr("key-recovery-hint").dimmed());
     }
 
-    print_info(format!(
-        "{}",
-        "Don't forget to add the public key to .sops.yaml!".bold(),
-    ));
+    if std::io::stdin().is_terminal() && confirm(&tr("key-save-in-op"), false).unwrap() {
+        let vault = input(
+            &tr("key-choose-vault"),
+            Some(
+                read_user_config()
+                    .default_vault
+                    .unwrap_or_else(|| "Personal".to_string()),
+            ),
+            false,
+        )
+        .unwrap();
+        let name = input(&tr("key-choose-item-name"), None, false).unwrap();
+        save_to_op(key, name, vault, mnemonic);
+    } else {
+        println!("{}", tr("key-save-securely").dimmed());
+    }
+
+    print_info(format!("{}", tr("key-add-to-sops-config").bold(),));
 }
 
-fn save_to_op(key: &x25519::Identity, item_name: String, vault: String) {
-    let item = OpItem {
+/// Stores the key pair as a new 1Password item, returning whether it succeeded.
+/// With `mnemonic` the recovery phrase goes into a separate field.
+pub fn save_to_op(
+    key: &x25519::Identity,
+    item_name: String,
+    vault: String,
+    mnemonic: bool,
+) -> bool {
+    let mut item = OpItem {
         vault: vault.to_string(),
         title: item_name.to_string(),
         category: OpCategory::Password,
@@ -81,6 +113,20 @@ fn save_to_op(key: &x25519::Identity, item_name: String, vault: String) {
             },
         ],
     };
+    if mnemonic {
+        match to_mnemonic(key) {
+            Ok(phrase) => item.fields.push(OpItemField {
+                section: None,
+                field: "Recovery Phrase".to_string(),
+                field_type: Some("PASSWORD".to_string()),
+                value: phrase.to_string(),
+            }),
+            Err(e) => {
+                print_error(e);
+                return false;
+            }
+        }
+    }
 
-    op_item_create(item);
+    op_item_create(item)
 }
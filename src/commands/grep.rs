@@ -0,0 +1,205 @@
+use age::secrecy::SecretString;
+use colored::Colorize;
+use regex::Regex;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use crate::{
+    GlobalContext,
+    util::{
+        decrypted_copies::flatten,
+        document::{REDACTED, parse_document, scalar_text},
+        i18n::{tr, tr_args},
+        op_key::get_age_key_from_1password,
+        print_status::{print_error, print_warning},
+        rule_match::relative_path,
+        sops_command::decrypt_in_memory,
+        sops_config::config_dir,
+        sops_files::{find_encrypted_files, is_sops_encrypted_file, sops_file_type},
+    },
+};
+
+/// What the pattern is matched against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrepTarget {
+    Keys,
+    Values,
+    Both,
+}
+
+/// A value whose key path or content matches
+#[derive(Debug, PartialEq)]
+struct Hit {
+    path: String,
+    value: String,
+}
+
+/// The leaves of `document` whose `a.b[0].c` key path or value matches `pattern`
+fn find_hits(document: &Value, pattern: &Regex, target: GrepTarget) -> Vec<Hit> {
+    let mut leaves = BTreeMap::new();
+    flatten(document, String::new(), &mut leaves);
+    leaves
+        .into_iter()
+        .filter_map(|(path, value)| {
+            let value = scalar_text(&value).unwrap_or_default();
+            let by_key = target != GrepTarget::Values && pattern.is_match(&path);
+            let by_value = target != GrepTarget::Keys && pattern.is_match(&value);
+            (by_key || by_value).then_some(Hit { path, value })
+        })
+        .collect()
+}
+
+/// Decrypts `file` in memory and searches it
+fn grep_file(
+    file: &Path,
+    key: &SecretString,
+    pattern: &Regex,
+    target: GrepTarget,
+    context: &GlobalContext,
+) -> Result<Vec<Hit>, String> {
+    let content = decrypt_in_memory(file, Some(key), context).map_err(|e| e.to_string())?;
+    let document = parse_document(&content, sops_file_type(file))?;
+    Ok(find_hits(&document, pattern, target))
+}
+
+/// Searches the key paths and values of encrypted files for `pattern`,
+/// decrypting them in memory in parallel and printing hits as files finish.
+/// Values are redacted unless `show_values`. Exits with 1 if nothing matched.
+pub fn grep(
+    pattern: String,
+    paths: Vec<OsString>,
+    target: GrepTarget,
+    show_values: bool,
+    ignore_case: bool,
+    context: &GlobalContext,
+) {
+    let pattern = match regex::RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+    {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            print_error(tr_args(
+                "grep-invalid-pattern",
+                &[("error", e.to_string().into())],
+            ));
+            std::process::exit(2);
+        }
+    };
+    let root = config_dir(context).unwrap_or_else(|| PathBuf::from("."));
+    let files: Vec<PathBuf> = if paths.is_empty() {
+        find_encrypted_files(&root)
+    } else {
+        paths
+            .into_iter()
+            .map(PathBuf::from)
+            .filter(|p| is_sops_encrypted_file(p))
+            .collect()
+    };
+    if files.is_empty() {
+        print_warning(tr("no-files-to-search"));
+        std::process::exit(1);
+    }
+
+    // One request to 1Password for all files
+    let key = match get_age_key_from_1password(context) {
+        Ok(key) => key,
+        Err(e) => {
+            print_error(format!("{} {}", tr("age-key-failed").red(), e));
+            std::process::exit(2);
+        }
+    };
+
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(files.len());
+    let (sender, receiver) = mpsc::channel();
+    let (mut matches, mut failed) = (0, 0);
+    std::thread::scope(|scope| {
+        for chunk in files.chunks(files.len().div_ceil(workers)) {
+            let sender = sender.clone();
+            let (key, pattern) = (&key, &pattern);
+            scope.spawn(move || {
+                for file in chunk {
+                    let result = grep_file(file, key, pattern, target, context);
+                    if sender.send((file, result)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        for (file, result) in receiver {
+            let name = relative_path(&root, file);
+            match result {
+                Ok(hits) => {
+                    for hit in hits {
+                        matches += 1;
+                        let value = if show_values {
+                            hit.value
+                        } else {
+                            REDACTED.to_string()
+                        };
+                        println!("{}: {} = {}", name.magenta(), hit.path.cyan(), value);
+                    }
+                }
+                Err(e) => {
+                    failed += 1;
+                    print_warning(tr_args(
+                        "skipped",
+                        &[
+                            ("path", name.as_str().into()),
+                            ("error", e.to_string().into()),
+                        ],
+                    ));
+                }
+            }
+        }
+    });
+
+    if matches == 0 {
+        std::process::exit(if failed > 0 { 2 } else { 1 });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GrepTarget, Hit, find_hits};
+    use regex::Regex;
+
+    #[test]
+    fn test_find_hits() {
+        let document: serde_yaml::Value = serde_yaml::from_str(
+            "grafana:\n  admin_token: glsa_123\n  url: https://grafana\nusers:\n- name: token-bot\n",
+        )
+        .unwrap();
+        let hit = |path: &str, value: &str| Hit {
+            path: path.to_string(),
+            value: value.to_string(),
+        };
+        let token = Regex::new("token").unwrap();
+        assert_eq!(
+            find_hits(&document, &token, GrepTarget::Both),
+            [
+                hit("grafana.admin_token", "glsa_123"),
+                hit("users[0].name", "token-bot")
+            ]
+        );
+        assert_eq!(
+            find_hits(&document, &token, GrepTarget::Keys),
+            [hit("grafana.admin_token", "glsa_123")]
+        );
+        assert_eq!(
+            find_hits(
+                &document,
+                &Regex::new("^glsa_").unwrap(),
+                GrepTarget::Values
+            ),
+            [hit("grafana.admin_token", "glsa_123")]
+        );
+    }
+}
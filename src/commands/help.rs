@@ -0,0 +1,91 @@
+use colored::Colorize;
+
+use crate::util::{
+    help_topics::{TOPICS, find_topic},
+    i18n::{tr, tr_args},
+    print_status::print_error,
+};
+
+fn print_topics() {
+    println!("{}", tr("help-guides").bold().underline());
+    for topic in TOPICS {
+        println!("  {:<14}{}", topic.name.bold(), topic.summary);
+    }
+    println!("\n{}", tr("help-read-guide"));
+}
+
+/// Prints the help of opsops or of the subcommand at `path`, or the guide
+/// named by `path`, see [`crate::util::help_topics`]
+pub fn help(path: Vec<String>, command: clap::Command) {
+    if let [name] = path.as_slice()
+        && let Some(topic) = find_topic(name)
+    {
+        print!("{}", topic.text);
+        return;
+    }
+
+    let mut command = command;
+    command.build();
+    for part in &path {
+        let Some(subcommand) = command.find_subcommand(part).cloned() else {
+            print_error(tr_args(
+                "help-unknown",
+                &[
+                    ("name", path.join(" ").into()),
+                    (
+                        "guides",
+                        TOPICS
+                            .iter()
+                            .map(|topic| topic.name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                            .into(),
+                    ),
+                ],
+            ));
+            std::process::exit(1);
+        };
+        command = subcommand;
+    }
+    println!("{}", command.render_long_help());
+    if path.is_empty() {
+        print_topics();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+    use regex::Regex;
+
+    use crate::Cli;
+    use crate::util::help_topics::TOPICS;
+
+    #[test]
+    fn test_guides_name_existing_commands() {
+        let mut cli = Cli::command();
+        cli.build();
+        let command = Regex::new(r"opsops ((?:[a-z][a-z-]*)(?: [a-z][a-z-]*)?)").unwrap();
+        for topic in TOPICS {
+            for found in command.captures_iter(topic.text) {
+                let mut words = found[1].split(' ');
+                let name = words.next().unwrap();
+                let subcommand = cli
+                    .find_subcommand(name)
+                    .unwrap_or_else(|| panic!("{}: no command '{}'", topic.name, name));
+                // Only commands with subcommands take a second word as a command
+                if let Some(next) = words.next()
+                    && subcommand.has_subcommands()
+                {
+                    assert!(
+                        subcommand.find_subcommand(next).is_some(),
+                        "{}: no command '{} {}'",
+                        topic.name,
+                        name,
+                        next
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use colored::Colorize;
+use serde_yaml::{Mapping, Value};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+use crate::{
+    GlobalContext,
+    util::{
+        csv_table::{delimiter_for, parse_table, table_to_yaml},
+        encrypted_insert::insert_values,
+        escrow::missing_escrow_reason,
+        i18n::{failed_to, tr, tr_args},
+        op::{ItemValue, item_values, read_secret},
+        op_key::get_age_key_from_1password,
+        op_reference::{OpDocument, OpReference},
+        print_status::{print_error, print_success, print_warning},
+        sops_command::run_sops_on_buffer,
+    },
+};
+
+/// Converts a CSV or TSV export of credentials into an encrypted YAML map with
+/// one entry per row. The plaintext YAML never touches the disk.
+pub fn csv(
+    path: OsString,
+    output: Option<OsString>,
+    key_column: Option<String>,
+    context: &GlobalContext,
+) {
+    let input = Path::new(&path);
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| input.with_extension("yaml"));
+    if output.exists() {
+        print_error(format!(
+            "{} {}",
+            tr("import-refusing-to-overwrite").red(),
+            output.display()
+        ));
+        std::process::exit(1);
+    }
+    if let Some(reason) = missing_escrow_reason(&output, context) {
+        print_error(format!(
+            "{} {}: {}",
+            tr("refusing-to-encrypt").red(),
+            output.display(),
+            reason
+        ));
+        std::process::exit(1);
+    }
+
+    let mut contents = match fs::read_to_string(input) {
+        Ok(c) => c,
+        Err(e) => {
+            print_error(failed_to("read-failed", input, e));
+            std::process::exit(1);
+        }
+    };
+    let table = parse_table(&contents, delimiter_for(input));
+    contents.zeroize();
+    let (entries, mut yaml) = match table
+        .and_then(|rows| table_to_yaml(&rows, key_column.as_deref()))
+        .and_then(|map| {
+            serde_yaml::to_string(&map)
+                .map(|yaml| (map.len(), yaml))
+                .map_err(|e| e.to_string())
+        }) {
+        Ok(result) => result,
+        Err(e) => {
+            print_error(format!(
+                "{} {}: {}",
+                tr("import-invalid-table").red(),
+                input.display(),
+                e
+            ));
+            std::process::exit(1);
+        }
+    };
+
+    let encrypted = run_sops_on_buffer("--encrypt", "yaml", &output, &yaml, context);
+    yaml.zeroize();
+    if let Err(e) = encrypted.and_then(|e| fs::write(&output, e).map_err(|e| e.to_string())) {
+        print_error(format!("{} {}", tr("import-encrypt-failed").red(), e));
+        std::process::exit(1);
+    }
+
+    print_success(tr_args(
+        "import-imported-entries",
+        &[
+            ("count", entries.into()),
+            ("path", input.display().to_string().into()),
+            ("output", output.display().to_string().into()),
+        ],
+    ));
+    print_warning(tr_args(
+        "import-plaintext-left",
+        &[("path", input.display().to_string().into())],
+    ));
+}
+
+/// The values to write for the fields of an item. `mappings` pick fields as
+/// `<label>=<key path>`, or `<label>` for the label under `path`; without them
+/// every field holding a value goes into a mapping at `path`, keyed by label.
+fn select_fields(
+    fields: &[ItemValue],
+    mappings: &[String],
+    path: Option<&str>,
+) -> Result<Vec<(String, Value)>, String> {
+    let with_values: Vec<(&str, &str)> = fields
+        .iter()
+        .filter_map(|field| Some((field.label.as_str(), field.value.as_deref()?)))
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+    if mappings.is_empty() {
+        let Some(path) = path else {
+            return Err(tr("import-pass-path"));
+        };
+        let mut map = Mapping::new();
+        for (label, value) in with_values {
+            if map.insert(label.into(), value.into()).is_some() {
+                return Err(tr_args(
+                    "import-duplicate-label",
+                    &[("label", label.into())],
+                ));
+            }
+        }
+        return Ok(vec![(path.to_string(), Value::Mapping(map))]);
+    }
+
+    let mut values = Vec::new();
+    for mapping in mappings {
+        let (label, target) = match mapping.split_once('=') {
+            Some((label, target)) => (label, target.to_string()),
+            None => (
+                mapping.as_str(),
+                path.map_or(mapping.clone(), |path| format!("{}.{}", path, mapping)),
+            ),
+        };
+        let mut matching = with_values.iter().filter(|(l, _)| *l == label);
+        let Some((_, value)) = matching.next() else {
+            return Err(tr_args("import-no-field", &[("label", label.into())]));
+        };
+        if matching.next().is_some() {
+            return Err(tr_args(
+                "import-ambiguous-label",
+                &[("label", label.into())],
+            ));
+        }
+        values.push((target, Value::from(*value)));
+    }
+    Ok(values)
+}
+
+/// Reads secrets from 1Password and inserts them into the encrypted file `into`,
+/// in memory. A field reference `op://<vault>/<item>[/<section>]/<field>` reads
+/// one value, stored at `path` or under the field's name. An item, by name or as
+/// `op://<vault>/<item>`, imports several fields, see [`select_fields`].
+pub fn op(
+    reference: String,
+    into: OsString,
+    path: Option<String>,
+    fields: Vec<String>,
+    vault: Option<String>,
+    force: bool,
+    context: &GlobalContext,
+) {
+    let fail = |message: String| -> ! {
+        print_error(message);
+        std::process::exit(1);
+    };
+    let into = PathBuf::from(into);
+
+    let values = if let Ok(field) = reference.parse::<OpReference>() {
+        if !fields.is_empty() {
+            fail(tr_args(
+                "import-single-field",
+                &[("reference", reference.as_str().into())],
+            ));
+        }
+        let value = read_secret(&reference).unwrap_or_else(|e| fail(e));
+        vec![(path.unwrap_or(field.field), Value::from(value.as_str()))]
+    } else {
+        let (item, vault) = match reference.parse::<OpDocument>() {
+            Ok(document) => (document.item, Some(document.vault)),
+            Err(_) if reference.starts_with("op://") => fail(tr_args(
+                "import-invalid-reference",
+                &[("reference", reference.as_str().into())],
+            )),
+            Err(_) => (reference.clone(), vault),
+        };
+        let item_fields = item_values(&item, vault.as_deref()).unwrap_or_else(|e| fail(e));
+        select_fields(&item_fields, &fields, path.as_deref()).unwrap_or_else(|e| fail(e))
+    };
+
+    let key = get_age_key_from_1password(context)
+        .unwrap_or_else(|e| fail(tr_args("age-key-get-failed", &[("error", e.into())])));
+    let paths: Vec<String> = values.iter().map(|(path, _)| path.clone()).collect();
+    let created = insert_values(&into, values, force, &key, context).unwrap_or_else(|e| fail(e));
+    print_success(tr_args(
+        "import-imported-op",
+        &[
+            ("paths", paths.join(", ").into()),
+            ("path", into.display().to_string().into()),
+        ],
+    ));
+    if created {
+        print_warning(tr_args(
+            "created-for-rule",
+            &[("path", into.display().to_string().into())],
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::select_fields;
+    use crate::util::op::ItemValue;
+
+    fn field(label: &str, value: Option<&str>) -> ItemValue {
+        ItemValue {
+            label: label.to_string(),
+            value: value.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_select_fields() {
+        let fields = [
+            field("username", Some("app")),
+            field("password", Some("hunter2")),
+            field("notesPlain", Some("")),
+            field("website", None),
+        ];
+        let all = select_fields(&fields, &[], Some("db")).unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].0, "db");
+        assert_eq!(all[0].1["password"], "hunter2");
+        assert!(all[0].1.get("notesPlain").is_none());
+        assert!(select_fields(&fields, &[], None).is_err());
+
+        let picked = select_fields(
+            &fields,
+            &[
+                "password=database.password".to_string(),
+                "username".to_string(),
+            ],
+            Some("db"),
+        )
+        .unwrap();
+        assert_eq!(picked[0].0, "database.password");
+        assert_eq!(picked[1].0, "db.username");
+        assert!(select_fields(&fields, &["website".to_string()], None).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use colored::Colorize;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::{
+    GlobalContext,
+    util::{
+        config_include::load_effective_config,
+        environment::{BuildInfo, ToolInfo, build_info, detect_tool},
+        find_project_root::find_project_root,
+        i18n::{tr, tr_args},
+        opsops_config::opsops_config_path,
+        output_format::{OutputFormat, render_structured},
+        print_status::print_error,
+        sops_config::sops_config_path,
+        user_config::user_config_path,
+    },
+};
+
+/// Everything worth pasting into a bug report. Never includes key material.
+#[derive(Serialize)]
+struct Info {
+    #[serde(flatten)]
+    build: BuildInfo,
+    tools: Vec<ToolInfo>,
+    user_config: Option<PathBuf>,
+    project_root: Option<PathBuf>,
+    sops_config: Option<PathBuf>,
+    opsops_config: Option<PathBuf>,
+    /// 1Password reference of the age key in use
+    key_reference: Option<String>,
+    /// `--op-item` or `.opsops.yaml`
+    key_source: Option<&'static str>,
+}
+
+/// The key reference in effect and where it was set
+fn key_reference(context: &GlobalContext) -> (Option<String>, Option<&'static str>) {
+    if let Some(reference) = &context.opitem {
+        return (Some(reference.clone()), Some("--op-item"));
+    }
+    match load_effective_config(context) {
+        Ok(effective) if !effective.config.onepassworditem.is_empty() => {
+            (Some(effective.config.onepassworditem), Some(".opsops.yaml"))
+        }
+        _ => (None, None),
+    }
+}
+
+fn collect_info(context: &GlobalContext) -> Info {
+    let (key_reference, key_source) = key_reference(context);
+    Info {
+        build: build_info(),
+        tools: ["sops", "op", "age"].into_iter().map(detect_tool).collect(),
+        user_config: user_config_path(),
+        project_root: find_project_root(context),
+        sops_config: sops_config_path(context),
+        opsops_config: opsops_config_path(context),
+        key_reference,
+        key_source,
+    }
+}
+
+fn show(label: &str, value: Option<String>) {
+    match value {
+        Some(value) => println!("  {:14} {}", label.cyan(), value),
+        None => println!("  {:14} {}", label.cyan(), tr("none").dimmed()),
+    }
+}
+
+fn show_path(label: &str, path: &Option<PathBuf>) {
+    show(label, path.as_ref().map(|p| p.display().to_string()));
+}
+
+/// Prints the opsops build, the tools it found and the configuration in
+/// effect, for bug reports
+pub fn info(context: &GlobalContext, format: OutputFormat) {
+    let info = collect_info(context);
+
+    if let Some(rendered) = render_structured(&info, format) {
+        match rendered {
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(e) => {
+                print_error(e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    println!("{}", "opsops".bold());
+    show(&tr("info-version"), Some(info.build.version.to_string()));
+    show(&tr("info-build"), info.build.build.map(str::to_string));
+    show(&tr("info-target"), Some(info.build.target));
+
+    println!("\n{}", tr("info-tools").bold());
+    for tool in &info.tools {
+        match &tool.path {
+            Some(path) => show(
+                tool.name,
+                Some(format!(
+                    "{} {}",
+                    tool.version_or_unknown(),
+                    format!("({})", path.display()).dimmed()
+                )),
+            ),
+            None => println!("  {:14} {}", tool.name.cyan(), tr("not-found").red()),
+        }
+    }
+
+    println!("\n{}", tr("info-configuration").bold());
+    show_path(&tr("user-config"), &info.user_config);
+    show_path(&tr("info-project-root"), &info.project_root);
+    show_path(".sops.yaml", &info.sops_config);
+    show_path(".opsops.yaml", &info.opsops_config);
+    show(
+        &tr("info-key"),
+        info.key_reference.map(|reference| {
+            format!(
+                "{} {}",
+                reference,
+                tr_args(
+                    "info-key-source",
+                    &[("source", info.key_source.unwrap_or("?").into())],
+                )
+                .dimmed()
+            )
+        }),
+    );
+}
@@ -1,174 +1,289 @@
 use crate::GlobalContext;
-use crate::util::op::{get_fields, get_items, get_vaults};
+use crate::util::config_edit::{basic_config, set_op_item};
+use crate::util::escrow::{escrow_recipient, with_escrow};
+use crate::util::i18n::{tr, tr_args};
+use crate::util::migrations::CURRENT_VERSION;
+use crate::util::op::{ItemField, default_field, get_fields, get_items, get_vaults};
+use crate::util::op_key::{
+    extract_public_key, normalize_op_reference, read_key_from_op, validate_age_recipients,
+};
+use crate::util::op_reference::OpReference;
+use crate::util::opsops_config::{OpsopsConfig, read_opsops_config, write_opsops_config};
+use crate::util::passphrase_key::{KeyProvider, new_passphrase_key, read_passphrase};
 use crate::util::print_status::{print_error, print_info, print_success, print_warning};
+use crate::util::prompts::{confirm, fuzzy_select};
+use crate::util::rule_spec::parse_rule_spec;
 use crate::util::sops_config::{get_sops_config, read_or_create_config, write_config};
 use crate::util::sops_structs::{CreationRule, SopsConfig};
+use age::secrecy::ExposeSecret;
 use colored::Colorize;
-use dialoguer::Confirm;
-use dialoguer::{FuzzySelect, theme::ColorfulTheme};
-use serde_yaml::from_str;
-use std::io::Read;
 
-pub fn init(context: &GlobalContext) {
-    match get_sops_config(context) {
-        Some(mut file) => {
-            let mut contents = String::new();
-            if let Err(e) = file.read_to_string(&mut contents) {
-                print_error(format!("{} {}", "Failed to read config file:".red(), e));
-                return;
-            }
+pub fn init(
+    from_key: Option<String>,
+    passphrase: bool,
+    rules: Vec<String>,
+    context: &GlobalContext,
+) {
+    if passphrase {
+        if let Err(e) = init_passphrase(&rules, context) {
+            print_error(format!("{} {}", tr("init-failed").red(), e));
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(key) = from_key {
+        if let Err(e) = init_from_key(&key, &rules, context) {
+            print_error(format!("{} {}", tr("init-failed").red(), e));
+            std::process::exit(1);
+        }
+        return;
+    }
 
-            // Check if onepassworditem field is missing
-            if !contents.contains("onepassworditem") {
-                print_warning(format!(
-                    "{}",
-                    "⚠️  .sops.yaml exists but is missing onepassworditem field.".yellow()
-                ));
-                assign_op_item(context);
+    match get_sops_config(context) {
+        Some(_) => {
+            // Reads through the migrations, so a reference still in .sops.yaml counts
+            if let Err(e) = read_or_create_config(context) {
+                print_error(format!("{} {}", tr("config-read-failed").red(), e));
                 return;
             }
-
-            let _: SopsConfig = match from_str(&contents) {
-                Ok(c) => c,
+            let configured = match read_opsops_config(context) {
+                Ok(opsops) => opsops.and_then(|c| c.onepassworditem).is_some(),
                 Err(e) => {
-                    print_error(format!("{} {}", "Failed to parse YAML:".red(), e));
+                    print_error(format!("{} {}", tr("config-read-failed").red(), e));
                     return;
                 }
             };
 
-            // Config file exists with onepassworditem field, do nothing
-            print_success(format!(
-                "{}",
-                ".sops.yaml file exists. No action needed.".green()
-            ));
+            if !configured {
+                print_warning(format!("{}", tr("init-no-reference").yellow()));
+                assign_op_item(context);
+                return;
+            }
+
+            // Config file exists with a 1Password reference, do nothing
+            print_success(format!("{}", tr("init-nothing-to-do").green()));
         }
         None => {
-            print_error(format!("{}", ".sops.yaml is missing.".red()));
-
-            if Confirm::with_theme(&ColorfulTheme::default())
-                .with_prompt("Would you like to create a basic .sops.yaml file?")
-                .default(true)
-                .interact()
-                .unwrap()
-            {
+            print_error(format!("{}", tr("init-missing").red()));
+
+            if confirm(&tr("init-create-basic"), true).unwrap() {
                 // Create a minimal config with creation_rules
-                let config = SopsConfig {
-                    creation_rules: vec![CreationRule {
-                        path_regex: Some(".*".to_string()),
-                        age: None,
-                        encrypted_regex: None,
-                        key_groups: Vec::new(),
-                    }],
-                    onepassworditem: String::new(),
-                };
-
-                if let Err(e) = write_config(&config, context) {
-                    print_error(format!("{} {}", "Failed to create config file:".red(), e));
+                if let Err(e) = write_config(&basic_config(), context) {
+                    print_error(format!("{} {}", tr("init-create-failed").red(), e));
                     return;
                 }
 
-                print_success(format!("{}", "Created basic .sops.yaml file.".green()));
+                print_success(format!("{}", tr("init-created-basic").green()));
                 assign_op_item(context);
             } else {
-                print_info(format!("{}", "Please create a .sops.yaml file manually following the guide at: https://github.com/getsops/sops#using-sops-yaml-conf-to-select-kms-pgp-and-age-for-new-files".yellow()));
+                print_info(format!("{}", tr("init-create-manually").yellow()));
             }
         }
     }
 }
 
+/// Writes a complete config without prompting: `key` is either the 1Password
+/// reference of the age key or an age public key, `rules` are rule specs (see
+/// [`parse_rule_spec`]) encrypting to it. Without rules every file is covered.
+pub fn init_from_key(key: &str, rules: &[String], context: &GlobalContext) -> Result<(), String> {
+    if get_sops_config(context).is_some() {
+        return Err(tr("init-already-exists"));
+    }
+
+    let (reference, public_key) = if key.starts_with("op://") {
+        let reference = normalize_op_reference(key)?;
+        let private_key = read_key_from_op(&reference)?;
+        let public_key = extract_public_key(private_key.expose_secret()).map_err(str::to_string)?;
+        (Some(reference), public_key)
+    } else {
+        validate_age_recipients(key)?;
+        (context.opitem.clone(), key.to_string())
+    };
+    let config = SopsConfig {
+        creation_rules: creation_rules(&public_key, rules, context)?,
+        onepassworditem: reference.clone().unwrap_or_default(),
+        ..Default::default()
+    };
+    write_config(&config, context)?;
+
+    print_success(tr_args(
+        "init-created",
+        &[
+            ("count", config.creation_rules.len().into()),
+            ("key", public_key.as_str().into()),
+        ],
+    ));
+    match reference {
+        Some(reference) => print_success(tr_args(
+            "init-wrote-reference",
+            &[("reference", reference.into())],
+        )),
+        None => print_warning(tr("init-pass-reference")),
+    }
+    Ok(())
+}
+
+/// The rules of a new config encrypting to `public_key`, from rule specs or
+/// one rule covering every file
+fn creation_rules(
+    public_key: &str,
+    rules: &[String],
+    context: &GlobalContext,
+) -> Result<Vec<CreationRule>, String> {
+    let recipients = with_escrow(public_key, escrow_recipient(context).as_deref());
+    if rules.is_empty() {
+        let mut config = basic_config();
+        config.creation_rules[0].age = Some(recipients);
+        return Ok(config.creation_rules);
+    }
+    rules
+        .iter()
+        .map(|spec| parse_rule_spec(spec, &recipients))
+        .collect()
+}
+
+/// Writes a config for a key derived from a passphrase, for projects without
+/// 1Password. `key upgrade` moves them to a key in 1Password later.
+pub fn init_passphrase(rules: &[String], context: &GlobalContext) -> Result<(), String> {
+    if get_sops_config(context).is_some() {
+        return Err(tr("init-already-exists"));
+    }
+
+    let passphrase = read_passphrase(true)?;
+    let (settings, _) = new_passphrase_key(&passphrase)?;
+    let public_key = settings.public_key.clone();
+    let config = SopsConfig {
+        creation_rules: creation_rules(&public_key, rules, context)?,
+        ..Default::default()
+    };
+    write_config(&config, context)?;
+    let opsops = OpsopsConfig {
+        version: CURRENT_VERSION,
+        keyprovider: KeyProvider::Passphrase,
+        passphrase: Some(settings),
+        ..read_opsops_config(context)?.unwrap_or_default()
+    };
+    write_opsops_config(&opsops, context)?;
+
+    print_success(tr_args(
+        "init-created",
+        &[
+            ("count", config.creation_rules.len().into()),
+            ("key", public_key.as_str().into()),
+        ],
+    ));
+    print_success(tr("init-wrote-passphrase"));
+    print_warning(tr("init-passphrase-only"));
+    Ok(())
+}
+
 fn assign_op_item(context: &GlobalContext) {
-    if Confirm::with_theme(&ColorfulTheme::default())
-        .with_prompt("Would you like to assign an age key from 1Password?")
-        .default(true)
-        .interact()
-        .unwrap()
-    {
-        // Get the vault names
-        let vaults = match get_vaults() {
-            Some(vaults) => vaults,
-            None => {
-                print_error("Failed to retrieve vaults.".to_string());
-                return;
-            }
+    // A reference passed via --op-item is used as-is, without prompting
+    if context.opitem.is_some() || confirm(&tr("init-assign-key"), true).unwrap() {
+        let reference = match context.opitem.clone().or_else(select_op_reference) {
+            Some(reference) => reference,
+            None => return,
         };
-        // If no vaults are found, exit
-        if vaults.is_empty() {
-            print_error("No vaults found.".to_string());
-            return;
-        }
-        // Let the user select a vault
-        let selected_vault = FuzzySelect::with_theme(&ColorfulTheme::default())
-            .with_prompt("Choose a Vault")
-            .items(&vaults)
-            .interact()
-            .unwrap();
-        let items = match get_items(&vaults[selected_vault]) {
-            Some(vaults) => vaults,
-            None => {
-                print_error("Failed to retrieve items.".to_string());
-                return;
-            }
-        };
-        // If no vaults are found, exit
-        if items.is_empty() {
-            print_error("No items found.".to_string());
-            return;
-        }
-        // Prompt for the 1Password item name
-        let selected_item = FuzzySelect::with_theme(&ColorfulTheme::default())
-            .with_prompt("Choose an Item")
-            .items(&items)
-            .interact()
-            .unwrap();
-        let fields = match get_fields(&items[selected_item], &vaults[selected_vault]) {
-            Some(vaults) => vaults,
-            None => {
-                print_error("Failed to retrieve items.".to_string());
-                return;
-            }
-        };
-        // If no vaults are found, exit
-        if fields.is_empty() {
-            print_error("No items found.".to_string());
-            return;
-        }
-        // Prompt for the 1Password item name
-        let selected_field = FuzzySelect::with_theme(&ColorfulTheme::default())
-            .with_prompt("Choose a Field")
-            .items(&fields)
-            .interact()
-            .unwrap();
-        // Handle the chosen vault and op_name further, if necessary
-        let reference = format!(
-            "op://{}/{}/{}",
-            vaults[selected_vault], items[selected_item], fields[selected_field]
-        );
-        print_info(format!(
-            "🔐 Writing 1Password reference to config: {}",
-            reference
+        print_info(tr_args(
+            "init-writing-reference",
+            &[("reference", reference.as_str().into())],
         ));
 
         // Read the existing config
-        let mut config = match read_or_create_config(context) {
+        let config = match read_or_create_config(context) {
             Ok(cfg) => cfg,
             Err(e) => {
-                print_error(format!("Failed to read or create config: {}", e));
+                print_error(tr_args(
+                    "init-read-or-create-failed",
+                    &[("error", e.to_string().into())],
+                ));
                 return;
             }
         };
 
-        // Update the config with the new 1Password reference
-        config.onepassworditem = reference;
-
-        // Write the updated config back to disk
-        if let Err(e) = write_config(&config, context) {
-            print_error(format!("Failed to write config: {}", e));
+        // Write the config with the new 1Password reference back to disk
+        if let Err(e) = write_config(&set_op_item(config, &reference), context) {
+            print_error(format!("{} {}", tr("config-write-failed"), e));
             return;
         }
 
-        print_success(format!(
-            "{}",
-            "Successfully updated .sops.yaml with 1Password reference.".green()
-        ));
+        print_success(format!("{}", tr("init-updated-reference").green()));
+    }
+}
+
+/// Lets the user pick a vault, item and field and returns the resulting op:// reference
+pub fn select_op_reference() -> Option<String> {
+    // Get the vault names
+    let vaults = match get_vaults() {
+        Some(vaults) => vaults,
+        None => {
+            print_error(tr("op-vaults-failed"));
+            return None;
+        }
+    };
+    // If no vaults are found, exit
+    if vaults.is_empty() {
+        print_error(tr("op-no-vaults"));
+        return None;
+    }
+    // Let the user select a vault
+    let selected_vault = fuzzy_select(&tr("op-choose-vault"), &vaults, None).unwrap();
+    let items = match get_items(&vaults[selected_vault]) {
+        Some(vaults) => vaults,
+        None => {
+            print_error(tr("op-items-failed"));
+            return None;
+        }
+    };
+    // If no vaults are found, exit
+    if items.is_empty() {
+        print_error(tr("op-no-items"));
+        return None;
+    }
+    // Prompt for the 1Password item name
+    let selected_item = fuzzy_select(&tr("op-choose-item"), &items, None).unwrap();
+    let fields = match get_fields(&items[selected_item], &vaults[selected_vault]) {
+        Some(vaults) => vaults,
+        None => {
+            print_error(tr("op-items-failed"));
+            return None;
+        }
+    };
+    // If no vaults are found, exit
+    if fields.is_empty() {
+        print_error(tr("op-no-items"));
+        return None;
+    }
+    // Concealed fields are where keys live, usernames or URLs never work
+    let choices: Vec<String> = fields.iter().map(ItemField::describe).collect();
+    let mut default = default_field(&fields);
+    loop {
+        let selected_field = fuzzy_select(&tr("op-choose-field"), &choices, Some(default)).unwrap();
+        let reference = OpReference::new(
+            &vaults[selected_vault],
+            &items[selected_item],
+            &fields[selected_field].label,
+        )
+        .to_string();
+
+        // Catch fields that don't hold a key now rather than on the first decrypt
+        match read_key_from_op(&reference) {
+            Ok(_) => return Some(reference),
+            Err(e) => {
+                print_error(format!(
+                    "{} {}",
+                    tr_args(
+                        "init-not-a-key",
+                        &[("field", fields[selected_field].label.as_str().into())],
+                    )
+                    .red(),
+                    e
+                ));
+                if !confirm(&tr("init-choose-another"), true).unwrap() {
+                    return None;
+                }
+                default = selected_field;
+            }
+        }
     }
 }
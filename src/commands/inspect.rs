@@ -0,0 +1,285 @@
+use age::secrecy::SecretString;
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::{
+    GlobalContext,
+    util::{
+        certificates::{PemDetails, inspect_pem, pem_blocks},
+        decrypted_copies::flatten,
+        document::{extract, parse_document, scalar_text},
+        i18n::{failed_to, tr, tr_args},
+        op_key::get_age_key_from_1password,
+        output_format::{OutputFormat, render_structured},
+        print_status::{print_error, print_info, print_warning},
+        recipient_expiry::today,
+        rule_match::relative_path,
+        sops_command::decrypt_in_memory,
+        sops_config::config_dir,
+        sops_files::{find_encrypted_files, sops_file_type},
+        value_expiry::{DEFAULT_WARNING_DAYS, ExpiryState, date_in, expiry_state},
+    },
+};
+
+fn fail(message: impl Into<String>) -> ! {
+    print_error(message.into());
+    std::process::exit(1);
+}
+
+/// A certificate found by `inspect --certs`
+#[derive(Debug, Serialize)]
+struct CertificateRow {
+    file: String,
+    key: String,
+    /// Position in a chain stored in one value, 1-based
+    index: usize,
+    subject: Option<String>,
+    expires: Option<String>,
+    state: ExpiryState,
+}
+
+fn get_key(context: &GlobalContext) -> SecretString {
+    get_age_key_from_1password(context)
+        .unwrap_or_else(|e| fail(format!("{} {}", tr("age-key-failed").red(), e)))
+}
+
+/// The leaves of the decrypted document in `file` as `(key path, text)`
+fn decrypted_values(
+    file: &Path,
+    key: &SecretString,
+    context: &GlobalContext,
+) -> Result<BTreeMap<String, Zeroizing<String>>, String> {
+    let content = decrypt_in_memory(file, Some(key), context).map_err(|e| e.to_string())?;
+    let document = parse_document(&content, sops_file_type(file))?;
+    let mut leaves = BTreeMap::new();
+    flatten(&document, String::new(), &mut leaves);
+    Ok(leaves
+        .into_iter()
+        .filter_map(|(path, value)| Some((path, Zeroizing::new(scalar_text(&value)?))))
+        .collect())
+}
+
+fn show(label: &str, value: impl std::fmt::Display) {
+    println!("  {:12} {}", label.cyan(), value);
+}
+
+fn colored_expiry(expires: &str, state: ExpiryState) -> String {
+    match state {
+        ExpiryState::Expired => tr_args("inspect-expired", &[("date", expires.into())])
+            .red()
+            .to_string(),
+        ExpiryState::Soon => tr_args(
+            "inspect-expires-soon",
+            &[
+                ("date", expires.into()),
+                ("days", DEFAULT_WARNING_DAYS.into()),
+            ],
+        )
+        .yellow()
+        .to_string(),
+        ExpiryState::Valid => expires.to_string(),
+    }
+}
+
+/// Prints the details of the certificates and keys in the value at
+/// `<file>#<key_path>`, or the value itself if it holds no PEM block
+pub fn inspect(file: PathBuf, key_path: String, format: OutputFormat, context: &GlobalContext) {
+    let key = get_key(context);
+    let content = decrypt_in_memory(&file, Some(&key), context)
+        .unwrap_or_else(|e| fail(failed_to("decrypt-path-failed", &file, e)));
+    let document = parse_document(&content, sops_file_type(&file)).unwrap_or_else(|e| fail(e));
+    let value = extract(&document, &key_path)
+        .ok()
+        .and_then(scalar_text)
+        .map(Zeroizing::new)
+        .unwrap_or_else(|| {
+            fail(tr_args(
+                "inspect-not-a-value",
+                &[(
+                    "location",
+                    format!("{}#{}", file.display(), key_path).into(),
+                )],
+            ))
+        });
+
+    let blocks = pem_blocks(&value);
+    if blocks.is_empty() {
+        if format != OutputFormat::Text {
+            fail(tr_args(
+                "inspect-no-pem",
+                &[(
+                    "location",
+                    format!("{}#{}", file.display(), key_path).into(),
+                )],
+            ));
+        }
+        println!("{}", *value);
+        return;
+    }
+    let details: Vec<PemDetails> = blocks
+        .iter()
+        .map(|block| inspect_pem(block).unwrap_or_else(|e| fail(e)))
+        .collect();
+
+    if let Some(rendered) = render_structured(&details, format) {
+        match rendered {
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(e) => fail(e),
+        }
+        return;
+    }
+    let (today, cutoff) = (today(), date_in(DEFAULT_WARNING_DAYS));
+    for (i, details) in details.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let heading = match details.kind.as_str() {
+            "certificate" => tr("inspect-certificate"),
+            "private key" => tr("inspect-private-key"),
+            "public key" => tr("inspect-public-key"),
+            other => other.to_string(),
+        };
+        if blocks.len() > 1 {
+            println!(
+                "{} {}",
+                heading.bold(),
+                tr_args(
+                    "inspect-block-of",
+                    &[("number", (i + 1).into()), ("count", blocks.len().into())],
+                )
+                .dimmed()
+            );
+        } else {
+            println!("{}", heading.bold());
+        }
+        if let Some(subject) = &details.subject {
+            show(&tr("inspect-subject"), subject);
+        }
+        if let Some(issuer) = &details.issuer {
+            show(&tr("inspect-issuer"), issuer);
+        }
+        if !details.sans.is_empty() {
+            show("SANs", details.sans.join(", "));
+        }
+        if let Some(not_before) = &details.not_before {
+            show(&tr("inspect-not-before"), not_before);
+        }
+        if let Some(expires) = &details.expires {
+            show(
+                &tr("inspect-expires"),
+                colored_expiry(expires, expiry_state(expires, &today, &cutoff)),
+            );
+        }
+        if let Some(key_type) = &details.key_type {
+            show(&tr("inspect-key"), key_type);
+        }
+    }
+}
+
+/// Lists every certificate stored in the project's encrypted files, or in
+/// `file` alone, with its subject and expiry date, the soonest first
+pub fn certificates(file: Option<PathBuf>, format: OutputFormat, context: &GlobalContext) {
+    let root = config_dir(context).unwrap_or_else(|| PathBuf::from("."));
+    let files = match file {
+        Some(file) => vec![file],
+        None => find_encrypted_files(&root),
+    };
+    if files.is_empty() {
+        print_info(tr("no-files-to-search"));
+        return;
+    }
+
+    let key = get_key(context);
+    let (today, cutoff) = (today(), date_in(DEFAULT_WARNING_DAYS));
+    let mut rows = Vec::new();
+    for file in &files {
+        let name = relative_path(&root, file);
+        let values = match decrypted_values(file, &key, context) {
+            Ok(values) => values,
+            Err(e) => {
+                print_warning(tr_args(
+                    "skipped",
+                    &[
+                        ("path", name.as_str().into()),
+                        ("error", e.to_string().into()),
+                    ],
+                ));
+                continue;
+            }
+        };
+        for (path, value) in &values {
+            let certificates = pem_blocks(value)
+                .into_iter()
+                .filter(|block| block.label == "CERTIFICATE");
+            for (i, block) in certificates.enumerate() {
+                let details = match inspect_pem(&block) {
+                    Ok(details) => details,
+                    Err(e) => {
+                        print_warning(tr_args(
+                            "skipped",
+                            &[
+                                ("path", format!("{}#{}", name, path).into()),
+                                ("error", e.to_string().into()),
+                            ],
+                        ));
+                        continue;
+                    }
+                };
+                rows.push(CertificateRow {
+                    file: name.clone(),
+                    key: path.clone(),
+                    index: i + 1,
+                    state: details
+                        .expires
+                        .as_deref()
+                        .map_or(ExpiryState::Expired, |expires| {
+                            expiry_state(expires, &today, &cutoff)
+                        }),
+                    subject: details.subject,
+                    expires: details.expires,
+                });
+            }
+        }
+    }
+    rows.sort_by(|a, b| a.expires.cmp(&b.expires));
+
+    if let Some(rendered) = render_structured(&rows, format) {
+        match rendered {
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(e) => fail(e),
+        }
+        return;
+    }
+    if rows.is_empty() {
+        print_info(tr_args(
+            "inspect-no-certificates",
+            &[("count", files.len().into())],
+        ));
+        return;
+    }
+    for row in &rows {
+        let unknown = tr("unknown");
+        let expires = row.expires.as_deref().unwrap_or(&unknown);
+        let expires = match row.state {
+            ExpiryState::Expired => expires.red(),
+            ExpiryState::Soon => expires.yellow(),
+            ExpiryState::Valid => expires.normal(),
+        };
+        let chain = if row.index > 1 {
+            format!(" ({})", row.index)
+        } else {
+            String::new()
+        };
+        println!(
+            "  {:10}  {}#{}{}  {}",
+            expires,
+            row.file,
+            row.key,
+            chain.dimmed(),
+            row.subject.as_deref().unwrap_or_default().dimmed()
+        );
+    }
+}
@@ -0,0 +1,528 @@
+use age::secrecy::ExposeSecret;
+use colored::Colorize;
+use dialoguer::{Password, theme::ColorfulTheme};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::{
+    GlobalContext,
+    commands::{generate_age_key::present_key, init::select_op_reference},
+    util::{
+        backups::backup_or_exit,
+        bulk::run_resumable,
+        config_edit::{add_recipients, remove_recipients},
+        escrow::rule_recipients,
+        file_picker::quiet_config,
+        i18n::{tr, tr_args},
+        journal::Journal,
+        member_registry::read_member_registry,
+        mnemonic::from_mnemonic,
+        notifications::{NotifyEvent, notify},
+        op_key::{
+            extract_public_key, fingerprint as fingerprint_of, get_age_key_from_1password,
+            normalize_op_reference, public_keys, read_key_from_op, validate_age_recipients,
+            with_fingerprint,
+        },
+        opsops_config::{OpsopsConfig, read_opsops_config, write_opsops_config},
+        passphrase_key::{KeyProvider, key_from_passphrase, passphrase_settings},
+        print_status::{print_error, print_info, print_success, print_warning},
+        recipient_expiry::{expired_recipients, remaining_access},
+        rule_match::relative_path,
+        sops_command::SopsCommandBuilder,
+        sops_config::{config_dir, read_or_create_config, write_config},
+        sops_files::find_encrypted_files,
+        sops_metadata::file_recipients,
+    },
+};
+
+/// Reconstructs an age key from the recovery phrase made by
+/// `generate-age-key --mnemonic`. The phrase is prompted for, or read from
+/// stdin when it isn't a terminal, so it never ends up in the shell history.
+pub fn recover(context: &GlobalContext) {
+    let phrase = if std::io::stdin().is_terminal() {
+        Zeroizing::new(
+            Password::with_theme(&ColorfulTheme::default())
+                .with_prompt(tr("key-recovery-phrase"))
+                .interact()
+                .unwrap(),
+        )
+    } else {
+        let mut phrase = Zeroizing::new(String::new());
+        if let Err(e) = std::io::stdin().read_to_string(&mut phrase) {
+            print_error(format!("{} {}", tr("key-recovery-read-failed").red(), e));
+            std::process::exit(1);
+        }
+        phrase
+    };
+
+    let key = match from_mnemonic(&phrase) {
+        Ok(key) => key,
+        Err(e) => {
+            print_error(format!("{} {}", tr("key-recovery-invalid").red(), e));
+            std::process::exit(1);
+        }
+    };
+
+    // Tell whether the project can be decrypted with it
+    if let Some(config) = quiet_config(context) {
+        let public_key = key.to_public().to_string();
+        if config
+            .creation_rules
+            .iter()
+            .any(|rule| rule_recipients(rule).contains(&public_key))
+        {
+            print_success(tr("key-recovered-recipient"));
+        } else {
+            print_warning(tr("key-recovered-not-recipient"));
+        }
+    }
+
+    present_key(&key, false);
+}
+
+/// Adds the public keys of registry members to the rules with `path_regex`, or
+/// to every rule. Files are re-encrypted for them with `drift --fix`.
+pub fn add_recipient(members: Vec<String>, path_regex: Option<String>, context: &GlobalContext) {
+    let registry = match read_member_registry() {
+        Ok(registry) if !registry.members.is_empty() => registry,
+        Ok(_) => {
+            print_error(format!(
+                "{} {}",
+                tr("registry-empty").red(),
+                tr("registry-sync-first").dimmed()
+            ));
+            std::process::exit(1);
+        }
+        Err(e) => {
+            print_error(format!("{} {}", tr("registry-read-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let member_keys = match members
+        .iter()
+        .map(|name| registry.recipients_of(name))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(keys) => keys,
+        Err(e) => {
+            print_error(e);
+            std::process::exit(1);
+        }
+    };
+    let recipients = member_keys.join(",");
+
+    let config = match read_or_create_config(context) {
+        Ok(config) => config,
+        Err(e) => {
+            print_error(format!("{} {}", tr("config-read-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let skipped: Vec<_> = config
+        .creation_rules
+        .iter()
+        .filter(|rule| path_regex.is_none() || rule.path_regex == path_regex)
+        .filter(|rule| !rule.key_groups.is_empty())
+        .map(|rule| rule.path_regex.clone().unwrap_or_default())
+        .collect();
+    let config = match add_recipients(config, &recipients, path_regex.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            print_error(e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = write_config(&config, context) {
+        print_error(format!("{} {}", tr("config-write-failed").red(), e));
+        std::process::exit(1);
+    }
+
+    for rule in skipped {
+        print_warning(tr_args(
+            "key-skipped-key-groups",
+            &[("pattern", rule.into())],
+        ));
+    }
+    print_success(tr_args(
+        "key-added-members",
+        &[("members", members.join(", ").into())],
+    ));
+    // Lets them confirm over chat that these are their keys
+    for (name, keys) in members.iter().zip(&member_keys) {
+        for key in keys.split(',').map(str::trim) {
+            println!("    {}: {}", name, with_fingerprint(key));
+        }
+    }
+    print_info(tr("key-run-drift-fix"));
+    if let Some(root) = config_dir(context) {
+        let added: Vec<String> = recipients
+            .split(',')
+            .map(|r| r.trim().to_string())
+            .collect();
+        notify(NotifyEvent::AddRecipient, &root, 0, &added, context);
+    }
+}
+
+/// Removes recipients past their `recipient_expiry` date from the creation
+/// rules and rotates the data key of every file they could decrypt, so they
+/// lose access to the current values as well
+pub fn expire_sweep(dry_run: bool, context: &GlobalContext) {
+    let expired = match expired_recipients(context) {
+        Ok(expired) => expired,
+        Err(e) => {
+            print_error(format!("{} {}", tr("opsops-config-read-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    if expired.is_empty() {
+        print_info(tr("key-none-expired"));
+        return;
+    }
+    let Some(root) = config_dir(context) else {
+        print_error(tr("no-sops-config"));
+        std::process::exit(1);
+    };
+    let config = match read_or_create_config(context) {
+        Ok(config) => config,
+        Err(e) => {
+            print_error(format!("{} {}", tr("config-read-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+
+    // Files to rotate with the expired recipients they have
+    let mut files: BTreeMap<PathBuf, Vec<&str>> = BTreeMap::new();
+    let mut in_rules = Vec::new();
+    for entry in &expired {
+        let access = remaining_access(&entry.recipient, &config.creation_rules, &root);
+        if access.is_empty() {
+            continue;
+        }
+        println!(
+            "{} {}",
+            "✗".red(),
+            tr_args(
+                "key-expired-after",
+                &[
+                    ("recipient", entry.describe().into()),
+                    ("date", entry.expires.to_string().into()),
+                ],
+            )
+        );
+        for i in &access.rules {
+            println!(
+                "    {}",
+                tr_args(
+                    "key-expired-rule",
+                    &[
+                        ("number", (i + 1).into()),
+                        (
+                            "pattern",
+                            config.creation_rules[*i]
+                                .path_regex
+                                .as_deref()
+                                .unwrap_or("<no path_regex>")
+                                .into(),
+                        ),
+                    ],
+                )
+            );
+        }
+        for file in access.files {
+            println!("    {}", relative_path(&root, &file));
+            files.entry(file).or_default().push(&entry.recipient);
+        }
+        if !access.rules.is_empty() {
+            in_rules.push(entry.recipient.as_str());
+        }
+    }
+    // What an interrupted earlier sweep already rotated
+    let mut operation: Vec<&str> = expired.iter().map(|e| e.recipient.as_str()).collect();
+    operation.sort();
+    let journal = Journal::open(&root, "expire-sweep", &operation.join(","));
+    if files.is_empty() && in_rules.is_empty() && journal.completed().next().is_none() {
+        print_success(tr("expired-no-access"));
+        return;
+    }
+    if dry_run {
+        return;
+    }
+
+    if !in_rules.is_empty() {
+        let config = remove_recipients(config, &in_rules);
+        if let Err(e) = write_config(&config, context) {
+            print_error(format!("{} {}", tr("config-write-failed").red(), e));
+            std::process::exit(1);
+        }
+        print_success(tr_args(
+            "key-removed-expired",
+            &[("count", in_rules.len().into())],
+        ));
+    }
+
+    // Only asks 1Password if anything is left to rotate
+    let mut key = None;
+    let paths: Vec<PathBuf> = files.keys().cloned().collect();
+    let still_has_access = |file: &Path| {
+        let contents = fs::read_to_string(file).map_err(|e| e.to_string())?;
+        match file_recipients(&contents)
+            .iter()
+            .find(|r| operation.contains(&r.as_str()))
+        {
+            Some(recipient) => Err(tr_args(
+                "key-access-again",
+                &[("recipient", recipient.as_str().into())],
+            )),
+            None => Ok(()),
+        }
+    };
+    let report = run_resumable(&root, &paths, "rotate", journal, still_has_access, |file| {
+        let key = key.get_or_insert_with(|| match get_age_key_from_1password(context) {
+            Ok(key) => key,
+            Err(e) => {
+                print_error(format!("{} {}", tr("age-key-failed").red(), e));
+                std::process::exit(1);
+            }
+        });
+        backup_or_exit(file, context);
+        let status = SopsCommandBuilder::new(context)
+            .arg("--rotate")
+            .arg("--in-place")
+            .arg("--rm-age")
+            .arg(files[file].join(","))
+            .arg_path(file)
+            .with_age_key_value(key)
+            .status()
+            .map_err(|e| format!("{} {}", tr("sops-launch-failed"), e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(tr_args(
+                "sops-exited",
+                &[("status", status.to_string().into())],
+            ))
+        }
+    });
+    report.print_summary();
+    if report.exit_code() != 0 {
+        print_error(tr("key-sweep-incomplete").red());
+        std::process::exit(report.exit_code());
+    }
+    let removed: Vec<String> = operation.iter().map(|r| r.to_string()).collect();
+    notify(
+        NotifyEvent::Rotate,
+        &root,
+        report.succeeded(),
+        &removed,
+        context,
+    );
+}
+
+/// Moves a project from its passphrase key to a key in 1Password: the new key
+/// replaces the passphrase key in the creation rules and, through
+/// `sops updatekeys`, in every file encrypted to it. `.opsops.yaml` switches to
+/// the 1Password reference last, so an interrupted upgrade can be run again.
+pub fn upgrade(context: &GlobalContext) {
+    let settings = match passphrase_settings(context) {
+        Ok(Some(settings)) => settings,
+        Ok(None) => {
+            print_error(tr("key-no-passphrase"));
+            std::process::exit(1);
+        }
+        Err(e) => {
+            print_error(format!("{} {}", tr("opsops-config-read-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let Some(root) = config_dir(context) else {
+        print_error(tr("no-sops-config"));
+        std::process::exit(1);
+    };
+
+    // The key to move to, given via --op-item or picked from 1Password
+    let reference = match context.opitem.clone().or_else(|| {
+        std::io::stdin()
+            .is_terminal()
+            .then(select_op_reference)
+            .flatten()
+    }) {
+        Some(reference) => reference,
+        None => {
+            print_error(tr("key-pass-reference"));
+            std::process::exit(1);
+        }
+    };
+    let reference = match normalize_op_reference(&reference) {
+        Ok(reference) => reference,
+        Err(e) => {
+            print_error(e);
+            std::process::exit(1);
+        }
+    };
+    let new_public_key = match read_key_from_op(&reference)
+        .and_then(|key| extract_public_key(key.expose_secret()).map_err(str::to_string))
+    {
+        Ok(public_key) => public_key,
+        Err(e) => {
+            print_error(format!("{} {}", tr("key-new-key-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let old_key = match key_from_passphrase(&settings) {
+        Ok(key) => key,
+        Err(e) => {
+            print_error(format!("{} {}", tr("key-derive-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let old_public_key = settings.public_key.as_str();
+
+    let config = match read_or_create_config(context) {
+        Ok(config) => config,
+        Err(e) => {
+            print_error(format!("{} {}", tr("config-read-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    if config
+        .creation_rules
+        .iter()
+        .any(|rule| rule_recipients(rule).iter().any(|r| r == old_public_key))
+    {
+        let config = match add_recipients(config, &new_public_key, None) {
+            Ok(config) => remove_recipients(config, &[old_public_key]),
+            Err(e) => {
+                print_error(e);
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = write_config(&config, context) {
+            print_error(format!("{} {}", tr("config-write-failed").red(), e));
+            std::process::exit(1);
+        }
+        print_success(tr_args(
+            "key-replaced-passphrase",
+            &[("key", new_public_key.as_str().into())],
+        ));
+    }
+
+    let files: Vec<PathBuf> = find_encrypted_files(&root)
+        .into_iter()
+        .filter(|file| {
+            fs::read_to_string(file).is_ok_and(|contents| {
+                file_recipients(&contents)
+                    .iter()
+                    .any(|r| r == old_public_key)
+            })
+        })
+        .collect();
+    let re_encrypted = |file: &Path| {
+        let contents = fs::read_to_string(file).map_err(|e| e.to_string())?;
+        let recipients = file_recipients(&contents);
+        if recipients.iter().any(|r| r == old_public_key) {
+            Err(tr("key-still-passphrase"))
+        } else if !recipients.contains(&new_public_key) {
+            Err(tr_args(
+                "key-not-encrypted-to",
+                &[("key", new_public_key.as_str().into())],
+            ))
+        } else {
+            Ok(())
+        }
+    };
+    let journal = Journal::open(&root, "key-upgrade", &new_public_key);
+    let report = run_resumable(&root, &files, "rekey", journal, re_encrypted, |file| {
+        backup_or_exit(file, context);
+        let status = SopsCommandBuilder::new(context)
+            .arg("updatekeys")
+            .arg("--yes")
+            .arg_path(file)
+            .with_age_key_value(&old_key)
+            .status()
+            .map_err(|e| format!("{} {}", tr("sops-launch-failed"), e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(tr_args(
+                "sops-exited",
+                &[("status", status.to_string().into())],
+            ))
+        }
+    });
+    report.print_summary();
+    if report.exit_code() != 0 {
+        print_error(tr("key-upgrade-incomplete").red());
+        std::process::exit(report.exit_code());
+    }
+
+    let opsops = match read_opsops_config(context) {
+        Ok(opsops) => opsops.unwrap_or_default(),
+        Err(e) => {
+            print_error(format!("{} {}", tr("opsops-config-read-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let opsops = OpsopsConfig {
+        onepassworditem: Some(reference.clone()),
+        keyprovider: KeyProvider::OnePassword,
+        passphrase: None,
+        ..opsops
+    };
+    if let Err(e) = write_opsops_config(&opsops, context) {
+        print_error(format!("{} {}", tr("opsops-config-write-failed").red(), e));
+        std::process::exit(1);
+    }
+    print_success(tr_args(
+        "key-upgraded",
+        &[("reference", reference.as_str().into())],
+    ));
+    print_info(tr("key-keep-passphrase"));
+    notify(
+        NotifyEvent::Rekey,
+        &root,
+        report.succeeded(),
+        &[new_public_key],
+        context,
+    );
+}
+
+/// Prints the fingerprints of `keys`, public keys or age secret keys, or of the
+/// project's own key, so people can compare keys without pasting them
+pub fn fingerprint(keys: Vec<String>, context: &GlobalContext) {
+    let recipients = if keys.is_empty() {
+        match get_age_key_from_1password(context)
+            .and_then(|key| public_keys(key.expose_secret()).map_err(str::to_string))
+        {
+            Ok(recipients) => recipients,
+            Err(e) => {
+                print_error(format!("{} {}", tr("age-key-failed").red(), e));
+                std::process::exit(1);
+            }
+        }
+    } else {
+        let mut recipients = Vec::new();
+        for key in &keys {
+            // Fingerprint the public half of a pasted secret key, never the secret
+            let recipient = if key.trim().starts_with("AGE-SECRET-KEY-") {
+                extract_public_key(key).map_err(str::to_string)
+            } else {
+                validate_age_recipients(key).map(|_| key.trim().to_string())
+            };
+            match recipient {
+                Ok(recipient) => recipients.push(recipient),
+                Err(e) => {
+                    print_error(e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        recipients
+    };
+
+    for recipient in recipients {
+        println!("{}  {}", fingerprint_of(&recipient).bold(), recipient);
+    }
+}
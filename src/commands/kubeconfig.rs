@@ -0,0 +1,190 @@
+use colored::Colorize;
+use serde_yaml::Value;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+use crate::{
+    GlobalContext,
+    util::{
+        decrypted_copies::shred,
+        editor::resolve_tmpdir,
+        i18n::{failed_to, tr, tr_args},
+        op_key::get_age_key_from_1password,
+        print_status::{print_error, print_info, print_success, print_warning},
+        session_record,
+        sops_command::decrypt_in_memory,
+    },
+};
+
+/// Prefix of the directories the decrypted kubeconfigs are written to, which
+/// `wipe` insists on so it can't be pointed at anything else
+const DIR_PREFIX: &str = "opsops-kube-";
+
+/// Name of the decrypted kubeconfig inside its directory
+const FILE_NAME: &str = "config";
+
+fn fail(message: impl Into<String>) -> ! {
+    print_error(message.into());
+    std::process::exit(1);
+}
+
+/// `value` in single quotes for POSIX shells
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Whether `content` parses as a kubeconfig, i.e. a mapping with `clusters`
+fn is_kubeconfig(content: &[u8]) -> bool {
+    serde_yaml::from_slice::<Value>(content)
+        .ok()
+        .and_then(|document| document.get("clusters").cloned())
+        .is_some()
+}
+
+/// Writes `content` to `config` in a fresh directory under `tmpdir`, readable
+/// by the current user only
+fn write_kubeconfig(tmpdir: &Path, content: &[u8]) -> Result<(TempDir, PathBuf), String> {
+    let dir = tempfile::Builder::new()
+        .prefix(DIR_PREFIX)
+        .tempdir_in(tmpdir)
+        .map_err(|e| failed_to("create-dir-in-failed", tmpdir, e))?;
+    let path = dir.path().join(FILE_NAME);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(content))
+        .map_err(|e| failed_to("write-failed", &path, e))?;
+    Ok((dir, path))
+}
+
+/// Keeps Ctrl-C and a closed terminal from stopping opsops before the shell
+/// exits, so the kubeconfig is always wiped. The shell gets the default
+/// handlers back when it is executed.
+#[cfg(unix)]
+fn outlive_shell() {
+    extern "C" fn ignore(_: libc::c_int) {}
+    unsafe {
+        libc::signal(libc::SIGINT, ignore as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGHUP, ignore as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn outlive_shell() {}
+
+/// Decrypts the kubeconfig at `path` into a RAM backed directory (or `tmpdir`)
+/// and starts `$SHELL` with `KUBECONFIG` pointing at it, wiping it once the
+/// shell exits. With `print`, prints `export KUBECONFIG=...` and an `EXIT`
+/// trap running `kubeconfig wipe` instead, for `eval` in the current shell.
+pub fn use_kubeconfig(path: OsString, print: bool, tmpdir: String, context: &GlobalContext) {
+    let path = PathBuf::from(path);
+    let tmpdir = resolve_tmpdir(&tmpdir).unwrap_or_else(|e| fail(e));
+    let key = get_age_key_from_1password(context)
+        .unwrap_or_else(|e| fail(format!("{} {}", tr("age-key-failed").red(), e)));
+    let content = decrypt_in_memory(&path, Some(&key), context)
+        .unwrap_or_else(|e| fail(failed_to("decrypt-path-failed", &path, e)));
+    if !is_kubeconfig(&content) {
+        fail(tr_args(
+            "kubeconfig-not-a-kubeconfig",
+            &[("path", path.display().to_string().into())],
+        ));
+    }
+    let (dir, kubeconfig) = write_kubeconfig(&tmpdir, &content).unwrap_or_else(|e| fail(e));
+    drop(content);
+
+    if print {
+        let _ = dir.keep();
+        let exe = std::env::current_exe()
+            .map(|exe| exe.display().to_string())
+            .unwrap_or_else(|_| "opsops".to_string());
+        let kubeconfig = kubeconfig.display().to_string();
+        let wipe = format!(
+            "{} kubeconfig wipe {}",
+            shell_quote(&exe),
+            shell_quote(&kubeconfig)
+        );
+        println!("export KUBECONFIG={}", shell_quote(&kubeconfig));
+        println!("trap {} EXIT", shell_quote(&wipe));
+        return;
+    }
+
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    print_info(tr_args(
+        "kubeconfig-starting-shell",
+        &[
+            ("shell", shell.as_str().into()),
+            ("path", kubeconfig.display().to_string().into()),
+        ],
+    ));
+    outlive_shell();
+    let status = session_record::status(Command::new(&shell).env("KUBECONFIG", &kubeconfig));
+
+    match shred(&kubeconfig) {
+        Ok(()) => print_success(tr_args(
+            "kubeconfig-wiped",
+            &[("path", kubeconfig.display().to_string().into())],
+        )),
+        Err(e) => print_warning(failed_to("kubeconfig-wipe-failed-by-hand", &kubeconfig, e)),
+    }
+    drop(dir);
+    match status {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => fail(failed_to("start-failed", Path::new(&shell), e)),
+    }
+}
+
+/// Shreds a kubeconfig written by `kubeconfig use --print` and removes its
+/// directory. Anything else is refused.
+pub fn wipe(path: OsString) {
+    let path = PathBuf::from(path);
+    let dir = path.parent().filter(|dir| {
+        path.file_name().is_some_and(|name| name == FILE_NAME)
+            && dir
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(DIR_PREFIX))
+    });
+    let Some(dir) = dir else {
+        fail(tr_args(
+            "kubeconfig-not-written-by-use",
+            &[("path", path.display().to_string().into())],
+        ));
+    };
+    if !path.exists() {
+        return;
+    }
+    if let Err(e) = shred(&path).and_then(|()| fs::remove_dir(dir)) {
+        fail(failed_to("kubeconfig-wipe-failed", &path, e));
+    }
+    print_success(tr_args(
+        "kubeconfig-wiped",
+        &[("path", path.display().to_string().into())],
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_kubeconfig, shell_quote};
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/dev/shm/config"), "'/dev/shm/config'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_is_kubeconfig() {
+        assert!(is_kubeconfig(
+            b"apiVersion: v1\nkind: Config\nclusters:\n- name: prod\n"
+        ));
+        assert!(is_kubeconfig(br#"{"clusters": []}"#));
+        assert!(!is_kubeconfig(b"password: hunter2\n"));
+        assert!(!is_kubeconfig(b"\x00\x01"));
+    }
+}
@@ -5,51 +5,128 @@ use std::io::Read;
 use crate::{
     GlobalContext,
     util::{
-        print_status::{print_error, print_info},
-        sops_config::get_sops_config,
+        config_include::{EffectiveConfig, resolve_includes},
+        destinations::{describe, destination_problems},
+        i18n::{tr, tr_args},
+        member_registry::{MemberRegistry, read_member_registry},
+        op_key::fingerprint,
+        opsops_config::apply_opsops_config,
+        output_format::{OutputFormat, render_structured},
+        print_status::{print_error, print_info, print_warning},
+        rule_match::{match_rules, relative_path},
+        sops_config::{config_dir, get_sops_config, sops_config_path},
+        sops_files::walk_files,
         sops_structs::SopsConfig,
     },
 };
 
-pub fn list_config(context: &GlobalContext) {
+/// How many matching files are listed per rule
+const MAX_PREVIEW_FILES: usize = 5;
+
+/// Recipients with the registry member they belong to and their fingerprint,
+/// e.g. `age1... (alice) [3f2a9c1e]`
+fn with_owners(recipients: &str, registry: &MemberRegistry) -> String {
+    recipients
+        .split(',')
+        .map(str::trim)
+        .map(|key| {
+            let fingerprint = format!("[{}]", fingerprint(key)).dimmed();
+            match registry.owner_of(key) {
+                Some(owner) => format!(
+                    "{} {} {}",
+                    key.green(),
+                    format!("({})", owner).dimmed(),
+                    fingerprint
+                ),
+                None => format!("{} {}", key.green(), fingerprint),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn list_config(context: &GlobalContext, format: OutputFormat) {
     let mut file = match get_sops_config(context) {
         Some(f) => f,
         None => {
-            print_error(format!(
-                "{}",
-                "Error: No SOPS configuration file found.".red()
-            ));
+            print_error(format!("{}", tr("list-no-config").red()));
             return;
         }
     };
 
     let mut contents = String::new();
     if let Err(e) = file.read_to_string(&mut contents) {
-        print_error(format!("{} {}", "Failed to read config file:".red(), e));
+        print_error(format!("{} {}", tr("config-read-failed").red(), e));
         return;
     }
 
-    let config: SopsConfig = match from_str(&contents) {
+    let mut config: SopsConfig = match from_str(&contents) {
         Ok(c) => c,
         Err(e) => {
-            print_error(format!("{} {}", "Failed to parse YAML:".red(), e));
+            print_error(format!("{} {}", tr("list-parse-failed").red(), e));
+            return;
+        }
+    };
+    if let Err(e) = apply_opsops_config(&mut config, context) {
+        print_error(e);
+        return;
+    }
+
+    let path = sops_config_path(context).unwrap_or_default();
+    let EffectiveConfig {
+        config, sources, ..
+    } = match resolve_includes(config, &path) {
+        Ok(e) => e,
+        Err(e) => {
+            print_error(format!("{} {}", tr("list-includes-failed").red(), e));
             return;
         }
     };
 
+    if let Some(rendered) = render_structured(&config, format) {
+        match rendered {
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(e) => {
+                print_error(e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     print_info(format!(
         "{} {}\n",
-        "Assigned 1Password item:".cyan(),
+        tr("list-item").cyan(),
         config.onepassworditem.green()
     ));
-    print!("{}", "Rules:".cyan());
+    print!("{}", tr("list-rules").cyan());
+
+    // Collect the project's files to preview what each rule matches
+    let files: Vec<String> = match config_dir(context) {
+        Some(root) => walk_files(&root)
+            .iter()
+            .map(|path| relative_path(&root, path))
+            .collect(),
+        None => Vec::new(),
+    };
+    let matches = match_rules(&config.creation_rules, &files);
+    // Name the members recipients belong to, once the registry was synced
+    let registry = read_member_registry().unwrap_or_default();
 
     for (i, rule) in config.creation_rules.iter().enumerate() {
         println!();
-        println!("{} {}", "🔹 Rule #".yellow(), (i + 1).to_string().yellow());
+        println!(
+            "{} {}",
+            tr("list-rule").yellow(),
+            (i + 1).to_string().yellow()
+        );
+
+        if let Some(source) = &sources[i] {
+            println!("  {} {}", tr("list-from").cyan(), source.green());
+        }
 
         if let Some(pattern) = &rule.path_regex {
-            println!("{} {}", "  📂 File pattern:".cyan(), pattern.green());
+            println!("  {} {}", tr("list-file-pattern").cyan(), pattern.green());
         }
 
         if !rule.key_groups.is_empty() {
@@ -57,24 +134,91 @@ pub fn list_config(context: &GlobalContext) {
             for group in &rule.key_groups {
                 if !group.age.is_empty() {
                     if !any_age {
-                        println!("{}", "  🔑 Age Keys:".cyan());
+                        println!("  {}", tr("list-age-keys").cyan());
                         any_age = true;
                     }
                     for key in &group.age {
-                        println!("    - {}", key.green());
+                        println!("    - {}", with_owners(key, &registry));
                     }
                 }
             }
         }
 
         if let Some(age_key) = &rule.age {
-            println!("{} {}", "  🔑 Age Key:".cyan(), age_key.green());
+            println!(
+                "  {} {}",
+                tr("list-age-key").cyan(),
+                with_owners(age_key, &registry)
+            );
+        }
+
+        match &matches.per_rule[i] {
+            None => print_error(format!("  {}", tr("list-invalid-pattern").red())),
+            Some(matched) if matched.is_empty() => {
+                print_warning(format!("  {}", tr("list-no-matches").yellow()))
+            }
+            Some(matched) => {
+                println!(
+                    "  {} {}",
+                    tr("list-matching-files").cyan(),
+                    matched.len().to_string().green()
+                );
+                for file_index in matched.iter().take(MAX_PREVIEW_FILES) {
+                    println!("    - {}", files[*file_index]);
+                }
+                if matched.len() > MAX_PREVIEW_FILES {
+                    println!(
+                        "    {}",
+                        tr_args(
+                            "list-and-more",
+                            &[("count", (matched.len() - MAX_PREVIEW_FILES).into())],
+                        )
+                        .dimmed()
+                    );
+                }
+            }
+        }
+    }
+
+    if !matches.overlaps.is_empty() {
+        println!();
+        print_warning(format!("{}", tr("list-overlaps").yellow()));
+        for (file_index, rules) in &matches.overlaps {
+            let rule_numbers: Vec<String> = rules.iter().map(|r| format!("#{}", r + 1)).collect();
+            println!("    - {} ({})", files[*file_index], rule_numbers.join(", "));
+        }
+    }
+
+    if !config.destination_rules.is_empty() {
+        println!();
+        print!("{}", tr("list-destinations").cyan());
+        for (i, rule) in config.destination_rules.iter().enumerate() {
+            println!();
+            println!(
+                "{} {}",
+                tr("list-destination").yellow(),
+                (i + 1).to_string().yellow()
+            );
+            if let Some(pattern) = &rule.path_regex {
+                println!("  {} {}", tr("list-file-pattern").cyan(), pattern.green());
+            }
+            if let Some(target) = describe(rule) {
+                println!("  {} {}", tr("list-publishes-to").cyan(), target.green());
+            }
+            if let Some(age_key) = rule.recreation_rule.as_ref().and_then(|r| r.age.as_ref()) {
+                println!(
+                    "  {} {}",
+                    tr("list-reencrypted-for").cyan(),
+                    age_key.green()
+                );
+            }
+        }
+        println!();
+        for problem in destination_problems(&config.destination_rules) {
+            print_error(format!("  {}", problem).red());
         }
     }
 
     println!();
-    print_info(format!(
-        "{}",
-        "This configuration will be used when encrypting files with SOPS.".dimmed()
-    ));
+    print_info(format!("{}", tr("list-used-by-sops").dimmed()));
 }
@@ -0,0 +1,44 @@
+use clap_mangen::Man;
+use std::io::Write;
+
+use crate::util::{i18n::tr_args, print_status::print_error};
+
+/// Prints the man page of opsops, or of the subcommand at `path`, e.g.
+/// `opsops man key upgrade > opsops-key-upgrade.1`
+pub fn man(path: Vec<String>, command: clap::Command) {
+    let mut command = command;
+    command.build();
+    let mut name = command.get_name().to_string();
+    for part in &path {
+        let Some(subcommand) = command.find_subcommand(part).cloned() else {
+            print_error(tr_args(
+                "man-no-subcommand",
+                &[
+                    ("command", name.as_str().into()),
+                    ("name", part.as_str().into()),
+                ],
+            ));
+            std::process::exit(1);
+        };
+        name = format!("{}-{}", name, subcommand.get_name());
+        command = subcommand;
+    }
+    // The page is named after the full command, like git-commit(1)
+    let command = command.name(name).version(env!("CARGO_PKG_VERSION"));
+
+    let mut page = Vec::new();
+    if let Err(e) = Man::new(command).render(&mut page) {
+        print_error(tr_args(
+            "man-render-failed",
+            &[("error", e.to_string().into())],
+        ));
+        std::process::exit(1);
+    }
+    if let Err(e) = std::io::stdout().write_all(&page) {
+        print_error(tr_args(
+            "man-write-failed",
+            &[("error", e.to_string().into())],
+        ));
+        std::process::exit(1);
+    }
+}
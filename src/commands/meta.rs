@@ -0,0 +1,127 @@
+use colored::Colorize;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use crate::util::{
+    i18n::{failed_to, tr, tr_args},
+    output_format::{OutputFormat, render_structured},
+    print_status::print_error,
+    sops_metadata::{SopsMetadata, read_metadata},
+};
+
+#[derive(Serialize)]
+struct Meta {
+    path: PathBuf,
+    /// Whether the file still carries its MAC
+    mac: bool,
+    #[serde(flatten)]
+    metadata: SopsMetadata,
+}
+
+fn show(label: &str, value: Option<String>) {
+    match value {
+        Some(value) => println!("  {:18} {}", label.cyan(), value),
+        None => println!("  {:18} {}", label.cyan(), tr("none").dimmed()),
+    }
+}
+
+/// Other key sources by name, e.g. `kms (1)`
+fn other_sources(other: &std::collections::BTreeMap<String, serde_yaml::Value>) -> Vec<String> {
+    other
+        .iter()
+        .filter_map(|(name, value)| {
+            let count = value.as_sequence().map_or(1, Vec::len);
+            (count > 0).then(|| format!("{} ({})", name, count))
+        })
+        .collect()
+}
+
+/// Prints the sops metadata of an encrypted file: recipients, when and by
+/// which sops version it was written, whether it has a MAC and which values
+/// were encrypted. Needs no key.
+pub fn meta(path: OsString, format: OutputFormat) {
+    let path = PathBuf::from(path);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            print_error(failed_to("read-failed", &path, e));
+            std::process::exit(1);
+        }
+    };
+    let Some(metadata) = read_metadata(&contents) else {
+        print_error(tr_args(
+            "meta-not-encrypted",
+            &[("path", path.display().to_string().into())],
+        ));
+        std::process::exit(1);
+    };
+
+    let meta = Meta {
+        path,
+        mac: metadata.has_mac(),
+        metadata,
+    };
+    if let Some(rendered) = render_structured(&meta, format) {
+        match rendered {
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(e) => {
+                print_error(e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let metadata = &meta.metadata;
+    println!("{}", meta.path.display().to_string().bold());
+    show(&tr("meta-sops-version"), metadata.version.clone());
+    show(&tr("meta-last-modified"), metadata.lastmodified.clone());
+    println!(
+        "  {:18} {}",
+        "mac".cyan(),
+        if meta.mac {
+            tr("meta-present").green()
+        } else {
+            tr("meta-missing").red()
+        }
+    );
+    let selector = metadata.selector();
+    if selector.is_empty() {
+        show(&tr("meta-encrypted-values"), None);
+    }
+    for (option, value) in selector {
+        show(option, Some(value));
+    }
+
+    println!("\n{}", tr("meta-recipients").bold());
+    for age in &metadata.age {
+        println!("  {:6} {}", "age".cyan(), age.recipient);
+    }
+    for pgp in &metadata.pgp {
+        println!("  {:6} {}", "pgp".cyan(), pgp.fp);
+    }
+    let other = other_sources(&metadata.other);
+    if !other.is_empty() {
+        println!("  {:6} {}", tr("meta-other").cyan(), other.join(", "));
+    }
+    for (i, group) in metadata.key_groups.iter().enumerate() {
+        println!(
+            "  {}",
+            tr_args("meta-key-group", &[("number", (i + 1).into())]).bold()
+        );
+        for age in &group.age {
+            println!("    {:6} {}", "age".cyan(), age.recipient);
+        }
+        for pgp in &group.pgp {
+            println!("    {:6} {}", "pgp".cyan(), pgp.fp);
+        }
+        let other = other_sources(&group.other);
+        if !other.is_empty() {
+            println!("    {:6} {}", tr("meta-other").cyan(), other.join(", "));
+        }
+    }
+    if let Some(threshold) = metadata.shamir_threshold {
+        show(&tr("meta-shamir-threshold"), Some(threshold.to_string()));
+    }
+}
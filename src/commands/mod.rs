@@ -1,9 +1,50 @@
+#[cfg(unix)]
+pub mod agent;
+pub mod argocd;
+pub mod bench;
+pub mod ci;
+pub mod complete;
+pub mod completions;
+pub mod cp;
 pub mod decrypt;
+pub mod diff;
 pub mod doctor;
+pub mod drift;
 pub mod edit;
 pub mod encrypt;
+pub mod escrow;
+pub mod export;
+pub mod flux;
 pub mod generate_age_key;
+pub mod grep;
+pub mod help;
+pub mod import;
+pub mod info;
 pub mod init;
+pub mod inspect;
+pub mod key;
+pub mod kubeconfig;
 pub mod list_config;
+pub mod man;
+pub mod meta;
+pub mod mv;
+pub mod new;
+pub mod paths;
+pub mod preview;
+pub mod publish;
 pub mod read;
+pub mod registry;
+pub mod replay;
+pub mod restore;
+pub mod rm;
+pub mod rule;
+pub mod scan;
+pub mod serve;
+pub mod set;
 pub mod set_key;
+pub mod setup;
+pub mod stats;
+pub mod sync;
+pub mod talos;
+pub mod teardown;
+pub mod verify;
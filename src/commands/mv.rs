@@ -0,0 +1,134 @@
+use colored::Colorize;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{
+    GlobalContext,
+    util::{
+        config_edit::rename_file_rules,
+        config_include::load_effective_config,
+        git_index::{is_tracked, record_move},
+        i18n::{failed_to, tr, tr_args},
+        print_status::{print_error, print_info, print_success, print_warning},
+        rule_match::{first_matching_rule, project_relative_path},
+        sops_config::{config_dir, read_or_create_config, write_config},
+        sops_files::is_sops_encrypted_file,
+    },
+};
+
+fn fail(message: impl Into<String>) -> ! {
+    print_error(message.into());
+    std::process::exit(1);
+}
+
+/// The creation rule sops picks for the root relative `file`, with its pattern
+fn covering_rule(file: &str, context: &GlobalContext) -> Option<(usize, Option<String>)> {
+    let rules = load_effective_config(context).ok()?.config.creation_rules;
+    let index = first_matching_rule(&rules, file)?;
+    Some((index, rules[index].path_regex.clone()))
+}
+
+/// Moves an encrypted file and points the rules written for exactly that file
+/// at its new path, with `git mv` semantics for tracked files
+pub fn mv(from: OsString, to: OsString, context: &GlobalContext) {
+    let from = PathBuf::from(from);
+    let mut to = PathBuf::from(to);
+    let Some(root) = config_dir(context) else {
+        fail(tr("no-sops-config"));
+    };
+    if !is_sops_encrypted_file(&from) {
+        fail(tr_args(
+            "mv-not-encrypted",
+            &[("path", from.display().to_string().into())],
+        ));
+    }
+    if to.is_dir() {
+        to = to.join(from.file_name().unwrap_or_default());
+    }
+    if to.exists() {
+        fail(tr_args(
+            "already-exists",
+            &[("path", to.display().to_string().into())],
+        ));
+    }
+    let (Some(from_name), Some(to_name)) = (
+        project_relative_path(&root, &from),
+        project_relative_path(&root, &to),
+    ) else {
+        fail(tr("mv-outside-project"));
+    };
+    let previous_rule = covering_rule(&from_name, context);
+    let tracked = is_tracked(&from);
+
+    if let Some(dir) = to.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).unwrap_or_else(|e| fail(failed_to("create-failed", dir, e)));
+    }
+    if let Err(e) = fs::rename(&from, &to) {
+        fail(tr_args(
+            "mv-failed",
+            &[
+                ("path", from.display().to_string().into()),
+                ("destination", to.display().to_string().into()),
+                ("error", e.to_string().into()),
+            ],
+        ));
+    }
+
+    let config = read_or_create_config(context).and_then(|config| {
+        let (config, renamed) = rename_file_rules(config, &from_name, &to_name);
+        if renamed > 0 {
+            write_config(&config, context)?;
+        }
+        Ok(renamed)
+    });
+    let renamed = match config {
+        Ok(renamed) => renamed,
+        Err(e) => {
+            // Leave everything as it was rather than a file no rule points at
+            let _ = fs::rename(&to, &from);
+            fail(tr_args("mv-config-failed", &[("error", e.into())]));
+        }
+    };
+    if tracked && let Err(e) = record_move(&from, &to) {
+        print_warning(tr_args("mv-record-move", &[("error", e.into())]));
+    }
+
+    print_success(tr_args(
+        "mv-moved",
+        &[
+            ("path", from_name.as_str().into()),
+            ("destination", to_name.as_str().into()),
+        ],
+    ));
+    if renamed > 0 {
+        print_info(tr_args("mv-updated-rules", &[("count", renamed.into())]));
+    }
+    match (previous_rule, covering_rule(&to_name, context)) {
+        (_, None) => print_warning(tr_args(
+            "mv-no-rule",
+            &[
+                ("path", to_name.as_str().into()),
+                (
+                    "command",
+                    format!("opsops target-keys {}", to.display())
+                        .yellow()
+                        .to_string()
+                        .into(),
+                ),
+            ],
+        )),
+        (Some((before, _)), Some((after, pattern))) if before != after && renamed == 0 => {
+            let every_file = tr("mv-every-file");
+            print_warning(tr_args(
+                "mv-other-rule",
+                &[
+                    ("path", to_name.as_str().into()),
+                    ("number", (after + 1).into()),
+                    ("pattern", pattern.as_deref().unwrap_or(&every_file).into()),
+                ],
+            ))
+        }
+        _ => {}
+    }
+}
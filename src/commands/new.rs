@@ -0,0 +1,171 @@
+use colored::Colorize;
+use git2::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    GlobalContext,
+    commands::init::{init_from_key, init_passphrase},
+    util::{
+        git_hooks::install_pre_commit_hook,
+        i18n::{failed_to, tr, tr_args},
+        print_status::{print_error, print_info, print_success, print_warning},
+        project_templates::ProjectTemplate,
+        rule_match::absolute_path,
+        sops_command::SopsCommandBuilder,
+    },
+};
+
+/// Scaffolds a project from `template` in `dir`: its layout, a .sops.yaml with
+/// the template's rules encrypting to `from_key` or a passphrase key, the
+/// example secrets encrypted, a CI workflow and the pre-commit hook. `dir`
+/// becomes a git repository unless it is inside one.
+pub fn new(
+    template: ProjectTemplate,
+    dir: PathBuf,
+    from_key: Option<String>,
+    passphrase: bool,
+    context: &GlobalContext,
+) {
+    let key = from_key.or_else(|| context.opitem.clone());
+    if key.is_none() && !passphrase {
+        print_error(tr("new-pass-key"));
+        std::process::exit(1);
+    }
+
+    let dir = absolute_path(&dir);
+    if let Err(e) = check_free(&dir, template) {
+        print_error(e);
+        std::process::exit(1);
+    }
+    let enclosing = dir
+        .ancestors()
+        .find(|d| d.is_dir())
+        .and_then(|d| Repository::discover(d).ok());
+    let repo = match enclosing {
+        Some(repo) => repo,
+        None => match Repository::init(&dir) {
+            Ok(repo) => {
+                print_success(tr_args(
+                    "new-created-repository",
+                    &[("path", dir.display().to_string().into())],
+                ));
+                repo
+            }
+            Err(e) => {
+                print_error(failed_to("create-failed", &dir, e));
+                std::process::exit(1);
+            }
+        },
+    };
+
+    for file in template.files() {
+        let path = dir.join(file.path);
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, file.contents));
+        if let Err(e) = result {
+            print_error(failed_to("write-failed", &path, e));
+            std::process::exit(1);
+        }
+    }
+
+    // The config of the new project, wherever opsops was started
+    let project = GlobalContext {
+        sops_file: Some(dir.join(".sops.yaml").to_string_lossy().into_owned()),
+        opitem: context.opitem.clone(),
+        age_key_env: context.age_key_env,
+        chdir: Some(dir.clone()),
+        read_only: context.read_only,
+        key_preference: context.key_preference,
+    };
+    let rules: Vec<String> = template.rules().iter().map(|r| r.to_string()).collect();
+    let initialized = match &key {
+        Some(key) => init_from_key(key, &rules, &project),
+        None => init_passphrase(&rules, &project),
+    };
+    if let Err(e) = initialized {
+        print_error(format!("{} {}", tr("init-failed").red(), e));
+        std::process::exit(1);
+    }
+
+    // Encrypting only needs the public keys, never the key itself
+    for file in template.files().iter().filter(|f| f.encrypt) {
+        let path = dir.join(file.path);
+        let status = SopsCommandBuilder::new(&project)
+            .arg("--encrypt")
+            .arg("--output")
+            .arg_path(&path)
+            .arg_path(&path)
+            .status();
+        match status {
+            Ok(status) if status.success() => {
+                println!("{} {}", "✓".green(), file.path);
+            }
+            Ok(status) => {
+                print_error(tr_args(
+                    "new-encrypt-failed",
+                    &[
+                        ("path", file.path.into()),
+                        ("status", status.to_string().into()),
+                    ],
+                ));
+                std::process::exit(1);
+            }
+            Err(e) => {
+                print_error(format!("{} {:?}", tr("sops-launch-failed").red(), e));
+                std::process::exit(1);
+            }
+        }
+    }
+
+    match install_pre_commit_hook(&repo) {
+        Ok(_) => print_success(tr("new-installed-hook")),
+        Err(e) => print_warning(tr_args(
+            "new-hook-failed",
+            &[("error", e.to_string().into())],
+        )),
+    }
+    if repo.workdir().map(absolute_path).as_deref() != Some(dir.as_path()) {
+        print_info(tr_args(
+            "new-not-repository-root",
+            &[("path", dir.display().to_string().into())],
+        ));
+    }
+
+    println!();
+    print_success(format!("{}", tr("new-created").green().bold()));
+    println!("{}\n", tr("new-next-steps"));
+    for step in template.next_steps() {
+        println!("  {}", step.yellow());
+    }
+    println!();
+}
+
+/// Fails if the project would overwrite anything in `dir`
+fn check_free(dir: &Path, template: ProjectTemplate) -> Result<(), String> {
+    if dir.join(".sops.yaml").exists() {
+        return Err(tr_args(
+            "new-has-config",
+            &[("path", dir.display().to_string().into())],
+        ));
+    }
+    let existing: Vec<&str> = template
+        .files()
+        .iter()
+        .filter(|file| dir.join(file.path).exists())
+        .map(|file| file.path)
+        .collect();
+    if existing.is_empty() {
+        Ok(())
+    } else {
+        Err(tr_args(
+            "new-refusing-to-overwrite",
+            &[
+                ("path", dir.display().to_string().into()),
+                ("files", existing.join(", ").into()),
+            ],
+        ))
+    }
+}
@@ -0,0 +1,108 @@
+use colored::Colorize;
+use git2::Repository;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::{
+    GlobalContext,
+    util::{
+        agent::socket_path,
+        dirs::{cache_dir, config_dir, project_state_dir, state_dir},
+        file_lock::locks_dir,
+        find_project_root::find_project_root,
+        i18n::tr,
+        key_registry::key_registry_path,
+        opsops_config::opsops_config_path,
+        output_format::{OutputFormat, render_structured},
+        print_status::print_error,
+        reference_cache::reference_cache_path,
+        sops_config::sops_config_path,
+        user_config::user_config_path,
+    },
+};
+
+/// Every location opsops reads or writes, `None` where it can't be determined
+#[derive(Serialize)]
+struct Paths {
+    config_dir: Option<PathBuf>,
+    user_config: Option<PathBuf>,
+    key_registry: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    reference_cache: Option<PathBuf>,
+    state_dir: Option<PathBuf>,
+    agent_socket: PathBuf,
+    project_root: Option<PathBuf>,
+    sops_config: Option<PathBuf>,
+    opsops_config: Option<PathBuf>,
+    project_state: Option<PathBuf>,
+    locks: Option<PathBuf>,
+}
+
+fn collect_paths(context: &GlobalContext) -> Paths {
+    let project_root = find_project_root(context);
+    let locks = project_root
+        .as_ref()
+        .and_then(|root| Repository::open(root).ok())
+        .map(|repo| locks_dir(&repo));
+
+    Paths {
+        config_dir: config_dir(),
+        user_config: user_config_path(),
+        key_registry: key_registry_path(),
+        cache_dir: cache_dir(),
+        reference_cache: reference_cache_path(),
+        state_dir: state_dir(),
+        agent_socket: socket_path(),
+        sops_config: sops_config_path(context),
+        opsops_config: opsops_config_path(context),
+        project_state: project_root.as_deref().map(project_state_dir),
+        locks,
+        project_root,
+    }
+}
+
+fn show(label: &str, path: &Option<PathBuf>) {
+    match path {
+        Some(path) => {
+            let exists = if path.exists() {
+                String::new()
+            } else {
+                format!(" {}", tr("paths-not-created").dimmed())
+            };
+            println!("  {:18} {}{}", label.cyan(), path.display(), exists);
+        }
+        None => println!("  {:18} {}", label.cyan(), tr("unknown").dimmed()),
+    }
+}
+
+/// Prints where opsops keeps its configuration, caches, state and locks
+pub fn paths(context: &GlobalContext, format: OutputFormat) {
+    let paths = collect_paths(context);
+
+    if let Some(rendered) = render_structured(&paths, format) {
+        match rendered {
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(e) => {
+                print_error(e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    println!("{}", tr("paths-user").bold());
+    show(&tr("paths-config-dir"), &paths.config_dir);
+    show(&tr("user-config"), &paths.user_config);
+    show(&tr("paths-key-registry"), &paths.key_registry);
+    show(&tr("paths-cache-dir"), &paths.cache_dir);
+    show(&tr("paths-reference-cache"), &paths.reference_cache);
+    show(&tr("paths-state-dir"), &paths.state_dir);
+    show(&tr("paths-agent-socket"), &Some(paths.agent_socket));
+
+    println!("\n{}", tr("paths-project").bold());
+    show(&tr("paths-root"), &paths.project_root);
+    show(".sops.yaml", &paths.sops_config);
+    show(".opsops.yaml", &paths.opsops_config);
+    show(&tr("paths-state"), &paths.project_state);
+    show(&tr("paths-locks"), &paths.locks);
+}
@@ -0,0 +1,98 @@
+use colored::Colorize;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use crate::{
+    GlobalContext,
+    util::{
+        config_include::load_effective_config,
+        document::parse_document,
+        encrypted_keys::{KeySelector, preview as preview_keys, print_preview},
+        i18n::{failed_to, tr, tr_args},
+        print_status::{print_error, print_file_error},
+        rule_match::{first_matching_rule, project_relative_path},
+        sops_config::config_dir,
+        sops_files::{is_sops_encrypted, sops_file_type},
+    },
+};
+
+/// Shows which values of a plaintext file the matching rule would encrypt,
+/// without encrypting anything
+pub fn preview(path: OsString, context: &GlobalContext) {
+    let path = Path::new(&path);
+    let contents = match fs::read(path) {
+        Ok(c) => c,
+        Err(e) => {
+            print_error(failed_to("read-failed", path, e));
+            std::process::exit(1);
+        }
+    };
+    if is_sops_encrypted(&String::from_utf8_lossy(&contents)) {
+        print_error(format!(
+            "{} {}",
+            path.display(),
+            tr("preview-already-encrypted").red()
+        ));
+        std::process::exit(1);
+    }
+
+    let config = match load_effective_config(context) {
+        Ok(c) => c.config,
+        Err(e) => {
+            print_error(format!("{} {}", tr("sops-config-error").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let Some(relative) = config_dir(context).and_then(|root| project_relative_path(&root, path))
+    else {
+        print_error(tr("file-outside-project"));
+        std::process::exit(1);
+    };
+    let Some(index) = first_matching_rule(&config.creation_rules, &relative) else {
+        print_file_error(
+            format!("{} {}", tr("preview-no-rule").red(), relative),
+            &relative,
+        );
+        std::process::exit(1);
+    };
+    let rule = &config.creation_rules[index];
+
+    let selector = match KeySelector::from_rule(rule) {
+        Ok(s) => s,
+        Err(e) => {
+            print_error(format!(
+                "{}: {}",
+                tr_args("rule-number", &[("number", (index + 1).into())]).red(),
+                e
+            ));
+            std::process::exit(1);
+        }
+    };
+    let document = match parse_document(&contents, sops_file_type(path)) {
+        Ok(d) => d,
+        Err(e) => {
+            print_error(e);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "{} {} {}",
+        tr_args("rule-number", &[("number", (index + 1).into())]).bold(),
+        rule.path_regex.as_deref().unwrap_or("<no path_regex>"),
+        describe(&selector).dimmed()
+    );
+    println!();
+
+    print_preview(&preview_keys(&document, &selector));
+}
+
+fn describe(selector: &KeySelector) -> String {
+    match selector {
+        KeySelector::EncryptedRegex(r) => format!("(encrypted_regex: {})", r),
+        KeySelector::UnencryptedRegex(r) => format!("(unencrypted_regex: {})", r),
+        KeySelector::EncryptedSuffix(s) => format!("(encrypted_suffix: {})", s),
+        KeySelector::UnencryptedSuffix(s) => format!("(unencrypted_suffix: {})", s),
+    }
+}
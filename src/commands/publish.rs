@@ -0,0 +1,99 @@
+use colored::Colorize;
+use std::path::PathBuf;
+
+use crate::{
+    GlobalContext,
+    util::{
+        config_include::load_effective_config,
+        destinations::{describe, destination_problems, first_matching_destination},
+        i18n::{tr, tr_args},
+        print_status::{print_error, print_info, print_success},
+        rule_match::project_relative_path,
+        sops_command::SopsCommandBuilder,
+        sops_config::config_dir,
+        sops_files::is_sops_encrypted_file,
+    },
+};
+
+/// Pushes an encrypted file to the destination its `destination_rules` entry
+/// names, via `sops publish` with the key from 1Password
+pub fn publish(path: String, yes: bool, omit_extensions: bool, context: &GlobalContext) {
+    let file = PathBuf::from(&path);
+    if !is_sops_encrypted_file(&file) {
+        print_error(format!("{} {}", path, tr("publish-not-encrypted").red()));
+        std::process::exit(1);
+    }
+
+    let config = match load_effective_config(context) {
+        Ok(c) => c.config,
+        Err(e) => {
+            print_error(format!("{} {}", tr("sops-config-error").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let problems = destination_problems(&config.destination_rules);
+    if !problems.is_empty() {
+        print_error(tr("publish-invalid-destinations").red());
+        for problem in problems {
+            println!("    {}", problem);
+        }
+        std::process::exit(1);
+    }
+    let Some(relative) = config_dir(context).and_then(|root| project_relative_path(&root, &file))
+    else {
+        print_error(tr("file-outside-project"));
+        std::process::exit(1);
+    };
+    let Some(index) = first_matching_destination(&config.destination_rules, &relative) else {
+        print_error(format!("{} {}", tr("publish-no-rule").red(), relative));
+        std::process::exit(1);
+    };
+    let destination = &config.destination_rules[index];
+    print_info(tr_args(
+        "publish-publishing",
+        &[
+            ("path", relative.as_str().into()),
+            (
+                "destination",
+                describe(destination)
+                    .unwrap_or_default()
+                    .cyan()
+                    .to_string()
+                    .into(),
+            ),
+        ],
+    ));
+
+    let mut sops_command = SopsCommandBuilder::new(context).arg("publish");
+    if yes {
+        sops_command = sops_command.arg("--yes");
+    }
+    if omit_extensions {
+        sops_command = sops_command.arg("--omit-extensions");
+    }
+    let sops_command = match sops_command.arg_path(&file).with_age_key() {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            print_error(format!("{} {}", tr("age-key-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+
+    match sops_command.status() {
+        Ok(status) if status.success() => {
+            print_success(format!("{}", tr("publish-published").green()));
+        }
+        Ok(status) => {
+            print_error(format!(
+                "{} {}",
+                tr("publish-failed").red(),
+                tr_args("exit-code", &[("status", status.to_string().into())])
+            ));
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        Err(e) => {
+            print_error(format!("{} {}", tr("sops-launch-failed").red(), e));
+            std::process::exit(1);
+        }
+    }
+}
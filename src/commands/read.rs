@@ -1,47 +1,187 @@
-use std::{ffi::OsString, path::Path};
+use std::{
+    ffi::OsString,
+    io::{self, Write},
+    path::Path,
+};
 
 use colored::Colorize;
+use serde_yaml::Value;
+use zeroize::Zeroize;
 
 use crate::{
     GlobalContext,
-    util::{print_status::print_error, sops_command::SopsCommandBuilder},
+    util::{
+        advice::advice_for,
+        document::{extract, parse_document, redact, render_document},
+        document_includes::read_with_includes,
+        file_picker::pick_file,
+        formatting::{Formatting, resolve_formatting},
+        i18n::{tr, tr_args},
+        markdown::{decrypt_note, is_markdown},
+        output_format::{OutputFormat, render_structured},
+        print_status::{print_advice, print_error},
+        sops_command::decrypt_in_memory_with_args,
+        sops_files::sops_file_type,
+    },
 };
 
-pub fn read(path: OsString, context: &GlobalContext) {
+/// Post-processing applied to the decrypted content
+pub struct ReadOptions {
+    pub format: OutputFormat,
+    /// Only print the value at this key path
+    pub extract: Option<String>,
+    /// Replace every value with a placeholder
+    pub redact: bool,
+    /// `--indent` and `--sort-keys`
+    pub formatting: Formatting,
+    /// Inline the files referenced with `!opsops-include` or `$opsopsInclude`
+    pub resolve_includes: bool,
+}
+
+impl ReadOptions {
+    /// Whether the content is printed exactly as sops returned it
+    fn passthrough(&self) -> bool {
+        self.format == OutputFormat::Text
+            && self.extract.is_none()
+            && !self.redact
+            && !self.resolve_includes
+    }
+}
+
+pub fn read(path: Option<OsString>, options: ReadOptions, context: &GlobalContext) {
+    // Without a path, let the user pick one of the files matched by a rule
+    let path = path.unwrap_or_else(|| pick_file(context, "read"));
+
     // Convert the path from OsString to String
     let path_str = match path.into_string() {
         Ok(p) => p,
         Err(os) => {
-            print_error(format!("{} {:?}", "Invalid UTF-8 in path:".red(), os));
+            print_error(format!("{} {:?}", tr("invalid-utf8-path").red(), os));
             std::process::exit(1);
         }
     };
 
     // Check if the file exists
     if !Path::new(&path_str).is_file() {
-        print_error(format!("{} {}", "File not found:".red(), path_str));
+        print_error(format!("{} {}", tr("file-not-found").red(), path_str));
         std::process::exit(1);
     }
 
-    let sops_command = match SopsCommandBuilder::new(context)
-        .arg("-d")
-        .arg(&path_str)
-        .with_age_key()
-    {
-        Ok(cmd) => cmd,
+    if is_markdown(Path::new(&path_str)) {
+        read_note(&path_str, &options, context);
+        return;
+    }
+
+    let formatting = match resolve_formatting(&options.formatting, context) {
+        Ok(formatting) => formatting,
         Err(e) => {
-            print_error(format!("{} {}", "Failed to get Age key:".red(), e));
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    };
+    let file_type = sops_file_type(Path::new(&path_str));
+
+    if options.resolve_includes {
+        let result = read_with_includes(Path::new(&path_str), context)
+            .and_then(|document| process_document(document, file_type, &options, &formatting))
+            .map(|rendered| println!("{}", rendered));
+        if let Err(e) = result {
+            print_error(format!("{} {}", tr("read-file-failed").red(), e));
             std::process::exit(1);
         }
+        return;
+    }
+
+    // The plaintext only ever exists in this buffer and the pipe it was read from
+    let plaintext = match decrypt_in_memory_with_args(
+        Path::new(&path_str),
+        None,
+        &formatting.sops_args(file_type),
+        context,
+    ) {
+        Ok(plaintext) => plaintext,
+        Err(e) => {
+            // sops' own message, as if it was run directly
+            eprintln!("{}", e);
+            if let Some(advice) = advice_for(&e.message, Some(&path_str)) {
+                print_advice(advice);
+            }
+            std::process::exit(e.code);
+        }
+    };
+
+    let result = if options.passthrough() {
+        formatting
+            .canonicalize(&plaintext, file_type)
+            .and_then(|canonical| {
+                io::stdout()
+                    .write_all(canonical.as_deref().unwrap_or(&plaintext))
+                    .map_err(|e| tr_args("output-write-failed", &[("error", e.to_string().into())]))
+            })
+    } else {
+        post_process(&plaintext, file_type, &options, &formatting)
+            .map(|rendered| println!("{}", rendered))
     };
 
-    match sops_command.status() {
-        Ok(status) => {
-            std::process::exit(status.code().unwrap_or(1));
+    if let Err(e) = result {
+        print_error(format!("{} {}", tr("read-file-failed").red(), e));
+        std::process::exit(1);
+    }
+}
+
+/// Prints a decrypted note. Only the front matter is structured, so the
+/// post-processing options don't apply to notes.
+fn read_note(path: &str, options: &ReadOptions, context: &GlobalContext) {
+    if !options.passthrough() {
+        print_error(format!("{} {}", tr("read-note-options").red(), path));
+        std::process::exit(1);
+    }
+    let result = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| decrypt_note(&contents, Path::new(path), context));
+    match result {
+        Ok(mut note) => {
+            print!("{}", note);
+            note.zeroize();
         }
         Err(e) => {
-            print_error(format!("{} {:?}", "Failed to launch sops:".red(), e));
+            print_error(format!("{} {}", tr("read-file-failed").red(), e));
             std::process::exit(1);
         }
     }
 }
+
+fn post_process(
+    content: &[u8],
+    file_type: &str,
+    options: &ReadOptions,
+    formatting: &Formatting,
+) -> Result<String, String> {
+    process_document(
+        parse_document(content, file_type)?,
+        file_type,
+        options,
+        formatting,
+    )
+}
+
+/// Applies `--redact` and `--extract` and renders the result in `--format`
+fn process_document(
+    mut document: Value,
+    file_type: &str,
+    options: &ReadOptions,
+    formatting: &Formatting,
+) -> Result<String, String> {
+    formatting.apply(&mut document);
+    if options.redact {
+        redact(&mut document);
+    }
+    let selected = match &options.extract {
+        Some(key_path) => extract(&document, key_path)?,
+        None => &document,
+    };
+    match render_structured(selected, options.format) {
+        Some(rendered) => rendered.map(|s| s.trim_end().to_string()),
+        None => render_document(selected, file_type),
+    }
+}
@@ -0,0 +1,89 @@
+use colored::Colorize;
+
+use crate::{
+    GlobalContext,
+    util::{
+        i18n::{tr, tr_args},
+        member_registry::{
+            MemberRegistry, RegistrySource, read_member_registry, write_member_registry,
+        },
+        opsops_config::read_opsops_config,
+        print_status::{print_error, print_info, print_success},
+    },
+};
+
+/// Pulls the team's registry of public keys and caches it. The source is
+/// `source`, else `member_registry` from `.opsops.yaml`, else the source of
+/// the last sync.
+pub fn sync(source: Option<String>, context: &GlobalContext) {
+    let cached = match read_member_registry() {
+        Ok(registry) => registry,
+        Err(e) => {
+            print_error(format!("{} {}", tr("registry-read-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let Some(source) = source
+        .or_else(|| {
+            read_opsops_config(context)
+                .ok()
+                .flatten()
+                .and_then(|c| c.member_registry)
+        })
+        .or_else(|| cached.source.clone())
+    else {
+        print_error(format!(
+            "{} {}",
+            tr("registry-no-source").red(),
+            tr("registry-no-source-hint").dimmed()
+        ));
+        std::process::exit(1);
+    };
+
+    print_info(tr_args(
+        "registry-syncing",
+        &[("source", source.cyan().to_string().into())],
+    ));
+    let registry = RegistrySource::parse(&source)
+        .and_then(|s| s.fetch())
+        .and_then(|yaml| MemberRegistry::parse(&yaml));
+    let mut registry = match registry {
+        Ok(registry) => registry,
+        Err(e) => {
+            print_error(format!("{} {}", tr("registry-sync-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+    registry.source = Some(source);
+
+    for (name, member) in &registry.members {
+        match cached.members.get(name) {
+            None => println!("  {} {}", "+".green(), name),
+            Some(old) if old.age != member.age => {
+                println!("  {} {} {}", "~".yellow(), name, tr("registry-new-key"))
+            }
+            Some(_) => {}
+        }
+    }
+    for name in cached
+        .members
+        .keys()
+        .filter(|name| !registry.members.contains_key(*name))
+    {
+        println!("  {} {}", "-".red(), name);
+    }
+
+    match write_member_registry(&registry) {
+        Ok(path) => print_success(tr_args(
+            "registry-synced",
+            &[
+                ("count", registry.members.len().into()),
+                ("path", path.display().to_string().into()),
+            ],
+        )),
+        Err(e) => {
+            print_error(format!("{} {}", tr("registry-write-failed").red(), e));
+            std::process::exit(1);
+        }
+    }
+}
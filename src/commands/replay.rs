@@ -0,0 +1,128 @@
+use colored::Colorize;
+use std::path::PathBuf;
+
+use crate::util::{
+    backups::format_timestamp,
+    i18n::{tr, tr_args},
+    print_status::print_error,
+    session_record::{Invocation, read_session},
+};
+
+fn seconds(ms: u64) -> String {
+    format!("{:.3}s", ms as f64 / 1000.0)
+}
+
+fn show_invocation(number: usize, invocation: &Invocation) {
+    let outcome = match (&invocation.error, invocation.exit_code) {
+        (Some(error), _) => {
+            tr_args("replay-start-failed", &[("error", error.as_str().into())]).red()
+        }
+        (None, Some(0)) => tr_args("replay-exit", &[("code", 0.into())]).green(),
+        (None, Some(code)) => tr_args("replay-exit", &[("code", code.to_string().into())]).red(),
+        (None, None) => tr("replay-killed").red(),
+    };
+    println!(
+        "{:>3}. {} {}",
+        number,
+        format!("+{}", seconds(invocation.started_ms)).dimmed(),
+        invocation.command.join(" ")
+    );
+    println!(
+        "     {}",
+        tr_args(
+            "replay-took",
+            &[
+                ("outcome", outcome.to_string().into()),
+                ("duration", seconds(invocation.duration_ms).into()),
+            ],
+        )
+    );
+    if let Some(dir) = &invocation.dir {
+        println!("     {} {}", "dir".cyan(), dir.display());
+    }
+    if !invocation.env.is_empty() {
+        println!("     {} {}", "env".cyan(), invocation.env.join(", "));
+    }
+}
+
+/// Prints a session recorded with `--record`, for maintainers following what
+/// opsops ran on someone else's machine
+pub fn replay(path: PathBuf) {
+    let session = match read_session(&path) {
+        Ok(session) => session,
+        Err(e) => {
+            print_error(e);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "{} {}",
+        tr("replay-recorded").bold(),
+        format_timestamp(session.started_at)
+    );
+    println!(
+        "  {:10} {}",
+        tr("replay-command").cyan(),
+        session.command.join(" ")
+    );
+    println!(
+        "  {:10} {}{}",
+        "opsops".cyan(),
+        session.version,
+        session
+            .build
+            .map(|build| format!(" ({})", build))
+            .unwrap_or_default()
+    );
+    println!(
+        "  {:10} {}-{}",
+        tr("replay-platform").cyan(),
+        session.environment.os,
+        session.environment.arch
+    );
+    for (tool, version) in &session.environment.tools {
+        match version {
+            Some(version) => println!("  {:10} {}", tool.cyan(), version),
+            None => println!("  {:10} {}", tool.cyan(), tr("not-found").red()),
+        }
+    }
+    if !session.environment.variables.is_empty() {
+        println!(
+            "  {:10} {}",
+            tr("replay-variables").cyan(),
+            session.environment.variables.join(", ")
+        );
+    }
+
+    println!(
+        "\n{} ({})",
+        tr("replay-external-commands").bold(),
+        session.invocations.len()
+    );
+    for (i, invocation) in session.invocations.iter().enumerate() {
+        show_invocation(i + 1, invocation);
+    }
+
+    if !session.errors.is_empty() {
+        println!("\n{}", tr("replay-errors").bold());
+        for error in &session.errors {
+            println!("  {}", error.red());
+        }
+    }
+
+    println!();
+    match (session.exit_code, session.duration_ms) {
+        (Some(code), Some(ms)) => println!(
+            "{}",
+            tr_args(
+                "replay-exited",
+                &[
+                    ("code", code.to_string().into()),
+                    ("duration", seconds(ms).into()),
+                ],
+            )
+        ),
+        _ => println!("{}", tr("replay-exited-early").yellow()),
+    }
+}
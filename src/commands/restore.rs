@@ -0,0 +1,86 @@
+use colored::Colorize;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use crate::{
+    GlobalContext,
+    util::{
+        backups::{backup_ciphertext, list_backups},
+        find_project_root::find_project_root,
+        i18n::{failed_to, tr, tr_args},
+        print_status::{print_error, print_info, print_success},
+    },
+};
+
+/// Rolls `path` back to a backup taken before opsops overwrote it, the latest
+/// one or the latest one whose timestamp starts with `at`
+pub fn restore(path: OsString, at: Option<String>, list: bool, context: &GlobalContext) {
+    let path = Path::new(&path);
+    let Some(root) = find_project_root(context) else {
+        print_error(tr("no-project-root"));
+        std::process::exit(1);
+    };
+
+    let backups = list_backups(&root, path, context);
+    if backups.is_empty() {
+        print_error(format!(
+            "{} {}",
+            tr("restore-no-backups").red(),
+            path.display()
+        ));
+        std::process::exit(1);
+    }
+
+    if list {
+        for (timestamp, backup) in &backups {
+            println!("{}  {}", timestamp.cyan(), backup.display());
+        }
+        return;
+    }
+
+    let Some((timestamp, backup)) = backups
+        .iter()
+        .rev()
+        .find(|(timestamp, _)| at.as_deref().is_none_or(|at| timestamp.starts_with(at)))
+    else {
+        print_error(format!(
+            "{} {}",
+            tr_args(
+                "restore-no-backup-at",
+                &[
+                    ("path", path.display().to_string().into()),
+                    ("at", at.unwrap_or_default().into()),
+                ],
+            )
+            .red(),
+            tr("restore-list-hint").dimmed()
+        ));
+        std::process::exit(1);
+    };
+
+    // Keep the current ciphertext, so the restore itself can be undone
+    match backup_ciphertext(&root, path, context) {
+        Ok(Some(current)) => print_info(tr_args(
+            "restore-backed-up",
+            &[("path", current.display().to_string().into())],
+        )),
+        Ok(None) => {}
+        Err(e) => {
+            print_error(e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Err(e) = fs::copy(backup, path) {
+        print_error(failed_to("restore-failed", path, e));
+        std::process::exit(1);
+    }
+    print_success(tr_args(
+        "restore-restored",
+        &[
+            ("path", path.display().to_string().into()),
+            ("timestamp", timestamp.as_str().into()),
+        ],
+    ));
+}
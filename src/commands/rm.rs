@@ -0,0 +1,199 @@
+use colored::Colorize;
+use std::ffi::OsString;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    GlobalContext,
+    util::{
+        audit_log::{AuditEntry, append},
+        backups::backup_ciphertext,
+        config_edit::remove_file_rules,
+        decrypted_copies::{copies_of, forget, shred},
+        git_index::{is_tracked, record_removal},
+        i18n::{failed_to, tr, tr_args},
+        print_status::{print_error, print_info, print_success, print_warning},
+        prompts::confirm,
+        rule_match::project_relative_path,
+        sops_config::{config_dir, read_or_create_config, write_config},
+        sops_files::is_sops_encrypted_file,
+        sops_metadata::read_metadata,
+    },
+};
+
+fn fail(message: impl Into<String>) -> ! {
+    print_error(message.into());
+    std::process::exit(1);
+}
+
+/// Plaintext copies of `file`: the ones `decrypt` recorded and the sibling it
+/// would decrypt to, if that exists and isn't encrypted itself
+fn plaintext_copies(root: &Path, file: &Path) -> Vec<PathBuf> {
+    let mut copies = copies_of(root, file);
+    let sibling = file
+        .to_str()
+        .and_then(|name| name.strip_suffix(".enc"))
+        .map(PathBuf::from);
+    if let Some(sibling) = sibling
+        && sibling.is_file()
+        && !is_sops_encrypted_file(&sibling)
+    {
+        copies.push(sibling);
+    }
+    copies.retain(|copy| copy.is_file());
+    copies.sort();
+    copies.dedup_by(|a, b| fs::canonicalize(&*a).ok() == fs::canonicalize(&*b).ok());
+    copies
+}
+
+/// The recipients that could decrypt `contents`, age recipients and PGP
+/// fingerprints
+fn recipients(contents: &str) -> Vec<String> {
+    let Some(metadata) = read_metadata(contents) else {
+        return Vec::new();
+    };
+    let mut recipients = metadata.age_recipients();
+    let pgp = metadata
+        .pgp
+        .iter()
+        .chain(metadata.key_groups.iter().flat_map(|group| &group.pgp));
+    recipients.extend(pgp.map(|key| key.fp.clone()));
+    recipients.sort();
+    recipients.dedup();
+    recipients
+}
+
+/// Deletes an encrypted file together with its plaintext copies and, once
+/// confirmed, the rules written for exactly that file. The ciphertext is backed
+/// up first so `restore` can bring it back, and the deletion is recorded in the
+/// project's audit log.
+pub fn rm(path: OsString, yes: bool, context: &GlobalContext) {
+    let path = PathBuf::from(path);
+    let Some(root) = config_dir(context) else {
+        fail(tr("no-sops-config"));
+    };
+    if !is_sops_encrypted_file(&path) {
+        fail(tr_args(
+            "rm-not-encrypted",
+            &[("path", path.display().to_string().into())],
+        ));
+    }
+    let Some(name) = project_relative_path(&root, &path) else {
+        fail(tr("outside-project"));
+    };
+    let contents =
+        fs::read_to_string(&path).unwrap_or_else(|e| fail(failed_to("read-failed", &path, e)));
+    let recipients = recipients(&contents);
+    let copies = plaintext_copies(&root, &path);
+    let tracked = is_tracked(&path);
+
+    if let Err(e) = backup_ciphertext(&root, &path, context) {
+        fail(e);
+    }
+    if let Err(e) = fs::remove_file(&path) {
+        fail(failed_to("delete-failed", &path, e));
+    }
+    if tracked && let Err(e) = record_removal(&path) {
+        print_warning(tr_args(
+            "rm-record-removal",
+            &[("error", e.into()), ("name", name.as_str().into())],
+        ));
+    }
+    print_success(tr_args("rm-deleted", &[("name", name.as_str().into())]));
+
+    for copy in &copies {
+        match shred(copy) {
+            Ok(()) => {
+                print_success(tr_args(
+                    "rm-shredded-copy",
+                    &[("path", copy.display().to_string().into())],
+                ));
+                if let Err(e) = forget(&root, copy) {
+                    print_warning(tr_args("tracking-failed", &[("error", e.into())]));
+                }
+            }
+            Err(e) => print_warning(failed_to("rm-shred-failed", copy, e)),
+        }
+    }
+
+    let mut pruned = Vec::new();
+    match read_or_create_config(context) {
+        Ok(config) => {
+            let (pruned_config, patterns) = remove_file_rules(config, &name);
+            let consent = !patterns.is_empty()
+                && (yes
+                    || std::io::stdin().is_terminal()
+                        && confirm(
+                            &tr_args(
+                                "rm-remove-rules",
+                                &[
+                                    ("count", patterns.len().into()),
+                                    ("name", name.as_str().into()),
+                                ],
+                            ),
+                            true,
+                        )
+                        .unwrap_or(false));
+            if consent {
+                match write_config(&pruned_config, context) {
+                    Ok(()) => pruned = patterns,
+                    Err(e) => print_warning(tr_args(
+                        "sops-config-update-failed",
+                        &[("error", e.to_string().into())],
+                    )),
+                }
+            } else if !patterns.is_empty() {
+                print_info(tr_args("rm-kept-rules", &[("name", name.as_str().into())]));
+            }
+        }
+        Err(e) => print_warning(tr_args(
+            "sops-config-read-failed",
+            &[("error", e.to_string().into())],
+        )),
+    }
+    if !pruned.is_empty() {
+        print_info(tr_args(
+            "rm-removed-rules",
+            &[("count", pruned.len().into())],
+        ));
+    }
+
+    let mut entry = AuditEntry::new("rm", &name);
+    entry.recipients = recipients.clone();
+    entry.note = Some(format!(
+        "shredded {} plaintext cop{}, removed {} rule(s)",
+        copies.len(),
+        if copies.len() == 1 { "y" } else { "ies" },
+        pruned.len()
+    ));
+    if let Err(e) = append(&root, &entry) {
+        print_warning(tr_args("audit-log-failed", &[("error", e.into())]));
+    }
+
+    print_info(tr_args(
+        "rm-undo",
+        &[(
+            "command",
+            format!("opsops restore {}", path.display())
+                .yellow()
+                .to_string()
+                .into(),
+        )],
+    ));
+    if !recipients.is_empty() {
+        let id = if tracked {
+            "rm-access-kept-in-history"
+        } else {
+            "rm-access-kept"
+        };
+        print_warning(tr_args(
+            id,
+            &[
+                ("count", recipients.len().into()),
+                ("name", name.as_str().into()),
+                ("recipients", recipients.join(", ").into()),
+            ],
+        ));
+    }
+}
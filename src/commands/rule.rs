@@ -0,0 +1,41 @@
+use colored::Colorize;
+use regex::Regex;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use crate::{
+    GlobalContext,
+    util::{
+        document::parse_document,
+        encrypted_keys::{KeySelector, preview, print_preview},
+        i18n::{failed_to, tr},
+        print_status::print_error,
+        sops_files::sops_file_type,
+    },
+};
+
+/// Lists which values of the document at `path` an `encrypted_regex` of `regex`
+/// would encrypt
+pub fn test(regex: String, path: OsString, _context: &GlobalContext) {
+    let regex = match Regex::new(&regex) {
+        Ok(r) => r,
+        Err(e) => {
+            print_error(format!("{} {}", tr("invalid-regex").red(), e));
+            std::process::exit(1);
+        }
+    };
+    let path = Path::new(&path);
+    let document = match fs::read(path)
+        .map_err(|e| failed_to("read-failed", path, e))
+        .and_then(|contents| parse_document(&contents, sops_file_type(path)))
+    {
+        Ok(d) => d,
+        Err(e) => {
+            print_error(e);
+            std::process::exit(1);
+        }
+    };
+
+    print_preview(&preview(&document, &KeySelector::EncryptedRegex(regex)));
+}
@@ -0,0 +1,85 @@
+use colored::Colorize;
+use git2::Repository;
+
+use crate::{
+    GlobalContext,
+    util::{
+        find_project_root::find_project_root,
+        i18n::{tr, tr_args},
+        output_format::FindingsFormat,
+        print_status::{print_error, print_success},
+        sarif::{Finding, SarifRule, to_sarif},
+        secret_scan::{LeakFinding, scan_repository},
+    },
+};
+
+const COMMITTED_KEY: SarifRule = SarifRule {
+    id: "committed-private-key",
+    description: "A private key is committed to the repository",
+};
+
+fn describe(finding: &LeakFinding) -> String {
+    match finding.commit {
+        Some(commit) => format!(
+            "{} contains {} (commit {})",
+            finding.path,
+            finding.kind,
+            &commit.to_string()[..8]
+        ),
+        None => format!("{} contains {}", finding.path, finding.kind),
+    }
+}
+
+/// Scans tracked files and the last `history` commits for private keys, the
+/// check `doctor` runs, without needing the key from 1Password. Exits with 1
+/// if anything was found.
+pub fn scan(history: usize, format: FindingsFormat, context: &GlobalContext) {
+    let Some(repo) = find_project_root(context).and_then(|root| Repository::open(root).ok()) else {
+        print_error(tr("scan-no-repository"));
+        std::process::exit(1);
+    };
+    let findings = match scan_repository(&repo, None, history) {
+        Ok(findings) => findings,
+        Err(e) => {
+            print_error(format!("{} {}", tr("scan-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+
+    match format {
+        FindingsFormat::Sarif => {
+            let results: Vec<Finding> = findings
+                .iter()
+                .map(|finding| Finding {
+                    rule_id: COMMITTED_KEY.id,
+                    message: describe(finding),
+                    path: finding.path.clone(),
+                    line: Some(finding.line),
+                })
+                .collect();
+            match to_sarif(&[COMMITTED_KEY], &results) {
+                Ok(sarif) => println!("{}", sarif),
+                Err(e) => {
+                    print_error(e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        FindingsFormat::Text => {
+            for finding in &findings {
+                println!("{} {}:{}", "✗".red(), finding.path, finding.line);
+                println!("    {}", describe(finding));
+            }
+            if findings.is_empty() {
+                print_success(tr_args("scan-clean", &[("history", history.into())]));
+            }
+        }
+    }
+
+    if !findings.is_empty() {
+        if format == FindingsFormat::Text {
+            print_error(tr("scan-committed").red());
+        }
+        std::process::exit(1);
+    }
+}
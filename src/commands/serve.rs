@@ -0,0 +1,314 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::{
+    GlobalContext,
+    util::{
+        config_include::load_effective_config,
+        escrow::missing_escrow_reason,
+        i18n::tr_args,
+        print_status::print_error,
+        protected_files::protected_reason,
+        rule_match::{absolute_path, first_matching_rule, project_relative_path},
+        sops_command::run_sops_on_buffer,
+        sops_config::config_dir,
+        sops_files::{is_sops_encrypted_file, sops_file_type},
+    },
+};
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct BufferParams {
+    /// Path of the file the buffer belongs to, used for rule matching and file type
+    path: String,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct PathParams {
+    path: String,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Serves a newline-delimited JSON-RPC 2.0 API on stdin/stdout for editor integrations
+pub fn serve(context: &GlobalContext) {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => {
+                print_error(tr_args(
+                    "serve-stdin-failed",
+                    &[("error", e.to_string().into())],
+                ));
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = handle_line(&line, context)
+            && (writeln!(stdout, "{}", response).is_err() || stdout.flush().is_err())
+        {
+            break;
+        }
+    }
+}
+
+/// Handles one request line, returning the serialized response.
+/// Notifications (requests without an id) produce no response.
+fn handle_line(line: &str, context: &GlobalContext) -> Option<String> {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(v) => v,
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, e.to_string()),
+            ));
+        }
+    };
+    let request: Request = match serde_json::from_value(value) {
+        Ok(r) => r,
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(INVALID_REQUEST, e.to_string()),
+            ));
+        }
+    };
+    if request.jsonrpc != "2.0" {
+        return Some(error_response(
+            request.id.unwrap_or(Value::Null),
+            RpcError::new(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported"),
+        ));
+    }
+
+    let result = dispatch(&request.method, request.params, context);
+    let id = request.id?;
+
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string(),
+        Err(err) => error_response(id, err),
+    })
+}
+
+fn error_response(id: Value, err: RpcError) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": err.code, "message": err.message },
+    })
+    .to_string()
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn dispatch(method: &str, params: Value, context: &GlobalContext) -> Result<Value, RpcError> {
+    match method {
+        "decrypt" => transform_buffer(parse_params(params)?, "--decrypt", context),
+        "encrypt" => {
+            let params: BufferParams = parse_params(params)?;
+            if let Some(reason) = protected_reason(Path::new(&params.path), context) {
+                return Err(RpcError::new(
+                    SERVER_ERROR,
+                    format!("Refusing to encrypt {}: {}", params.path, reason),
+                ));
+            }
+            if let Some(reason) = missing_escrow_reason(Path::new(&params.path), context) {
+                return Err(RpcError::new(
+                    SERVER_ERROR,
+                    format!("Refusing to encrypt {}: {}", params.path, reason),
+                ));
+            }
+            transform_buffer(params, "--encrypt", context)
+        }
+        "ruleForPath" => rule_for_path(parse_params(params)?, context),
+        "status" => status(parse_params(params)?, context),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method: {}", method),
+        )),
+    }
+}
+
+/// Pipes a buffer through sops, returning the transformed content
+fn transform_buffer(
+    params: BufferParams,
+    action: &str,
+    context: &GlobalContext,
+) -> Result<Value, RpcError> {
+    let path = Path::new(&params.path);
+    let content = Zeroizing::new(
+        run_sops_on_buffer(action, sops_file_type(path), path, &params.content, context)
+            .map_err(|e| RpcError::new(SERVER_ERROR, e))?,
+    );
+    Ok(json!({ "content": content.as_str() }))
+}
+
+/// Finds the creation rule sops would apply to a path
+fn rule_for_path(params: PathParams, context: &GlobalContext) -> Result<Value, RpcError> {
+    let config = load_effective_config(context)
+        .map_err(|e| RpcError::new(SERVER_ERROR, e))?
+        .config;
+    let root = config_dir(context)
+        .ok_or_else(|| RpcError::new(SERVER_ERROR, "Could not determine project root"))?;
+
+    let relative = resolve_relative(&root, &params.path);
+    Ok(
+        match first_matching_rule(&config.creation_rules, &relative) {
+            Some(index) => json!({
+                "index": index,
+                "rule": config.creation_rules[index],
+            }),
+            None => Value::Null,
+        },
+    )
+}
+
+/// Reports whether a file exists, is encrypted and which rule applies to it
+fn status(params: PathParams, context: &GlobalContext) -> Result<Value, RpcError> {
+    let config = load_effective_config(context)
+        .map_err(|e| RpcError::new(SERVER_ERROR, e))?
+        .config;
+    let root = config_dir(context)
+        .ok_or_else(|| RpcError::new(SERVER_ERROR, "Could not determine project root"))?;
+
+    let path = Path::new(&params.path);
+    let relative = resolve_relative(&root, &params.path);
+
+    Ok(json!({
+        "exists": path.is_file(),
+        "encrypted": is_sops_encrypted_file(path),
+        "rule": first_matching_rule(&config.creation_rules, &relative),
+        "onepassworditem": config.onepassworditem,
+    }))
+}
+
+fn resolve_relative(root: &Path, path: &str) -> String {
+    project_relative_path(root, Path::new(path))
+        .unwrap_or_else(|| absolute_path(Path::new(path)).to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use std::fs;
+    use tempfile::tempdir;
+
+    use super::handle_line;
+    use crate::GlobalContext;
+
+    fn context_with_config(config: &str) -> (tempfile::TempDir, GlobalContext) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(".sops.yaml");
+        fs::write(&path, config).unwrap();
+        let context = GlobalContext {
+            sops_file: Some(path.to_string_lossy().into()),
+            opitem: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
+        };
+        (dir, context)
+    }
+
+    fn call(line: &str, context: &GlobalContext) -> Value {
+        serde_json::from_str(&handle_line(line, context).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let (_dir, context) = context_with_config("onepassworditem: ''\n");
+        let response = call("{not json", &context);
+        assert_eq!(response["error"]["code"], -32700);
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        let (_dir, context) = context_with_config("onepassworditem: ''\n");
+        let response = call(r#"{"jsonrpc":"2.0","id":1,"method":"nope"}"#, &context);
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn test_notification_has_no_response() {
+        let (_dir, context) = context_with_config("onepassworditem: ''\n");
+        assert!(handle_line(r#"{"jsonrpc":"2.0","method":"nope"}"#, &context).is_none());
+    }
+
+    #[test]
+    fn test_rule_for_path() {
+        let (dir, context) = context_with_config(
+            "creation_rules:\n- path_regex: ^k8s/.*\\.yaml$\n  age: age1abc\nonepassworditem: op://V/I/F\n",
+        );
+        let file = dir.path().join("k8s/secret.yaml");
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": "a",
+            "method": "ruleForPath",
+            "params": { "path": file },
+        });
+
+        let response = call(&request.to_string(), &context);
+        assert_eq!(response["result"]["index"], 0);
+        assert_eq!(response["result"]["rule"]["age"], "age1abc");
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": "b",
+            "method": "ruleForPath",
+            "params": { "path": dir.path().join("other.yaml") },
+        });
+        let response = call(&request.to_string(), &context);
+        assert_eq!(response["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let (_dir, context) = context_with_config("onepassworditem: ''\n");
+        let response = call(
+            r#"{"jsonrpc":"2.0","id":1,"method":"status","params":{}}"#,
+            &context,
+        );
+        assert_eq!(response["error"]["code"], -32602);
+    }
+}
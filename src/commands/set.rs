@@ -0,0 +1,83 @@
+use colored::Colorize;
+use dialoguer::{Password, theme::ColorfulTheme};
+use serde_yaml::Value;
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+use crate::{
+    GlobalContext,
+    util::{
+        encrypted_insert::{check_post_processable, insert_values},
+        generators::Generator,
+        i18n::{tr, tr_args},
+        op_key::get_age_key_from_1password,
+        print_status::{print_error, print_success, print_warning},
+    },
+};
+
+fn fail(message: impl Into<String>) -> ! {
+    print_error(message.into());
+    std::process::exit(1);
+}
+
+/// The value to set: prompted for without echo, or read from stdin when it
+/// isn't a terminal, without the trailing newline
+fn read_value() -> Zeroizing<String> {
+    if std::io::stdin().is_terminal() {
+        return Zeroizing::new(
+            Password::with_theme(&ColorfulTheme::default())
+                .with_prompt(tr("set-value"))
+                .interact()
+                .unwrap_or_else(|e| fail(format!("{} {}", tr("set-read-failed"), e))),
+        );
+    }
+    let mut value = Zeroizing::new(String::new());
+    if let Err(e) = std::io::stdin().read_to_string(&mut value) {
+        fail(format!("{} {}", tr("set-read-failed").red(), e));
+    }
+    let trimmed = value.trim_end_matches(['\n', '\r']).len();
+    value.truncate(trimmed);
+    value
+}
+
+/// Writes a value into `<file>#<key_path>`, decrypting and encrypting the file
+/// in memory. The value is minted by the generator `generate`, see
+/// [`crate::util::generators`], or read without showing up in the shell
+/// history. A file that doesn't exist yet is created under its creation rule.
+pub fn set(
+    file: PathBuf,
+    key_path: String,
+    generate: Option<String>,
+    force: bool,
+    context: &GlobalContext,
+) {
+    if let Err(e) = check_post_processable(&file) {
+        fail(e);
+    }
+    let generator = generate.map(|spec| Generator::parse(&spec).unwrap_or_else(|e| fail(e)));
+    let key = get_age_key_from_1password(context)
+        .unwrap_or_else(|e| fail(tr_args("age-key-get-failed", &[("error", e.into())])));
+    let value = match &generator {
+        Some(generator) => generator.generate().unwrap_or_else(|e| fail(e)),
+        None => Value::String(read_value().to_string()),
+    };
+
+    let values = vec![(key_path.clone(), value)];
+    let created = insert_values(&file, values, force, &key, context).unwrap_or_else(|e| fail(e));
+    let target = format!("{}#{}", file.display(), key_path);
+    print_success(tr_args(
+        if generator.is_some() {
+            "set-generated"
+        } else {
+            "set-done"
+        },
+        &[("target", target.into())],
+    ));
+    if created {
+        print_warning(tr_args(
+            "created-for-rule",
+            &[("path", file.display().to_string().into())],
+        ));
+    }
+}
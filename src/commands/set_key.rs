@@ -1,14 +1,30 @@
 use crate::GlobalContext;
+use crate::util::document::parse_document;
+use crate::util::encrypted_keys::{KeySelector, preview, print_preview};
+use crate::util::escrow::{escrow_recipient, with_escrow};
+use crate::util::i18n::{failed_to, tr, tr_args};
 use crate::util::op_key::extract_public_key;
-use crate::util::print_status::{print_error, print_success};
-use crate::util::{op_key, sops_config};
+use crate::util::print_status::{print_error, print_info, print_success};
+use crate::util::prompts::{confirm, input, select};
+use crate::util::rule_conditions::{matching_condition, preset_regex, rule_conditions};
+use crate::util::rule_match::project_relative_path;
+use crate::util::rule_templates::{COMMON_REGEX, KUBERNETES_REGEX, TALOS_REGEX};
+use crate::util::sops_files::sops_file_type;
+use crate::util::{config_edit, op_key, sops_config};
+use age::secrecy::ExposeSecret;
 use colored::Colorize;
-use dialoguer::{Select, theme::ColorfulTheme};
+use regex::Regex;
 use std::ffi::OsString;
+use std::fs;
 use std::path::Path;
 
 // Set encryption patterns for a file in .sops.yaml
-pub fn set_keys(path: OsString, context: &GlobalContext) {
+pub fn set_keys(
+    path: OsString,
+    path_regex: Option<String>,
+    encrypted_regex: Option<String>,
+    context: &GlobalContext,
+) {
     let path_str = path.to_string_lossy().to_string();
     let file_path = Path::new(&path_str);
 
@@ -16,8 +32,8 @@ pub fn set_keys(path: OsString, context: &GlobalContext) {
     if !file_path.exists() {
         print_error(format!(
             "{} {}",
-            "Error:".red().bold(),
-            "File not found.".red()
+            tr("error-prefix").red().bold(),
+            tr("set-key-not-found").red()
         ));
         return;
     }
@@ -28,16 +44,16 @@ pub fn set_keys(path: OsString, context: &GlobalContext) {
         if !["yaml", "yml", "json"].contains(&ext_str.as_str()) {
             print_error(format!(
                 "{} {}",
-                "Error:".red().bold(),
-                "Only YAML and JSON files are supported.".red()
+                tr("error-prefix").red().bold(),
+                tr("set-key-unsupported").red()
             ));
             return;
         }
     } else {
         print_error(format!(
             "{} {}\n",
-            "Error:".red().bold(),
-            "File has no extension. Only YAML and JSON files are supported.".red()
+            tr("error-prefix").red().bold(),
+            tr("set-key-no-extension").red()
         ));
         return;
     }
@@ -46,147 +62,184 @@ pub fn set_keys(path: OsString, context: &GlobalContext) {
     match op_key::get_age_key_from_1password(context) {
         Ok(key) => {
             // Extract public key from the private key
-            let pubkey = match extract_public_key(&key) {
+            let pubkey = match extract_public_key(key.expose_secret()) {
                 Ok(k) => k,
                 Err(err) => {
-                    print_error(format!("{}{}", "Error getting public key: \n".red(), err));
+                    print_error(tr_args("public-key-failed", &[("error", err.into())]));
                     return;
                 }
             };
             if pubkey.is_empty() {
                 print_error(format!(
                     "{} {}",
-                    "Error:".red().bold(),
-                    "Could not extract public key from the age key.\n".red()
+                    tr("error-prefix").red().bold(),
+                    tr("set-key-no-public-key").red()
                 ));
                 return;
             }
 
-            // Get the file name for the rule
-            let file_name = file_path.to_string_lossy();
+            // Rules are written for the path relative to .sops.yaml, however it was typed
+            let Some(file_name) = sops_config::config_dir(context)
+                .and_then(|root| project_relative_path(&root, file_path))
+            else {
+                print_error(format!(
+                    "{} {}",
+                    tr("error-prefix").red().bold(),
+                    tr("file-outside-project").red()
+                ));
+                return;
+            };
 
-            // Prompt the user for encryption options
-            let encrypted_regex = match prompt_for_encryption_pattern() {
+            // A pattern given on the command line wins over rule_conditions,
+            // the user is only asked if neither picks one
+            let encrypted_regex = match encrypted_regex.map(Ok).or_else(|| {
+                conditional_pattern(file_path, context)
+                    .transpose()
+                    .map(|result| result.map_err(std::io::Error::other))
+            }) {
+                Some(pattern) => pattern,
+                None => prompt_for_encryption_pattern(file_path),
+            };
+            let encrypted_regex = match encrypted_regex {
                 Ok(t) => t,
                 Err(error) => {
-                    print_error(format!("{}: {}", "Error getting regex\n".red(), error));
+                    print_error(format!("{} {}", tr("set-key-regex-failed").red(), error));
                     return;
                 }
             };
 
+            // The escrow recipient goes into every rule next to our own key
+            let recipients = with_escrow(&pubkey, escrow_recipient(context).as_deref());
+
             // Update the SOPS configuration
-            match update_sops_config(&file_name, &pubkey, &encrypted_regex, context) {
+            match update_sops_config(
+                &file_name,
+                path_regex.as_deref(),
+                &recipients,
+                &encrypted_regex,
+                context,
+            ) {
                 Ok(_) => {
-                    print_success(format!("{}", "Successfully updated .sops.yaml\n".green()));
+                    print_success(format!("{}\n", tr("set-key-updated").green()));
                 }
                 Err(err) => {
-                    print_error(format!("{}: {}", "Error updating .sops.yam\n".red(), err));
+                    print_error(tr_args(
+                        "sops-config-update-failed",
+                        &[("error", err.to_string().into())],
+                    ));
                     return;
                 }
             }
 
-            println!("You can now encrypt your file with:\n");
+            println!("{}\n", tr("set-key-next-step"));
             println!("  {} {}\n", "opsops encrypt".yellow(), path_str.yellow());
         }
         Err(e) => {
-            print_error(format!("{} {}", "Error:".red().bold(), e.red()));
+            print_error(format!("{} {}", tr("error-prefix").red().bold(), e.red()));
         }
     }
 }
 
+// The pattern of the first entry of rule_conditions the file meets
+fn conditional_pattern(
+    file_path: &Path,
+    context: &GlobalContext,
+) -> Result<Option<String>, String> {
+    let conditions = rule_conditions(context)?;
+    if conditions.is_empty() {
+        return Ok(None);
+    }
+    let content = fs::read(file_path).map_err(|e| failed_to("read-failed", file_path, e))?;
+    Ok(matching_condition(&conditions, &content).map(|condition| {
+        print_info(tr_args(
+            "set-key-conditional-pattern",
+            &[("pattern", condition.encrypted_regex.as_str().into())],
+        ));
+        preset_regex(&condition.encrypted_regex).to_string()
+    }))
+}
+
 // Prompt the user to choose an encryption pattern
-fn prompt_for_encryption_pattern() -> std::io::Result<String> {
-    let options = vec![
-        "All values (encrypt entire file)",
-        "Kubernetes (data, stringData, password, ingress, token fields)",
-        "Talos configuration secrets (secrets sections, certs, keys)",
-        "Common sensitive data (passwords, tokens, keys, credentials)",
-        "Custom pattern (provide your own regex)",
+fn prompt_for_encryption_pattern(file_path: &Path) -> std::io::Result<String> {
+    let options = [
+        tr("set-key-pattern-all"),
+        tr("set-key-pattern-kubernetes"),
+        tr("set-key-pattern-talos"),
+        tr("set-key-pattern-common"),
+        tr("set-key-pattern-custom"),
     ];
 
-    let selection = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("What do you want to encrypt in this file?")
-        .default(0)
-        .items(&options)
-        .interact()
-        .map_err(std::io::Error::other)?;
+    let selection = select(&tr("set-key-choose-pattern"), &options, 0)?;
 
     let encrypted_regex = match selection {
         0 => Ok(".*".to_string()),
-        1 => Ok("^(data|stringData|password|token|secret|key|cert|ca.crt|tls|ingress|backupTarget)"
-            .to_string()),
-        2 => Ok("^(secrets|privateKey|token|key|crt|cert|password|secret|kubeconfig|talosconfig)"
-            .to_string()),
-        3 => Ok("^(password|token|secret|key|auth|credential|private|apiKey|cert)".to_string()),
-        4 => {
-            dialoguer::Input::<String>::new()
-                .with_prompt("Enter your regex pattern to match keys you want to encrypt\nExample: ^(password|api_key|secret)")
-                .interact()
-                .map_err(std::io::Error::other)
-        }
+        1 => Ok(KUBERNETES_REGEX.to_string()),
+        2 => Ok(TALOS_REGEX.to_string()),
+        3 => Ok(COMMON_REGEX.to_string()),
+        4 => prompt_for_custom_pattern(file_path),
         _ => Ok(".*".to_string()),
     }?;
 
     Ok(encrypted_regex)
 }
 
+// Ask for a custom pattern until the user is happy with the keys it matches in the file
+fn prompt_for_custom_pattern(file_path: &Path) -> std::io::Result<String> {
+    let document = fs::read(file_path)
+        .ok()
+        .and_then(|contents| parse_document(&contents, sops_file_type(file_path)).ok());
+    loop {
+        let pattern = input(&tr("set-key-enter-pattern"), None, false)?;
+        let regex = match Regex::new(&pattern) {
+            Ok(r) => r,
+            Err(e) => {
+                print_error(format!("{} {}", tr("invalid-regex").red(), e));
+                continue;
+            }
+        };
+        let Some(document) = &document else {
+            return Ok(pattern);
+        };
+
+        print_preview(&preview(document, &KeySelector::EncryptedRegex(regex)));
+        if confirm(&tr("set-key-use-pattern"), true)? {
+            return Ok(pattern);
+        }
+    }
+}
+
 // Update the SOPS configuration with the new encryption pattern
-// TODO: Move this somehwere better
 fn update_sops_config(
     file_name: &str,
+    path_regex: Option<&str>,
     pubkey: &str,
     encrypted_regex: &str,
     context: &GlobalContext,
 ) -> std::io::Result<()> {
     // Read the current SOPS configuration
-    let mut config = match sops_config::read_or_create_config(context) {
+    let config = match sops_config::read_or_create_config(context) {
         Ok(cfg) => cfg,
         Err(e) => {
             print_error(format!(
                 "{} {}",
-                "Error:".red().bold(),
-                format!("Failed to read SOPS config: {}", e).red()
+                tr("error-prefix").red().bold(),
+                format!("{} {}", tr("config-read-failed"), e).red()
             ));
             return Ok(());
         }
     };
 
-    // Check if there's an existing rule for this file
-    let mut existing_rule_index = None;
-    for (i, rule) in config.creation_rules.iter().enumerate() {
-        if let Some(path_regex) = &rule.path_regex {
-            if path_regex == file_name {
-                existing_rule_index = Some(i);
-                break;
-            }
-        }
-    }
-
-    if let Some(index) = existing_rule_index {
-        // Update existing rule
-        if let Some(rule) = config.creation_rules.get_mut(index) {
-            rule.age = Some(pubkey.to_string());
-            rule.encrypted_regex = Some(encrypted_regex.to_string());
-        }
-    } else {
-        // Create a new rule
-        let new_rule = crate::util::sops_structs::CreationRule {
-            path_regex: Some(file_name.to_string()),
-            age: Some(pubkey.to_string()),
-            encrypted_regex: Some(encrypted_regex.to_string()),
-            key_groups: vec![],
-        };
-
-        // Add rule to configuration
-        config.creation_rules.push(new_rule);
-    }
+    // Add a rule for this file or update the existing one
+    let config =
+        config_edit::upsert_file_rule(config, file_name, path_regex, pubkey, encrypted_regex)
+            .map_err(std::io::Error::other)?;
 
     // Write the updated configuration
     if let Err(e) = sops_config::write_config(&config, context) {
         print_error(format!(
             "{} {}",
-            "Error:".red().bold(),
-            format!("Failed to write SOPS config: {}", e).red()
+            tr("error-prefix").red().bold(),
+            format!("{} {}", tr("config-write-failed"), e).red()
         ));
         return Ok(());
     }
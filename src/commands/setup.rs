@@ -0,0 +1,226 @@
+use age::{secrecy::ExposeSecret, x25519};
+use colored::Colorize;
+use git2::Repository;
+
+use crate::{
+    GlobalContext,
+    commands::{generate_age_key::save_to_op, init::select_op_reference},
+    util::{
+        config_edit::{add_template_rule, set_op_item},
+        escrow::{escrow_recipient, with_escrow},
+        find_project_root::find_project_root,
+        git_hooks::install_pre_commit_hook,
+        i18n::{tr, tr_args},
+        op::{is_signed_in, sign_in},
+        op_key::{extract_public_key, get_age_key_from_1password},
+        op_reference::OpReference,
+        print_status::{print_error, print_info, print_success, print_warning},
+        prompts::{confirm, input, select},
+        rule_templates::TEMPLATES,
+        sops_config::{read_or_create_config, write_config},
+        user_config::{read_user_config, write_user_config},
+    },
+};
+
+/// Guided first-run setup: tools, 1Password session, age key, .sops.yaml, git hooks and user config
+pub fn setup(context: &GlobalContext) {
+    println!("{}\n", tr("setup-title").bold());
+
+    // 1. Required tools
+    let sops_ok = check_tool(
+        "sops",
+        "brew install sops | nix profile install nixpkgs#sops | https://github.com/getsops/sops/releases",
+    );
+    let op_ok = check_tool(
+        "op",
+        "brew install 1password-cli | nix profile install nixpkgs#_1password-cli | https://1password.com/downloads/command-line/",
+    );
+    if !sops_ok || !op_ok {
+        print_error(tr("setup-install-missing"));
+        return;
+    }
+
+    // 2. 1Password session
+    if is_signed_in() {
+        print_success(tr("setup-signed-in"));
+    } else {
+        print_warning(tr("setup-signing-in"));
+        if !sign_in() {
+            print_error(tr("setup-sign-in-failed"));
+            return;
+        }
+        print_success(tr("setup-signed-in"));
+    }
+
+    // 3. Age key
+    let (reference, vault) = match choose_key() {
+        Some(k) => k,
+        None => return,
+    };
+    let key_context = GlobalContext {
+        sops_file: context.sops_file.clone(),
+        opitem: Some(reference.clone()),
+        age_key_env: context.age_key_env,
+        chdir: context.chdir.clone(),
+        read_only: context.read_only,
+        key_preference: context.key_preference,
+    };
+    let public_key = match get_age_key_from_1password(&key_context)
+        .and_then(|key| extract_public_key(key.expose_secret()).map_err(|e| e.to_string()))
+    {
+        Ok(k) => k,
+        Err(e) => {
+            print_error(format!("{} {}", tr("setup-age-key-failed").red(), e));
+            return;
+        }
+    };
+    print_success(format!("{} {}", tr("setup-public-key").green(), public_key));
+
+    // 4. Project .sops.yaml
+    if !write_project_config(context, &reference, &public_key) {
+        return;
+    }
+
+    // 5. Git hooks
+    install_hooks(context);
+
+    // 6. User config
+    if let Some(vault) = vault {
+        let mut user_config = read_user_config();
+        user_config.default_vault = Some(vault);
+        match write_user_config(&user_config) {
+            Ok(path) => print_success(tr_args(
+                "setup-wrote-user-config",
+                &[("path", path.display().to_string().into())],
+            )),
+            Err(e) => print_warning(tr_args(
+                "setup-user-config-failed",
+                &[("error", e.to_string().into())],
+            )),
+        }
+    }
+
+    println!();
+    print_success(format!("{}", tr("setup-complete").green().bold()));
+    println!("{}\n", tr("setup-next-step"));
+    println!("  {}\n", "opsops encrypt <file>".yellow());
+}
+
+/// Checks that a tool is on PATH and prints install hints otherwise
+fn check_tool(name: &str, install_hint: &str) -> bool {
+    match which::which(name) {
+        Ok(path) => {
+            print_success(tr_args(
+                "setup-found-tool",
+                &[
+                    ("tool", name.into()),
+                    ("path", path.display().to_string().into()),
+                ],
+            ));
+            true
+        }
+        Err(_) => {
+            print_error(tr_args("setup-missing-tool", &[("tool", name.into())]));
+            println!("  {} {}", tr("setup-install-hint"), install_hint.dimmed());
+            false
+        }
+    }
+}
+
+/// Generates a new key or selects an existing one.
+/// Returns the op:// reference and the vault chosen for new items, if any.
+fn choose_key() -> Option<(String, Option<String>)> {
+    let options = [tr("setup-generate-key"), tr("setup-existing-key")];
+    let selection = select(&tr("setup-choose-key"), &options, 0).unwrap();
+
+    if selection == 1 {
+        return select_op_reference().map(|reference| (reference, None));
+    }
+
+    let vault: String = input(
+        &tr("key-choose-vault"),
+        Some(
+            read_user_config()
+                .default_vault
+                .unwrap_or_else(|| "Personal".to_string()),
+        ),
+        false,
+    )
+    .unwrap();
+    let name: String = input(&tr("key-choose-item-name"), None, false).unwrap();
+
+    let key = x25519::Identity::generate();
+    if !save_to_op(&key, name.clone(), vault.clone(), false) {
+        return None;
+    }
+    print_success(tr("setup-stored-key"));
+
+    let reference = OpReference::new(&vault, &name, "Private Key").to_string();
+    Some((reference, Some(vault)))
+}
+
+/// Creates or updates the project's .sops.yaml with the key and a rule template
+fn write_project_config(context: &GlobalContext, reference: &str, public_key: &str) -> bool {
+    let mut config = match read_or_create_config(context) {
+        Ok(c) => set_op_item(c, reference),
+        Err(e) => {
+            print_error(format!("{} {}", tr("config-read-failed").red(), e));
+            return false;
+        }
+    };
+
+    let mut options: Vec<String> = TEMPLATES.iter().map(|t| t.name.to_string()).collect();
+    options.push(tr("setup-no-template"));
+
+    let selection = select(&tr("setup-choose-template"), &options, 0).unwrap();
+
+    if let Some(template) = TEMPLATES.get(selection) {
+        let exists = config
+            .creation_rules
+            .iter()
+            .any(|r| r.path_regex.as_deref() == Some(template.path_regex));
+        if exists
+            && !confirm(
+                &tr_args(
+                    "setup-rule-exists",
+                    &[("pattern", template.path_regex.into())],
+                ),
+                false,
+            )
+            .unwrap()
+        {
+            print_info(tr("setup-keeping-rules"));
+        } else {
+            let recipients = with_escrow(public_key, escrow_recipient(context).as_deref());
+            config = add_template_rule(config, template, &recipients);
+        }
+    }
+
+    if let Err(e) = write_config(&config, context) {
+        print_error(format!("{} {}", tr("config-write-failed").red(), e));
+        return false;
+    }
+    print_success(tr("setup-wrote-sops-config"));
+    true
+}
+
+/// Offers to install the opsops git hooks if the project is a git repository
+fn install_hooks(context: &GlobalContext) {
+    let repo = match find_project_root(context).and_then(|root| Repository::open(root).ok()) {
+        Some(r) => r,
+        None => {
+            print_info(tr("setup-no-repository"));
+            return;
+        }
+    };
+
+    if !confirm(&tr("setup-install-hook"), true).unwrap() {
+        return;
+    }
+
+    match install_pre_commit_hook(&repo) {
+        Ok(true) => print_success(tr("setup-hook-installed")),
+        Ok(false) => print_info(tr("setup-hook-exists")),
+        Err(e) => print_warning(e),
+    }
+}
@@ -0,0 +1,329 @@
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::{
+    GlobalContext,
+    util::{
+        config_include::load_effective_config,
+        i18n::{failed_to, tr, tr_args},
+        opsops_config::read_opsops_config,
+        output_format::{OutputFormat, render_structured},
+        print_status::print_error,
+        recipient_expiry::today,
+        rule_match::{first_matching_rule, project_relative_path, relative_path},
+        sops_config::config_dir,
+        sops_files::find_encrypted_files,
+        sops_metadata::read_metadata,
+        value_expiry::{ExpiryState, annotations, date_in, expiry_state},
+    },
+};
+
+/// How many files the rotation and size lists show
+const LISTED_FILES: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+struct FileStats {
+    path: String,
+    bytes: u64,
+    /// `lastmodified` from the sops metadata, when the data key was last written
+    last_modified: Option<String>,
+    recipients: usize,
+}
+
+/// A value with an expiry date, from a `__expires` annotation or `value_expiry:`
+#[derive(Debug, Serialize)]
+struct ExpiringValue {
+    file: String,
+    key: String,
+    expires: String,
+    state: ExpiryState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RuleStats {
+    /// 1-based, as in `list-config`
+    rule: usize,
+    path_regex: Option<String>,
+    files: usize,
+}
+
+/// The secret posture of a project, for periodic security reviews
+#[derive(Debug, Serialize)]
+struct Stats {
+    encrypted_files: usize,
+    total_bytes: u64,
+    rules: usize,
+    /// Every age recipient at least one file is encrypted to
+    recipients: Vec<String>,
+    files_per_rule: Vec<RuleStats>,
+    /// Encrypted files no creation rule covers anymore
+    files_without_rule: usize,
+    oldest_rotation: Option<String>,
+    newest_rotation: Option<String>,
+    least_recently_rotated: Vec<FileStats>,
+    largest: Vec<FileStats>,
+    /// Values with an expiry date, the soonest first
+    value_expiry: Vec<ExpiringValue>,
+    /// `file#key` of `__expires` annotations sops encrypted
+    unreadable_expiry: Vec<String>,
+}
+
+fn collect_stats(root: &Path, warning_days: u64, context: &GlobalContext) -> Result<Stats, String> {
+    let rules = load_effective_config(context)?.config.creation_rules;
+    let (today, cutoff) = (today(), date_in(warning_days));
+    let expiring =
+        |file: String, key: String, expires: String, note: Option<String>| ExpiringValue {
+            state: expiry_state(&expires, &today, &cutoff),
+            file,
+            key,
+            expires,
+            note,
+        };
+    let mut value_expiry: Vec<ExpiringValue> = read_opsops_config(context)?
+        .map(|config| config.value_expiry)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| expiring(entry.file, entry.key, entry.expires, entry.note))
+        .collect();
+    let mut unreadable_expiry = Vec::new();
+    let mut files = Vec::new();
+    let mut per_rule = vec![0; rules.len()];
+    let mut files_without_rule = 0;
+    let mut recipients = BTreeSet::new();
+    for file in find_encrypted_files(root) {
+        let contents =
+            std::fs::read_to_string(&file).map_err(|e| failed_to("read-failed", &file, e))?;
+        match project_relative_path(root, &file)
+            .and_then(|relative| first_matching_rule(&rules, &relative))
+        {
+            Some(index) => per_rule[index] += 1,
+            None => files_without_rule += 1,
+        }
+        let path = relative_path(root, &file);
+        for (key, expires) in annotations(&contents, &file) {
+            match expires {
+                Some(expires) => value_expiry.push(expiring(path.clone(), key, expires, None)),
+                None => unreadable_expiry.push(format!("{}#{}", path, key)),
+            }
+        }
+        let metadata = read_metadata(&contents).unwrap_or_default();
+        let file_recipients = metadata.age_recipients();
+        files.push(FileStats {
+            path,
+            bytes: contents.len() as u64,
+            last_modified: metadata.lastmodified,
+            recipients: file_recipients.len(),
+        });
+        recipients.extend(file_recipients);
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    // Timestamps sops writes sort chronologically; files without one first
+    let mut by_rotation = files.clone();
+    by_rotation.sort_by(|a, b| a.last_modified.cmp(&b.last_modified));
+    let mut largest = files.clone();
+    largest.sort_by_key(|file| std::cmp::Reverse(file.bytes));
+    let rotations = files.iter().filter_map(|file| file.last_modified.clone());
+    value_expiry.sort_by(|a, b| (a.state, &a.expires).cmp(&(b.state, &b.expires)));
+
+    Ok(Stats {
+        encrypted_files: files.len(),
+        total_bytes: files.iter().map(|file| file.bytes).sum(),
+        rules: rules.len(),
+        recipients: recipients.into_iter().collect(),
+        files_per_rule: rules
+            .iter()
+            .zip(per_rule)
+            .enumerate()
+            .map(|(i, (rule, files))| RuleStats {
+                rule: i + 1,
+                path_regex: rule.path_regex.clone(),
+                files,
+            })
+            .collect(),
+        files_without_rule,
+        oldest_rotation: rotations.clone().min(),
+        newest_rotation: rotations.max(),
+        least_recently_rotated: by_rotation.into_iter().take(LISTED_FILES).collect(),
+        largest: largest.into_iter().take(LISTED_FILES).collect(),
+        value_expiry,
+        unreadable_expiry,
+    })
+}
+
+/// `1.5 KiB`, `12 B`
+fn size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn show(label: &str, value: impl std::fmt::Display) {
+    println!("  {:18} {}", label.cyan(), value);
+}
+
+/// Summarizes the encrypted files of the project: how many there are, the
+/// rules and recipients covering them, when they were last rotated, the
+/// largest ones and values expired or expiring within `warning_days`. Reads
+/// sops metadata and plaintext annotations only, no key is needed. With
+/// `fail_on_expired`, exits with 1 if any value has expired.
+pub fn stats(
+    context: &GlobalContext,
+    format: OutputFormat,
+    warning_days: u64,
+    fail_on_expired: bool,
+) {
+    let Some(root) = config_dir(context) else {
+        print_error(tr("no-sops-config"));
+        std::process::exit(1);
+    };
+    let stats = match collect_stats(&root, warning_days, context) {
+        Ok(stats) => stats,
+        Err(e) => {
+            print_error(e);
+            std::process::exit(1);
+        }
+    };
+
+    let expired = stats
+        .value_expiry
+        .iter()
+        .filter(|value| value.state == ExpiryState::Expired)
+        .count();
+
+    if let Some(rendered) = render_structured(&stats, format) {
+        match rendered {
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(e) => {
+                print_error(e);
+                std::process::exit(1);
+            }
+        }
+        if fail_on_expired && expired > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let never = || tr("unknown").dimmed().to_string();
+    println!("{}", tr("stats-secrets").bold());
+    show(
+        &tr("stats-encrypted-files"),
+        format!("{} ({})", stats.encrypted_files, size(stats.total_bytes)),
+    );
+    show(&tr("stats-creation-rules"), stats.rules);
+    show(&tr("stats-recipients"), stats.recipients.len());
+    show(
+        &tr("stats-oldest-rotation"),
+        stats.oldest_rotation.clone().unwrap_or_else(never),
+    );
+    show(
+        &tr("stats-newest-rotation"),
+        stats.newest_rotation.clone().unwrap_or_else(never),
+    );
+
+    println!("\n{}", tr("stats-files-per-rule").bold());
+    for rule in &stats.files_per_rule {
+        println!(
+            "  {:>5}  {:<40} {}",
+            rule.files,
+            rule.path_regex
+                .clone()
+                .unwrap_or_else(|| tr("stats-every-file")),
+            tr_args("stats-rule", &[("number", rule.rule.into())]).dimmed()
+        );
+    }
+    if stats.files_without_rule > 0 {
+        println!(
+            "  {:>5}  {}",
+            stats.files_without_rule.to_string().red(),
+            tr("stats-no-rule").red()
+        );
+    }
+
+    if stats.encrypted_files > 0 {
+        print_files(&stats);
+    }
+    print_value_expiry(&stats, warning_days);
+    if fail_on_expired && expired > 0 {
+        print_error(tr_args("stats-expired", &[("count", expired.into())]).red());
+        std::process::exit(1);
+    }
+}
+
+fn print_value_expiry(stats: &Stats, warning_days: u64) {
+    let due: Vec<&ExpiringValue> = stats
+        .value_expiry
+        .iter()
+        .filter(|value| value.state != ExpiryState::Valid)
+        .collect();
+    if stats.value_expiry.is_empty() && stats.unreadable_expiry.is_empty() {
+        return;
+    }
+    println!("\n{}", tr("stats-value-expiry").bold());
+    show(
+        &tr("stats-with-date"),
+        tr_args(
+            "stats-due",
+            &[
+                ("count", stats.value_expiry.len().into()),
+                ("due", due.len().into()),
+                ("days", warning_days.to_string().into()),
+            ],
+        ),
+    );
+    for value in due {
+        let expires = match value.state {
+            ExpiryState::Expired => value.expires.red(),
+            _ => value.expires.yellow(),
+        };
+        let note = value
+            .note
+            .as_ref()
+            .map(|note| format!(" ({})", note).dimmed().to_string())
+            .unwrap_or_default();
+        println!("  {:10}  {}#{}{}", expires, value.file, value.key, note);
+    }
+    for annotation in &stats.unreadable_expiry {
+        println!(
+            "  {:10}  {} {}",
+            tr("stats-encrypted").dimmed(),
+            annotation,
+            tr("stats-unreadable-expiry").dimmed()
+        );
+    }
+}
+
+fn print_files(stats: &Stats) {
+    let never = || tr("unknown").dimmed().to_string();
+    println!("\n{}", tr("stats-least-recently-rotated").bold());
+    for file in &stats.least_recently_rotated {
+        println!(
+            "  {:20}  {}",
+            file.last_modified.clone().unwrap_or_else(never),
+            file.path
+        );
+    }
+    println!("\n{}", tr("stats-largest").bold());
+    for file in &stats.largest {
+        println!("  {:>10}  {}", size(file.bytes), file.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::size;
+
+    #[test]
+    fn test_size() {
+        assert_eq!(size(12), "12 B");
+        assert_eq!(size(1536), "1.5 KiB");
+        assert_eq!(size(3 * 1_048_576), "3.0 MiB");
+    }
+}
@@ -0,0 +1,259 @@
+use age::secrecy::SecretString;
+use colored::Colorize;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::{
+    GlobalContext,
+    util::{
+        document::{extract, parse_document, scalar_text},
+        encrypted_insert::insert_values,
+        i18n::{failed_to, tr, tr_args},
+        op::{OpCategory, OpItem, OpItemField, op_item_edit, read_secret},
+        op_key::get_age_key_from_1password,
+        print_status::{print_error, print_info, print_success, print_warning},
+        sops_command::decrypt_in_memory,
+        sops_files::sops_file_type,
+        sync_map::{SyncEntry, sync_entries},
+    },
+};
+
+/// Where a `sync:` entry stands
+enum State {
+    InSync,
+    /// Both sides hold a value, a different one
+    Differs,
+    /// The file or the key path doesn't exist yet
+    MissingInFile,
+    Failed(String),
+}
+
+struct Checked<'a> {
+    entry: &'a SyncEntry,
+    file: PathBuf,
+    path: String,
+    op_value: Option<Zeroizing<String>>,
+    file_value: Option<Zeroizing<String>>,
+    state: State,
+}
+
+fn fail(message: impl Into<String>) -> ! {
+    print_error(message.into());
+    std::process::exit(1);
+}
+
+/// Decrypts `file` in memory, `None` if it doesn't exist yet
+fn read_document(
+    file: &Path,
+    key: &SecretString,
+    context: &GlobalContext,
+) -> Result<Option<Value>, String> {
+    if !file.exists() {
+        return Ok(None);
+    }
+    let content = decrypt_in_memory(file, Some(key), context)
+        .map_err(|e| failed_to("decrypt-path-failed", file, e))?;
+    parse_document(&content, sops_file_type(file)).map(Some)
+}
+
+/// Compares each entry's 1Password field with its target, decrypting every
+/// file once
+fn check<'a>(
+    entries: &'a [SyncEntry],
+    root: &Path,
+    key: &SecretString,
+    context: &GlobalContext,
+) -> Vec<Checked<'a>> {
+    let mut documents: BTreeMap<PathBuf, Result<Option<Value>, String>> = BTreeMap::new();
+    let mut checked = Vec::new();
+    for entry in entries {
+        // Validated by sync_entries
+        let (file, path) = entry.location(root).unwrap_or_default();
+        let document = documents
+            .entry(file.clone())
+            .or_insert_with(|| read_document(&file, key, context));
+        let file_value = match document {
+            Ok(Some(document)) => match extract(document, &path) {
+                Ok(value) => match scalar_text(value) {
+                    Some(text) => Ok(Some(Zeroizing::new(text))),
+                    None => Err(tr_args(
+                        "sync-not-a-value",
+                        &[("target", format!("{}#{}", file.display(), path).into())],
+                    )),
+                },
+                Err(_) => Ok(None),
+            },
+            Ok(None) => Ok(None),
+            Err(e) => Err(e.clone()),
+        };
+        let op_value = read_secret(&entry.op);
+        let state = match (&op_value, &file_value) {
+            (Err(e), _) | (_, Err(e)) => State::Failed(e.clone()),
+            (Ok(_), Ok(None)) => State::MissingInFile,
+            (Ok(op), Ok(Some(file))) if op == file => State::InSync,
+            (Ok(_), Ok(Some(_))) => State::Differs,
+        };
+        checked.push(Checked {
+            entry,
+            file,
+            path,
+            op_value: op_value.ok(),
+            file_value: file_value.ok().flatten(),
+            state,
+        });
+    }
+    checked
+}
+
+fn load(context: &GlobalContext) -> (PathBuf, Vec<SyncEntry>, SecretString) {
+    let (root, entries) = sync_entries(context).unwrap_or_else(|e| fail(e));
+    if entries.is_empty() {
+        print_info(tr("sync-no-entries"));
+        std::process::exit(0);
+    }
+    let key = get_age_key_from_1password(context)
+        .unwrap_or_else(|e| fail(tr_args("age-key-get-failed", &[("error", e.into())])));
+    (root, entries, key)
+}
+
+/// Lists each `sync:` entry with whether 1Password and the file agree, without
+/// printing values. Exits with 1 if any entry drifted or couldn't be read.
+pub fn status(context: &GlobalContext) {
+    let (root, entries, key) = load(context);
+    let checked = check(&entries, &root, &key, context);
+    let mut drifted = 0;
+    for entry in &checked {
+        let state = match &entry.state {
+            State::InSync => tr("sync-in-sync").green(),
+            State::Differs => tr("sync-differs").yellow(),
+            State::MissingInFile => tr("sync-missing-in-file").yellow(),
+            State::Failed(e) => tr_args("sync-failed", &[("error", e.as_str().into())]).red(),
+        };
+        if !matches!(entry.state, State::InSync) {
+            drifted += 1;
+        }
+        println!("{} -> {}  {}", entry.entry.op, entry.entry.target, state);
+    }
+    if drifted > 0 {
+        print_warning(tr_args(
+            "sync-drifted",
+            &[
+                ("drifted", drifted.into()),
+                ("count", checked.len().into()),
+                ("pull", "opsops sync pull".yellow().to_string().into()),
+                ("push", "opsops sync push".yellow().to_string().into()),
+            ],
+        ));
+        std::process::exit(1);
+    }
+}
+
+/// Writes the values of the 1Password fields into their targets, each file
+/// encrypted once
+pub fn pull(context: &GlobalContext) {
+    let (root, entries, key) = load(context);
+    let mut updates: BTreeMap<PathBuf, Vec<(String, Value)>> = BTreeMap::new();
+    let mut failed = false;
+    for entry in check(&entries, &root, &key, context) {
+        match (entry.state, entry.op_value) {
+            (State::Differs | State::MissingInFile, Some(value)) => updates
+                .entry(entry.file)
+                .or_default()
+                .push((entry.path, Value::from(value.as_str()))),
+            (State::Failed(e), _) => {
+                print_error(format!("{}: {}", entry.entry.target, e));
+                failed = true;
+            }
+            _ => {}
+        }
+    }
+    if updates.is_empty() && !failed {
+        print_success(tr("sync-everything-in-sync"));
+    }
+    for (file, values) in updates {
+        let count = values.len();
+        match insert_values(&file, values, true, &key, context) {
+            Ok(_) => print_success(tr_args(
+                "sync-updated-values",
+                &[
+                    ("count", count.into()),
+                    ("path", file.display().to_string().into()),
+                ],
+            )),
+            Err(e) => {
+                print_error(e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Writes the values of the targets into their 1Password fields
+pub fn push(context: &GlobalContext) {
+    let (root, entries, key) = load(context);
+    let mut failed = false;
+    let mut pushed = 0;
+    for entry in check(&entries, &root, &key, context) {
+        match (&entry.state, &entry.file_value) {
+            (State::Differs, Some(value)) => {
+                // Validated by sync_entries
+                let Ok(reference) = entry.entry.reference() else {
+                    continue;
+                };
+                let item = OpItem {
+                    vault: reference.vault,
+                    title: reference.item,
+                    category: OpCategory::SecureNote,
+                    fields: vec![OpItemField {
+                        section: reference.section,
+                        field: reference.field,
+                        field_type: None,
+                        value: value.to_string(),
+                    }],
+                };
+                match op_item_edit(&item) {
+                    Ok(()) => {
+                        pushed += 1;
+                        print_success(tr_args(
+                            "sync-updated-field",
+                            &[("reference", entry.entry.op.as_str().into())],
+                        ));
+                    }
+                    Err(e) => {
+                        print_error(tr_args(
+                            "sync-update-failed",
+                            &[
+                                ("reference", entry.entry.op.as_str().into()),
+                                ("error", e.into()),
+                            ],
+                        ));
+                        failed = true;
+                    }
+                }
+            }
+            (State::MissingInFile, _) => print_warning(tr_args(
+                "sync-nothing-to-push-to",
+                &[
+                    ("target", entry.entry.target.as_str().into()),
+                    ("reference", entry.entry.op.as_str().into()),
+                ],
+            )),
+            (State::Failed(e), _) => {
+                print_error(format!("{}: {}", entry.entry.target, e));
+                failed = true;
+            }
+            _ => {}
+        }
+    }
+    if pushed == 0 && !failed {
+        print_success(tr("sync-nothing-to-push"));
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
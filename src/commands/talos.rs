@@ -0,0 +1,217 @@
+use age::secrecy::ExposeSecret;
+use colored::Colorize;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::Instant;
+
+use crate::{
+    GlobalContext,
+    commands::encrypt::encrypt,
+    util::{
+        config_edit::upsert_file_rule,
+        escrow::{escrow_recipient, with_escrow},
+        file_picker::quiet_config,
+        find_project_root::find_project_root,
+        formatting::Formatting,
+        i18n::{failed_to, tr, tr_args},
+        op_key::{extract_public_key, get_age_key_from_1password},
+        print_status::{print_error, print_info, print_success},
+        rule_match::{first_matching_rule, project_relative_path, relative_path},
+        rule_templates::TALOS_REGEX,
+        session_record,
+        sops_command::decrypt_in_memory,
+        sops_config::{config_dir, read_or_create_config, write_config},
+        sops_files::{is_sops_encrypted_file, sops_file_type, walk_files},
+        talos::{is_talos_config, plaintext_secrets},
+    },
+};
+
+/// Where `talosctl gen secrets` writes the secrets bundle
+const DEFAULT_SECRETS_FILE: &str = "secrets.yaml";
+
+/// Encrypts the secrets bundle from `talosctl gen secrets`/`gen config`, adding
+/// a rule from the Talos template first if no rule covers it yet
+pub fn encrypt_secrets(path: Option<OsString>, context: &GlobalContext) {
+    let path = path.unwrap_or_else(|| DEFAULT_SECRETS_FILE.into());
+    let file = Path::new(&path);
+    if !file.is_file() {
+        print_error(format!(
+            "{} {}. {}",
+            tr("file-not-found").red(),
+            file.display(),
+            tr("talos-generate-first").dimmed()
+        ));
+        std::process::exit(1);
+    }
+    if is_sops_encrypted_file(file) {
+        print_info(format!(
+            "{} {}",
+            file.display(),
+            tr("preview-already-encrypted")
+        ));
+        return;
+    }
+
+    if let Err(e) = ensure_rule(file, context) {
+        print_error(format!("{} {}", tr("talos-rule-failed").red(), e));
+        std::process::exit(1);
+    }
+    encrypt(
+        Some(path),
+        false,
+        false,
+        false,
+        Formatting::default(),
+        context,
+    );
+}
+
+/// Adds a Talos rule for `file` unless one of the rules matches it already
+fn ensure_rule(file: &Path, context: &GlobalContext) -> Result<(), String> {
+    let root = config_dir(context).ok_or_else(|| tr("no-project-root"))?;
+    let relative = project_relative_path(&root, file).ok_or_else(|| tr("file-outside-project"))?;
+    let rules = quiet_config(context)
+        .map(|c| c.creation_rules)
+        .unwrap_or_default();
+    if first_matching_rule(&rules, &relative).is_some() {
+        return Ok(());
+    }
+
+    let key = get_age_key_from_1password(context)?;
+    let pubkey = extract_public_key(key.expose_secret()).map_err(str::to_string)?;
+    let recipients = with_escrow(&pubkey, escrow_recipient(context).as_deref());
+    let config = upsert_file_rule(
+        read_or_create_config(context)?,
+        &relative,
+        None,
+        &recipients,
+        TALOS_REGEX,
+    )?;
+    write_config(&config, context)?;
+    print_success(tr_args(
+        "talos-added-rule",
+        &[("path", relative.as_str().into())],
+    ));
+    Ok(())
+}
+
+/// Decrypts a machine config in memory and pipes it into
+/// `talosctl apply-config`, passing `args` through
+pub fn apply(path: OsString, args: Vec<String>, context: &GlobalContext) {
+    let Ok(talosctl) = which::which("talosctl") else {
+        print_error(format!(
+            "{} {}",
+            tr("talos-no-talosctl").red(),
+            tr("install-it-first").dimmed()
+        ));
+        std::process::exit(1);
+    };
+
+    let file = Path::new(&path);
+    let plaintext = match decrypt_in_memory(file, None, context) {
+        Ok(plaintext) => plaintext,
+        Err(e) => {
+            print_error(failed_to("decrypt-path-failed", file, &e));
+            std::process::exit(e.code);
+        }
+    };
+
+    // The plaintext only ever exists in this pipe
+    let mut command = Command::new(talosctl);
+    command
+        .args(["apply-config", "--file", "/dev/stdin"])
+        .args(&args)
+        .stdin(Stdio::piped());
+    let started = Instant::now();
+    let result = command.spawn().and_then(|mut child| {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&plaintext)?;
+        }
+        child.wait()
+    });
+    drop(plaintext);
+    session_record::record(&command, started, result.as_ref().map(ExitStatus::code));
+
+    match result {
+        Ok(status) if status.success() => {
+            print_success(tr_args(
+                "talos-applied",
+                &[("path", file.display().to_string().into())],
+            ));
+        }
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            print_error(format!("{} {}", tr("talos-run-failed").red(), e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Checks that the secrets sections of Talos configs are encrypted, either the
+/// given files or every machine config and secrets bundle in the project
+pub fn verify(paths: Vec<OsString>, context: &GlobalContext) {
+    let Some(root) = find_project_root(context) else {
+        print_error(tr("no-project-root"));
+        std::process::exit(1);
+    };
+    let explicit = !paths.is_empty();
+    let files: Vec<PathBuf> = if explicit {
+        paths.into_iter().map(PathBuf::from).collect()
+    } else {
+        walk_files(&root)
+            .into_iter()
+            .filter(|f| sops_file_type(f) == "yaml")
+            .collect()
+    };
+
+    let mut checked = 0;
+    let mut failed = 0;
+    for file in files {
+        let document = match fs::read_to_string(&file)
+            .map_err(|e| e.to_string())
+            .and_then(|c| serde_yaml::from_str(&c).map_err(|e| e.to_string()))
+        {
+            Ok(d) => d,
+            Err(e) => {
+                if explicit {
+                    print_error(failed_to("read-failed", &file, e));
+                    failed += 1;
+                }
+                continue;
+            }
+        };
+        if !explicit && !is_talos_config(&document) {
+            continue;
+        }
+        checked += 1;
+
+        let name = relative_path(&root, &file);
+        let plaintext = plaintext_secrets(&document);
+        if plaintext.is_empty() {
+            println!("{} {}", "✓".green(), name);
+        } else {
+            failed += 1;
+            println!("{} {}", "✗".red(), name);
+            for key in plaintext {
+                println!("    {} {}", tr("talos-plaintext").red(), key);
+            }
+        }
+    }
+
+    if checked == 0 && failed == 0 {
+        print_info(tr("talos-none-found"));
+        return;
+    }
+    if failed > 0 {
+        print_error(format!(
+            "{} {}",
+            tr_args("talos-plaintext-configs", &[("count", failed.into())]).red(),
+            tr("talos-encrypt-them").dimmed()
+        ));
+        std::process::exit(1);
+    }
+    print_success(tr_args("talos-all-encrypted", &[("count", checked.into())]));
+}
@@ -0,0 +1,181 @@
+use colored::Colorize;
+use git2::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    GlobalContext,
+    util::{
+        backups::{backup_ciphertext, backups_dir},
+        bulk::run_bulk,
+        dirs::project_state_dir,
+        file_lock::locks_dir,
+        find_project_root::find_project_root,
+        git_hooks::{remove_diff_driver, remove_opsops_hooks},
+        i18n::{failed_to, tr, tr_args},
+        op_key::get_age_key_from_1password,
+        output_permissions::{apply_permissions, resolve_permissions},
+        print_status::{print_error, print_info, print_success, print_warning},
+        prompts::{confirm, input},
+        sops_command::SopsCommandBuilder,
+        sops_files::find_encrypted_files,
+    },
+};
+
+/// Reverses what `setup` did for the current project
+pub fn teardown(fail_fast: bool, context: &GlobalContext) {
+    let root = match find_project_root(context) {
+        Some(r) => r,
+        None => {
+            print_error(tr("no-project-root"));
+            return;
+        }
+    };
+
+    println!("{} {}\n", tr("teardown-heading").bold(), root.display());
+
+    // Optionally decrypt everything, for teams migrating away
+    let encrypted = find_encrypted_files(&root);
+    if !encrypted.is_empty() {
+        print_warning(tr_args(
+            "teardown-found-encrypted",
+            &[("count", encrypted.len().into())],
+        ));
+        let decrypt = confirm(&tr("teardown-decrypt-all"), false).unwrap();
+
+        if decrypt {
+            let confirmation: String = input(&tr("teardown-type-decrypt"), None, true).unwrap();
+            if confirmation.trim() == "decrypt" {
+                decrypt_all(&encrypted, &root, fail_fast, context);
+            } else {
+                print_info(tr("teardown-not-confirmed"));
+            }
+        }
+    }
+
+    // Git hooks and diff drivers
+    if let Ok(repo) = Repository::open(&root) {
+        match remove_opsops_hooks(&repo) {
+            Ok(removed) if removed.is_empty() => print_info(tr("teardown-no-hooks")),
+            Ok(removed) => {
+                for hook in removed {
+                    print_success(tr_args(
+                        "teardown-removed-hook",
+                        &[("path", hook.display().to_string().into())],
+                    ));
+                }
+            }
+            Err(e) => print_error(e),
+        }
+        match remove_diff_driver(&repo) {
+            Ok(removed) => {
+                for key in removed {
+                    print_success(tr_args(
+                        "teardown-removed-git-config",
+                        &[("key", key.into())],
+                    ));
+                }
+            }
+            Err(e) => print_error(e),
+        }
+
+        // Edit locks
+        let locks = locks_dir(&repo);
+        if locks.is_dir() {
+            match fs::remove_dir_all(&locks) {
+                Ok(_) => print_success(tr_args(
+                    "removed",
+                    &[("path", locks.display().to_string().into())],
+                )),
+                Err(e) => print_error(failed_to("remove-failed", &locks, e)),
+            }
+        }
+    }
+
+    // Caches and logs, but not the backups `opsops restore` needs after teardown
+    let state_dir = project_state_dir(&root);
+    let backups = backups_dir(&root, context);
+    if state_dir.is_dir() {
+        match remove_state(&state_dir, &backups) {
+            Ok(false) => print_success(tr_args(
+                "removed",
+                &[("path", state_dir.display().to_string().into())],
+            )),
+            Ok(true) => print_success(tr_args(
+                "teardown-kept-backups",
+                &[
+                    ("path", state_dir.display().to_string().into()),
+                    ("backups", backups.display().to_string().into()),
+                ],
+            )),
+            Err(e) => print_error(failed_to("remove-failed", &state_dir, e)),
+        }
+    }
+
+    println!();
+    print_success(format!("{}", tr("teardown-complete").green()));
+}
+
+/// Removes `state_dir` except for `backups` if it lies inside, returning
+/// whether backups were kept
+fn remove_state(state_dir: &Path, backups: &Path) -> std::io::Result<bool> {
+    if !backups.starts_with(state_dir) || !backups.is_dir() {
+        fs::remove_dir_all(state_dir)?;
+        return Ok(false);
+    }
+    for entry in fs::read_dir(state_dir)? {
+        let path = entry?.path();
+        if backups.starts_with(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(true)
+}
+
+/// Decrypts every file in place. Exits before anything else is removed if any
+/// of them failed, so the project isn't left half torn down.
+fn decrypt_all(files: &[PathBuf], root: &Path, fail_fast: bool, context: &GlobalContext) {
+    // Fetch the key once instead of once per file
+    let age_key = match get_age_key_from_1password(context) {
+        Ok(key) => key,
+        Err(e) => {
+            print_error(format!("{} {}", tr("age-key-failed").red(), e));
+            std::process::exit(1);
+        }
+    };
+
+    let report = run_bulk(files, "decrypt", fail_fast, |file| {
+        // Decrypting in place drops the ciphertext, keep it for `opsops restore`
+        backup_ciphertext(root, file, context)?;
+        let permissions = resolve_permissions(file, None, None, context)?;
+        let output = SopsCommandBuilder::new(context)
+            .arg("--decrypt")
+            .arg("--in-place")
+            .arg_path(file)
+            .with_age_key_value(&age_key)
+            ._output()
+            .map_err(|e| format!("{} {}", tr("sops-launch-failed"), e))?;
+        if output.status.success() {
+            apply_permissions(file, &permissions)
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(stderr
+                .trim()
+                .lines()
+                .last()
+                .unwrap_or("sops failed")
+                .to_string())
+        }
+    });
+    report.print_summary();
+
+    if report.exit_code() != 0 {
+        print_error(tr("teardown-still-encrypted"));
+        std::process::exit(report.exit_code());
+    }
+}
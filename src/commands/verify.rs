@@ -0,0 +1,248 @@
+use colored::Colorize;
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{
+    GlobalContext,
+    util::{
+        find_project_root::find_project_root,
+        i18n::{tr, tr_args},
+        json_schema::{SchemaIndex, validate_content},
+        op_key::{get_age_key_from_1password, validate_age_recipients, with_fingerprint},
+        output_format::FindingsFormat,
+        print_status::{print_error, print_info, print_success},
+        rule_match::relative_path,
+        sarif::{Finding, SarifRule, to_sarif},
+        sops_command::decrypt_in_memory,
+        sops_files::{is_sops_encrypted_file, walk_files},
+        sops_metadata::read_metadata,
+    },
+};
+
+const UNENCRYPTED_FILE: SarifRule = SarifRule {
+    id: "unencrypted-file",
+    description: "A file covered by a rule with a schema is not encrypted",
+};
+const SCHEMA_VIOLATION: SarifRule = SarifRule {
+    id: "schema-violation",
+    description: "The decrypted file doesn't match the schema of its rule",
+};
+const DECRYPT_FAILED: SarifRule = SarifRule {
+    id: "decrypt-failed",
+    description: "The file could not be decrypted",
+};
+
+/// Checks the files of rules with a schema attached: that they are encrypted
+/// and, with `deep`, that their decrypted content matches the schema
+pub fn verify(paths: Vec<OsString>, deep: bool, format: FindingsFormat, context: &GlobalContext) {
+    let Some(root) = find_project_root(context) else {
+        print_error(tr("no-project-root"));
+        std::process::exit(1);
+    };
+    let index = match SchemaIndex::load(context) {
+        Ok(Some(index)) => index,
+        Ok(None) => {
+            print_info(tr("verify-no-schemas"));
+            return;
+        }
+        Err(e) => {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    };
+
+    let files: Vec<(PathBuf, PathBuf)> = if paths.is_empty() {
+        walk_files(&root)
+    } else {
+        paths.into_iter().map(PathBuf::from).collect()
+    }
+    .into_iter()
+    .filter_map(|file| index.schema_for(&file).map(|schema| (file, schema)))
+    .collect();
+    if files.is_empty() {
+        print_info(tr("verify-no-files"));
+        return;
+    }
+
+    // Fetch the key once instead of once per file
+    let age_key = if deep {
+        match get_age_key_from_1password(context) {
+            Ok(key) => Some(key),
+            Err(e) => {
+                print_error(format!("{} {}", tr("age-key-failed").red(), e));
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut failed = 0;
+    let mut findings = Vec::new();
+    for (file, schema) in &files {
+        let name = relative_path(&root, file);
+        let problems: Vec<(&SarifRule, String)> = if !is_sops_encrypted_file(file) {
+            vec![(&UNENCRYPTED_FILE, tr("verify-not-encrypted"))]
+        } else if let Some(age_key) = &age_key {
+            match decrypt_in_memory(file, Some(age_key), context) {
+                Ok(plaintext) => match validate_content(schema, file, &plaintext) {
+                    Ok(errors) => errors
+                        .iter()
+                        .map(|e| (&SCHEMA_VIOLATION, e.to_string()))
+                        .collect(),
+                    Err(e) => vec![(&SCHEMA_VIOLATION, e)],
+                },
+                Err(e) => vec![(
+                    &DECRYPT_FAILED,
+                    tr_args("verify-decrypt-failed", &[("error", e.to_string().into())]),
+                )],
+            }
+        } else {
+            Vec::new()
+        };
+
+        if !problems.is_empty() {
+            failed += 1;
+        }
+        if format == FindingsFormat::Sarif {
+            findings.extend(problems.into_iter().map(|(rule, problem)| Finding {
+                rule_id: rule.id,
+                message: format!("{}: {}", name, problem),
+                path: name.clone(),
+                line: None,
+            }));
+        } else if problems.is_empty() {
+            println!("{} {}", "✓".green(), name);
+        } else {
+            println!("{} {}", "✗".red(), name);
+            for (_, problem) in problems {
+                println!("    {}", problem);
+            }
+        }
+    }
+
+    if format == FindingsFormat::Sarif {
+        match to_sarif(
+            &[UNENCRYPTED_FILE, SCHEMA_VIOLATION, DECRYPT_FAILED],
+            &findings,
+        ) {
+            Ok(sarif) => println!("{}", sarif),
+            Err(e) => {
+                print_error(e);
+                std::process::exit(1);
+            }
+        }
+        if failed > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if failed > 0 {
+        print_error(
+            tr_args(
+                "verify-failed",
+                &[("failed", failed.into()), ("count", files.len().into())],
+            )
+            .red(),
+        );
+        std::process::exit(1);
+    }
+    print_success(tr_args(
+        if deep {
+            "verify-all-match"
+        } else {
+            "verify-all-encrypted"
+        },
+        &[("count", files.len().into())],
+    ));
+}
+
+/// The form `key` is compared in: PGP fingerprints in upper case, everything
+/// else as given
+fn comparable_key(key: &str) -> String {
+    let key = key.trim();
+    if key.len() >= 16 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+        key.to_ascii_uppercase()
+    } else {
+        key.to_string()
+    }
+}
+
+/// Checks that each file's data key is encrypted to exactly the keys in
+/// `against`, reading only the sops metadata. Lists the missing and extra
+/// keys of every file that differs and exits with 1 if any does.
+pub fn verify_recipients(paths: Vec<OsString>, against: Vec<String>) {
+    for key in against.iter().filter(|key| key.starts_with("age1")) {
+        if let Err(e) = validate_age_recipients(key) {
+            print_error(e.red());
+            std::process::exit(1);
+        }
+    }
+    let expected: BTreeSet<String> = against.iter().map(|key| comparable_key(key)).collect();
+
+    let mut failed = 0;
+    for path in &paths {
+        let name = path.to_string_lossy();
+        let metadata = fs::read_to_string(path)
+            .map_err(|e| tr_args("verify-read-failed", &[("error", e.to_string().into())]))
+            .and_then(|contents| read_metadata(&contents).ok_or_else(|| tr("verify-not-sops")));
+        let actual: BTreeSet<String> = match metadata {
+            Ok(metadata) => metadata
+                .key_ids()
+                .iter()
+                .map(|key| comparable_key(key))
+                .collect(),
+            Err(e) => {
+                failed += 1;
+                println!("{} {}: {}", "✗".red(), name, e);
+                continue;
+            }
+        };
+        if actual == expected {
+            println!("{} {}", "✓".green(), name);
+            continue;
+        }
+        failed += 1;
+        println!("{} {}", "✗".red(), name);
+        for key in expected.difference(&actual) {
+            println!(
+                "    {} {}  {}",
+                "-".red(),
+                with_fingerprint(key),
+                tr("verify-missing")
+            );
+        }
+        for key in actual.difference(&expected) {
+            println!(
+                "    {} {}  {}",
+                "+".yellow(),
+                with_fingerprint(key),
+                tr("verify-extra")
+            );
+        }
+    }
+
+    if failed > 0 {
+        print_error(
+            tr_args(
+                "verify-recipients-failed",
+                &[
+                    ("failed", failed.into()),
+                    ("count", paths.len().into()),
+                    ("keys", expected.len().into()),
+                ],
+            )
+            .red(),
+        );
+        std::process::exit(1);
+    }
+    print_success(tr_args(
+        "verify-recipients-ok",
+        &[
+            ("count", paths.len().into()),
+            ("keys", expected.len().into()),
+        ],
+    ));
+}
@@ -1,76 +1,740 @@
 mod commands;
 mod util;
 use clap::{CommandFactory, Parser, Subcommand};
-use clap_complete::{generate_to, shells::Fish};
 use clap_mangen::Man;
+use commands::complete::CompletionKind;
 use std::ffi::OsString;
 use std::fs;
 use std::io;
-use std::path::Path;
-use util::print_status::print_info;
+use std::path::{Path, PathBuf};
+use util::check_report::FailOn;
+use util::ci_pipeline::CiProvider;
+use util::env_key::KeyPreference;
+use util::formatter::{FormatterKind, set_formatter};
+use util::formatting::Formatting;
+use util::i18n::{failed_to, tr, tr_args};
+use util::output_format::{FindingsFormat, OutputFormat};
+use util::print_status::{print_error, print_info};
+use util::project_templates::ProjectTemplate;
+use util::prompts::set_accessible;
+use util::session_record::{finish_recording, start_recording};
 
 #[derive(Debug, Parser)]
 #[command(name = "opsops")]
 #[command(version, about = "A wrapper that integrates sops with 1Password", long_about = None)]
+#[command(disable_version_flag = true, disable_help_subcommand = true)]
 struct Cli {
+    /// Print version, with --json including the build hash
+    #[arg(short = 'V', long = "version")]
+    show_version: bool,
+
+    /// Print the version as JSON
+    #[arg(long = "json", requires = "show_version")]
+    version_json: bool,
+
+    /// Run as if opsops was started in this directory
+    #[arg(
+        short = 'C',
+        long = "chdir",
+        env = "OPSOPS_CHDIR",
+        global = true,
+        value_name = "DIR",
+        help = "Run as if opsops was started in <DIR>"
+    )]
+    chdir: Option<PathBuf>,
+
     /// Path to the .sops.yaml file
-    #[arg(long, global = true, help = "Path to the .sops.yaml file")]
+    #[arg(
+        long,
+        env = "OPSOPS_SOPS_FILE",
+        global = true,
+        help = "Path to the .sops.yaml file"
+    )]
     sops_file: Option<String>,
 
     /// 1Password item reference e.g., op://Personal/test/Private Key
     #[arg(
         long,
+        env = "OPSOPS_OP_ITEM",
         global = true,
         help = "1Password item reference (e.g. op://MyVault/MyItem/MyField)"
     )]
     op_item: Option<String>,
 
+    /// Hand the age key to sops via SOPS_AGE_KEY instead of a file descriptor
+    #[arg(
+        long,
+        env = "OPSOPS_AGE_KEY_ENV",
+        global = true,
+        help = "Pass the age key to sops via the SOPS_AGE_KEY environment variable instead of a file descriptor"
+    )]
+    age_key_env: bool,
+
+    /// Refuse to change any files, configs, caches or 1Password items
+    #[arg(
+        long,
+        env = "OPSOPS_READ_ONLY",
+        global = true,
+        help = "Refuse every write to files, configs, caches, clusters and 1Password; decrypt prints to stdout"
+    )]
+    read_only: bool,
+
+    /// How status messages are printed
+    #[arg(
+        long,
+        env = "OPSOPS_FORMATTER",
+        global = true,
+        value_enum,
+        default_value_t = FormatterKind::Human,
+        help = "How status messages are printed; plain and json also disable colors"
+    )]
+    formatter: FormatterKind,
+
+    /// Screen reader friendly prompts and output
+    #[arg(
+        long,
+        env = "OPSOPS_ACCESSIBLE",
+        global = true,
+        help = "Screen reader friendly: numbered plain-text prompts, no redrawn lines, plain messages"
+    )]
+    accessible: bool,
+
+    /// Record the external commands opsops runs, for troubleshooting
+    #[arg(
+        long,
+        env = "OPSOPS_RECORD",
+        global = true,
+        value_name = "FILE",
+        help = "Record the external commands opsops runs (redacted), their exit codes and timings to <FILE>; show it with `opsops replay`"
+    )]
+    record: Option<PathBuf>,
+
+    /// Use the age key from SOPS_AGE_KEY, SOPS_AGE_KEY_FILE or SOPS_AGE_KEY_CMD
+    #[arg(
+        long,
+        env = "OPSOPS_PREFER_ENV_KEY",
+        global = true,
+        conflicts_with = "prefer_op_key",
+        help = "Use the age key from SOPS_AGE_KEY, SOPS_AGE_KEY_FILE or SOPS_AGE_KEY_CMD instead of the project's key"
+    )]
+    prefer_env_key: bool,
+
+    /// Use the project's key even if the environment holds one, without a warning
+    #[arg(
+        long,
+        env = "OPSOPS_PREFER_OP_KEY",
+        global = true,
+        help = "Use the project's key (1Password or passphrase) and ignore age keys in the environment without warning"
+    )]
+    prefer_op_key: bool,
+
+    /// Print where the time went: 1Password, sops, config IO or opsops itself
+    #[arg(
+        long,
+        env = "OPSOPS_PROFILE_PERF",
+        global = true,
+        help = "Print to stderr how long key retrieval, op and sops calls and config IO took, as JSON with --formatter json"
+    )]
+    profile_perf: bool,
+
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Parse and display the .sops.yaml for this project
     #[command(arg_required_else_help = false)]
-    ListConfig {},
+    ListConfig {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
 
     /// Generate an age key pair
     #[command(arg_required_else_help = false)]
-    GenerateAgeKey {},
+    GenerateAgeKey {
+        /// Also print the key as a 24 word recovery phrase and store it in 1Password
+        #[arg(long)]
+        mnemonic: bool,
+    },
 
     /// Edit a file using sops with a key from 1password
-    #[command(arg_required_else_help = true)]
     Edit {
-        #[arg(value_name = "PATH", help = "Path to the file to edit")]
-        path: OsString,
+        #[arg(
+            value_name = "PATH",
+            help = "Path to the file to edit, picked interactively if omitted"
+        )]
+        path: Option<OsString>,
+
+        /// Create the file if it doesn't exist, from its rule's schema or preset
+        #[arg(long)]
+        create: bool,
+
+        /// Edit even if another opsops process holds the file's lock
+        #[arg(long)]
+        force: bool,
+
+        /// Directory for the plaintext while editing, `tmpfs` picks a RAM backed one
+        #[arg(long, value_name = "DIR|tmpfs")]
+        tmpdir: Option<String>,
+
+        /// Run the editor without network access (Linux, needs unprivileged user namespaces)
+        #[arg(long)]
+        sandbox: bool,
     },
 
     /// Encrypt a file using sops
-    #[command(arg_required_else_help = true)]
     Encrypt {
-        #[arg(value_name = "PATH", help = "Path to the file to encrypt")]
-        path: OsString,
+        #[arg(
+            value_name = "PATH",
+            help = "Path to the file to encrypt, picked interactively if omitted"
+        )]
+        path: Option<OsString>,
+
+        /// Encrypt even if another opsops process holds the file's lock
+        #[arg(long)]
+        force: bool,
+
+        /// Encrypt even config files and files inside .git, which rules never should match
+        #[arg(long)]
+        allow_protected: bool,
+
+        /// Summarize which keys were newly encrypted, left in plaintext, and whether the data key was reused
+        #[arg(long)]
+        diff: bool,
+
+        /// Spaces per indentation level of the YAML or JSON sops writes [default: `formatting:` in .opsops.yaml]
+        #[arg(long, value_name = "N")]
+        indent: Option<u8>,
     },
 
     /// Decrypt a file using sops
-    #[command(arg_required_else_help = true)]
     Decrypt {
-        #[arg(value_name = "PATH", help = "Path to the encrypted file to decrypt")]
+        #[arg(
+            value_name = "PATH",
+            help = "Path to the encrypted file to decrypt, picked interactively if omitted"
+        )]
+        path: Option<OsString>,
+
+        /// Mode of the decrypted file in octal [default: 0600]
+        #[arg(long, value_name = "MODE")]
+        mode: Option<String>,
+
+        /// Owner of the decrypted file as user:group [default: the invoking user under sudo]
+        #[arg(long, value_name = "USER:GROUP")]
+        owner: Option<String>,
+
+        /// Decrypt to a new path per invocation in the runtime directory and print it
+        #[arg(long)]
+        unique_output: bool,
+
+        /// Spaces per indentation level of the YAML or JSON sops writes [default: `formatting:` in .opsops.yaml]
+        #[arg(long, value_name = "N")]
+        indent: Option<u8>,
+
+        /// Sort the keys of YAML and JSON documents
+        #[arg(long)]
+        sort_keys: bool,
+
+        /// Decrypt and inline the files referenced with `!opsops-include <path>` or `$opsopsInclude`
+        #[arg(long)]
+        resolve_includes: bool,
+
+        /// Decrypt every encrypted file to its `decrypt_outputs:` destination, or next to it for .enc files
+        #[arg(long, conflicts_with_all = ["path", "mode", "owner", "unique_output", "resolve_includes"])]
+        all: bool,
+
+        /// With --all, stop at the first file that fails
+        #[arg(long, requires = "all")]
+        fail_fast: bool,
+    },
+
+    /// Hold the age key in memory so 1Password is only asked once per session
+    Agent {
+        /// Stop a running agent
+        #[arg(long)]
+        stop: bool,
+    },
+
+    /// Benchmark rule matching, key retrieval and bulk encryption on this machine
+    Bench {
+        /// Benchmark opsops itself
+        #[arg(long = "self", required = true)]
+        self_: bool,
+
+        /// Number of files in the synthetic repository used for rule matching
+        #[arg(long, default_value_t = 10_000)]
+        files: usize,
+
+        /// How often each measurement is repeated
+        #[arg(long, default_value_t = 5)]
+        rounds: usize,
+    },
+
+    /// Print the opsops build, the sops/op/age versions and the configuration in effect for bug reports
+    Info {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// Print the sops metadata of an encrypted file without decrypting it
+    Meta {
+        #[arg(value_name = "PATH", help = "Path to the encrypted file")]
         path: OsString,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// Show the subject, SANs and expiry of a certificate or the type of a key stored in an encrypted file
+    Inspect {
+        #[arg(
+            value_name = "FILE",
+            required_unless_present = "certs",
+            help = "Encrypted file, with --certs the only one to search"
+        )]
+        file: Option<PathBuf>,
+
+        #[arg(
+            value_name = "KEY_PATH",
+            required_unless_present = "certs",
+            conflicts_with = "certs",
+            help = "Key path of the value, e.g. tls.crt"
+        )]
+        key_path: Option<String>,
+
+        /// List every certificate in the project's encrypted files with its expiry date
+        #[arg(long)]
+        certs: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// Summarize the project's encrypted files, rules, recipients, rotation dates and sizes
+    Stats {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// List values expiring within this many days next to expired ones
+        #[arg(long, value_name = "DAYS", default_value_t = util::value_expiry::DEFAULT_WARNING_DAYS)]
+        warn_days: u64,
+
+        /// Exit with 1 if a value is past its expiry date, for CI
+        #[arg(long)]
+        fail_on_expired: bool,
+    },
+
+    /// Print where opsops keeps its configuration, caches, state and locks
+    Paths {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// Print help for a command, or a guide: workflows, key-rotation, ci
+    Help {
+        #[arg(
+            value_name = "COMMAND|GUIDE",
+            help = "Command, e.g. 'key upgrade', or guide to show"
+        )]
+        topic: Vec<String>,
+    },
+
+    /// Print the man page of opsops or of a command, e.g. 'opsops man > opsops.1'
+    Man {
+        #[arg(
+            value_name = "COMMAND",
+            help = "Command to print the page of, e.g. 'key upgrade'"
+        )]
+        command: Vec<String>,
+    },
+
+    /// Generate CI pipelines that check and decrypt the project's secrets
+    Ci {
+        #[command(subcommand)]
+        command: CiCommands,
     },
 
     /// Troubleshoot your current config
     #[command(arg_required_else_help = false)]
-    Doctor {},
+    Doctor {
+        /// Never prompt and exit non-zero when a check fails
+        #[arg(long)]
+        ci: bool,
+
+        /// Lowest severity that fails the run with --ci
+        #[arg(long, value_enum, default_value_t = FailOn::Error)]
+        fail_on: FailOn,
+
+        /// Warn when the age key also protects other projects in the key registry
+        #[arg(long)]
+        org: bool,
+
+        /// Write a JUnit XML report of all checks to this file
+        #[arg(long, value_name = "FILE")]
+        junit: Option<PathBuf>,
+
+        /// Write a JSON report of all checks to this file
+        #[arg(long, value_name = "FILE")]
+        json: Option<PathBuf>,
+    },
 
     /// Initialize opsops
-    Init {},
+    Init {
+        /// Write the config without prompting, for this age public key or 1Password reference (op://...)
+        #[arg(long, value_name = "PUBKEY|OP_REF", group = "key")]
+        from_key: Option<String>,
+
+        /// Derive the age key from a passphrase instead of keeping it in 1Password ($OPSOPS_PASSPHRASE or prompted)
+        #[arg(long, group = "key")]
+        passphrase: bool,
+
+        /// Creation rule to write with --from-key or --passphrase, e.g. 'path_regex=secrets/.*,encrypted_regex=^data$' (repeatable)
+        #[arg(long = "rule", value_name = "SPEC", requires = "key")]
+        rules: Vec<String>,
+    },
+
+    /// Scaffold a project from a template: layout, .sops.yaml rules, encrypted examples, git hooks and CI
+    New {
+        /// The layout to create
+        #[arg(value_enum)]
+        template: ProjectTemplate,
+
+        /// Where to create it, a git repository is initialized unless it is inside one
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Age public key or 1Password reference (op://...) to encrypt to [default: --op-item]
+        #[arg(long, value_name = "PUBKEY|OP_REF", conflicts_with = "passphrase")]
+        from_key: Option<String>,
+
+        /// Derive the age key from a passphrase instead of keeping it in 1Password
+        #[arg(long)]
+        passphrase: bool,
+    },
+
+    /// Guided first-run setup: tools, 1Password, age key, .sops.yaml and git hooks
+    Setup {},
+
+    /// Remove opsops git hooks and state from this project, optionally decrypting all files
+    Teardown {
+        /// Stop decrypting at the first file that fails
+        #[arg(long)]
+        fail_fast: bool,
+    },
+
+    /// Argo CD integration: set up KSOPS decryption with the key from 1Password
+    Argocd {
+        #[command(subcommand)]
+        command: ArgocdCommands,
+    },
+
+    /// Flux integration: check Kustomization decryption and manage the cluster decryption Secret
+    Flux {
+        #[command(subcommand)]
+        command: FluxCommands,
+    },
+
+    /// Talos workflows: encrypt the secrets bundle, apply encrypted machine configs and verify them
+    Talos {
+        #[command(subcommand)]
+        command: TalosCommands,
+    },
+
+    /// Use encrypted kubeconfigs: decrypt one to tmpfs for a shell and wipe it afterwards
+    Kubeconfig {
+        #[command(subcommand)]
+        command: KubeconfigCommands,
+    },
+
+    /// Check that every file stays recoverable by the escrow recipient from .opsops.yaml
+    Escrow {
+        #[command(subcommand)]
+        command: EscrowCommands,
+    },
+
+    /// Keep 1Password fields and the encrypted files listed under sync: in .opsops.yaml in step
+    Sync {
+        #[command(subcommand)]
+        command: SyncCommands,
+    },
+
+    /// Recover age keys and add team members as recipients
+    Key {
+        #[command(subcommand)]
+        command: KeyCommands,
+    },
+
+    /// Sync the team's shared registry of member public keys
+    Registry {
+        #[command(subcommand)]
+        command: RegistryCommands,
+    },
+
+    /// Check that files of rules with a schema are encrypted and, with --deep, match the schema
+    Verify {
+        #[arg(
+            value_name = "PATH",
+            help = "Files to check [default: all files covered by a rule with a schema]"
+        )]
+        paths: Vec<OsString>,
+
+        /// Decrypt the files and validate them against the schema of their rule
+        #[arg(long)]
+        deep: bool,
+
+        /// Output format, sarif for GitHub code scanning and editors
+        #[arg(long, value_enum, default_value_t = FindingsFormat::Text)]
+        format: FindingsFormat,
+    },
+
+    /// Check that files are encrypted to exactly the given keys, without decrypting them
+    VerifyRecipients {
+        #[arg(
+            value_name = "PATH",
+            required = true,
+            help = "Encrypted files to check"
+        )]
+        paths: Vec<OsString>,
+
+        /// The expected keys: age recipients, PGP fingerprints or `<source>:<id>`, e.g. `kms:<arn>`
+        #[arg(long, value_name = "KEY", required = true, num_args = 1.., value_delimiter = ',')]
+        against: Vec<String>,
+    },
+
+    /// Look for private keys in tracked files and recent git history
+    Scan {
+        /// How many commits back from HEAD to look
+        #[arg(long, value_name = "COMMITS", default_value_t = util::secret_scan::HISTORY_DEPTH)]
+        history: usize,
+
+        /// Output format, sarif for GitHub code scanning and editors
+        #[arg(long, value_enum, default_value_t = FindingsFormat::Text)]
+        format: FindingsFormat,
+    },
+
+    /// List encrypted files whose recipients or encrypted keys drifted from their creation rule
+    Drift {
+        #[arg(
+            value_name = "PATH",
+            help = "Files to check [default: all encrypted files in the project]"
+        )]
+        paths: Vec<OsString>,
+
+        /// Run `sops updatekeys` on files whose recipients drifted
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Import credentials from other formats into encrypted files
+    Import {
+        #[command(subcommand)]
+        command: ImportCommands,
+    },
+
+    /// Export encrypted files into other formats
+    Export {
+        #[command(subcommand)]
+        command: ExportCommands,
+    },
+
+    /// Push an encrypted file to the S3/GCS bucket or Vault path of its destination rule
+    Publish {
+        #[arg(value_name = "PATH", help = "Path to the encrypted file")]
+        path: String,
+
+        /// Don't ask before publishing
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Drop the file extension from the destination name
+        #[arg(long)]
+        omit_extensions: bool,
+    },
+
+    /// Search the keys and values of encrypted files, decrypting them in memory
+    #[command(arg_required_else_help = true)]
+    Grep {
+        /// Regex matched against `a.b[0].c` key paths and values
+        pattern: String,
+
+        #[arg(
+            value_name = "PATH",
+            help = "Files to search [default: all encrypted files]"
+        )]
+        paths: Vec<OsString>,
+
+        /// Only match key paths
+        #[arg(long, conflicts_with = "values")]
+        keys: bool,
+
+        /// Only match values
+        #[arg(long)]
+        values: bool,
+
+        /// Print the values of hits instead of redacting them
+        #[arg(long)]
+        show_values: bool,
+
+        /// Match case-insensitively
+        #[arg(short, long)]
+        ignore_case: bool,
+    },
+
+    /// Copy a value or subtree from one encrypted file into another, in memory
+    #[command(arg_required_else_help = true)]
+    Cp {
+        #[arg(
+            value_name = "SRC",
+            help = "Encrypted file and key path to copy, e.g. api.yaml#db.password"
+        )]
+        source: String,
+
+        #[arg(
+            value_name = "DST",
+            help = "Encrypted file and key path to write to, the source's key path if omitted"
+        )]
+        destination: String,
+
+        /// Overwrite a value that already exists at the destination
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Write a value into an encrypted file, generated or read without echo
+    #[command(arg_required_else_help = true)]
+    Set {
+        #[arg(
+            value_name = "FILE",
+            help = "Encrypted file, created if it doesn't exist"
+        )]
+        file: PathBuf,
+
+        #[arg(
+            value_name = "KEY_PATH",
+            help = "Where the value goes, e.g. db.password"
+        )]
+        key_path: String,
+
+        /// Mint the value: `password[:N]`, `hex[:N]`, `base64[:N]`, `uuid`, `rsa[:BITS]`, `ed25519` or `htpasswd:<user>`
+        #[arg(long, value_name = "GENERATOR")]
+        generate: Option<String>,
+
+        /// Overwrite a value that already exists
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Move an encrypted file, updating the rules written for exactly that file
+    #[command(arg_required_else_help = true)]
+    Mv {
+        #[arg(value_name = "FROM", help = "Encrypted file to move")]
+        from: OsString,
+
+        #[arg(value_name = "TO", help = "New path, or a directory to move it into")]
+        to: OsString,
+    },
+
+    /// Show which values of a plaintext file its rule would encrypt, without encrypting it
+    Preview {
+        #[arg(value_name = "PATH", help = "Path to the plaintext file")]
+        path: OsString,
+    },
+
+    /// Delete an encrypted file, its plaintext copies and the rules written for it
+    Rm {
+        #[arg(value_name = "PATH", help = "Encrypted file to delete")]
+        path: OsString,
+
+        /// Remove the rules written for exactly this file without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Work on creation rules
+    Rule {
+        #[command(subcommand)]
+        command: RuleCommands,
+    },
+
+    /// Roll an encrypted file back to a backup taken before opsops overwrote it
+    Restore {
+        #[arg(value_name = "PATH", help = "Path to the file to restore")]
+        path: OsString,
+
+        /// Restore the latest backup whose timestamp starts with this, e.g. 20240131 or 20240131T1530
+        #[arg(long, value_name = "TIMESTAMP")]
+        at: Option<String>,
+
+        /// List the backups of the file instead of restoring one
+        #[arg(long, conflicts_with = "at")]
+        list: bool,
+    },
+
+    /// Print a session recorded with --record
+    Replay {
+        #[arg(value_name = "FILE", help = "Session file written by --record")]
+        path: PathBuf,
+    },
 
     /// Read an encrypted file and print its decrypted content to stdout
     Read {
-        #[arg(value_name = "PATH", help = "Path to the file to read")]
+        #[arg(
+            value_name = "PATH",
+            help = "Path to the file to read, picked interactively if omitted"
+        )]
+        path: Option<OsString>,
+
+        /// Output format, `text` keeps the format of the file
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Only print the value at this key path, e.g. `data.password` or `users[0].name`
+        #[arg(long, value_name = "KEY_PATH")]
+        extract: Option<String>,
+
+        /// Replace every value with a placeholder, keeping only keys and structure
+        #[arg(long)]
+        redact: bool,
+
+        /// Spaces per indentation level of the YAML or JSON sops writes [default: `formatting:` in .opsops.yaml]
+        #[arg(long, value_name = "N")]
+        indent: Option<u8>,
+
+        /// Sort the keys of YAML and JSON documents
+        #[arg(long)]
+        sort_keys: bool,
+
+        /// Decrypt and inline the files referenced with `!opsops-include <path>` or `$opsopsInclude`
+        #[arg(long)]
+        resolve_includes: bool,
+    },
+
+    /// Show how the plaintext of an encrypted file changed since a git revision
+    Diff {
+        #[arg(value_name = "PATH", help = "Encrypted file to compare")]
         path: OsString,
+
+        /// Revision to compare the working tree with
+        #[arg(long, value_name = "REV", default_value = "HEAD")]
+        rev: String,
+
+        /// `inline`, `side-by-side` or a command such as `delta` [default: `diff_renderer:` in the user config, else inline]
+        #[arg(long, value_name = "RENDERER")]
+        renderer: Option<String>,
+    },
+
+    /// Serve a JSON-RPC API for editor integrations
+    Serve {
+        /// Communicate over stdin/stdout (newline-delimited JSON-RPC 2.0)
+        #[arg(long, required = true)]
+        stdio: bool,
     },
 
     /// Set up encryption patterns for a file
@@ -81,6 +745,37 @@ enum Commands {
             help = "Path to the file to configure encryption for"
         )]
         path: OsString,
+
+        /// Custom path_regex for the rule instead of matching exactly this file
+        #[arg(long, value_name = "REGEX")]
+        path_regex: Option<String>,
+
+        /// Regex of keys to encrypt, skips the interactive prompt
+        #[arg(long, value_name = "REGEX")]
+        encrypted_regex: Option<String>,
+    },
+
+    /// Print dynamic completion candidates for shell completion scripts
+    #[command(name = "__complete", alias = "completions-data", hide = true)]
+    Complete {
+        /// What to complete
+        #[arg(value_enum)]
+        kind: CompletionKind,
+
+        /// The word being completed
+        #[arg(default_value = "", allow_hyphen_values = true)]
+        prefix: String,
+    },
+
+    /// Print the shell completion script, or install it with --install
+    Completions {
+        /// The shell to complete for [default: the one in $SHELL]
+        #[arg(value_enum)]
+        shell: Option<clap_complete::Shell>,
+
+        /// Write the script where the shell loads completions from, asking before editing startup files
+        #[arg(long)]
+        install: bool,
     },
 
     /// Generate shell completions and man pages
@@ -92,10 +787,374 @@ enum Commands {
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum RuleCommands {
+    /// List which keys of a YAML/JSON document an encrypted_regex would encrypt
+    Test {
+        /// The encrypted_regex to try
+        #[arg(long, value_name = "PATTERN")]
+        regex: String,
+
+        #[arg(value_name = "PATH", help = "Path to a sample document")]
+        path: OsString,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ArgocdCommands {
+    /// Apply the sops-age Secret and write the repo-server and argocd-cm patches for KSOPS
+    Bootstrap {
+        /// Namespace Argo CD runs in
+        #[arg(long, default_value = "argocd")]
+        namespace: String,
+
+        /// Name of the Secret holding the age key
+        #[arg(long, default_value = "sops-age")]
+        secret_name: String,
+
+        /// Image providing the ksops and kustomize binaries
+        #[arg(long, default_value = util::argocd::DEFAULT_KSOPS_IMAGE)]
+        ksops_image: String,
+
+        /// Directory to write the patches to
+        #[arg(long, value_name = "DIR", default_value = "argocd-ksops")]
+        patch_dir: PathBuf,
+
+        /// Only validate the Secret with kubectl apply --dry-run=client
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum FluxCommands {
+    /// Check that Kustomizations decrypt with sops and their Secret holds the key from 1Password
+    Check {},
+
+    /// Create or update the Secret Flux decrypts with from the key in 1Password
+    CreateSecret {
+        /// Name of the Secret, as in spec.decryption.secretRef.name
+        #[arg(long, default_value = "sops-age")]
+        name: String,
+
+        /// Namespace of the Secret
+        #[arg(long, default_value = "flux-system")]
+        namespace: String,
+
+        /// Only validate the Secret with kubectl apply --dry-run=client
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum TalosCommands {
+    /// Encrypt the secrets bundle from 'talosctl gen secrets', adding a Talos rule if needed
+    EncryptSecrets {
+        #[arg(
+            value_name = "PATH",
+            help = "Path to the secrets bundle [default: secrets.yaml]"
+        )]
+        path: Option<OsString>,
+    },
+
+    /// Decrypt a machine config in memory and pipe it into 'talosctl apply-config'
+    Apply {
+        #[arg(value_name = "PATH", help = "Path to the encrypted machine config")]
+        path: OsString,
+
+        /// Arguments passed on to talosctl apply-config, e.g. -- --nodes 10.0.0.2
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+
+    /// Check that the secrets sections of Talos configs are encrypted
+    Verify {
+        #[arg(
+            value_name = "PATH",
+            help = "Files to check [default: all machine configs and secrets bundles in the project]"
+        )]
+        paths: Vec<OsString>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum KubeconfigCommands {
+    /// Start a shell with KUBECONFIG set to a decrypted copy, wiped when the shell exits
+    Use {
+        #[arg(value_name = "PATH", help = "Path to the encrypted kubeconfig")]
+        path: OsString,
+
+        /// Print export and trap lines for eval "$(opsops kubeconfig use --print ...)" instead
+        #[arg(long)]
+        print: bool,
+
+        /// Directory for the decrypted kubeconfig, `tmpfs` picks a RAM backed one
+        #[arg(long, value_name = "DIR|tmpfs", default_value = "tmpfs")]
+        tmpdir: String,
+    },
+
+    /// Wipe a kubeconfig written by 'kubeconfig use --print', run by its trap
+    Wipe {
+        #[arg(value_name = "PATH", help = "The decrypted kubeconfig")]
+        path: OsString,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ImportCommands {
+    /// Convert a CSV or TSV export (e.g. a password spreadsheet) into an encrypted YAML map
+    Csv {
+        #[arg(
+            value_name = "PATH",
+            help = "CSV file with a header row, tab separated for .tsv"
+        )]
+        path: OsString,
+
+        /// Encrypted file to create [default: PATH with a .yaml extension]
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<OsString>,
+
+        /// Column naming the entries [default: the first column]
+        #[arg(long, value_name = "COLUMN")]
+        key: Option<String>,
+    },
+
+    /// Insert values of 1Password items into an encrypted file, in memory
+    Op {
+        #[arg(
+            value_name = "REFERENCE",
+            help = "op://<vault>/<item>[/<section>]/<field> for one value, an item name or op://<vault>/<item> for several"
+        )]
+        reference: String,
+
+        /// Encrypted file to insert into, created under its creation rule if missing
+        #[arg(long, value_name = "PATH")]
+        into: OsString,
+
+        /// Key path of the value; for a whole item, the mapping its fields go under
+        #[arg(long, value_name = "KEY.PATH")]
+        path: Option<String>,
+
+        /// Import only this field of the item, at a key path with LABEL=KEY.PATH (repeatable)
+        #[arg(long = "field", value_name = "LABEL[=KEY.PATH]")]
+        fields: Vec<String>,
+
+        /// Vault of an item given by name
+        #[arg(long)]
+        vault: Option<String>,
+
+        /// Overwrite values that already exist in the file
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ExportCommands {
+    /// Convert an encrypted YAML map of entries back into a CSV or TSV table
+    Csv {
+        #[arg(value_name = "PATH", help = "Encrypted file to export")]
+        path: OsString,
+
+        /// Write the table to this file instead of stdout, tab separated for .tsv
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<OsString>,
+
+        /// Header of the column holding the entry names
+        #[arg(long, value_name = "COLUMN", default_value = "name")]
+        key: String,
+    },
+
+    /// Mirror an encrypted file into a 1Password item, a section per subtree
+    Op {
+        #[arg(value_name = "PATH", help = "Encrypted file to export")]
+        path: OsString,
+
+        /// Vault of the item
+        #[arg(long)]
+        vault: String,
+
+        /// Title of the item to create or update [default: the file name]
+        #[arg(long, value_name = "TITLE")]
+        item: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CiCommands {
+    /// Write a pipeline running verify, drift, scan and doctor, and decrypting with the CI key
+    Generate {
+        #[arg(value_enum, value_name = "PROVIDER", help = "Where the pipeline runs")]
+        provider: CiProvider,
+
+        /// Where to write it [default: .github/workflows/opsops.yml or ci/opsops.gitlab-ci.yml]
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Overwrite an existing file
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SyncCommands {
+    /// Show which entries differ between 1Password and their files, without values
+    Status,
+    /// Copy the 1Password fields into their files
+    Pull,
+    /// Copy the values in the files into their 1Password fields
+    Push,
+}
+
+#[derive(Debug, Subcommand)]
+enum EscrowCommands {
+    /// Verify that all rules and encrypted files include the escrow recipient
+    Verify {},
+}
+
+#[derive(Debug, Subcommand)]
+enum KeyCommands {
+    /// Reconstruct an age key from the recovery phrase of 'generate-age-key --mnemonic'
+    Recover {
+        /// Read the 24 word recovery phrase, prompted for or piped to stdin
+        #[arg(long, required = true)]
+        mnemonic: bool,
+    },
+    /// Add the public keys of members from the synced registry to the creation rules
+    AddRecipient {
+        /// Name of the member in the registry
+        #[arg(long = "member", value_name = "NAME", required = true)]
+        members: Vec<String>,
+
+        /// Only add them to the rule with this path_regex [default: every rule]
+        #[arg(long, value_name = "REGEX")]
+        rule: Option<String>,
+    },
+    /// Remove recipients past their recipient_expiry date and rotate the files they could decrypt
+    ExpireSweep {
+        /// Only list what would be removed and rotated
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move a passphrase project to the key in 1Password given with --op-item, re-encrypting its files
+    Upgrade {},
+    /// Print short fingerprints of keys to compare them over chat or a call
+    Fingerprint {
+        /// Age public or secret keys [default: the project's key]
+        keys: Vec<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum RegistryCommands {
+    /// Pull the team's registry of public keys and cache it
+    Sync {
+        /// op://<vault>/<note> or <git url>[#<path>] [default: member_registry in .opsops.yaml, else the last source]
+        source: Option<String>,
+    },
+}
+
 /// Global context passed to all commands
 pub struct GlobalContext {
     pub sops_file: Option<String>,
     pub opitem: Option<String>,
+    /// Pass the age key via SOPS_AGE_KEY instead of an inherited file descriptor
+    pub age_key_env: bool,
+    /// Absolute directory given via `-C`, `None` to use the working directory
+    pub chdir: Option<PathBuf>,
+    /// `--read-only`, see [`util::read_only`]
+    pub read_only: bool,
+    /// Which key wins if the environment holds one, see [`util::env_key`]
+    pub key_preference: KeyPreference,
+}
+
+impl GlobalContext {
+    /// The directory project discovery and relative paths start from
+    pub fn working_dir(&self) -> PathBuf {
+        self.chdir
+            .clone()
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+}
+
+impl Commands {
+    /// The catalog id of the message refusing what the command would change,
+    /// for commands `--read-only` refuses
+    fn mutation(&self) -> Option<&'static str> {
+        match self {
+            Commands::GenerateAgeKey { .. } => Some("read-only-create-a-1password-item"),
+            Commands::Key {
+                command: KeyCommands::Recover { .. },
+            } => Some("read-only-create-a-1password-item"),
+            Commands::Key {
+                command: KeyCommands::AddRecipient { .. },
+            } => Some("read-only-edit-sops-yaml"),
+            Commands::Key {
+                command: KeyCommands::ExpireSweep { dry_run: false },
+            } => Some("read-only-remove-recipients"),
+            Commands::Key {
+                command: KeyCommands::Upgrade {},
+            } => Some("read-only-upgrade-the-key"),
+            Commands::Registry { .. } => Some("read-only-update-the-member-registry"),
+            Commands::Decrypt { all: true, .. } => Some("read-only-decrypt-files"),
+            Commands::Kubeconfig {
+                command: KubeconfigCommands::Use { .. },
+            } => Some("read-only-write-decrypted-kubeconfigs"),
+            Commands::Kubeconfig {
+                command: KubeconfigCommands::Wipe { .. },
+            } => Some("read-only-delete-kubeconfigs"),
+            Commands::Edit { .. } => Some("read-only-edit-files"),
+            Commands::Encrypt { .. } => Some("read-only-encrypt-files"),
+            Commands::Agent { .. } => Some("read-only-start-or-stop-the-agent"),
+            Commands::Bench { .. } => Some("read-only-run-benchmarks"),
+            Commands::Doctor { junit, json, .. } if junit.is_some() || json.is_some() => {
+                Some("read-only-write-reports")
+            }
+            Commands::Init { .. } => Some("read-only-initialize-a-project"),
+            Commands::New { .. } => Some("read-only-create-a-project"),
+            Commands::Setup {} => Some("read-only-set-up-a-project"),
+            Commands::Teardown { .. } => Some("read-only-tear-down-a-project"),
+            Commands::Argocd { .. } => Some("read-only-bootstrap-argo-cd"),
+            Commands::Flux {
+                command: FluxCommands::CreateSecret { dry_run: false, .. },
+            } => Some("read-only-apply-secrets"),
+            Commands::Talos {
+                command: TalosCommands::EncryptSecrets { .. },
+            } => Some("read-only-encrypt-files"),
+            Commands::Talos {
+                command: TalosCommands::Apply { .. },
+            } => Some("read-only-apply-machine-configs"),
+            Commands::Drift { fix: true, .. } => Some("read-only-update-keys"),
+            Commands::Publish { .. } => Some("read-only-publish-files"),
+            Commands::Import { .. } => Some("read-only-import-files"),
+            Commands::Export { command } => match command {
+                ExportCommands::Csv {
+                    output: Some(_), ..
+                } => Some("read-only-write-files"),
+                ExportCommands::Csv { .. } => None,
+                ExportCommands::Op { .. } => Some("read-only-write-1password-items"),
+            },
+            Commands::Restore { list: false, .. } => Some("read-only-restore-backups"),
+            Commands::Mv { .. } => Some("read-only-move-files"),
+            Commands::Sync {
+                command: SyncCommands::Pull,
+            } => Some("read-only-sync-secrets"),
+            Commands::Sync {
+                command: SyncCommands::Push,
+            } => Some("read-only-write-1password-items"),
+            Commands::Cp { .. } => Some("read-only-copy-secrets"),
+            Commands::Set { .. } => Some("read-only-write-secrets"),
+            Commands::Ci { .. } => Some("read-only-write-files"),
+            Commands::Rm { .. } => Some("read-only-delete-files"),
+            Commands::TargetKeys { .. } => Some("read-only-change-creation-rules"),
+            Commands::GenerateDocs { .. } => Some("read-only-write-documentation"),
+            Commands::Completions { install: true, .. } => Some("read-only-install-completions"),
+            _ => None,
+        }
+    }
 }
 
 impl Cli {
@@ -118,16 +1177,28 @@ impl Cli {
         let man_path = man_dir.join("opsops.1");
         fs::write(man_path, buffer)?;
         println!(
-            "Generated man page at: {}",
-            man_dir.join("opsops.1").display()
+            "{}",
+            tr_args(
+                "docs-generated-man",
+                &[(
+                    "path",
+                    man_dir.join("opsops.1").display().to_string().into()
+                )],
+            )
         );
 
         // Generate Fish completions
-        let mut cmd = Cli::command();
-        let path = generate_to(Fish, &mut cmd, "opsops", &completion_dir)?;
-        print_info(format!("Generated Fish completions at: {}", path.display()));
+        let path = completion_dir.join("opsops.fish");
+        fs::write(
+            &path,
+            commands::completions::completion_script(clap_complete::Shell::Fish, Cli::command()),
+        )?;
+        print_info(tr_args(
+            "docs-generated-completions",
+            &[("path", path.display().to_string().into())],
+        ));
 
-        println!("\nTo install:");
+        println!("\n{}", tr("docs-to-install"));
         println!("  Man pages:          mkdir -p ~/.local/share/man/man1");
         println!(
             "                      cp {}/opsops.1 ~/.local/share/man/man1/",
@@ -146,24 +1217,383 @@ impl Cli {
 
 fn main() -> io::Result<()> {
     let args = Cli::parse();
+    if args.profile_perf {
+        util::perf::enable(args.formatter == FormatterKind::Json);
+    }
+    // Screen readers spell out symbols and emoji, unless a formatter was chosen
+    let formatter = match args.formatter {
+        FormatterKind::Human if args.accessible => FormatterKind::Plain,
+        formatter => formatter,
+    };
+    set_formatter(
+        formatter,
+        util::user_config::read_user_config()
+            .theme
+            .unwrap_or_default(),
+    );
+    set_accessible(args.accessible);
+
+    if args.show_version {
+        let build = util::environment::build_info();
+        if args.version_json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&build).unwrap_or_default()
+            );
+        } else {
+            println!("{} {}", build.name, build.version);
+        }
+        return Ok(());
+    }
+    let Some(command) = args.command else {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingSubcommand,
+                "a subcommand is required",
+            )
+            .exit();
+    };
+
+    if let Some(record) = &args.record {
+        if args.read_only {
+            print_error(tr("read-only-record"));
+            std::process::exit(1);
+        }
+        if let Err(e) = start_recording(record) {
+            print_error(failed_to("record-failed", record, e));
+            std::process::exit(1);
+        }
+    }
+
+    // Changing the process directory makes relative paths, e.g. --sops-file and
+    // file arguments, resolve against -C as well
+    let chdir = args.chdir.map(|dir| {
+        match std::env::set_current_dir(&dir).and_then(|_| std::env::current_dir()) {
+            Ok(dir) => dir,
+            Err(e) => {
+                print_error(failed_to("chdir-failed", &dir, e));
+                std::process::exit(1);
+            }
+        }
+    });
 
     let context = GlobalContext {
         sops_file: args.sops_file,
         opitem: args.op_item,
+        age_key_env: args.age_key_env,
+        chdir,
+        read_only: args.read_only,
+        key_preference: if args.prefer_env_key {
+            KeyPreference::Environment
+        } else if args.prefer_op_key {
+            KeyPreference::Project
+        } else {
+            KeyPreference::Unspecified
+        },
     };
 
-    match args.command {
-        Commands::ListConfig {} => commands::list_config::list_config(&context),
-        Commands::GenerateAgeKey {} => commands::generate_age_key::generate_age_key(&context),
-        Commands::Edit { path } => commands::edit::edit(path, &context),
-        Commands::Encrypt { path } => commands::encrypt::encrypt(path, &context),
-        Commands::Decrypt { path } => commands::decrypt::decrypt(path, &context),
-        Commands::Init {} => commands::init::init(&context),
-        Commands::Doctor {} => commands::doctor::doctor(&context),
-        Commands::TargetKeys { path } => commands::set_key::set_keys(path, &context),
+    if context.read_only
+        && let Some(action) = command.mutation()
+    {
+        print_error(tr(action));
+        std::process::exit(1);
+    }
+
+    match command {
+        Commands::ListConfig { format } => commands::list_config::list_config(&context, format),
+        Commands::GenerateAgeKey { mnemonic } => {
+            commands::generate_age_key::generate_age_key(mnemonic, &context)
+        }
+        Commands::Edit {
+            path,
+            create,
+            force,
+            tmpdir,
+            sandbox,
+        } => commands::edit::edit(path, create, force, tmpdir, sandbox, &context),
+        Commands::Encrypt {
+            path,
+            force,
+            allow_protected,
+            diff,
+            indent,
+        } => commands::encrypt::encrypt(
+            path,
+            force,
+            allow_protected,
+            diff,
+            Formatting {
+                indent,
+                sort_keys: false,
+            },
+            &context,
+        ),
+        Commands::Decrypt {
+            indent,
+            sort_keys,
+            all: true,
+            fail_fast,
+            ..
+        } => commands::decrypt::decrypt_all(Formatting { indent, sort_keys }, fail_fast, &context),
+        Commands::Decrypt {
+            path,
+            mode,
+            owner,
+            unique_output,
+            indent,
+            sort_keys,
+            resolve_includes,
+            ..
+        } => commands::decrypt::decrypt(
+            path,
+            mode,
+            owner,
+            unique_output,
+            Formatting { indent, sort_keys },
+            resolve_includes,
+            &context,
+        ),
+        Commands::Init {
+            from_key,
+            passphrase,
+            rules,
+        } => commands::init::init(from_key, passphrase, rules, &context),
+        Commands::New {
+            template,
+            dir,
+            from_key,
+            passphrase,
+        } => commands::new::new(template, dir, from_key, passphrase, &context),
+        Commands::Setup {} => commands::setup::setup(&context),
+        Commands::Teardown { fail_fast } => commands::teardown::teardown(fail_fast, &context),
+        #[cfg(unix)]
+        Commands::Agent { stop } => commands::agent::agent(&context, stop),
+        #[cfg(not(unix))]
+        Commands::Agent { .. } => {
+            print_error(tr("agent-unsupported"));
+            std::process::exit(1);
+        }
+        Commands::Bench {
+            self_: _,
+            files,
+            rounds,
+        } => commands::bench::bench(&context, files, rounds),
+        Commands::Paths { format } => commands::paths::paths(&context, format),
+        Commands::Help { topic } => commands::help::help(topic, Cli::command()),
+        Commands::Man { command } => commands::man::man(command, Cli::command()),
+        Commands::Info { format } => commands::info::info(&context, format),
+        Commands::Meta { path, format } => commands::meta::meta(path, format),
+        Commands::Inspect {
+            file,
+            key_path,
+            certs,
+            format,
+        } => match (certs, file, key_path) {
+            (false, Some(file), Some(key_path)) => {
+                commands::inspect::inspect(file, key_path, format, &context)
+            }
+            (_, file, _) => commands::inspect::certificates(file, format, &context),
+        },
+        Commands::Stats {
+            format,
+            warn_days,
+            fail_on_expired,
+        } => commands::stats::stats(&context, format, warn_days, fail_on_expired),
+        Commands::Doctor {
+            ci,
+            fail_on,
+            org,
+            junit,
+            json,
+        } => commands::doctor::doctor(
+            commands::doctor::DoctorOptions {
+                ci,
+                fail_on,
+                org,
+                junit,
+                json,
+            },
+            &context,
+        ),
+        Commands::TargetKeys {
+            path,
+            path_regex,
+            encrypted_regex,
+        } => commands::set_key::set_keys(path, path_regex, encrypted_regex, &context),
+        Commands::Complete { kind, prefix } => {
+            commands::complete::complete(kind, &prefix, &context)
+        }
         Commands::GenerateDocs { dir } => Cli::generate_docs(&dir)?,
-        Commands::Read { path } => commands::read::read(path, &context),
+        Commands::Completions { shell, install } => {
+            commands::completions::completions(shell, install, Cli::command())
+        }
+        Commands::Argocd { command } => match command {
+            ArgocdCommands::Bootstrap {
+                namespace,
+                secret_name,
+                ksops_image,
+                patch_dir,
+                dry_run,
+            } => commands::argocd::bootstrap(
+                commands::argocd::BootstrapOptions {
+                    namespace,
+                    secret_name,
+                    ksops_image,
+                    patch_dir,
+                    dry_run,
+                },
+                &context,
+            ),
+        },
+        Commands::Flux { command } => match command {
+            FluxCommands::Check {} => commands::flux::check(&context),
+            FluxCommands::CreateSecret {
+                name,
+                namespace,
+                dry_run,
+            } => commands::flux::create_secret(name, namespace, dry_run, &context),
+        },
+        Commands::Talos { command } => match command {
+            TalosCommands::EncryptSecrets { path } => {
+                commands::talos::encrypt_secrets(path, &context)
+            }
+            TalosCommands::Apply { path, args } => commands::talos::apply(path, args, &context),
+            TalosCommands::Verify { paths } => commands::talos::verify(paths, &context),
+        },
+        Commands::Kubeconfig { command } => match command {
+            KubeconfigCommands::Use {
+                path,
+                print,
+                tmpdir,
+            } => commands::kubeconfig::use_kubeconfig(path, print, tmpdir, &context),
+            KubeconfigCommands::Wipe { path } => commands::kubeconfig::wipe(path),
+        },
+        Commands::Verify {
+            paths,
+            deep,
+            format,
+        } => commands::verify::verify(paths, deep, format, &context),
+        Commands::VerifyRecipients { paths, against } => {
+            commands::verify::verify_recipients(paths, against)
+        }
+        Commands::Scan { history, format } => commands::scan::scan(history, format, &context),
+        Commands::Drift { paths, fix } => commands::drift::drift(paths, fix, &context),
+        Commands::Import { command } => match command {
+            ImportCommands::Csv { path, output, key } => {
+                commands::import::csv(path, output, key, &context)
+            }
+            ImportCommands::Op {
+                reference,
+                into,
+                path,
+                fields,
+                vault,
+                force,
+            } => commands::import::op(reference, into, path, fields, vault, force, &context),
+        },
+        Commands::Export { command } => match command {
+            ExportCommands::Csv { path, output, key } => {
+                commands::export::csv(path, output, key, &context)
+            }
+            ExportCommands::Op { path, vault, item } => {
+                commands::export::op(path, vault, item, &context)
+            }
+        },
+        Commands::Ci { command } => match command {
+            CiCommands::Generate {
+                provider,
+                output,
+                force,
+            } => commands::ci::generate(provider, output, force, &context),
+        },
+        Commands::Sync { command } => match command {
+            SyncCommands::Status => commands::sync::status(&context),
+            SyncCommands::Pull => commands::sync::pull(&context),
+            SyncCommands::Push => commands::sync::push(&context),
+        },
+        Commands::Escrow { command } => match command {
+            EscrowCommands::Verify {} => commands::escrow::verify(&context),
+        },
+        Commands::Key { command } => match command {
+            KeyCommands::Recover { mnemonic: _ } => commands::key::recover(&context),
+            KeyCommands::AddRecipient { members, rule } => {
+                commands::key::add_recipient(members, rule, &context)
+            }
+            KeyCommands::ExpireSweep { dry_run } => commands::key::expire_sweep(dry_run, &context),
+            KeyCommands::Upgrade {} => commands::key::upgrade(&context),
+            KeyCommands::Fingerprint { keys } => commands::key::fingerprint(keys, &context),
+        },
+        Commands::Registry { command } => match command {
+            RegistryCommands::Sync { source } => commands::registry::sync(source, &context),
+        },
+        Commands::Publish {
+            path,
+            yes,
+            omit_extensions,
+        } => commands::publish::publish(path, yes, omit_extensions, &context),
+        Commands::Grep {
+            pattern,
+            paths,
+            keys,
+            values,
+            show_values,
+            ignore_case,
+        } => {
+            let target = match (keys, values) {
+                (true, _) => commands::grep::GrepTarget::Keys,
+                (_, true) => commands::grep::GrepTarget::Values,
+                _ => commands::grep::GrepTarget::Both,
+            };
+            commands::grep::grep(pattern, paths, target, show_values, ignore_case, &context)
+        }
+        Commands::Cp {
+            source,
+            destination,
+            force,
+        } => commands::cp::cp(source, destination, force, &context),
+        Commands::Set {
+            file,
+            key_path,
+            generate,
+            force,
+        } => commands::set::set(file, key_path, generate, force, &context),
+        Commands::Mv { from, to } => commands::mv::mv(from, to, &context),
+        Commands::Preview { path } => commands::preview::preview(path, &context),
+        Commands::Rm { path, yes } => commands::rm::rm(path, yes, &context),
+        Commands::Rule { command } => match command {
+            RuleCommands::Test { regex, path } => commands::rule::test(regex, path, &context),
+        },
+        Commands::Restore { path, at, list } => {
+            commands::restore::restore(path, at, list, &context)
+        }
+        Commands::Replay { path } => commands::replay::replay(path),
+        Commands::Diff {
+            path,
+            rev,
+            renderer,
+        } => commands::diff::diff(path, rev, renderer, &context),
+        Commands::Read {
+            path,
+            format,
+            extract,
+            redact,
+            indent,
+            sort_keys,
+            resolve_includes,
+        } => commands::read::read(
+            path,
+            commands::read::ReadOptions {
+                format,
+                extract,
+                redact,
+                formatting: Formatting { indent, sort_keys },
+                resolve_includes,
+            },
+            &context,
+        ),
+        Commands::Serve { stdio: _ } => commands::serve::serve(&context),
     }
 
+    finish_recording(0);
     Ok(())
 }
@@ -0,0 +1,181 @@
+//! Next steps for failures with a known way out. [`print_error`] classifies
+//! every message and prints the matching command beneath it, so each command
+//! gets the same advice without repeating it at every call site.
+//!
+//! [`print_error`]: super::print_status::print_error
+
+use std::path::Path;
+use std::process::ExitStatus;
+
+use super::{
+    file_picker::quiet_config,
+    i18n::{tr, tr_args},
+    print_status::print_advice,
+    rule_match::{first_matching_rule, project_relative_path},
+    sops_config::config_dir,
+};
+use crate::GlobalContext;
+
+/// sops exit codes, see `cmd/sops/codes` in the sops repository
+const SOPS_MAC_MISMATCH: i32 = 51;
+const SOPS_CONFIG_FILE_NOT_FOUND: i32 = 61;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Failure {
+    /// No creation rule covers the file
+    NoCreationRule,
+    /// The key from 1Password can't open the file's data key
+    KeyNotRecipient,
+    /// The file changed outside of sops
+    MacMismatch,
+    OpSignedOut,
+    OpRateLimited,
+    SopsMissing,
+    NoKeyReference,
+    NoSopsConfig,
+}
+
+impl Failure {
+    /// What to run about it, `file` filling in commands that take one
+    pub fn advice(&self, file: Option<&str>) -> String {
+        let id = match self {
+            Failure::NoCreationRule => "advice-no-creation-rule",
+            Failure::KeyNotRecipient => "advice-key-not-recipient",
+            Failure::MacMismatch => "advice-mac-mismatch",
+            Failure::OpSignedOut => "advice-op-signed-out",
+            Failure::OpRateLimited => "advice-op-rate-limited",
+            Failure::SopsMissing => "advice-sops-missing",
+            Failure::NoKeyReference | Failure::NoSopsConfig => "advice-init",
+        };
+        tr_args(id, &[("file", file.unwrap_or("<file>").into())])
+    }
+
+    /// The command the advice boils down to, to skip messages that name it already
+    fn command(&self) -> &'static str {
+        match self {
+            Failure::NoCreationRule => "opsops target-keys",
+            Failure::KeyNotRecipient => "opsops doctor",
+            Failure::MacMismatch => "opsops restore",
+            Failure::OpSignedOut => "op signin",
+            Failure::OpRateLimited => "op_rate_limit",
+            Failure::SopsMissing => "opsops setup",
+            Failure::NoKeyReference | Failure::NoSopsConfig => "opsops init",
+        }
+    }
+}
+
+/// Recognizes a failure by what opsops, sops or op said about it
+pub fn classify(message: &str) -> Option<Failure> {
+    let lower = message.to_lowercase();
+    let has = |needle: &str| lower.contains(needle);
+    if has("no matching creation rules") || has("no creation rule matches") {
+        Some(Failure::NoCreationRule)
+    } else if has("0 successful groups required")
+        || has("no identity matched")
+        || has("failed to get the data key")
+    {
+        Some(Failure::KeyNotRecipient)
+    } else if has("mac mismatch") {
+        Some(Failure::MacMismatch)
+    } else if has("not currently signed in")
+        || has("session expired")
+        || has("authorization prompt dismissed")
+    {
+        Some(Failure::OpSignedOut)
+    } else if has("too many requests") || has("rate limit") {
+        Some(Failure::OpRateLimited)
+    } else if has("'sops' is not installed")
+        || has("sops is not installed")
+        || has(&tr("sops-not-installed").to_lowercase())
+    {
+        Some(Failure::SopsMissing)
+    } else if has("no 1password reference found") {
+        Some(Failure::NoKeyReference)
+    } else if has("could not find .sops.yaml") || has("config file not found") {
+        Some(Failure::NoSopsConfig)
+    } else {
+        None
+    }
+}
+
+/// Advice for an error message about `file`, unless the message already names
+/// the command
+pub fn advice_for(message: &str, file: Option<&str>) -> Option<String> {
+    let failure = classify(message)?;
+    if message.contains(failure.command()) {
+        return None;
+    }
+    Some(failure.advice(file))
+}
+
+/// Why sops failed on `file` when its stderr went straight to the terminal:
+/// by its exit code, else by whether any rule covers the file
+pub fn classify_sops_exit(
+    status: &ExitStatus,
+    file: &Path,
+    context: &GlobalContext,
+) -> Option<Failure> {
+    match status.code() {
+        Some(SOPS_MAC_MISMATCH) => return Some(Failure::MacMismatch),
+        Some(SOPS_CONFIG_FILE_NOT_FOUND) => return Some(Failure::NoSopsConfig),
+        _ => {}
+    }
+    let config = quiet_config(context)?;
+    let root = config_dir(context)?;
+    let relative = project_relative_path(&root, file)?;
+    first_matching_rule(&config.creation_rules, &relative)
+        .is_none()
+        .then_some(Failure::NoCreationRule)
+}
+
+/// Prints the next step after sops exited with `status` on `file`
+pub fn advise_sops_failure(status: &ExitStatus, file: &Path, context: &GlobalContext) {
+    if let Some(failure) = classify_sops_exit(status, file, context) {
+        print_advice(failure.advice(Some(&file.to_string_lossy())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Failure, advice_for, classify};
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            classify("error loading config: no matching creation rules found"),
+            Some(Failure::NoCreationRule)
+        );
+        assert_eq!(
+            classify("Error getting data key: 0 successful groups required, got 0"),
+            Some(Failure::KeyNotRecipient)
+        );
+        assert_eq!(
+            classify("[ERROR] 2024/05/01 You are not currently signed in."),
+            Some(Failure::OpSignedOut)
+        );
+        assert_eq!(
+            classify("Failed to verify data integrity: MAC mismatch"),
+            Some(Failure::MacMismatch)
+        );
+        assert_eq!(classify("File not found: a.yaml"), None);
+    }
+
+    #[test]
+    fn test_advice_skips_messages_naming_the_command() {
+        assert_eq!(
+            advice_for(
+                "No creation rule matches k8s/app.yaml",
+                Some("k8s/app.yaml")
+            )
+            .as_deref(),
+            Some("Add a creation rule for it: opsops target-keys k8s/app.yaml")
+        );
+        assert_eq!(
+            advice_for(
+                "No 1Password reference found in .opsops.yaml and none provided via --op-item. Run 'opsops init' to configure.",
+                None
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! The key agent keeps identities in memory and hands them to opsops
+//! processes of the same user over a unix socket, see `opsops agent`. Without
+//! unix sockets there is no agent and every process asks 1Password itself.
+
+use age::secrecy::SecretString;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::env;
+use std::path::PathBuf;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::dirs;
+
+#[cfg(unix)]
+use super::platform::{Native, Platform};
+#[cfg(unix)]
+use age::secrecy::ExposeSecret;
+#[cfg(unix)]
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::time::Duration;
+
+/// Overrides the agent socket location
+pub const SOCKET_ENV: &str = "OPSOPS_AGENT_SOCK";
+
+/// How long clients wait for the agent, which may be waiting on a 1Password prompt
+#[cfg(unix)]
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long the agent waits for a request line, so a client that never sends
+/// one can't block every other process
+#[cfg(unix)]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Location of the agent socket: `$OPSOPS_AGENT_SOCK`, else `agent.sock` in
+/// [`dirs::runtime_dir`]
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = env::var_os(SOCKET_ENV).filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+    dirs::runtime_dir().join("agent.sock")
+}
+
+/// A secret kept in memory that is locked against swapping and zeroed on drop
+#[cfg(unix)]
+pub struct LockedSecret {
+    bytes: Vec<u8>,
+}
+
+#[cfg(unix)]
+impl LockedSecret {
+    pub fn new(secret: &SecretString) -> Self {
+        let secret = secret.expose_secret().as_bytes();
+        // Allocate and lock first so the copy never lands in swappable memory
+        let mut bytes = Vec::with_capacity(secret.len());
+        // Best effort, fails e.g. when RLIMIT_MEMLOCK is exhausted
+        unsafe {
+            libc::mlock(bytes.as_ptr() as *const libc::c_void, secret.len());
+        }
+        bytes.extend_from_slice(secret);
+        LockedSecret { bytes }
+    }
+
+    pub fn expose(&self) -> &str {
+        std::str::from_utf8(&self.bytes).unwrap_or_default()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for LockedSecret {
+    fn drop(&mut self) {
+        let len = self.bytes.len();
+        let ptr = self.bytes.as_ptr();
+        self.bytes.zeroize();
+        unsafe {
+            libc::munlock(ptr as *const libc::c_void, len);
+        }
+    }
+}
+
+/// Returns the uid of the process on the other end of a unix socket
+#[cfg(target_os = "linux")]
+pub fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    (ret == 0).then_some(cred.uid)
+}
+
+/// Returns the uid of the process on the other end of a unix socket
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    let ret = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    (ret == 0).then_some(uid)
+}
+
+/// Identities held by the agent, keyed by their 1Password reference
+#[cfg(unix)]
+pub type KeyCache = HashMap<String, LockedSecret>;
+
+/// Handles a single agent connection. Returns `true` if the agent was asked to stop.
+///
+/// Protocol, one line per request and response:
+/// `GET <op reference>` -> `OK <base64 key>` | `ERR <message>`,
+/// `PING` -> `OK`, `STOP` -> `OK`. The key is base64 encoded because it may
+/// hold several identities, one per line.
+#[cfg(unix)]
+pub fn handle_connection<F>(stream: UnixStream, cache: &mut KeyCache, fetch: &F) -> bool
+where
+    F: Fn(&str) -> Result<SecretString, String>,
+{
+    let mut writer = match stream.try_clone() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    if peer_uid(&stream) != Native::effective_uid() {
+        let _ = writeln!(writer, "ERR permission denied");
+        return false;
+    }
+
+    let mut line = String::new();
+    if stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err()
+        || BufReader::new(&stream).read_line(&mut line).is_err()
+    {
+        return false;
+    }
+    let line = line.trim_end();
+
+    let (response, stop) = match line.split_once(' ') {
+        Some(("GET", reference)) => {
+            if !cache.contains_key(reference) {
+                match fetch(reference) {
+                    Ok(key) => {
+                        cache.insert(reference.to_string(), LockedSecret::new(&key));
+                    }
+                    Err(e) => {
+                        let _ = writeln!(writer, "ERR {}", e.replace('\n', " "));
+                        return false;
+                    }
+                }
+            }
+            (
+                format!("OK {}", STANDARD.encode(cache[reference].expose())),
+                false,
+            )
+        }
+        _ if line == "PING" => ("OK".to_string(), false),
+        _ if line == "STOP" => ("OK".to_string(), true),
+        _ => ("ERR unknown request".to_string(), false),
+    };
+
+    let mut response = response;
+    let _ = writeln!(writer, "{}", response);
+    response.zeroize();
+    stop
+}
+
+/// Sends a single request to the running agent and returns its response line
+#[cfg(unix)]
+fn request(line: &str) -> Option<String> {
+    let stream = UnixStream::connect(socket_path()).ok()?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT)).ok()?;
+    let mut writer = stream.try_clone().ok()?;
+    writeln!(writer, "{}", line).ok()?;
+
+    let mut response = String::new();
+    BufReader::new(&stream).read_line(&mut response).ok()?;
+    let trimmed = response.trim_end().to_string();
+    response.zeroize();
+    Some(trimmed)
+}
+
+/// There is no agent without unix sockets
+#[cfg(not(unix))]
+fn request(_line: &str) -> Option<String> {
+    None
+}
+
+/// Asks the agent for the key behind `reference`.
+/// Returns `None` if no agent is running or it couldn't provide the key.
+pub fn request_key(reference: &str) -> Option<SecretString> {
+    decode_key(request(&format!("GET {}", reference))?)
+}
+
+/// The key in an `OK <base64 key>` response, zeroing the response
+fn decode_key(mut response: String) -> Option<SecretString> {
+    let decoded = response
+        .strip_prefix("OK ")
+        .and_then(|encoded| STANDARD.decode(encoded).ok())
+        .map(Zeroizing::new);
+    response.zeroize();
+    let key = std::str::from_utf8(decoded.as_deref()?).ok()?;
+    Some(SecretString::from(key.to_string()))
+}
+
+/// Checks whether an agent is listening on the socket
+pub fn is_running() -> bool {
+    request("PING").is_some_and(|r| r == "OK")
+}
+
+/// Asks a running agent to shut down
+pub fn stop() -> bool {
+    request("STOP").is_some_and(|r| r == "OK")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::cell::Cell;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::time::Instant;
+
+    use age::secrecy::{ExposeSecret, SecretString};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+
+    use super::{KeyCache, LockedSecret, REQUEST_TIMEOUT, decode_key, handle_connection};
+
+    fn roundtrip<F>(request: &str, cache: &mut KeyCache, fetch: &F) -> (String, bool)
+    where
+        F: Fn(&str) -> Result<SecretString, String>,
+    {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        writeln!(client, "{}", request).unwrap();
+        let stop = handle_connection(server, cache, fetch);
+        let mut response = String::new();
+        BufReader::new(&client).read_line(&mut response).unwrap();
+        (response.trim_end().to_string(), stop)
+    }
+
+    #[test]
+    fn test_locked_secret_expose() {
+        let secret = LockedSecret::new(&SecretString::from("AGE-SECRET-KEY-1ABC"));
+        assert_eq!(secret.expose(), "AGE-SECRET-KEY-1ABC");
+    }
+
+    #[test]
+    fn test_get_fetches_once_and_caches() {
+        let calls = Cell::new(0);
+        let fetch = |reference: &str| {
+            calls.set(calls.get() + 1);
+            Ok(SecretString::from(format!("KEY-FOR-{}", reference)))
+        };
+        let mut cache = KeyCache::new();
+
+        let (response, stop) = roundtrip("GET op://V/I/F", &mut cache, &fetch);
+        assert_eq!(
+            response,
+            format!("OK {}", STANDARD.encode("KEY-FOR-op://V/I/F"))
+        );
+        assert!(!stop);
+
+        let (response, _) = roundtrip("GET op://V/I/F", &mut cache, &fetch);
+        assert_eq!(
+            response,
+            format!("OK {}", STANDARD.encode("KEY-FOR-op://V/I/F"))
+        );
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_get_keeps_every_identity() {
+        const KEY: &str = "AGE-SECRET-KEY-1FIRST\nAGE-SECRET-KEY-1SECOND";
+        let fetch = |_: &str| Ok(SecretString::from(KEY));
+        let mut cache = KeyCache::new();
+        let (response, _) = roundtrip("GET op://V/I/F", &mut cache, &fetch);
+        assert_eq!(decode_key(response).unwrap().expose_secret(), KEY);
+        assert!(decode_key("ERR not signed in".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_get_reports_fetch_errors() {
+        let fetch = |_: &str| Err("not signed in\nrun op signin".to_string());
+        let mut cache = KeyCache::new();
+        let (response, _) = roundtrip("GET op://V/I/F", &mut cache, &fetch);
+        assert_eq!(response, "ERR not signed in run op signin");
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_silent_client_times_out() {
+        let fetch = |_: &str| Ok(SecretString::from(""));
+        let mut cache = KeyCache::new();
+        let (_client, server) = UnixStream::pair().unwrap();
+        let started = Instant::now();
+        assert!(!handle_connection(server, &mut cache, &fetch));
+        assert!(started.elapsed() < REQUEST_TIMEOUT * 2);
+    }
+
+    #[test]
+    fn test_ping_and_stop() {
+        let fetch = |_: &str| Ok(SecretString::from(""));
+        let mut cache = KeyCache::new();
+        assert_eq!(
+            roundtrip("PING", &mut cache, &fetch),
+            ("OK".to_string(), false)
+        );
+        assert_eq!(
+            roundtrip("STOP", &mut cache, &fetch),
+            ("OK".to_string(), true)
+        );
+        assert_eq!(
+            roundtrip("HELLO", &mut cache, &fetch).0,
+            "ERR unknown request"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Manifests Argo CD needs to build opsops-managed kustomizations with KSOPS:
+//! the repo-server gets the ksops and kustomize binaries and the age key from
+//! a Secret, argocd-cm enables exec plugins.
+
+/// Key of the age key in the Secret, mounted as sops' default keys file
+pub const AGE_KEYS_FILE: &str = "keys.txt";
+
+pub const DEFAULT_KSOPS_IMAGE: &str = "viaductoss/ksops:v4.3.2";
+
+/// Strategic merge patch for the `argocd-repo-server` Deployment
+pub fn repo_server_patch(namespace: &str, secret_name: &str, ksops_image: &str) -> String {
+    format!(
+        r#"apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: argocd-repo-server
+  namespace: {namespace}
+spec:
+  template:
+    spec:
+      volumes:
+        - name: custom-tools
+          emptyDir: {{}}
+        - name: sops-age
+          secret:
+            secretName: {secret_name}
+      initContainers:
+        - name: install-ksops
+          image: {ksops_image}
+          command: ["/bin/sh", "-c"]
+          args:
+            - mv ksops kustomize /custom-tools/
+          volumeMounts:
+            - mountPath: /custom-tools
+              name: custom-tools
+      containers:
+        - name: argocd-repo-server
+          env:
+            - name: XDG_CONFIG_HOME
+              value: /.config
+            - name: SOPS_AGE_KEY_FILE
+              value: /.config/sops/age/{keys_file}
+          volumeMounts:
+            - mountPath: /usr/local/bin/kustomize
+              name: custom-tools
+              subPath: kustomize
+            - mountPath: /usr/local/bin/ksops
+              name: custom-tools
+              subPath: ksops
+            - mountPath: /.config/sops/age
+              name: sops-age
+"#,
+        keys_file = AGE_KEYS_FILE
+    )
+}
+
+/// Patch for `argocd-cm` allowing kustomize to run KSOPS
+pub fn argocd_cm_patch(namespace: &str) -> String {
+    format!(
+        r#"apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: argocd-cm
+  namespace: {namespace}
+data:
+  kustomize.buildOptions: "--enable-alpha-plugins --enable-exec"
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{argocd_cm_patch, repo_server_patch};
+
+    #[test]
+    fn test_patches_are_valid_yaml() {
+        let patch: serde_yaml::Value =
+            serde_yaml::from_str(&repo_server_patch("argocd", "sops-age", "ksops:v1")).unwrap();
+        let spec = &patch["spec"]["template"]["spec"];
+        assert_eq!(spec["volumes"][1]["secret"]["secretName"], "sops-age");
+        assert_eq!(spec["initContainers"][0]["image"], "ksops:v1");
+        assert_eq!(
+            spec["containers"][0]["env"][1]["value"],
+            "/.config/sops/age/keys.txt"
+        );
+
+        let cm: serde_yaml::Value = serde_yaml::from_str(&argocd_cm_patch("argocd")).unwrap();
+        assert_eq!(cm["metadata"]["namespace"], "argocd");
+    }
+}
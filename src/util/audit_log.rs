@@ -0,0 +1,83 @@
+//! A record of destructive changes to a project's secrets, appended as JSON
+//! lines to `.opsops/audit.jsonl`. Entries name files and recipients, never
+//! values.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use super::{backups::timestamp_now, dirs::project_state_dir, op::user_name};
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    /// UTC, e.g. `20240131T235959Z`
+    pub at: String,
+    /// Who ran opsops, the invoking user under sudo
+    pub user: String,
+    /// What happened, e.g. `rm`
+    pub action: String,
+    /// The file, relative to the project root
+    pub path: String,
+    /// Recipients that could decrypt the file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(action: &str, path: &str) -> AuditEntry {
+        AuditEntry {
+            at: timestamp_now(),
+            user: user_name(),
+            action: action.to_string(),
+            path: path.to_string(),
+            recipients: Vec::new(),
+            note: None,
+        }
+    }
+}
+
+/// Appends `entry` to the audit log of the project at `root`
+pub fn append(root: &Path, entry: &AuditEntry) -> Result<(), String> {
+    let dir = project_state_dir(root);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(AUDIT_FILE);
+    let line =
+        serde_json::to_string(entry).map_err(|e| format!("Failed to serialize entry: {}", e))?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AUDIT_FILE, AuditEntry, append};
+    use crate::util::dirs::project_state_dir;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_and_read() {
+        let dir = TempDir::new().unwrap();
+        let mut entry = AuditEntry::new("rm", "secrets.yaml");
+        entry.recipients = vec!["age1me".to_string()];
+        append(dir.path(), &entry).unwrap();
+        append(dir.path(), &AuditEntry::new("rm", "other.yaml")).unwrap();
+
+        let log: Vec<AuditEntry> =
+            std::fs::read_to_string(project_state_dir(dir.path()).join(AUDIT_FILE))
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0], entry);
+        assert_eq!(log[1].path, "other.yaml");
+    }
+}
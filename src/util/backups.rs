@@ -0,0 +1,207 @@
+//! Copies of ciphertext taken before opsops overwrites an encrypted file, so a
+//! re-encryption with the wrong key can be rolled back with `opsops restore`.
+//! Backups live in `.opsops/backups/<timestamp>/<path relative to the project>`,
+//! `backup_dir:` in `.opsops.yaml` moves them elsewhere.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    dirs::project_state_dir, find_project_root::find_project_root,
+    opsops_config::read_opsops_config, print_status::print_error,
+    rule_match::project_relative_path, sops_files::is_sops_encrypted_file,
+};
+use crate::GlobalContext;
+
+/// Where backups of the project at `root` go
+pub fn backups_dir(root: &Path, context: &GlobalContext) -> PathBuf {
+    match read_opsops_config(context)
+        .ok()
+        .flatten()
+        .and_then(|c| c.backup_dir)
+    {
+        // Relative locations are relative to the project, absolute ones win in join
+        Some(dir) => root.join(dir),
+        None => project_state_dir(root).join("backups"),
+    }
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days_from_civil, inverted
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// A UTC timestamp like `20240131T235959Z`, which sorts chronologically
+pub fn format_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rest = secs % 86_400;
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3_600,
+        rest % 3_600 / 60,
+        rest % 60
+    )
+}
+
+/// The current time as [`format_timestamp`] writes it
+pub fn timestamp_now() -> String {
+    format_timestamp(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    )
+}
+
+/// Backups of `file`, oldest first, as (timestamp, path) pairs
+pub fn list_backups(root: &Path, file: &Path, context: &GlobalContext) -> Vec<(String, PathBuf)> {
+    let Some(relative) = project_relative_path(root, file) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(backups_dir(root, context)) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .map(|entry| {
+            (
+                entry.file_name().to_string_lossy().to_string(),
+                entry.path().join(&relative),
+            )
+        })
+        .filter(|(_, path)| path.is_file())
+        .collect();
+    backups.sort();
+    backups
+}
+
+/// Copies `file` into a new backup if it is encrypted and differs from its latest
+/// backup. Returns the backup, `None` if nothing needed backing up.
+pub fn backup_ciphertext(
+    root: &Path,
+    file: &Path,
+    context: &GlobalContext,
+) -> Result<Option<PathBuf>, String> {
+    if !is_sops_encrypted_file(file) {
+        return Ok(None);
+    }
+    let relative = project_relative_path(root, file).ok_or_else(|| {
+        format!(
+            "Can't back up {}, it is outside of the project",
+            file.display()
+        )
+    })?;
+
+    let contents =
+        fs::read(file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+    if let Some((_, latest)) = list_backups(root, file, context).last()
+        && fs::read(latest).is_ok_and(|backup| backup == contents)
+    {
+        return Ok(None);
+    }
+
+    // Several backups of a file within a second get a counter appended
+    let (dir, timestamp) = (backups_dir(root, context), timestamp_now());
+    let mut target = dir.join(&timestamp).join(&relative);
+    let mut counter = 1;
+    while target.exists() {
+        target = dir
+            .join(format!("{}-{}", timestamp, counter))
+            .join(&relative);
+        counter += 1;
+    }
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    fs::write(&target, contents)
+        .map_err(|e| format!("Failed to back up {}: {}", file.display(), e))?;
+    Ok(Some(target))
+}
+
+/// Backs up `file` before a command overwrites it, exiting if that fails rather
+/// than risking the only copy of the ciphertext
+pub fn backup_or_exit(file: &Path, context: &GlobalContext) {
+    let Some(root) = find_project_root(context) else {
+        return;
+    };
+    if let Err(e) = backup_ciphertext(&root, file, context) {
+        print_error(e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::{backup_ciphertext, format_timestamp, list_backups};
+    use crate::GlobalContext;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "19700101T000000Z");
+        assert_eq!(format_timestamp(951_782_400), "20000229T000000Z");
+        assert_eq!(format_timestamp(1_706_745_599), "20240131T235959Z");
+    }
+
+    #[test]
+    fn test_backs_up_changed_ciphertext_only() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let context = GlobalContext {
+            sops_file: Some(root.join(".sops.yaml").to_string_lossy().into()),
+            opitem: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
+        };
+        let plaintext = root.join("plain.yaml");
+        let secret = root.join("k8s/secret.yaml");
+        fs::create_dir_all(root.join("k8s")).unwrap();
+        fs::write(&plaintext, "password: hunter2\n").unwrap();
+        fs::write(&secret, "password: ENC[abc]\nsops:\n    mac: one\n").unwrap();
+
+        // Plaintext is never copied anywhere
+        assert_eq!(backup_ciphertext(&root, &plaintext, &context), Ok(None));
+
+        let backup = backup_ciphertext(&root, &secret, &context)
+            .unwrap()
+            .unwrap();
+        assert!(backup.starts_with(root.join(".opsops/backups")));
+        assert!(backup.ends_with("k8s/secret.yaml"));
+
+        // Unchanged since the last backup
+        assert_eq!(backup_ciphertext(&root, &secret, &context), Ok(None));
+        assert_eq!(list_backups(&root, &secret, &context).len(), 1);
+
+        // A new version within the same second doesn't replace the first backup
+        fs::write(&secret, "password: ENC[def]\nsops:\n    mac: two\n").unwrap();
+        backup_ciphertext(&root, &secret, &context)
+            .unwrap()
+            .unwrap();
+        let backups = list_backups(&root, &secret, &context);
+        assert_eq!(backups.len(), 2);
+        assert!(
+            fs::read_to_string(&backups[1].1)
+                .unwrap()
+                .contains("mac: two")
+        );
+    }
+}
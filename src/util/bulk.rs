@@ -0,0 +1,309 @@
+//! Runs one action over many files and reports how each of them went, so a
+//! partially failed run is never mistaken for a successful one.
+
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::{
+    i18n::{tr, tr_args},
+    journal::Journal,
+    rule_match::relative_path,
+};
+
+/// Exit code when some, but not all, files failed
+pub const EXIT_PARTIAL_FAILURE: i32 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Done,
+    Failed(String),
+    /// Not attempted because an earlier file failed with `--fail-fast`
+    Skipped,
+    /// Finished by an interrupted earlier run and verified since
+    Resumed,
+}
+
+#[derive(Debug)]
+pub struct FileResult {
+    pub file: PathBuf,
+    pub duration: Duration,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug)]
+pub struct BulkReport {
+    /// What was done to each file, e.g. "decrypt"
+    pub action: &'static str,
+    pub results: Vec<FileResult>,
+}
+
+/// Runs `op` on every file in order. With `fail_fast` the remaining files are
+/// skipped after the first failure.
+pub fn run_bulk<F>(
+    files: &[PathBuf],
+    action: &'static str,
+    fail_fast: bool,
+    mut op: F,
+) -> BulkReport
+where
+    F: FnMut(&Path) -> Result<(), String>,
+{
+    let mut results = Vec::with_capacity(files.len());
+    let mut stop = false;
+    for file in files {
+        if stop {
+            results.push(FileResult {
+                file: file.clone(),
+                duration: Duration::ZERO,
+                outcome: Outcome::Skipped,
+            });
+            continue;
+        }
+        let start = Instant::now();
+        let outcome = match op(file) {
+            Ok(()) => Outcome::Done,
+            Err(e) => {
+                stop = fail_fast;
+                Outcome::Failed(e)
+            }
+        };
+        results.push(FileResult {
+            file: file.clone(),
+            duration: start.elapsed(),
+            outcome,
+        });
+    }
+    BulkReport { action, results }
+}
+
+/// Like [`run_bulk`], recording each finished file in `journal` so an
+/// interrupted run can be picked up again. Files an earlier run finished are
+/// checked with `verify` instead of being processed again, including those no
+/// longer in `files` because they don't need the action anymore. A run without
+/// failures deletes the journal.
+pub fn run_resumable<V, F>(
+    root: &Path,
+    files: &[PathBuf],
+    action: &'static str,
+    journal: Journal,
+    mut verify: V,
+    mut op: F,
+) -> BulkReport
+where
+    V: FnMut(&Path) -> Result<(), String>,
+    F: FnMut(&Path) -> Result<(), String>,
+{
+    let mut journal = journal;
+    let mut results = Vec::new();
+    let mut pending = Vec::new();
+    let earlier: Vec<PathBuf> = journal.completed().map(|f| root.join(f)).collect();
+    for file in earlier
+        .iter()
+        .chain(files.iter().filter(|f| !earlier.contains(f)))
+    {
+        let relative = relative_path(root, file);
+        if !journal.is_completed(&relative) {
+            pending.push(file.clone());
+            continue;
+        }
+        let start = Instant::now();
+        match verify(file) {
+            Ok(()) => results.push(FileResult {
+                file: file.clone(),
+                duration: start.elapsed(),
+                outcome: Outcome::Resumed,
+            }),
+            // Changed since, do it again
+            Err(_) if files.contains(file) => pending.push(file.clone()),
+            Err(e) => results.push(FileResult {
+                file: file.clone(),
+                duration: start.elapsed(),
+                outcome: Outcome::Failed(e),
+            }),
+        }
+    }
+
+    let mut report = run_bulk(&pending, action, false, |file| {
+        op(file)?;
+        journal.complete(&relative_path(root, file))
+    });
+    results.append(&mut report.results);
+    report.results = results;
+    if report.failed() == 0 {
+        journal.finish();
+    }
+    report
+}
+
+impl BulkReport {
+    fn count(&self, matches: impl Fn(&Outcome) -> bool) -> usize {
+        self.results.iter().filter(|r| matches(&r.outcome)).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Failed(_)))
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Done | Outcome::Resumed))
+    }
+
+    pub fn resumed(&self) -> usize {
+        self.count(|o| *o == Outcome::Resumed)
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|o| *o == Outcome::Skipped)
+    }
+
+    /// 0 if every file succeeded, 1 if none did and [`EXIT_PARTIAL_FAILURE`]
+    /// otherwise
+    pub fn exit_code(&self) -> i32 {
+        if self.failed() == 0 && self.skipped() == 0 {
+            0
+        } else if self.succeeded() == 0 {
+            1
+        } else {
+            EXIT_PARTIAL_FAILURE
+        }
+    }
+
+    /// Prints one row per file and a totals line
+    pub fn print_summary(&self) {
+        let width = self
+            .results
+            .iter()
+            .map(|r| r.file.display().to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max("FILE".len());
+
+        println!(
+            "\n{:width$}  {:8}  {:>9}  {}",
+            "FILE".bold(),
+            "ACTION".bold(),
+            "DURATION".bold(),
+            "RESULT".bold(),
+            width = width
+        );
+        for result in &self.results {
+            let outcome = match &result.outcome {
+                Outcome::Done => tr("bulk-ok").green(),
+                Outcome::Failed(e) => tr_args("sync-failed", &[("error", e.as_str().into())]).red(),
+                Outcome::Skipped => tr("bulk-skipped").dimmed(),
+                Outcome::Resumed => tr("bulk-resumed").green(),
+            };
+            println!(
+                "{:width$}  {:8}  {:>7}ms  {}",
+                result.file.display(),
+                self.action,
+                result.duration.as_millis(),
+                outcome,
+                width = width
+            );
+        }
+
+        let mut totals = tr_args(
+            "bulk-totals",
+            &[
+                ("succeeded", self.succeeded().into()),
+                ("failed", self.failed().into()),
+                ("skipped", self.skipped().into()),
+            ],
+        );
+        if self.resumed() > 0 {
+            totals.push_str(&tr_args(
+                "bulk-totals-resumed",
+                &[("resumed", self.resumed().into())],
+            ));
+        }
+        if self.exit_code() == 0 {
+            println!("\n{}", totals.green());
+        } else {
+            println!("\n{}", totals.red());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{EXIT_PARTIAL_FAILURE, Outcome, run_bulk, run_resumable};
+    use crate::util::journal::Journal;
+
+    fn files() -> Vec<PathBuf> {
+        ["a.yaml", "b.yaml", "c.yaml"].map(PathBuf::from).to_vec()
+    }
+
+    fn fail_b(path: &std::path::Path) -> Result<(), String> {
+        if path.ends_with("b.yaml") {
+            Err("sops exited with 1".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_partial_failure_continues() {
+        let report = run_bulk(&files(), "decrypt", false, fail_b);
+        assert_eq!(
+            (report.succeeded(), report.failed(), report.skipped()),
+            (2, 1, 0)
+        );
+        assert_eq!(report.exit_code(), EXIT_PARTIAL_FAILURE);
+    }
+
+    #[test]
+    fn test_fail_fast_skips_the_rest() {
+        let report = run_bulk(&files(), "decrypt", true, fail_b);
+        assert_eq!(report.results[2].outcome, Outcome::Skipped);
+        assert_eq!(
+            (report.succeeded(), report.failed(), report.skipped()),
+            (1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_resumes_interrupted_run() {
+        let root = tempfile::TempDir::new().unwrap();
+        let files: Vec<PathBuf> = files().iter().map(|f| root.path().join(f)).collect();
+        let journal = || Journal::open(root.path(), "test", "rotate");
+
+        let report = run_resumable(root.path(), &files, "rotate", journal(), |_| Ok(()), fail_b);
+        assert_eq!((report.succeeded(), report.failed()), (2, 1));
+
+        // a.yaml was finished and needs nothing anymore, b.yaml failed
+        let mut processed = Vec::new();
+        let report = run_resumable(
+            root.path(),
+            &files[1..],
+            "rotate",
+            journal(),
+            |_| Ok(()),
+            |file| {
+                processed.push(file.to_path_buf());
+                Ok(())
+            },
+        );
+        assert_eq!(processed, vec![files[1].clone()]);
+        assert_eq!(report.resumed(), 2);
+        assert_eq!(report.exit_code(), 0);
+        // Finished, the next run starts over
+        assert_eq!(journal().completed().count(), 0);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(
+            run_bulk(&files(), "encrypt", false, |_| Ok(())).exit_code(),
+            0
+        );
+        assert_eq!(
+            run_bulk(&files(), "encrypt", false, |_| Err("no".into())).exit_code(),
+            1
+        );
+        assert_eq!(run_bulk(&[], "encrypt", false, |_| Ok(())).exit_code(), 0);
+    }
+}
@@ -0,0 +1,205 @@
+//! The one layout opsops writes .sops.yaml in. Serializing the structs directly
+//! follows their field order, which changed between opsops versions, so
+//! teammates on different versions kept rewriting each other's files. Fields
+//! here are written in a fixed order instead, options the structs don't model
+//! after the known ones, sorted by name. Rules keep their order: sops uses the
+//! first one that matches.
+
+use serde_yaml::{Mapping, Value};
+
+use super::config_merge::normalize;
+use super::formatting::sort_keys;
+
+const CONFIG_FIELDS: &[&str] = &["include", "creation_rules", "destination_rules", "stores"];
+
+const RULE_FIELDS: &[&str] = &[
+    "path_regex",
+    "age",
+    "pgp",
+    "kms",
+    "aws_profile",
+    "gcp_kms",
+    "azure_keyvault",
+    "hc_vault_transit_uri",
+    "encrypted_regex",
+    "unencrypted_regex",
+    "encrypted_suffix",
+    "unencrypted_suffix",
+    "encrypted_comment_regex",
+    "unencrypted_comment_regex",
+    "shamir_threshold",
+    "mac_only_encrypted",
+    "key_groups",
+];
+
+const KEY_GROUP_FIELDS: &[&str] = &["age", "pgp", "kms", "gcp_kms", "azure_keyvault", "hc_vault"];
+
+const DESTINATION_FIELDS: &[&str] = &[
+    "path_regex",
+    "s3_bucket",
+    "s3_prefix",
+    "gcs_bucket",
+    "gcs_prefix",
+    "vault_path",
+    "vault_address",
+    "vault_kv_mount_name",
+    "vault_kv_version",
+    "recreation_rule",
+    "omit_extensions",
+];
+
+/// Moves the `fields` of `mapping` to the front in that order, the rest
+/// follows sorted. `nested` lays out the value of each field.
+fn order(mapping: Mapping, fields: &[&str], nested: impl Fn(&str, Value) -> Value) -> Mapping {
+    let mut rest: Vec<(Value, Value)> = mapping.into_iter().collect();
+    let mut ordered = Mapping::with_capacity(rest.len());
+    for field in fields {
+        if let Some(i) = rest.iter().position(|(key, _)| key.as_str() == Some(field)) {
+            let (key, value) = rest.remove(i);
+            ordered.insert(key, nested(field, value));
+        }
+    }
+    let mut unknown = Value::Mapping(rest.into_iter().collect());
+    sort_keys(&mut unknown);
+    if let Value::Mapping(unknown) = unknown {
+        ordered.extend(unknown);
+    }
+    ordered
+}
+
+/// Lays out every mapping in the sequence `value` with `layout`
+fn each(value: Value, layout: fn(Value) -> Value) -> Value {
+    match value {
+        Value::Sequence(items) => Value::Sequence(items.into_iter().map(layout).collect()),
+        other => other,
+    }
+}
+
+fn mapping(value: Value, fields: &[&str], nested: impl Fn(&str, Value) -> Value) -> Value {
+    match value {
+        Value::Mapping(map) => Value::Mapping(order(map, fields, nested)),
+        mut other => {
+            sort_keys(&mut other);
+            other
+        }
+    }
+}
+
+fn key_group(value: Value) -> Value {
+    mapping(value, KEY_GROUP_FIELDS, |_, value| value)
+}
+
+fn creation_rule(value: Value) -> Value {
+    mapping(value, RULE_FIELDS, |field, value| match field {
+        "key_groups" => each(value, key_group),
+        _ => value,
+    })
+}
+
+fn destination_rule(value: Value) -> Value {
+    mapping(value, DESTINATION_FIELDS, |field, value| match field {
+        "recreation_rule" => creation_rule(value),
+        _ => value,
+    })
+}
+
+/// Serializes a .sops.yaml document in the canonical layout: fixed field
+/// order, rules in their order, unset options left out, two spaces per level
+pub fn canonical_yaml(config: Value) -> Result<String, String> {
+    let config = mapping(
+        normalize(config),
+        CONFIG_FIELDS,
+        |field, value| match field {
+            "creation_rules" => each(value, creation_rule),
+            "destination_rules" => each(value, destination_rule),
+            _ => {
+                let mut value = value;
+                sort_keys(&mut value);
+                value
+            }
+        },
+    );
+    serde_yaml::to_string(&config).map_err(|e| format!("Failed to serialize config: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::canonical_yaml;
+
+    #[test]
+    fn test_canonical_layout() {
+        let yaml = r#"
+stores:
+  yaml:
+    indent: 4
+  json:
+    indent: 2
+destination_rules:
+- s3_prefix: prod/
+  path_regex: s3/.*
+  recreation_rule:
+    encrypted_regex: ^data$
+    age: age1ghi
+  s3_bucket: team-secrets
+creation_rules:
+- key_groups:
+  - pgp:
+    - FINGERPRINT
+    age:
+    - age1def
+  kms: arn:aws:kms:us-east-1:1234:key/abc
+  shamir_threshold: 2
+  age: age1abc
+  path_regex: secrets/.*\.yaml$
+- encrypted_regex: .*
+  path_regex: app.json
+  age: age1abc
+  key_groups: []
+  pgp: null
+  x_custom: 1
+  azure_keyvault: https://vault
+"#;
+        let config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            canonical_yaml(config).unwrap(),
+            r#"creation_rules:
+- path_regex: secrets/.*\.yaml$
+  age: age1abc
+  kms: arn:aws:kms:us-east-1:1234:key/abc
+  shamir_threshold: 2
+  key_groups:
+  - age:
+    - age1def
+    pgp:
+    - FINGERPRINT
+- path_regex: app.json
+  age: age1abc
+  azure_keyvault: https://vault
+  encrypted_regex: .*
+  x_custom: 1
+destination_rules:
+- path_regex: s3/.*
+  s3_bucket: team-secrets
+  s3_prefix: prod/
+  recreation_rule:
+    age: age1ghi
+    encrypted_regex: ^data$
+stores:
+  json:
+    indent: 2
+  yaml:
+    indent: 4
+"#
+        );
+    }
+
+    #[test]
+    fn test_canonical_layout_is_stable() {
+        let config: serde_yaml::Value =
+            serde_yaml::from_str("creation_rules:\n- age: age1abc\n  path_regex: .*\n").unwrap();
+        let once = canonical_yaml(config).unwrap();
+        let twice = canonical_yaml(serde_yaml::from_str(&once).unwrap()).unwrap();
+        assert_eq!(once, twice);
+        assert_eq!(once, "creation_rules:\n- path_regex: .*\n  age: age1abc\n");
+    }
+}
@@ -0,0 +1,188 @@
+//! Results of health checks like `opsops doctor`. Each result is printed as it
+//! comes in and collected, so CI can gate on them and read them as JSON or JUnit XML.
+
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::Serialize;
+
+use super::print_status::{print_error, print_success, print_warning};
+
+/// Outcome of a check, ordered by severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warning,
+    Error,
+}
+
+/// From which severity on a check counts as failed
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum FailOn {
+    Warning,
+    #[default]
+    Error,
+}
+
+impl FailOn {
+    fn threshold(self) -> Status {
+        match self {
+            FailOn::Warning => Status::Warning,
+            FailOn::Error => Status::Error,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CheckReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl CheckReport {
+    fn record(
+        &mut self,
+        name: &'static str,
+        status: Status,
+        message: String,
+        details: Vec<String>,
+    ) {
+        match status {
+            Status::Pass => print_success(message.green()),
+            Status::Warning => print_warning(message.yellow()),
+            Status::Error => print_error(&message),
+        }
+        for line in &details {
+            println!("  {}", line);
+        }
+        self.checks.push(CheckResult {
+            name,
+            status,
+            message,
+            details,
+        });
+    }
+
+    pub fn pass(&mut self, name: &'static str, message: impl Into<String>) {
+        self.record(name, Status::Pass, message.into(), Vec::new());
+    }
+
+    pub fn warn(&mut self, name: &'static str, message: impl Into<String>, details: Vec<String>) {
+        self.record(name, Status::Warning, message.into(), details);
+    }
+
+    pub fn fail(&mut self, name: &'static str, message: impl Into<String>, details: Vec<String>) {
+        self.record(name, Status::Error, message.into(), details);
+    }
+
+    /// Whether any check is at least as severe as `fail_on`
+    pub fn failed(&self, fail_on: FailOn) -> bool {
+        self.checks
+            .iter()
+            .any(|check| check.status >= fail_on.threshold())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize JSON: {}", e))
+    }
+
+    /// One test case per check, failed from the `fail_on` severity on
+    pub fn to_junit(&self, suite: &str, fail_on: FailOn) -> String {
+        let failures = self
+            .checks
+            .iter()
+            .filter(|c| c.status >= fail_on.threshold())
+            .count();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+            xml_escape(suite),
+            self.checks.len(),
+            failures
+        ));
+        for check in &self.checks {
+            xml.push_str(&format!(
+                "  <testcase classname=\"{}\" name=\"{}\"",
+                xml_escape(suite),
+                check.name
+            ));
+            let body = std::iter::once(check.message.as_str())
+                .chain(check.details.iter().map(String::as_str))
+                .map(xml_escape)
+                .collect::<Vec<_>>()
+                .join("\n");
+            if check.status >= fail_on.threshold() {
+                xml.push_str(&format!(
+                    ">\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+                    xml_escape(&check.message),
+                    body
+                ));
+            } else if check.status == Status::Warning {
+                xml.push_str(&format!(
+                    ">\n    <system-out>{}</system-out>\n  </testcase>\n",
+                    body
+                ));
+            } else {
+                xml.push_str("/>\n");
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckReport, FailOn};
+
+    fn report() -> CheckReport {
+        let mut report = CheckReport::default();
+        report.pass("sops", "Found sops");
+        report.warn(
+            "rule_patterns",
+            "Rule #1 path_regex 'a.yaml' is a plain path",
+            vec!["also matches a-yaml".to_string()],
+        );
+        report
+    }
+
+    #[test]
+    fn test_fail_on_threshold() {
+        let mut report = report();
+        assert!(!report.failed(FailOn::Error));
+        assert!(report.failed(FailOn::Warning));
+
+        report.fail("public_key", "No matching public key", Vec::new());
+        assert!(report.failed(FailOn::Error));
+    }
+
+    #[test]
+    fn test_junit() {
+        let xml = report().to_junit("opsops doctor", FailOn::Warning);
+        assert!(xml.contains("tests=\"2\" failures=\"1\""));
+        assert!(xml.contains("<testcase classname=\"opsops doctor\" name=\"sops\"/>"));
+        assert!(xml.contains(
+            "<failure message=\"Rule #1 path_regex &apos;a.yaml&apos; is a plain path\">"
+        ));
+
+        let xml = report().to_junit("opsops doctor", FailOn::Error);
+        assert!(xml.contains("failures=\"0\""));
+        assert!(xml.contains("<system-out>"));
+    }
+}
@@ -0,0 +1,267 @@
+//! Pipeline fragments `opsops ci generate` writes: a job running the checks
+//! that need no key and, if the project has a key to decrypt with, a job
+//! decrypting through a 1Password service account or the passphrase.
+
+use clap::ValueEnum;
+
+/// The sops release the pipelines install, the one `opsops new` workflows use
+const SOPS_VERSION: &str = "3.10.2";
+
+/// The 1Password CLI release the GitLab pipeline installs
+const OP_VERSION: &str = "2.30.0";
+
+/// Where the pipeline runs
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CiProvider {
+    /// A GitHub Actions workflow
+    Github,
+    /// A GitLab CI file to include from .gitlab-ci.yml
+    Gitlab,
+}
+
+impl CiProvider {
+    /// Where the fragment goes, relative to the project root
+    pub fn default_path(&self) -> &'static str {
+        match self {
+            CiProvider::Github => ".github/workflows/opsops.yml",
+            CiProvider::Gitlab => "ci/opsops.gitlab-ci.yml",
+        }
+    }
+}
+
+/// How the decrypt job gets the key
+#[derive(Debug, Clone, PartialEq)]
+pub enum CiKey {
+    /// From 1Password at this reference, through a service account
+    OnePassword(String),
+    /// Derived from `OPSOPS_PASSPHRASE`
+    Passphrase,
+}
+
+/// What the pipeline is customized to
+#[derive(Debug, Clone, PartialEq)]
+pub struct CiSettings {
+    /// `None` leaves out the decrypt job
+    pub key: Option<CiKey>,
+    /// A file the decrypt job reads, relative to the project root
+    pub sample_file: Option<String>,
+    /// Rules have JSON Schemas, so `verify --deep` has something to check
+    pub schemas: bool,
+}
+
+impl CiSettings {
+    fn decrypt_commands(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+        if let Some(file) = &self.sample_file {
+            commands.push(format!("opsops read {} > /dev/null", file));
+        }
+        if self.schemas {
+            commands.push("opsops verify --deep".to_string());
+        }
+        commands
+    }
+}
+
+fn github(settings: &CiSettings) -> String {
+    let mut workflow = format!(
+        r#"# Generated by `opsops ci generate github`
+name: opsops
+
+on:
+  push:
+  pull_request:
+
+jobs:
+  checks:
+    runs-on: ubuntu-latest
+    env:
+      OPSOPS_FORMATTER: plain
+    steps:
+      - uses: actions/checkout@v4
+        with:
+          fetch-depth: 0
+      - name: Install sops and opsops
+        run: |
+          curl -sSLo /usr/local/bin/sops https://github.com/getsops/sops/releases/download/v{sops}/sops-v{sops}.linux.amd64
+          chmod +x /usr/local/bin/sops
+          cargo install --locked --git https://github.com/frostplexx/opsops
+      - name: Files covered by a rule are encrypted
+        run: opsops verify
+      - name: Files match their creation rules
+        run: opsops drift
+      - name: No private keys are committed
+        run: opsops scan
+      - name: Doctor
+        run: opsops doctor --ci --junit opsops-doctor.xml
+      - uses: actions/upload-artifact@v4
+        if: always()
+        with:
+          name: opsops-doctor
+          path: opsops-doctor.xml
+"#,
+        sops = SOPS_VERSION
+    );
+    let (Some(key), commands) = (&settings.key, settings.decrypt_commands()) else {
+        return workflow;
+    };
+    if commands.is_empty() {
+        return workflow;
+    }
+    let (env, setup) = match key {
+        CiKey::OnePassword(reference) => (
+            format!(
+                "      # The service account needs read access to this item\n      OPSOPS_OP_ITEM: \"{}\"\n      OP_SERVICE_ACCOUNT_TOKEN: ${{{{ secrets.OP_SERVICE_ACCOUNT_TOKEN }}}}\n",
+                reference
+            ),
+            "      - uses: 1password/install-cli-action@v1\n",
+        ),
+        CiKey::Passphrase => (
+            "      OPSOPS_PASSPHRASE: ${{ secrets.OPSOPS_PASSPHRASE }}\n".to_string(),
+            "",
+        ),
+    };
+    workflow.push_str(&format!(
+        r#"
+  decrypt:
+    runs-on: ubuntu-latest
+    needs: checks
+    env:
+      OPSOPS_FORMATTER: plain
+      OPSOPS_READ_ONLY: "1"
+{env}    steps:
+      - uses: actions/checkout@v4
+{setup}      - name: Install sops and opsops
+        run: |
+          curl -sSLo /usr/local/bin/sops https://github.com/getsops/sops/releases/download/v{sops}/sops-v{sops}.linux.amd64
+          chmod +x /usr/local/bin/sops
+          cargo install --locked --git https://github.com/frostplexx/opsops
+      - name: Secrets decrypt with the CI key
+        run: |
+"#,
+        env = env,
+        setup = setup,
+        sops = SOPS_VERSION
+    ));
+    for command in commands {
+        workflow.push_str(&format!("          {}\n", command));
+    }
+    workflow
+}
+
+fn gitlab(settings: &CiSettings) -> String {
+    let mut pipeline = format!(
+        r#"# Generated by `opsops ci generate gitlab`, include it from .gitlab-ci.yml:
+#
+#   include:
+#     - local: {path}
+
+.opsops:
+  image: rust:latest
+  variables:
+    OPSOPS_FORMATTER: plain
+    GIT_DEPTH: "0"
+  before_script:
+    - curl -sSLo /usr/local/bin/sops https://github.com/getsops/sops/releases/download/v{sops}/sops-v{sops}.linux.amd64
+    - chmod +x /usr/local/bin/sops
+    - cargo install --locked --git https://github.com/frostplexx/opsops
+
+opsops:checks:
+  extends: .opsops
+  script:
+    - opsops verify
+    - opsops drift
+    - opsops scan
+    - opsops doctor --ci --junit opsops-doctor.xml
+  artifacts:
+    when: always
+    reports:
+      junit: opsops-doctor.xml
+"#,
+        path = CiProvider::Gitlab.default_path(),
+        sops = SOPS_VERSION
+    );
+    let (Some(key), commands) = (&settings.key, settings.decrypt_commands()) else {
+        return pipeline;
+    };
+    if commands.is_empty() {
+        return pipeline;
+    }
+    let (variables, setup) = match key {
+        CiKey::OnePassword(reference) => (
+            format!(
+                "    # The service account behind the OP_SERVICE_ACCOUNT_TOKEN CI/CD variable\n    # needs read access to this item\n    OPSOPS_OP_ITEM: \"{}\"\n",
+                reference
+            ),
+            format!(
+                "    - curl -sSLo op.zip https://cache.agilebits.com/dist/1P/op2/pkg/v{op}/op_linux_amd64_v{op}.zip\n    - unzip -o op.zip op -d /usr/local/bin\n",
+                op = OP_VERSION
+            ),
+        ),
+        CiKey::Passphrase => (
+            "    # OPSOPS_PASSPHRASE comes from a masked CI/CD variable\n".to_string(),
+            String::new(),
+        ),
+    };
+    pipeline.push_str(&format!(
+        r#"
+opsops:decrypt:
+  extends: .opsops
+  needs: ["opsops:checks"]
+  variables:
+    OPSOPS_FORMATTER: plain
+    OPSOPS_READ_ONLY: "1"
+{variables}  script:
+{setup}"#,
+        variables = variables,
+        setup = setup
+    ));
+    for command in commands {
+        pipeline.push_str(&format!("    - {}\n", command));
+    }
+    pipeline
+}
+
+/// The pipeline fragment for `provider`
+pub fn pipeline(provider: CiProvider, settings: &CiSettings) -> String {
+    match provider {
+        CiProvider::Github => github(settings),
+        CiProvider::Gitlab => gitlab(settings),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CiKey, CiProvider, CiSettings, pipeline};
+    use insta::assert_snapshot;
+
+    fn settings(key: Option<CiKey>) -> CiSettings {
+        CiSettings {
+            key,
+            sample_file: Some("secrets/app.yaml".to_string()),
+            schemas: true,
+        }
+    }
+
+    #[test]
+    fn snapshot_ci_github() {
+        let key = CiKey::OnePassword("op://CI/deploy-key/Private Key".to_string());
+        assert_snapshot!(pipeline(CiProvider::Github, &settings(Some(key))));
+    }
+
+    #[test]
+    fn snapshot_ci_gitlab_passphrase() {
+        assert_snapshot!(pipeline(
+            CiProvider::Gitlab,
+            &settings(Some(CiKey::Passphrase))
+        ));
+    }
+
+    #[test]
+    fn test_checks_only_without_key() {
+        let github = pipeline(CiProvider::Github, &settings(None));
+        assert!(github.contains("opsops doctor --ci"));
+        assert!(!github.contains("decrypt:"));
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&github).unwrap();
+        assert!(yaml["jobs"]["checks"].is_mapping());
+    }
+}
@@ -0,0 +1,369 @@
+//! Pure transformations of a .sops.yaml document. Commands read the config,
+//! pass it through one of these and write the result, which keeps every change
+//! to the file format covered by the snapshot tests below.
+
+use super::{
+    canonical_config::canonical_yaml,
+    migrations::CURRENT_VERSION,
+    op_key::validate_age_recipients,
+    opsops_config::OpsopsConfig,
+    rule_match::{exact_path_regex, targets_file},
+    rule_templates::RuleTemplate,
+    sops_structs::{CreationRule, SopsConfig},
+};
+
+/// The config `opsops init` creates when a project has no .sops.yaml yet
+pub fn basic_config() -> SopsConfig {
+    SopsConfig {
+        creation_rules: vec![CreationRule {
+            path_regex: Some(".*".to_string()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+/// Points the config at an age key in 1Password
+pub fn set_op_item(mut config: SopsConfig, reference: &str) -> SopsConfig {
+    config.onepassworditem = reference.to_string();
+    config
+}
+
+/// Adds a rule for the file at the root relative `path`, or updates the key and
+/// pattern of an existing one. The rule matches exactly that file unless a custom
+/// `path_regex` is given. Rules written by older versions, which used the path as
+/// typed (e.g. `./secrets.yaml`), are found and migrated as well.
+pub fn upsert_file_rule(
+    mut config: SopsConfig,
+    path: &str,
+    path_regex: Option<&str>,
+    pubkey: &str,
+    encrypted_regex: &str,
+) -> Result<SopsConfig, String> {
+    // Refuse to write anything that isn't a valid public recipient
+    validate_age_recipients(pubkey)?;
+
+    let exact = exact_path_regex(path);
+    let path_regex = match path_regex {
+        Some(custom) => {
+            regex::Regex::new(custom)
+                .map_err(|e| format!("Invalid path_regex '{}': {}", custom, e))?;
+            custom.to_string()
+        }
+        None => exact.clone(),
+    };
+    match config.creation_rules.iter_mut().find(|rule| {
+        rule.path_regex
+            .as_deref()
+            .is_some_and(|r| r == path_regex || targets_file(r, path))
+    }) {
+        Some(rule) => {
+            rule.path_regex = Some(path_regex);
+            rule.age = Some(pubkey.to_string());
+            rule.encrypted_regex = Some(encrypted_regex.to_string());
+        }
+        None => config.creation_rules.push(CreationRule {
+            path_regex: Some(path_regex),
+            age: Some(pubkey.to_string()),
+            encrypted_regex: Some(encrypted_regex.to_string()),
+            ..Default::default()
+        }),
+    }
+
+    Ok(config)
+}
+
+/// Points the rules written for exactly the file at `from` at `to` instead,
+/// both relative to the root. Returns how many rules changed.
+pub fn rename_file_rules(mut config: SopsConfig, from: &str, to: &str) -> (SopsConfig, usize) {
+    let mut renamed = 0;
+    for rule in &mut config.creation_rules {
+        if rule
+            .path_regex
+            .as_deref()
+            .is_some_and(|r| targets_file(r, from))
+        {
+            rule.path_regex = Some(exact_path_regex(to));
+            renamed += 1;
+        }
+    }
+    (config, renamed)
+}
+
+/// Removes the rules written for exactly the file at the root relative `path`.
+/// Returns the patterns of the removed rules.
+pub fn remove_file_rules(mut config: SopsConfig, path: &str) -> (SopsConfig, Vec<String>) {
+    let mut removed = Vec::new();
+    config
+        .creation_rules
+        .retain(|rule| match rule.path_regex.as_deref() {
+            Some(r) if targets_file(r, path) => {
+                removed.push(r.to_string());
+                false
+            }
+            _ => true,
+        });
+    (config, removed)
+}
+
+/// Appends a rule built from one of the setup templates
+pub fn add_template_rule(
+    mut config: SopsConfig,
+    template: &RuleTemplate,
+    public_key: &str,
+) -> SopsConfig {
+    config.creation_rules.push(CreationRule {
+        path_regex: Some(template.path_regex.to_string()),
+        age: Some(public_key.to_string()),
+        encrypted_regex: template.encrypted_regex.map(str::to_string),
+        ..Default::default()
+    });
+    config
+}
+
+/// Adds `recipients` to the `age` keys of the rules with the given `path_regex`,
+/// or of every rule. Rules using key groups are left alone: a recipient in one
+/// group only holds a share of the data key.
+pub fn add_recipients(
+    mut config: SopsConfig,
+    recipients: &str,
+    path_regex: Option<&str>,
+) -> Result<SopsConfig, String> {
+    validate_age_recipients(recipients)?;
+    let mut selected = config
+        .creation_rules
+        .iter_mut()
+        .filter(|rule| path_regex.is_none() || rule.path_regex.as_deref() == path_regex)
+        .peekable();
+    if selected.peek().is_none() {
+        return Err(format!(
+            "No rule with path_regex '{}'",
+            path_regex.unwrap_or_default()
+        ));
+    }
+    for rule in selected.filter(|rule| rule.key_groups.is_empty()) {
+        let mut keys = rule.age.clone().unwrap_or_default();
+        for recipient in recipients.split(',').map(str::trim) {
+            if !keys.split(',').any(|key| key.trim() == recipient) {
+                if !keys.is_empty() {
+                    keys.push(',');
+                }
+                keys.push_str(recipient);
+            }
+        }
+        rule.age = Some(keys);
+    }
+    Ok(config)
+}
+
+/// Removes `recipients` from the `age` keys and key groups of every rule
+pub fn remove_recipients(mut config: SopsConfig, recipients: &[&str]) -> SopsConfig {
+    for rule in &mut config.creation_rules {
+        if let Some(age) = &rule.age {
+            let kept: Vec<&str> = age
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !recipients.contains(key))
+                .collect();
+            rule.age = Some(kept.join(",")).filter(|keys| !keys.is_empty());
+        }
+        for group in &mut rule.key_groups {
+            group.age.retain(|key| !recipients.contains(&key.as_str()));
+        }
+    }
+    config
+}
+
+/// Serializes the config exactly as it is written to .sops.yaml, in the
+/// canonical layout of [`canonical_yaml`]. The 1Password
+/// reference is left out, it goes to `.opsops.yaml`, see [`opsops_settings`].
+pub fn render_config(config: &SopsConfig) -> Result<String, String> {
+    let mut value =
+        serde_yaml::to_value(config).map_err(|e| format!("Failed to serialize config: {}", e))?;
+    if let Some(mapping) = value.as_mapping_mut() {
+        mapping.remove("onepassworditem");
+    }
+    canonical_yaml(value)
+}
+
+/// The `.opsops.yaml` belonging to `config`, keeping the other settings of the
+/// `existing` one
+pub fn opsops_settings(config: &SopsConfig, existing: OpsopsConfig) -> OpsopsConfig {
+    OpsopsConfig {
+        version: CURRENT_VERSION,
+        onepassworditem: Some(config.onepassworditem.clone()).filter(|item| !item.is_empty()),
+        ..existing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::{
+        add_recipients, add_template_rule, basic_config, opsops_settings, remove_file_rules,
+        remove_recipients, rename_file_rules, render_config, set_op_item, upsert_file_rule,
+    };
+    use crate::util::rule_templates::TEMPLATES;
+    use crate::util::sops_structs::SopsConfig;
+
+    const PUBKEY: &str = "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p";
+    const OTHER_PUBKEY: &str = "age1lggyhqrw2nlhcxprm67z43rta597azn8gknawjehu9d9dl0jq3yqqvfafg";
+
+    /// A config using most of what sops supports, to catch dropped or reordered keys
+    const EXISTING: &str = r#"
+creation_rules:
+- path_regex: secrets/.*\.yaml$
+  age: age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p
+  encrypted_regex: ^(data|stringData)$
+  shamir_threshold: 2
+  kms: arn:aws:kms:us-east-1:1234:key/abc
+  key_groups:
+  - age:
+    - age1lggyhqrw2nlhcxprm67z43rta597azn8gknawjehu9d9dl0jq3yqqvfafg
+    pgp:
+    - FINGERPRINT
+- path_regex: app.json
+  age: age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p
+  encrypted_regex: .*
+onepassworditem: op://Vault/Item/Field
+stores:
+  yaml:
+    indent: 2
+"#;
+
+    fn existing() -> SopsConfig {
+        serde_yaml::from_str(EXISTING).unwrap()
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        assert_snapshot!(render_config(&existing()).unwrap());
+    }
+
+    #[test]
+    fn snapshot_init_basic_config() {
+        assert_snapshot!(render_config(&basic_config()).unwrap());
+    }
+
+    #[test]
+    fn snapshot_init_set_op_item() {
+        let config = set_op_item(basic_config(), "op://Personal/opsops/Private Key");
+        let opsops = opsops_settings(&config, Default::default());
+        assert_snapshot!(format!(
+            "# .sops.yaml\n{}# .opsops.yaml\n{}",
+            render_config(&config).unwrap(),
+            serde_yaml::to_string(&opsops).unwrap()
+        ));
+    }
+
+    #[test]
+    fn snapshot_target_keys_new_rule() {
+        let config =
+            upsert_file_rule(existing(), "deployment.yaml", None, OTHER_PUBKEY, "^data$").unwrap();
+        assert_snapshot!(render_config(&config).unwrap());
+    }
+
+    #[test]
+    fn snapshot_target_keys_updates_rule() {
+        let config =
+            upsert_file_rule(existing(), "app.json", None, OTHER_PUBKEY, "^password$").unwrap();
+        assert_snapshot!(render_config(&config).unwrap());
+    }
+
+    #[test]
+    fn test_target_keys_finds_rules_however_the_path_was_typed() {
+        for legacy in ["./app.json", "app.json", "^app\\.json$"] {
+            let mut config = existing();
+            config.creation_rules[1].path_regex = Some(legacy.to_string());
+            let config = upsert_file_rule(config, "app.json", None, OTHER_PUBKEY, ".*").unwrap();
+            assert_eq!(config.creation_rules.len(), 2, "{}", legacy);
+            assert_eq!(
+                config.creation_rules[1].path_regex.as_deref(),
+                Some("^app\\.json$")
+            );
+        }
+    }
+
+    #[test]
+    fn test_target_keys_custom_path_regex() {
+        let config = upsert_file_rule(
+            existing(),
+            "app.json",
+            Some("^(app|web)\\.json$"),
+            OTHER_PUBKEY,
+            ".*",
+        )
+        .unwrap();
+        assert_eq!(config.creation_rules.len(), 2);
+        assert_eq!(
+            config.creation_rules[1].path_regex.as_deref(),
+            Some("^(app|web)\\.json$")
+        );
+
+        assert!(
+            upsert_file_rule(existing(), "app.json", Some("(app"), OTHER_PUBKEY, ".*").is_err()
+        );
+    }
+
+    #[test]
+    fn snapshot_mv_renames_exact_rules() {
+        let mut config = existing();
+        config.creation_rules[1].path_regex = Some("./app.json".to_string());
+        let (config, renamed) = rename_file_rules(config, "app.json", "config/app.json");
+        assert_eq!(renamed, 1);
+        assert_snapshot!(render_config(&config).unwrap());
+
+        // Patterns covering more than the file stay as they are
+        let (_, renamed) = rename_file_rules(existing(), "secrets/a.yaml", "b.yaml");
+        assert_eq!(renamed, 0);
+    }
+
+    #[test]
+    fn test_rm_removes_exact_rules() {
+        let (config, removed) = remove_file_rules(existing(), "app.json");
+        assert_eq!(removed, ["app.json"]);
+        assert_eq!(config.creation_rules.len(), 1);
+        let (config, removed) = remove_file_rules(existing(), "secrets/a.yaml");
+        assert!(removed.is_empty());
+        assert_eq!(config.creation_rules.len(), 2);
+    }
+
+    #[test]
+    fn test_target_keys_rejects_private_key() {
+        let result = upsert_file_rule(
+            existing(),
+            "app.json",
+            None,
+            "AGE-SECRET-KEY-1X9Q72KQG3J383K5SA030D46Q8WTYPDEKV6UA0RXZCXN56YVN22YQMNNCXJ",
+            ".*",
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn snapshot_key_add_recipient() {
+        let config = add_recipients(existing(), OTHER_PUBKEY, None).unwrap();
+        // Adding twice changes nothing
+        let config = add_recipients(config, OTHER_PUBKEY, Some("app.json")).unwrap();
+        assert_snapshot!(render_config(&config).unwrap());
+
+        assert!(add_recipients(existing(), OTHER_PUBKEY, Some("other.yaml")).is_err());
+        assert!(add_recipients(existing(), "AGE-SECRET-KEY-1ABC", None).is_err());
+    }
+
+    #[test]
+    fn snapshot_key_expire_sweep() {
+        let config = add_recipients(existing(), OTHER_PUBKEY, Some("app.json")).unwrap();
+        assert_snapshot!(render_config(&remove_recipients(config, &[OTHER_PUBKEY])).unwrap());
+    }
+
+    #[test]
+    fn snapshot_setup_templates() {
+        let mut config = set_op_item(SopsConfig::default(), "op://Vault/Item/Field");
+        for template in &TEMPLATES {
+            config = add_template_rule(config, template, PUBKEY);
+        }
+        assert_snapshot!(render_config(&config).unwrap());
+    }
+}
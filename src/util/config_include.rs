@@ -0,0 +1,284 @@
+//! `include:` support for .sops.yaml. A root config can pull in rule fragments,
+//! e.g. one per team in a monorepo:
+//!
+//! ```yaml
+//! include:
+//!   - teams/payments/sops-rules.yaml
+//!   - teams/platform/sops-rules.yaml
+//! creation_rules:
+//!   - path_regex: .*
+//!     age: age1...
+//! ```
+//!
+//! Fragments contain `creation_rules` and may include further fragments. Include
+//! paths are relative to the file that includes them, `path_regex` is always
+//! relative to the root config, as in sops. Rules are merged depth-first in the
+//! order they are listed, fragments before the rules of the file including them,
+//! so specific team rules win over catch-all rules in the root config.
+//!
+//! `${VAR}` references are expanded in the root config and every fragment before
+//! merging, see [`super::interpolate`].
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use super::{
+    config_edit::render_config,
+    interpolate::interpolate_config,
+    perf::{Category, span},
+    rule_match::{absolute_path, relative_path},
+    sops_config::{read_or_create_config, sops_config_path},
+    sops_structs::{CreationRule, SopsConfig},
+};
+use crate::GlobalContext;
+
+/// A file included from .sops.yaml
+#[derive(Deserialize, Serialize)]
+struct Fragment {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    creation_rules: Vec<CreationRule>,
+}
+
+/// The config sops actually sees, with all includes merged
+pub struct EffectiveConfig {
+    pub config: SopsConfig,
+    /// For every rule, the fragment it was defined in relative to the root config,
+    /// `None` for rules of the root config itself
+    pub sources: Vec<Option<String>>,
+    /// Every included file, canonicalized
+    pub fragments: Vec<PathBuf>,
+}
+
+fn collect(
+    includes: &[String],
+    base_dir: &Path,
+    root_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    effective: &mut EffectiveConfig,
+) -> Result<(), String> {
+    for include in includes {
+        let path = base_dir.join(include);
+        let path = fs::canonicalize(&path)
+            .map_err(|e| format!("Failed to read included file {}: {}", path.display(), e))?;
+        if stack.contains(&path) {
+            return Err(format!("{} includes itself", path.display()));
+        }
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read included file {}: {}", path.display(), e))?;
+        let fragment: Fragment = serde_yaml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse included file {}: {}", path.display(), e))?;
+        let fragment = interpolate_config(fragment)
+            .map_err(|e| format!("In included file {}: {}", path.display(), e))?;
+
+        let dir = path.parent().unwrap_or(root_dir).to_path_buf();
+        effective.fragments.push(path.clone());
+        stack.push(path.clone());
+        collect(&fragment.include, &dir, root_dir, stack, effective)?;
+        stack.pop();
+
+        let source = relative_path(root_dir, &path);
+        for rule in fragment.creation_rules {
+            effective.config.creation_rules.push(rule);
+            effective.sources.push(Some(source.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Expands environment variables in `config`, which was read from `config_path`,
+/// and merges its includes
+pub fn resolve_includes(config: SopsConfig, config_path: &Path) -> Result<EffectiveConfig, String> {
+    let config = interpolate_config(config)?;
+    let config_path = fs::canonicalize(config_path).unwrap_or_else(|_| config_path.to_path_buf());
+    let root_dir = config_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+    let SopsConfig {
+        include,
+        creation_rules,
+        destination_rules,
+        onepassworditem,
+        extra,
+        ..
+    } = config;
+    let mut effective = EffectiveConfig {
+        config: SopsConfig {
+            destination_rules,
+            onepassworditem,
+            extra,
+            ..Default::default()
+        },
+        sources: Vec::new(),
+        fragments: Vec::new(),
+    };
+
+    let mut stack = vec![config_path];
+    collect(&include, &root_dir, &root_dir, &mut stack, &mut effective)?;
+
+    effective
+        .sources
+        .extend(creation_rules.iter().map(|_| None));
+    effective.config.creation_rules.extend(creation_rules);
+    Ok(effective)
+}
+
+/// Reads .sops.yaml, expands environment variables and merges its includes
+pub fn load_effective_config(context: &GlobalContext) -> Result<EffectiveConfig, String> {
+    let _span = span(Category::ConfigIo, "read .sops.yaml with includes");
+    let config = read_or_create_config(context)?;
+    let path = sops_config_path(context).ok_or("Could not determine project root")?;
+    resolve_includes(config, &path)
+}
+
+/// If .sops.yaml uses includes or environment variables, writes the effective
+/// config to a temporary file next to it, so sops resolves `path_regex` against
+/// the same directory. The file is removed when the returned handle is dropped.
+pub fn materialize_config(context: &GlobalContext) -> Result<Option<NamedTempFile>, String> {
+    let _span = span(Category::ConfigIo, "merge .sops.yaml includes");
+    let path = match sops_config_path(context) {
+        Some(p) if p.is_file() => p,
+        _ => return Ok(None),
+    };
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let config: SopsConfig = match serde_yaml::from_str(&contents) {
+        Ok(c) => c,
+        // Leave reporting broken configs to sops
+        Err(_) => return Ok(None),
+    };
+    if config.include.is_empty() && !contents.contains("${") {
+        return Ok(None);
+    }
+
+    let effective = resolve_includes(config, &path)?;
+    let yaml = render_config(&effective.config)?;
+    // Absolute, as sops resolves path_regex against the config's directory
+    let dir = absolute_path(
+        path.parent()
+            .filter(|d| !d.as_os_str().is_empty())
+            .unwrap_or(Path::new(".")),
+    );
+    let dir = dir.as_path();
+    let mut file = tempfile::Builder::new()
+        .prefix(".sops.opsops-")
+        .suffix(".yaml")
+        .tempfile_in(dir)
+        .map_err(|e| format!("Failed to create merged config in {}: {}", dir.display(), e))?;
+    std::io::Write::write_all(&mut file, yaml.as_bytes())
+        .map_err(|e| format!("Failed to write merged config: {}", e))?;
+    Ok(Some(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::{materialize_config, resolve_includes};
+    use crate::GlobalContext;
+    use crate::util::sops_structs::SopsConfig;
+
+    fn setup() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("teams/payments")).unwrap();
+        fs::write(
+            dir.path().join(".sops.yaml"),
+            "include:\n- teams/payments/rules.yaml\n- teams/platform.yaml\ncreation_rules:\n- path_regex: .*\n  age: age1root\nonepassworditem: op://V/I/F\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("teams/payments/rules.yaml"),
+            "include:\n- common.yaml\ncreation_rules:\n- path_regex: ^teams/payments/\n  age: age1payments\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("teams/payments/common.yaml"),
+            "creation_rules:\n- path_regex: \\.env$\n  age: age1common\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("teams/platform.yaml"),
+            "creation_rules:\n- path_regex: ^teams/platform/\n  age: age1platform\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    fn read(dir: &TempDir) -> SopsConfig {
+        serde_yaml::from_str(&fs::read_to_string(dir.path().join(".sops.yaml")).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_merges_includes_in_order_with_provenance() {
+        let dir = setup();
+        let effective = resolve_includes(read(&dir), &dir.path().join(".sops.yaml")).unwrap();
+
+        let ages: Vec<_> = effective
+            .config
+            .creation_rules
+            .iter()
+            .map(|r| r.age.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            ages,
+            vec!["age1common", "age1payments", "age1platform", "age1root"]
+        );
+        assert_eq!(
+            effective.sources,
+            vec![
+                Some("teams/payments/common.yaml".to_string()),
+                Some("teams/payments/rules.yaml".to_string()),
+                Some("teams/platform.yaml".to_string()),
+                None,
+            ]
+        );
+        assert!(effective.config.include.is_empty());
+        assert_eq!(effective.config.onepassworditem, "op://V/I/F");
+    }
+
+    #[test]
+    fn test_rejects_include_cycles() {
+        let dir = setup();
+        fs::write(
+            dir.path().join("teams/platform.yaml"),
+            "include:\n- ../.sops.yaml\n",
+        )
+        .unwrap();
+        let err = resolve_includes(read(&dir), &dir.path().join(".sops.yaml"))
+            .err()
+            .unwrap();
+        assert!(err.contains("includes itself"), "{}", err);
+    }
+
+    #[test]
+    fn test_materialize_writes_merged_config_next_to_root() {
+        let dir = setup();
+        let context = GlobalContext {
+            sops_file: Some(dir.path().join(".sops.yaml").to_string_lossy().into()),
+            opitem: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
+        };
+
+        let file = materialize_config(&context).unwrap().unwrap();
+        assert_eq!(
+            file.path().parent().unwrap().canonicalize().unwrap(),
+            dir.path().canonicalize().unwrap()
+        );
+        let merged: SopsConfig =
+            serde_yaml::from_str(&fs::read_to_string(file.path()).unwrap()).unwrap();
+        assert_eq!(merged.creation_rules.len(), 4);
+        assert!(merged.include.is_empty());
+
+        let path = file.path().to_path_buf();
+        drop(file);
+        assert!(!path.exists());
+    }
+}
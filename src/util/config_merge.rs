@@ -0,0 +1,167 @@
+//! Three-way merge of `.sops.yaml`, for when the file changed between opsops
+//! reading it and writing it back, e.g. because another opsops invocation or
+//! a person edited it meanwhile.
+
+use serde_yaml::{Mapping, Value};
+
+use super::canonical_config::canonical_yaml;
+
+/// Merges the changes from `base` to `ours` and from `base` to `theirs`.
+/// Changes only one side made are taken, as are identical changes. Rules
+/// appended on both sides are all kept. Anything else is a conflict, reported
+/// with the key path it happened at. Without a `base` the file didn't exist
+/// when it was read, so `theirs` can only be taken if it equals `ours`.
+pub fn merge_yaml(base: Option<&str>, ours: &str, theirs: &str) -> Result<String, String> {
+    let parse = |yaml: &str| {
+        serde_yaml::from_str::<Value>(yaml)
+            .map(normalize)
+            .map_err(|e| format!("Failed to parse YAML: {}", e))
+    };
+    let (ours, theirs) = (parse(ours)?, parse(theirs)?);
+    let Some(base) = base else {
+        if ours != theirs {
+            return Err("it was created by someone else meanwhile".to_string());
+        }
+        return canonical_yaml(ours);
+    };
+    let merged = merge(Some(&parse(base)?), Some(&ours), Some(&theirs), "")?;
+    canonical_yaml(merged.unwrap_or(Value::Mapping(Mapping::new())))
+}
+
+/// Drops unset options: opsops writes them as `null` or `[]` (e.g. `key_groups`),
+/// which means the same to sops as leaving them out
+pub fn normalize(value: Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .map(|(k, v)| (k, normalize(v)))
+                .filter(|(_, v)| {
+                    !(v.is_null()
+                        || v.as_sequence().is_some_and(Vec::is_empty)
+                        || v.as_mapping().is_some_and(Mapping::is_empty))
+                })
+                .collect(),
+        ),
+        Value::Sequence(sequence) => Value::Sequence(sequence.into_iter().map(normalize).collect()),
+        other => other,
+    }
+}
+
+fn merge(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    path: &str,
+) -> Result<Option<Value>, String> {
+    if ours == theirs {
+        return Ok(ours.cloned());
+    }
+    if ours == base {
+        return Ok(theirs.cloned());
+    }
+    if theirs == base {
+        return Ok(ours.cloned());
+    }
+
+    let conflict = || {
+        Err(format!(
+            "both changed {}",
+            if path.is_empty() { "the config" } else { path }
+        ))
+    };
+    match (base, ours, theirs) {
+        (base, Some(Value::Mapping(ours)), Some(Value::Mapping(theirs)))
+            if base.is_none_or(Value::is_mapping) =>
+        {
+            let empty = Mapping::new();
+            let base = base.and_then(Value::as_mapping).unwrap_or(&empty);
+            let mut merged = Mapping::new();
+            for key in theirs
+                .keys()
+                .chain(ours.keys().filter(|k| !theirs.contains_key(*k)))
+            {
+                let name = key
+                    .as_str()
+                    .map_or_else(|| format!("{:?}", key), str::to_string);
+                let child = if path.is_empty() {
+                    name
+                } else {
+                    format!("{}.{}", path, name)
+                };
+                if let Some(value) = merge(base.get(key), ours.get(key), theirs.get(key), &child)? {
+                    merged.insert(key.clone(), value);
+                }
+            }
+            Ok(Some(Value::Mapping(merged)))
+        }
+        (base, Some(Value::Sequence(ours)), Some(Value::Sequence(theirs)))
+            if base.is_none_or(Value::is_sequence) =>
+        {
+            let empty = Vec::new();
+            let base = base.and_then(Value::as_sequence).unwrap_or(&empty);
+            // Both appended entries
+            if ours.starts_with(base) && theirs.starts_with(base) {
+                let mut merged = theirs.clone();
+                merged.extend(
+                    ours[base.len()..]
+                        .iter()
+                        .filter(|v| !theirs.contains(v))
+                        .cloned(),
+                );
+                return Ok(Some(Value::Sequence(merged)));
+            }
+            // Both changed entries in place
+            if ours.len() == base.len() && theirs.len() == base.len() {
+                let mut merged = Vec::new();
+                for (i, ((b, o), t)) in base.iter().zip(ours).zip(theirs).enumerate() {
+                    let child = format!("{}[{}]", path, i);
+                    if let Some(value) = merge(Some(b), Some(o), Some(t), &child)? {
+                        merged.push(value);
+                    }
+                }
+                return Ok(Some(Value::Sequence(merged)));
+            }
+            conflict()
+        }
+        _ => conflict(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_yaml;
+
+    const BASE: &str =
+        "creation_rules:\n- path_regex: a\n  age: age1a\n- path_regex: b\n  age: age1b\n";
+
+    #[test]
+    fn test_merges_independent_changes() {
+        let ours = "creation_rules:\n- path_regex: a\n  age: age1new\n  key_groups: []\n- path_regex: b\n  age: age1b\n  pgp: null\n";
+        let theirs = "creation_rules:\n- path_regex: a\n  age: age1a\n- path_regex: b\n  age: age1b\n  encrypted_regex: ^data$\nstores:\n  yaml:\n    indent: 2\n";
+        assert_eq!(
+            merge_yaml(Some(BASE), ours, theirs).unwrap(),
+            "creation_rules:\n- path_regex: a\n  age: age1new\n- path_regex: b\n  age: age1b\n  encrypted_regex: ^data$\nstores:\n  yaml:\n    indent: 2\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_rules_appended_on_both_sides() {
+        let ours = format!("{}- path_regex: c\n  age: age1c\n", BASE);
+        let theirs = format!("{}- path_regex: d\n  age: age1d\n", BASE);
+        let merged = merge_yaml(Some(BASE), &ours, &theirs).unwrap();
+        assert!(merged.ends_with("- path_regex: d\n  age: age1d\n- path_regex: c\n  age: age1c\n"));
+    }
+
+    #[test]
+    fn test_reports_conflicts() {
+        let ours = BASE.replace("age1a", "age1ours");
+        let theirs = BASE.replace("age1a", "age1theirs");
+        assert_eq!(
+            merge_yaml(Some(BASE), &ours, &theirs).unwrap_err(),
+            "both changed creation_rules[0].age"
+        );
+        // A config created meanwhile
+        assert!(merge_yaml(None, "creation_rules: []\n", BASE).is_err());
+    }
+}
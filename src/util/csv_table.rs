@@ -0,0 +1,248 @@
+//! CSV and TSV credential exports, like the spreadsheets teams keep passwords
+//! in, and their translation to and from YAML maps keyed by one of the columns.
+
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+
+/// Column delimiter of a table file, TSV for `.tsv`/`.tab`, CSV otherwise
+pub fn delimiter_for(path: &Path) -> char {
+    match path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("tsv") | Some("tab") => '\t',
+        _ => ',',
+    }
+}
+
+/// Parses RFC 4180 style CSV: fields may be quoted, quoted fields may contain
+/// delimiters, newlines and doubled quotes
+pub fn parse_table(contents: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = contents.trim_start_matches('\u{feff}').chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            c if quoted => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                line += 1;
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("Unterminated quoted field in line {}", line));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    // Blank lines, e.g. at the end of spreadsheet exports
+    rows.retain(|r| !(r.len() == 1 && r[0].is_empty()));
+    Ok(rows)
+}
+
+/// Renders rows as CSV, quoting fields where needed
+pub fn render_table(rows: &[Vec<String>], delimiter: char) -> String {
+    let mut out = String::new();
+    for row in rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|field| {
+                if field.contains([delimiter, '"', '\n', '\r']) {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field.clone()
+                }
+            })
+            .collect();
+        out.push_str(&fields.join(&delimiter.to_string()));
+        out.push('\n');
+    }
+    out
+}
+
+/// Turns a table with a header row into a map from the `key_column` (the
+/// first column by default) to the other columns of each row
+pub fn table_to_yaml(rows: &[Vec<String>], key_column: Option<&str>) -> Result<Mapping, String> {
+    let (header, records) = rows.split_first().ok_or("The table is empty")?;
+    let key_index = match key_column {
+        Some(column) => header
+            .iter()
+            .position(|h| h == column)
+            .ok_or_else(|| format!("The table has no column '{}'", column))?,
+        None => 0,
+    };
+
+    let mut map = Mapping::new();
+    for (i, record) in records.iter().enumerate() {
+        // Line numbers as shown by spreadsheets, counting the header
+        let line = i + 2;
+        if record.len() != header.len() {
+            return Err(format!(
+                "Row {} has {} columns, the header has {}",
+                line,
+                record.len(),
+                header.len()
+            ));
+        }
+        let key = &record[key_index];
+        if key.is_empty() {
+            return Err(format!("Row {} has an empty '{}'", line, header[key_index]));
+        }
+        let mut entry = Mapping::new();
+        for (column, value) in header
+            .iter()
+            .zip(record)
+            .filter(|(c, _)| **c != header[key_index])
+        {
+            entry.insert(column.as_str().into(), value.as_str().into());
+        }
+        if map
+            .insert(key.as_str().into(), Value::Mapping(entry))
+            .is_some()
+        {
+            return Err(format!("Row {} repeats the key '{}'", line, key));
+        }
+    }
+    Ok(map)
+}
+
+/// Turns a map like the ones [`table_to_yaml`] creates back into a table, with
+/// the keys in a column named `key_column`
+pub fn yaml_to_table(document: &Value, key_column: &str) -> Result<Vec<Vec<String>>, String> {
+    let map = document
+        .as_mapping()
+        .ok_or("The file is not a map of entries")?;
+    let scalar = |value: &Value| match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Null => Some(String::new()),
+        _ => None,
+    };
+
+    // Columns in the order they first appear
+    let mut columns: Vec<String> = Vec::new();
+    let mut entries = Vec::new();
+    for (key, entry) in map {
+        let key = scalar(key).ok_or("Entry names have to be scalars")?;
+        let entry = entry
+            .as_mapping()
+            .ok_or_else(|| format!("'{}' is not a map of columns", key))?;
+        let mut fields = Vec::new();
+        for (column, value) in entry {
+            let column = scalar(column).ok_or("Column names have to be scalars")?;
+            let value = scalar(value)
+                .ok_or_else(|| format!("'{}.{}' is nested, tables are flat", key, column))?;
+            if !columns.contains(&column) {
+                columns.push(column.clone());
+            }
+            fields.push((column, value));
+        }
+        entries.push((key, fields));
+    }
+
+    let mut header = vec![key_column.to_string()];
+    header.extend(columns.iter().cloned());
+    let mut rows = vec![header];
+    for (key, fields) in entries {
+        let mut row = vec![key];
+        row.extend(columns.iter().map(|column| {
+            fields
+                .iter()
+                .find(|(c, _)| c == column)
+                .map(|(_, v)| v.clone())
+                .unwrap_or_default()
+        }));
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{delimiter_for, parse_table, render_table, table_to_yaml, yaml_to_table};
+
+    const EXPORT: &str = "name,username,password\r\ngithub,bob,\"p,w \"\"1\"\"\"\r\naws,alice,\"multi\nline\"\r\n\r\n";
+
+    #[test]
+    fn test_parse_table() {
+        let rows = parse_table(EXPORT, ',').unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1], vec!["github", "bob", "p,w \"1\""]);
+        assert_eq!(rows[2][2], "multi\nline");
+        assert_eq!(
+            parse_table("a\tb\n1\t2", '\t').unwrap(),
+            vec![vec!["a", "b"], vec!["1", "2"]]
+        );
+        assert!(parse_table("a,\"b\n", ',').is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rows = parse_table(EXPORT, ',').unwrap();
+        let map = table_to_yaml(&rows, None).unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&map).unwrap(),
+            "github:\n  username: bob\n  password: p,w \"1\"\naws:\n  username: alice\n  password: |-\n    multi\n    line\n"
+        );
+
+        let back = yaml_to_table(&serde_yaml::Value::Mapping(map), "name").unwrap();
+        assert_eq!(back, rows);
+        assert_eq!(
+            render_table(&back, ','),
+            "name,username,password\ngithub,bob,\"p,w \"\"1\"\"\"\naws,alice,\"multi\nline\"\n"
+        );
+    }
+
+    #[test]
+    fn test_table_to_yaml_errors() {
+        let rows = parse_table("name,password\ngithub,a\ngithub,b\n", ',').unwrap();
+        assert!(table_to_yaml(&rows, None).unwrap_err().contains("repeats"));
+        assert!(table_to_yaml(&rows, Some("url")).is_err());
+        let ragged = parse_table("name,password\ngithub\n", ',').unwrap();
+        assert!(table_to_yaml(&ragged, None).unwrap_err().contains("Row 2"));
+    }
+
+    #[test]
+    fn test_key_column() {
+        let rows = parse_table("url,name\nexample.com,web\n", ',').unwrap();
+        let map = table_to_yaml(&rows, Some("name")).unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&map).unwrap(),
+            "web:\n  url: example.com\n"
+        );
+    }
+
+    #[test]
+    fn test_delimiter_for() {
+        assert_eq!(delimiter_for(Path::new("export.tsv")), '\t');
+        assert_eq!(delimiter_for(Path::new("export.csv")), ',');
+    }
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{dirs::project_state_dir, rule_match::relative_path};
+
+/// Tracks plaintext copies produced by `decrypt`, relative to the project root
+const STATE_FILE: &str = "decrypted.json";
+
+/// Where a plaintext copy came from and what its source looked like at the time
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DecryptedCopy {
+    /// The encrypted file, relative to the project root
+    pub source: String,
+    pub source_sha256: String,
+    /// Unix timestamp of the decryption
+    pub decrypted_at: u64,
+}
+
+type Copies = BTreeMap<String, DecryptedCopy>;
+
+fn state_path(root: &Path) -> PathBuf {
+    project_state_dir(root).join(STATE_FILE)
+}
+
+fn read_copies(root: &Path) -> Copies {
+    fs::read_to_string(state_path(root))
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+fn write_copies(root: &Path, copies: &Copies) -> Result<(), String> {
+    let path = state_path(root);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(copies)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn key(root: &Path, path: &Path) -> String {
+    let absolute = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    relative_path(&root, &absolute)
+}
+
+fn sha256_file(path: &Path) -> Option<String> {
+    let contents = fs::read(path).ok()?;
+    Some(
+        Sha256::digest(&contents)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+    )
+}
+
+/// Remembers that `plaintext` was just decrypted from `source`
+pub fn record_decryption(root: &Path, plaintext: &Path, source: &Path) -> Result<(), String> {
+    let source_sha256 =
+        sha256_file(source).ok_or_else(|| format!("Failed to read {}", source.display()))?;
+    let decrypted_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    let mut copies = read_copies(root);
+    copies.insert(
+        key(root, plaintext),
+        DecryptedCopy {
+            source: key(root, source),
+            source_sha256,
+            decrypted_at,
+        },
+    );
+    write_copies(root, &copies)
+}
+
+/// Returns the encrypted source of `plaintext` if it changed after the copy was decrypted
+pub fn changed_source(root: &Path, plaintext: &Path) -> Option<PathBuf> {
+    let copies = read_copies(root);
+    let copy = copies.get(&key(root, plaintext))?;
+    let source = root.join(&copy.source);
+    let current = sha256_file(&source)?;
+    (current != copy.source_sha256).then_some(source)
+}
+
+/// The tracked plaintext copies decrypted from `source`
+pub fn copies_of(root: &Path, source: &Path) -> Vec<PathBuf> {
+    let source = key(root, source);
+    read_copies(root)
+        .into_iter()
+        .filter(|(_, copy)| copy.source == source)
+        .map(|(plaintext, _)| root.join(plaintext))
+        .collect()
+}
+
+/// Stops tracking `plaintext`, e.g. once it has been encrypted
+pub fn forget(root: &Path, plaintext: &Path) -> Result<(), String> {
+    let mut copies = read_copies(root);
+    if copies.remove(&key(root, plaintext)).is_none() {
+        return Ok(());
+    }
+    write_copies(root, &copies)
+}
+
+/// Overwrites `path` with zeros before removing it, so the plaintext doesn't
+/// linger in the blocks the file used. Copy-on-write filesystems and SSDs may
+/// still keep the old blocks around.
+pub fn shred(path: &Path) -> std::io::Result<()> {
+    let length = fs::metadata(path)?.len();
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 4096];
+    let mut left = length;
+    while left > 0 {
+        let chunk = left.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        left -= chunk as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)
+}
+
+/// A key path that differs between two documents
+#[derive(Debug, PartialEq)]
+pub enum KeyChange {
+    Added(String),
+    Removed(String),
+    Changed(String),
+}
+
+/// Collects the leaves of `value` by their `a.b[0].c` key path
+pub fn flatten(value: &Value, prefix: String, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Mapping(map) => {
+            for (k, v) in map {
+                let k = match k {
+                    Value::String(s) => s.clone(),
+                    other => serde_yaml::to_string(other)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                };
+                let path = if prefix.is_empty() {
+                    k
+                } else {
+                    format!("{}.{}", prefix, k)
+                };
+                flatten(v, path, out);
+            }
+        }
+        Value::Sequence(items) => {
+            for (i, v) in items.iter().enumerate() {
+                flatten(v, format!("{}[{}]", prefix, i), out);
+            }
+        }
+        _ => {
+            out.insert(prefix, value.clone());
+        }
+    }
+}
+
+/// Compares two documents by key path, without exposing any values
+pub fn structural_diff(old: &Value, new: &Value) -> Vec<KeyChange> {
+    let (mut old_keys, mut new_keys) = (BTreeMap::new(), BTreeMap::new());
+    flatten(old, String::new(), &mut old_keys);
+    flatten(new, String::new(), &mut new_keys);
+
+    let mut changes = Vec::new();
+    for (path, value) in &old_keys {
+        match new_keys.get(path) {
+            None => changes.push(KeyChange::Removed(path.clone())),
+            Some(v) if v != value => changes.push(KeyChange::Changed(path.clone())),
+            Some(_) => {}
+        }
+    }
+    for path in new_keys.keys() {
+        if !old_keys.contains_key(path) {
+            changes.push(KeyChange::Added(path.clone()));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::{KeyChange, changed_source, forget, record_decryption, structural_diff};
+
+    #[test]
+    fn test_detects_changed_source() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("secrets.yaml.enc");
+        let plaintext = dir.path().join("secrets.yaml");
+        fs::write(&source, "encrypted v1").unwrap();
+        fs::write(&plaintext, "password: hunter2").unwrap();
+
+        record_decryption(dir.path(), &plaintext, &source).unwrap();
+        assert_eq!(changed_source(dir.path(), &plaintext), None);
+
+        // A teammate pushed a new version
+        fs::write(&source, "encrypted v2").unwrap();
+        assert_eq!(changed_source(dir.path(), &plaintext), Some(source));
+
+        forget(dir.path(), &plaintext).unwrap();
+        assert_eq!(changed_source(dir.path(), &plaintext), None);
+    }
+
+    #[test]
+    fn test_structural_diff() {
+        let old =
+            serde_yaml::from_str("db:\n  user: admin\n  password: a\nhosts: [x, y]\n").unwrap();
+        let new =
+            serde_yaml::from_str("db:\n  user: admin\n  password: b\n  port: 5432\nhosts: [x]\n")
+                .unwrap();
+        assert_eq!(
+            structural_diff(&old, &new),
+            vec![
+                KeyChange::Changed("db.password".to_string()),
+                KeyChange::Removed("hosts[1]".to_string()),
+                KeyChange::Added("db.port".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! `destination_rules` of .sops.yaml, which tell `sops publish` where to push
+//! a file: an S3 or GCS bucket (re-encrypted with the rule's
+//! `recreation_rule`) or a Vault KV path (decrypted).
+
+use regex::Regex;
+
+use super::sops_structs::DestinationRule;
+
+/// Describes where a destination rule publishes to, e.g. `s3://bucket/prefix`
+pub fn describe(rule: &DestinationRule) -> Option<String> {
+    let with_prefix = |scheme: &str, bucket: &str, prefix: &Option<String>| {
+        format!(
+            "{}://{}/{}",
+            scheme,
+            bucket,
+            prefix.as_deref().unwrap_or("")
+        )
+    };
+    if let Some(bucket) = &rule.s3_bucket {
+        return Some(with_prefix("s3", bucket, &rule.s3_prefix));
+    }
+    if let Some(bucket) = &rule.gcs_bucket {
+        return Some(with_prefix("gcs", bucket, &rule.gcs_prefix));
+    }
+    rule.vault_path.as_ref().map(|path| {
+        format!(
+            "vault {}{}/{}",
+            rule.vault_address
+                .as_deref()
+                .map(|a| format!("{} ", a))
+                .unwrap_or_default(),
+            rule.vault_kv_mount_name.as_deref().unwrap_or("secret"),
+            path
+        )
+    })
+}
+
+/// Problems sops would reject or silently misbehave on, one per line
+pub fn destination_problems(rules: &[DestinationRule]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (i, rule) in rules.iter().enumerate() {
+        let mut problem = |message: String| {
+            problems.push(format!("Destination #{}: {}", i + 1, message));
+        };
+        match &rule.path_regex {
+            Some(pattern) => {
+                if let Err(e) = Regex::new(pattern) {
+                    problem(format!("invalid path_regex: {}", e));
+                }
+            }
+            None => problem("has no path_regex".to_string()),
+        }
+
+        let targets = [
+            rule.s3_bucket.is_some(),
+            rule.gcs_bucket.is_some(),
+            rule.vault_path.is_some(),
+        ];
+        match targets.iter().filter(|t| **t).count() {
+            0 => problem("sets none of s3_bucket, gcs_bucket and vault_path".to_string()),
+            1 => {}
+            _ => problem("sets more than one of s3_bucket, gcs_bucket and vault_path".to_string()),
+        }
+        if rule.vault_path.is_some() && rule.recreation_rule.is_some() {
+            problem(
+                "recreation_rule is ignored for Vault, which stores the decrypted values"
+                    .to_string(),
+            );
+        }
+        if rule.s3_prefix.is_some() && rule.s3_bucket.is_none() {
+            problem("s3_prefix without s3_bucket".to_string());
+        }
+        if rule.gcs_prefix.is_some() && rule.gcs_bucket.is_none() {
+            problem("gcs_prefix without gcs_bucket".to_string());
+        }
+        if let Some(version) = rule.vault_kv_version
+            && version != 1
+            && version != 2
+        {
+            problem(format!("vault_kv_version {} is neither 1 nor 2", version));
+        }
+    }
+    problems
+}
+
+/// Index of the destination rule `sops publish` would use for the project
+/// relative `file`: the first one whose `path_regex` matches
+pub fn first_matching_destination(rules: &[DestinationRule], file: &str) -> Option<usize> {
+    rules.iter().position(|rule| {
+        rule.path_regex
+            .as_deref()
+            .and_then(|pattern| Regex::new(pattern).ok())
+            .is_some_and(|regex| regex.is_match(file))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, destination_problems, first_matching_destination};
+    use crate::util::sops_structs::{DestinationRule, SopsConfig};
+
+    fn rules() -> Vec<DestinationRule> {
+        let config: SopsConfig = serde_yaml::from_str(
+            r#"
+destination_rules:
+- path_regex: s3/.*
+  s3_bucket: team-secrets
+  s3_prefix: prod/
+- path_regex: vault/.*
+  vault_path: apps/
+  vault_address: https://vault.example.com
+- path_regex: "["
+  s3_bucket: a
+  gcs_bucket: b
+  vault_kv_version: 3
+"#,
+        )
+        .unwrap();
+        config.destination_rules
+    }
+
+    #[test]
+    fn test_describe() {
+        let rules = rules();
+        assert_eq!(describe(&rules[0]).unwrap(), "s3://team-secrets/prod/");
+        assert_eq!(
+            describe(&rules[1]).unwrap(),
+            "vault https://vault.example.com secret/apps/"
+        );
+        assert_eq!(describe(&DestinationRule::default()), None);
+    }
+
+    #[test]
+    fn test_destination_problems() {
+        let problems = destination_problems(&rules());
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems.iter().all(|p| p.starts_with("Destination #3")));
+        assert!(problems[1].contains("more than one"));
+    }
+
+    #[test]
+    fn test_first_matching_destination() {
+        let rules = rules();
+        assert_eq!(
+            first_matching_destination(&rules, "vault/app.yaml"),
+            Some(1)
+        );
+        assert_eq!(first_matching_destination(&rules, "local.yaml"), None);
+    }
+}
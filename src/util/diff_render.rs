@@ -0,0 +1,407 @@
+//! How `diff` shows the change between two plaintexts. Built in are `inline`,
+//! a unified diff, and `side-by-side`. Anything else is a command line, e.g.
+//! `delta` or `difft --display inline`, that gets the old and the new version
+//! as its last two arguments. On unix those are named pipes in a private
+//! directory, so the plaintext never lands on disk; elsewhere they are files
+//! in a temporary directory that is removed once the tool exits.
+//!
+//! `--renderer` picks one for a run, `diff_renderer:` in the user config sets
+//! the default.
+
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use super::{session_record, user_config::read_user_config};
+
+/// Lines of unchanged context around each hunk of `inline`
+const CONTEXT: usize = 3;
+
+/// Width of `side-by-side` output when `$COLUMNS` isn't set
+const DEFAULT_WIDTH: usize = 120;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffRenderer {
+    Inline,
+    SideBySide,
+    /// Program and arguments, split at whitespace
+    External(Vec<String>),
+}
+
+impl DiffRenderer {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec.trim() {
+            "" => Err("The diff renderer can't be empty".to_string()),
+            "inline" => Ok(DiffRenderer::Inline),
+            "side-by-side" => Ok(DiffRenderer::SideBySide),
+            command => Ok(DiffRenderer::External(
+                command.split_whitespace().map(str::to_string).collect(),
+            )),
+        }
+    }
+}
+
+/// `flag` if given, else `diff_renderer:` of the user config, else `inline`
+pub fn configured_renderer(flag: Option<&str>) -> Result<DiffRenderer, String> {
+    match flag
+        .map(str::to_string)
+        .or(read_user_config().diff_renderer)
+    {
+        Some(spec) => DiffRenderer::parse(&spec),
+        None => Ok(DiffRenderer::Inline),
+    }
+}
+
+/// One line of a line by line diff
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The lines of `old` and `new` as removed, added or unchanged, from their
+/// longest common subsequence
+pub fn line_changes<'a>(old: &'a str, new: &'a str) -> Vec<Change<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // lengths[i][j]: longest common subsequence of a[i..] and b[j..]
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes: Vec<Change> = old[..prefix].iter().map(|l| Change::Same(l)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            changes.push(Change::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            changes.push(Change::Removed(a[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(b[j]));
+            j += 1;
+        }
+    }
+    changes.extend(old[old.len() - suffix..].iter().map(|l| Change::Same(l)));
+    changes
+}
+
+/// `changes` as a unified diff with [`CONTEXT`] lines around each hunk,
+/// empty if nothing changed
+pub fn render_inline(changes: &[Change], old_label: &str, new_label: &str) -> String {
+    let changed: Vec<usize> = (0..changes.len())
+        .filter(|&i| !matches!(changes[i], Change::Same(_)))
+        .collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    // Line numbers in the old and the new version where each change starts
+    let mut positions = Vec::with_capacity(changes.len());
+    let (mut old_line, mut new_line) = (1, 1);
+    for change in changes {
+        positions.push((old_line, new_line));
+        match change {
+            Change::Same(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Change::Removed(_) => old_line += 1,
+            Change::Added(_) => new_line += 1,
+        }
+    }
+
+    let mut out = format!(
+        "{}\n{}\n",
+        format!("--- {}", old_label).red(),
+        format!("+++ {}", new_label).green()
+    );
+    let mut next = 0;
+    while next < changed.len() {
+        let start = changed[next].saturating_sub(CONTEXT);
+        let mut last = changed[next];
+        while next < changed.len() && changed[next] <= last + 2 * CONTEXT {
+            last = changed[next];
+            next += 1;
+        }
+        let hunk = &changes[start..(last + CONTEXT + 1).min(changes.len())];
+        let old_count = hunk
+            .iter()
+            .filter(|c| !matches!(c, Change::Added(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|c| !matches!(c, Change::Removed(_)))
+            .count();
+        let (old_start, new_start) = positions[start];
+        out.push_str(&format!(
+            "{}\n",
+            format!(
+                "@@ -{},{} +{},{} @@",
+                old_start, old_count, new_start, new_count
+            )
+            .cyan()
+        ));
+        for change in hunk {
+            let line = match change {
+                Change::Same(line) => format!(" {}", line).normal(),
+                Change::Removed(line) => format!("-{}", line).red(),
+                Change::Added(line) => format!("+{}", line).green(),
+            };
+            out.push_str(&format!("{}\n", line));
+        }
+    }
+    out
+}
+
+/// `text` cut or padded to `width` characters
+fn column(text: &str, width: usize) -> String {
+    let text: String = text.chars().take(width).collect();
+    format!("{:<width$}", text)
+}
+
+/// `changes` in two columns as `diff --side-by-side` prints them: changed
+/// lines marked `|`, removed ones `<` and added ones `>`. `width` is that of
+/// the whole output, `$COLUMNS` or [`DEFAULT_WIDTH`] if `None`.
+pub fn render_side_by_side(changes: &[Change], width: Option<usize>) -> String {
+    let width = width
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(DEFAULT_WIDTH);
+    let half = width.saturating_sub(3) / 2;
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < changes.len() {
+        if let Change::Same(line) = changes[i] {
+            out.push_str(&format!("{}   {}\n", column(line, half), line));
+            i += 1;
+            continue;
+        }
+        // A run of removed lines followed by added ones is shown as changed pairs
+        let removed: Vec<&str> = changes[i..]
+            .iter()
+            .map_while(|c| match c {
+                Change::Removed(line) => Some(*line),
+                _ => None,
+            })
+            .collect();
+        let added: Vec<&str> = changes[i + removed.len()..]
+            .iter()
+            .map_while(|c| match c {
+                Change::Added(line) => Some(*line),
+                _ => None,
+            })
+            .collect();
+        i += removed.len() + added.len();
+        for k in 0..removed.len().max(added.len()) {
+            let line = match (removed.get(k), added.get(k)) {
+                (Some(old), Some(new)) => format!(
+                    "{} {} {}",
+                    column(old, half).red(),
+                    "|".yellow(),
+                    new.green()
+                ),
+                (Some(old), None) => format!("{} {}", column(old, half).red(), "<".red()),
+                (None, Some(new)) => {
+                    format!("{} {} {}", column("", half), ">".green(), new.green())
+                }
+                (None, None) => unreachable!(),
+            };
+            out.push_str(&format!("{}\n", line));
+        }
+    }
+    out
+}
+
+/// Runs `command` with the old and the new version as its last two
+/// arguments, named after `file_name` in directories `a` and `b` as git does.
+/// Exit codes 0 and 1, which diff tools use for "differences found", count
+/// as success.
+pub fn run_external(
+    command: &[String],
+    file_name: &str,
+    old: &[u8],
+    new: &[u8],
+) -> Result<(), String> {
+    let Some((program, args)) = command.split_first() else {
+        return Err("The diff renderer can't be empty".to_string());
+    };
+    let dir = tempfile::Builder::new()
+        .prefix("opsops-diff")
+        .tempdir()
+        .map_err(|e| format!("Failed to create a temporary directory: {}", e))?;
+    let mut paths = Vec::new();
+    for side in ["a", "b"] {
+        let side = dir.path().join(side);
+        fs::create_dir(&side).map_err(|e| format!("Failed to create {}: {}", side.display(), e))?;
+        paths.push(side.join(file_name));
+    }
+
+    let mut tool = Command::new(program);
+    tool.args(args).args(&paths);
+    let status = feed_inputs(&mut tool, [(&paths[0], old), (&paths[1], new)])
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    match status.code() {
+        Some(0 | 1) => Ok(()),
+        Some(code) => Err(format!("{} exited with {}", program, code)),
+        None => Err(format!("{} was terminated by a signal", program)),
+    }
+}
+
+/// Runs `tool` while writing each input into a named pipe at its path
+#[cfg(unix)]
+fn feed_inputs(
+    tool: &mut Command,
+    inputs: [(&Path, &[u8]); 2],
+) -> std::io::Result<std::process::ExitStatus> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    for (path, _) in &inputs {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    let exited = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        for (path, content) in inputs {
+            let exited = &exited;
+            scope.spawn(move || write_fifo(path, content, || exited.load(Ordering::Relaxed)));
+        }
+        let status = session_record::status(tool);
+        exited.store(true, Ordering::Relaxed);
+        status
+    })
+}
+
+/// Writes `content` into the named pipe at `path` once a reader opened it.
+/// Gives up when `exited` says the tool is gone without ever opening it.
+#[cfg(unix)]
+fn write_fifo(path: &Path, content: &[u8], exited: impl Fn() -> bool) {
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    // Opening for writing blocks until there is a reader, or fails with ENXIO
+    // when non-blocking, so poll instead of hanging on a tool that never reads
+    let file = loop {
+        match fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+        {
+            Ok(file) => break file,
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) && !exited() => {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            Err(_) => return,
+        }
+    };
+    unsafe {
+        let flags = libc::fcntl(file.as_raw_fd(), libc::F_GETFL);
+        libc::fcntl(file.as_raw_fd(), libc::F_SETFL, flags & !libc::O_NONBLOCK);
+    }
+    // Fails with a broken pipe if the tool stops reading early, which is its business
+    let _ = (&file).write_all(content);
+}
+
+/// Writes each input to its path and runs `tool`
+#[cfg(not(unix))]
+fn feed_inputs(
+    tool: &mut Command,
+    inputs: [(&Path, &[u8]); 2],
+) -> std::io::Result<std::process::ExitStatus> {
+    for (path, content) in inputs {
+        fs::write(path, content)?;
+    }
+    session_record::status(tool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, DiffRenderer, line_changes, render_inline, render_side_by_side};
+
+    #[test]
+    fn test_parse_renderer() {
+        assert_eq!(DiffRenderer::parse("inline"), Ok(DiffRenderer::Inline));
+        assert_eq!(
+            DiffRenderer::parse("side-by-side"),
+            Ok(DiffRenderer::SideBySide)
+        );
+        assert_eq!(
+            DiffRenderer::parse(" difft --display inline"),
+            Ok(DiffRenderer::External(vec![
+                "difft".to_string(),
+                "--display".to_string(),
+                "inline".to_string()
+            ]))
+        );
+        assert!(DiffRenderer::parse(" ").is_err());
+    }
+
+    #[test]
+    fn test_line_changes() {
+        assert_eq!(
+            line_changes("a\nb\nc\nd\n", "a\nc\nx\nd\n"),
+            vec![
+                Change::Same("a"),
+                Change::Removed("b"),
+                Change::Same("c"),
+                Change::Added("x"),
+                Change::Same("d"),
+            ]
+        );
+        assert_eq!(line_changes("", "a\n"), vec![Change::Added("a")]);
+        assert!(
+            line_changes("a\nb\n", "a\nb\n")
+                .iter()
+                .all(|c| matches!(c, Change::Same(_)))
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        colored::control::set_override(false);
+        let old: String = (1..=20).map(|i| format!("line{}\n", i)).collect();
+        let new = old.replace("line2\n", "line2b\n").replace("line18\n", "");
+        let changes = line_changes(&old, &new);
+        assert_eq!(
+            render_inline(&changes, "a/f", "b/f"),
+            "--- a/f\n+++ b/f\n\
+             @@ -1,5 +1,5 @@\n line1\n-line2\n+line2b\n line3\n line4\n line5\n\
+             @@ -15,6 +15,5 @@\n line15\n line16\n line17\n-line18\n line19\n line20\n"
+        );
+        assert_eq!(render_inline(&line_changes(&old, &old), "a", "b"), "");
+
+        let changes = line_changes("a\nb\nc\n", "a\nB\nc\nd\n");
+        assert_eq!(
+            render_side_by_side(&changes, Some(13)),
+            "a       a\nb     | B\nc       c\n      > d\n"
+        );
+    }
+}
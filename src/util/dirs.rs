@@ -0,0 +1,169 @@
+//! Where opsops keeps its files. User level directories follow the XDG base
+//! directory spec on Linux and the platform conventions on macOS and Windows.
+//! Explicitly set `XDG_*` variables are honored everywhere.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use super::platform::{Native, Platform};
+
+/// Name of the opsops directory inside each base directory
+const APP_DIR: &str = "opsops";
+
+/// Directory inside a project holding opsops state such as decrypted copies
+pub const PROJECT_STATE_DIR: &str = ".opsops";
+
+/// The user's home directory
+pub fn home() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+/// A base directory from the environment. The XDG spec says relative paths are
+/// invalid and must be ignored.
+fn valid_base(value: Option<OsString>) -> Option<PathBuf> {
+    value.map(PathBuf::from).filter(|p| p.is_absolute())
+}
+
+fn xdg(var: &str) -> Option<PathBuf> {
+    valid_base(env::var_os(var))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_config_dir() -> Option<PathBuf> {
+    let home = home()?;
+    // Versions before util::dirs used ~/.config on macOS as well, keep finding it
+    let legacy = home.join(".config").join(APP_DIR);
+    if legacy.is_dir() {
+        return Some(legacy);
+    }
+    Some(home.join("Library/Application Support").join(APP_DIR))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_cache_dir() -> Option<PathBuf> {
+    Some(home()?.join("Library/Caches").join(APP_DIR))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_state_dir() -> Option<PathBuf> {
+    Some(home()?.join("Library/Application Support").join(APP_DIR))
+}
+
+#[cfg(windows)]
+fn platform_config_dir() -> Option<PathBuf> {
+    Some(PathBuf::from(env::var_os("APPDATA")?).join(APP_DIR))
+}
+
+#[cfg(windows)]
+fn platform_cache_dir() -> Option<PathBuf> {
+    Some(
+        PathBuf::from(env::var_os("LOCALAPPDATA")?)
+            .join(APP_DIR)
+            .join("cache"),
+    )
+}
+
+#[cfg(windows)]
+fn platform_state_dir() -> Option<PathBuf> {
+    Some(
+        PathBuf::from(env::var_os("LOCALAPPDATA")?)
+            .join(APP_DIR)
+            .join("state"),
+    )
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn platform_config_dir() -> Option<PathBuf> {
+    Some(home()?.join(".config").join(APP_DIR))
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn platform_cache_dir() -> Option<PathBuf> {
+    Some(home()?.join(".cache").join(APP_DIR))
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn platform_state_dir() -> Option<PathBuf> {
+    Some(home()?.join(".local/state").join(APP_DIR))
+}
+
+/// Per-user settings: `$XDG_CONFIG_HOME/opsops`, `~/.config/opsops` on Linux,
+/// `~/Library/Application Support/opsops` on macOS
+pub fn config_dir() -> Option<PathBuf> {
+    xdg("XDG_CONFIG_HOME")
+        .map(|d| d.join(APP_DIR))
+        .or_else(platform_config_dir)
+}
+
+/// Data that can be recreated at any time: `$XDG_CACHE_HOME/opsops`,
+/// `~/.cache/opsops` on Linux, `~/Library/Caches/opsops` on macOS
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg("XDG_CACHE_HOME")
+        .map(|d| d.join(APP_DIR))
+        .or_else(platform_cache_dir)
+}
+
+/// Data worth keeping but not worth syncing, such as logs: `$XDG_STATE_HOME/opsops`,
+/// `~/.local/state/opsops` on Linux, `~/Library/Application Support/opsops` on macOS
+pub fn state_dir() -> Option<PathBuf> {
+    xdg("XDG_STATE_HOME")
+        .map(|d| d.join(APP_DIR))
+        .or_else(platform_state_dir)
+}
+
+/// Sockets and other files that only live as long as the session:
+/// `$XDG_RUNTIME_DIR/opsops`, else `<tmp>/opsops-<uid>`, or `<tmp>/opsops`
+/// where the temporary directory is per user anyway
+pub fn runtime_dir() -> PathBuf {
+    match (xdg("XDG_RUNTIME_DIR"), Native::effective_uid()) {
+        (Some(dir), _) => dir.join(APP_DIR),
+        (None, Some(uid)) => env::temp_dir().join(format!("{}-{}", APP_DIR, uid)),
+        (None, None) => env::temp_dir().join(APP_DIR),
+    }
+}
+
+/// `$XDG_CONFIG_HOME`, else `~/.config`, where shells such as fish look for
+/// their files on every platform
+pub fn xdg_config_home() -> Option<PathBuf> {
+    xdg("XDG_CONFIG_HOME").or_else(|| Some(home()?.join(".config")))
+}
+
+/// `$XDG_DATA_HOME`, else `~/.local/share`
+pub fn xdg_data_home() -> Option<PathBuf> {
+    xdg("XDG_DATA_HOME").or_else(|| Some(home()?.join(".local/share")))
+}
+
+/// The opsops state directory of the project at `root`
+pub fn project_state_dir(root: &Path) -> PathBuf {
+    root.join(PROJECT_STATE_DIR)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::path::{Path, PathBuf};
+
+    use super::{project_state_dir, valid_base};
+
+    #[test]
+    fn test_valid_base() {
+        assert_eq!(
+            valid_base(Some(OsString::from("/home/me/.config"))),
+            Some(PathBuf::from("/home/me/.config"))
+        );
+        assert_eq!(valid_base(Some(OsString::from(""))), None);
+        assert_eq!(valid_base(Some(OsString::from("relative/config"))), None);
+        assert_eq!(valid_base(None), None);
+    }
+
+    #[test]
+    fn test_project_state_dir() {
+        assert_eq!(
+            project_state_dir(Path::new("/repo")),
+            PathBuf::from("/repo/.opsops")
+        );
+    }
+}
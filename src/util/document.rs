@@ -0,0 +1,238 @@
+//! Decrypted documents as values, for commands that post-process what sops
+//! returns instead of passing it through. Key paths use the same `a.b[0].c`
+//! notation as [`super::decrypted_copies::structural_diff`].
+
+use serde_yaml::{Mapping, Value};
+
+/// What `--redact` puts in place of every value
+pub const REDACTED: &str = "<redacted>";
+
+/// Parses decrypted content of the given sops file type, see
+/// [`super::sops_files::sops_file_type`]
+pub fn parse_document(content: &[u8], file_type: &str) -> Result<Value, String> {
+    match file_type {
+        "yaml" | "json" => serde_yaml::from_slice(content)
+            .map_err(|e| format!("Failed to parse decrypted {}: {}", file_type, e)),
+        "dotenv" => Ok(parse_dotenv(&String::from_utf8_lossy(content))),
+        other => Err(format!(
+            "Only YAML, JSON and dotenv files can be post-processed, not {}",
+            other
+        )),
+    }
+}
+
+fn parse_dotenv(content: &str) -> Value {
+    let mut map = Mapping::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            map.insert(key.trim().into(), value.into());
+        }
+    }
+    Value::Mapping(map)
+}
+
+/// Splits `a.b[0].c` into keys and indices
+fn parse_key_path(path: &str) -> Result<Vec<Value>, String> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(i) => part.split_at(i),
+            None => (part, ""),
+        };
+        if !key.is_empty() {
+            segments.push(Value::from(key));
+        }
+        while let Some(stripped) = rest.strip_prefix('[') {
+            let (index, tail) = stripped
+                .split_once(']')
+                .ok_or_else(|| format!("Unclosed '[' in key path '{}'", path))?;
+            let index: u64 = index
+                .parse()
+                .map_err(|_| format!("Invalid index '{}' in key path '{}'", index, path))?;
+            segments.push(Value::from(index));
+            rest = tail;
+        }
+        if !rest.is_empty() || (key.is_empty() && part.is_empty()) {
+            return Err(format!("Invalid key path '{}'", path));
+        }
+    }
+    Ok(segments)
+}
+
+/// The value at `path`, e.g. `data.password` or `users[0].name`
+pub fn extract<'a>(document: &'a Value, path: &str) -> Result<&'a Value, String> {
+    let mut current = document;
+    for segment in parse_key_path(path)? {
+        let next = match (&segment, current) {
+            (Value::Number(n), Value::Sequence(items)) => {
+                n.as_u64().and_then(|i| items.get(i as usize))
+            }
+            (key, Value::Mapping(map)) => map.get(key),
+            _ => None,
+        };
+        current = next.ok_or_else(|| format!("Key path '{}' not found", path))?;
+    }
+    Ok(current)
+}
+
+/// Puts `value` at `path`, creating the mappings on the way, and returns the
+/// value it replaced. Indices have to exist, or point one past the end of a
+/// sequence to append.
+pub fn insert(document: &mut Value, path: &str, value: Value) -> Result<Option<Value>, String> {
+    let mut segments = parse_key_path(path)?;
+    let last = segments
+        .pop()
+        .ok_or_else(|| format!("Invalid key path '{}'", path))?;
+    let mut current = document;
+    for segment in segments {
+        if current.is_null() {
+            *current = Value::Mapping(Mapping::new());
+        }
+        current = match (segment, current) {
+            (Value::Number(n), Value::Sequence(items)) => n
+                .as_u64()
+                .and_then(|i| items.get_mut(i as usize))
+                .ok_or_else(|| format!("Key path '{}' not found", path))?,
+            (key, Value::Mapping(map)) => map
+                .entry(key)
+                .or_insert_with(|| Value::Mapping(Mapping::new())),
+            _ => return Err(format!("Key path '{}' runs into a value", path)),
+        };
+    }
+    if current.is_null() {
+        *current = Value::Mapping(Mapping::new());
+    }
+    match (last, current) {
+        (Value::Number(n), Value::Sequence(items)) => match n.as_u64().map(|i| i as usize) {
+            Some(i) if i < items.len() => Ok(Some(std::mem::replace(&mut items[i], value))),
+            Some(i) if i == items.len() => {
+                items.push(value);
+                Ok(None)
+            }
+            _ => Err(format!("Key path '{}' not found", path)),
+        },
+        (key, Value::Mapping(map)) => Ok(map.insert(key, value)),
+        _ => Err(format!("Key path '{}' runs into a value", path)),
+    }
+}
+
+/// Replaces every value with [`REDACTED`], keeping keys and structure
+pub fn redact(document: &mut Value) {
+    match document {
+        Value::Mapping(map) => map.values_mut().for_each(redact),
+        Value::Sequence(items) => items.iter_mut().for_each(redact),
+        Value::Tagged(tagged) => redact(&mut tagged.value),
+        Value::Null => {}
+        scalar => *scalar = REDACTED.into(),
+    }
+}
+
+/// A scalar as a user would write it, without YAML quoting
+pub fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Null => Some(String::new()),
+        _ => None,
+    }
+}
+
+/// Renders `value` in the file type it came from. Scalars are printed bare, so
+/// `--extract` output can be used directly in scripts.
+pub fn render_document(value: &Value, file_type: &str) -> Result<String, String> {
+    if let Some(text) = scalar_text(value) {
+        return Ok(text);
+    }
+    match file_type {
+        "json" => serde_json::to_string_pretty(value)
+            .map_err(|e| format!("Failed to serialize JSON: {}", e)),
+        "dotenv" => match value {
+            Value::Mapping(map) => Ok(map
+                .iter()
+                .map(|(k, v)| {
+                    format!(
+                        "{}={}",
+                        scalar_text(k).unwrap_or_default(),
+                        scalar_text(v).unwrap_or_default()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")),
+            _ => Err("Only flat documents can be written as dotenv".to_string()),
+        },
+        _ => serde_yaml::to_string(value)
+            .map(|s| s.trim_end().to_string())
+            .map_err(|e| format!("Failed to serialize YAML: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{REDACTED, extract, insert, parse_document, redact, render_document};
+
+    const DOCUMENT: &str =
+        "data:\n  password: hunter2\n  port: 5432\nusers:\n- name: alice\n- name: bob\n";
+
+    #[test]
+    fn test_extract_key_paths() {
+        let doc = parse_document(DOCUMENT.as_bytes(), "yaml").unwrap();
+        assert_eq!(extract(&doc, "data.password").unwrap(), "hunter2");
+        assert_eq!(extract(&doc, "users[1].name").unwrap(), "bob");
+        assert!(extract(&doc, "data.missing").is_err());
+        assert!(extract(&doc, "users[5]").is_err());
+        assert!(extract(&doc, "users[x]").is_err());
+        assert!(extract(&doc, "data..password").is_err());
+    }
+
+    #[test]
+    fn test_insert_creates_mappings() {
+        let mut doc = parse_document(DOCUMENT.as_bytes(), "yaml").unwrap();
+        let previous = insert(&mut doc, "data.password", "swordfish".into()).unwrap();
+        assert_eq!(previous.unwrap(), "hunter2");
+        assert!(
+            insert(&mut doc, "db.primary.password", "x".into())
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(extract(&doc, "db.primary.password").unwrap(), "x");
+        insert(&mut doc, "users[2]", "carol".into()).unwrap();
+        assert_eq!(extract(&doc, "users[2]").unwrap(), "carol");
+        assert!(insert(&mut doc, "users[7]", "dave".into()).is_err());
+        assert!(insert(&mut doc, "data.port.number", "1".into()).is_err());
+    }
+
+    #[test]
+    fn test_redact_keeps_structure() {
+        let mut doc = parse_document(DOCUMENT.as_bytes(), "yaml").unwrap();
+        redact(&mut doc);
+        assert_eq!(extract(&doc, "data.port").unwrap(), REDACTED);
+        assert_eq!(extract(&doc, "users[0].name").unwrap(), REDACTED);
+        assert!(!render_document(&doc, "yaml").unwrap().contains("hunter2"));
+    }
+
+    #[test]
+    fn test_render_in_source_format() {
+        let doc = parse_document(b"# comment\nTOKEN=abc=def\nPORT=80\n", "dotenv").unwrap();
+        assert_eq!(extract(&doc, "TOKEN").unwrap(), "abc=def");
+        assert_eq!(
+            render_document(&doc, "dotenv").unwrap(),
+            "TOKEN=abc=def\nPORT=80"
+        );
+
+        let doc = parse_document(br#"{"a": {"b": 1}}"#, "json").unwrap();
+        assert_eq!(
+            render_document(&doc, "json").unwrap(),
+            "{\n  \"a\": {\n    \"b\": 1\n  }\n}"
+        );
+        assert_eq!(
+            render_document(extract(&doc, "a.b").unwrap(), "json").unwrap(),
+            "1"
+        );
+        assert!(parse_document(b"\x00\x01", "binary").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Composite documents assembled from other files with `--resolve-includes`:
+//!
+//! ```yaml
+//! database: !opsops-include secrets/db.yaml
+//! ```
+//!
+//! or, in JSON, a mapping whose only key is `$opsopsInclude`:
+//!
+//! ```json
+//! {"database": {"$opsopsInclude": "secrets/db.json"}}
+//! ```
+//!
+//! Both are replaced by the content of the file, decrypted in memory if it is
+//! encrypted. Paths are relative to the including file, which may itself be
+//! plaintext. Included files may include others, but not one that is already
+//! being included.
+
+use age::secrecy::SecretString;
+use serde_yaml::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{
+    document::parse_document,
+    op_key::get_age_key_from_1password,
+    sops_command::decrypt_in_memory,
+    sops_files::{is_sops_encrypted_file, sops_file_type},
+};
+use crate::GlobalContext;
+
+/// YAML tag of an include, its value is the path
+pub const INCLUDE_TAG: &str = "!opsops-include";
+
+/// Key of a mapping standing for the file at its value
+pub const INCLUDE_KEY: &str = "$opsopsInclude";
+
+/// The path `node` includes, `None` if it isn't an include
+fn include_target(node: &Value) -> Result<Option<&str>, String> {
+    match node {
+        Value::Tagged(tagged) if tagged.tag == INCLUDE_TAG => match &tagged.value {
+            Value::String(path) => Ok(Some(path)),
+            _ => Err(format!("{} takes a path", INCLUDE_TAG)),
+        },
+        Value::Mapping(map) => match map.get(INCLUDE_KEY) {
+            None => Ok(None),
+            Some(_) if map.len() > 1 => Err(format!(
+                "{} has to be the only key of its mapping",
+                INCLUDE_KEY
+            )),
+            Some(Value::String(path)) => Ok(Some(path)),
+            Some(_) => Err(format!("{} takes a path", INCLUDE_KEY)),
+        },
+        _ => Ok(None),
+    }
+}
+
+fn resolve<F>(
+    node: &mut Value,
+    dir: &Path,
+    load: &mut F,
+    chain: &mut Vec<PathBuf>,
+) -> Result<(), String>
+where
+    F: FnMut(&Path) -> Result<Value, String>,
+{
+    if let Some(target) = include_target(node)? {
+        let path = dir.join(target);
+        let canonical = fs::canonicalize(&path)
+            .map_err(|e| format!("Cannot include {}: {}", path.display(), e))?;
+        if chain.contains(&canonical) {
+            return Err(format!("{} includes itself", path.display()));
+        }
+        let mut included = load(&path)?;
+        chain.push(canonical);
+        resolve(
+            &mut included,
+            path.parent().unwrap_or(Path::new("")),
+            load,
+            chain,
+        )?;
+        chain.pop();
+        *node = included;
+        return Ok(());
+    }
+    match node {
+        Value::Mapping(map) => {
+            for (_, value) in map.iter_mut() {
+                resolve(value, dir, load, chain)?;
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                resolve(item, dir, load, chain)?;
+            }
+        }
+        Value::Tagged(tagged) => resolve(&mut tagged.value, dir, load, chain)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replaces the includes in `document`, the content of `file`, with what
+/// `load` returns for the included paths
+pub fn resolve_includes<F>(document: &mut Value, file: &Path, load: &mut F) -> Result<(), String>
+where
+    F: FnMut(&Path) -> Result<Value, String>,
+{
+    let canonical =
+        fs::canonicalize(file).map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+    let dir = file.parent().unwrap_or(Path::new(""));
+    resolve(document, dir, load, &mut vec![canonical])
+}
+
+/// Reads `file` with its includes resolved. Encrypted files are decrypted in
+/// memory, 1Password is asked for the key once, if at all.
+pub fn read_with_includes(file: &Path, context: &GlobalContext) -> Result<Value, String> {
+    let mut key: Option<SecretString> = None;
+    let mut load = |path: &Path| -> Result<Value, String> {
+        let file_type = sops_file_type(path);
+        if !is_sops_encrypted_file(path) {
+            let content =
+                fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            return parse_document(&content, file_type);
+        }
+        if key.is_none() {
+            key = Some(get_age_key_from_1password(context)?);
+        }
+        let content = decrypt_in_memory(path, key.as_ref(), context)
+            .map_err(|e| format!("Failed to decrypt {}: {}", path.display(), e))?;
+        parse_document(&content, file_type)
+    };
+    let mut document = load(file)?;
+    resolve_includes(&mut document, file, &mut load)?;
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::Value;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    use super::resolve_includes;
+
+    fn load(path: &Path) -> Result<Value, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_yaml::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    fn resolved(dir: &TempDir, name: &str) -> Result<Value, String> {
+        let file = dir.path().join(name);
+        let mut document = load(&file)?;
+        resolve_includes(&mut document, &file, &mut load).map(|_| document)
+    }
+
+    #[test]
+    fn test_resolves_nested_includes() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("secrets")).unwrap();
+        fs::write(
+            dir.path().join("app.yaml"),
+            "name: app\ndb: !opsops-include secrets/db.yaml\nextra:\n- {\"$opsopsInclude\": secrets/api.json}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("secrets/db.yaml"),
+            "password: hunter2\ntls: !opsops-include tls.yaml\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("secrets/tls.yaml"), "cert: abc\n").unwrap();
+        fs::write(dir.path().join("secrets/api.json"), r#"{"token": "t0k"}"#).unwrap();
+
+        let document = resolved(&dir, "app.yaml").unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&document).unwrap(),
+            "name: app\ndb:\n  password: hunter2\n  tls:\n    cert: abc\nextra:\n- token: t0k\n"
+        );
+    }
+
+    #[test]
+    fn test_rejects_cycles_and_malformed_includes() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.yaml"), "b: !opsops-include b.yaml\n").unwrap();
+        fs::write(dir.path().join("b.yaml"), "a: !opsops-include a.yaml\n").unwrap();
+        assert!(
+            resolved(&dir, "a.yaml")
+                .unwrap_err()
+                .contains("includes itself")
+        );
+
+        fs::write(
+            dir.path().join("c.yaml"),
+            "x:\n  $opsopsInclude: b.yaml\n  other: 1\n",
+        )
+        .unwrap();
+        assert!(resolved(&dir, "c.yaml").unwrap_err().contains("only key"));
+
+        fs::write(
+            dir.path().join("d.yaml"),
+            "x: !opsops-include missing.yaml\n",
+        )
+        .unwrap();
+        assert!(
+            resolved(&dir, "d.yaml")
+                .unwrap_err()
+                .contains("missing.yaml")
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Drift between encrypted files and the creation rules they'd be encrypted
+//! with today: sops records the recipients and which keys it encrypted in each
+//! file's metadata, and nothing updates that when `.sops.yaml` changes.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::{
+    escrow::rule_recipients,
+    sops_metadata::{SELECTOR_OPTIONS, SopsMetadata},
+    sops_structs::CreationRule,
+};
+
+/// What sops records when a rule sets none of the selector options
+const DEFAULT_SELECTOR: (&str, &str) = ("unencrypted_suffix", "_unencrypted");
+
+/// A difference between a file's metadata and its rule
+#[derive(Debug, PartialEq)]
+pub enum Drift {
+    /// No creation rule matches the file anymore
+    NoRule,
+    /// The rule's age recipients differ from the ones the file is encrypted to.
+    /// `sops updatekeys` fixes this.
+    Recipients {
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+    /// The file was encrypted with a different key selection. Only
+    /// re-encrypting fixes this.
+    Selector {
+        option: &'static str,
+        file: Option<String>,
+        rule: Option<String>,
+    },
+}
+
+impl Drift {
+    /// Whether `sops updatekeys` resolves it
+    pub fn fixed_by_updatekeys(&self) -> bool {
+        matches!(self, Drift::Recipients { .. })
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::NoRule => write!(f, "no creation rule matches it anymore"),
+            Drift::Recipients { missing, extra } => {
+                let mut parts = Vec::new();
+                if !missing.is_empty() {
+                    parts.push(format!("not yet encrypted to {}", missing.join(", ")));
+                }
+                if !extra.is_empty() {
+                    parts.push(format!("still encrypted to {}", extra.join(", ")));
+                }
+                write!(f, "recipients: {}", parts.join("; "))
+            }
+            Drift::Selector { option, file, rule } => write!(
+                f,
+                "{}: file has {}, rule has {}",
+                option,
+                file.as_deref().unwrap_or("none"),
+                rule.as_deref().unwrap_or("none")
+            ),
+        }
+    }
+}
+
+/// The selector options sops would record for a file encrypted with `rule`
+fn rule_selector(rule: &CreationRule) -> Vec<(&'static str, String)> {
+    let options: Vec<(&'static str, String)> = [
+        ("encrypted_regex", &rule.encrypted_regex),
+        ("unencrypted_regex", &rule.unencrypted_regex),
+        ("encrypted_suffix", &rule.encrypted_suffix),
+        ("unencrypted_suffix", &rule.unencrypted_suffix),
+        ("encrypted_comment_regex", &rule.encrypted_comment_regex),
+        ("unencrypted_comment_regex", &rule.unencrypted_comment_regex),
+    ]
+    .into_iter()
+    .filter_map(|(option, value)| Some((option, value.clone()?)))
+    .collect();
+
+    let mut options = if options.is_empty() {
+        vec![(DEFAULT_SELECTOR.0, DEFAULT_SELECTOR.1.to_string())]
+    } else {
+        options
+    };
+    if rule.mac_only_encrypted == Some(true) {
+        options.push(("mac_only_encrypted", "true".to_string()));
+    }
+    options
+}
+
+/// Compares the metadata of an encrypted file with `rule`, the rule that
+/// matches the file today
+pub fn file_drift(metadata: &SopsMetadata, rule: Option<&CreationRule>) -> Vec<Drift> {
+    let Some(rule) = rule else {
+        return vec![Drift::NoRule];
+    };
+    let mut drift = Vec::new();
+
+    let expected: BTreeSet<String> = rule_recipients(rule).into_iter().collect();
+    let actual: BTreeSet<String> = metadata.age_recipients().into_iter().collect();
+    let missing: Vec<String> = expected.difference(&actual).cloned().collect();
+    let extra: Vec<String> = actual.difference(&expected).cloned().collect();
+    if !missing.is_empty() || !extra.is_empty() {
+        drift.push(Drift::Recipients { missing, extra });
+    }
+
+    let recorded = metadata.selector();
+    let wanted = rule_selector(rule);
+    for option in SELECTOR_OPTIONS {
+        let get = |options: &[(&str, String)]| {
+            options
+                .iter()
+                .find(|(o, _)| *o == option)
+                .map(|(_, v)| v.clone())
+        };
+        let (file, rule) = (get(&recorded), get(&wanted));
+        // sops doesn't record mac_only_encrypted: false
+        let is_false = |v: &Option<String>| v.as_deref().is_none_or(|v| v == "false");
+        if option == "mac_only_encrypted" && is_false(&file) && is_false(&rule) {
+            continue;
+        }
+        if file != rule {
+            drift.push(Drift::Selector { option, file, rule });
+        }
+    }
+    drift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Drift, file_drift};
+    use crate::util::sops_metadata::read_metadata;
+    use crate::util::sops_structs::CreationRule;
+
+    const FILE: &str = "data: ENC[...]\nsops:\n    age:\n        - recipient: age1me\n          enc: x\n        - recipient: age1old\n          enc: y\n    encrypted_regex: ^data$\n    mac: ENC[...]\n";
+
+    fn rule(age: &str, encrypted_regex: Option<&str>) -> CreationRule {
+        CreationRule {
+            path_regex: Some(".*".to_string()),
+            age: Some(age.to_string()),
+            encrypted_regex: encrypted_regex.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_no_drift() {
+        let rule = rule("age1me,age1old", Some("^data$"));
+        assert!(file_drift(&read_metadata(FILE).unwrap(), Some(&rule)).is_empty());
+    }
+
+    #[test]
+    fn test_recipient_and_selector_drift() {
+        let rule = rule("age1me, age1new", Some("^(data|stringData)$"));
+        let drift = file_drift(&read_metadata(FILE).unwrap(), Some(&rule));
+        assert_eq!(
+            drift,
+            vec![
+                Drift::Recipients {
+                    missing: vec!["age1new".to_string()],
+                    extra: vec!["age1old".to_string()],
+                },
+                Drift::Selector {
+                    option: "encrypted_regex",
+                    file: Some("^data$".to_string()),
+                    rule: Some("^(data|stringData)$".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            drift[0].to_string(),
+            "recipients: not yet encrypted to age1new; still encrypted to age1old"
+        );
+        assert!(drift[0].fixed_by_updatekeys());
+        assert!(!drift[1].fixed_by_updatekeys());
+    }
+
+    #[test]
+    fn test_default_selector() {
+        let dotenv = "A=ENC[...]\nsops_age__list_0__map_recipient=age1me\nsops_unencrypted_suffix=_unencrypted\nsops_mac=x\n";
+        let rule = rule("age1me", None);
+        let metadata = read_metadata(dotenv).unwrap();
+        assert!(file_drift(&metadata, Some(&rule)).is_empty());
+        assert_eq!(file_drift(&metadata, None), vec![Drift::NoRule]);
+    }
+}
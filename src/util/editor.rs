@@ -0,0 +1,142 @@
+//! The editor `edit` opens decrypted files in, and where the plaintext lives
+//! meanwhile. sops writes it to a temporary directory on the main disk unless
+//! told otherwise, so users can move it onto a RAM backed filesystem and cut
+//! the editor off from the network.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::session_record;
+use super::user_config::read_user_config;
+
+/// `--tmpdir` value that picks a RAM backed directory
+pub const TMPFS: &str = "tmpfs";
+
+/// Runs the editor in new user and network namespaces, i.e. without network
+const UNSHARE_ARGS: [&str; 3] = ["--user", "--map-root-user", "--net"];
+
+/// How `edit` runs the editor
+#[derive(Debug, PartialEq)]
+pub struct EditorSettings {
+    /// Where the plaintext is written while editing, the system default if `None`
+    pub tmpdir: Option<PathBuf>,
+    /// Editor command line, possibly wrapped in `unshare`
+    pub editor: String,
+}
+
+/// The editor sops would use
+pub fn editor_command() -> String {
+    std::env::var("SOPS_EDITOR")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vim".to_string())
+}
+
+/// Combines `--tmpdir`/`--sandbox` with `edit_tmpdir`/`sandbox_editor` from
+/// the user config
+pub fn editor_settings(tmpdir: Option<String>, sandbox: bool) -> Result<EditorSettings, String> {
+    let user_config = read_user_config();
+    let tmpdir = match tmpdir.or(user_config.edit_tmpdir) {
+        Some(setting) => Some(resolve_tmpdir(&setting)?),
+        None => None,
+    };
+    let editor = editor_command();
+    let editor = if sandbox || user_config.sandbox_editor {
+        sandboxed(&editor)?
+    } else {
+        editor
+    };
+    Ok(EditorSettings { tmpdir, editor })
+}
+
+/// Resolves `tmpfs` to a RAM backed directory, anything else is used as is
+pub fn resolve_tmpdir(setting: &str) -> Result<PathBuf, String> {
+    if setting != TMPFS {
+        let path = PathBuf::from(setting);
+        if !path.is_dir() {
+            return Err(format!("Temporary directory {} doesn't exist", setting));
+        }
+        return Ok(path);
+    }
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain([PathBuf::from("/dev/shm")])
+        .find(|dir| dir.is_dir() && is_ram_backed(dir))
+        .ok_or_else(|| {
+            "No RAM backed directory found ($XDG_RUNTIME_DIR or /dev/shm), pass one with --tmpdir"
+                .to_string()
+        })
+}
+
+#[cfg(target_os = "linux")]
+fn is_ram_backed(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    const TMPFS_MAGIC: i64 = 0x0102_1994;
+    const RAMFS_MAGIC: i64 = 0x8584_58f6;
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    let kind = stat.f_type as i64;
+    kind == TMPFS_MAGIC || kind == RAMFS_MAGIC
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_ram_backed(_dir: &Path) -> bool {
+    false
+}
+
+/// Wraps `editor` in `unshare` so it has no network access, if the system
+/// allows unprivileged user namespaces
+fn sandboxed(editor: &str) -> Result<String, String> {
+    let unshare = which::which("unshare")
+        .map_err(|_| "The editor sandbox needs 'unshare', which is not installed".to_string())?;
+    let works = session_record::status(
+        Command::new(&unshare)
+            .args(UNSHARE_ARGS)
+            .arg("true")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+    )
+    .is_ok_and(|s| s.success());
+    if !works {
+        return Err("The editor sandbox needs unprivileged user namespaces, which this system doesn't allow".to_string());
+    }
+    Ok(format!(
+        "{} {} -- {}",
+        unshare.display(),
+        UNSHARE_ARGS.join(" "),
+        editor
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::resolve_tmpdir;
+
+    #[test]
+    fn test_resolve_tmpdir() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().to_str().unwrap();
+        assert_eq!(resolve_tmpdir(path).unwrap(), dir.path());
+        assert!(resolve_tmpdir(&format!("{}/missing", path)).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_dev_shm_is_ram_backed() {
+        let shm = std::path::Path::new("/dev/shm");
+        if shm.is_dir() {
+            assert!(super::is_ram_backed(shm));
+        }
+        assert!(!super::is_ram_backed(std::path::Path::new(
+            "/definitely/missing"
+        )));
+    }
+}
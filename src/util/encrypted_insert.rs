@@ -0,0 +1,104 @@
+//! Writing values into an encrypted file without its plaintext touching the
+//! disk, for commands that bring secrets in from elsewhere (`cp`, `import op`).
+//! The file is decrypted in memory, the values are put at their key paths and
+//! the document is encrypted again under the file's creation rule.
+
+use age::secrecy::SecretString;
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use super::{
+    backups::backup_ciphertext,
+    document::{extract, insert, parse_document, render_document},
+    escrow::missing_escrow_reason,
+    file_lock::lock_file,
+    find_project_root::find_project_root,
+    sops_command::{decrypt_in_memory, run_sops_on_buffer},
+    sops_files::{is_sops_encrypted_file, sops_file_type},
+};
+use crate::GlobalContext;
+
+/// Fails unless decrypted `file` can be parsed and written back by opsops
+pub fn check_post_processable(file: &Path) -> Result<(), String> {
+    match sops_file_type(file) {
+        "yaml" | "json" | "dotenv" => Ok(()),
+        other => Err(format!(
+            "Only YAML, JSON and dotenv files can hold copied values, {} is {}",
+            file.display(),
+            other
+        )),
+    }
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !matches!(value, Value::Mapping(_) | Value::Sequence(_))
+}
+
+/// Puts each `(key path, value)` of `values` into the encrypted `file`, creating
+/// it if it doesn't exist. Values that already exist are only replaced with
+/// `force`. Returns whether the file was created.
+pub fn insert_values(
+    file: &Path,
+    values: Vec<(String, Value)>,
+    force: bool,
+    key: &SecretString,
+    context: &GlobalContext,
+) -> Result<bool, String> {
+    check_post_processable(file)?;
+    let file_type = sops_file_type(file);
+    let exists = file.exists();
+    if exists && !is_sops_encrypted_file(file) {
+        return Err(format!(
+            "{} is not an encrypted file, refusing to write secrets into it",
+            file.display()
+        ));
+    }
+    if !exists && let Some(reason) = missing_escrow_reason(file, context) {
+        return Err(format!("Refusing to create {}: {}", file.display(), reason));
+    }
+
+    // An edit session of the file would overwrite the inserted values
+    let _lock = match exists {
+        true => Some(lock_file(file, false)?.0),
+        false => None,
+    };
+    let mut document = if exists {
+        let content = decrypt_in_memory(file, Some(key), context)
+            .map_err(|e| format!("Failed to decrypt {}: {}", file.display(), e))?;
+        parse_document(&content, file_type)?
+    } else {
+        Value::Mapping(Mapping::new())
+    };
+    for (path, value) in values {
+        if file_type == "dotenv" && (path.contains(['.', '[']) || !is_scalar(&value)) {
+            return Err(format!(
+                "dotenv files only hold top level values, can't write {} into {}",
+                path,
+                file.display()
+            ));
+        }
+        if !force && extract(&document, &path).is_ok() {
+            return Err(format!(
+                "{}#{} already exists, pass --force to overwrite it",
+                file.display(),
+                path
+            ));
+        }
+        insert(&mut document, &path, value).map_err(|e| format!("{}: {}", file.display(), e))?;
+    }
+
+    let plaintext = Zeroizing::new(render_document(&document, file_type)? + "\n");
+    let ciphertext = run_sops_on_buffer("--encrypt", file_type, file, &plaintext, context)
+        .map_err(|e| format!("Failed to encrypt {}: {}", file.display(), e))?;
+    if exists && let Some(root) = find_project_root(context) {
+        backup_ciphertext(&root, file, context)?;
+    } else if let Some(dir) = file.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    fs::write(file, ciphertext)
+        .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+    Ok(!exists)
+}
@@ -0,0 +1,428 @@
+//! Which values of a document sops encrypts under a creation rule, following its
+//! `encrypted_regex`, `unencrypted_regex`, `encrypted_suffix` and
+//! `unencrypted_suffix`. A key matching one of them decides for everything below
+//! it, just like in sops.
+
+use colored::Colorize;
+use regex::Regex;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+
+use super::{
+    decrypted_copies::flatten,
+    i18n::{tr, tr_args},
+    sops_structs::CreationRule,
+};
+
+/// What sops leaves in plaintext when a rule doesn't say otherwise
+const DEFAULT_UNENCRYPTED_SUFFIX: &str = "_unencrypted";
+
+/// The part of a creation rule that selects values to encrypt
+#[derive(Debug)]
+pub enum KeySelector {
+    EncryptedRegex(Regex),
+    UnencryptedRegex(Regex),
+    EncryptedSuffix(String),
+    UnencryptedSuffix(String),
+}
+
+impl KeySelector {
+    /// sops allows only one of the four options per rule
+    pub fn from_rule(rule: &CreationRule) -> Result<Self, String> {
+        let regex = |pattern: &str| {
+            Regex::new(pattern).map_err(|e| format!("Invalid regex '{}': {}", pattern, e))
+        };
+        let mut selectors = Vec::new();
+        if let Some(pattern) = &rule.encrypted_regex {
+            selectors.push(KeySelector::EncryptedRegex(regex(pattern)?));
+        }
+        if let Some(pattern) = &rule.unencrypted_regex {
+            selectors.push(KeySelector::UnencryptedRegex(regex(pattern)?));
+        }
+        if let Some(suffix) = &rule.encrypted_suffix {
+            selectors.push(KeySelector::EncryptedSuffix(suffix.clone()));
+        }
+        if let Some(suffix) = &rule.unencrypted_suffix {
+            selectors.push(KeySelector::UnencryptedSuffix(suffix.clone()));
+        }
+        match selectors.len() {
+            0 => Ok(KeySelector::UnencryptedSuffix(
+                DEFAULT_UNENCRYPTED_SUFFIX.to_string(),
+            )),
+            1 => Ok(selectors.remove(0)),
+            _ => Err(
+                "Only one of encrypted_regex, unencrypted_regex, encrypted_suffix and unencrypted_suffix can be set per rule"
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// Whether a value below the keys in `path` gets encrypted
+    pub fn is_encrypted(&self, path: &[String]) -> bool {
+        match self {
+            KeySelector::EncryptedRegex(regex) => path.iter().any(|k| regex.is_match(k)),
+            KeySelector::UnencryptedRegex(regex) => !path.iter().any(|k| regex.is_match(k)),
+            KeySelector::EncryptedSuffix(suffix) => path.iter().any(|k| k.ends_with(suffix)),
+            KeySelector::UnencryptedSuffix(suffix) => !path.iter().any(|k| k.ends_with(suffix)),
+        }
+    }
+}
+
+/// One line of the preview tree
+#[derive(Debug, PartialEq)]
+pub struct PreviewLine {
+    pub depth: usize,
+    /// The key, or `-` for sequence items
+    pub label: String,
+    /// `None` for mappings and sequences, whose values are listed below them
+    pub encrypted: Option<bool>,
+}
+
+/// Walks `document` and marks every value as encrypted or not
+pub fn preview(document: &Value, selector: &KeySelector) -> Vec<PreviewLine> {
+    let mut lines = Vec::new();
+    walk(document, &mut Vec::new(), 0, selector, &mut lines);
+    lines
+}
+
+fn walk(
+    value: &Value,
+    path: &mut Vec<String>,
+    depth: usize,
+    selector: &KeySelector,
+    lines: &mut Vec<PreviewLine>,
+) {
+    let children: Vec<(String, Option<String>, &Value)> = match value {
+        Value::Mapping(map) => map
+            .iter()
+            .map(|(key, value)| {
+                let key = match key {
+                    Value::String(s) => s.clone(),
+                    other => serde_yaml::to_string(other)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                };
+                (key.clone(), Some(key), value)
+            })
+            .collect(),
+        // Indices don't take part in matching
+        Value::Sequence(items) => items.iter().map(|v| ("-".to_string(), None, v)).collect(),
+        Value::Tagged(tagged) => return walk(&tagged.value, path, depth, selector, lines),
+        _ => return,
+    };
+
+    for (label, key, child) in children {
+        if let Some(key) = &key {
+            path.push(key.clone());
+        }
+        let leaf = !matches!(child, Value::Mapping(_) | Value::Sequence(_));
+        lines.push(PreviewLine {
+            depth,
+            label,
+            encrypted: leaf.then(|| selector.is_encrypted(path)),
+        });
+        if !leaf {
+            walk(child, path, depth + 1, selector, lines);
+        }
+        if key.is_some() {
+            path.pop();
+        }
+    }
+}
+
+/// The values `selector` picks for encryption, with their `a.b[0].c` key paths.
+/// sops' own metadata is left out.
+pub fn selected_values<'a>(
+    document: &'a Value,
+    selector: &KeySelector,
+) -> Vec<(String, &'a Value)> {
+    fn collect<'a>(
+        value: &'a Value,
+        keys: &mut Vec<String>,
+        path: String,
+        selector: &KeySelector,
+        out: &mut Vec<(String, &'a Value)>,
+    ) {
+        match value {
+            Value::Mapping(map) => {
+                for (key, child) in map {
+                    let Some(key) = key.as_str() else { continue };
+                    if keys.is_empty() && key == "sops" {
+                        continue;
+                    }
+                    let child_path = if path.is_empty() {
+                        key.to_string()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    keys.push(key.to_string());
+                    collect(child, keys, child_path, selector, out);
+                    keys.pop();
+                }
+            }
+            Value::Sequence(items) => {
+                for (i, child) in items.iter().enumerate() {
+                    collect(child, keys, format!("{}[{}]", path, i), selector, out);
+                }
+            }
+            Value::Tagged(tagged) => collect(&tagged.value, keys, path, selector, out),
+            leaf => {
+                if selector.is_encrypted(keys) {
+                    out.push((path, leaf));
+                }
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    collect(document, &mut Vec::new(), String::new(), selector, &mut out);
+    out
+}
+
+/// Prints the tree with the values to encrypt highlighted, and how many they are
+pub fn print_preview(lines: &[PreviewLine]) {
+    for line in lines {
+        let indent = "  ".repeat(line.depth);
+        match line.encrypted {
+            Some(true) => println!("{}{} {}", indent, "🔒".yellow(), line.label.yellow()),
+            Some(false) => println!("{}   {}", indent, line.label.dimmed()),
+            None => println!("{}   {}", indent, line.label),
+        }
+    }
+
+    let values = lines.iter().filter(|l| l.encrypted.is_some()).count();
+    let encrypted = lines.iter().filter(|l| l.encrypted == Some(true)).count();
+    println!(
+        "\n{}",
+        tr_args(
+            "preview-would-encrypt",
+            &[
+                ("encrypted", encrypted.to_string().bold().to_string().into()),
+                ("count", values.into()),
+            ],
+        )
+    );
+}
+
+/// Where each value of a document stands, without its content: whether it is
+/// encrypted by key path, and the encrypted data keys of its sops metadata
+#[derive(Debug, Clone, Default)]
+pub struct EncryptionState {
+    values: BTreeMap<String, bool>,
+    data_keys: BTreeMap<String, Value>,
+}
+
+/// What encrypting a document did, see [`compare_encryption`]
+#[derive(Debug, PartialEq)]
+pub struct EncryptionSummary {
+    /// Plaintext before, encrypted now
+    pub newly_encrypted: Vec<String>,
+    /// Encrypted before already
+    pub still_encrypted: Vec<String>,
+    /// Left in plaintext by the rule's key selection
+    pub plaintext: Vec<String>,
+    /// Whether the data key of the earlier ciphertext was kept
+    pub data_key_reused: bool,
+}
+
+/// sops metadata: the `sops` mapping of YAML and JSON, flattened to `sops_...`
+/// keys in dotenv and INI files
+fn is_metadata(path: &str) -> bool {
+    path == "sops" || path.starts_with("sops.") || path.starts_with("sops_")
+}
+
+pub fn encryption_state(document: &Value) -> EncryptionState {
+    let mut leaves = BTreeMap::new();
+    flatten(document, String::new(), &mut leaves);
+    let mut state = EncryptionState::default();
+    for (path, value) in leaves {
+        if is_metadata(&path) {
+            // The data key encrypted to each recipient, age, pgp and kms alike
+            if path.ends_with(".enc") || path.ends_with("__map_enc") {
+                state.data_keys.insert(path, value);
+            }
+        } else {
+            let encrypted = value.as_str().is_some_and(|v| v.starts_with("ENC["));
+            state.values.insert(path, encrypted);
+        }
+    }
+    state
+}
+
+/// Compares a document before and after encryption. The data key counts as
+/// reused when the earlier ciphertext's encrypted data keys are unchanged.
+pub fn compare_encryption(before: &EncryptionState, after: &EncryptionState) -> EncryptionSummary {
+    let mut summary = EncryptionSummary {
+        newly_encrypted: Vec::new(),
+        still_encrypted: Vec::new(),
+        plaintext: Vec::new(),
+        data_key_reused: !before.data_keys.is_empty() && before.data_keys == after.data_keys,
+    };
+    for (path, encrypted) in &after.values {
+        let list = match (encrypted, before.values.get(path)) {
+            (false, _) => &mut summary.plaintext,
+            (true, Some(true)) => &mut summary.still_encrypted,
+            (true, _) => &mut summary.newly_encrypted,
+        };
+        list.push(path.clone());
+    }
+    summary
+}
+
+impl EncryptionSummary {
+    pub fn print(&self) {
+        if !self.newly_encrypted.is_empty() {
+            println!(
+                "{}",
+                tr_args(
+                    "summary-encrypted",
+                    &[(
+                        "count",
+                        self.newly_encrypted
+                            .len()
+                            .to_string()
+                            .bold()
+                            .to_string()
+                            .into()
+                    )],
+                )
+            );
+            for path in &self.newly_encrypted {
+                println!("  {} {}", "🔒".yellow(), path.yellow());
+            }
+        }
+        if !self.still_encrypted.is_empty() {
+            println!(
+                "{}",
+                tr_args(
+                    "summary-still-encrypted",
+                    &[("count", self.still_encrypted.len().into())],
+                )
+            );
+            for path in &self.still_encrypted {
+                println!("  🔒 {}", path);
+            }
+        }
+        if !self.plaintext.is_empty() {
+            println!(
+                "{}",
+                tr_args(
+                    "summary-plaintext",
+                    &[("count", self.plaintext.len().into())]
+                )
+            );
+            for path in &self.plaintext {
+                println!("     {}", path.dimmed());
+            }
+        }
+        if self.data_key_reused {
+            println!("{}", tr("summary-data-key-reused"));
+        } else {
+            println!("{}", tr("summary-data-key-generated"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EncryptionSummary, KeySelector, compare_encryption, encryption_state, preview,
+        selected_values,
+    };
+    use crate::util::sops_structs::CreationRule;
+
+    const SECRET: &str = r#"
+apiVersion: v1
+kind: Secret
+metadata:
+  name: db
+data:
+  password: aHVudGVyMg==
+  hosts:
+  - a
+token_unencrypted: visible
+"#;
+
+    fn encrypted_paths(rule: CreationRule) -> Vec<String> {
+        let selector = KeySelector::from_rule(&rule).unwrap();
+        preview(&serde_yaml::from_str(SECRET).unwrap(), &selector)
+            .into_iter()
+            .filter(|line| line.encrypted == Some(true))
+            .map(|line| line.label)
+            .collect()
+    }
+
+    #[test]
+    fn test_encrypted_regex_covers_subtrees() {
+        let rule = CreationRule {
+            encrypted_regex: Some("^(data|stringData)$".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(encrypted_paths(rule), ["password", "-"]);
+    }
+
+    #[test]
+    fn test_default_unencrypted_suffix() {
+        assert_eq!(
+            encrypted_paths(CreationRule::default()),
+            ["apiVersion", "kind", "name", "password", "-"]
+        );
+    }
+
+    #[test]
+    fn test_unencrypted_regex() {
+        let rule = CreationRule {
+            unencrypted_regex: Some("^(apiVersion|kind|metadata)$".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            encrypted_paths(rule),
+            ["password", "-", "token_unencrypted"]
+        );
+    }
+
+    #[test]
+    fn test_compare_encryption() {
+        let before = serde_yaml::from_str(
+            "db:\n  user: admin\n  password: ENC[AES256_GCM,data:a]\n  token: t\n",
+        )
+        .unwrap();
+        let after = serde_yaml::from_str(
+            "db:\n  user: admin\n  password: ENC[AES256_GCM,data:b]\n  token: ENC[AES256_GCM,data:c]\nsops:\n  age:\n  - recipient: age1x\n    enc: key\n",
+        )
+        .unwrap();
+        assert_eq!(
+            compare_encryption(&encryption_state(&before), &encryption_state(&after)),
+            EncryptionSummary {
+                newly_encrypted: vec!["db.token".to_string()],
+                still_encrypted: vec!["db.password".to_string()],
+                plaintext: vec!["db.user".to_string()],
+                data_key_reused: false,
+            }
+        );
+        let state = encryption_state(&after);
+        assert!(compare_encryption(&state, &state).data_key_reused);
+    }
+
+    #[test]
+    fn test_selected_values() {
+        let document = serde_yaml::from_str(SECRET).unwrap();
+        let selector = KeySelector::EncryptedRegex(regex::Regex::new("^data$").unwrap());
+        let paths: Vec<String> = selected_values(&document, &selector)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(paths, ["data.password", "data.hosts[0]"]);
+    }
+
+    #[test]
+    fn test_conflicting_options() {
+        let rule = CreationRule {
+            encrypted_regex: Some("a".to_string()),
+            encrypted_suffix: Some("_secret".to_string()),
+            ..Default::default()
+        };
+        assert!(KeySelector::from_rule(&rule).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Age keys the user's environment already holds. sops reads `SOPS_AGE_KEY`,
+//! `SOPS_AGE_KEY_FILE` and `SOPS_AGE_KEY_CMD` on its own, so with one of them
+//! set it would try that key next to the one opsops hands it. opsops uses one
+//! key: the project's unless `--prefer-env-key` says otherwise, and it clears
+//! the variables for sops.
+
+use age::secrecy::SecretString;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use zeroize::Zeroize;
+
+use super::i18n::tr_args;
+use super::op_key::parse_identity_file;
+use super::print_status::eprint_warning;
+use super::session_record;
+
+/// The variables sops reads age identities from
+pub const KEY_VARIABLES: [&str; 3] = ["SOPS_AGE_KEY", "SOPS_AGE_KEY_FILE", "SOPS_AGE_KEY_CMD"];
+
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Which key wins when the environment holds one, `--prefer-env-key` and
+/// `--prefer-op-key`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum KeyPreference {
+    /// The project's key, with a warning that the environment's is ignored
+    #[default]
+    Unspecified,
+    /// The environment's key, the project's if the environment has none
+    Environment,
+    /// The project's key, silently
+    Project,
+}
+
+/// The key variables that are set and not empty
+pub fn env_key_variables() -> Vec<&'static str> {
+    KEY_VARIABLES
+        .into_iter()
+        .filter(|var| std::env::var_os(var).is_some_and(|value| !value.is_empty()))
+        .collect()
+}
+
+/// Runs `SOPS_AGE_KEY_CMD` the way sops does and returns what it printed
+fn key_command_output(command: &str) -> Result<String, String> {
+    let mut output = session_record::output(Command::new("sh").arg("-c").arg(command))
+        .map_err(|e| format!("Failed to run SOPS_AGE_KEY_CMD: {}", e))?;
+    if !output.status.success() {
+        output.stdout.zeroize();
+        return Err(format!(
+            "SOPS_AGE_KEY_CMD failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let text = String::from_utf8_lossy(&output.stdout).into_owned();
+    output.stdout.zeroize();
+    Ok(text)
+}
+
+/// The identities of the first key variable that is set, in the order sops
+/// checks them, `None` if none is
+pub fn key_from_env() -> Result<Option<SecretString>, String> {
+    let Some(var) = env_key_variables().into_iter().next() else {
+        return Ok(None);
+    };
+    let value = std::env::var(var).map_err(|e| format!("Cannot read {}: {}", var, e))?;
+    let mut contents = match var {
+        "SOPS_AGE_KEY" => value,
+        "SOPS_AGE_KEY_FILE" => std::fs::read_to_string(&value)
+            .map_err(|e| format!("Cannot read SOPS_AGE_KEY_FILE {}: {}", value, e))?,
+        _ => key_command_output(&value)?,
+    };
+    let key = parse_identity_file(&contents).map_err(|e| format!("{}: {}", var, e));
+    contents.zeroize();
+    key.map(Some)
+}
+
+/// Warns, once per run, that the environment's key is set but not used
+pub fn warn_ignored_env_key(preference: KeyPreference) {
+    if preference != KeyPreference::Unspecified {
+        return;
+    }
+    let variables = env_key_variables();
+    if variables.is_empty() || WARNED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    eprint_warning(tr_args(
+        "env-key-hidden",
+        &[("variables", variables.join(" and ").into())],
+    ));
+}
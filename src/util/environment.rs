@@ -0,0 +1,89 @@
+//! What opsops runs with: its own build and the external tools it calls.
+//! `doctor` checks the tools, `info` reports all of it for bug reports.
+
+use serde::Serialize;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The opsops version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Commit opsops was built from, set by build.rs
+pub const BUILD_HASH: Option<&str> = option_env!("OPSOPS_BUILD_HASH");
+
+/// The opsops build, as `--version --json` prints it
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    /// Commit hash, `None` for builds outside a git checkout
+    pub build: Option<&'static str>,
+    /// `<os>-<arch>` it was built for
+    pub target: String,
+}
+
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        name: env!("CARGO_PKG_NAME"),
+        version: VERSION,
+        build: BUILD_HASH,
+        target: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+    }
+}
+
+/// An external tool and the version it reports
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ToolInfo {
+    pub name: &'static str,
+    /// `None` when it isn't on PATH
+    pub path: Option<PathBuf>,
+    /// First line of `<tool> --version`
+    pub version: Option<String>,
+}
+
+impl ToolInfo {
+    /// The version, or `unknown` if the tool didn't tell
+    pub fn version_or_unknown(&self) -> &str {
+        self.version.as_deref().unwrap_or("unknown")
+    }
+}
+
+/// Looks `name` up on PATH and asks it for its version
+pub fn detect_tool(name: &'static str) -> ToolInfo {
+    let path = which::which(name).ok();
+    let version = path.as_ref().and_then(|path| {
+        let output = Command::new(path).arg("--version").output().ok()?;
+        first_line(&output.stdout)
+    });
+    ToolInfo {
+        name,
+        path,
+        version,
+    }
+}
+
+fn first_line(output: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::first_line;
+
+    #[test]
+    fn test_first_line() {
+        assert_eq!(
+            first_line(b"sops 3.10.2 (latest)\n"),
+            Some("sops 3.10.2 (latest)".to_string())
+        );
+        assert_eq!(
+            first_line(b"\n2.30.0\nupdate available\n"),
+            Some("2.30.0".to_string())
+        );
+        assert_eq!(first_line(b"  \n"), None);
+    }
+}
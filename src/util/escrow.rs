@@ -0,0 +1,127 @@
+//! The organizational escrow (break-glass) recipient. When `.opsops.yaml` sets
+//! `escrow_recipient:`, every creation rule has to encrypt to it as well, so all
+//! files stay recoverable when individual keys are lost.
+
+use std::path::Path;
+
+use super::{
+    file_picker::quiet_config,
+    opsops_config::read_opsops_config,
+    rule_match::{first_matching_rule, project_relative_path},
+    sops_config::config_dir,
+    sops_structs::CreationRule,
+};
+use crate::GlobalContext;
+
+/// The escrow recipient configured for the project, if any
+pub fn escrow_recipient(context: &GlobalContext) -> Option<String> {
+    read_opsops_config(context)
+        .ok()
+        .flatten()
+        .and_then(|c| c.escrow_recipient)
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+}
+
+/// All age recipients of a rule, from `age:` and its key groups
+pub fn rule_recipients(rule: &CreationRule) -> Vec<String> {
+    rule.age
+        .iter()
+        .flat_map(|age| age.split(','))
+        .chain(
+            rule.key_groups
+                .iter()
+                .flat_map(|group| group.age.iter().map(String::as_str)),
+        )
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+/// Indices of the rules that don't encrypt to `escrow`. Rules with key groups
+/// need the escrow recipient in every group, as sops may need all of them.
+pub fn rules_missing_escrow(rules: &[CreationRule], escrow: &str) -> Vec<usize> {
+    rules
+        .iter()
+        .enumerate()
+        .filter(|(_, rule)| {
+            if rule.key_groups.is_empty() {
+                !rule_recipients(rule).iter().any(|r| r == escrow)
+            } else {
+                !rule
+                    .key_groups
+                    .iter()
+                    .all(|group| group.age.iter().any(|r| r.trim() == escrow))
+            }
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Appends `escrow` to a comma separated recipient list unless it is in it already
+pub fn with_escrow(recipients: &str, escrow: Option<&str>) -> String {
+    match escrow {
+        Some(escrow) if !recipients.split(',').any(|r| r.trim() == escrow) => {
+            format!("{},{}", recipients, escrow)
+        }
+        _ => recipients.to_string(),
+    }
+}
+
+/// Explains why encrypting `path` would leave it unrecoverable by the escrow
+/// recipient, `None` if there is no escrow recipient or its rule has it
+pub fn missing_escrow_reason(path: &Path, context: &GlobalContext) -> Option<String> {
+    let escrow = escrow_recipient(context)?;
+    let config = quiet_config(context)?;
+    let relative = project_relative_path(&config_dir(context)?, path)?;
+    let index = first_matching_rule(&config.creation_rules, &relative)?;
+    rules_missing_escrow(&config.creation_rules[index..=index], &escrow)
+        .first()
+        .map(|_| {
+            format!(
+                "rule #{} doesn't encrypt to the escrow recipient {} from .opsops.yaml",
+                index + 1,
+                escrow
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rules_missing_escrow, with_escrow};
+    use crate::util::sops_structs::SopsConfig;
+
+    const ESCROW: &str = "age1escrow";
+
+    #[test]
+    fn test_rules_missing_escrow() {
+        let config: SopsConfig = serde_yaml::from_str(
+            r#"
+creation_rules:
+- path_regex: a
+  age: age1me, age1escrow
+- path_regex: b
+  age: age1me
+- path_regex: c
+  key_groups:
+  - age: [age1me, age1escrow]
+  - age: [age1you]
+- path_regex: d
+  key_groups:
+  - age: [age1escrow]
+"#,
+        )
+        .unwrap();
+        assert_eq!(rules_missing_escrow(&config.creation_rules, ESCROW), [1, 2]);
+    }
+
+    #[test]
+    fn test_with_escrow() {
+        assert_eq!(with_escrow("age1me", Some(ESCROW)), "age1me,age1escrow");
+        assert_eq!(
+            with_escrow("age1me,age1escrow", Some(ESCROW)),
+            "age1me,age1escrow"
+        );
+        assert_eq!(with_escrow("age1me", None), "age1me");
+    }
+}
@@ -0,0 +1,231 @@
+use git2::Repository;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use super::platform::{Native, Platform};
+
+/// Directory inside `.git` holding one lock file per locked secret file
+const LOCKS_DIR: &str = "opsops-locks";
+
+/// An advisory lock on a secret file, released when dropped
+pub struct FileLock {
+    /// The locked file, `None` if the lock was overridden with `--force`
+    file: Option<File>,
+    /// Whether `file` is a lock file recording the holder, rather than the target itself
+    records_holder: bool,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Clear the holder before closing the file releases the lock.
+        // The lock file itself is kept, deleting it would race with other processes opening it.
+        if let Some(file) = &mut self.file
+            && self.records_holder
+        {
+            let _ = file.set_len(0);
+        }
+    }
+}
+
+/// The directory holding the lock files of a repository
+pub fn locks_dir(repo: &Repository) -> PathBuf {
+    repo.path().join(LOCKS_DIR)
+}
+
+/// Where the lock for `target` lives: `.git/opsops-locks/<escaped path>` inside a
+/// git repository. `None` outside of one, then the target itself is locked.
+pub fn lock_path(target: &Path) -> Option<PathBuf> {
+    // A target that doesn't exist yet is resolved through its directory
+    let absolute = fs::canonicalize(target)
+        .ok()
+        .or_else(|| {
+            let dir = target.parent().filter(|d| !d.as_os_str().is_empty());
+            let dir = fs::canonicalize(dir.unwrap_or(Path::new("."))).ok()?;
+            Some(dir.join(target.file_name()?))
+        })
+        .unwrap_or_else(|| target.to_path_buf());
+
+    if let Ok(repo) = Repository::discover(absolute.parent().unwrap_or(Path::new(".")))
+        && let Some(workdir) = repo.workdir().and_then(|w| fs::canonicalize(w).ok())
+        && let Ok(relative) = absolute.strip_prefix(&workdir)
+    {
+        let name = relative
+            .to_string_lossy()
+            .replace('%', "%25")
+            .replace('/', "%2F");
+        return Some(locks_dir(&repo).join(format!("{}.lock", name)));
+    }
+    None
+}
+
+/// Opens the lock file at `path`, or `target` itself outside of a git repository.
+/// A `target` that doesn't exist yet is locked through its directory.
+fn open_lock(target: &Path, path: Option<&Path>) -> Result<File, String> {
+    match path {
+        Some(path) => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)
+                    .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            }
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+                .map_err(|e| format!("Failed to open lock file {}: {}", path.display(), e))
+        }
+        None if !target.exists() => {
+            let dir = target.parent().filter(|d| !d.as_os_str().is_empty());
+            let dir = dir.unwrap_or(Path::new("."));
+            File::open(dir).map_err(|e| format!("Failed to open {}: {}", dir.display(), e))
+        }
+        None => {
+            File::open(target).map_err(|e| format!("Failed to open {}: {}", target.display(), e))
+        }
+    }
+}
+
+/// Takes an exclusive advisory lock on `target`, waiting for whoever holds it.
+/// For short read-modify-write cycles like config updates, where waiting beats
+/// failing.
+pub fn wait_for_lock(target: &Path) -> Result<FileLock, String> {
+    let path = lock_path(target);
+    let file = open_lock(target, path.as_deref())?;
+    file.lock()
+        .map_err(|e| format!("Failed to lock {}: {}", target.display(), e))?;
+    Ok(FileLock {
+        file: Some(file),
+        records_holder: false,
+    })
+}
+
+/// Takes an exclusive advisory lock on `target`, failing with the holder's PID and
+/// host if another opsops process has it. With `force` a held lock only produces
+/// a warning message and an unlocked guard is returned.
+pub fn lock_file(target: &Path, force: bool) -> Result<(FileLock, Option<String>), String> {
+    let path = lock_path(target);
+    let mut file = open_lock(target, path.as_deref())?;
+
+    if file.try_lock().is_err() {
+        let mut holder = String::new();
+        if path.is_some() {
+            let _ = file.read_to_string(&mut holder);
+        }
+        let holder = match holder.trim().split_once(' ') {
+            Some((pid, host)) => format!("PID {} on {}", pid, host),
+            None => "another opsops process".to_string(),
+        };
+        let message = format!("{} is being edited by {}", target.display(), holder);
+        if force {
+            let unlocked = FileLock {
+                file: None,
+                records_holder: false,
+            };
+            return Ok((unlocked, Some(message)));
+        }
+        return Err(format!("{}. Use --force to override.", message));
+    }
+
+    let Some(path) = path else {
+        let lock = FileLock {
+            file: Some(file),
+            records_holder: false,
+        };
+        return Ok((lock, None));
+    };
+
+    // We hold the lock, record who we are for anyone else trying
+    let host = Native::hostname().unwrap_or_else(|| "unknown host".to_string());
+    file.set_len(0)
+        .and_then(|_| file.rewind())
+        .and_then(|_| writeln!(file, "{} {}", std::process::id(), host))
+        .map_err(|e| format!("Failed to write lock file {}: {}", path.display(), e))?;
+
+    let lock = FileLock {
+        file: Some(file),
+        records_holder: true,
+    };
+    Ok((lock, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use git2::Repository;
+    use tempfile::TempDir;
+
+    use super::{lock_file, lock_path, wait_for_lock};
+
+    #[test]
+    fn test_lock_path_in_git_repository() {
+        let dir = TempDir::new().unwrap();
+        Repository::init(dir.path()).unwrap();
+        fs::create_dir(dir.path().join("k8s")).unwrap();
+        let target = dir.path().join("k8s").join("secret.yaml");
+        fs::write(&target, "").unwrap();
+
+        let path = lock_path(&target).unwrap();
+        assert!(path.ends_with(".git/opsops-locks/k8s%2Fsecret.yaml.lock"));
+    }
+
+    #[test]
+    fn test_second_lock_fails_until_released() {
+        let dir = TempDir::new().unwrap();
+        Repository::init(dir.path()).unwrap();
+        let target = dir.path().join("secret.yaml");
+        fs::write(&target, "").unwrap();
+
+        let (first, warning) = lock_file(&target, false).unwrap();
+        assert!(warning.is_none());
+
+        // Locks belong to the open file, so a second open conflicts
+        let err = lock_file(&target, false).err().unwrap();
+        assert!(err.contains(&format!("PID {}", std::process::id())));
+        assert!(err.contains("--force"));
+
+        let (_, warning) = lock_file(&target, true).unwrap();
+        assert!(warning.unwrap().contains("is being edited by"));
+
+        drop(first);
+        assert!(lock_file(&target, false).is_ok());
+    }
+
+    #[test]
+    fn test_locks_target_outside_git_repository() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("secret.yaml");
+        fs::write(&target, "password: hunter2\n").unwrap();
+        assert!(lock_path(&target).is_none());
+
+        let (first, _) = lock_file(&target, false).unwrap();
+        let err = lock_file(&target, false).err().unwrap();
+        assert!(err.contains("another opsops process"));
+
+        drop(first);
+        assert!(lock_file(&target, false).is_ok());
+        // The target is never written to
+        assert_eq!(fs::read_to_string(&target).unwrap(), "password: hunter2\n");
+    }
+
+    #[test]
+    fn test_wait_for_lock_waits_for_the_holder() {
+        let dir = TempDir::new().unwrap();
+        Repository::init(dir.path()).unwrap();
+        let target = dir.path().join(".sops.yaml");
+
+        let first = wait_for_lock(&target).unwrap();
+        assert!(lock_file(&target, false).is_err());
+
+        let waiter = {
+            let target = target.clone();
+            std::thread::spawn(move || wait_for_lock(&target).map(|_| std::time::Instant::now()))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let released = std::time::Instant::now();
+        drop(first);
+        assert!(waiter.join().unwrap().unwrap() >= released);
+    }
+}
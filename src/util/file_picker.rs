@@ -0,0 +1,96 @@
+use colored::Colorize;
+use std::ffi::OsString;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use super::{
+    config_include::{EffectiveConfig, resolve_includes},
+    i18n::{tr, tr_args},
+    opsops_config::{OPSOPS_CONFIG_FILE, read_opsops_config},
+    print_status::print_error,
+    prompts::{fuzzy_select_opt, is_accessible},
+    rule_match::{RuleMatcher, relative_path},
+    sops_config::{config_dir, sops_config_path},
+    sops_files::{is_sops_encrypted_file, walk_files},
+    sops_structs::SopsConfig,
+};
+use crate::GlobalContext;
+
+/// Reads the project's effective config without printing anything, e.g. for
+/// completion scripts which must never see error output
+pub fn quiet_config(context: &GlobalContext) -> Option<SopsConfig> {
+    quiet_effective_config(context).map(|e| e.config)
+}
+
+/// Like [`quiet_config`], keeping track of where rules came from
+pub fn quiet_effective_config(context: &GlobalContext) -> Option<EffectiveConfig> {
+    let path = sops_config_path(context)?;
+    let mut config: SopsConfig = serde_yaml::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+    if let Some(item) = read_opsops_config(context)
+        .ok()
+        .flatten()
+        .and_then(|c| c.onepassworditem)
+    {
+        config.onepassworditem = item;
+    }
+    resolve_includes(config, &path).ok()
+}
+
+/// Files in the project a creation rule applies to, relative to `cwd` as the user
+/// would type them. Files outside of `cwd` are left out.
+pub fn rule_matched_files(context: &GlobalContext, cwd: &Path) -> Vec<PathBuf> {
+    let (Some(config), Some(root)) = (quiet_config(context), config_dir(context)) else {
+        return Vec::new();
+    };
+    let root = fs::canonicalize(&root).unwrap_or(root);
+    let cwd = fs::canonicalize(cwd).unwrap_or_else(|_| cwd.to_path_buf());
+    let matcher = RuleMatcher::new(&config.creation_rules);
+
+    walk_files(&root)
+        .iter()
+        .filter(|path| {
+            path.file_name()
+                .is_none_or(|name| name != ".sops.yaml" && name != OPSOPS_CONFIG_FILE)
+        })
+        .filter(|path| matcher.first_match(&relative_path(&root, path)).is_some())
+        .filter_map(|path| path.strip_prefix(&cwd).ok().map(Path::to_path_buf))
+        .collect()
+}
+
+/// Lets the user pick one of the files matched by a creation rule, for commands
+/// invoked without a path. Exits if there is nothing to pick or no terminal.
+pub fn pick_file(context: &GlobalContext, action: &str) -> OsString {
+    let files = rule_matched_files(context, &context.working_dir());
+    if files.is_empty() {
+        print_error(format!(
+            "{} {}",
+            tr("pick-no-files").red(),
+            tr_args("pick-pass-file", &[("action", action.into())]).dimmed()
+        ));
+        std::process::exit(1);
+    }
+
+    // FuzzySelect spins instead of failing without a terminal
+    if !is_accessible() && (!std::io::stdin().is_terminal() || !std::io::stderr().is_terminal()) {
+        print_error(format!("{}", tr("pick-no-terminal").red()));
+        std::process::exit(1);
+    }
+
+    let items: Vec<String> = files
+        .iter()
+        .map(|path| {
+            let status = if is_sops_encrypted_file(path) {
+                "🔒 encrypted".green()
+            } else {
+                "plaintext".yellow()
+            };
+            format!("{} {}", path.display(), status)
+        })
+        .collect();
+
+    match fuzzy_select_opt(&format!("Choose a file to {}", action), &items) {
+        Ok(Some(index)) => files[index].clone().into_os_string(),
+        Ok(None) | Err(_) => std::process::exit(1),
+    }
+}
@@ -0,0 +1,80 @@
+//! What `edit --create` puts into a file that doesn't exist yet: a skeleton
+//! with the required keys of the schema attached to the file's creation rule,
+//! a `Secret` for rules using the Kubernetes preset, or a front matter and a
+//! secret block for notes. Anything else is left to sops, which opens new
+//! files with its own example.
+
+use std::path::Path;
+
+use super::{
+    document::render_document,
+    file_picker::quiet_config,
+    json_schema::{SchemaIndex, read_schema, skeleton},
+    markdown::is_markdown,
+    rule_match::{first_matching_rule, project_relative_path},
+    rule_templates::KUBERNETES_REGEX,
+    sops_config::config_dir,
+    sops_files::sops_file_type,
+};
+use crate::GlobalContext;
+
+const KUBERNETES_SECRET: &str = "apiVersion: v1
+kind: Secret
+metadata:
+  name: {name}
+type: Opaque
+stringData:
+  password: change-me
+";
+
+const NOTE: &str = "---
+password: change-me
+---
+# {name}
+
+The front matter above and blocks like the one below are encrypted.
+
+```secret
+change me
+```
+";
+
+/// The plaintext to start `path` with, `None` to let sops pick. Fails if no
+/// creation rule covers the path, sops couldn't encrypt it either.
+pub fn scaffold(path: &Path, context: &GlobalContext) -> Result<Option<String>, String> {
+    let (Some(root), Some(config)) = (config_dir(context), quiet_config(context)) else {
+        return Err("No .sops.yaml found, create one with 'opsops init'".to_string());
+    };
+    let rule = project_relative_path(&root, path)
+        .and_then(|relative| first_matching_rule(&config.creation_rules, &relative))
+        .map(|i| &config.creation_rules[i])
+        .ok_or_else(|| format!("No creation rule in .sops.yaml covers {}", path.display()))?;
+
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map_or("secrets", |n| n.split('.').next().unwrap_or(n));
+    if is_markdown(path) {
+        return Ok(Some(NOTE.replace("{name}", name)));
+    }
+
+    let file_type = sops_file_type(path);
+    if let Some(schema_path) = SchemaIndex::load(context)?.and_then(|i| i.schema_for(path)) {
+        let document = serde_yaml::to_value(skeleton(&read_schema(&schema_path)?))
+            .map_err(|e| format!("Invalid schema {}: {}", schema_path.display(), e))?;
+        return render_document(&document, file_type)
+            .map(|text| Some(format!("{}\n", text)))
+            .map_err(|e| {
+                format!(
+                    "Can't start {} from {}: {}",
+                    path.display(),
+                    schema_path.display(),
+                    e
+                )
+            });
+    }
+    if file_type == "yaml" && rule.encrypted_regex.as_deref() == Some(KUBERNETES_REGEX) {
+        return Ok(Some(KUBERNETES_SECRET.replace("{name}", name)));
+    }
+    Ok(None)
+}
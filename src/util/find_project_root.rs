@@ -1,25 +1,24 @@
 use git2::Repository;
 use std::path::PathBuf;
 
+use super::i18n::tr;
 use super::print_status::print_warning;
+use crate::GlobalContext;
 
-pub fn find_project_root() -> Option<PathBuf> {
+/// The project containing the working directory, which `-C` may override
+pub fn find_project_root(context: &GlobalContext) -> Option<PathBuf> {
     // Root indicators to fall back on
     let root_indicators = vec![".git", "src", "flake.nix", "package.json", "Cargo.toml"];
+    let start_dir = context.working_dir();
 
     // Try to find Git repository root
-    Repository::discover(".")
+    Repository::discover(&start_dir)
         .ok()
         .and_then(|repo| repo.workdir().map(|p| p.to_path_buf()))
-        .or_else(|| find_root_by_indicators(&root_indicators))
+        .or_else(|| find_root_by_indicators_from_dir(&root_indicators, &start_dir))
 }
 
 /// Fallback method to find root by walking up directories looking for indicators.
-fn find_root_by_indicators(indicators: &[&str]) -> Option<PathBuf> {
-    find_root_by_indicators_from_dir(indicators, &std::env::current_dir().ok()?)
-}
-
-/// Internal function that takes a starting directory - useful for testing
 fn find_root_by_indicators_from_dir(
     indicators: &[&str],
     start_dir: &std::path::Path,
@@ -40,9 +39,7 @@ fn find_root_by_indicators_from_dir(
         }
     }
 
-    print_warning(
-        "Couldn't find project root, falling back to current directory. Please use the --sops-file flag",
-    );
+    print_warning(tr("project-root-fallback"));
     Some(start_dir.to_path_buf())
 }
 
@@ -52,6 +49,27 @@ mod tests {
     use std::fs::{self, File};
     use tempfile::TempDir;
 
+    #[test]
+    fn test_find_project_root_starts_at_chdir() {
+        let temp_dir = TempDir::new().unwrap();
+        git2::Repository::init(temp_dir.path()).unwrap();
+        let nested_dir = temp_dir.path().join("services").join("api");
+        fs::create_dir_all(&nested_dir).unwrap();
+
+        let context = GlobalContext {
+            sops_file: None,
+            opitem: None,
+            age_key_env: false,
+            chdir: Some(nested_dir),
+            read_only: false,
+            key_preference: Default::default(),
+        };
+
+        let expected = temp_dir.path().canonicalize().unwrap();
+        let actual = find_project_root(&context).unwrap().canonicalize().unwrap();
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn test_find_root_by_indicators_with_git() {
         let temp_dir = TempDir::new().unwrap();
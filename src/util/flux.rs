@@ -0,0 +1,157 @@
+//! Flux `Kustomization` manifests and the cluster Secret their sops decryption
+//! reads the age key from.
+
+use age::secrecy::SecretString;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+use super::{
+    kubectl::secret_manifest,
+    sops_files::{sops_file_type, walk_files},
+};
+
+/// The data key Flux reads age keys from has to end in this
+pub const AGE_KEY_SUFFIX: &str = ".agekey";
+
+/// A Flux Kustomization and how it decrypts secrets
+#[derive(Debug, PartialEq)]
+pub struct FluxKustomization {
+    pub file: PathBuf,
+    pub name: String,
+    pub namespace: String,
+    /// `spec.decryption.provider`
+    pub provider: Option<String>,
+    /// `spec.decryption.secretRef.name`
+    pub secret_name: Option<String>,
+}
+
+/// Every Flux Kustomization in the YAML files below `root`, including
+/// multi-document files
+pub fn find_kustomizations(root: &Path) -> Vec<FluxKustomization> {
+    walk_files(root)
+        .into_iter()
+        .filter(|f| sops_file_type(f) == "yaml")
+        .flat_map(|file| {
+            let contents = fs::read_to_string(&file).unwrap_or_default();
+            parse_kustomizations(&contents)
+                .into_iter()
+                .map(move |k| FluxKustomization {
+                    file: file.clone(),
+                    ..k
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn parse_kustomizations(contents: &str) -> Vec<FluxKustomization> {
+    serde_yaml::Deserializer::from_str(contents)
+        .filter_map(|document| serde_yaml::Value::deserialize(document).ok())
+        .filter(|document| {
+            let str_at = |key: &str| document.get(key).and_then(|v| v.as_str());
+            str_at("kind") == Some("Kustomization")
+                && str_at("apiVersion")
+                    .is_some_and(|v| v.starts_with("kustomize.toolkit.fluxcd.io/"))
+        })
+        .map(|document| {
+            let str_at = |path: &[&str]| {
+                path.iter()
+                    .try_fold(&document, |value, key| value.get(*key))
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+            };
+            FluxKustomization {
+                file: PathBuf::new(),
+                name: str_at(&["metadata", "name"]).unwrap_or_default(),
+                namespace: str_at(&["metadata", "namespace"])
+                    .unwrap_or_else(|| "flux-system".to_string()),
+                provider: str_at(&["spec", "decryption", "provider"]),
+                secret_name: str_at(&["spec", "decryption", "secretRef", "name"]),
+            }
+        })
+        .collect()
+}
+
+/// The age key in a Secret as printed by `kubectl get secret -o json`
+pub fn age_key_from_secret(json: &str) -> Result<SecretString, String> {
+    let secret: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse the Secret: {}", e))?;
+    let encoded = secret
+        .get("data")
+        .and_then(|d| d.as_object())
+        .and_then(|data| {
+            data.iter()
+                .find(|(key, _)| key.ends_with(AGE_KEY_SUFFIX))
+                .and_then(|(_, value)| value.as_str())
+        })
+        .ok_or_else(|| format!("The Secret has no key ending in {}", AGE_KEY_SUFFIX))?;
+    let mut decoded = STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("The age key in the Secret is not valid base64: {}", e))?;
+    let key = String::from_utf8_lossy(&decoded).trim().to_string();
+    decoded.zeroize();
+    Ok(SecretString::from(key))
+}
+
+/// A Secret holding `age_key` the way Flux expects it
+pub fn decryption_secret_manifest(name: &str, namespace: &str, age_key: &SecretString) -> String {
+    secret_manifest(name, namespace, &format!("age{}", AGE_KEY_SUFFIX), age_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use age::secrecy::{ExposeSecret, SecretString};
+
+    use super::{age_key_from_secret, decryption_secret_manifest, parse_kustomizations};
+
+    #[test]
+    fn test_parse_kustomizations() {
+        let found = parse_kustomizations(
+            r#"
+apiVersion: kustomize.toolkit.fluxcd.io/v1
+kind: Kustomization
+metadata:
+  name: apps
+  namespace: flux-system
+spec:
+  decryption:
+    provider: sops
+    secretRef:
+      name: sops-age
+---
+apiVersion: kustomize.config.k8s.io/v1beta1
+kind: Kustomization
+resources: [a.yaml]
+---
+apiVersion: kustomize.toolkit.fluxcd.io/v1
+kind: Kustomization
+metadata:
+  name: infra
+"#,
+        );
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].provider.as_deref(), Some("sops"));
+        assert_eq!(found[0].secret_name.as_deref(), Some("sops-age"));
+        assert_eq!(found[1].name, "infra");
+        assert_eq!(found[1].namespace, "flux-system");
+        assert_eq!(found[1].provider, None);
+    }
+
+    #[test]
+    fn test_age_key_round_trip() {
+        let key = SecretString::from("AGE-SECRET-KEY-1ABC".to_string());
+        let manifest = decryption_secret_manifest("sops-age", "flux-system", &key);
+        assert!(manifest.contains("  age.agekey: AGE-SECRET-KEY-1ABC\n"));
+
+        // "AGE-SECRET-KEY-1ABC\n" in base64
+        let json = r#"{"data": {"other": "eA==", "age.agekey": "QUdFLVNFQ1JFVC1LRVktMUFCQwo="}}"#;
+        assert_eq!(
+            age_key_from_secret(json).unwrap().expose_secret(),
+            "AGE-SECRET-KEY-1ABC"
+        );
+        assert!(age_key_from_secret(r#"{"data": {"other": "eA=="}}"#).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! How status messages look, see [`super::print_status`]. `--formatter` picks
+//! the implementation, `theme:` in the user config the colors and symbols of
+//! the human one.
+
+use clap::ValueEnum;
+use colored::{Color, Colorize};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// The formatter of this process, set once in `main`
+static FORMATTER: OnceLock<Box<dyn Formatter>> = OnceLock::new();
+
+/// The kind of a status message
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Success,
+    Warning,
+    Error,
+    /// The next step after an error, printed beneath it
+    Advice,
+    Info,
+}
+
+impl Level {
+    /// Errors and their advice go to stderr, everything else to stdout
+    fn is_error(self) -> bool {
+        matches!(self, Level::Error | Level::Advice)
+    }
+}
+
+/// Renders status messages
+pub trait Formatter: Send + Sync {
+    /// Prints `message` to stderr if `stderr`, else to stdout
+    fn message_to(&self, level: Level, message: &str, stderr: bool);
+
+    fn message(&self, level: Level, message: &str) {
+        self.message_to(level, message, level.is_error());
+    }
+}
+
+/// `--formatter`
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum FormatterKind {
+    /// Colored, with symbols, styled by the theme
+    #[default]
+    Human,
+    /// No colors, ASCII symbols, no emoji
+    Plain,
+    /// One JSON object per message, for wrappers and CI logs
+    Json,
+    /// Only errors
+    Quiet,
+}
+
+/// `theme:` in the user config
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Theme {
+    /// Symbols and emoji, off for terminals that render them poorly
+    pub emoji: bool,
+    pub success: String,
+    pub warning: String,
+    pub error: String,
+    pub advice: String,
+    pub info: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            emoji: true,
+            success: "green".to_string(),
+            warning: "yellow".to_string(),
+            error: "red".to_string(),
+            advice: "cyan".to_string(),
+            info: "blue".to_string(),
+        }
+    }
+}
+
+impl Theme {
+    /// The color of `level`, the default one if the configured name is unknown
+    fn color(&self, level: Level) -> Color {
+        let (name, default) = match level {
+            Level::Success => (&self.success, Color::Green),
+            Level::Warning => (&self.warning, Color::Yellow),
+            Level::Error => (&self.error, Color::Red),
+            Level::Advice => (&self.advice, Color::Cyan),
+            Level::Info => (&self.info, Color::Blue),
+        };
+        name.parse().unwrap_or(default)
+    }
+}
+
+/// The symbol in front of a message, `emoji` picks Unicode over ASCII
+fn symbol(level: Level, emoji: bool) -> &'static str {
+    match (level, emoji) {
+        (Level::Success, true) => "✔",
+        (Level::Success, false) => "ok",
+        (Level::Warning, true) => "⚠",
+        (Level::Warning, false) => "!",
+        (Level::Error, true) => "⨯",
+        (Level::Error, false) => "x",
+        (Level::Advice, true) => "→",
+        (Level::Advice, false) => "->",
+        (Level::Info, _) => "",
+    }
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c,
+        '\u{1F000}'..='\u{1FAFF}'
+        | '\u{2139}'
+        | '\u{2300}'..='\u{23FF}'
+        | '\u{2600}'..='\u{27BF}'
+        | '\u{2B00}'..='\u{2BFF}'
+        | '\u{FE0F}'
+        | '\u{200D}')
+}
+
+/// `message` without emoji, e.g. "🔐 Encrypting" becomes "Encrypting"
+pub fn strip_emoji(message: &str) -> String {
+    let stripped: String = message.chars().filter(|c| !is_emoji(*c)).collect();
+    if stripped.len() == message.len() {
+        return stripped;
+    }
+    stripped.trim_start().replace("  ", " ")
+}
+
+/// A message behind its symbol, advice indented beneath its error
+fn line(level: Level, symbol: &str, message: &str) -> String {
+    let indent = if level == Level::Advice { "  " } else { "" };
+    if symbol.is_empty() {
+        format!("{}{}", indent, message)
+    } else {
+        format!("{}{} {}", indent, symbol, message)
+    }
+}
+
+fn write(stderr: bool, line: String) {
+    if stderr {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+pub struct Human {
+    pub theme: Theme,
+}
+
+impl Formatter for Human {
+    fn message_to(&self, level: Level, message: &str, stderr: bool) {
+        let color = self.theme.color(level);
+        let message = if self.theme.emoji {
+            message.to_string()
+        } else {
+            strip_emoji(message)
+        };
+        // Errors are colored as a whole, other messages only by their symbol
+        let message = match level {
+            Level::Error => message.color(color).to_string(),
+            _ => message,
+        };
+        let symbol = symbol(level, self.theme.emoji).color(color).to_string();
+        write(stderr, line(level, &symbol, &message));
+    }
+}
+
+pub struct Plain;
+
+impl Formatter for Plain {
+    fn message_to(&self, level: Level, message: &str, stderr: bool) {
+        write(
+            stderr,
+            line(level, symbol(level, false), &strip_emoji(message)),
+        );
+    }
+}
+
+pub struct Json;
+
+#[derive(Serialize)]
+struct JsonMessage<'a> {
+    level: Level,
+    message: &'a str,
+}
+
+impl Formatter for Json {
+    fn message_to(&self, level: Level, message: &str, stderr: bool) {
+        let message = strip_emoji(message);
+        let json = serde_json::to_string(&JsonMessage {
+            level,
+            message: &message,
+        })
+        .unwrap_or_default();
+        write(stderr, json);
+    }
+}
+
+/// The human formatter, for errors only
+pub struct Quiet(pub Human);
+
+impl Formatter for Quiet {
+    fn message_to(&self, level: Level, message: &str, stderr: bool) {
+        if level.is_error() {
+            self.0.message_to(level, message, stderr);
+        }
+    }
+}
+
+/// Installs the formatter of this process. The plain and JSON ones disable
+/// colors for all output, not only status messages.
+pub fn set_formatter(kind: FormatterKind, theme: Theme) {
+    let formatter: Box<dyn Formatter> = match kind {
+        FormatterKind::Human => Box::new(Human { theme }),
+        FormatterKind::Plain => Box::new(Plain),
+        FormatterKind::Json => Box::new(Json),
+        FormatterKind::Quiet => Box::new(Quiet(Human { theme })),
+    };
+    if kind == FormatterKind::Plain || kind == FormatterKind::Json {
+        colored::control::set_override(false);
+    }
+    let _ = FORMATTER.set(formatter);
+}
+
+/// The formatter of this process, the human one with the default theme until
+/// [`set_formatter`] ran
+pub fn formatter() -> &'static dyn Formatter {
+    FORMATTER
+        .get_or_init(|| {
+            Box::new(Human {
+                theme: Theme::default(),
+            })
+        })
+        .as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Level, Theme, line, strip_emoji, symbol};
+    use colored::Color;
+
+    #[test]
+    fn test_strip_emoji() {
+        assert_eq!(
+            strip_emoji("🔐 Encrypting to a.yaml"),
+            "Encrypting to a.yaml"
+        );
+        assert_eq!(
+            strip_emoji("ℹ️ File has not changed."),
+            "File has not changed."
+        );
+        assert_eq!(strip_emoji("Took ⏱ 3s"), "Took 3s");
+        assert_eq!(strip_emoji("café → tea"), "café → tea");
+    }
+
+    #[test]
+    fn test_plain_lines() {
+        let plain = |level, message| line(level, symbol(level, false), message);
+        assert_eq!(plain(Level::Success, "Done"), "ok Done");
+        assert_eq!(plain(Level::Advice, "Run x"), "  -> Run x");
+        assert_eq!(plain(Level::Info, "Note"), "Note");
+        assert_eq!(
+            line(Level::Warning, symbol(Level::Warning, true), "Careful"),
+            "⚠ Careful"
+        );
+    }
+
+    #[test]
+    fn test_theme_colors() {
+        let theme: Theme =
+            serde_yaml::from_str("emoji: false\nsuccess: bright magenta\nerror: nope\n").unwrap();
+        assert!(!theme.emoji);
+        assert_eq!(theme.color(Level::Success), Color::BrightMagenta);
+        assert_eq!(theme.color(Level::Error), Color::Red);
+        assert_eq!(theme.color(Level::Info), Color::Blue);
+    }
+}
@@ -0,0 +1,161 @@
+//! Layout of the YAML and JSON documents sops writes. sops re-emits them with
+//! its own indentation, which changed between versions, and keeps keys in the
+//! order it found them, so files churn between teammates. `formatting:` in
+//! `.opsops.yaml` fixes the layout for the project, `--indent` and
+//! `--sort-keys` override it per run.
+
+use serde::{Deserialize, Serialize};
+use serde_json::ser::PrettyFormatter;
+use serde_yaml::{Mapping, Value};
+use zeroize::Zeroizing;
+
+use super::document::parse_document;
+use super::opsops_config::read_opsops_config;
+use crate::GlobalContext;
+
+/// `formatting:` in `.opsops.yaml`
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct Formatting {
+    /// Spaces per level, passed to sops as `--indent` (sops 3.9 and newer)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indent: Option<u8>,
+    /// Sort the keys of decrypted YAML and JSON documents
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sort_keys: bool,
+}
+
+/// The project's formatting with the flags of this run, `flags`, applied
+pub fn resolve_formatting(
+    flags: &Formatting,
+    context: &GlobalContext,
+) -> Result<Formatting, String> {
+    let configured = read_opsops_config(context)?
+        .and_then(|config| config.formatting)
+        .unwrap_or_default();
+    Ok(Formatting {
+        indent: flags.indent.or(configured.indent),
+        sort_keys: flags.sort_keys || configured.sort_keys,
+    })
+}
+
+/// Sorts the keys of every mapping in `value`
+pub fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Mapping(map) => {
+            let mut entries: Vec<(Value, Value)> = std::mem::take(map).into_iter().collect();
+            entries.sort_by_cached_key(|(key, _)| match key {
+                Value::String(s) => s.clone(),
+                other => serde_yaml::to_string(other).unwrap_or_default(),
+            });
+            let mut sorted = Mapping::with_capacity(entries.len());
+            for (key, mut value) in entries {
+                sort_keys(&mut value);
+                sorted.insert(key, value);
+            }
+            *map = sorted;
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(sort_keys),
+        Value::Tagged(tagged) => sort_keys(&mut tagged.value),
+        _ => {}
+    }
+}
+
+impl Formatting {
+    /// Arguments for sops writing a document of `file_type`
+    pub fn sops_args(&self, file_type: &str) -> Vec<String> {
+        match self.indent {
+            Some(indent) if file_type == "yaml" || file_type == "json" => {
+                vec!["--indent".to_string(), indent.to_string()]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Sorts the keys of `value` if configured
+    pub fn apply(&self, value: &mut Value) {
+        if self.sort_keys {
+            sort_keys(value);
+        }
+    }
+
+    /// Rewrites decrypted `content` with sorted keys, `None` if there is
+    /// nothing to do for the file type. JSON keeps the configured indentation,
+    /// YAML is written with two spaces, the only layout opsops can produce.
+    pub fn canonicalize(
+        &self,
+        content: &[u8],
+        file_type: &str,
+    ) -> Result<Option<Zeroizing<Vec<u8>>>, String> {
+        if !self.sort_keys || !(file_type == "yaml" || file_type == "json") {
+            return Ok(None);
+        }
+        let mut document = parse_document(content, file_type)?;
+        sort_keys(&mut document);
+        let mut output = Zeroizing::new(Vec::new());
+        if file_type == "json" {
+            let indent = " ".repeat(self.indent.unwrap_or(4).into());
+            let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+            let mut serializer = serde_json::Serializer::with_formatter(&mut *output, formatter);
+            document
+                .serialize(&mut serializer)
+                .map_err(|e| format!("Failed to serialize JSON: {}", e))?;
+            output.push(b'\n');
+        } else {
+            serde_yaml::to_writer(&mut *output, &document)
+                .map_err(|e| format!("Failed to serialize YAML: {}", e))?;
+        }
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Formatting;
+
+    fn sorted(indent: Option<u8>) -> Formatting {
+        Formatting {
+            indent,
+            sort_keys: true,
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_yaml() {
+        let content = b"zeta: 1\nalpha:\n  b: 2\n  a: 1\nlist:\n- y: 1\n  x: 2\n";
+        let output = sorted(None).canonicalize(content, "yaml").unwrap().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output),
+            "alpha:\n  a: 1\n  b: 2\nlist:\n- x: 2\n  y: 1\nzeta: 1\n"
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_json() {
+        let content = br#"{"b": {"d": 1, "c": [2]}, "a": "x"}"#;
+        let output = sorted(Some(2))
+            .canonicalize(content, "json")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output),
+            "{\n  \"a\": \"x\",\n  \"b\": {\n    \"c\": [\n      2\n    ],\n    \"d\": 1\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_nothing_to_do() {
+        let unsorted = Formatting {
+            indent: Some(4),
+            sort_keys: false,
+        };
+        assert!(unsorted.canonicalize(b"b: 1\n", "yaml").unwrap().is_none());
+        assert!(
+            sorted(None)
+                .canonicalize(b"B=1\n", "dotenv")
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(unsorted.sops_args("json"), ["--indent", "4"]);
+        assert!(unsorted.sops_args("dotenv").is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+//! Fresh credentials for `set --generate`, minted in memory and written
+//! straight into an encrypted file so they never pass through the clipboard,
+//! the shell history or the process list. `edit` has no generators. A
+//! generator is named by a spec of the form `<kind>[:<argument>]`:
+//!
+//! - `password[:N]`: `N` characters of letters, digits and `-_.!@#%^*+=`, 32 by default
+//! - `hex[:N]` and `base64[:N]`: `N` random bytes, 32 by default
+//! - `uuid`: a random (version 4) UUID
+//! - `rsa[:BITS]` and `ed25519`: a keypair as PEM, `private_key` and `public_key`
+//! - `htpasswd:<user>`: a password with its `user:$apr1$...` line, `password` and `htpasswd`
+//!
+//! Keypairs and htpasswd hashes come from `openssl`, which gets everything
+//! secret through pipes.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rand::Rng;
+use rand::rngs::OsRng;
+use serde_yaml::{Mapping, Value};
+use zeroize::Zeroizing;
+
+use super::openssl::openssl;
+
+const PASSWORD_CHARS: &[u8] =
+    b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-_.!@#%^*+=";
+
+/// Length of passwords and number of bytes of `hex` and `base64` by default
+const DEFAULT_LENGTH: usize = 32;
+
+const DEFAULT_RSA_BITS: u32 = 4096;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generator {
+    Password(usize),
+    Hex(usize),
+    Base64(usize),
+    Uuid,
+    Rsa(u32),
+    Ed25519,
+    Htpasswd(String),
+}
+
+impl Generator {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (kind, argument) = match spec.split_once(':') {
+            Some((kind, argument)) => (kind, Some(argument)),
+            None => (spec, None),
+        };
+        let number = |default: usize, min: usize| -> Result<usize, String> {
+            let Some(argument) = argument else {
+                return Ok(default);
+            };
+            match argument.parse() {
+                Ok(n) if n >= min => Ok(n),
+                _ => Err(format!(
+                    "'{}' needs a number of at least {}, got '{}'",
+                    kind, min, argument
+                )),
+            }
+        };
+        match kind {
+            "password" => Ok(Generator::Password(number(DEFAULT_LENGTH, 8)?)),
+            "hex" => Ok(Generator::Hex(number(DEFAULT_LENGTH, 8)?)),
+            "base64" => Ok(Generator::Base64(number(DEFAULT_LENGTH, 8)?)),
+            "rsa" => Ok(Generator::Rsa(
+                number(DEFAULT_RSA_BITS as usize, 2048)? as u32
+            )),
+            "uuid" | "ed25519" if argument.is_some() => {
+                Err(format!("'{}' takes no argument", kind))
+            }
+            "uuid" => Ok(Generator::Uuid),
+            "ed25519" => Ok(Generator::Ed25519),
+            "htpasswd" => match argument {
+                Some(user) if !user.is_empty() && !user.contains(':') => {
+                    Ok(Generator::Htpasswd(user.to_string()))
+                }
+                _ => Err("'htpasswd' needs a user name, e.g. htpasswd:admin".to_string()),
+            },
+            _ => Err(format!(
+                "Unknown generator '{}', use password, hex, base64, uuid, rsa, ed25519 or htpasswd",
+                kind
+            )),
+        }
+    }
+
+    /// A new value, a mapping for keypairs and htpasswd entries
+    pub fn generate(&self) -> Result<Value, String> {
+        match self {
+            Generator::Password(length) => Ok(Value::String(password(*length))),
+            Generator::Hex(bytes) => Ok(Value::String(
+                random_bytes(*bytes)
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect(),
+            )),
+            Generator::Base64(bytes) => Ok(Value::String(STANDARD.encode(random_bytes(*bytes)))),
+            Generator::Uuid => Ok(Value::String(uuid())),
+            Generator::Rsa(bits) => keypair(&[
+                "genpkey",
+                "-algorithm",
+                "RSA",
+                "-pkeyopt",
+                &format!("rsa_keygen_bits:{}", bits),
+            ]),
+            Generator::Ed25519 => keypair(&["genpkey", "-algorithm", "ed25519"]),
+            Generator::Htpasswd(user) => {
+                let password = Zeroizing::new(password(DEFAULT_LENGTH));
+                let hash = openssl(&["passwd", "-apr1", "-stdin"], password.as_bytes())?;
+                let mut entry = Mapping::new();
+                entry.insert("password".into(), password.as_str().into());
+                entry.insert(
+                    "htpasswd".into(),
+                    format!("{}:{}", user, hash.trim()).into(),
+                );
+                Ok(Value::Mapping(entry))
+            }
+        }
+    }
+}
+
+fn random_bytes(count: usize) -> Zeroizing<Vec<u8>> {
+    let mut bytes = Zeroizing::new(vec![0u8; count]);
+    OsRng.fill(bytes.as_mut_slice());
+    bytes
+}
+
+fn password(length: usize) -> String {
+    (0..length)
+        .map(|_| PASSWORD_CHARS[OsRng.gen_range(0..PASSWORD_CHARS.len())] as char)
+        .collect()
+}
+
+fn uuid() -> String {
+    let mut bytes: [u8; 16] = OsRng.r#gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// A private key from `openssl` run with `args` and its public key
+fn keypair(args: &[&str]) -> Result<Value, String> {
+    let private_key = openssl(args, b"")?;
+    let public_key = openssl(&["pkey", "-pubout"], private_key.as_bytes())?;
+    let mut pair = Mapping::new();
+    pair.insert("private_key".into(), private_key.as_str().into());
+    pair.insert("public_key".into(), public_key.as_str().into());
+    Ok(Value::Mapping(pair))
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use regex::Regex;
+    use serde_yaml::Value;
+
+    use super::{Generator, PASSWORD_CHARS};
+
+    fn generated(spec: &str) -> String {
+        match Generator::parse(spec).unwrap().generate().unwrap() {
+            Value::String(value) => value,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_generator() {
+        assert_eq!(Generator::parse("password"), Ok(Generator::Password(32)));
+        assert_eq!(Generator::parse("hex:16"), Ok(Generator::Hex(16)));
+        assert_eq!(Generator::parse("rsa"), Ok(Generator::Rsa(4096)));
+        assert_eq!(
+            Generator::parse("htpasswd:admin"),
+            Ok(Generator::Htpasswd("admin".to_string()))
+        );
+        assert!(Generator::parse("password:4").is_err());
+        assert!(Generator::parse("rsa:1024").is_err());
+        assert!(Generator::parse("uuid:4").is_err());
+        assert!(Generator::parse("htpasswd").is_err());
+        assert!(Generator::parse("pin").is_err());
+    }
+
+    #[test]
+    fn test_generate() {
+        let password = generated("password:40");
+        assert_eq!(password.len(), 40);
+        assert!(password.bytes().all(|b| PASSWORD_CHARS.contains(&b)));
+        assert_ne!(password, generated("password:40"));
+
+        let hex = generated("hex:16");
+        assert_eq!(hex.len(), 32);
+        assert!(hex.bytes().all(|b| b.is_ascii_hexdigit()));
+
+        assert_eq!(STANDARD.decode(generated("base64:24")).unwrap().len(), 24);
+
+        let uuid =
+            Regex::new("^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$")
+                .unwrap();
+        assert!(uuid.is_match(&generated("uuid")));
+    }
+}
@@ -0,0 +1,191 @@
+use git2::Repository;
+use std::fs;
+use std::path::PathBuf;
+
+use super::secret_scan::AGE_SECRET_KEY_PATTERN;
+
+/// Marks hooks written by opsops so they can be told apart from user hooks
+pub const HOOK_MARKER: &str = "# Installed by opsops";
+
+/// The pre-commit hook, matching full age keys like the scanner does, so
+/// commits mentioning the prefix in code and docs go through
+fn pre_commit_hook() -> String {
+    format!(
+        r#"#!/bin/sh
+# Installed by opsops
+# Refuses commits that add private key material.
+if git diff --cached -U0 | grep -E '^\+.*({}|BEGIN PGP PRIVATE KEY BLOCK)' >/dev/null; then
+    echo "opsops: refusing to commit a private key. Run 'opsops doctor' for details." >&2
+    exit 1
+fi
+"#,
+        AGE_SECRET_KEY_PATTERN
+    )
+}
+
+/// Returns the hooks directory of the repository, honoring core.hooksPath
+pub fn hooks_dir(repo: &Repository) -> PathBuf {
+    let configured = repo
+        .config()
+        .ok()
+        .and_then(|c| c.get_path("core.hooksPath").ok());
+
+    match (configured, repo.workdir()) {
+        (Some(path), Some(workdir)) if path.is_relative() => workdir.join(path),
+        (Some(path), _) => path,
+        (None, _) => repo.path().join("hooks"),
+    }
+}
+
+/// Installs the opsops pre-commit hook.
+/// Returns `Ok(false)` if an opsops hook was already installed.
+pub fn install_pre_commit_hook(repo: &Repository) -> Result<bool, String> {
+    let dir = hooks_dir(repo);
+    let hook_path = dir.join("pre-commit");
+
+    if hook_path.exists() {
+        let existing = fs::read_to_string(&hook_path)
+            .map_err(|e| format!("Failed to read {}: {}", hook_path.display(), e))?;
+        if existing.contains(HOOK_MARKER) {
+            return Ok(false);
+        }
+        return Err(format!(
+            "A pre-commit hook not managed by opsops already exists at {}",
+            hook_path.display()
+        ));
+    }
+
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    fs::write(&hook_path, pre_commit_hook())
+        .map_err(|e| format!("Failed to write {}: {}", hook_path.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to make hook executable: {}", e))?;
+    }
+
+    Ok(true)
+}
+
+/// Removes every hook containing the opsops marker, returning the removed paths
+pub fn remove_opsops_hooks(repo: &Repository) -> Result<Vec<PathBuf>, String> {
+    let dir = hooks_dir(repo);
+    let mut removed = Vec::new();
+
+    let entries = match fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(_) => return Ok(removed),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_ours = fs::read_to_string(&path)
+            .map(|c| c.contains(HOOK_MARKER))
+            .unwrap_or(false);
+        if is_ours {
+            fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+            removed.push(path);
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Removes `diff.opsops.*` entries from the repository config, returning the removed keys
+pub fn remove_diff_driver(repo: &Repository) -> Result<Vec<String>, String> {
+    let mut config = repo
+        .config()
+        .and_then(|c| c.open_level(git2::ConfigLevel::Local))
+        .map_err(|e| format!("Failed to open git config: {}", e))?;
+
+    let mut names = Vec::new();
+    if let Ok(entries) = config.entries(Some(r"^diff\.opsops\.")) {
+        entries
+            .for_each(|entry| {
+                if let Some(name) = entry.name() {
+                    names.push(name.to_string());
+                }
+            })
+            .map_err(|e| format!("Failed to read git config: {}", e))?;
+    }
+
+    for name in &names {
+        config
+            .remove(name)
+            .map_err(|e| format!("Failed to remove {}: {}", name, e))?;
+    }
+
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Repository;
+    use tempfile::TempDir;
+
+    use super::{
+        HOOK_MARKER, hooks_dir, install_pre_commit_hook, remove_diff_driver, remove_opsops_hooks,
+    };
+
+    #[test]
+    fn test_install_pre_commit_hook() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        assert_eq!(install_pre_commit_hook(&repo), Ok(true));
+        let hook = std::fs::read_to_string(hooks_dir(&repo).join("pre-commit")).unwrap();
+        assert!(hook.contains(HOOK_MARKER));
+        assert!(hook.contains("(AGE-SECRET-KEY-1[02-9AC-HJ-NP-Z]{58}|BEGIN PGP"));
+
+        // Installing again is a no-op
+        assert_eq!(install_pre_commit_hook(&repo), Ok(false));
+    }
+
+    #[test]
+    fn test_install_pre_commit_hook_keeps_foreign_hook() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let hook_path = hooks_dir(&repo).join("pre-commit");
+        std::fs::create_dir_all(hook_path.parent().unwrap()).unwrap();
+        std::fs::write(&hook_path, "#!/bin/sh\nexit 0\n").unwrap();
+
+        assert!(install_pre_commit_hook(&repo).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&hook_path).unwrap(),
+            "#!/bin/sh\nexit 0\n"
+        );
+    }
+
+    #[test]
+    fn test_remove_opsops_hooks() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        install_pre_commit_hook(&repo).unwrap();
+        let foreign = hooks_dir(&repo).join("post-commit");
+        std::fs::write(&foreign, "#!/bin/sh\n").unwrap();
+
+        let removed = remove_opsops_hooks(&repo).unwrap();
+        assert_eq!(removed, vec![hooks_dir(&repo).join("pre-commit")]);
+        assert!(foreign.exists());
+    }
+
+    #[test]
+    fn test_remove_diff_driver() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config
+            .set_str("diff.opsops.textconv", "opsops read")
+            .unwrap();
+        config.set_str("diff.other.textconv", "cat").unwrap();
+
+        let removed = remove_diff_driver(&repo).unwrap();
+        assert_eq!(removed, vec!["diff.opsops.textconv".to_string()]);
+
+        let config = repo.config().unwrap().snapshot().unwrap();
+        assert!(config.get_str("diff.opsops.textconv").is_err());
+        assert_eq!(config.get_str("diff.other.textconv").unwrap(), "cat");
+    }
+}
@@ -0,0 +1,74 @@
+//! Keeping git's index in step when opsops moves or deletes files, as `git mv`
+//! and `git rm` would, and reading files as they were committed. Files outside
+//! a repository or not tracked are left to the caller.
+
+use git2::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::rule_match::absolute_path;
+
+/// The repository `path` lies in and its path relative to the work tree
+fn locate(path: &Path) -> Option<(Repository, PathBuf)> {
+    let absolute = absolute_path(path);
+    let repo = Repository::discover(absolute.parent()?).ok()?;
+    let workdir = fs::canonicalize(repo.workdir()?).ok()?;
+    let relative = absolute.strip_prefix(&workdir).ok()?.to_path_buf();
+    Some((repo, relative))
+}
+
+/// Whether git tracks the file at `path`
+pub fn is_tracked(path: &Path) -> bool {
+    locate(path).is_some_and(|(repo, relative)| {
+        repo.index()
+            .is_ok_and(|index| index.get_path(&relative, 0).is_some())
+    })
+}
+
+/// Whether `path` lies in a git work tree without being ignored, so its
+/// content could end up in a commit
+pub fn could_be_committed(path: &Path) -> bool {
+    locate(path).is_some_and(|(repo, relative)| !repo.is_path_ignored(&relative).unwrap_or(true))
+}
+
+/// The content of the file at `path` in the revision `rev`, e.g. `HEAD~2`
+pub fn committed_content(path: &Path, rev: &str) -> Result<Vec<u8>, String> {
+    let Some((repo, relative)) = locate(path) else {
+        return Err(format!("{} is not inside a git repository", path.display()));
+    };
+    let commit = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|e| format!("Unknown revision '{}': {}", rev, e.message()))?;
+    let blob = commit
+        .tree()
+        .and_then(|tree| tree.get_path(&relative))
+        .and_then(|entry| entry.to_object(&repo))
+        .and_then(|object| object.peel_to_blob())
+        .map_err(|_| format!("{} doesn't exist in {}", relative.display(), rev))?;
+    Ok(blob.content().to_vec())
+}
+
+/// Removes the tracked file at `path` from the index, after it was deleted
+pub fn record_removal(path: &Path) -> Result<(), String> {
+    let failed = |e: git2::Error| format!("Failed to update the git index: {}", e);
+    let Some((repo, relative)) = locate(path) else {
+        return Err("Not inside a git repository".to_string());
+    };
+    let mut index = repo.index().map_err(failed)?;
+    index.remove_path(&relative).map_err(failed)?;
+    index.write().map_err(failed)
+}
+
+/// Records in the index that the tracked file `from` was moved to `to`, after
+/// it was moved on disk. `to` has to be in the same repository.
+pub fn record_move(from: &Path, to: &Path) -> Result<(), String> {
+    let failed = |e: git2::Error| format!("Failed to update the git index: {}", e);
+    let (Some((repo, from)), Some((_, to))) = (locate(from), locate(to)) else {
+        return Err("Not inside a git repository".to_string());
+    };
+    let mut index = repo.index().map_err(failed)?;
+    index.remove_path(&from).map_err(failed)?;
+    index.add_path(&to).map_err(failed)?;
+    index.write().map_err(failed)
+}
@@ -0,0 +1,233 @@
+//! Checks on a plaintext file before `encrypt` hands it to sops, for mistakes
+//! that encrypt fine but shouldn't: a database dump far larger than any
+//! config, binary data going through the YAML, JSON or dotenv store, and values
+//! that are the path to a secret rather than the secret. `guardrails:` in
+//! `.opsops.yaml` sets the size limit and whether findings stop the encryption.
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use super::document::{parse_document, scalar_text};
+use super::opsops_config::read_opsops_config;
+use crate::GlobalContext;
+
+/// Files above this many bytes are reported unless `max_size` says otherwise
+pub const DEFAULT_MAX_SIZE: u64 = 1024 * 1024;
+
+/// How many path-like values are listed
+const LISTED_PATHS: usize = 5;
+
+/// What a finding does
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Encrypt anyway and print the finding
+    #[default]
+    Warning,
+    /// Refuse to encrypt
+    Error,
+}
+
+impl Severity {
+    fn is_default(&self) -> bool {
+        *self == Severity::Warning
+    }
+}
+
+/// `guardrails:` in `.opsops.yaml`
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct Guardrails {
+    /// Largest plaintext, in bytes or with a `K`, `M` or `G` suffix [default: 1M]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+    #[serde(default, skip_serializing_if = "Severity::is_default")]
+    pub severity: Severity,
+}
+
+impl Guardrails {
+    pub fn max_bytes(&self) -> Result<u64, String> {
+        self.max_size
+            .as_deref()
+            .map_or(Ok(DEFAULT_MAX_SIZE), parse_size)
+    }
+}
+
+/// `512`, `64K`, `10M`, `1G`, the suffixes are powers of 1024
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let trimmed = size.trim();
+    let digits = trimmed.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let factor = match trimmed[digits.len()..].to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        _ => {
+            return Err(format!(
+                "Invalid size '{}', expected e.g. 512K or 10M",
+                size
+            ));
+        }
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(factor))
+        .ok_or_else(|| format!("Invalid size '{}', expected e.g. 512K or 10M", size))
+}
+
+/// Binary by git's rule of thumb: a NUL byte early on, or not UTF-8
+fn looks_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|&b| b == 0) || std::str::from_utf8(content).is_err()
+}
+
+/// Absolute, home relative or explicitly relative paths without whitespace
+fn looks_like_path(value: &str) -> bool {
+    let drive = value
+        .as_bytes()
+        .first()
+        .is_some_and(u8::is_ascii_alphabetic)
+        && value.get(1..3) == Some(":\\");
+    let rest = if drive {
+        value.get(3..)
+    } else {
+        ["~/", "./", "../", "/"]
+            .iter()
+            .find_map(|prefix| value.strip_prefix(prefix))
+    };
+    let Some(rest) = rest else {
+        return false;
+    };
+    !rest.is_empty() && !rest.contains(char::is_whitespace) && rest.contains(['/', '\\', '.'])
+}
+
+/// Key paths of the string values in `node` that look like file paths
+fn path_values(node: &Value, prefix: &str, found: &mut Vec<String>) {
+    match node {
+        Value::Mapping(map) => {
+            for (key, value) in map {
+                let key = scalar_text(key).unwrap_or_default();
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                path_values(value, &path, found);
+            }
+        }
+        Value::Sequence(items) => {
+            for (i, item) in items.iter().enumerate() {
+                path_values(item, &format!("{}[{}]", prefix, i), found);
+            }
+        }
+        Value::Tagged(tagged) => path_values(&tagged.value, prefix, found),
+        Value::String(value) if looks_like_path(value) => found.push(prefix.to_string()),
+        _ => {}
+    }
+}
+
+/// What's wrong with encrypting `content` as `file_type`, one message each
+pub fn findings(content: &[u8], file_type: &str, max_bytes: u64) -> Vec<String> {
+    let mut findings = Vec::new();
+    if content.len() as u64 > max_bytes {
+        findings.push(format!(
+            "It is {:.1} MiB, above the limit of {:.1} MiB (guardrails.max_size)",
+            content.len() as f64 / 1_048_576.0,
+            max_bytes as f64 / 1_048_576.0
+        ));
+    }
+    let structured = matches!(file_type, "yaml" | "json" | "dotenv");
+    if structured && looks_binary(content) {
+        findings.push(format!(
+            "It looks like binary data but would be encrypted as {}, without a .{} extension sops encrypts it as a binary file",
+            file_type,
+            if file_type == "dotenv" { "env" } else { file_type }
+        ));
+        return findings;
+    }
+    if !structured {
+        return findings;
+    }
+    let mut paths = Vec::new();
+    if let Ok(document) = parse_document(content, file_type) {
+        path_values(&document, "", &mut paths);
+    }
+    if !paths.is_empty() {
+        let more = paths.len().saturating_sub(LISTED_PATHS);
+        let mut listed = paths[..paths.len().min(LISTED_PATHS)].join(", ");
+        if more > 0 {
+            listed.push_str(&format!(" and {} more", more));
+        }
+        findings.push(format!(
+            "Values look like file paths rather than secrets, the files stay unencrypted: {}",
+            listed
+        ));
+    }
+    findings
+}
+
+/// The project's guardrails and the findings for the plaintext `content`
+pub fn check_guardrails(
+    content: &[u8],
+    file_type: &str,
+    context: &GlobalContext,
+) -> Result<(Severity, Vec<String>), String> {
+    let guardrails = read_opsops_config(context)?
+        .and_then(|config| config.guardrails)
+        .unwrap_or_default();
+    let max_bytes = guardrails
+        .max_bytes()
+        .map_err(|e| format!("guardrails.max_size: {}", e))?;
+    Ok((guardrails.severity, findings(content, file_type, max_bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{findings, looks_like_path, parse_size};
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64K"), Ok(64 * 1024));
+        assert_eq!(parse_size("10 MiB"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1g"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("ten").is_err());
+        assert!(parse_size("5T").is_err());
+    }
+
+    #[test]
+    fn test_looks_like_path() {
+        for path in [
+            "/etc/ssl/key.pem",
+            "~/.ssh/id_ed25519",
+            "./certs/tls.key",
+            "C:\\keys\\a.pfx",
+        ] {
+            assert!(looks_like_path(path), "{}", path);
+        }
+        for value in [
+            "hunter2",
+            "/",
+            "https://example.com/a",
+            "a/b",
+            "/not a path",
+        ] {
+            assert!(!looks_like_path(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_findings() {
+        let yaml = b"db:\n  password: hunter2\n  cert: /etc/ssl/db.pem\nkeys:\n- ./id.key\n";
+        let found = findings(yaml, "yaml", 1024);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].ends_with("db.cert, keys[0]"), "{}", found[0]);
+
+        let found = findings(yaml, "yaml", 16);
+        assert!(found[0].contains("guardrails.max_size"));
+
+        let found = findings(b"\x00\x01dump", "yaml", 1024);
+        assert!(found[0].contains("binary data"));
+        assert!(findings(b"\x00\x01dump", "binary", 1024).is_empty());
+    }
+}
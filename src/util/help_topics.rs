@@ -0,0 +1,30 @@
+//! Long-form guides for `opsops help <topic>`, compiled in so they are there
+//! offline. The texts live in `docs/help/`.
+
+pub struct HelpTopic {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub text: &'static str,
+}
+
+pub const TOPICS: &[HelpTopic] = &[
+    HelpTopic {
+        name: "workflows",
+        summary: "Setting up a project, editing, reading and checking secrets",
+        text: include_str!("../../docs/help/workflows.md"),
+    },
+    HelpTopic {
+        name: "key-rotation",
+        summary: "Adding and removing recipients, expiring and replacing keys",
+        text: include_str!("../../docs/help/key-rotation.md"),
+    },
+    HelpTopic {
+        name: "ci",
+        summary: "Checks for pipelines and decrypting without prompts",
+        text: include_str!("../../docs/help/ci.md"),
+    },
+];
+
+pub fn find_topic(name: &str) -> Option<&'static HelpTopic> {
+    TOPICS.iter().find(|topic| topic.name == name)
+}
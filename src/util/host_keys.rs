@@ -0,0 +1,180 @@
+//! Per-host age keys for server fleets: one repository, every host decrypting
+//! with a key of its own. `host_keys:` in `.opsops.yaml` maps hostname
+//! patterns to a 1Password reference or an identity file on the host, e.g.
+//! for NixOS activation where a machine only has its own key:
+//!
+//! ```yaml
+//! host_keys:
+//! - host: web-*
+//!   onepassworditem: op://Infra/web-age-key/key
+//! - host: db-?.example.com
+//!   key_file: /var/lib/opsops/age.txt
+//! ```
+//!
+//! Patterns use `*` and `?` as in shell globs and are matched without regard
+//! to case against the hostname and its first label. The first match wins
+//! over `onepassworditem:` and `keyprovider:`, `--op-item` still wins over it.
+
+use age::secrecy::SecretString;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+use super::{
+    op_key::parse_identity_file,
+    opsops_config::read_opsops_config,
+    platform::{Native, Platform},
+    sops_config::config_dir,
+};
+use crate::GlobalContext;
+
+/// One entry of `host_keys:` in `.opsops.yaml`, with exactly one key source
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct HostKey {
+    /// Hostname pattern, e.g. `web-*`
+    pub host: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onepassworditem: Option<String>,
+    /// Identity file on the host, relative paths are relative to `.opsops.yaml`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<String>,
+}
+
+/// Where a host's key comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostIdentity {
+    OnePassword(String),
+    KeyFile(PathBuf),
+}
+
+impl fmt::Display for HostIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostIdentity::OnePassword(reference) => write!(f, "{}", reference),
+            HostIdentity::KeyFile(path) => write!(f, "the key file {}", path.display()),
+        }
+    }
+}
+
+/// The entry this host matched
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostMatch {
+    pub hostname: String,
+    pub pattern: String,
+    pub identity: HostIdentity,
+}
+
+/// Whether the glob `pattern` matches `hostname` or its first label
+pub fn host_matches(pattern: &str, hostname: &str) -> bool {
+    let regex = format!(
+        "^{}$",
+        regex::escape(pattern)
+            .replace(r"\*", ".*")
+            .replace(r"\?", ".")
+    );
+    let Ok(regex) = RegexBuilder::new(&regex).case_insensitive(true).build() else {
+        return false;
+    };
+    let short = hostname.split('.').next().unwrap_or(hostname);
+    regex.is_match(hostname) || regex.is_match(short)
+}
+
+/// The identity of the first of `entries` matching `hostname`. Relative key
+/// files are resolved against `dir`.
+pub fn host_identity(
+    entries: &[HostKey],
+    hostname: &str,
+    dir: &Path,
+) -> Result<Option<(String, HostIdentity)>, String> {
+    let Some(entry) = entries
+        .iter()
+        .find(|entry| host_matches(&entry.host, hostname))
+    else {
+        return Ok(None);
+    };
+    let identity = match (&entry.onepassworditem, &entry.key_file) {
+        (Some(reference), None) => HostIdentity::OnePassword(reference.clone()),
+        (None, Some(file)) => HostIdentity::KeyFile(dir.join(file)),
+        _ => {
+            return Err(format!(
+                "host_keys entry '{}' needs either onepassworditem or key_file",
+                entry.host
+            ));
+        }
+    };
+    Ok(Some((entry.host.clone(), identity)))
+}
+
+/// The `host_keys:` entry of the host opsops runs on, `None` if the project
+/// has none for it
+pub fn current_host_identity(context: &GlobalContext) -> Result<Option<HostMatch>, String> {
+    let entries = read_opsops_config(context)?
+        .map(|config| config.host_keys)
+        .unwrap_or_default();
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let Some(hostname) = Native::hostname() else {
+        return Ok(None);
+    };
+    let dir = config_dir(context).unwrap_or_else(|| context.working_dir());
+    Ok(
+        host_identity(&entries, &hostname, &dir)?.map(|(pattern, identity)| HostMatch {
+            hostname,
+            pattern,
+            identity,
+        }),
+    )
+}
+
+/// Reads the age identities from the key file at `path`
+pub fn read_key_file(path: &Path) -> Result<SecretString, String> {
+    let mut contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read the key file {}: {}", path.display(), e))?;
+    let key = parse_identity_file(&contents)
+        .map_err(|e| format!("Invalid key file {}: {}", path.display(), e));
+    contents.zeroize();
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{HostIdentity, HostKey, host_identity, host_matches};
+
+    #[test]
+    fn test_host_matches() {
+        assert!(host_matches("web-*", "web-3"));
+        assert!(host_matches("web-*", "WEB-3.example.com"));
+        assert!(host_matches("db-?.example.com", "db-1.example.com"));
+        assert!(host_matches("db-1", "db-1.example.com"));
+        assert!(!host_matches("db-?", "db-12"));
+        assert!(!host_matches("web.*", "webX1"));
+    }
+
+    #[test]
+    fn test_host_identity() {
+        let entries: Vec<HostKey> = serde_yaml::from_str(
+            "- host: web-*\n  onepassworditem: op://Infra/web/key\n- host: db-*\n  key_file: keys/db.txt\n- host: broken\n",
+        )
+        .unwrap();
+        let dir = Path::new("/repo");
+        assert_eq!(
+            host_identity(&entries, "web-1", dir),
+            Ok(Some((
+                "web-*".to_string(),
+                HostIdentity::OnePassword("op://Infra/web/key".to_string())
+            )))
+        );
+        assert_eq!(
+            host_identity(&entries, "db-2", dir).unwrap().unwrap().1,
+            HostIdentity::KeyFile(PathBuf::from("/repo/keys/db.txt"))
+        );
+        assert_eq!(host_identity(&entries, "laptop", dir), Ok(None));
+        assert!(host_identity(&entries, "broken", dir).is_err());
+    }
+}
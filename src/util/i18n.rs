@@ -0,0 +1,213 @@
+//! Translations of user-facing messages. The Fluent catalogs in `locales/` are
+//! compiled in; `language:` in the user config picks one, else `LC_ALL`,
+//! `LC_MESSAGES` or `LANG`. Messages missing from a catalog fall back to
+//! English.
+
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue, concurrent::FluentBundle};
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+use super::user_config::read_user_config;
+
+/// Catalogs by language, English first as the fallback
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../../locales/en.ftl")),
+    ("de", include_str!("../../locales/de.ftl")),
+];
+
+struct Catalogs {
+    selected: Option<FluentBundle<FluentResource>>,
+    english: FluentBundle<FluentResource>,
+}
+
+static CATALOGS_LOADED: OnceLock<Catalogs> = OnceLock::new();
+
+/// The languages opsops has messages in
+pub fn available_languages() -> impl Iterator<Item = &'static str> {
+    CATALOGS.iter().map(|(language, _)| *language)
+}
+
+/// The language of a locale like `de_DE.UTF-8`, `None` for `C` and `POSIX`
+pub fn language_of(locale: &str) -> Option<String> {
+    let language = locale
+        .split(['_', '.', '@', '-'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if language.is_empty() || language == "c" || language == "posix" {
+        None
+    } else {
+        Some(language)
+    }
+}
+
+/// The language to translate into, see the module docs
+pub fn selected_language() -> String {
+    let from_env = || {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+    };
+    read_user_config()
+        .language
+        .or_else(from_env)
+        .and_then(|locale| language_of(&locale))
+        .filter(|language| available_languages().any(|l| l == language))
+        .unwrap_or_else(|| "en".to_string())
+}
+
+fn bundle(language: &str) -> Option<FluentBundle<FluentResource>> {
+    let (_, source) = CATALOGS.iter().find(|(l, _)| *l == language)?;
+    let id: LanguageIdentifier = language.parse().ok()?;
+    // The catalogs are compiled in, a syntax error is caught by the tests
+    let resource = FluentResource::try_new(source.to_string()).ok()?;
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // Isolation marks would end up in logs and terminals that don't hide them
+    bundle.set_use_isolating(false);
+    bundle.add_resource(resource).ok()?;
+    Some(bundle)
+}
+
+fn catalogs() -> &'static Catalogs {
+    CATALOGS_LOADED.get_or_init(|| {
+        // Unit tests assert on English messages, whatever the developer's locale
+        let language = if cfg!(test) {
+            "en".to_string()
+        } else {
+            selected_language()
+        };
+        Catalogs {
+            selected: (language != "en").then(|| bundle(&language)).flatten(),
+            english: bundle("en").expect("the English catalog is valid"),
+        }
+    })
+}
+
+fn format(bundle: &FluentBundle<FluentResource>, id: &str, args: &FluentArgs) -> Option<String> {
+    let pattern = bundle.get_message(id)?.value()?;
+    let mut errors = Vec::new();
+    let message = bundle.format_pattern(pattern, Some(args), &mut errors);
+    errors.is_empty().then(|| message.into_owned())
+}
+
+/// The message `id` with `args` filled in, in the selected language
+pub fn tr_args(id: &str, args: &[(&str, FluentValue)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+    let catalogs = catalogs();
+    catalogs
+        .selected
+        .as_ref()
+        .and_then(|bundle| format(bundle, id, &fluent_args))
+        .or_else(|| format(&catalogs.english, id, &fluent_args))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// The message `id` in the selected language
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+/// The message `id`, e.g. `read-failed`, about `path` failing with `error`
+pub fn failed_to(id: &str, path: &Path, error: impl Display) -> String {
+    tr_args(
+        id,
+        &[
+            ("path", path.display().to_string().into()),
+            ("error", error.to_string().into()),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CATALOGS, bundle, format, language_of};
+    use fluent_bundle::FluentArgs;
+    use regex::Regex;
+    use std::path::Path;
+
+    fn ids(source: &str) -> Vec<&str> {
+        source
+            .lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_alphabetic()))
+            .filter_map(|line| line.split_once(" =").map(|(id, _)| id))
+            .collect()
+    }
+
+    #[test]
+    fn test_catalogs_are_complete() {
+        let english = ids(CATALOGS[0].1);
+        for (language, source) in CATALOGS {
+            assert_eq!(ids(source), english, "{} differs from en", language);
+            assert!(bundle(language).is_some(), "{} doesn't parse", language);
+        }
+    }
+
+    /// The Rust sources of opsops, read at test time
+    fn sources() -> Vec<String> {
+        let mut sources = Vec::new();
+        let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("src")];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|e| e == "rs") {
+                    sources.push(std::fs::read_to_string(path).unwrap());
+                }
+            }
+        }
+        sources
+    }
+
+    #[test]
+    fn test_catalog_matches_the_sources() {
+        let english = ids(CATALOGS[0].1);
+        let sources = sources();
+        let used = Regex::new(r#"\b(?:tr|tr_args|failed_to)\(\s*"([a-z0-9-]+)""#).unwrap();
+        for source in &sources {
+            for id in used.captures_iter(source) {
+                assert!(english.contains(&&id[1]), "{} is not in en.ftl", &id[1]);
+            }
+        }
+        for id in english {
+            let literal = format!("\"{}\"", id);
+            assert!(
+                sources.iter().any(|source| source.contains(&literal)),
+                "{} is never used",
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let de = bundle("de").unwrap();
+        let mut args = FluentArgs::new();
+        args.set("path", "a.yaml");
+        assert_eq!(
+            format(&de, "encrypting-protected", &args),
+            None,
+            "missing arguments are errors"
+        );
+        args.set("reason", "it is the sops config");
+        assert_eq!(
+            format(&de, "encrypting-protected", &args).unwrap(),
+            "a.yaml wird verschlüsselt, obwohl it is the sops config (--allow-protected)"
+        );
+        assert!(format(&de, "no-such-message", &args).is_none());
+    }
+
+    #[test]
+    fn test_language_of() {
+        assert_eq!(language_of("de_DE.UTF-8").as_deref(), Some("de"));
+        assert_eq!(language_of("en").as_deref(), Some("en"));
+        assert_eq!(language_of("C.UTF-8"), None);
+        assert_eq!(language_of("POSIX"), None);
+    }
+}
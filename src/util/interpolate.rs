@@ -0,0 +1,145 @@
+//! `${VAR}` interpolation for config values, so one committed .sops.yaml can
+//! resolve to a different vault per developer:
+//!
+//! ```yaml
+//! onepassworditem: op://${OPSOPS_VAULT}/infra-age-key/private
+//! ```
+//!
+//! `${VAR:-default}` falls back to `default` when `VAR` is unset or empty and
+//! `$${` produces a literal `${`. A `$` not followed by `{` is kept as-is, so
+//! regex anchors like `\.env$` are unaffected.
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_yaml::Value;
+
+/// Expands `${VAR}` references in `input` using `lookup`
+fn interpolate_with<F: Fn(&str) -> Option<String>>(
+    input: &str,
+    lookup: &F,
+) -> Result<String, String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        if let Some(escaped) = after.strip_prefix("${") {
+            output.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = after.strip_prefix('{') {
+            let end = reference
+                .find('}')
+                .ok_or_else(|| format!("Unterminated '${{' in '{}'", input))?;
+            let (name, default) = match reference[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&reference[..end], None),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("Invalid variable name '{}' in '{}'", name, input));
+            }
+
+            match (lookup(name).filter(|v| !v.is_empty()), default) {
+                (Some(value), _) => output.push_str(&value),
+                (None, Some(default)) => output.push_str(default),
+                (None, None) => {
+                    return Err(format!(
+                        "Environment variable {} used in '{}' is not set",
+                        name, input
+                    ));
+                }
+            }
+            rest = &reference[end + 1..];
+        } else {
+            output.push('$');
+            rest = after;
+        }
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn interpolate_value<F: Fn(&str) -> Option<String>>(
+    value: &mut Value,
+    lookup: &F,
+) -> Result<(), String> {
+    match value {
+        Value::String(s) => *s = interpolate_with(s, lookup)?,
+        Value::Sequence(items) => {
+            for item in items {
+                interpolate_value(item, lookup)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (_, v) in map.iter_mut() {
+                interpolate_value(v, lookup)?;
+            }
+        }
+        Value::Tagged(tagged) => interpolate_value(&mut tagged.value, lookup)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Expands `${VAR}` references in every string value of a config section
+pub fn interpolate_config<T: Serialize + DeserializeOwned>(config: T) -> Result<T, String> {
+    let mut value =
+        serde_yaml::to_value(config).map_err(|e| format!("Failed to interpolate config: {}", e))?;
+    interpolate_value(&mut value, &|name| std::env::var(name).ok())?;
+    serde_yaml::from_value(value).map_err(|e| format!("Failed to interpolate config: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::Value;
+
+    use super::{interpolate_value, interpolate_with};
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "OPSOPS_VAULT" => Some("Infra".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let cases = [
+            (
+                "op://${OPSOPS_VAULT}/infra-age-key/private",
+                "op://Infra/infra-age-key/private",
+            ),
+            ("op://${MISSING:-Personal}/key", "op://Personal/key"),
+            ("${EMPTY:-fallback}", "fallback"),
+            ("$${OPSOPS_VAULT}", "${OPSOPS_VAULT}"),
+            ("\\.env$", "\\.env$"),
+            ("^(a|b)$|cost: $5", "^(a|b)$|cost: $5"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(interpolate_with(input, &lookup).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        let err = interpolate_with("op://${MISSING}/x", &lookup).unwrap_err();
+        assert!(err.contains("MISSING"), "{}", err);
+        assert!(interpolate_with("${OPSOPS_VAULT", &lookup).is_err());
+        assert!(interpolate_with("${NOT A NAME}", &lookup).is_err());
+    }
+
+    #[test]
+    fn test_interpolates_nested_values() {
+        let mut value: Value = serde_yaml::from_str(
+            "creation_rules:\n- path_regex: ^${OPSOPS_VAULT}/\n  key_groups:\n  - age:\n    - age1${MISSING:-x}\n",
+        )
+        .unwrap();
+        interpolate_value(&mut value, &lookup).unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&value).unwrap(),
+            "creation_rules:\n- path_regex: ^Infra/\n  key_groups:\n  - age:\n    - age1x\n"
+        );
+    }
+}
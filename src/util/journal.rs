@@ -0,0 +1,106 @@
+//! Progress of batch re-encryptions such as `drift --fix` and
+//! `key expire-sweep`, kept in `.opsops/journal/<name>.json` while they run.
+//! A run that gets interrupted leaves its journal behind, so the next one
+//! skips the files that were already finished instead of starting over.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::dirs::project_state_dir;
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+struct JournalState {
+    /// What the run does, e.g. the recipients it removes. A journal of a
+    /// different operation is discarded.
+    operation: String,
+    /// Project relative paths of the finished files
+    completed: BTreeSet<String>,
+}
+
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    state: JournalState,
+}
+
+impl Journal {
+    /// Opens the journal `name` of the project at `root`, continuing it if it
+    /// was left behind by an interrupted run of the same `operation`
+    pub fn open(root: &Path, name: &str, operation: &str) -> Journal {
+        let path = project_state_dir(root)
+            .join("journal")
+            .join(format!("{}.json", name));
+        let state = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<JournalState>(&contents).ok())
+            .filter(|state| state.operation == operation)
+            .unwrap_or_else(|| JournalState {
+                operation: operation.to_string(),
+                completed: BTreeSet::new(),
+            });
+        Journal { path, state }
+    }
+
+    /// Files finished by an earlier run
+    pub fn completed(&self) -> impl Iterator<Item = &str> {
+        self.state.completed.iter().map(String::as_str)
+    }
+
+    pub fn is_completed(&self, relative: &str) -> bool {
+        self.state.completed.contains(relative)
+    }
+
+    /// Records a finished file, writing the journal right away so it survives
+    /// the process being killed
+    pub fn complete(&mut self, relative: &str) -> Result<(), String> {
+        self.state.completed.insert(relative.to_string());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(&self.state)
+            .map_err(|e| format!("Failed to serialize the journal: {}", e))?;
+        // Write and rename, a half written journal would lose the progress
+        let partial = self.path.with_extension("json.partial");
+        fs::write(&partial, json)
+            .and_then(|_| fs::rename(&partial, &self.path))
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    /// Deletes the journal once the run completed
+    pub fn finish(self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::Journal;
+
+    #[test]
+    fn test_resumes_the_same_operation() {
+        let root = TempDir::new().unwrap();
+        let mut journal = Journal::open(root.path(), "expire-sweep", "rm age1a");
+        journal.complete("a.yaml").unwrap();
+        journal.complete("dir/b.yaml").unwrap();
+
+        let journal = Journal::open(root.path(), "expire-sweep", "rm age1a");
+        assert_eq!(
+            journal.completed().collect::<Vec<_>>(),
+            vec!["a.yaml", "dir/b.yaml"]
+        );
+        assert!(journal.is_completed("a.yaml"));
+
+        // Another operation starts over
+        let other = Journal::open(root.path(), "expire-sweep", "rm age1b");
+        assert_eq!(other.completed().count(), 0);
+
+        journal.finish();
+        let journal = Journal::open(root.path(), "expire-sweep", "rm age1a");
+        assert_eq!(journal.completed().count(), 0);
+    }
+}
@@ -0,0 +1,510 @@
+//! Validation of decrypted documents against the JSON Schema attached to their
+//! creation rule. Schemas are attached in `.opsops.yaml` by repeating the
+//! rule's `path_regex`:
+//!
+//! ```yaml
+//! schemas:
+//!   - path_regex: ^secrets/db\.yaml$
+//!     schema: schemas/db.json
+//! ```
+//!
+//! Only the keywords that catch structural mistakes are supported: `type`,
+//! `required`, `properties`, `additionalProperties`, `items`, `enum`, `const`,
+//! `pattern`, `minLength`/`maxLength`, `minimum`/`maximum`,
+//! `minItems`/`maxItems`, `allOf`, `anyOf` and local `$ref`s. Others are ignored.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{
+    document::parse_document,
+    file_picker::quiet_config,
+    opsops_config::read_opsops_config,
+    rule_match::{first_matching_rule, project_relative_path},
+    sops_config::config_dir,
+    sops_files::sops_file_type,
+    sops_structs::CreationRule,
+};
+use crate::GlobalContext;
+
+/// A schema attached to the creation rule with the same `path_regex`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct RuleSchema {
+    pub path_regex: String,
+    /// JSON or YAML file, relative to the project root
+    pub schema: String,
+}
+
+/// A value that doesn't match the schema, `pointer` is a JSON Pointer to it
+#[derive(Debug, PartialEq)]
+pub struct SchemaError {
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            &self.pointer
+        };
+        write!(f, "{}: {}", pointer, self.message)
+    }
+}
+
+/// The creation rules and the schemas attached to them
+pub struct SchemaIndex {
+    root: PathBuf,
+    rules: Vec<CreationRule>,
+    schemas: Vec<RuleSchema>,
+}
+
+impl SchemaIndex {
+    /// `None` if the project has no schemas
+    pub fn load(context: &GlobalContext) -> Result<Option<Self>, String> {
+        let schemas = read_opsops_config(context)?
+            .map(|c| c.schemas)
+            .unwrap_or_default();
+        if schemas.is_empty() {
+            return Ok(None);
+        }
+        let (Some(root), Some(config)) = (config_dir(context), quiet_config(context)) else {
+            return Ok(None);
+        };
+        Ok(Some(SchemaIndex {
+            root,
+            rules: config.creation_rules,
+            schemas,
+        }))
+    }
+
+    /// The schema file attached to the creation rule sops would use for `file`
+    pub fn schema_for(&self, file: &Path) -> Option<PathBuf> {
+        let relative = project_relative_path(&self.root, file)?;
+        let rule = first_matching_rule(&self.rules, &relative)
+            .and_then(|i| self.rules[i].path_regex.as_ref())?;
+        self.schemas
+            .iter()
+            .find(|s| &s.path_regex == rule)
+            .map(|s| self.root.join(&s.schema))
+    }
+}
+
+/// Reads a schema written in JSON or YAML
+pub fn read_schema(path: &Path) -> Result<Value, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read schema {}: {}", path.display(), e))?;
+    serde_yaml::from_str(&contents)
+        .map_err(|e| format!("Failed to parse schema {}: {}", path.display(), e))
+}
+
+/// Validates a decrypted document, as parsed by
+/// [`super::document::parse_document`], against `schema`
+pub fn validate_document(
+    document: &serde_yaml::Value,
+    schema: &Value,
+) -> Result<Vec<SchemaError>, String> {
+    let instance = serde_json::to_value(document)
+        .map_err(|e| format!("The document can't be validated: {}", e))?;
+    let mut errors = Vec::new();
+    validate(&instance, schema, schema, "", &mut errors);
+    Ok(errors)
+}
+
+/// Validates the decrypted `content` of `file` against the schema attached to
+/// its rule. `None` if the rule has no schema.
+pub fn check_rule_schema(
+    file: &Path,
+    content: &[u8],
+    context: &GlobalContext,
+) -> Result<Option<(PathBuf, Vec<SchemaError>)>, String> {
+    let Some(schema_path) = SchemaIndex::load(context)?.and_then(|i| i.schema_for(file)) else {
+        return Ok(None);
+    };
+    let errors = validate_content(&schema_path, file, content)?;
+    Ok(Some((schema_path, errors)))
+}
+
+/// Validates the decrypted `content` of `file` against the schema at `schema_path`
+pub fn validate_content(
+    schema_path: &Path,
+    file: &Path,
+    content: &[u8],
+) -> Result<Vec<SchemaError>, String> {
+    let schema = read_schema(schema_path)?;
+    let document = parse_document(content, sops_file_type(file))?;
+    validate_document(&document, &schema)
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn type_matches(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Resolves `#/...` references within the root schema
+fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    root.pointer(reference.strip_prefix('#')?)
+}
+
+/// A document that has every required property of `schema`, with its
+/// `default`, `const` or first `enum` value where given and an empty value of
+/// the right type otherwise. Objects without `required` get all properties.
+pub fn skeleton(schema: &Value) -> Value {
+    skeleton_of(schema, schema, 0)
+}
+
+fn skeleton_of(schema: &Value, root: &Value, depth: usize) -> Value {
+    // Recursive `$ref`s would never end
+    let Some(schema) = schema.as_object().filter(|_| depth < 16) else {
+        return Value::Null;
+    };
+    if let Some(target) = schema
+        .get("$ref")
+        .and_then(|r| r.as_str())
+        .and_then(|r| resolve(root, r))
+    {
+        return skeleton_of(target, root, depth + 1);
+    }
+    if let Some(value) = schema
+        .get("default")
+        .or_else(|| schema.get("const"))
+        .or_else(|| schema.get("enum").and_then(|e| e.get(0)))
+    {
+        return value.clone();
+    }
+    if let Some(parts) = schema.get("allOf").and_then(|a| a.as_array()) {
+        let mut merged = serde_json::Map::new();
+        for part in parts {
+            if let Value::Object(object) = skeleton_of(part, root, depth + 1) {
+                merged.extend(object);
+            }
+        }
+        return Value::Object(merged);
+    }
+    if let Some(first) = schema
+        .get("anyOf")
+        .and_then(|a| a.as_array())
+        .and_then(|a| a.first())
+    {
+        return skeleton_of(first, root, depth + 1);
+    }
+    let kind = match schema.get("type") {
+        Some(Value::String(name)) => name.as_str(),
+        Some(Value::Array(names)) => names.first().and_then(|n| n.as_str()).unwrap_or(""),
+        _ if schema.contains_key("properties") => "object",
+        _ => "",
+    };
+    match kind {
+        "object" => {
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            let names: Vec<&str> = match schema.get("required").and_then(|r| r.as_array()) {
+                Some(required) => required.iter().filter_map(|n| n.as_str()).collect(),
+                None => properties
+                    .map(|p| p.keys().map(String::as_str).collect())
+                    .unwrap_or_default(),
+            };
+            let object = names
+                .into_iter()
+                .map(|name| {
+                    let value = properties
+                        .and_then(|p| p.get(name))
+                        .map_or(Value::String(String::new()), |property| {
+                            skeleton_of(property, root, depth + 1)
+                        });
+                    (name.to_string(), value)
+                })
+                .collect();
+            Value::Object(object)
+        }
+        "array" => Value::Array(Vec::new()),
+        "integer" | "number" => Value::from(0),
+        "boolean" => Value::Bool(false),
+        "null" => Value::Null,
+        _ => Value::String(String::new()),
+    }
+}
+
+fn validate(
+    value: &Value,
+    schema: &Value,
+    root: &Value,
+    pointer: &str,
+    errors: &mut Vec<SchemaError>,
+) {
+    let Some(schema) = schema.as_object() else {
+        // `true`/`false` schemas
+        if schema == &Value::Bool(false) {
+            errors.push(SchemaError {
+                pointer: pointer.to_string(),
+                message: "is not allowed".to_string(),
+            });
+        }
+        return;
+    };
+    let mut fail = |message: String| {
+        errors.push(SchemaError {
+            pointer: pointer.to_string(),
+            message,
+        })
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(|r| r.as_str()) {
+        match resolve(root, reference) {
+            Some(target) => validate(value, target, root, pointer, errors),
+            None => fail(format!("unresolvable $ref '{}'", reference)),
+        }
+        return;
+    }
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(|n| n.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|n| type_matches(value, n)) {
+            fail(format!("expected {}", names.join(" or ")));
+            return;
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(|e| e.as_array())
+        && !allowed.contains(value)
+    {
+        fail(format!(
+            "must be one of {}",
+            allowed
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if let Some(constant) = schema.get("const")
+        && constant != value
+    {
+        fail(format!("must be {}", constant));
+    }
+
+    if let Some(s) = value.as_str() {
+        let length = s.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(|m| m.as_u64())
+            && length < min
+        {
+            fail(format!("must be at least {} characters long", min));
+        }
+        if let Some(max) = schema.get("maxLength").and_then(|m| m.as_u64())
+            && length > max
+        {
+            fail(format!("must be at most {} characters long", max));
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(|p| p.as_str()) {
+            match regex::Regex::new(pattern) {
+                Ok(re) if !re.is_match(s) => fail(format!("must match '{}'", pattern)),
+                Ok(_) => {}
+                Err(_) => fail(format!("invalid pattern '{}' in schema", pattern)),
+            }
+        }
+    }
+    if let Some(n) = value.as_f64() {
+        if let Some(min) = schema.get("minimum").and_then(|m| m.as_f64())
+            && n < min
+        {
+            fail(format!("must be at least {}", min));
+        }
+        if let Some(max) = schema.get("maximum").and_then(|m| m.as_f64())
+            && n > max
+        {
+            fail(format!("must be at most {}", max));
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        if let Some(min) = schema.get("minItems").and_then(|m| m.as_u64())
+            && (items.len() as u64) < min
+        {
+            fail(format!("must have at least {} items", min));
+        }
+        if let Some(max) = schema.get("maxItems").and_then(|m| m.as_u64())
+            && (items.len() as u64) > max
+        {
+            fail(format!("must have at most {} items", max));
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                validate(
+                    item,
+                    item_schema,
+                    root,
+                    &format!("{}/{}", pointer, i),
+                    errors,
+                );
+            }
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !object.contains_key(key) {
+                    errors.push(SchemaError {
+                        pointer: format!("{}/{}", pointer, escape_pointer(key)),
+                        message: "is required".to_string(),
+                    });
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, child) in object {
+            let child_pointer = format!("{}/{}", pointer, escape_pointer(key));
+            match properties.and_then(|p| p.get(key)) {
+                Some(child_schema) => validate(child, child_schema, root, &child_pointer, errors),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => errors.push(SchemaError {
+                        pointer: child_pointer,
+                        message: "is not an allowed property".to_string(),
+                    }),
+                    Some(additional) => validate(child, additional, root, &child_pointer, errors),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    if let Some(all) = schema.get("allOf").and_then(|a| a.as_array()) {
+        for sub in all {
+            validate(value, sub, root, pointer, errors);
+        }
+    }
+    if let Some(any) = schema.get("anyOf").and_then(|a| a.as_array())
+        && !any.iter().any(|sub| {
+            let mut sub_errors = Vec::new();
+            validate(value, sub, root, pointer, &mut sub_errors);
+            sub_errors.is_empty()
+        })
+    {
+        errors.push(SchemaError {
+            pointer: pointer.to_string(),
+            message: "matches none of the anyOf schemas".to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{SchemaError, skeleton, validate_document};
+
+    fn errors(yaml: &str, schema: serde_json::Value) -> Vec<String> {
+        let document = serde_yaml::from_str(yaml).unwrap();
+        validate_document(&document, &schema)
+            .unwrap()
+            .iter()
+            .map(SchemaError::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_required_and_types() {
+        let schema = json!({
+            "type": "object",
+            "required": ["db"],
+            "properties": {
+                "db": {
+                    "type": "object",
+                    "required": ["user", "password"],
+                    "properties": {
+                        "port": {"type": "integer", "minimum": 1},
+                        "password": {"type": "string", "minLength": 12}
+                    }
+                }
+            }
+        });
+        assert!(errors("db: {user: a, password: long-enough-pw}\n", schema.clone()).is_empty());
+        assert_eq!(
+            errors("other: 1\n", schema.clone()),
+            vec!["/db: is required"]
+        );
+        assert_eq!(
+            errors("db: {user: a, password: short, port: web}\n", schema),
+            vec![
+                "/db/password: must be at least 12 characters long",
+                "/db/port: expected integer"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_additional_properties_and_refs() {
+        let schema = json!({
+            "$defs": {"token": {"type": "string", "pattern": "^ghp_"}},
+            "properties": {"tokens": {"type": "array", "items": {"$ref": "#/$defs/token"}}},
+            "additionalProperties": false
+        });
+        assert_eq!(
+            errors("tokens: [ghp_a, nope]\nextra/key: 1\n", schema),
+            vec![
+                "/extra~1key: is not an allowed property",
+                "/tokens/1: must match '^ghp_'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enum_and_any_of() {
+        let schema = json!({
+            "properties": {
+                "env": {"enum": ["prod", "staging"]},
+                "port": {"anyOf": [{"type": "integer"}, {"type": "string", "pattern": "^[0-9]+$"}]}
+            }
+        });
+        assert!(errors("env: prod\nport: \"80\"\n", schema.clone()).is_empty());
+        assert_eq!(
+            errors("env: dev\nport: http\n", schema),
+            vec![
+                "/env: must be one of \"prod\", \"staging\"",
+                "/port: matches none of the anyOf schemas"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skeleton() {
+        let schema = json!({
+            "$defs": {"port": {"type": "integer", "default": 5432}},
+            "type": "object",
+            "required": ["db", "env", "tags"],
+            "properties": {
+                "db": {
+                    "properties": {"password": {"type": "string"}, "port": {"$ref": "#/$defs/port"}}
+                },
+                "env": {"enum": ["prod", "staging"]},
+                "tags": {"type": "array"},
+                "optional": {"type": "string"}
+            }
+        });
+        let document = skeleton(&schema);
+        assert_eq!(
+            document,
+            json!({"db": {"password": "", "port": 5432}, "env": "prod", "tags": []})
+        );
+        let yaml: serde_yaml::Value = serde_json::from_value(document).unwrap();
+        assert!(errors(&serde_yaml::to_string(&yaml).unwrap(), schema).is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+//! The user's registry of which age key protects which project, used by
+//! `doctor --org` to discourage one key for every repository. It is a plain
+//! YAML file mapping a project (its `origin` URL, else its path) to the public
+//! key, so it can be maintained by hand or shared within a team.
+
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{dirs, user_config::read_user_config};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct KeyRegistry {
+    /// Project to public key
+    #[serde(default)]
+    pub projects: BTreeMap<String, String>,
+}
+
+impl KeyRegistry {
+    /// Other projects protected by `public_key`
+    pub fn projects_sharing(&self, project: &str, public_key: &str) -> Vec<&str> {
+        self.projects
+            .iter()
+            .filter(|(other, key)| *other != project && key.as_str() == public_key)
+            .map(|(other, _)| other.as_str())
+            .collect()
+    }
+}
+
+/// `key_registry:` from the user config, else `projects.yaml` in [`dirs::config_dir`]
+pub fn key_registry_path() -> Option<PathBuf> {
+    match read_user_config().key_registry {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(dirs::config_dir()?.join("projects.yaml")),
+    }
+}
+
+/// Reads the registry, empty if it doesn't exist yet
+pub fn read_key_registry() -> Result<KeyRegistry, String> {
+    let Some(path) = key_registry_path().filter(|p| p.is_file()) else {
+        return Ok(KeyRegistry::default());
+    };
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_yaml::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+pub fn write_key_registry(registry: &KeyRegistry) -> Result<PathBuf, String> {
+    let path = key_registry_path().ok_or("Could not determine user config directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let yaml = serde_yaml::to_string(registry)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    fs::write(&path, yaml).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// How the project at `root` is named in the registry: the URL of its `origin`
+/// remote, which is the same for every clone, else its path
+pub fn project_id(root: &Path) -> String {
+    Repository::open(root)
+        .ok()
+        .and_then(|repo| {
+            repo.find_remote("origin")
+                .ok()
+                .and_then(|remote| remote.url().map(str::to_string))
+        })
+        // Without the trailing slash git adds to work directories
+        .unwrap_or_else(|| root.components().collect::<PathBuf>().display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeyRegistry;
+
+    #[test]
+    fn test_projects_sharing() {
+        let registry: KeyRegistry = serde_yaml::from_str(
+            "projects:\n  git@github.com:org/infra.git: age1one\n  git@github.com:org/app.git: age1one\n  /home/me/blog: age1two\n",
+        )
+        .unwrap();
+        assert_eq!(
+            registry.projects_sharing("git@github.com:org/infra.git", "age1one"),
+            ["git@github.com:org/app.git"]
+        );
+        assert!(
+            registry
+                .projects_sharing("/home/me/blog", "age1two")
+                .is_empty()
+        );
+    }
+}
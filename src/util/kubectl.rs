@@ -0,0 +1,48 @@
+//! kubectl for the cluster integrations. Secrets are only ever handed to it on
+//! stdin, never written to disk.
+
+use age::secrecy::{ExposeSecret, SecretString};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::Instant;
+use zeroize::Zeroize;
+
+use super::session_record;
+
+pub fn kubectl() -> Result<PathBuf, String> {
+    which::which("kubectl").map_err(|_| {
+        "'kubectl' is not installed or not in PATH. Please install it first.".to_string()
+    })
+}
+
+/// An Opaque Secret with a single `key` holding `value`
+pub fn secret_manifest(name: &str, namespace: &str, key: &str, value: &SecretString) -> String {
+    format!(
+        "apiVersion: v1\nkind: Secret\nmetadata:\n  name: {}\n  namespace: {}\ntype: Opaque\nstringData:\n  {}: {}\n",
+        name,
+        namespace,
+        key,
+        value.expose_secret()
+    )
+}
+
+/// Pipes `manifest` into `kubectl apply -f -` and zeroizes it afterwards. With
+/// `dry_run` kubectl only validates it.
+pub fn apply_manifest(mut manifest: String, dry_run: bool) -> Result<ExitStatus, String> {
+    let mut command = Command::new(kubectl()?);
+    command.args(["apply", "-f", "-"]);
+    if dry_run {
+        command.arg("--dry-run=client");
+    }
+    let started = Instant::now();
+    let result = command.stdin(Stdio::piped()).spawn().and_then(|mut child| {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(manifest.as_bytes())?;
+        }
+        child.wait()
+    });
+    manifest.zeroize();
+    session_record::record(&command, started, result.as_ref().map(ExitStatus::code));
+    result.map_err(|e| format!("Failed to run kubectl: {}", e))
+}
@@ -0,0 +1,222 @@
+//! Markdown notes with credentials in them, like runbooks. Only the YAML front
+//! matter and fenced `secret` blocks are encrypted so the prose stays readable:
+//! the blocks move into the front matter under [`SECRET_BLOCKS_KEY`], sops
+//! encrypts the front matter as a YAML document and the blocks are left with a
+//! placeholder pointing at their entry.
+
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+use zeroize::Zeroize;
+
+use super::sops_command::run_sops_on_buffer;
+use crate::GlobalContext;
+
+/// Front matter key holding the contents of the secret blocks
+pub const SECRET_BLOCKS_KEY: &str = "opsops_secret_blocks";
+
+/// Info string of fenced code blocks that get encrypted
+const SECRET_FENCE: &str = "```secret";
+const FENCE: &str = "```";
+const FRONT_MATTER_DELIMITER: &str = "---";
+
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| e == "md" || e == "markdown")
+}
+
+/// Splits a note into its front matter, without the delimiters, and the rest
+pub fn split_front_matter(contents: &str) -> (Option<&str>, &str) {
+    let Some(rest) = contents
+        .strip_prefix(FRONT_MATTER_DELIMITER)
+        .and_then(|r| r.strip_prefix('\n'))
+    else {
+        return (None, contents);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == FRONT_MATTER_DELIMITER {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, contents)
+}
+
+fn placeholder(index: usize) -> String {
+    format!("{}[{}]\n", SECRET_BLOCKS_KEY, index)
+}
+
+/// Moves the secret blocks of a plaintext note into its front matter. Returns
+/// the YAML sops has to encrypt and the body with placeholders.
+fn extract_secrets(contents: &str) -> Result<(String, String), String> {
+    let (front_matter, body) = split_front_matter(contents);
+    let mut mapping: Mapping = match front_matter {
+        Some(yaml) if !yaml.trim().is_empty() => serde_yaml::from_str(yaml)
+            .map_err(|e| format!("The front matter is not a YAML map: {}", e))?,
+        _ => Mapping::new(),
+    };
+    if mapping.contains_key(SECRET_BLOCKS_KEY) {
+        return Err(format!(
+            "The front matter already has a '{}' key",
+            SECRET_BLOCKS_KEY
+        ));
+    }
+
+    let mut blocks = Vec::new();
+    let mut current: Option<String> = None;
+    let mut rest = String::new();
+    for line in body.split_inclusive('\n') {
+        match &mut current {
+            Some(block) if line.trim() == FENCE => {
+                rest.push_str(&placeholder(blocks.len()));
+                rest.push_str(line);
+                blocks.push(Value::String(std::mem::take(block)));
+                current = None;
+            }
+            Some(block) => block.push_str(line),
+            None => {
+                if line.trim_end() == SECRET_FENCE {
+                    current = Some(String::new());
+                }
+                rest.push_str(line);
+            }
+        }
+    }
+    if current.is_some() {
+        return Err("A secret block is missing its closing fence".to_string());
+    }
+    if !blocks.is_empty() {
+        mapping.insert(SECRET_BLOCKS_KEY.into(), Value::Sequence(blocks));
+    }
+    if mapping.is_empty() {
+        return Err("The note has no front matter or secret blocks to encrypt".to_string());
+    }
+
+    let yaml = serde_yaml::to_string(&mapping)
+        .map_err(|e| format!("Failed to serialize the front matter: {}", e))?;
+    Ok((yaml, rest))
+}
+
+/// Puts the secret blocks from decrypted front matter back into `body`
+fn restore_secrets(front_matter: &str, body: &str) -> Result<String, String> {
+    let mut mapping: Mapping = serde_yaml::from_str(front_matter)
+        .map_err(|e| format!("Failed to parse the decrypted front matter: {}", e))?;
+    let blocks: Vec<String> = match mapping.remove(SECRET_BLOCKS_KEY) {
+        Some(value) => serde_yaml::from_value(value)
+            .map_err(|e| format!("Invalid '{}': {}", SECRET_BLOCKS_KEY, e))?,
+        None => Vec::new(),
+    };
+
+    let mut note = String::new();
+    if !mapping.is_empty() {
+        let yaml = serde_yaml::to_string(&mapping)
+            .map_err(|e| format!("Failed to serialize the front matter: {}", e))?;
+        note.push_str(&format!(
+            "{}\n{}{}\n",
+            FRONT_MATTER_DELIMITER, yaml, FRONT_MATTER_DELIMITER
+        ));
+    }
+    let mut in_secret_block = false;
+    for line in body.split_inclusive('\n') {
+        if in_secret_block
+            && let Some(block) = (0..blocks.len())
+                .find(|i| placeholder(*i) == line)
+                .map(|i| &blocks[i])
+        {
+            note.push_str(block);
+            continue;
+        }
+        if line.trim_end() == SECRET_FENCE {
+            in_secret_block = true;
+        } else if line.trim() == FENCE {
+            in_secret_block = false;
+        }
+        note.push_str(line);
+    }
+    Ok(note)
+}
+
+/// Encrypts the front matter and secret blocks of the plaintext note at `path`
+pub fn encrypt_note(
+    contents: &str,
+    path: &Path,
+    context: &GlobalContext,
+) -> Result<String, String> {
+    let (mut yaml, body) = extract_secrets(contents)?;
+    let encrypted = run_sops_on_buffer("--encrypt", "yaml", path, &yaml, context);
+    yaml.zeroize();
+    Ok(format!(
+        "{}\n{}{}\n{}",
+        FRONT_MATTER_DELIMITER, encrypted?, FRONT_MATTER_DELIMITER, body
+    ))
+}
+
+/// Decrypts a note encrypted by [`encrypt_note`]
+pub fn decrypt_note(
+    contents: &str,
+    path: &Path,
+    context: &GlobalContext,
+) -> Result<String, String> {
+    let (Some(front_matter), body) = split_front_matter(contents) else {
+        return Err("The note has no encrypted front matter".to_string());
+    };
+    let mut yaml = run_sops_on_buffer("--decrypt", "yaml", path, front_matter, context)?;
+    let note = restore_secrets(&yaml, body);
+    yaml.zeroize();
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_secrets, is_markdown, restore_secrets, split_front_matter};
+    use std::path::Path;
+
+    const NOTE: &str = "---\ndb_password: hunter2\n---\n# Restore\n\nLog in with\n\n```secret\nadmin / s3cret\nline two\n```\n\n```sh\npg_restore\n```\n";
+
+    #[test]
+    fn test_is_markdown() {
+        assert!(is_markdown(Path::new("docs/runbook.md")));
+        assert!(is_markdown(Path::new("NOTES.Markdown")));
+        assert!(!is_markdown(Path::new("secrets.yaml")));
+    }
+
+    #[test]
+    fn test_split_front_matter() {
+        assert_eq!(
+            split_front_matter("---\na: b\n---\nbody\n"),
+            (Some("a: b\n"), "body\n")
+        );
+        assert_eq!(split_front_matter("# Title\n"), (None, "# Title\n"));
+        assert_eq!(split_front_matter("---\nnever closed\n").0, None);
+    }
+
+    #[test]
+    fn test_extract_secrets() {
+        let (yaml, body) = extract_secrets(NOTE).unwrap();
+        assert_eq!(
+            yaml,
+            "db_password: hunter2\nopsops_secret_blocks:\n- |\n  admin / s3cret\n  line two\n"
+        );
+        assert!(!body.contains("s3cret"));
+        assert!(body.contains("```secret\nopsops_secret_blocks[0]\n```\n"));
+        assert!(body.contains("```sh\npg_restore\n```\n"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (yaml, body) = extract_secrets(NOTE).unwrap();
+        assert_eq!(restore_secrets(&yaml, &body).unwrap(), NOTE);
+
+        let blocks_only = "# Title\n```secret\ntoken\n```\n";
+        let (yaml, body) = extract_secrets(blocks_only).unwrap();
+        assert_eq!(restore_secrets(&yaml, &body).unwrap(), blocks_only);
+    }
+
+    #[test]
+    fn test_extract_secrets_errors() {
+        assert!(extract_secrets("# Nothing secret\n").is_err());
+        assert!(extract_secrets("```secret\nunterminated\n").is_err());
+        assert!(extract_secrets("---\nopsops_secret_blocks: []\n---\n").is_err());
+    }
+}
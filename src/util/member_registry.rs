@@ -0,0 +1,252 @@
+//! The team's shared registry of member public keys. `registry sync` pulls it
+//! from a git repository or a 1Password secure note and caches it, so
+//! recipients are added by name (`key add-recipient --member alice`) instead
+//! of pasting keys through chat. The registry is a YAML file:
+//!
+//! ```yaml
+//! members:
+//!   alice:
+//!     age: age1...
+//!     email: alice@example.com
+//! ```
+
+use git2::{Cred, FetchOptions, RemoteCallbacks, build::RepoBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use super::{
+    dirs,
+    op::op_command,
+    op_key::validate_age_recipients,
+    op_rate_limit::run_op,
+    op_reference::{OpDocument, OpReference},
+};
+
+/// File read from a git source that doesn't name one
+pub const DEFAULT_REGISTRY_FILE: &str = "members.yaml";
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Member {
+    /// Public key, or several separated by commas
+    pub age: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct MemberRegistry {
+    /// Where the cached registry was synced from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub members: BTreeMap<String, Member>,
+}
+
+impl MemberRegistry {
+    /// Parses a registry, rejecting members without valid public keys
+    pub fn parse(yaml: &str) -> Result<Self, String> {
+        let registry: MemberRegistry = serde_yaml::from_str(yaml)
+            .map_err(|e| format!("Failed to parse the registry: {}", e))?;
+        for (name, member) in &registry.members {
+            validate_age_recipients(&member.age)
+                .map_err(|e| format!("Invalid key of member {}: {}", name, e))?;
+        }
+        Ok(registry)
+    }
+
+    /// The public keys of the member `name`
+    pub fn recipients_of(&self, name: &str) -> Result<&str, String> {
+        self.members
+            .get(name)
+            .map(|member| member.age.as_str())
+            .ok_or_else(|| {
+                format!(
+                    "No member '{}' in the registry. Known members: {}",
+                    name,
+                    self.members
+                        .keys()
+                        .map(String::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+
+    /// The member a public key belongs to
+    pub fn owner_of(&self, public_key: &str) -> Option<&str> {
+        self.members
+            .iter()
+            .find(|(_, member)| member.age.split(',').any(|key| key.trim() == public_key))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Where a registry comes from
+#[derive(Debug, PartialEq)]
+pub enum RegistrySource {
+    /// A field read with `op read`
+    OnePassword(String),
+    /// A file in a git repository
+    Git { url: String, path: String },
+}
+
+impl RegistrySource {
+    /// Parses `op://<vault>/<note>` (the note's text), `op://<vault>/<item>/<field>`
+    /// or `<git url>[#<path>]`
+    pub fn parse(source: &str) -> Result<Self, String> {
+        if source.starts_with("op://") {
+            if let Ok(note) = source.parse::<OpDocument>() {
+                return Ok(RegistrySource::OnePassword(format!("{}/notesPlain", note)));
+            }
+            return source
+                .parse::<OpReference>()
+                .map(|reference| RegistrySource::OnePassword(reference.to_string()))
+                .map_err(|e| format!("Invalid 1Password reference: {}", e));
+        }
+        let (url, path) = match source.rsplit_once('#') {
+            Some((url, path)) if !path.is_empty() => (url, path),
+            _ => (source.trim_end_matches('#'), DEFAULT_REGISTRY_FILE),
+        };
+        if url.is_empty() {
+            return Err("The registry source is empty".to_string());
+        }
+        Ok(RegistrySource::Git {
+            url: url.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// Downloads the registry
+    pub fn fetch(&self) -> Result<String, String> {
+        match self {
+            RegistrySource::OnePassword(reference) => {
+                let output = run_op(op_command().args(["read", reference]))
+                    .map_err(|e| format!("Failed to execute 1Password CLI: {}", e))?;
+                if !output.status.success() {
+                    return Err(format!(
+                        "1Password CLI returned an error: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+                Ok(String::from_utf8_lossy(&output.stdout).to_string())
+            }
+            RegistrySource::Git { url, path } => read_from_git(url, path),
+        }
+    }
+}
+
+/// Clones `url` into a temporary bare repository and reads `path` from its HEAD
+fn read_from_git(url: &str, path: &str) -> Result<String, String> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| {
+        if allowed.is_ssh_key() {
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        let config = git2::Config::open_default()?;
+        Cred::credential_helper(&config, url, username)
+    });
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+
+    let dir = TempDir::new().map_err(|e| format!("Failed to create a directory: {}", e))?;
+    let repo = RepoBuilder::new()
+        .bare(true)
+        .fetch_options(fetch_options)
+        .clone(url, dir.path())
+        .map_err(|e| format!("Failed to clone {}: {}", url, e.message()))?;
+    let tree = repo
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .map_err(|e| format!("Failed to read {}: {}", url, e.message()))?;
+    let blob = tree
+        .get_path(Path::new(path))
+        .and_then(|entry| entry.to_object(&repo))
+        .and_then(|object| object.peel_to_blob())
+        .map_err(|_| format!("{} has no file {}", url, path))?;
+    String::from_utf8(blob.content().to_vec()).map_err(|_| format!("{} is not UTF-8", path))
+}
+
+/// The cached registry: `members.yaml` in [`dirs::cache_dir`]
+pub fn member_registry_path() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join(DEFAULT_REGISTRY_FILE))
+}
+
+/// Reads the cached registry, empty if it was never synced
+pub fn read_member_registry() -> Result<MemberRegistry, String> {
+    let Some(path) = member_registry_path().filter(|p| p.is_file()) else {
+        return Ok(MemberRegistry::default());
+    };
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_yaml::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+pub fn write_member_registry(registry: &MemberRegistry) -> Result<PathBuf, String> {
+    let path = member_registry_path().ok_or("Could not determine user cache directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let yaml = serde_yaml::to_string(registry)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    fs::write(&path, yaml).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemberRegistry, RegistrySource};
+
+    const ALICE: &str = "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p";
+    const BOB: &str = "age1lggyhqrw2nlhcxprm67z43rta597azn8gknawjehu9d9dl0jq3yqqvfafg";
+
+    #[test]
+    fn test_parse_registry() {
+        let registry = MemberRegistry::parse(&format!(
+            "members:\n  alice:\n    age: {}\n    email: alice@example.com\n  bob:\n    age: {}\n",
+            ALICE, BOB
+        ))
+        .unwrap();
+        assert_eq!(registry.recipients_of("alice").unwrap(), ALICE);
+        assert!(
+            registry
+                .recipients_of("carol")
+                .unwrap_err()
+                .contains("Known members: alice, bob")
+        );
+        assert_eq!(registry.owner_of(BOB), Some("bob"));
+        assert_eq!(registry.owner_of("age1unknown"), None);
+
+        assert!(MemberRegistry::parse("members:\n  eve:\n    age: AGE-SECRET-KEY-1ABC\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            RegistrySource::parse("op://Team/Age keys").unwrap(),
+            RegistrySource::OnePassword("op://Team/Age keys/notesPlain".to_string())
+        );
+        assert_eq!(
+            RegistrySource::parse("op://Team/Age keys/registry").unwrap(),
+            RegistrySource::OnePassword("op://Team/Age keys/registry".to_string())
+        );
+        assert_eq!(
+            RegistrySource::parse("git@github.com:acme/keys.git#team/members.yaml").unwrap(),
+            RegistrySource::Git {
+                url: "git@github.com:acme/keys.git".to_string(),
+                path: "team/members.yaml".to_string()
+            }
+        );
+        assert_eq!(
+            RegistrySource::parse("https://github.com/acme/keys").unwrap(),
+            RegistrySource::Git {
+                url: "https://github.com/acme/keys".to_string(),
+                path: "members.yaml".to_string()
+            }
+        );
+    }
+}
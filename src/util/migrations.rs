@@ -0,0 +1,240 @@
+//! Upgrades project configs written by older versions of opsops. Every change
+//! to the layout of .sops.yaml or `.opsops.yaml` gets a [`Migration`] here and
+//! bumps [`CURRENT_VERSION`], which is stored as `version:` in `.opsops.yaml`.
+//! Migrations run automatically the first time a newer opsops reads the
+//! project, the previous files are kept in `.opsops/migrations/`.
+
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::Path;
+
+use super::{
+    dirs::project_state_dir,
+    i18n::tr_args,
+    opsops_config::{OPSOPS_CONFIG_FILE, opsops_config_path},
+    print_status::print_info,
+    sops_config::sops_config_path,
+};
+use crate::GlobalContext;
+
+/// Schema version written by this build
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Both project config files as plain YAML, `Value::Null` for a missing file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectConfigs {
+    pub sops: Value,
+    pub opsops: Value,
+}
+
+struct Migration {
+    /// Version the project is at after this migration
+    to: u32,
+    description: &'static str,
+    /// Returns whether anything changed
+    apply: fn(&mut ProjectConfigs) -> bool,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    to: 1,
+    description: "moved onepassworditem from .sops.yaml to .opsops.yaml",
+    apply: move_op_item,
+}];
+
+fn mapping(value: &mut Value) -> Option<&mut Mapping> {
+    if value.is_null() {
+        *value = Value::Mapping(Mapping::new());
+    }
+    value.as_mapping_mut()
+}
+
+/// v0 kept the 1Password reference in .sops.yaml, next to settings sops owns
+fn move_op_item(configs: &mut ProjectConfigs) -> bool {
+    let Some(item) = configs
+        .sops
+        .as_mapping_mut()
+        .and_then(|sops| sops.remove("onepassworditem"))
+    else {
+        return false;
+    };
+    let Some(opsops) = mapping(&mut configs.opsops) else {
+        return true;
+    };
+    // A reference already in .opsops.yaml is newer than the leftover one
+    let keep = item.as_str().is_some_and(|s| !s.is_empty());
+    if keep && !opsops.contains_key("onepassworditem") {
+        opsops.insert("onepassworditem".into(), item);
+    }
+    true
+}
+
+/// The version a project is at, 0 for projects without `.opsops.yaml`
+pub fn config_version(opsops: &Value) -> u32 {
+    opsops
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(0, |v| v as u32)
+}
+
+/// Runs every migration newer than the project's version and stamps the result
+/// with [`CURRENT_VERSION`]. Returns the descriptions of migrations that changed
+/// something.
+pub fn run_migrations(configs: &mut ProjectConfigs) -> Result<Vec<&'static str>, String> {
+    let version = config_version(&configs.opsops);
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "{} is at version {}, this opsops only supports up to version {}. Please upgrade opsops.",
+            OPSOPS_CONFIG_FILE, version, CURRENT_VERSION
+        ));
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.to > version) {
+        if (migration.apply)(configs) {
+            applied.push(migration.description);
+        }
+    }
+    if !applied.is_empty()
+        && let Some(opsops) = mapping(&mut configs.opsops)
+    {
+        opsops.insert("version".into(), CURRENT_VERSION.into());
+    }
+    Ok(applied)
+}
+
+fn read_yaml(path: &Path) -> Result<Value, String> {
+    if !path.is_file() {
+        return Ok(Value::Null);
+    }
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_yaml::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Copies `path` to `<project>/.opsops/migrations/<name>.v<version>.bak`
+fn backup(path: &Path, version: u32) -> Result<(), String> {
+    let root = path.parent().unwrap_or(Path::new("."));
+    let dir = project_state_dir(root).join("migrations");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let target = dir.join(format!("{}.v{}.bak", name, version));
+    fs::copy(path, &target)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to back up {}: {}", path.display(), e))
+}
+
+fn write_yaml(path: &Path, value: &Value) -> Result<(), String> {
+    let yaml = serde_yaml::to_string(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    fs::write(path, yaml).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Brings the project's config files up to [`CURRENT_VERSION`], backing up
+/// every file it rewrites. Does nothing for up to date or missing configs.
+pub fn migrate_project(context: &GlobalContext) -> Result<(), String> {
+    // Read-only sessions work with the configs as they are
+    if context.read_only {
+        return Ok(());
+    }
+    let (Some(sops_path), Some(opsops_path)) =
+        (sops_config_path(context), opsops_config_path(context))
+    else {
+        return Ok(());
+    };
+    let original = ProjectConfigs {
+        sops: read_yaml(&sops_path)?,
+        opsops: read_yaml(&opsops_path)?,
+    };
+    let version = config_version(&original.opsops);
+    let mut configs = original.clone();
+    let applied = run_migrations(&mut configs)?;
+    if applied.is_empty() {
+        return Ok(());
+    }
+
+    for (path, before, after) in [
+        (&sops_path, &original.sops, &configs.sops),
+        (&opsops_path, &original.opsops, &configs.opsops),
+    ] {
+        if before == after {
+            continue;
+        }
+        if path.is_file() {
+            backup(path, version)?;
+        }
+        write_yaml(path, after)?;
+    }
+
+    for description in applied {
+        print_info(tr_args(
+            "migrated-config",
+            &[("description", description.into())],
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::Value;
+
+    use super::{CURRENT_VERSION, ProjectConfigs, config_version, run_migrations};
+
+    fn project(sops: &str, opsops: &str) -> ProjectConfigs {
+        ProjectConfigs {
+            sops: serde_yaml::from_str(sops).unwrap(),
+            opsops: serde_yaml::from_str(opsops).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_moves_op_item_out_of_sops_yaml() {
+        let mut configs = project(
+            "creation_rules:\n- path_regex: .*\nonepassworditem: op://V/I/F\n",
+            "",
+        );
+        let applied = run_migrations(&mut configs).unwrap();
+
+        assert_eq!(applied.len(), 1);
+        assert!(configs.sops.get("onepassworditem").is_none());
+        assert!(configs.sops.get("creation_rules").is_some());
+        assert_eq!(configs.opsops["onepassworditem"], "op://V/I/F");
+        assert_eq!(config_version(&configs.opsops), CURRENT_VERSION);
+    }
+
+    #[test]
+    fn test_keeps_existing_op_item_and_drops_empty_one() {
+        let mut configs = project(
+            "onepassworditem: op://Old/I/F\n",
+            "onepassworditem: op://New/I/F\n",
+        );
+        run_migrations(&mut configs).unwrap();
+        assert_eq!(configs.opsops["onepassworditem"], "op://New/I/F");
+
+        let mut configs = project("onepassworditem: ''\n", "");
+        run_migrations(&mut configs).unwrap();
+        assert!(configs.opsops.get("onepassworditem").is_none());
+        assert!(configs.sops.get("onepassworditem").is_none());
+    }
+
+    #[test]
+    fn test_up_to_date_configs_are_left_alone() {
+        let mut configs = project("creation_rules: []\n", "version: 1\n");
+        let before = configs.clone();
+        assert!(run_migrations(&mut configs).unwrap().is_empty());
+        assert_eq!(configs, before);
+
+        // Nothing to migrate doesn't create .opsops.yaml either
+        let mut configs = project("creation_rules: []\n", "");
+        assert!(run_migrations(&mut configs).unwrap().is_empty());
+        assert_eq!(configs.opsops, Value::Null);
+    }
+
+    #[test]
+    fn test_rejects_newer_versions() {
+        let mut configs = project("creation_rules: []\n", "version: 99\n");
+        let err = run_migrations(&mut configs).unwrap_err();
+        assert!(err.contains("upgrade opsops"));
+    }
+}
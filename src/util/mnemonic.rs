@@ -0,0 +1,185 @@
+//! BIP39 recovery phrases for age keys: the 32 secret bytes of an X25519
+//! identity written as 24 words, a backup a person can copy onto paper and
+//! type back in.
+
+use age::{secrecy::ExposeSecret, x25519::Identity};
+use bech32::{FromBase32, ToBase32, Variant};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+/// The BIP39 English wordlist, 2048 words
+const WORDLIST: &str = include_str!("bip39-english.txt");
+/// Human readable part of the bech32 encoding of age secret keys
+const SECRET_KEY_HRP: &str = "age-secret-key-";
+const WORDS: usize = 24;
+
+fn wordlist() -> Vec<&'static str> {
+    WORDLIST.lines().collect()
+}
+
+/// Looks up a word, also accepting its first four letters, which are unique
+/// within the list
+fn word_index(words: &[&str], word: &str) -> Option<usize> {
+    words.iter().position(|w| *w == word).or_else(|| {
+        let mut matches = words
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| word.len() >= 4 && w.starts_with(word));
+        match (matches.next(), matches.next()) {
+            (Some((i, _)), None) => Some(i),
+            _ => None,
+        }
+    })
+}
+
+/// Encodes 32 bytes of entropy followed by an 8 bit SHA-256 checksum as 24 words
+fn phrase_from_entropy(entropy: &[u8]) -> Zeroizing<String> {
+    let checksum = Sha256::digest(entropy)[0];
+    let mut bits = Zeroizing::new(Vec::with_capacity((entropy.len() + 1) * 8));
+    for byte in entropy.iter().chain(std::iter::once(&checksum)) {
+        bits.extend((0..8).rev().map(|i| (byte >> i) & 1));
+    }
+    let words = wordlist();
+    let mut phrase = Zeroizing::new(String::new());
+    for chunk in bits.chunks(11) {
+        let index = chunk
+            .iter()
+            .fold(0usize, |acc, bit| (acc << 1) | *bit as usize);
+        if !phrase.is_empty() {
+            phrase.push(' ');
+        }
+        phrase.push_str(words[index]);
+    }
+    phrase
+}
+
+/// Decodes a phrase back to its 32 bytes of entropy, verifying the checksum
+fn entropy_from_phrase(phrase: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+    let given: Zeroizing<Vec<String>> =
+        Zeroizing::new(phrase.split_whitespace().map(str::to_lowercase).collect());
+    if given.len() != WORDS {
+        return Err(format!(
+            "A recovery phrase has {} words, got {}",
+            WORDS,
+            given.len()
+        ));
+    }
+
+    let words = wordlist();
+    let mut bits = Zeroizing::new(Vec::with_capacity(WORDS * 11));
+    for (n, word) in given.iter().enumerate() {
+        let index = word_index(&words, word)
+            .ok_or_else(|| format!("Word {} is not in the BIP39 word list", n + 1))?;
+        bits.extend((0..11).rev().map(|i| ((index >> i) & 1) as u8));
+    }
+    let mut bytes = Zeroizing::new(
+        bits.chunks(8)
+            .map(|chunk| chunk.iter().fold(0u8, |acc, bit| (acc << 1) | bit))
+            .collect::<Vec<u8>>(),
+    );
+
+    let checksum = bytes.pop();
+    if checksum != Some(Sha256::digest(bytes.as_slice())[0]) {
+        return Err(
+            "The recovery phrase checksum doesn't match, check the words and their order"
+                .to_string(),
+        );
+    }
+    Ok(bytes)
+}
+
+/// The recovery phrase of an identity
+pub fn to_mnemonic(identity: &Identity) -> Result<Zeroizing<String>, String> {
+    let (_, data, _) = bech32::decode(identity.to_string().expose_secret())
+        .map_err(|e| format!("Failed to decode the age key: {}", e))?;
+    let entropy = Zeroizing::new(
+        Vec::<u8>::from_base32(&data)
+            .map_err(|e| format!("Failed to decode the age key: {}", e))?,
+    );
+    Ok(phrase_from_entropy(&entropy))
+}
+
+/// Reconstructs the identity a recovery phrase was made from
+pub fn from_mnemonic(phrase: &str) -> Result<Identity, String> {
+    identity_from_bytes(&entropy_from_phrase(phrase)?)
+}
+
+/// The X25519 identity with these 32 secret bytes
+pub fn identity_from_bytes(secret: &[u8]) -> Result<Identity, String> {
+    let encoded = Zeroizing::new(
+        bech32::encode(SECRET_KEY_HRP, secret.to_base32(), Variant::Bech32)
+            .map_err(|e| format!("Failed to encode the age key: {}", e))?
+            .to_uppercase(),
+    );
+    Identity::from_str(&encoded).map_err(|e| format!("Invalid age key: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entropy_from_phrase, from_mnemonic, phrase_from_entropy, to_mnemonic};
+    use age::{secrecy::ExposeSecret, x25519::Identity};
+
+    #[test]
+    fn test_bip39_vectors() {
+        assert_eq!(
+            phrase_from_entropy(&[0; 32]).as_str(),
+            format!("{}art", "abandon ".repeat(23))
+        );
+        let legal = "legal winner thank year wave sausage worth useful ";
+        assert_eq!(
+            phrase_from_entropy(&[0x7f; 32]).as_str(),
+            format!(
+                "{}{}legal winner thank year wave sausage worth title",
+                legal, legal
+            )
+        );
+        assert_eq!(
+            entropy_from_phrase(&format!("{}art", "abandon ".repeat(23)))
+                .unwrap()
+                .as_slice(),
+            [0; 32]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let identity = Identity::generate();
+        let phrase = to_mnemonic(&identity).unwrap();
+        assert_eq!(phrase.split(' ').count(), 24);
+        let recovered = from_mnemonic(&phrase).unwrap();
+        assert_eq!(
+            recovered.to_string().expose_secret(),
+            identity.to_string().expose_secret()
+        );
+
+        // Abbreviated and upper case words
+        let abbreviated: Vec<String> = phrase
+            .split(' ')
+            .map(|w| w.chars().take(4).collect::<String>().to_uppercase())
+            .collect();
+        assert_eq!(
+            from_mnemonic(&abbreviated.join("  "))
+                .unwrap()
+                .to_public()
+                .to_string(),
+            identity.to_public().to_string()
+        );
+    }
+
+    #[test]
+    fn test_invalid_phrases() {
+        assert!(from_mnemonic("abandon abandon").is_err());
+        assert!(
+            entropy_from_phrase(&format!("{}opsops", "abandon ".repeat(23)))
+                .unwrap_err()
+                .contains("Word 24")
+        );
+        // Wrong checksum
+        assert!(
+            entropy_from_phrase(&"abandon ".repeat(24))
+                .unwrap_err()
+                .contains("checksum")
+        );
+    }
+}
@@ -1,8 +1,87 @@
+pub mod advice;
+pub mod agent;
+pub mod argocd;
+pub mod audit_log;
+pub mod backups;
+pub mod bulk;
+pub mod canonical_config;
+pub mod certificates;
+pub mod check_report;
+pub mod ci_pipeline;
+pub mod config_edit;
+pub mod config_include;
+pub mod config_merge;
+pub mod csv_table;
+pub mod decrypted_copies;
+pub mod destinations;
+pub mod diff_render;
+pub mod dirs;
+pub mod document;
+pub mod document_includes;
+pub mod drift;
+pub mod editor;
+pub mod encrypted_insert;
+pub mod encrypted_keys;
+pub mod env_key;
+pub mod environment;
+pub mod escrow;
+pub mod file_lock;
+pub mod file_picker;
+pub mod file_scaffold;
 pub mod find_project_root;
+pub mod flux;
+pub mod formatter;
+pub mod formatting;
+pub mod generators;
+pub mod git_hooks;
+pub mod git_index;
+pub mod guardrails;
+pub mod help_topics;
+pub mod host_keys;
+pub mod i18n;
+pub mod interpolate;
+pub mod journal;
+pub mod json_schema;
+pub mod key_registry;
+pub mod kubectl;
+pub mod markdown;
+pub mod member_registry;
+pub mod migrations;
+pub mod mnemonic;
+pub mod notifications;
 pub mod op;
 pub mod op_key;
+pub mod op_rate_limit;
+pub mod op_reference;
+pub mod openssl;
+pub mod opsops_config;
+pub mod output_destinations;
+pub mod output_format;
+pub mod output_permissions;
+pub mod passphrase_key;
+pub mod perf;
+pub mod platform;
 pub mod print_status;
+pub mod project_templates;
+pub mod prompts;
+pub mod protected_files;
+pub mod read_only;
+pub mod recipient_expiry;
+pub mod reference_cache;
+pub mod rule_conditions;
+pub mod rule_match;
+pub mod rule_spec;
+pub mod rule_templates;
+pub mod sarif;
+pub mod secret_scan;
+pub mod session_record;
 pub mod sops_command;
 pub mod sops_config;
+pub mod sops_files;
+pub mod sops_metadata;
 pub mod sops_status;
 pub mod sops_structs;
+pub mod sync_map;
+pub mod talos;
+pub mod user_config;
+pub mod value_expiry;
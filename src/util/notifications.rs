@@ -0,0 +1,267 @@
+//! Telling people that access to secrets changed. `notifications:` in
+//! `.opsops.yaml` lists Slack webhooks, generic webhooks and desktop
+//! notifications, sent after `key expire-sweep` rotated files, after a rekey
+//! (`drift --fix`, `key upgrade`) and after `key add-recipient`. Messages carry
+//! file counts and recipient fingerprints, never values or keys, and a failed
+//! notification only warns.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use super::i18n::tr_args;
+use super::op::user_name;
+use super::op_key::fingerprint;
+use super::opsops_config::read_opsops_config;
+use super::print_status::print_warning;
+use super::session_record;
+use crate::GlobalContext;
+
+/// What the default message says, see [`render`] for the placeholders
+const DEFAULT_MESSAGE: &str =
+    "opsops: {user} ran {event} in {project}, {files} file(s), recipients {fingerprints}";
+
+/// Seconds a webhook may take
+const TIMEOUT: &str = "10";
+
+/// What happened
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyEvent {
+    /// Data keys rotated, e.g. by `key expire-sweep`
+    Rotate,
+    /// Files encrypted to new recipients, by `drift --fix` or `key upgrade`
+    Rekey,
+    /// Recipients added to the creation rules
+    AddRecipient,
+}
+
+impl NotifyEvent {
+    fn name(self) -> &'static str {
+        match self {
+            NotifyEvent::Rotate => "rotate",
+            NotifyEvent::Rekey => "rekey",
+            NotifyEvent::AddRecipient => "add-recipient",
+        }
+    }
+}
+
+/// One entry of `notifications:` in `.opsops.yaml`. URLs starting with `$` are
+/// read from that environment variable, so webhooks needn't be committed.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct Notification {
+    /// Slack incoming webhook URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slack: Option<String>,
+    /// URL that gets the notice POSTed as JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+    /// A desktop notification, with notify-send or osascript
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub desktop: bool,
+    /// The events to send, every event if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on: Vec<NotifyEvent>,
+    /// Template of the message, see [`render`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// What is sent, free of secrets
+#[derive(Debug, Serialize)]
+pub struct Notice {
+    pub event: &'static str,
+    pub project: String,
+    pub user: String,
+    pub files: usize,
+    pub fingerprints: Vec<String>,
+}
+
+/// Fills `{event}`, `{project}`, `{user}`, `{files}` and `{fingerprints}` into
+/// `template`
+pub fn render(template: &str, notice: &Notice) -> String {
+    let fingerprints = match notice.fingerprints.is_empty() {
+        true => "none".to_string(),
+        false => notice.fingerprints.join(", "),
+    };
+    template
+        .replace("{event}", notice.event)
+        .replace("{project}", &notice.project)
+        .replace("{user}", &notice.user)
+        .replace("{files}", &notice.files.to_string())
+        .replace("{fingerprints}", &fingerprints)
+}
+
+/// A quoted string for a curl config file
+fn curl_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The curl config POSTing `body` to `url`. curl reads it from stdin, so
+/// neither shows up in the process list.
+fn curl_config(url: &str, body: &str) -> String {
+    format!(
+        "url = {}\nheader = \"Content-Type: application/json\"\ndata-binary = {}\nmax-time = {}\nsilent\nshow-error\nfail\n",
+        curl_quote(url),
+        curl_quote(body),
+        TIMEOUT
+    )
+}
+
+fn resolve_url(url: &str) -> Result<String, String> {
+    match url.strip_prefix('$') {
+        Some(var) => std::env::var(var).map_err(|_| format!("{} is not set", var)),
+        None => Ok(url.to_string()),
+    }
+}
+
+fn post(url: &str, body: &serde_json::Value) -> Result<(), String> {
+    let url = resolve_url(url)?;
+    let mut command = Command::new("curl");
+    command
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    let started = Instant::now();
+    let result = command.spawn().and_then(|mut child| {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(curl_config(&url, &body.to_string()).as_bytes())?;
+        }
+        child.wait_with_output()
+    });
+    session_record::record(
+        &command,
+        started,
+        result.as_ref().map(|output| output.status.code()),
+    );
+    let output = result.map_err(|e| format!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+fn desktop(message: &str) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let quoted = message.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification \"{}\" with title \"opsops\"",
+            quoted
+        ));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg("opsops").arg(message);
+        command
+    };
+    let output =
+        session_record::output(&mut command).map_err(|e| format!("Failed to notify: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+/// Sends `event` to every notification configured for it. `recipients` are the
+/// age recipients concerned, only their fingerprints are sent.
+pub fn notify(
+    event: NotifyEvent,
+    root: &Path,
+    files: usize,
+    recipients: &[String],
+    context: &GlobalContext,
+) {
+    let notifications = match read_opsops_config(context) {
+        Ok(Some(config)) => config.notifications,
+        _ => return,
+    };
+    let mut fingerprints: Vec<String> = recipients.iter().map(|r| fingerprint(r)).collect();
+    fingerprints.sort();
+    fingerprints.dedup();
+    let notice = Notice {
+        event: event.name(),
+        project: root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        user: user_name(),
+        files,
+        fingerprints,
+    };
+
+    for notification in notifications
+        .iter()
+        .filter(|n| n.on.is_empty() || n.on.contains(&event))
+    {
+        let message = render(
+            notification.message.as_deref().unwrap_or(DEFAULT_MESSAGE),
+            &notice,
+        );
+        let mut sent = Vec::new();
+        if let Some(url) = &notification.slack {
+            sent.push(("Slack", post(url, &serde_json::json!({ "text": message }))));
+        }
+        if let Some(url) = &notification.webhook {
+            let mut body = serde_json::to_value(&notice).unwrap_or_default();
+            body["message"] = message.clone().into();
+            sent.push(("webhook", post(url, &body)));
+        }
+        if notification.desktop {
+            sent.push(("desktop", desktop(&message)));
+        }
+        for (target, result) in sent {
+            if let Err(e) = result {
+                print_warning(tr_args(
+                    "notification-failed",
+                    &[("target", target.into()), ("error", e.into())],
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Notice, curl_config, render};
+
+    #[test]
+    fn test_render() {
+        let notice = Notice {
+            event: "rotate",
+            project: "infra".to_string(),
+            user: "alice".to_string(),
+            files: 3,
+            fingerprints: vec!["3f2a9c1e".to_string(), "0b1c2d3e".to_string()],
+        };
+        assert_eq!(
+            render(
+                "{user} ran {event} on {files} file(s) in {project}: {fingerprints}",
+                &notice
+            ),
+            "alice ran rotate on 3 file(s) in infra: 3f2a9c1e, 0b1c2d3e"
+        );
+    }
+
+    #[test]
+    fn test_curl_config_quotes() {
+        let config = curl_config("https://example.com/hook", r#"{"text":"a \ b"}"#);
+        assert!(config.starts_with("url = \"https://example.com/hook\"\n"));
+        assert!(config.contains(r#"data-binary = "{\"text\":\"a \\ b\"}""#));
+    }
+}
@@ -1,14 +1,60 @@
 use serde::Deserialize;
 use std::process::Command;
-use users::os::unix::UserExt;
+use std::sync::Mutex;
+use zeroize::{Zeroize, Zeroizing};
 
+use crate::util::i18n::{tr, tr_args};
 use crate::util::print_status::print_warning;
 
-use super::print_status::print_error;
+use super::{
+    op_rate_limit::run_op,
+    platform::{Account, Native, Platform},
+    print_status::print_error,
+    session_record,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct ItemField {
-    label: String,
+    pub label: String,
+    /// e.g. `CONCEALED`, `STRING`, `URL`
+    #[serde(rename = "type", default)]
+    pub field_type: String,
+    /// `USERNAME`, `PASSWORD` or `NOTES` for the built-in fields
+    #[serde(default)]
+    pub purpose: String,
+}
+
+impl ItemField {
+    /// Whether 1Password hides the value, as it does for keys and passwords
+    pub fn is_concealed(&self) -> bool {
+        self.field_type == "CONCEALED" || self.purpose == "PASSWORD"
+    }
+
+    /// The label with the field type, for pickers
+    pub fn describe(&self) -> String {
+        let kind = match self.purpose.as_str() {
+            "" => self.field_type.to_lowercase(),
+            purpose => purpose.to_lowercase(),
+        };
+        if kind.is_empty() {
+            self.label.clone()
+        } else {
+            format!("{} ({})", self.label, kind)
+        }
+    }
+}
+
+/// The field a picker should highlight: the first concealed one, preferring
+/// fields that look like they hold a key
+pub fn default_field(fields: &[ItemField]) -> usize {
+    let concealed = |f: &&ItemField| f.is_concealed();
+    fields
+        .iter()
+        .filter(concealed)
+        .find(|f| f.label.to_lowercase().contains("key"))
+        .or_else(|| fields.iter().find(concealed))
+        .and_then(|field| fields.iter().position(|f| std::ptr::eq(f, field)))
+        .unwrap_or(0)
 }
 
 #[derive(Debug, Deserialize)]
@@ -16,9 +62,15 @@ pub struct ItemFields {
     fields: Vec<ItemField>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ListItem {
     title: String,
+    vault: ItemVault,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ItemVault {
+    name: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,6 +89,7 @@ pub enum OpCategory {
     Password,
     _Identity,
     _Server,
+    SecureNote,
 }
 
 impl OpCategory {
@@ -46,6 +99,7 @@ impl OpCategory {
             OpCategory::Password => "password",
             OpCategory::_Identity => "identity",
             OpCategory::_Server => "server",
+            OpCategory::SecureNote => "Secure Note",
         }
     }
 }
@@ -58,14 +112,28 @@ pub struct OpItemField {
     pub value: String,
 }
 
+/// Escapes the characters that structure an assignment, `.`, `=` and `\`
+fn escape_assignment_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '.' | '=' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 impl OpItemField {
-    fn _to_flag(&self) -> String {
+    /// The assignment `op item create` and `op item edit` take,
+    /// `[<section>.]<field>[[<type>]]=<value>`
+    fn to_flag(&self) -> String {
         let mut flag = String::new();
         if let Some(section) = &self.section {
-            flag.push_str(section);
+            flag.push_str(&escape_assignment_name(section));
             flag.push('.');
         }
-        flag.push_str(&self.field);
+        flag.push_str(&escape_assignment_name(&self.field));
         if let Some(field_type) = &self.field_type {
             flag.push_str(&format!("[{}]", field_type));
         }
@@ -83,36 +151,96 @@ pub struct OpItem {
     pub(crate) fields: Vec<OpItemField>,
 }
 
-/// Helper to run the `op` CLI as the invoking user if running under sudo.
-pub fn op_command() -> Command {
-    use std::env;
-    use std::os::unix::process::CommandExt;
-
-    if let Ok(sudo_user) = env::var("SUDO_USER") {
-        if !sudo_user.is_empty() {
-            // Get the user's UID and GID
-            if let Some(user) = users::get_user_by_name(&sudo_user) {
-                let mut cmd = Command::new("op");
-                cmd.uid(user.uid());
-                cmd.gid(user.primary_group_id());
-                // Set HOME to the user's home directory
-                if let Some(home) = user.home_dir().to_str() {
-                    cmd.env("HOME", home);
-                } else {
-                    print_warning("Couldn't get home directory of sudo user");
+/// Environment variables set by privilege escalation tools, checked in order.
+/// `SUDO_USER` and `DOAS_USER` hold a user name, `SUDO_UID` and `PKEXEC_UID` a uid.
+const ESCALATION_VARS: [&str; 4] = ["SUDO_USER", "DOAS_USER", "SUDO_UID", "PKEXEC_UID"];
+
+/// The user that invoked opsops through sudo, doas or pkexec.
+#[derive(Debug, PartialEq)]
+enum InvokingUser {
+    Name(String),
+    Uid(u32),
+}
+
+/// Determines the invoking user from the escalation variables using `lookup`.
+/// Empty or malformed variables are skipped with a warning.
+fn invoking_user_from<F>(lookup: F) -> Option<(&'static str, InvokingUser)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    for var in ESCALATION_VARS {
+        let Some(value) = lookup(var) else {
+            continue;
+        };
+        if value.is_empty() {
+            print_warning(tr_args("op-variable-empty", &[("variable", var.into())]));
+            continue;
+        }
+        if var.ends_with("_UID") {
+            match value.parse::<u32>() {
+                Ok(uid) => return Some((var, InvokingUser::Uid(uid))),
+                Err(_) => {
+                    print_warning(tr_args(
+                        "op-variable-invalid-uid",
+                        &[("variable", var.into()), ("value", value.into())],
+                    ));
+                    continue;
                 }
-                return cmd;
-            } else {
-                print_warning("Couldn't get sudo user by name");
             }
-        } else {
-            print_warning("Environment variable SUDO_USER is set but empty");
         }
+        return Some((var, InvokingUser::Name(value)));
     }
-    Command::new("op")
+    None
+}
+
+/// The name of the person running opsops, the invoking user under sudo
+pub fn user_name() -> String {
+    invoking_user()
+        .map(|user| user.name)
+        .or_else(Native::current_user_name)
+        .unwrap_or_else(|| "unknown".to_string())
 }
 
-pub fn op_item_create(item: OpItem) {
+/// The user that ran opsops through sudo, doas or pkexec, `None` without escalation
+pub fn invoking_user() -> Option<Account> {
+    let (var, invoking) = invoking_user_from(|name| std::env::var(name).ok())?;
+    let user = match &invoking {
+        InvokingUser::Name(name) => Native::user_by_name(name),
+        InvokingUser::Uid(uid) => Native::user_by_uid(*uid),
+    };
+    if user.is_none() && cfg!(all(unix, feature = "impersonation")) {
+        print_warning(tr_args("op-no-invoking-user", &[("variable", var.into())]));
+    } else if user.is_none() {
+        print_warning(tr_args("op-no-impersonation", &[("variable", var.into())]));
+    }
+    user
+}
+
+/// Helper to run the `op` CLI as the invoking user if running under sudo, doas or pkexec.
+pub fn op_command() -> Command {
+    let mut cmd = Command::new("op");
+    if let Some(user) = invoking_user() {
+        Native::run_as(&mut cmd, &user);
+    }
+    cmd
+}
+
+/// Checks whether the `op` CLI has an active session
+pub fn is_signed_in() -> bool {
+    run_op(op_command().arg("whoami"))
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Runs `op signin` interactively, returning whether it succeeded
+pub fn sign_in() -> bool {
+    session_record::status(op_command().arg("signin"))
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Creates an item in 1Password, returning whether it succeeded
+pub fn op_item_create(item: OpItem) -> bool {
     let mut cmd = op_command();
 
     cmd.arg("item")
@@ -125,64 +253,136 @@ pub fn op_item_create(item: OpItem) {
         .arg(item.category.as_str());
 
     for field in item.fields {
-        let field_str = match (&field.section, &field.field_type) {
-            (Some(section), Some(ftype)) => {
-                format!("{}.{}[{}]={}", section, field.field, ftype, field.value)
-            }
-            (Some(section), None) => {
-                format!("{}.{}={}", section, field.field, field.value)
-            }
-            (None, Some(ftype)) => {
-                format!("{}[{}]={}", field.field, ftype, field.value)
-            }
-            (None, None) => {
-                format!("{}={}", field.field, field.value)
-            }
-        };
-        cmd.arg(field_str);
+        cmd.arg(field.to_flag());
     }
 
-    let status = cmd.status().expect("failed to run `op` command");
+    let status = session_record::status(&mut cmd).expect("failed to run `op` command");
 
     if !status.success() {
-        print_error("Failed to create item in 1Password".to_string());
+        print_error(tr("op-create-failed"));
     }
+    status.success()
+}
+
+/// Sets the fields of an existing item, adding the ones it doesn't have yet
+pub fn op_item_edit(item: &OpItem) -> Result<(), String> {
+    let mut cmd = op_command();
+    cmd.args(["item", "edit", &item.title, "--vault", &item.vault]);
+    cmd.args(item.fields.iter().map(OpItemField::to_flag));
+    let output = run_op(&mut cmd).map_err(|e| format!("Failed to execute 1Password CLI: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "1Password CLI returned an error: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
 }
 
 pub fn _op_item_get(item_name: &str, field: &str) -> Option<String> {
-    let output = op_command()
-        .arg("item")
-        .arg("get")
-        .arg(item_name)
-        .arg("--field")
-        .arg(field)
-        .output()
-        .ok()?;
+    let output = run_op(
+        op_command()
+            .arg("item")
+            .arg("get")
+            .arg(item_name)
+            .arg("--field")
+            .arg(field),
+    )
+    .ok()?;
 
     if output.status.success() {
         Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
     } else {
         print_error(format!(
-            "Error: {}",
+            "{} {}",
+            tr("error-prefix"),
             String::from_utf8_lossy(&output.stderr)
         ));
         None
     }
 }
 
+/// A field of an item with its value, as `op item get --format=json` prints it
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ItemValue {
+    pub label: String,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemValues {
+    #[serde(default)]
+    fields: Vec<ItemValue>,
+}
+
+/// The fields of `item` with their values, from `vault` or wherever op finds it
+pub fn item_values(item: &str, vault: Option<&str>) -> Result<Vec<ItemValue>, String> {
+    let mut command = op_command();
+    command.args(["item", "get", item, "--format=json"]);
+    if let Some(vault) = vault {
+        command.args(["--vault", vault]);
+    }
+    let mut output =
+        run_op(&mut command).map_err(|e| format!("Failed to execute 1Password CLI: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "1Password CLI returned an error: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let fields = serde_json::from_slice::<ItemValues>(&output.stdout)
+        .map(|item| item.fields)
+        .map_err(|e| format!("Failed to parse the item: {}", e));
+    output.stdout.zeroize();
+    fields
+}
+
+/// Reads the value behind a secret reference, without the newline op adds
+pub fn read_secret(reference: &str) -> Result<Zeroizing<String>, String> {
+    let mut output = run_op(op_command().arg("read").arg(reference))
+        .map_err(|e| format!("Failed to execute 1Password CLI: {}", e))?;
+    let value = Zeroizing::new(String::from_utf8_lossy(&output.stdout).into_owned());
+    output.stdout.zeroize();
+    if !output.status.success() {
+        return Err(format!(
+            "1Password CLI returned an error: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let trimmed = value.strip_suffix('\n').unwrap_or(&value);
+    Ok(Zeroizing::new(trimmed.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemCategory {
+    category: String,
+}
+
+/// The category of an item, e.g. `DOCUMENT` or `PASSWORD`
+pub fn item_category(item: &str, vault: &str) -> Result<String, String> {
+    let output =
+        run_op(op_command().args(["item", "get", item, "--vault", vault, "--format=json"]))
+            .map_err(|e| format!("Failed to execute 1Password CLI: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "1Password CLI returned an error: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    serde_json::from_slice::<ItemCategory>(&output.stdout)
+        .map(|item| item.category)
+        .map_err(|e| format!("Failed to parse the item: {}", e))
+}
+
 pub fn get_vaults() -> Option<Vec<String>> {
-    let output_json = op_command()
-        .arg("vault")
-        .arg("list")
-        .arg("--format=json")
-        .output()
-        .ok()?;
+    let output_json = run_op(op_command().arg("vault").arg("list").arg("--format=json")).ok()?;
 
     if output_json.status.success() {
         let vaults: Vec<Vault> = match serde_json::from_slice(&output_json.stdout) {
             Ok(v) => v,
             Err(e) => {
-                print_error(format!("Failed to parse JSON: {}", e));
+                print_error(format!("{} {}", tr("op-json-failed"), e));
                 return None;
             }
         };
@@ -191,68 +391,83 @@ pub fn get_vaults() -> Option<Vec<String>> {
         Some(vault_names)
     } else {
         print_error(format!(
-            "Error: {}",
+            "{} {}",
+            tr("error-prefix"),
             String::from_utf8_lossy(&output_json.stderr)
         ));
         None
     }
 }
 
-pub fn get_items(vault: &String) -> Option<Vec<String>> {
-    let output_json = op_command()
-        .arg("item")
-        .arg("list")
-        .arg("--vault")
-        .arg(vault)
-        .arg("--format=json")
-        .output()
-        .ok()?;
+/// Items of all vaults, listed once per process. Accounts behind SSO throttle
+/// `op`, so a single list call replaces one per vault.
+static ITEM_CATALOG: Mutex<Option<Vec<ListItem>>> = Mutex::new(None);
 
-    if output_json.status.success() {
-        let vaults: Vec<ListItem> = match serde_json::from_slice(&output_json.stdout) {
-            Ok(v) => v,
-            Err(e) => {
-                print_error(format!("Failed to parse JSON: {}", e));
-                return None;
-            }
-        };
+fn item_catalog() -> Option<Vec<ListItem>> {
+    let mut catalog = ITEM_CATALOG.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(items) = catalog.as_ref() {
+        return Some(items.clone());
+    }
 
-        let item_names: Vec<String> = vaults.into_iter().map(|item| item.title).collect();
-        Some(item_names)
-    } else {
+    let output_json = run_op(op_command().arg("item").arg("list").arg("--format=json")).ok()?;
+    if !output_json.status.success() {
         print_error(format!(
-            "Error: {}",
+            "{} {}",
+            tr("error-prefix"),
             String::from_utf8_lossy(&output_json.stderr)
         ));
-        None
+        return None;
     }
+    let items: Vec<ListItem> = match serde_json::from_slice(&output_json.stdout) {
+        Ok(v) => v,
+        Err(e) => {
+            print_error(format!("{} {}", tr("op-json-failed"), e));
+            return None;
+        }
+    };
+    *catalog = Some(items.clone());
+    Some(items)
 }
 
-pub fn get_fields(item: &String, vault: &String) -> Option<Vec<String>> {
-    let output_json = op_command()
-        .arg("item")
-        .arg("get")
-        .arg(item)
-        .arg("--vault")
-        .arg(vault)
-        .arg("--format=json")
-        .output()
-        .ok()?;
+/// Titles of the catalog's items in `vault`
+fn items_in(catalog: &[ListItem], vault: &str) -> Vec<String> {
+    catalog
+        .iter()
+        .filter(|item| item.vault.name == vault)
+        .map(|item| item.title.clone())
+        .collect()
+}
+
+pub fn get_items(vault: &str) -> Option<Vec<String>> {
+    Some(items_in(&item_catalog()?, vault))
+}
+
+pub fn get_fields(item: &String, vault: &String) -> Option<Vec<ItemField>> {
+    let output_json = run_op(
+        op_command()
+            .arg("item")
+            .arg("get")
+            .arg(item)
+            .arg("--vault")
+            .arg(vault)
+            .arg("--format=json"),
+    )
+    .ok()?;
 
     if output_json.status.success() {
         let fields: ItemFields = match serde_json::from_slice(&output_json.stdout) {
             Ok(v) => v,
             Err(e) => {
-                print_error(format!("Failed to parse JSON: {}", e));
+                print_error(format!("{} {}", tr("op-json-failed"), e));
                 return None;
             }
         };
 
-        let item_names: Vec<String> = fields.fields.into_iter().map(|item| item.label).collect();
-        Some(item_names)
+        Some(fields.fields)
     } else {
         print_error(format!(
-            "Error: {}",
+            "{} {}",
+            tr("error-prefix"),
             String::from_utf8_lossy(&output_json.stderr)
         ));
         None
@@ -261,7 +476,52 @@ pub fn get_fields(item: &String, vault: &String) -> Option<Vec<String>> {
 
 #[cfg(test)]
 mod tests {
-    use crate::util::op::{OpCategory, OpItem, OpItemField};
+    use std::collections::HashMap;
+
+    use crate::util::op::{
+        InvokingUser, ItemFields, ListItem, OpCategory, OpItem, OpItemField, default_field,
+        invoking_user_from, items_in,
+    };
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| map.get(name).cloned()
+    }
+
+    #[test]
+    fn test_invoking_user_none() {
+        assert_eq!(invoking_user_from(lookup(&[])), None);
+    }
+
+    #[test]
+    fn test_invoking_user_prefers_sudo_user() {
+        let found = invoking_user_from(lookup(&[("SUDO_USER", "alice"), ("DOAS_USER", "bob")]));
+        assert_eq!(
+            found,
+            Some(("SUDO_USER", InvokingUser::Name("alice".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_invoking_user_doas() {
+        let found = invoking_user_from(lookup(&[("DOAS_USER", "bob")]));
+        assert_eq!(
+            found,
+            Some(("DOAS_USER", InvokingUser::Name("bob".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_invoking_user_uid_fallbacks() {
+        let found = invoking_user_from(lookup(&[("SUDO_USER", ""), ("PKEXEC_UID", "1000")]));
+        assert_eq!(found, Some(("PKEXEC_UID", InvokingUser::Uid(1000))));
+
+        let found = invoking_user_from(lookup(&[("SUDO_UID", "abc"), ("PKEXEC_UID", "1001")]));
+        assert_eq!(found, Some(("PKEXEC_UID", InvokingUser::Uid(1001))));
+    }
 
     #[test]
     fn test_op_item_field_to_flag() {
@@ -271,7 +531,14 @@ mod tests {
             field_type: Some("text".to_string()),
             value: "admin".to_string(),
         };
-        assert_eq!(field._to_flag(), "auth.username[text]=admin");
+        assert_eq!(field.to_flag(), "auth.username[text]=admin");
+        let dotted = OpItemField {
+            section: Some("db".to_string()),
+            field: "primary.password".to_string(),
+            field_type: None,
+            value: "a=b".to_string(),
+        };
+        assert_eq!(dotted.to_flag(), "db.primary\\.password=a=b");
     }
 
     #[test]
@@ -302,7 +569,7 @@ mod tests {
 
         // You'd need to refactor `op_item_create` to allow inspecting the command, otherwise this test cannot safely verify the internals.
         // See note below.
-        assert!(item.fields[1]._to_flag() == "credentials.password[password]=secret");
+        assert!(item.fields[1].to_flag() == "credentials.password[password]=secret");
     }
 
     #[test]
@@ -333,4 +600,40 @@ mod tests {
 
         assert_eq!(names, vec!["TestVault", "AnotherVault"]);
     }
+
+    #[test]
+    fn test_items_in_vault() {
+        let catalog: Vec<ListItem> = serde_json::from_str(
+            r#"[
+                {"id": "a", "title": "Age key", "vault": {"id": "v1", "name": "Private"}},
+                {"id": "b", "title": "Deploy", "vault": {"id": "v2", "name": "Team"}},
+                {"id": "c", "title": "Age key", "vault": {"id": "v2", "name": "Team"}}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(items_in(&catalog, "Team"), vec!["Deploy", "Age key"]);
+        assert!(items_in(&catalog, "Shared").is_empty());
+    }
+
+    #[test]
+    fn test_item_field_types() {
+        let item: ItemFields = serde_json::from_str(
+            r#"{"fields": [
+                {"id": "username", "type": "STRING", "purpose": "USERNAME", "label": "username"},
+                {"id": "password", "type": "CONCEALED", "purpose": "PASSWORD", "label": "password"},
+                {"id": "notesPlain", "type": "STRING", "purpose": "NOTES", "label": "notesPlain"},
+                {"id": "abc", "type": "URL", "label": "website"},
+                {"id": "def", "type": "CONCEALED", "label": "age key"}
+            ]}"#,
+        )
+        .unwrap();
+        let fields = item.fields;
+        assert_eq!(fields[0].describe(), "username (username)");
+        assert_eq!(fields[3].describe(), "website (url)");
+        assert_eq!(fields[4].describe(), "age key (concealed)");
+        assert!(!fields[2].is_concealed());
+        assert_eq!(default_field(&fields), 4);
+        assert_eq!(default_field(&fields[..4]), 1);
+        assert_eq!(default_field(&fields[..1]), 0);
+    }
 }
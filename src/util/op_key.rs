@@ -1,31 +1,96 @@
 use crate::{
     GlobalContext,
-    util::{op::op_command, sops_config::read_or_create_config},
+    util::{
+        agent,
+        config_include::load_effective_config,
+        env_key::{KeyPreference, key_from_env, warn_ignored_env_key},
+        host_keys::{HostIdentity, current_host_identity, read_key_file},
+        i18n::tr,
+        op::{item_category, op_command},
+        op_rate_limit::run_op,
+        op_reference::{OpDocument, OpReference},
+        passphrase_key::{key_from_passphrase, passphrase_settings},
+        perf::{Category, span},
+        reference_cache::remember_reference,
+    },
 };
 use age::{
     secrecy::{ExposeSecret, SecretString},
-    x25519::Identity,
+    x25519::{Identity, Recipient},
 };
 use colored::Colorize;
+use sha2::{Digest, Sha256};
 use std::str::FromStr;
+use zeroize::Zeroize;
 
 use super::print_status::print_error;
 
-/// Retrieves the Age key from 1Password using the reference stored in .sops.yaml or from command line
-/// Returns the key as a string if successful, or an error message if not
-pub fn get_age_key_from_1password(context: &GlobalContext) -> Result<String, String> {
+/// Retrieves the Age key from 1Password using the reference stored in .opsops.yaml or from command line
+/// Returns the key as a zeroizing secret if successful, or an error message if not.
+/// Projects with `keyprovider: passphrase` derive it from the passphrase instead.
+/// A `host_keys:` entry for this host wins over both.
+/// With `--prefer-env-key` the key sops would read from the environment wins.
+pub fn get_age_key_from_1password(context: &GlobalContext) -> Result<SecretString, String> {
+    let _span = span(Category::KeyRetrieval, "age key");
+    if context.key_preference == KeyPreference::Environment
+        && let Some(key) = key_from_env()?
+    {
+        return Ok(key);
+    }
+    warn_ignored_env_key(context.key_preference);
+
+    // --op-item still reads from 1Password, e.g. for the new key of `key upgrade`
+    let host = match context.opitem {
+        Some(_) => None,
+        None => current_host_identity(context)?,
+    };
+    if let Some(HostIdentity::KeyFile(path)) = host.as_ref().map(|host| &host.identity) {
+        return read_key_file(path);
+    }
+    if context.opitem.is_none()
+        && host.is_none()
+        && let Some(settings) = passphrase_settings(context)?
+    {
+        return key_from_passphrase(&settings);
+    }
+
+    let op_reference = resolve_op_reference(context)?;
+
+    // Prefer a running agent, which only hits 1Password once per session
+    let key = match agent::request_key(&op_reference) {
+        Some(key) if key.expose_secret().starts_with("AGE-SECRET-KEY-") => key,
+        _ => read_key_from_op(&op_reference)?,
+    };
+
+    // Offer the reference in shell completion from now on
+    if !context.read_only {
+        remember_reference(&op_reference);
+    }
+    Ok(key)
+}
+
+/// The 1Password reference of the age key, from --op-item, this host's
+/// `host_keys:` entry or .opsops.yaml
+pub fn resolve_op_reference(context: &GlobalContext) -> Result<String, String> {
+    let host = match context.opitem {
+        Some(_) => None,
+        None => current_host_identity(context)?,
+    };
     let op_reference = if let Some(opitem) = &context.opitem {
         // Use the opitem from command line
         opitem.clone()
+    } else if let Some(HostIdentity::OnePassword(reference)) = host.map(|host| host.identity) {
+        reference
     } else {
         // Read the SOPS config to get the 1Password reference
-        let config = read_or_create_config(context)
-            .map_err(|e| format!("Failed to read SOPS config: {}", e))?;
+        let config = load_effective_config(context)
+            .map_err(|e| format!("Failed to read SOPS config: {}", e))?
+            .config;
 
         // Check if onepassworditem is set
         if config.onepassworditem.is_empty() {
             return Err(
-                "No 1Password reference found in .sops.yaml and none provided via --op-item. Run 'opsops init' to configure."
+                "No 1Password reference found in .opsops.yaml and none provided via --op-item. Run 'opsops init' to configure."
                     .to_string(),
             );
         }
@@ -33,41 +98,155 @@ pub fn get_age_key_from_1password(context: &GlobalContext) -> Result<String, Str
         config.onepassworditem
     };
 
+    normalize_op_reference(&op_reference)
+}
+
+/// Checks a key reference: a field (`op://<vault>/<item>/<field>`) or a
+/// Document item holding an identity file (`op://<vault>/<item>`)
+pub fn normalize_op_reference(op_reference: &str) -> Result<String, String> {
+    // Catch malformed references before handing them to op
+    if let Ok(document) = op_reference.parse::<OpDocument>() {
+        return Ok(document.to_string());
+    }
+    op_reference
+        .parse::<OpReference>()
+        .map(|reference| reference.to_string())
+        .map_err(|e| format!("Invalid 1Password reference: {}", e))
+}
+
+/// Parses an age identity file like `keys.txt`: blank lines and `#` comments
+/// are skipped, every other line has to be an identity. Returns all identities,
+/// one per line, as sops reads them from `SOPS_AGE_KEY_FILE`.
+pub fn parse_identity_file(contents: &str) -> Result<SecretString, String> {
+    let mut identities = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if Identity::from_str(line).is_err() {
+            return Err(
+                "The identity file contains a line that is neither a comment nor an age secret key"
+                    .to_string(),
+            );
+        }
+        identities.push(line);
+    }
+    if identities.is_empty() {
+        return Err("The identity file contains no age secret key".to_string());
+    }
+    Ok(SecretString::from(identities.join("\n")))
+}
+
+/// Reads the identity file stored as the Document item `document`
+fn read_document_key(document: &OpDocument) -> Result<SecretString, String> {
+    let category = item_category(&document.item, &document.vault)?;
+    if category != "DOCUMENT" {
+        return Err(format!(
+            "{} is a {} item, not a Document. Reference the field holding the key: {}/<field>",
+            document,
+            category.to_lowercase(),
+            document
+        ));
+    }
+
+    let mut output = run_op(op_command().args([
+        "document",
+        "get",
+        &document.item,
+        "--vault",
+        &document.vault,
+    ]))
+    .map_err(|e| format!("Failed to execute 1Password CLI: {}", e))?;
+    if !output.status.success() {
+        output.stdout.zeroize();
+        return Err(format!(
+            "1Password CLI returned an error: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    let key = parse_identity_file(&String::from_utf8_lossy(&output.stdout));
+    output.stdout.zeroize();
+    key
+}
+
+/// Reads the Age key behind `op_reference` directly from the 1Password CLI
+pub fn read_key_from_op(op_reference: &str) -> Result<SecretString, String> {
+    if let Ok(document) = op_reference.parse::<OpDocument>() {
+        return read_document_key(&document);
+    }
+
     // Run the op command to get the key
-    // Format: op://<vault>/<item>/<field>
-    let output = op_command()
-        .arg("read")
-        .arg(&op_reference)
-        .output()
+    // Format: op://<vault>/<item>[/<section>]/<field>
+    let mut output = run_op(op_command().arg("read").arg(op_reference))
         .map_err(|e| format!("Failed to execute 1Password CLI: {}", e))?;
 
     if !output.status.success() {
+        output.stdout.zeroize();
         let error = String::from_utf8_lossy(&output.stderr);
         return Err(format!("1Password CLI returned an error: {}", error));
     }
 
-    // Get the output as a string
-    let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // The field holds a single key or a whole keys.txt
+    let key = parse_identity_file(&String::from_utf8_lossy(&output.stdout)).map_err(|e| {
+        format!(
+            "Retrieved value is not a valid Age key. It should be an 'AGE-SECRET-KEY-' line or an age identity file. {}",
+            e
+        )
+    });
+    output.stdout.zeroize();
+    key
+}
 
-    // Validate that we got a proper Age key
-    if !key.starts_with("AGE-SECRET-KEY-") {
-        return Err(
-            "Retrieved value is not a valid Age key. It should start with 'AGE-SECRET-KEY-'."
-                .to_string(),
-        );
+/// Masks the middle of a private key for display, e.g. `AGE-SECRET-KEY-1****...****ABCDEFGH`
+pub fn mask_key(key: &SecretString) -> String {
+    let key = key.expose_secret();
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 23 {
+        return "*".repeat(chars.len());
     }
+    let prefix: String = chars[..15].iter().collect();
+    let suffix: String = chars[chars.len() - 8..].iter().collect();
+    format!("{}{}{}", prefix, "*".repeat(chars.len() - 23), suffix)
+}
 
-    Ok(key)
+/// A short fingerprint of a recipient, the first 8 hex digits of the SHA-256
+/// of the key as written, for comparing keys over chat or a call
+pub fn fingerprint(recipient: &str) -> String {
+    Sha256::digest(recipient.trim().as_bytes())[..4]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
 }
 
-// Extract the public key from the age private key
+/// A recipient followed by its fingerprint, e.g. `age1... [3f2a9c1e]`
+pub fn with_fingerprint(recipient: &str) -> String {
+    format!("{} [{}]", recipient, fingerprint(recipient))
+}
+
+/// The public keys of all identities in `identities`, in order
+pub fn public_keys(identities: &str) -> Result<Vec<String>, &'static str> {
+    identities
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(extract_public_key)
+        .collect()
+}
+
+// Extract the public key from the age private key. Of an identity file with
+// several keys, the first one counts.
 pub fn extract_public_key(private_key: &str) -> Result<String, &'static str> {
+    let private_key = private_key
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or_default();
     // Parse the private key into an Identity
     let secret_key = SecretString::from(private_key);
     let identity = match Identity::from_str(secret_key.expose_secret()) {
         Ok(id) => id,
         Err(err) => {
-            print_error(format!("{} {}", "Invalid private key format:".red(), err));
+            print_error(format!("{} {}", tr("invalid-private-key").red(), err));
             return Err(err);
         }
     };
@@ -79,10 +258,44 @@ pub fn extract_public_key(private_key: &str) -> Result<String, &'static str> {
     Ok(derived_public_key)
 }
 
+/// Validates a recipient before it is written as an `age` value in .sops.yaml.
+/// Accepts x25519 recipients (`age1...`) and ssh public keys. The value may be a
+/// comma separated list, as sops allows.
+pub fn validate_age_recipients(value: &str) -> Result<(), String> {
+    for recipient in value.split(',').map(str::trim) {
+        if recipient.is_empty() {
+            return Err("Empty age recipient in list.".to_string());
+        }
+
+        let upper = recipient.to_uppercase();
+        if upper.starts_with("AGE-SECRET-KEY-") || upper.contains("PRIVATE KEY-----") {
+            return Err(
+                "You pasted a PRIVATE key, refusing to store it in .sops.yaml. Use the public key (age1...) instead."
+                    .to_string(),
+            );
+        }
+
+        if recipient.starts_with("ssh-ed25519 ") || recipient.starts_with("ssh-rsa ") {
+            continue;
+        }
+
+        if let Err(err) = Recipient::from_str(recipient) {
+            return Err(format!("Invalid age recipient '{}': {}", recipient, err));
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
 
-    use crate::util::op_key::extract_public_key;
+    use age::secrecy::{ExposeSecret, SecretString};
+
+    use crate::util::op_key::{
+        extract_public_key, fingerprint, mask_key, normalize_op_reference, parse_identity_file,
+        public_keys, validate_age_recipients,
+    };
 
     #[test]
     fn test_extract_public_key_valid() {
@@ -95,10 +308,107 @@ mod tests {
         assert!(pub_key.starts_with("age1"));
     }
 
+    #[test]
+    fn test_parse_identity_file() {
+        let first = "AGE-SECRET-KEY-1X9Q72KQG3J383K5SA030D46Q8WTYPDEKV6UA0RXZCXN56YVN22YQMNNCXJ";
+        let second_identity = age::x25519::Identity::generate();
+        let second = second_identity.to_string();
+        let file = format!(
+            "# created: 2024-01-01T00:00:00Z\n# public key: age1...\n{}\n\n{}\n",
+            first,
+            second.expose_secret()
+        );
+        let identities = parse_identity_file(&file).unwrap();
+        assert_eq!(
+            identities.expose_secret(),
+            format!("{}\n{}", first, second.expose_secret())
+        );
+        assert_eq!(
+            extract_public_key(&file).unwrap(),
+            extract_public_key(first).unwrap()
+        );
+        assert_eq!(
+            public_keys(&file).unwrap(),
+            vec![
+                extract_public_key(first).unwrap(),
+                second_identity.to_public().to_string()
+            ]
+        );
+
+        assert!(parse_identity_file("# only a comment\n").is_err());
+        assert!(parse_identity_file(&format!("{}\npassword123\n", first)).is_err());
+    }
+
     #[test]
     fn test_extract_public_key_invalid() {
         let invalid_key = "not-a-valid-key";
         let result = extract_public_key(invalid_key);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_validate_age_recipients_valid() {
+        let pub_key = extract_public_key(
+            "AGE-SECRET-KEY-1X9Q72KQG3J383K5SA030D46Q8WTYPDEKV6UA0RXZCXN56YVN22YQMNNCXJ",
+        )
+        .unwrap();
+        assert!(validate_age_recipients(&pub_key).is_ok());
+        assert!(validate_age_recipients(&format!("{}, {}", pub_key, pub_key)).is_ok());
+        assert!(validate_age_recipients("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAA user@host").is_ok());
+    }
+
+    #[test]
+    fn test_validate_age_recipients_rejects_private_key() {
+        let err = validate_age_recipients(
+            "AGE-SECRET-KEY-1X9Q72KQG3J383K5SA030D46Q8WTYPDEKV6UA0RXZCXN56YVN22YQMNNCXJ",
+        )
+        .unwrap_err();
+        assert!(err.contains("PRIVATE key"));
+    }
+
+    #[test]
+    fn test_validate_age_recipients_invalid() {
+        assert!(validate_age_recipients("age1notarealkey").is_err());
+        assert!(validate_age_recipients("").is_err());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let key = "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p";
+        assert_eq!(fingerprint(key).len(), 8);
+        assert!(fingerprint(key).chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(fingerprint(key), fingerprint(&format!(" {}\n", key)));
+        assert_ne!(
+            fingerprint(key),
+            fingerprint("age1lggyhqrw2nlhcxprm67z43rta597azn8gknawjehu9d9dl0jq3yqqvfafg")
+        );
+    }
+
+    #[test]
+    fn test_mask_key() {
+        let key = SecretString::from(
+            "AGE-SECRET-KEY-1X9Q72KQG3J383K5SA030D46Q8WTYPDEKV6UA0RXZCXN56YVN22YQMNNCXJ",
+        );
+        let masked = mask_key(&key);
+        assert!(masked.starts_with("AGE-SECRET-KEY-"));
+        assert!(masked.ends_with("QMNNCXJ"));
+        assert!(!masked.contains("X9Q72KQG3J"));
+        assert_eq!(masked.len(), key.expose_secret().len());
+
+        assert_eq!(mask_key(&SecretString::from("short")), "*****");
+    }
+
+    #[test]
+    fn test_normalize_op_reference() {
+        assert_eq!(
+            normalize_op_reference("op://a/b/100%").unwrap(),
+            "op://a/b/100%25"
+        );
+        assert_eq!(
+            normalize_op_reference("op://a/b/100%25").unwrap(),
+            "op://a/b/100%25"
+        );
+        assert!(normalize_op_reference("op://a/b").is_ok());
+        assert!(normalize_op_reference("a/b/c").is_err());
+    }
 }
@@ -0,0 +1,122 @@
+//! Pacing of `op` invocations. Accounts behind SSO throttle the 1Password CLI,
+//! which answers with HTTP 429 once too many calls arrive in a short time.
+//! Calls are spaced by `op_rate_limit.delay_ms` from the user config and
+//! retried with exponential backoff when they were rate limited.
+
+use serde::{Deserialize, Serialize};
+use std::process::{Command, Output};
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
+
+use super::{
+    i18n::tr_args, print_status::print_warning, session_record, user_config::read_user_config,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct OpRateLimit {
+    /// Minimum time between two `op` calls
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// How often a rate limited call is retried
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every further one
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    1_000
+}
+
+impl Default for OpRateLimit {
+    fn default() -> Self {
+        OpRateLimit {
+            delay_ms: 0,
+            retries: default_retries(),
+            backoff_ms: default_backoff_ms(),
+        }
+    }
+}
+
+impl OpRateLimit {
+    /// Wait before retry number `attempt`, starting at 0
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_millis(self.backoff_ms.saturating_mul(1 << attempt.min(16)))
+    }
+}
+
+/// When the last `op` call of this process started
+static LAST_CALL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Whether `op` failed because the account was rate limited
+pub fn is_rate_limited(stderr: &[u8]) -> bool {
+    let stderr = String::from_utf8_lossy(stderr).to_lowercase();
+    stderr.contains("429") || stderr.contains("too many requests") || stderr.contains("rate limit")
+}
+
+/// Waits until `delay` passed since the previous call and marks a new one
+fn pace(delay: Duration) {
+    let mut last = LAST_CALL.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(previous) = *last {
+        let elapsed = previous.elapsed();
+        if elapsed < delay {
+            sleep(delay - elapsed);
+        }
+    }
+    *last = Some(Instant::now());
+}
+
+/// Runs an `op` command like [`Command::output`], paced and retried as
+/// configured. The output of failed attempts is wiped, it may hold secrets.
+pub fn run_op(command: &mut Command) -> std::io::Result<Output> {
+    let limit = read_user_config().op_rate_limit.unwrap_or_default();
+    let mut attempt = 0;
+    loop {
+        pace(Duration::from_millis(limit.delay_ms));
+        let mut output = session_record::output(command)?;
+        if output.status.success() || !is_rate_limited(&output.stderr) || attempt >= limit.retries {
+            return Ok(output);
+        }
+        output.stdout.zeroize();
+        let wait = limit.backoff(attempt);
+        print_warning(tr_args(
+            "op-rate-limited",
+            &[("seconds", format!("{:.1}", wait.as_secs_f64()).into())],
+        ));
+        sleep(wait);
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{OpRateLimit, is_rate_limited};
+
+    #[test]
+    fn test_is_rate_limited() {
+        assert!(is_rate_limited(
+            b"[ERROR] 2024/01/01 12:00:00 Too Many Requests (429)"
+        ));
+        assert!(is_rate_limited(b"rate limit exceeded"));
+        assert!(!is_rate_limited(
+            b"[ERROR] \"Item\" isn't an item in the \"Vault\" vault"
+        ));
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let limit: OpRateLimit = serde_yaml::from_str("backoff_ms: 250").unwrap();
+        assert_eq!(limit.retries, 3);
+        assert_eq!(limit.backoff(0), Duration::from_millis(250));
+        assert_eq!(limit.backoff(2), Duration::from_millis(1_000));
+    }
+}
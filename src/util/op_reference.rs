@@ -0,0 +1,264 @@
+use std::fmt;
+use std::str::FromStr;
+
+const SCHEME: &str = "op://";
+
+/// A 1Password secret reference: `op://<vault>/<item>[/<section>]/<field>`.
+///
+/// Names are percent-escaped when formatted, so a `/` inside a vault, item or
+/// field name can't shift the other segments. `%`, `/` and control characters are
+/// escaped, everything else (including unicode) is kept as-is. When parsing, a
+/// `%` that doesn't start a `%XX` escape is kept as well, as `op` takes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpReference {
+    pub vault: String,
+    pub item: String,
+    pub section: Option<String>,
+    pub field: String,
+}
+
+impl OpReference {
+    pub fn new(vault: &str, item: &str, field: &str) -> Self {
+        OpReference {
+            vault: vault.to_string(),
+            item: item.to_string(),
+            section: None,
+            field: field.to_string(),
+        }
+    }
+}
+
+fn escape(segment: &str) -> String {
+    let mut escaped = String::with_capacity(segment.len());
+    for c in segment.chars() {
+        if c == '%' || c == '/' || c.is_ascii_control() {
+            escaped.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn unescape(segment: &str) -> Result<String, String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| format!("Escaped bytes in '{}' are not UTF-8", segment))
+}
+
+impl fmt::Display for OpReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}/{}/",
+            SCHEME,
+            escape(&self.vault),
+            escape(&self.item)
+        )?;
+        if let Some(section) = &self.section {
+            write!(f, "{}/", escape(section))?;
+        }
+        write!(f, "{}", escape(&self.field))
+    }
+}
+
+impl FromStr for OpReference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s.strip_prefix(SCHEME).ok_or_else(|| {
+            format!(
+                "'{}' is not a 1Password reference, expected op://<vault>/<item>/<field>",
+                s
+            )
+        })?;
+
+        let segments = rest
+            .split('/')
+            .map(unescape)
+            .collect::<Result<Vec<_>, _>>()?;
+        if segments.iter().any(String::is_empty) {
+            return Err(format!("'{}' contains an empty segment", s));
+        }
+
+        let mut segments = segments.into_iter();
+        match (
+            segments.next(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+        ) {
+            (Some(vault), Some(item), Some(field), None, None) => Ok(OpReference {
+                vault,
+                item,
+                section: None,
+                field,
+            }),
+            (Some(vault), Some(item), Some(section), Some(field), None) => Ok(OpReference {
+                vault,
+                item,
+                section: Some(section),
+                field,
+            }),
+            _ => Err(format!(
+                "'{}' must have the form op://<vault>/<item>[/<section>]/<field>",
+                s
+            )),
+        }
+    }
+}
+
+/// A 1Password Document item, e.g. an uploaded `keys.txt`: `op://<vault>/<item>`.
+/// Escaped like [`OpReference`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpDocument {
+    pub vault: String,
+    pub item: String,
+}
+
+impl fmt::Display for OpDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}/{}",
+            SCHEME,
+            escape(&self.vault),
+            escape(&self.item)
+        )
+    }
+}
+
+impl FromStr for OpDocument {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s.strip_prefix(SCHEME).ok_or_else(|| {
+            format!(
+                "'{}' is not a 1Password reference, expected op://<vault>/<item>",
+                s
+            )
+        })?;
+        match rest
+            .split('/')
+            .map(unescape)
+            .collect::<Result<Vec<_>, _>>()?
+            .as_slice()
+        {
+            [vault, item] if !vault.is_empty() && !item.is_empty() => Ok(OpDocument {
+                vault: vault.clone(),
+                item: item.clone(),
+            }),
+            _ => Err(format!("'{}' must have the form op://<vault>/<item>", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{OpDocument, OpReference};
+
+    #[test]
+    fn test_parse() {
+        let reference: OpReference = "op://Personal/opsops/Private Key".parse().unwrap();
+        assert_eq!(
+            reference,
+            OpReference::new("Personal", "opsops", "Private Key")
+        );
+
+        let reference: OpReference = "op://Vault/Item/keys/age".parse().unwrap();
+        assert_eq!(reference.section.as_deref(), Some("keys"));
+        assert_eq!(reference.field, "age");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for input in [
+            "Personal/opsops/Private Key",
+            "op://Personal/opsops",
+            "op://Personal//Private Key",
+            "op://a/b/c/d/e",
+            "op://a/b/%ff",
+        ] {
+            assert!(input.parse::<OpReference>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_keeps_stray_percent() {
+        let reference: OpReference = "op://a/b/100%".parse().unwrap();
+        assert_eq!(reference.field, "100%");
+        let reference: OpReference = "op://a/50%off/%+1".parse().unwrap();
+        assert_eq!(reference.item, "50%off");
+        assert_eq!(reference.field, "%+1");
+    }
+
+    #[test]
+    fn test_parse_document() {
+        let document: OpDocument = "op://Infra/age%2Fkeys.txt".parse().unwrap();
+        assert_eq!(document.vault, "Infra");
+        assert_eq!(document.item, "age/keys.txt");
+        assert_eq!(document.to_string(), "op://Infra/age%2Fkeys.txt");
+        assert!("op://Infra/keys.txt/field".parse::<OpDocument>().is_err());
+        assert!("op://Infra/".parse::<OpDocument>().is_err());
+    }
+
+    #[test]
+    fn test_escapes_slashes_and_percent() {
+        let reference = OpReference::new("Team/Infra", "age 100%", "Private Key");
+        assert_eq!(
+            reference.to_string(),
+            "op://Team%2FInfra/age 100%25/Private Key"
+        );
+    }
+
+    #[test]
+    fn test_keeps_unicode() {
+        let reference = OpReference::new("Persönlich", "🔑", "Schlüssel");
+        assert_eq!(reference.to_string(), "op://Persönlich/🔑/Schlüssel");
+    }
+
+    fn name() -> impl Strategy<Value = String> {
+        any::<String>().prop_filter("names can't be empty", |s| !s.is_empty())
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(
+            vault in name(),
+            item in name(),
+            section in proptest::option::of(name()),
+            field in name(),
+        ) {
+            let reference = OpReference { vault, item, section, field };
+            let formatted = reference.to_string();
+            prop_assert_eq!(formatted.parse::<OpReference>(), Ok(reference.clone()));
+
+            let segments = formatted.trim_start_matches("op://").split('/').count();
+            prop_assert_eq!(segments, if reference.section.is_some() { 4 } else { 3 });
+        }
+
+        #[test]
+        fn prop_parse_never_panics(input in any::<String>()) {
+            let _ = input.parse::<OpReference>();
+            let _ = format!("op://{}", input).parse::<OpReference>();
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Running `openssl` for what opsops has no crate for: keypairs, password
+//! hashes and parsing certificates. Secrets go through its stdin and stdout,
+//! never its arguments.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+use zeroize::Zeroizing;
+
+use super::session_record;
+
+/// Runs `openssl` with `input` on stdin and returns what it printed
+pub fn openssl(args: &[&str], input: &[u8]) -> Result<Zeroizing<String>, String> {
+    let mut command = Command::new("openssl");
+    command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let started = Instant::now();
+    let result = command.spawn().and_then(|mut child| {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input)?;
+        }
+        child.wait_with_output()
+    });
+    session_record::record(
+        &command,
+        started,
+        result.as_ref().map(|output| output.status.code()),
+    );
+    let output = result.map_err(|e| format!("Failed to run openssl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "openssl {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout)
+        .map(Zeroizing::new)
+        .map_err(|e| format!("openssl returned invalid UTF-8: {}", e))
+}
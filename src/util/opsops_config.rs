@@ -0,0 +1,136 @@
+//! `.opsops.yaml`, the project config for everything opsops adds on top of sops.
+//! It lives next to .sops.yaml, which is left to sops itself.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use super::{
+    formatting::Formatting,
+    guardrails::Guardrails,
+    host_keys::HostKey,
+    json_schema::RuleSchema,
+    notifications::Notification,
+    output_destinations::OutputDestination,
+    output_permissions::OutputPermissionRule,
+    passphrase_key::{KeyProvider, PassphraseSettings},
+    perf::{Category, span},
+    read_only::ensure_writable,
+    recipient_expiry::ExpiringRecipient,
+    rule_conditions::RuleCondition,
+    sops_config::sops_config_path,
+    sops_structs::SopsConfig,
+    sync_map::SyncEntry,
+    value_expiry::ValueExpiry,
+};
+use crate::GlobalContext;
+
+pub const OPSOPS_CONFIG_FILE: &str = ".opsops.yaml";
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct OpsopsConfig {
+    /// Schema version, see [`super::migrations`]. Missing means a project that
+    /// predates `.opsops.yaml`.
+    #[serde(default)]
+    pub version: u32,
+    /// Reference to the age key in 1Password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onepassworditem: Option<String>,
+    /// Keys of particular hosts, see [`super::host_keys`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub host_keys: Vec<HostKey>,
+    /// Where the age key comes from, 1Password unless set
+    #[serde(default, skip_serializing_if = "KeyProvider::is_default")]
+    pub keyprovider: KeyProvider,
+    /// How the key is derived with `keyprovider: passphrase`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase: Option<PassphraseSettings>,
+    /// Where backups of ciphertext go, relative to the project root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_dir: Option<String>,
+    /// Break-glass age recipient every creation rule has to encrypt to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escrow_recipient: Option<String>,
+    /// Where `registry sync` pulls the team's public keys from, see
+    /// [`super::member_registry`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub member_registry: Option<String>,
+    /// Recipients whose access ends on a given day, see [`super::recipient_expiry`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipient_expiry: Vec<ExpiringRecipient>,
+    /// Values that have to be renewed by a given day, see [`super::value_expiry`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub value_expiry: Vec<ValueExpiry>,
+    /// Where decrypted files go, see [`super::output_destinations`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decrypt_outputs: Vec<OutputDestination>,
+    /// Mode and owner of decrypted files, first matching `path_regex` wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_permissions: Vec<OutputPermissionRule>,
+    /// JSON Schemas decrypted documents have to match, per creation rule
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schemas: Vec<RuleSchema>,
+    /// Indentation and key order of documents sops writes, see [`super::formatting`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatting: Option<Formatting>,
+    /// Size limit and severity of the checks before encrypting, see
+    /// [`super::guardrails`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guardrails: Option<Guardrails>,
+    /// The `encrypted_regex` of new rules by file content, see
+    /// [`super::rule_conditions`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rule_conditions: Vec<RuleCondition>,
+    /// 1Password fields mirrored into encrypted files, see [`super::sync_map`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sync: Vec<SyncEntry>,
+    /// Who to tell when access to secrets changes, see [`super::notifications`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notifications: Vec<Notification>,
+    /// Settings of newer versions, kept for round-tripping
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+/// `.opsops.yaml` in the directory of .sops.yaml
+pub fn opsops_config_path(context: &GlobalContext) -> Option<PathBuf> {
+    let sops_path = sops_config_path(context)?;
+    Some(sops_path.parent().map_or_else(
+        || PathBuf::from(OPSOPS_CONFIG_FILE),
+        |dir| dir.join(OPSOPS_CONFIG_FILE),
+    ))
+}
+
+/// Reads `.opsops.yaml`, `None` if the project doesn't have one
+pub fn read_opsops_config(context: &GlobalContext) -> Result<Option<OpsopsConfig>, String> {
+    let _span = span(Category::ConfigIo, "read .opsops.yaml");
+    let Some(path) = opsops_config_path(context).filter(|p| p.is_file()) else {
+        return Ok(None);
+    };
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_yaml::from_str(&contents)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+pub fn write_opsops_config(config: &OpsopsConfig, context: &GlobalContext) -> Result<(), String> {
+    ensure_writable(context, "read-only-write-opsops-yaml")?;
+    let path = opsops_config_path(context).ok_or("Could not determine project root")?;
+    let yaml = serde_yaml::to_string(config)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    fs::write(&path, yaml).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Fills the settings kept in `.opsops.yaml` into a config read from .sops.yaml.
+/// A reference passed via `--op-item` wins over both files.
+pub fn apply_opsops_config(config: &mut SopsConfig, context: &GlobalContext) -> Result<(), String> {
+    if let Some(item) = read_opsops_config(context)?.and_then(|c| c.onepassworditem) {
+        config.onepassworditem = item;
+    }
+    if let Some(opitem) = &context.opitem {
+        config.onepassworditem = opitem.clone();
+    }
+    Ok(())
+}
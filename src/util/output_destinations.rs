@@ -0,0 +1,129 @@
+//! Where `decrypt` writes the plaintext of an encrypted file. Without
+//! configuration it goes next to the file, dropping a `.enc` extension.
+//! `decrypt_outputs:` in `.opsops.yaml` sends it elsewhere, typically into a
+//! git-ignored directory:
+//!
+//! ```yaml
+//! decrypt_outputs:
+//! - path_regex: ^k8s/(.+)\.enc\.yaml$
+//!   output: .generated/k8s/$1.yaml
+//! ```
+//!
+//! Both sides are relative to the project root and `output` may refer to the
+//! groups of `path_regex` as `$1` or `${name}`. The first matching entry wins.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::{
+    opsops_config::read_opsops_config, rule_match::project_relative_path, sops_config::config_dir,
+};
+use crate::GlobalContext;
+
+/// One entry of `decrypt_outputs:` in `.opsops.yaml`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct OutputDestination {
+    /// Matched against the encrypted file's path relative to the project root
+    pub path_regex: String,
+    /// Where its plaintext goes, relative to the project root
+    pub output: String,
+}
+
+/// The destination `destinations` give the root relative `file`, `None` if no
+/// entry matches
+pub fn mapped_output(
+    destinations: &[OutputDestination],
+    file: &str,
+) -> Result<Option<String>, String> {
+    for destination in destinations {
+        let regex = Regex::new(&destination.path_regex).map_err(|e| {
+            format!(
+                "Invalid path_regex '{}' in decrypt_outputs: {}",
+                destination.path_regex, e
+            )
+        })?;
+        if let Some(captures) = regex.captures(file) {
+            let mut output = String::new();
+            captures.expand(&destination.output, &mut output);
+            if output.is_empty() || Path::new(&output).is_absolute() || output.contains("..") {
+                return Err(format!(
+                    "decrypt_outputs maps {} to '{}', outputs have to stay inside the project",
+                    file, output
+                ));
+            }
+            return Ok(Some(output));
+        }
+    }
+    Ok(None)
+}
+
+/// `source` without its `.enc` extension, or `source` itself if it has none
+pub fn default_output(source: &str) -> &str {
+    source.strip_suffix(".enc").unwrap_or(source)
+}
+
+/// Where the plaintext of `source` goes: its `decrypt_outputs:` destination,
+/// relative to the working directory if it lies below it, or the default
+pub fn output_for(source: &str, context: &GlobalContext) -> Result<PathBuf, String> {
+    let destinations = read_opsops_config(context)?
+        .map(|config| config.decrypt_outputs)
+        .unwrap_or_default();
+    let Some(root) = config_dir(context).filter(|_| !destinations.is_empty()) else {
+        return Ok(PathBuf::from(default_output(source)));
+    };
+    let mapped = project_relative_path(&root, Path::new(source))
+        .map(|relative| mapped_output(&destinations, &relative))
+        .transpose()?
+        .flatten();
+    let Some(output) = mapped else {
+        return Ok(PathBuf::from(default_output(source)));
+    };
+    let output = root.join(output);
+    let working_dir = context.working_dir();
+    Ok(output
+        .strip_prefix(&working_dir)
+        .map(Path::to_path_buf)
+        .unwrap_or(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OutputDestination, default_output, mapped_output};
+
+    fn destination(path_regex: &str, output: &str) -> OutputDestination {
+        OutputDestination {
+            path_regex: path_regex.to_string(),
+            output: output.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_mapped_output() {
+        let destinations = [
+            destination(r"^k8s/(.+)\.enc\.yaml$", ".generated/k8s/$1.yaml"),
+            destination(r"^(?P<env>\w+)/\.env\.enc$", ".generated/${env}.env"),
+            destination(r"^escape/", "../outside"),
+        ];
+        assert_eq!(
+            mapped_output(&destinations, "k8s/apps/db.enc.yaml"),
+            Ok(Some(".generated/k8s/apps/db.yaml".to_string()))
+        );
+        assert_eq!(
+            mapped_output(&destinations, "prod/.env.enc"),
+            Ok(Some(".generated/prod.env".to_string()))
+        );
+        assert_eq!(mapped_output(&destinations, "other.yaml"), Ok(None));
+        assert!(
+            mapped_output(&destinations, "escape/a.yaml")
+                .unwrap_err()
+                .contains("inside the project")
+        );
+    }
+
+    #[test]
+    fn test_default_output() {
+        assert_eq!(default_output("secrets.yaml.enc"), "secrets.yaml");
+        assert_eq!(default_output("secrets.yaml"), "secrets.yaml");
+    }
+}
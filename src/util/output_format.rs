@@ -0,0 +1,39 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Output format for commands that can emit machine-readable output
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable, colored text
+    #[default]
+    Text,
+    Json,
+    Yaml,
+}
+
+/// Output format for commands that report findings in files
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum FindingsFormat {
+    /// Human readable, colored text
+    #[default]
+    Text,
+    /// SARIF 2.1.0, for GitHub code scanning and editors
+    Sarif,
+}
+
+/// Serializes `value` in a structured format. Returns `None` for `OutputFormat::Text`.
+pub fn render_structured<T: Serialize>(
+    value: &T,
+    format: OutputFormat,
+) -> Option<Result<String, String>> {
+    match format {
+        OutputFormat::Text => None,
+        OutputFormat::Json => Some(
+            serde_json::to_string_pretty(value)
+                .map_err(|e| format!("Failed to serialize JSON: {}", e)),
+        ),
+        OutputFormat::Yaml => Some(
+            serde_yaml::to_string(value).map_err(|e| format!("Failed to serialize YAML: {}", e)),
+        ),
+    }
+}
@@ -0,0 +1,241 @@
+//! Mode and ownership of plaintext files opsops writes. sops creates them with
+//! the umask, which leaves world-readable secrets on shared build hosts, so
+//! decrypted files are `0600` unless `--mode`, `--owner` or an
+//! `output_permissions:` entry in `.opsops.yaml` says otherwise. Under sudo
+//! they belong to the invoking user rather than root.
+
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::path::Path;
+
+use super::{
+    op::invoking_user,
+    opsops_config::read_opsops_config,
+    platform::{Native, Platform},
+    rule_match::project_relative_path,
+    sops_config::config_dir,
+};
+use crate::GlobalContext;
+
+/// Mode of decrypted files unless configured otherwise
+pub const DEFAULT_MODE: u32 = 0o600;
+
+/// Defaults for decrypted files whose path matches `path_regex`, in `.opsops.yaml`
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct OutputPermissionRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_regex: Option<String>,
+    /// Octal, e.g. "0640"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// `user`, `user:group` or `:group`, names or ids
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+/// What to apply to a decrypted file
+#[derive(Debug, PartialEq)]
+pub struct OutputPermissions {
+    pub mode: u32,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+pub fn parse_mode(mode: &str) -> Result<u32, String> {
+    let digits = mode.trim_start_matches("0o");
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|m| *m <= 0o7777)
+        .ok_or_else(|| format!("Invalid mode '{}', expected octal like 0600", mode))
+}
+
+fn unknown(kind: &str, name: &str) -> String {
+    if cfg!(all(unix, feature = "impersonation")) {
+        format!("Unknown {} '{}'", kind, name)
+    } else {
+        format!(
+            "Can't look up {} '{}', this build of opsops only takes numeric ids",
+            kind, name
+        )
+    }
+}
+
+/// Parses `user:group`, `user` or `:group` into ids
+pub fn parse_owner(owner: &str) -> Result<(Option<u32>, Option<u32>), String> {
+    let (user, group) = owner.split_once(':').unwrap_or((owner, ""));
+    let uid = match user {
+        "" => None,
+        user => Some(match user.parse() {
+            Ok(uid) => uid,
+            Err(_) => {
+                Native::user_by_name(user)
+                    .ok_or_else(|| unknown("user", user))?
+                    .uid
+            }
+        }),
+    };
+    let gid = match group {
+        "" => None,
+        group => Some(match group.parse() {
+            Ok(gid) => gid,
+            Err(_) => Native::group_by_name(group).ok_or_else(|| unknown("group", group))?,
+        }),
+    };
+    Ok((uid, gid))
+}
+
+/// The first rule whose `path_regex` matches the root relative `file`
+fn matching_rule<'a>(
+    rules: &'a [OutputPermissionRule],
+    file: &str,
+) -> Option<&'a OutputPermissionRule> {
+    rules.iter().find(|rule| match &rule.path_regex {
+        Some(pattern) => regex::Regex::new(pattern).is_ok_and(|r| r.is_match(file)),
+        None => true,
+    })
+}
+
+/// Settles the permissions for `file`: command line options win over the
+/// matching `output_permissions:` entry, which wins over the defaults
+pub fn resolve_permissions(
+    file: &Path,
+    mode: Option<&str>,
+    owner: Option<&str>,
+    context: &GlobalContext,
+) -> Result<OutputPermissions, String> {
+    let rules = read_opsops_config(context)?
+        .map(|c| c.output_permissions)
+        .unwrap_or_default();
+    let rule = config_dir(context)
+        .and_then(|root| project_relative_path(&root, file))
+        .and_then(|relative| matching_rule(&rules, &relative).cloned())
+        .unwrap_or_default();
+
+    let mode = match mode.or(rule.mode.as_deref()) {
+        Some(mode) => parse_mode(mode)?,
+        None => DEFAULT_MODE,
+    };
+    let (uid, gid) = match owner.or(rule.owner.as_deref()) {
+        Some(owner) => parse_owner(owner)?,
+        // Under sudo the plaintext belongs to whoever asked for it, not root
+        None => match invoking_user() {
+            Some(user) => (Some(user.uid), Some(user.gid)),
+            None => (None, None),
+        },
+    };
+    Ok(OutputPermissions { mode, uid, gid })
+}
+
+/// Restricts `file` to its owner before sops writes plaintext to it, creating
+/// it if needed, so it is never readable by others in between. The configured
+/// mode is applied afterwards, it might not allow sops to write.
+#[cfg(unix)]
+pub fn prepare_output(file: &Path) -> Result<(), String> {
+    use std::fs::{self, Permissions};
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    if file.exists() {
+        fs::set_permissions(file, Permissions::from_mode(DEFAULT_MODE))
+    } else {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(DEFAULT_MODE)
+            .open(file)
+            .map(|_| ())
+    }
+    .map_err(|e| format!("Failed to restrict {}: {}", file.display(), e))
+}
+
+/// Creates `file` if needed. Without unix modes it inherits the access rules
+/// of its directory.
+#[cfg(not(unix))]
+pub fn prepare_output(file: &Path) -> Result<(), String> {
+    if file.exists() {
+        return Ok(());
+    }
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(file)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to create {}: {}", file.display(), e))
+}
+
+/// Applies mode and owner to a written file
+#[cfg(unix)]
+pub fn apply_permissions(file: &Path, permissions: &OutputPermissions) -> Result<(), String> {
+    use std::fs::{self, Permissions};
+    use std::os::unix::fs::{PermissionsExt, chown};
+
+    fs::set_permissions(file, Permissions::from_mode(permissions.mode))
+        .map_err(|e| format!("Failed to set the mode of {}: {}", file.display(), e))?;
+    if permissions.uid.is_some() || permissions.gid.is_some() {
+        chown(file, permissions.uid, permissions.gid)
+            .map_err(|e| format!("Failed to change the owner of {}: {}", file.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Owners can't be set without unix ids, the mode has no equivalent and is
+/// ignored
+#[cfg(not(unix))]
+pub fn apply_permissions(file: &Path, permissions: &OutputPermissions) -> Result<(), String> {
+    if permissions.uid.is_some() || permissions.gid.is_some() {
+        return Err(format!(
+            "Can't change the owner of {} on this platform",
+            file.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OutputPermissionRule, matching_rule, parse_mode, parse_owner};
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("0600"), Ok(0o600));
+        assert_eq!(parse_mode("640"), Ok(0o640));
+        assert!(parse_mode("0900").is_err());
+        assert!(parse_mode("rw-------").is_err());
+    }
+
+    #[test]
+    fn test_parse_owner() {
+        assert_eq!(parse_owner("1000:1001"), Ok((Some(1000), Some(1001))));
+        assert_eq!(parse_owner(":0"), Ok((None, Some(0))));
+        assert!(parse_owner("no-such-user-here").is_err());
+        if cfg!(feature = "impersonation") {
+            assert_eq!(parse_owner("root").map(|(uid, _)| uid), Ok(Some(0)));
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = vec![
+            OutputPermissionRule {
+                path_regex: Some("^ci/".to_string()),
+                mode: Some("0640".to_string()),
+                owner: None,
+            },
+            OutputPermissionRule {
+                path_regex: None,
+                mode: Some("0400".to_string()),
+                owner: None,
+            },
+        ];
+        assert_eq!(
+            matching_rule(&rules, "ci/env.yaml")
+                .unwrap()
+                .mode
+                .as_deref(),
+            Some("0640")
+        );
+        assert_eq!(
+            matching_rule(&rules, "app.yaml").unwrap().mode.as_deref(),
+            Some("0400")
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Passphrase mode, for small projects without a key manager: the age
+//! identity is derived from a passphrase with scrypt instead of being read
+//! from 1Password. `.opsops.yaml` keeps the salt, the work factor and the
+//! public key, which tells a mistyped passphrase from the right one.
+
+use age::{
+    secrecy::{ExposeSecret, SecretString},
+    x25519::Identity,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use dialoguer::{Password, theme::ColorfulTheme};
+use serde::{Deserialize, Serialize};
+use std::io::IsTerminal;
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+use super::{i18n::tr, mnemonic::identity_from_bytes, opsops_config::read_opsops_config};
+use crate::GlobalContext;
+
+/// Read instead of prompting, for scripts
+pub const PASSPHRASE_ENV: &str = "OPSOPS_PASSPHRASE";
+/// 2^18 iterations, what age itself uses for passphrases
+const DEFAULT_LOG_N: u8 = 18;
+const SALT_LEN: usize = 16;
+
+/// The key derived in this process, so bulk commands prompt only once
+static DERIVED_KEY: Mutex<Option<SecretString>> = Mutex::new(None);
+
+/// Where the age key comes from, `keyprovider:` in `.opsops.yaml`
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyProvider {
+    #[default]
+    #[serde(rename = "1password")]
+    OnePassword,
+    Passphrase,
+}
+
+impl KeyProvider {
+    pub fn is_default(&self) -> bool {
+        *self == KeyProvider::default()
+    }
+}
+
+/// `passphrase:` in `.opsops.yaml`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct PassphraseSettings {
+    /// Base64 encoded scrypt salt
+    pub salt: String,
+    /// scrypt work factor, log2 of the iterations
+    #[serde(default = "default_log_n")]
+    pub log_n: u8,
+    /// Public key of the derived identity
+    pub public_key: String,
+}
+
+fn default_log_n() -> u8 {
+    DEFAULT_LOG_N
+}
+
+/// Derives the age identity of `passphrase` with scrypt (r = 8, p = 1)
+pub fn derive_identity(passphrase: &str, salt: &[u8], log_n: u8) -> Result<Identity, String> {
+    let params = scrypt::Params::new(log_n, 8, 1, 32)
+        .map_err(|e| format!("Invalid scrypt work factor {}: {}", log_n, e))?;
+    let mut secret = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, secret.as_mut())
+        .map_err(|e| format!("Failed to derive the key: {}", e))?;
+    identity_from_bytes(secret.as_ref())
+}
+
+/// Settings for a new passphrase key with a random salt, and the key
+pub fn new_passphrase_key(passphrase: &str) -> Result<(PassphraseSettings, Identity), String> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let identity = derive_identity(passphrase, &salt, DEFAULT_LOG_N)?;
+    let settings = PassphraseSettings {
+        salt: STANDARD.encode(salt),
+        log_n: DEFAULT_LOG_N,
+        public_key: identity.to_public().to_string(),
+    };
+    Ok((settings, identity))
+}
+
+/// The passphrase from `OPSOPS_PASSPHRASE`, else prompted for. `confirm` asks
+/// twice, for a new one.
+pub fn read_passphrase(confirm: bool) -> Result<Zeroizing<String>, String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(format!(
+            "The project's key is derived from a passphrase. Set {} or run opsops in a terminal.",
+            PASSPHRASE_ENV
+        ));
+    }
+    let theme = ColorfulTheme::default();
+    let mut prompt = Password::with_theme(&theme).with_prompt(tr("passphrase"));
+    if confirm {
+        prompt = prompt.with_confirmation(tr("passphrase-repeat"), tr("passphrase-mismatch"));
+    }
+    prompt
+        .interact()
+        .map(Zeroizing::new)
+        .map_err(|e| format!("Failed to read the passphrase: {}", e))
+}
+
+/// The passphrase settings of the project, `None` unless it uses
+/// `keyprovider: passphrase`
+pub fn passphrase_settings(context: &GlobalContext) -> Result<Option<PassphraseSettings>, String> {
+    let Some(config) = read_opsops_config(context)? else {
+        return Ok(None);
+    };
+    match (config.keyprovider, config.passphrase) {
+        (KeyProvider::OnePassword, _) => Ok(None),
+        (KeyProvider::Passphrase, Some(settings)) => Ok(Some(settings)),
+        (KeyProvider::Passphrase, None) => Err(
+            ".opsops.yaml sets keyprovider: passphrase but has no passphrase settings. Run 'opsops init --passphrase' in a new project."
+                .to_string(),
+        ),
+    }
+}
+
+/// Derives the project's key from the passphrase, refusing one that derives
+/// a different key
+pub fn key_from_passphrase(settings: &PassphraseSettings) -> Result<SecretString, String> {
+    let mut cached = DERIVED_KEY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(key) = cached.as_ref() {
+        return Ok(key.clone());
+    }
+
+    let salt = STANDARD
+        .decode(&settings.salt)
+        .map_err(|e| format!("Invalid passphrase salt in .opsops.yaml: {}", e))?;
+    let passphrase = read_passphrase(false)?;
+    let identity = derive_identity(&passphrase, &salt, settings.log_n)?;
+    if identity.to_public().to_string() != settings.public_key {
+        return Err("Wrong passphrase, it doesn't derive the project's key".to_string());
+    }
+
+    let key = SecretString::from(identity.to_string().expose_secret().to_string());
+    *cached = Some(key.clone());
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::derive_identity;
+
+    #[test]
+    fn test_derive_identity() {
+        let salt = [7u8; 16];
+        let key = derive_identity("correct horse battery staple", &salt, 10).unwrap();
+        assert_eq!(
+            key.to_public(),
+            derive_identity("correct horse battery staple", &salt, 10)
+                .unwrap()
+                .to_public()
+        );
+        assert_ne!(
+            key.to_public(),
+            derive_identity("correct horse battery staple", &[8u8; 16], 10)
+                .unwrap()
+                .to_public()
+        );
+        assert_ne!(
+            key.to_public(),
+            derive_identity("correct horse battery stapler", &salt, 10)
+                .unwrap()
+                .to_public()
+        );
+        assert!(derive_identity("x", &salt, 64).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! `--profile-perf`: where a command spent its time, to tell whether slowness
+//! comes from 1Password, sops or opsops itself. Spans are only measured while
+//! profiling; the report is printed to stderr when the process exits, however
+//! it exits.
+
+use serde::Serialize;
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use super::i18n::tr;
+
+/// How many of the slowest spans the report lists
+const SLOWEST: usize = 5;
+
+/// What a span measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// Runs of the `op` CLI
+    Op,
+    /// Runs of sops
+    Sops,
+    /// Other external programs, e.g. kubectl or the editor
+    External,
+    /// Getting the age key, including the op calls and agent requests for it
+    KeyRetrieval,
+    /// Reading and writing .sops.yaml, .opsops.yaml and the user config
+    ConfigIo,
+}
+
+impl Category {
+    pub fn for_program(program: &str) -> Category {
+        match program.rsplit('/').next().unwrap_or(program) {
+            "op" => Category::Op,
+            "sops" => Category::Sops,
+            _ => Category::External,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Category::Op => "1Password (op)",
+            Category::Sops => "sops",
+            Category::External => "other programs",
+            Category::KeyRetrieval => "key retrieval",
+            Category::ConfigIo => "config IO",
+        }
+    }
+
+    /// Time in external programs, the rest is opsops itself
+    fn is_external(self) -> bool {
+        matches!(self, Category::Op | Category::Sops | Category::External)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Measured {
+    category: Category,
+    label: String,
+    ms: u64,
+}
+
+struct Profile {
+    started: Instant,
+    json: bool,
+    spans: Vec<Measured>,
+}
+
+static PROFILE: OnceLock<Mutex<Profile>> = OnceLock::new();
+
+thread_local! {
+    /// Open spans per category, nested ones would be counted twice
+    static OPEN: Cell<[u32; 5]> = const { Cell::new([0; 5]) };
+}
+
+/// Starts profiling this process and prints the report when it exits, as JSON
+/// with `json`
+pub fn enable(json: bool) {
+    let profile = Profile {
+        started: Instant::now(),
+        json,
+        spans: Vec::new(),
+    };
+    if PROFILE.set(Mutex::new(profile)).is_ok() {
+        // Commands exit through process::exit, which runs atexit handlers
+        unsafe {
+            libc::atexit(print_report_at_exit);
+        }
+    }
+}
+
+/// Records a finished span
+pub fn record(category: Category, label: impl Into<String>, duration: Duration) {
+    if let Some(profile) = PROFILE.get() {
+        let mut profile = profile.lock().unwrap_or_else(|e| e.into_inner());
+        profile.spans.push(Measured {
+            category,
+            label: label.into(),
+            ms: duration.as_millis() as u64,
+        });
+    }
+}
+
+/// Measures from now until it is dropped
+pub struct Span {
+    category: Category,
+    label: &'static str,
+    /// `None` if profiling is off or a span of the category is already open
+    started: Option<Instant>,
+}
+
+/// Starts a span, e.g. `let _span = span(Category::ConfigIo, "read .sops.yaml");`
+pub fn span(category: Category, label: &'static str) -> Span {
+    let started = PROFILE.get().and_then(|_| {
+        OPEN.with(|open| {
+            let mut counts = open.get();
+            counts[category as usize] += 1;
+            open.set(counts);
+            (counts[category as usize] == 1).then(Instant::now)
+        })
+    });
+    Span {
+        category,
+        label,
+        started,
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if PROFILE.get().is_none() {
+            return;
+        }
+        OPEN.with(|open| {
+            let mut counts = open.get();
+            counts[self.category as usize] -= 1;
+            open.set(counts);
+        });
+        if let Some(started) = self.started {
+            record(self.category, self.label, started.elapsed());
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Total {
+    ms: u64,
+    count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    total_ms: u64,
+    /// Wall time outside external programs
+    opsops_ms: u64,
+    categories: BTreeMap<Category, Total>,
+    slowest: Vec<Measured>,
+}
+
+fn report(total: Duration, spans: &[Measured]) -> Report {
+    let mut categories: BTreeMap<Category, Total> = BTreeMap::new();
+    for span in spans {
+        let entry = categories.entry(span.category).or_default();
+        entry.ms += span.ms;
+        entry.count += 1;
+    }
+    let total_ms = total.as_millis() as u64;
+    let external: u64 = categories
+        .iter()
+        .filter(|(category, _)| category.is_external())
+        .map(|(_, total)| total.ms)
+        .sum();
+    let mut slowest = spans.to_vec();
+    slowest.sort_by_key(|span| Reverse(span.ms));
+    slowest.truncate(SLOWEST);
+    Report {
+        total_ms,
+        opsops_ms: total_ms.saturating_sub(external),
+        categories,
+        slowest,
+    }
+}
+
+fn print_report(report: &Report, json: bool) {
+    if json {
+        let json = serde_json::json!({ "perf": report });
+        eprintln!("{}", json);
+        return;
+    }
+    eprintln!("{}", tr("perf-heading"));
+    eprintln!("  {:16} {:>7} ms", tr("perf-total"), report.total_ms);
+    for (category, total) in &report.categories {
+        eprintln!(
+            "  {:16} {:>7} ms  {}x",
+            category.label(),
+            total.ms,
+            total.count
+        );
+    }
+    eprintln!("  {:16} {:>7} ms", tr("perf-opsops"), report.opsops_ms);
+    if !report.slowest.is_empty() {
+        eprintln!("{}", tr("perf-slowest"));
+        for span in &report.slowest {
+            eprintln!("  {:>7} ms  {}", span.ms, span.label);
+        }
+    }
+}
+
+extern "C" fn print_report_at_exit() {
+    let Some(profile) = PROFILE.get() else {
+        return;
+    };
+    // Released before printing, the messages may be loaded from the user
+    // config, which records a span of its own
+    let (report, json) = {
+        let profile = profile.lock().unwrap_or_else(|e| e.into_inner());
+        (
+            report(profile.started.elapsed(), &profile.spans),
+            profile.json,
+        )
+    };
+    print_report(&report, json);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Category, Measured, report};
+    use std::time::Duration;
+
+    #[test]
+    fn test_report() {
+        let span = |category, label: &str, ms| Measured {
+            category,
+            label: label.to_string(),
+            ms,
+        };
+        let report = report(
+            Duration::from_millis(1000),
+            &[
+                span(Category::KeyRetrieval, "age key", 600),
+                span(Category::Op, "op read", 550),
+                span(Category::Sops, "sops --decrypt", 300),
+                span(Category::ConfigIo, "read .sops.yaml", 5),
+            ],
+        );
+        assert_eq!(report.opsops_ms, 150);
+        assert_eq!(report.categories[&Category::Op].count, 1);
+        assert_eq!(report.slowest[0].label, "age key");
+        assert_eq!(report.slowest.len(), 4);
+        assert_eq!(Category::for_program("/usr/bin/sops"), Category::Sops);
+        assert_eq!(Category::for_program("kubectl"), Category::External);
+    }
+}
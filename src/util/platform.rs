@@ -0,0 +1,209 @@
+//! What opsops needs from the operating system beyond std: who runs it, who
+//! invoked it through sudo, doas or pkexec, and running `op` as that user.
+//! [`Unix`] implements it with libc and, with the `impersonation` feature, the
+//! system user database. [`Portable`] gets by with the environment on targets
+//! without uids, such as Windows. [`Native`] is the one for the build target.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A user from the system user database
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub name: String,
+    pub uid: u32,
+    /// The primary group
+    pub gid: u32,
+    pub home: PathBuf,
+}
+
+pub trait Platform {
+    /// The effective uid of this process, `None` where there are no uids
+    fn effective_uid() -> Option<u32>;
+
+    /// The login name of the user running opsops
+    fn current_user_name() -> Option<String>;
+
+    fn hostname() -> Option<String>;
+
+    /// `None` if the user doesn't exist or the platform can't look users up
+    fn user_by_name(name: &str) -> Option<Account>;
+
+    fn user_by_uid(uid: u32) -> Option<Account>;
+
+    /// The gid of the group called `name`
+    fn group_by_name(name: &str) -> Option<u32>;
+
+    /// Makes `command` run as `account`, with its home directory as `HOME`
+    fn run_as(command: &mut Command, account: &Account);
+}
+
+/// Linux, macOS, the BSDs and other unix targets
+#[cfg(unix)]
+pub struct Unix;
+
+/// Targets without uids or a user database
+#[cfg(not(unix))]
+pub struct Portable;
+
+#[cfg(unix)]
+pub type Native = Unix;
+
+#[cfg(not(unix))]
+pub type Native = Portable;
+
+/// The value of the first of `variables` that is set and not empty
+#[cfg(any(not(unix), not(feature = "impersonation")))]
+fn first_set(variables: &[&str]) -> Option<String> {
+    variables
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+}
+
+#[cfg(all(unix, feature = "impersonation"))]
+mod user_db {
+    use users::os::unix::UserExt;
+
+    use super::Account;
+
+    fn account(user: users::User) -> Account {
+        Account {
+            name: user.name().to_string_lossy().into_owned(),
+            uid: user.uid(),
+            gid: user.primary_group_id(),
+            home: user.home_dir().to_path_buf(),
+        }
+    }
+
+    pub fn current_name() -> Option<String> {
+        users::get_current_username().map(|name| name.to_string_lossy().into_owned())
+    }
+
+    pub fn by_name(name: &str) -> Option<Account> {
+        users::get_user_by_name(name).map(account)
+    }
+
+    pub fn by_uid(uid: u32) -> Option<Account> {
+        users::get_user_by_uid(uid).map(account)
+    }
+
+    pub fn group(name: &str) -> Option<u32> {
+        users::get_group_by_name(name).map(|group| group.gid())
+    }
+}
+
+/// Without the user database only the environment names the user, and
+/// nobody can be impersonated
+#[cfg(all(unix, not(feature = "impersonation")))]
+mod user_db {
+    use super::{Account, first_set};
+
+    pub fn current_name() -> Option<String> {
+        first_set(&["USER", "LOGNAME"])
+    }
+
+    pub fn by_name(_name: &str) -> Option<Account> {
+        None
+    }
+
+    pub fn by_uid(_uid: u32) -> Option<Account> {
+        None
+    }
+
+    pub fn group(_name: &str) -> Option<u32> {
+        None
+    }
+}
+
+#[cfg(unix)]
+impl Platform for Unix {
+    fn effective_uid() -> Option<u32> {
+        Some(unsafe { libc::geteuid() })
+    }
+
+    fn current_user_name() -> Option<String> {
+        user_db::current_name()
+    }
+
+    fn hostname() -> Option<String> {
+        let mut buf = [0u8; 256];
+        let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+        if ret != 0 {
+            return None;
+        }
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        Some(String::from_utf8_lossy(&buf[..len]).to_string())
+    }
+
+    fn user_by_name(name: &str) -> Option<Account> {
+        user_db::by_name(name)
+    }
+
+    fn user_by_uid(uid: u32) -> Option<Account> {
+        user_db::by_uid(uid)
+    }
+
+    fn group_by_name(name: &str) -> Option<u32> {
+        user_db::group(name)
+    }
+
+    fn run_as(command: &mut Command, account: &Account) {
+        use std::os::unix::process::CommandExt;
+
+        command
+            .uid(account.uid)
+            .gid(account.gid)
+            .env("HOME", &account.home);
+    }
+}
+
+#[cfg(not(unix))]
+impl Platform for Portable {
+    fn effective_uid() -> Option<u32> {
+        None
+    }
+
+    fn current_user_name() -> Option<String> {
+        first_set(&["USERNAME", "USER"])
+    }
+
+    fn hostname() -> Option<String> {
+        first_set(&["COMPUTERNAME", "HOSTNAME"])
+    }
+
+    fn user_by_name(_name: &str) -> Option<Account> {
+        None
+    }
+
+    fn user_by_uid(_uid: u32) -> Option<Account> {
+        None
+    }
+
+    fn group_by_name(_name: &str) -> Option<u32> {
+        None
+    }
+
+    fn run_as(_command: &mut Command, _account: &Account) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Native, Platform};
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_identity() {
+        assert!(Native::effective_uid().is_some());
+        assert!(Native::hostname().is_some_and(|name| !name.is_empty()));
+    }
+
+    #[cfg(all(unix, feature = "impersonation"))]
+    #[test]
+    fn test_user_database() {
+        let root = Native::user_by_uid(0).unwrap();
+        assert_eq!(root.name, "root");
+        assert_eq!(Native::user_by_name("root"), Some(root));
+        assert!(Native::user_by_name("no-such-user-opsops").is_none());
+    }
+}
@@ -1,19 +1,48 @@
-use colored::Colorize;
 use std::fmt::Display;
 
+use super::advice::advice_for;
+use super::formatter::{Level, formatter};
+use super::session_record::record_error;
+
 pub fn print_success<T: Display>(message: T) {
-    println!("{} {}", "✔".green(), message)
+    formatter().message(Level::Success, &message.to_string())
 }
 
 pub fn print_warning<T: Display>(message: T) {
-    println!("{} {}", "⚠".yellow(), message)
+    formatter().message(Level::Warning, &message.to_string())
+}
+
+/// Like [`print_warning`], on stderr, for warnings that can come up while
+/// stdout is the output of the command, e.g. decrypted content
+pub fn eprint_warning<T: Display>(message: T) {
+    formatter().message_to(Level::Warning, &message.to_string(), true)
 }
 
+/// Prints an error, followed by the next step if it is a known failure
 pub fn print_error<T: Display>(message: T) {
-    eprintln!("{} {}", "⨯".red(), message.to_string().red())
+    let message = message.to_string();
+    formatter().message(Level::Error, &message);
+    record_error(&message);
+    if let Some(advice) = advice_for(&message, None) {
+        print_advice(advice);
+    }
+}
+
+/// Like [`print_error`], with `file` filled into the advice
+pub fn print_file_error<T: Display>(message: T, file: &str) {
+    let message = message.to_string();
+    formatter().message(Level::Error, &message);
+    record_error(&message);
+    if let Some(advice) = advice_for(&message, Some(file)) {
+        print_advice(advice);
+    }
+}
+
+/// A command that gets the user past an error, printed beneath it
+pub fn print_advice<T: Display>(message: T) {
+    formatter().message(Level::Advice, &message.to_string())
 }
 
 pub fn print_info<T: Display>(message: T) {
-    // println!("{} {}", "ℹ".blue(), message)
-    println!("{} {}", "".blue(), message)
+    formatter().message(Level::Info, &message.to_string())
 }
@@ -0,0 +1,245 @@
+//! Layouts `opsops new` scaffolds: directories, creation rules, example
+//! secrets encrypted right away and a CI workflow checking them.
+
+use clap::ValueEnum;
+
+/// A built-in project layout
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ProjectTemplate {
+    /// Flux GitOps repository with SOPS decryption of Kubernetes Secrets
+    FluxCluster,
+    /// Terraform root modules per environment reading secrets with the sops provider
+    TerraformLive,
+    /// Application with encrypted .env files per environment
+    DotenvApp,
+}
+
+/// A file of a template, relative to the new project
+pub struct TemplateFile {
+    pub path: &'static str,
+    pub contents: &'static str,
+    /// Example secrets, encrypted after the config is written
+    pub encrypt: bool,
+}
+
+const fn file(path: &'static str, contents: &'static str) -> TemplateFile {
+    TemplateFile {
+        path,
+        contents,
+        encrypt: false,
+    }
+}
+
+const fn secret(path: &'static str, contents: &'static str) -> TemplateFile {
+    TemplateFile {
+        path,
+        contents,
+        encrypt: true,
+    }
+}
+
+/// Runs the checks that need no key on every push
+const CI_WORKFLOW: &str = r#"name: secrets
+
+on:
+  push:
+  pull_request:
+
+jobs:
+  secrets:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+        with:
+          fetch-depth: 0
+      - name: Install sops and opsops
+        run: |
+          curl -sSLo /usr/local/bin/sops https://github.com/getsops/sops/releases/download/v3.10.2/sops-v3.10.2.linux.amd64
+          chmod +x /usr/local/bin/sops
+          cargo install --locked --git https://github.com/frostplexx/opsops
+      - name: Files covered by a rule are encrypted
+        run: opsops verify
+      - name: Files match their creation rules
+        run: opsops drift
+      - name: No private keys are committed
+        run: opsops scan
+"#;
+
+const FLUX_FILES: &[TemplateFile] = &[
+    file(
+        "clusters/production/apps.yaml",
+        r#"apiVersion: kustomize.toolkit.fluxcd.io/v1
+kind: Kustomization
+metadata:
+  name: apps
+  namespace: flux-system
+spec:
+  interval: 10m
+  path: ./apps/production
+  prune: true
+  sourceRef:
+    kind: GitRepository
+    name: flux-system
+  decryption:
+    provider: sops
+    secretRef:
+      name: sops-age
+"#,
+    ),
+    file(
+        "apps/production/kustomization.yaml",
+        r#"apiVersion: kustomize.config.k8s.io/v1beta1
+kind: Kustomization
+resources:
+  - secrets/app-secret.yaml
+"#,
+    ),
+    secret(
+        "apps/production/secrets/app-secret.yaml",
+        r#"apiVersion: v1
+kind: Secret
+metadata:
+  name: app-secret
+  namespace: default
+type: Opaque
+stringData:
+  DATABASE_PASSWORD: change-me
+"#,
+    ),
+    file(".github/workflows/secrets.yml", CI_WORKFLOW),
+];
+
+const TERRAFORM_FILES: &[TemplateFile] = &[
+    file(
+        "live/production/main.tf",
+        r#"terraform {
+  required_providers {
+    sops = {
+      source  = "carlpett/sops"
+      version = "~> 1.0"
+    }
+  }
+}
+
+data "sops_file" "secrets" {
+  source_file = "secrets.yaml"
+}
+
+# e.g. data.sops_file.secrets.data["database_password"]
+"#,
+    ),
+    secret(
+        "live/production/secrets.yaml",
+        "database_password: change-me\n",
+    ),
+    file(
+        "live/staging/main.tf",
+        r#"terraform {
+  required_providers {
+    sops = {
+      source  = "carlpett/sops"
+      version = "~> 1.0"
+    }
+  }
+}
+
+data "sops_file" "secrets" {
+  source_file = "secrets.yaml"
+}
+"#,
+    ),
+    secret(
+        "live/staging/secrets.yaml",
+        "database_password: change-me\n",
+    ),
+    file(
+        ".gitignore",
+        ".terraform/\n*.tfstate\n*.tfstate.*\n*.tfplan\n",
+    ),
+    file(".github/workflows/secrets.yml", CI_WORKFLOW),
+];
+
+const DOTENV_FILES: &[TemplateFile] = &[
+    file(
+        ".env.example",
+        "# Copy to .env for local development, the real values are in config/\nDATABASE_URL=\nAPI_TOKEN=\n",
+    ),
+    secret(
+        "config/development.env",
+        "DATABASE_URL=postgres://localhost/app\nAPI_TOKEN=change-me\n",
+    ),
+    secret(
+        "config/production.env",
+        "DATABASE_URL=postgres://db.internal/app\nAPI_TOKEN=change-me\n",
+    ),
+    file(".gitignore", ".env\n"),
+    file(".github/workflows/secrets.yml", CI_WORKFLOW),
+];
+
+impl ProjectTemplate {
+    /// Creation rules as rule specs, see [`super::rule_spec`]
+    pub fn rules(&self) -> &'static [&'static str] {
+        match self {
+            ProjectTemplate::FluxCluster => {
+                &[r"path_regex=.*/secrets/.*\.ya?ml$,encrypted_regex=^(data|stringData)$"]
+            }
+            ProjectTemplate::TerraformLive => &[r"path_regex=live/.*/secrets\.ya?ml$"],
+            ProjectTemplate::DotenvApp => &[r"path_regex=config/.*\.env$"],
+        }
+    }
+
+    pub fn files(&self) -> &'static [TemplateFile] {
+        match self {
+            ProjectTemplate::FluxCluster => FLUX_FILES,
+            ProjectTemplate::TerraformLive => TERRAFORM_FILES,
+            ProjectTemplate::DotenvApp => DOTENV_FILES,
+        }
+    }
+
+    /// What to do once the project exists
+    pub fn next_steps(&self) -> &'static [&'static str] {
+        match self {
+            ProjectTemplate::FluxCluster => &[
+                "flux bootstrap git ... --path=clusters/production",
+                "opsops flux create-secret",
+                "opsops edit apps/production/secrets/app-secret.yaml",
+            ],
+            ProjectTemplate::TerraformLive => &[
+                "opsops edit live/production/secrets.yaml",
+                "terraform -chdir=live/production init",
+            ],
+            ProjectTemplate::DotenvApp => &[
+                "opsops edit config/development.env",
+                "opsops read config/development.env > .env",
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::ValueEnum;
+    use regex::Regex;
+
+    use super::ProjectTemplate;
+    use crate::util::rule_spec::parse_rule_spec;
+
+    #[test]
+    fn test_template_secrets_match_their_rules() {
+        for template in ProjectTemplate::value_variants() {
+            let rules: Vec<_> = template
+                .rules()
+                .iter()
+                .map(|spec| parse_rule_spec(spec, "age1example").unwrap())
+                .collect();
+            for file in template.files() {
+                let covered = rules.iter().any(|rule| {
+                    Regex::new(rule.path_regex.as_deref().unwrap())
+                        .unwrap()
+                        .is_match(file.path)
+                });
+                assert_eq!(covered, file.encrypt, "{:?}: {}", template, file.path);
+            }
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Interactive prompts. The dialoguer widgets redraw their lines as the
+//! selection moves, which screen readers can't follow. `--accessible` replaces
+//! them with numbered lists and questions answered by typing a line, which
+//! also works without a terminal. Emoji are left out, screen readers spell
+//! them out.
+
+use dialoguer::{Confirm, FuzzySelect, Input, Select, theme::ColorfulTheme};
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::formatter::strip_emoji;
+use super::i18n::{tr, tr_args};
+
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+/// Switches all prompts to plain text, set once in `main`
+pub fn set_accessible(accessible: bool) {
+    ACCESSIBLE.store(accessible, Ordering::Relaxed);
+}
+
+pub fn is_accessible() -> bool {
+    ACCESSIBLE.load(Ordering::Relaxed)
+}
+
+/// Asks `question` on stderr and reads the answer from stdin
+fn ask(question: &str) -> io::Result<String> {
+    eprint!("{} ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "No answer, the input was closed",
+        ));
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Reads an answer to a numbered list. `filter` takes text to narrow down the
+/// list, `cancel` an empty answer for no choice.
+fn choose(
+    prompt: &str,
+    items: &[String],
+    default: Option<usize>,
+    filter: bool,
+    cancel: bool,
+) -> io::Result<Option<usize>> {
+    if items.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Nothing to choose from for '{}'", prompt),
+        ));
+    }
+    let mut shown: Vec<usize> = (0..items.len()).collect();
+    loop {
+        eprintln!("{}:", prompt);
+        for (number, index) in shown.iter().enumerate() {
+            eprintln!("  {}. {}", number + 1, strip_emoji(&items[*index]));
+        }
+        let mut hint = tr_args("prompt-enter-number", &[("count", shown.len().into())]);
+        if filter {
+            hint.push_str(&tr("prompt-or-filter"));
+        }
+        match default {
+            Some(default) => hint.push_str(&tr_args(
+                "prompt-nothing-for",
+                &[("default", items[default].as_str().into())],
+            )),
+            None if cancel => hint.push_str(&tr("prompt-nothing-to-cancel")),
+            None => {}
+        }
+
+        let answer = ask(&format!("{}:", hint))?;
+        if answer.is_empty() {
+            if default.is_some() || cancel {
+                return Ok(default);
+            }
+            eprintln!("{}", tr("prompt-answer-required"));
+            continue;
+        }
+        if let Ok(number) = answer.parse::<usize>() {
+            if (1..=shown.len()).contains(&number) {
+                let index = shown[number - 1];
+                eprintln!(
+                    "{}",
+                    tr_args("prompt-chose", &[("item", items[index].as_str().into())])
+                );
+                return Ok(Some(index));
+            }
+            eprintln!(
+                "{}",
+                tr_args(
+                    "prompt-out-of-range",
+                    &[("number", number.into()), ("count", shown.len().into()),],
+                )
+            );
+            continue;
+        }
+        if !filter {
+            eprintln!(
+                "{}",
+                tr_args("prompt-not-a-number", &[("answer", answer.as_str().into())])
+            );
+            continue;
+        }
+        let needle = answer.to_lowercase();
+        let matching: Vec<usize> = (0..items.len())
+            .filter(|i| items[*i].to_lowercase().contains(&needle))
+            .collect();
+        if matching.is_empty() {
+            eprintln!(
+                "{}",
+                tr_args("prompt-no-match", &[("answer", answer.as_str().into())])
+            );
+            shown = (0..items.len()).collect();
+        } else {
+            shown = matching;
+        }
+    }
+}
+
+fn to_strings<T: Display>(items: &[T]) -> Vec<String> {
+    items.iter().map(ToString::to_string).collect()
+}
+
+/// Picks one of a few `items`
+pub fn select<T: Display>(prompt: &str, items: &[T], default: usize) -> io::Result<usize> {
+    if is_accessible() {
+        let chosen = choose(prompt, &to_strings(items), Some(default), false, false)?;
+        return Ok(chosen.unwrap_or(default));
+    }
+    Ok(Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(default)
+        .items(items)
+        .interact()?)
+}
+
+/// Picks one of many `items`, searchable
+pub fn fuzzy_select<T: Display>(
+    prompt: &str,
+    items: &[T],
+    default: Option<usize>,
+) -> io::Result<usize> {
+    if is_accessible() {
+        let chosen = choose(prompt, &to_strings(items), default, true, false)?;
+        // Only cancelable lists answer with no choice
+        return Ok(chosen.unwrap_or_default());
+    }
+    let theme = ColorfulTheme::default();
+    let mut select = FuzzySelect::with_theme(&theme)
+        .with_prompt(prompt)
+        .items(items);
+    if let Some(default) = default {
+        select = select.default(default);
+    }
+    Ok(select.interact()?)
+}
+
+/// Like [`fuzzy_select`], `None` if the user cancels
+pub fn fuzzy_select_opt<T: Display>(prompt: &str, items: &[T]) -> io::Result<Option<usize>> {
+    if is_accessible() {
+        return choose(prompt, &to_strings(items), None, true, true);
+    }
+    Ok(FuzzySelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(items)
+        .interact_opt()?)
+}
+
+/// Asks a yes or no question
+pub fn confirm(prompt: &str, default: bool) -> io::Result<bool> {
+    if is_accessible() {
+        let default_answer = if default { "yes" } else { "no" };
+        loop {
+            let question = format!(
+                "{} {}",
+                prompt,
+                tr_args("prompt-yes-or-no", &[("default", default_answer.into())])
+            );
+            match ask(&question)?.to_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => eprintln!("{}", tr("prompt-answer-yes-or-no")),
+            }
+        }
+    }
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(default)
+        .interact()?)
+}
+
+/// Asks for a line of text, `default` for an empty answer. Without a default
+/// an answer is required unless `allow_empty`.
+pub fn input(prompt: &str, default: Option<String>, allow_empty: bool) -> io::Result<String> {
+    if is_accessible() {
+        loop {
+            let question = match &default {
+                Some(default) => format!(
+                    "{}{}:",
+                    prompt,
+                    tr_args(
+                        "prompt-nothing-for",
+                        &[("default", default.as_str().into())]
+                    )
+                ),
+                None => format!("{}:", prompt),
+            };
+            let answer = ask(&question)?;
+            match &default {
+                Some(default) if answer.is_empty() => return Ok(default.clone()),
+                None if answer.is_empty() && !allow_empty => {
+                    eprintln!("{}", tr("prompt-answer-required"))
+                }
+                _ => return Ok(answer),
+            }
+        }
+    }
+    let theme = ColorfulTheme::default();
+    let mut input = Input::<String>::with_theme(&theme)
+        .with_prompt(prompt)
+        .allow_empty(allow_empty);
+    if let Some(default) = default {
+        input = input.default(default);
+    }
+    Ok(input.interact_text()?)
+}
@@ -0,0 +1,94 @@
+//! Files opsops refuses to encrypt even when a catch-all rule matches them:
+//! the configs needed to decrypt anything at all, and git's own files.
+
+use std::path::{Component, Path};
+
+use super::{
+    dirs::PROJECT_STATE_DIR, file_picker::quiet_effective_config,
+    opsops_config::OPSOPS_CONFIG_FILE, rule_match::absolute_path, sops_config::sops_config_path,
+};
+use crate::GlobalContext;
+
+/// Explains why `path` must not be encrypted, `None` if it may be
+pub fn protected_reason(path: &Path, context: &GlobalContext) -> Option<String> {
+    let absolute = absolute_path(path);
+
+    if absolute
+        .components()
+        .any(|c| c == Component::Normal(".git".as_ref()))
+    {
+        return Some("it is inside the .git directory".to_string());
+    }
+    if absolute
+        .components()
+        .any(|c| c == Component::Normal(PROJECT_STATE_DIR.as_ref()))
+    {
+        return Some(format!("it is opsops state in {}", PROJECT_STATE_DIR));
+    }
+
+    let config_file =
+        "it is a config file, encrypting it would make every file in the project undecryptable";
+    let name = absolute.file_name().unwrap_or_default();
+    let is_sops_config = sops_config_path(context).is_some_and(|p| absolute_path(&p) == absolute);
+    if name == ".sops.yaml" || name == OPSOPS_CONFIG_FILE || is_sops_config {
+        return Some(config_file.to_string());
+    }
+
+    // Fragments pulled in via include: are part of the config as well
+    if quiet_effective_config(context).is_some_and(|e| e.fragments.contains(&absolute)) {
+        return Some(config_file.to_string());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::protected_reason;
+    use crate::GlobalContext;
+
+    #[test]
+    fn test_protects_configs_and_git() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(
+            root.join(".sops.yaml"),
+            "include:\n- teams/rules.yaml\ncreation_rules:\n- path_regex: .*\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("teams")).unwrap();
+        fs::write(root.join("teams/rules.yaml"), "creation_rules: []\n").unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+
+        let context = GlobalContext {
+            sops_file: Some(root.join(".sops.yaml").to_string_lossy().into()),
+            opitem: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
+        };
+
+        for protected in [
+            ".sops.yaml",
+            ".opsops.yaml",
+            "teams/rules.yaml",
+            ".git/config",
+            ".opsops/migrations/.sops.yaml.v0.bak",
+        ] {
+            assert!(
+                protected_reason(&root.join(protected), &context).is_some(),
+                "{}",
+                protected
+            );
+        }
+        assert_eq!(protected_reason(&root.join("secrets.yaml"), &context), None);
+        assert_eq!(
+            protected_reason(&root.join("teams/app.yaml"), &context),
+            None
+        );
+    }
+}
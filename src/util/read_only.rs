@@ -0,0 +1,40 @@
+//! `--read-only`, for running opsops where modifying state must be impossible,
+//! like production debugging sessions. Commands that only exist to change
+//! something are refused before they start, the functions that write configs,
+//! caches or 1Password items check again.
+
+use super::i18n::tr;
+use crate::GlobalContext;
+
+/// Fails with the message of the catalog id `action` in read-only mode
+pub fn ensure_writable(context: &GlobalContext, action: &str) -> Result<(), String> {
+    if context.read_only {
+        Err(tr(action))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ensure_writable;
+    use crate::GlobalContext;
+
+    #[test]
+    fn test_ensure_writable() {
+        let mut context = GlobalContext {
+            sops_file: None,
+            opitem: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
+        };
+        assert!(ensure_writable(&context, "read-only-write-sops-yaml").is_ok());
+        context.read_only = true;
+        assert_eq!(
+            ensure_writable(&context, "read-only-write-sops-yaml"),
+            Err("Refusing to write .sops.yaml in read-only mode".to_string())
+        );
+    }
+}
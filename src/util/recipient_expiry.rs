@@ -0,0 +1,143 @@
+//! Time-boxed access: `recipient_expiry` in `.opsops.yaml` gives recipients,
+//! e.g. a contractor's key, a last day. `doctor` warns while an expired
+//! recipient can still decrypt anything and `key expire-sweep` removes it.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    backups::civil_from_days, escrow::rule_recipients, opsops_config::read_opsops_config,
+    sops_files::find_encrypted_files, sops_metadata::file_recipients, sops_structs::CreationRule,
+};
+use crate::GlobalContext;
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ExpiringRecipient {
+    /// Public key
+    pub recipient: String,
+    /// Last day of access, `YYYY-MM-DD`
+    pub expires: String,
+    /// Whose key it is, shown in warnings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl ExpiringRecipient {
+    /// The key and, if set, who it belongs to
+    pub fn describe(&self) -> String {
+        match &self.note {
+            Some(note) => format!("{} ({})", self.recipient, note),
+            None => self.recipient.clone(),
+        }
+    }
+}
+
+/// Checks a `YYYY-MM-DD` date
+pub fn validate_date(date: &str) -> Result<(), String> {
+    let parts: Vec<&str> = date.split('-').collect();
+    let valid = match parts.as_slice() {
+        [year, month, day] if year.len() == 4 && month.len() == 2 && day.len() == 2 => {
+            let numbers: Vec<u32> = parts.iter().filter_map(|p| p.parse().ok()).collect();
+            numbers.len() == 3 && (1..=12).contains(&numbers[1]) && (1..=31).contains(&numbers[2])
+        }
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a date like 2025-12-31", date))
+    }
+}
+
+/// Today's UTC date as `YYYY-MM-DD`
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The entries whose last day is before `today`. Invalid dates count as
+/// expired, so a typo never extends access.
+pub fn expired<'a>(entries: &'a [ExpiringRecipient], today: &str) -> Vec<&'a ExpiringRecipient> {
+    entries
+        .iter()
+        .filter(|entry| validate_date(&entry.expires).is_err() || entry.expires.as_str() < today)
+        .collect()
+}
+
+/// The expired entries of the project's `recipient_expiry`
+pub fn expired_recipients(context: &GlobalContext) -> Result<Vec<ExpiringRecipient>, String> {
+    let entries = read_opsops_config(context)?
+        .map(|c| c.recipient_expiry)
+        .unwrap_or_default();
+    Ok(expired(&entries, &today()).into_iter().cloned().collect())
+}
+
+/// Where an expired recipient still has access
+#[derive(Debug, Default, PartialEq)]
+pub struct RemainingAccess {
+    /// Indices of the rules encrypting to it
+    pub rules: Vec<usize>,
+    /// Encrypted files whose metadata has it
+    pub files: Vec<std::path::PathBuf>,
+}
+
+impl RemainingAccess {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.files.is_empty()
+    }
+}
+
+/// Rules and encrypted files below `root` that `recipient` can decrypt
+pub fn remaining_access(recipient: &str, rules: &[CreationRule], root: &Path) -> RemainingAccess {
+    RemainingAccess {
+        rules: rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule_recipients(rule).iter().any(|r| r == recipient))
+            .map(|(i, _)| i)
+            .collect(),
+        files: find_encrypted_files(root)
+            .into_iter()
+            .filter(|file| {
+                std::fs::read_to_string(file)
+                    .is_ok_and(|contents| file_recipients(&contents).iter().any(|r| r == recipient))
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExpiringRecipient, expired, validate_date};
+
+    fn entry(expires: &str) -> ExpiringRecipient {
+        ExpiringRecipient {
+            recipient: "age1contractor".to_string(),
+            expires: expires.to_string(),
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_date() {
+        assert!(validate_date("2025-12-31").is_ok());
+        assert!(validate_date("2025-13-01").is_err());
+        assert!(validate_date("31.12.2025").is_err());
+        assert!(validate_date("2025-1-1").is_err());
+    }
+
+    #[test]
+    fn test_expired() {
+        let entries = [entry("2025-06-30"), entry("2025-07-01"), entry("soon")];
+        let expired: Vec<_> = expired(&entries, "2025-07-01")
+            .into_iter()
+            .map(|e| e.expires.as_str())
+            .collect();
+        // The last day itself still grants access
+        assert_eq!(expired, vec!["2025-06-30", "soon"]);
+    }
+}
@@ -0,0 +1,73 @@
+use std::{fs, path::Path, path::PathBuf};
+
+use super::dirs;
+
+/// How many references are remembered for completion
+const MAX_REFERENCES: usize = 20;
+
+/// Location of the reference cache: `references` in [`dirs::cache_dir`]
+pub fn reference_cache_path() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join("references"))
+}
+
+fn read_from(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .map(|c| {
+            c.lines()
+                .filter(|l| !l.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn remember_in(path: &Path, reference: &str) -> std::io::Result<()> {
+    let mut references = read_from(path);
+    references.retain(|r| r != reference);
+    references.insert(0, reference.to_string());
+    references.truncate(MAX_REFERENCES);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, references.join("\n") + "\n")
+}
+
+/// 1Password references that recently resolved to a key, most recent first.
+/// Only references are stored, never the keys behind them.
+pub fn cached_references() -> Vec<String> {
+    reference_cache_path()
+        .map(|p| read_from(&p))
+        .unwrap_or_default()
+}
+
+/// Records a reference that resolved to a key. Failures are ignored, the cache
+/// only feeds shell completion.
+pub fn remember_reference(reference: &str) {
+    if let Some(path) = reference_cache_path() {
+        let _ = remember_in(&path, reference);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::{MAX_REFERENCES, read_from, remember_in};
+
+    #[test]
+    fn test_most_recent_first_without_duplicates() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("opsops").join("references");
+
+        remember_in(&path, "op://A/a/key").unwrap();
+        remember_in(&path, "op://B/b/key").unwrap();
+        remember_in(&path, "op://A/a/key").unwrap();
+        assert_eq!(read_from(&path), vec!["op://A/a/key", "op://B/b/key"]);
+
+        for i in 0..MAX_REFERENCES + 5 {
+            remember_in(&path, &format!("op://V/{}/key", i)).unwrap();
+        }
+        assert_eq!(read_from(&path).len(), MAX_REFERENCES);
+    }
+}
@@ -0,0 +1,170 @@
+//! Picking the `encrypted_regex` of a new rule by what a file contains rather
+//! than where it lives. `rule_conditions:` in `.opsops.yaml` lists conditions
+//! on the content, e.g. `content_kind: kubernetes-secret` or
+//! `has_key: stringData`, each with the pattern to use. `target-keys` takes
+//! the first entry a file meets instead of asking, so reorganizing the repo
+//! doesn't change how its files are encrypted.
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use super::opsops_config::read_opsops_config;
+use super::rule_templates::{COMMON_REGEX, KUBERNETES_REGEX, TALOS_REGEX};
+use crate::GlobalContext;
+
+/// What kind of document a file holds
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContentKind {
+    /// A Kubernetes `Secret`
+    KubernetesSecret,
+    /// Any Kubernetes object, a document with `apiVersion` and `kind`
+    KubernetesManifest,
+    /// A Talos machine config, a document with `machine` and `cluster`
+    TalosConfig,
+}
+
+/// One entry of `rule_conditions:` in `.opsops.yaml`. Every condition given
+/// has to hold.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct RuleCondition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_kind: Option<ContentKind>,
+    /// A key of this name anywhere in the document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_key: Option<String>,
+    /// A preset, `kubernetes`, `talos`, `common` or `all`, or a regex
+    pub encrypted_regex: String,
+}
+
+/// The regex behind a preset name, anything else is taken as a regex
+pub fn preset_regex(preset: &str) -> &str {
+    match preset {
+        "kubernetes" => KUBERNETES_REGEX,
+        "talos" => TALOS_REGEX,
+        "common" => COMMON_REGEX,
+        "all" => ".*",
+        regex => regex,
+    }
+}
+
+fn is_kind(document: &Value, kind: ContentKind) -> bool {
+    let has = |key: &str| document.get(key).is_some();
+    match kind {
+        ContentKind::KubernetesSecret => {
+            has("apiVersion") && document.get("kind").and_then(Value::as_str) == Some("Secret")
+        }
+        ContentKind::KubernetesManifest => has("apiVersion") && has("kind"),
+        ContentKind::TalosConfig => has("machine") && has("cluster"),
+    }
+}
+
+fn has_key(value: &Value, key: &str) -> bool {
+    match value {
+        Value::Mapping(map) => map
+            .iter()
+            .any(|(k, v)| k.as_str() == Some(key) || has_key(v, key)),
+        Value::Sequence(items) => items.iter().any(|item| has_key(item, key)),
+        Value::Tagged(tagged) => has_key(&tagged.value, key),
+        _ => false,
+    }
+}
+
+impl RuleCondition {
+    /// Whether one of the `documents` of a file meets every condition
+    pub fn matches(&self, documents: &[Value]) -> bool {
+        documents.iter().any(|document| {
+            self.content_kind.is_none_or(|kind| is_kind(document, kind))
+                && self
+                    .has_key
+                    .as_deref()
+                    .is_none_or(|key| has_key(document, key))
+        })
+    }
+}
+
+/// The documents of a YAML or JSON file, several for multi-document YAML, up
+/// to the first one that doesn't parse
+pub fn parse_documents(content: &[u8]) -> Vec<Value> {
+    // The deserializer keeps yielding the same error once parsing failed
+    serde_yaml::Deserializer::from_slice(content)
+        .map_while(|document| Value::deserialize(document).ok())
+        .collect()
+}
+
+/// The first of `conditions` that `content` meets
+pub fn matching_condition<'a>(
+    conditions: &'a [RuleCondition],
+    content: &[u8],
+) -> Option<&'a RuleCondition> {
+    let documents = parse_documents(content);
+    conditions
+        .iter()
+        .find(|condition| condition.matches(&documents))
+}
+
+/// The project's `rule_conditions:`
+pub fn rule_conditions(context: &GlobalContext) -> Result<Vec<RuleCondition>, String> {
+    let conditions = read_opsops_config(context)?
+        .map(|config| config.rule_conditions)
+        .unwrap_or_default();
+    for condition in &conditions {
+        regex::Regex::new(preset_regex(&condition.encrypted_regex)).map_err(|e| {
+            format!(
+                "Invalid encrypted_regex '{}' in rule_conditions: {}",
+                condition.encrypted_regex, e
+            )
+        })?;
+    }
+    Ok(conditions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContentKind, RuleCondition, matching_condition, preset_regex};
+    use crate::util::rule_templates::KUBERNETES_REGEX;
+
+    #[test]
+    fn test_matching_condition() {
+        let conditions: Vec<RuleCondition> = serde_yaml::from_str(
+            r#"
+- content_kind: kubernetes-secret
+  encrypted_regex: kubernetes
+- content_kind: kubernetes-manifest
+  has_key: password
+  encrypted_regex: ^password$
+- content_kind: talos-config
+  encrypted_regex: talos
+- has_key: apiKey
+  encrypted_regex: common
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            conditions[0].content_kind,
+            Some(ContentKind::KubernetesSecret)
+        );
+
+        let matched = |content: &str| {
+            matching_condition(&conditions, content.as_bytes())
+                .map(|condition| preset_regex(&condition.encrypted_regex).to_string())
+        };
+        let secret = "apiVersion: v1\nkind: Secret\nstringData:\n  a: b\n";
+        assert_eq!(matched(secret).as_deref(), Some(KUBERNETES_REGEX));
+        // Any document of a multi-document file counts
+        let both = format!("apiVersion: v1\nkind: ConfigMap\n---\n{}", secret);
+        assert_eq!(matched(&both).as_deref(), Some(KUBERNETES_REGEX));
+        let deployment = "apiVersion: apps/v1\nkind: Deployment\nspec:\n  password: x\n";
+        assert_eq!(matched(deployment).as_deref(), Some("^password$"));
+        assert_eq!(
+            matched(r#"{"service": {"apiKey": "x"}}"#).as_deref(),
+            Some(preset_regex("common"))
+        );
+        assert_eq!(
+            matched("machine: {}\ncluster: {}\n").as_deref(),
+            Some(preset_regex("talos"))
+        );
+        assert_eq!(matched("apiVersion: v1\nkind: ConfigMap\n"), None);
+        assert_eq!(matched("not: [valid"), None);
+    }
+}
@@ -0,0 +1,289 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use super::sops_structs::CreationRule;
+
+/// Turns `path` into a `/`-separated path relative to `root`, the form
+/// sops matches `path_regex` against
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Makes `path` absolute against the current directory and resolves symlinks,
+/// also for files that don't exist yet such as an output path
+pub fn absolute_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    if let Ok(canonical) = absolute.canonicalize() {
+        return canonical;
+    }
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|p| p.join(name))
+            .unwrap_or(absolute),
+        _ => absolute,
+    }
+}
+
+/// `path` relative to the project `root` (the directory of .sops.yaml), however it
+/// was spelled on the command line. `None` if it lies outside of `root`.
+pub fn project_relative_path(root: &Path, path: &Path) -> Option<String> {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let absolute = absolute_path(path);
+    absolute.strip_prefix(&root).ok()?;
+    Some(relative_path(&root, &absolute))
+}
+
+/// A `path_regex` matching exactly the file at the root relative `path`
+pub fn exact_path_regex(path: &str) -> String {
+    format!("^{}$", regex::escape(path))
+}
+
+/// Whether `path_regex` was written for exactly the file at `path`, by this
+/// version or an older one that used the path as typed (e.g. `./secrets.yaml`)
+pub fn targets_file(path_regex: &str, path: &str) -> bool {
+    path_regex == exact_path_regex(path) || path_regex.trim_start_matches("./") == path
+}
+
+/// Checks for a `path_regex` that is really a plain file path such as
+/// `config.prod.yaml`, as written by older versions of `target-keys`. As a regex
+/// its dots match any character and it matches anywhere in a path, so it can
+/// cover more files than intended. Returns the exact pattern to use instead.
+pub fn unescaped_path_suggestion(path_regex: &str) -> Option<String> {
+    let literal = path_regex.trim_start_matches("./");
+    let plain = literal
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'));
+    (plain && literal.contains('.')).then(|| exact_path_regex(literal))
+}
+
+/// How a single rule's `path_regex` matches
+enum RulePattern {
+    /// No `path_regex`, matches every file as in sops
+    Any,
+    Regex(Regex),
+    /// The `path_regex` doesn't compile, matches nothing
+    Invalid,
+}
+
+/// The `path_regex` of every creation rule, compiled once
+pub struct RuleMatcher {
+    patterns: Vec<RulePattern>,
+}
+
+impl RuleMatcher {
+    pub fn new(rules: &[CreationRule]) -> Self {
+        let patterns = rules
+            .iter()
+            .map(|rule| match &rule.path_regex {
+                Some(pattern) => {
+                    Regex::new(pattern).map_or(RulePattern::Invalid, RulePattern::Regex)
+                }
+                None => RulePattern::Any,
+            })
+            .collect();
+        RuleMatcher { patterns }
+    }
+
+    /// Whether rule `index` has a `path_regex` that compiles, or none at all
+    pub fn is_valid(&self, index: usize) -> bool {
+        !matches!(self.patterns[index], RulePattern::Invalid)
+    }
+
+    /// Whether rule `index` matches `file`, `None` if its `path_regex` is invalid
+    pub fn matches(&self, index: usize, file: &str) -> Option<bool> {
+        match &self.patterns[index] {
+            RulePattern::Any => Some(true),
+            RulePattern::Regex(regex) => Some(regex.is_match(file)),
+            RulePattern::Invalid => None,
+        }
+    }
+
+    /// Returns the index of the rule sops would use for `file`: the first one whose
+    /// `path_regex` matches. Rules with an invalid `path_regex` are skipped.
+    pub fn first_match(&self, file: &str) -> Option<usize> {
+        (0..self.patterns.len()).find(|&i| self.matches(i, file) == Some(true))
+    }
+}
+
+/// Files matched by each creation rule
+pub struct RuleMatches {
+    /// Indices into the file list, one entry per rule.
+    /// `None` if the rule's `path_regex` failed to compile.
+    pub per_rule: Vec<Option<Vec<usize>>>,
+    /// Indices into the file list for every file matched by more than one rule,
+    /// together with the indices of the matching rules
+    pub overlaps: Vec<(usize, Vec<usize>)>,
+}
+
+/// Matches every rule's `path_regex` against `files`.
+/// Rules without a `path_regex` match every file, as in sops.
+pub fn match_rules(rules: &[CreationRule], files: &[String]) -> RuleMatches {
+    let matcher = RuleMatcher::new(rules);
+
+    let per_rule = (0..rules.len())
+        .map(|rule| {
+            matcher.is_valid(rule).then(|| {
+                files
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, f)| matcher.matches(rule, f) == Some(true))
+                    .map(|(i, _)| i)
+                    .collect()
+            })
+        })
+        .collect::<Vec<Option<Vec<usize>>>>();
+
+    let mut overlaps = Vec::new();
+    for file_index in 0..files.len() {
+        let matching: Vec<usize> = per_rule
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.as_ref().is_some_and(|m| m.contains(&file_index)))
+            .map(|(i, _)| i)
+            .collect();
+        if matching.len() > 1 {
+            overlaps.push((file_index, matching));
+        }
+    }
+
+    RuleMatches { per_rule, overlaps }
+}
+
+/// Returns the index of the rule sops would use for `file`, see [`RuleMatcher::first_match`]
+pub fn first_matching_rule(rules: &[CreationRule], file: &str) -> Option<usize> {
+    RuleMatcher::new(rules).first_match(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use proptest::prelude::*;
+
+    use super::{
+        RuleMatcher, first_matching_rule, match_rules, relative_path, unescaped_path_suggestion,
+    };
+    use crate::util::sops_structs::CreationRule;
+
+    fn rule(path_regex: Option<&str>) -> CreationRule {
+        CreationRule {
+            path_regex: path_regex.map(str::to_string),
+            age: None,
+            encrypted_regex: None,
+            key_groups: vec![],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("/repo"), Path::new("/repo/k8s/secret.yaml")),
+            "k8s/secret.yaml"
+        );
+    }
+
+    #[test]
+    fn test_match_rules() {
+        let rules = vec![
+            rule(Some(r"\.yaml$")),
+            rule(Some(r"^k8s/")),
+            rule(Some(r"\.toml$")),
+            rule(Some("(")),
+        ];
+        let files = vec!["k8s/secret.yaml".to_string(), "app.yaml".to_string()];
+
+        let matches = match_rules(&rules, &files);
+        assert_eq!(matches.per_rule[0], Some(vec![0, 1]));
+        assert_eq!(matches.per_rule[1], Some(vec![0]));
+        assert_eq!(matches.per_rule[2], Some(vec![]));
+        assert_eq!(matches.per_rule[3], None);
+        assert_eq!(matches.overlaps, vec![(0, vec![0, 1])]);
+    }
+
+    #[test]
+    fn test_rule_without_path_regex_matches_all() {
+        let files = vec!["a".to_string(), "b".to_string()];
+        let matches = match_rules(&[rule(None)], &files);
+        assert_eq!(matches.per_rule[0], Some(vec![0, 1]));
+    }
+
+    #[test]
+    fn test_first_matching_rule() {
+        let rules = vec![
+            rule(Some("(")),
+            rule(Some(r"^k8s/")),
+            rule(Some(r"\.yaml$")),
+        ];
+        assert_eq!(first_matching_rule(&rules, "k8s/secret.yaml"), Some(1));
+        assert_eq!(first_matching_rule(&rules, "app.yaml"), Some(2));
+        assert_eq!(first_matching_rule(&rules, "app.toml"), None);
+    }
+
+    fn rules(patterns: Vec<Option<String>>) -> Vec<CreationRule> {
+        patterns
+            .into_iter()
+            .map(|path_regex| CreationRule {
+                path_regex,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unescaped_path_suggestion() {
+        assert_eq!(
+            unescaped_path_suggestion("config.prod.yaml").as_deref(),
+            Some("^config\\.prod\\.yaml$")
+        );
+        assert_eq!(
+            unescaped_path_suggestion("./k8s/secret.yaml").as_deref(),
+            Some("^k8s/secret\\.yaml$")
+        );
+        for regex in [
+            ".*",
+            "^config\\.prod\\.yaml$",
+            "\\.env$",
+            "secrets",
+            "(a|b).yaml",
+        ] {
+            assert_eq!(unescaped_path_suggestion(regex), None, "{}", regex);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_first_match_is_first_matching_rule(
+            patterns in proptest::collection::vec(proptest::option::of("[a-z.*^$()/\\\\]{0,8}"), 0..6),
+            file in "[a-z./]{0,12}",
+        ) {
+            let rules = rules(patterns);
+            let matcher = RuleMatcher::new(&rules);
+            let expected = (0..rules.len()).find(|&i| matcher.matches(i, &file) == Some(true));
+            prop_assert_eq!(matcher.first_match(&file), expected);
+        }
+
+        #[test]
+        fn prop_escaped_path_matches_itself(file in any::<String>()) {
+            let rules = rules(vec![Some(format!("^{}$", regex::escape(&file)))]);
+            prop_assert_eq!(first_matching_rule(&rules, &file), Some(0));
+        }
+
+        #[test]
+        fn prop_arbitrary_patterns_never_panic(pattern in any::<String>(), file in any::<String>()) {
+            let rules = rules(vec![Some(pattern), None]);
+            // The rule without a path_regex always catches what the first one doesn't
+            prop_assert!(first_matching_rule(&rules, &file).is_some());
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Creation rules given on the command line as `key=value` lists, e.g.
+//! `path_regex=secrets/.*\.yaml$,encrypted_regex=^(data|stringData)$`.
+
+use super::sops_structs::CreationRule;
+
+/// Options a rule spec may set
+const KEYS: [&str; 9] = [
+    "path_regex",
+    "age",
+    "encrypted_regex",
+    "unencrypted_regex",
+    "encrypted_suffix",
+    "unencrypted_suffix",
+    "encrypted_comment_regex",
+    "unencrypted_comment_regex",
+    "mac_only_encrypted",
+];
+
+/// Splits a spec into its `key=value` pairs. Values may contain commas, e.g.
+/// regex quantifiers, as only a comma followed by `<name>=` starts a new pair.
+fn split_pairs(spec: &str) -> Result<Vec<(&str, &str)>, String> {
+    let starts_pair = |rest: &str| {
+        rest.split_once('=').is_some_and(|(key, _)| {
+            !key.is_empty() && key.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+        })
+    };
+    let mut boundaries = vec![0];
+    boundaries.extend(
+        spec.match_indices(',')
+            .map(|(i, _)| i + 1)
+            .filter(|i| starts_pair(&spec[*i..])),
+    );
+
+    let mut pairs = Vec::new();
+    for (n, start) in boundaries.iter().enumerate() {
+        let end = boundaries.get(n + 1).map_or(spec.len(), |next| next - 1);
+        let pair = &spec[*start..end];
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' is not a key=value pair", pair))?;
+        if !KEYS.contains(&key) {
+            return Err(format!(
+                "Unknown rule option '{}', expected one of {}",
+                key,
+                KEYS.join(", ")
+            ));
+        }
+        pairs.push((key, value));
+    }
+    Ok(pairs)
+}
+
+/// Parses a rule spec. Rules without `age` encrypt to `recipients`.
+pub fn parse_rule_spec(spec: &str, recipients: &str) -> Result<CreationRule, String> {
+    let mut rule = CreationRule {
+        age: Some(recipients.to_string()),
+        ..Default::default()
+    };
+    for (key, value) in split_pairs(spec)? {
+        let value = Some(value.to_string());
+        match key {
+            "path_regex" => rule.path_regex = value,
+            "age" => rule.age = value,
+            "encrypted_regex" => rule.encrypted_regex = value,
+            "unencrypted_regex" => rule.unencrypted_regex = value,
+            "encrypted_suffix" => rule.encrypted_suffix = value,
+            "unencrypted_suffix" => rule.unencrypted_suffix = value,
+            "encrypted_comment_regex" => rule.encrypted_comment_regex = value,
+            "unencrypted_comment_regex" => rule.unencrypted_comment_regex = value,
+            "mac_only_encrypted" => {
+                rule.mac_only_encrypted = Some(value.as_deref() == Some("true"));
+            }
+            _ => unreachable!("split_pairs only returns known keys"),
+        }
+    }
+    if rule.path_regex.is_none() {
+        return Err(format!("Rule '{}' has no path_regex", spec));
+    }
+    if let Some(pattern) = &rule.path_regex
+        && let Err(e) = regex::Regex::new(pattern)
+    {
+        return Err(format!("Invalid path_regex '{}': {}", pattern, e));
+    }
+    Ok(rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_rule_spec;
+
+    #[test]
+    fn test_parse_rule_spec() {
+        let rule = parse_rule_spec(
+            r"path_regex=k8s/.*\.yaml$,encrypted_regex=^(data|stringData)$",
+            "age1me",
+        )
+        .unwrap();
+        assert_eq!(rule.path_regex.as_deref(), Some(r"k8s/.*\.yaml$"));
+        assert_eq!(rule.encrypted_regex.as_deref(), Some("^(data|stringData)$"));
+        assert_eq!(rule.age.as_deref(), Some("age1me"));
+    }
+
+    #[test]
+    fn test_values_with_commas() {
+        let rule = parse_rule_spec(
+            "path_regex=^env/[a-z]{2,8}/,age=age1a,age1b,mac_only_encrypted=true",
+            "age1me",
+        )
+        .unwrap();
+        assert_eq!(rule.path_regex.as_deref(), Some("^env/[a-z]{2,8}/"));
+        assert_eq!(rule.age.as_deref(), Some("age1a,age1b"));
+        assert_eq!(rule.mac_only_encrypted, Some(true));
+    }
+
+    #[test]
+    fn test_invalid_specs() {
+        assert!(parse_rule_spec("encrypted_regex=^data$", "age1me").is_err());
+        assert!(parse_rule_spec("path_regex=[", "age1me").is_err());
+        assert!(
+            parse_rule_spec("path_regex=.*,kms=arn", "age1me")
+                .unwrap_err()
+                .contains("Unknown rule option 'kms'")
+        );
+        assert!(parse_rule_spec("colour=blue", "age1me").is_err());
+    }
+}
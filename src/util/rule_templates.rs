@@ -0,0 +1,40 @@
+/// Keys commonly holding secrets in Kubernetes manifests
+pub const KUBERNETES_REGEX: &str =
+    "^(data|stringData|password|token|secret|key|cert|ca.crt|tls|ingress|backupTarget)";
+
+/// Secret sections of Talos machine configs
+pub const TALOS_REGEX: &str =
+    "^(secrets|privateKey|token|key|crt|cert|password|secret|kubeconfig|talosconfig)";
+
+/// Generic sensitive key names
+pub const COMMON_REGEX: &str = "^(password|token|secret|key|auth|credential|private|apiKey|cert)";
+
+/// A starting point for a creation rule in a new .sops.yaml
+pub struct RuleTemplate {
+    pub name: &'static str,
+    pub path_regex: &'static str,
+    pub encrypted_regex: Option<&'static str>,
+}
+
+pub const TEMPLATES: [RuleTemplate; 4] = [
+    RuleTemplate {
+        name: "Catch-all (encrypt every value in YAML, JSON and .env files)",
+        path_regex: r".*\.(yaml|yml|json|env)$",
+        encrypted_regex: None,
+    },
+    RuleTemplate {
+        name: "Kubernetes manifests (data, stringData, tokens, certs)",
+        path_regex: r".*\.ya?ml$",
+        encrypted_regex: Some(KUBERNETES_REGEX),
+    },
+    RuleTemplate {
+        name: "Talos machine configs (secrets sections, certs, keys)",
+        path_regex: r".*\.ya?ml$",
+        encrypted_regex: Some(TALOS_REGEX),
+    },
+    RuleTemplate {
+        name: "Common sensitive keys (passwords, tokens, keys, credentials)",
+        path_regex: r".*\.(yaml|yml|json)$",
+        encrypted_regex: Some(COMMON_REGEX),
+    },
+];
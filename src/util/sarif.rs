@@ -0,0 +1,182 @@
+//! SARIF 2.1.0 logs of findings, the format GitHub code scanning and editor
+//! plugins read to show problems inline. Paths are relative to the repository
+//! root (`%SRCROOT%`).
+
+use serde::Serialize;
+
+use super::environment::VERSION;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const INFORMATION_URI: &str = "https://github.com/frostplexx/opsops";
+
+/// A kind of finding a command reports
+pub struct SarifRule {
+    pub id: &'static str,
+    pub description: &'static str,
+}
+
+/// One finding in a file
+pub struct Finding {
+    pub rule_id: &'static str,
+    pub message: String,
+    /// Relative to the repository root
+    pub path: String,
+    /// 1-based, the start of the file if the finding has no line
+    pub line: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Log {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize)]
+struct Run {
+    tool: Tool,
+    results: Vec<ResultEntry>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<RuleEntry>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RuleEntry {
+    id: &'static str,
+    short_description: Text,
+    default_configuration: Configuration,
+}
+
+#[derive(Serialize)]
+struct Configuration {
+    level: &'static str,
+}
+
+#[derive(Serialize)]
+struct Text {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResultEntry {
+    rule_id: &'static str,
+    level: &'static str,
+    message: Text,
+    locations: Vec<Location>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactLocation {
+    uri: String,
+    uri_base_id: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+}
+
+/// Renders `findings` as a SARIF log with one run of opsops. Every finding is
+/// an error, secrets in the clear never are a matter of style.
+pub fn to_sarif(rules: &[SarifRule], findings: &[Finding]) -> Result<String, String> {
+    let log = Log {
+        schema: SCHEMA,
+        version: "2.1.0",
+        runs: vec![Run {
+            tool: Tool {
+                driver: Driver {
+                    name: "opsops",
+                    version: VERSION,
+                    information_uri: INFORMATION_URI,
+                    rules: rules
+                        .iter()
+                        .map(|rule| RuleEntry {
+                            id: rule.id,
+                            short_description: Text {
+                                text: rule.description.to_string(),
+                            },
+                            default_configuration: Configuration { level: "error" },
+                        })
+                        .collect(),
+                },
+            },
+            results: findings
+                .iter()
+                .map(|finding| ResultEntry {
+                    rule_id: finding.rule_id,
+                    level: "error",
+                    message: Text {
+                        text: finding.message.clone(),
+                    },
+                    locations: vec![Location {
+                        physical_location: PhysicalLocation {
+                            artifact_location: ArtifactLocation {
+                                uri: finding.path.replace('\\', "/"),
+                                uri_base_id: "%SRCROOT%",
+                            },
+                            region: Region {
+                                start_line: finding.line.unwrap_or(1),
+                            },
+                        },
+                    }],
+                })
+                .collect(),
+        }],
+    };
+    serde_json::to_string_pretty(&log).map_err(|e| format!("Failed to serialize SARIF: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::{Finding, SarifRule, to_sarif};
+
+    #[test]
+    fn snapshot_sarif_log() {
+        let rules = [SarifRule {
+            id: "unencrypted-file",
+            description: "A file covered by a creation rule is not encrypted",
+        }];
+        let findings = [Finding {
+            rule_id: "unencrypted-file",
+            message: "secrets/db.yaml is not encrypted".to_string(),
+            path: "secrets/db.yaml".to_string(),
+            line: None,
+        }];
+        let sarif = to_sarif(&rules, &findings)
+            .unwrap()
+            .replace(env!("CARGO_PKG_VERSION"), "<version>");
+        assert_snapshot!(sarif);
+    }
+}
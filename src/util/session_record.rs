@@ -0,0 +1,326 @@
+//! `--record <file>`: a log of the external programs opsops runs, for
+//! maintainers reproducing a problem they can't see. Only command lines, exit
+//! codes, timings and the names of environment variables are kept; output and
+//! variable values never are, and arguments that may carry a secret are
+//! redacted. `opsops replay` prints a recorded session. Every external program
+//! goes through [`record`], which is why `--profile-perf` measures them here.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::environment::{BUILD_HASH, VERSION, detect_tool};
+use super::perf::{self, Category};
+
+/// Replaces everything that might be a secret
+pub const REDACTED: &str = "<redacted>";
+
+/// Arguments whose next argument is a value, e.g. sops `--set '["a"] "secret"'`
+const VALUE_FLAGS: &[&str] = &["--set", "--passphrase", "--password", "--token"];
+
+/// Flags whose value after `=` is a secret, e.g. `--from-literal=key=secret`
+const SECRET_FLAGS: &[&str] = &["--from-literal", "--passphrase", "--password", "--token"];
+
+/// Prefixes of the environment variables that change how opsops, sops and op
+/// behave; the fingerprint lists which of them are set
+const RELEVANT_VARIABLES: &[&str] = &["OPSOPS_", "SOPS_", "OP_", "SUDO_", "EDITOR", "VISUAL"];
+
+/// The tools whose versions are part of the fingerprint
+const TOOLS: &[&str] = &["sops", "op", "age"];
+
+/// What opsops ran with
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub os: String,
+    pub arch: String,
+    /// `<tool> --version`, `None` if the tool isn't on PATH
+    pub tools: Vec<(String, Option<String>)>,
+    /// Names of the relevant variables that are set, never their values
+    pub variables: Vec<String>,
+}
+
+/// One run of an external program
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Invocation {
+    /// Command line, redacted
+    pub command: Vec<String>,
+    /// Working directory, if opsops set one
+    pub dir: Option<PathBuf>,
+    /// Variables opsops set for the program, names only
+    pub env: Vec<String>,
+    /// `None` if the program didn't start or was killed by a signal
+    pub exit_code: Option<i32>,
+    /// Why the program didn't start
+    pub error: Option<String>,
+    /// Milliseconds since the session started
+    pub started_ms: u64,
+    pub duration_ms: u64,
+}
+
+/// A recorded run of opsops
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    /// The opsops version
+    pub version: String,
+    /// Commit opsops was built from
+    pub build: Option<String>,
+    /// The opsops command line, redacted
+    pub command: Vec<String>,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    pub environment: Fingerprint,
+    pub invocations: Vec<Invocation>,
+    /// Errors opsops printed
+    pub errors: Vec<String>,
+    /// `None` if opsops exited early, usually after one of the errors
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<u64>,
+}
+
+struct Recorder {
+    path: PathBuf,
+    started: Instant,
+    session: Session,
+}
+
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+/// Whether `arg` holds a secret on its own, e.g. a pasted age identity
+fn looks_secret(arg: &str) -> bool {
+    arg.contains("AGE-SECRET-KEY-") || arg.contains("-----BEGIN")
+}
+
+/// Redacts a command line. Values of `key=value` arguments, which `op item
+/// create` takes field values as, are dropped, so are values of flags that
+/// take a secret. Paths, references and other flags stay readable.
+pub fn redact_args<I, S>(args: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut redacted = Vec::new();
+    let mut value_follows = false;
+    for arg in args {
+        let arg = arg.as_ref();
+        if value_follows || looks_secret(arg) {
+            redacted.push(REDACTED.to_string());
+            value_follows = false;
+            continue;
+        }
+        value_follows = VALUE_FLAGS.contains(&arg);
+        let redacted_arg = match arg.split_once('=') {
+            Some((flag, _)) if SECRET_FLAGS.contains(&flag) => format!("{}={}", flag, REDACTED),
+            Some((flag, _)) if flag.starts_with('-') => arg.to_string(),
+            Some((key, _)) if !key.contains('/') => format!("{}={}", key, REDACTED),
+            _ => arg.to_string(),
+        };
+        redacted.push(redacted_arg);
+    }
+    redacted
+}
+
+fn fingerprint() -> Fingerprint {
+    let mut variables: Vec<String> = std::env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .filter(|name| RELEVANT_VARIABLES.iter().any(|p| name.starts_with(p)))
+        .collect();
+    variables.sort();
+    Fingerprint {
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        tools: TOOLS
+            .iter()
+            .map(|name| {
+                let tool = detect_tool(name);
+                (tool.name.to_string(), tool.version)
+            })
+            .collect(),
+        variables,
+    }
+}
+
+fn millis(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+/// Writes the session as it is so far; opsops often exits without returning
+/// from `main`, so every change is saved right away
+fn save(recorder: &Recorder) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(&recorder.session).map_err(io::Error::other)?;
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Paths and item names are nobody else's business
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&recorder.path)?;
+    file.write_all(json.as_bytes())?;
+    file.write_all(b"\n")
+}
+
+fn update(change: impl FnOnce(&mut Recorder)) {
+    let mut recorder = RECORDER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(recorder) = recorder.as_mut() {
+        change(recorder);
+        // Recording must never make the command itself fail
+        let _ = save(recorder);
+    }
+}
+
+/// Starts recording this process to `path`
+pub fn start_recording(path: &Path) -> io::Result<()> {
+    let recorder = Recorder {
+        path: path.to_path_buf(),
+        started: Instant::now(),
+        session: Session {
+            version: VERSION.to_string(),
+            build: BUILD_HASH.map(str::to_string),
+            command: redact_args(std::env::args_os().map(|arg| arg.to_string_lossy().into_owned())),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            environment: fingerprint(),
+            invocations: Vec::new(),
+            errors: Vec::new(),
+            exit_code: None,
+            duration_ms: None,
+        },
+    };
+    save(&recorder)?;
+    *RECORDER.lock().unwrap_or_else(|e| e.into_inner()) = Some(recorder);
+    Ok(())
+}
+
+/// Records that opsops is about to exit with `code`
+pub fn finish_recording(code: i32) {
+    update(|recorder| {
+        recorder.session.exit_code = Some(code);
+        recorder.session.duration_ms = Some(millis(recorder.started));
+    });
+}
+
+/// Records an error message opsops printed, without its colors
+pub fn record_error(message: &str) {
+    update(|recorder| {
+        let colors = Regex::new(r"\x1b\[[0-9;]*m").expect("valid regex");
+        let message = colors.replace_all(message, "");
+        let words = message.split(' ').map(|word| {
+            if looks_secret(word) {
+                REDACTED.to_string()
+            } else {
+                word.to_string()
+            }
+        });
+        recorder
+            .session
+            .errors
+            .push(words.collect::<Vec<_>>().join(" "));
+    });
+}
+
+/// Records a run of `command` that started at `started` and ended with
+/// `result`, the exit code or why it didn't start. Also the span of the run
+/// for `--profile-perf`.
+pub fn record(command: &Command, started: Instant, result: Result<Option<i32>, &io::Error>) {
+    let program = command.get_program().to_string_lossy();
+    let args = command.get_args().map(|arg| arg.to_string_lossy());
+    let redacted = redact_args(std::iter::once(program.clone()).chain(args));
+    perf::record(
+        Category::for_program(&program),
+        redacted.join(" "),
+        started.elapsed(),
+    );
+
+    update(|recorder| {
+        let mut env: Vec<String> = command
+            .get_envs()
+            .filter(|(_, value)| value.is_some())
+            .map(|(name, _)| name.to_string_lossy().into_owned())
+            .collect();
+        env.sort();
+        let (exit_code, error) = match result {
+            Ok(code) => (code, None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        recorder.session.invocations.push(Invocation {
+            command: redacted,
+            dir: command.get_current_dir().map(Path::to_path_buf),
+            env,
+            exit_code,
+            error,
+            started_ms: started
+                .saturating_duration_since(recorder.started)
+                .as_millis() as u64,
+            duration_ms: millis(started),
+        });
+    });
+}
+
+/// [`Command::status`], recorded
+pub fn status(command: &mut Command) -> io::Result<ExitStatus> {
+    let started = Instant::now();
+    let result = command.status();
+    record(command, started, result.as_ref().map(ExitStatus::code));
+    result
+}
+
+/// [`Command::output`], recorded
+pub fn output(command: &mut Command) -> io::Result<Output> {
+    let started = Instant::now();
+    let result = command.output();
+    record(command, started, result.as_ref().map(|o| o.status.code()));
+    result
+}
+
+/// Reads a session written by `--record`
+pub fn read_session(path: &Path) -> Result<Session, String> {
+    let file = File::open(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    serde_json::from_reader(file)
+        .map_err(|e| format!("{} is not a recorded session: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{REDACTED, redact_args};
+
+    #[test]
+    fn test_redact_args() {
+        let redacted = redact_args([
+            "op",
+            "item",
+            "create",
+            "--vault",
+            "Private",
+            "--format=json",
+            "Private Key[password]=AGE-SECRET-KEY-1ABC",
+            "Section.notes=hello",
+            "op://Private/Item/Key",
+        ]);
+        assert_eq!(
+            redacted,
+            [
+                "op",
+                "item",
+                "create",
+                "--vault",
+                "Private",
+                "--format=json",
+                REDACTED,
+                "Section.notes=<redacted>",
+                "op://Private/Item/Key",
+            ]
+        );
+        assert_eq!(
+            redact_args(["sops", "--set", r#"["a"] "b""#, "a.yaml"]),
+            ["sops", "--set", REDACTED, "a.yaml"]
+        );
+        assert_eq!(
+            redact_args(["kubectl", "--from-literal=key=value", "--namespace=flux"]),
+            ["kubectl", "--from-literal=<redacted>", "--namespace=flux"]
+        );
+    }
+}
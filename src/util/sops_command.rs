@@ -1,38 +1,155 @@
-use crate::{GlobalContext, util::op_key::get_age_key_from_1password};
+use crate::{
+    GlobalContext,
+    util::{
+        config_include::materialize_config,
+        env_key::KEY_VARIABLES,
+        i18n::tr_args,
+        op_key::get_age_key_from_1password,
+        rule_match::{absolute_path, project_relative_path},
+        session_record,
+        sops_config::config_dir,
+    },
+};
+use age::secrecy::{ExposeSecret, SecretString};
+use std::fmt;
+#[cfg(unix)]
+use std::fs::File;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
+use std::time::Instant;
+use tempfile::NamedTempFile;
+use zeroize::Zeroizing;
 
 /// A helper type for executing SOPS commands with the Age key from 1Password
 pub struct SopsCommandBuilder<'a> {
     command: Command,
     has_age_key: bool,
+    /// Holds the key for sops, kept open until the builder is consumed
+    #[cfg(unix)]
+    key_fd: Option<OwnedFd>,
+    /// The .sops.yaml with all includes merged, removed once the builder is dropped
+    _merged_config: Option<NamedTempFile>,
+    /// Set if the includes of .sops.yaml couldn't be resolved, running then fails
+    config_error: Option<String>,
+    /// The directory of .sops.yaml, file arguments are passed relative to it
+    project_dir: Option<PathBuf>,
     context: &'a GlobalContext,
 }
 
+/// Writes the key into an anonymous memory file that is never linked into the filesystem
+#[cfg(target_os = "linux")]
+fn key_file_descriptor(key: &SecretString) -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::memfd_create(c"opsops-age-key".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return key_pipe(key);
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(key.expose_secret().as_bytes())?;
+    Ok(file.into())
+}
+
+/// Writes the key into a pipe and returns its read end
+#[cfg(all(unix, not(target_os = "linux")))]
+fn key_file_descriptor(key: &SecretString) -> io::Result<OwnedFd> {
+    key_pipe(key)
+}
+
+/// Writes the key into a pipe and returns its read end. The pipe buffer easily holds
+/// an identity, so the write never blocks, but sops can read it only once.
+#[cfg(unix)]
+fn key_pipe(key: &SecretString) -> io::Result<OwnedFd> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    for fd in [&read, &write] {
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    File::from(write).write_all(key.expose_secret().as_bytes())?;
+    Ok(read)
+}
+
 impl<'a> SopsCommandBuilder<'a> {
     /// Create a new SopsCommandBuilder initialized with the sops binary
     pub fn new(context: &'a GlobalContext) -> Self {
         let mut command = Command::new("sops");
 
-        // If a custom sops file is specified, add the --config flag
-        if let Some(sops_file) = &context.sops_file {
-            command.arg("--config").arg(sops_file);
+        // sops doesn't know about includes, hand it the merged config instead
+        let (merged_config, config_error) = match materialize_config(context) {
+            Ok(merged) => (merged, None),
+            Err(e) => (None, Some(e)),
+        };
+
+        if let Some(merged) = &merged_config {
+            command.arg("--config").arg(merged.path());
+        } else if let Some(sops_file) = &context.sops_file {
+            // If a custom sops file is specified, add the --config flag. sops
+            // resolves path_regex against its directory, which only works for an
+            // absolute path.
+            command
+                .arg("--config")
+                .arg(absolute_path(Path::new(sops_file)));
         }
 
         SopsCommandBuilder {
             command,
             has_age_key: false,
+            #[cfg(unix)]
+            key_fd: None,
+            _merged_config: merged_config,
+            config_error,
+            project_dir: config_dir(context),
             context,
         }
     }
 
+    /// Fails if the command can't run with the project's config
+    fn check_config(&self) -> io::Result<()> {
+        match &self.config_error {
+            Some(e) => Err(io::Error::other(e.clone())),
+            None => Ok(()),
+        }
+    }
+
     /// Add an argument to the SOPS command
     pub fn arg<S: AsRef<std::ffi::OsStr>>(mut self, arg: S) -> Self {
         self.command.arg(arg);
         self
     }
 
+    /// Add a file argument. Files inside the project are passed relative to the
+    /// directory of .sops.yaml and sops runs from there, so `path_regex` matches
+    /// no matter where opsops was started or how the path was spelled. Use this
+    /// for every file argument of a command, mixing it with `arg` breaks
+    /// relative paths.
+    pub fn arg_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        let path = path.as_ref();
+        match &self.project_dir {
+            Some(dir) => match project_relative_path(dir, path) {
+                Some(relative) => {
+                    self.command.current_dir(dir).arg(relative);
+                }
+                None => {
+                    self.command.arg(absolute_path(path));
+                }
+            },
+            None => {
+                self.command.arg(path);
+            }
+        }
+        self
+    }
+
     /// Add multiple arguments to the SOPS command
-    pub fn _args<I, S>(mut self, args: I) -> Self
+    pub fn args<I, S>(mut self, args: I) -> Self
     where
         I: IntoIterator<Item = S>,
         S: AsRef<std::ffi::OsStr>,
@@ -47,37 +164,125 @@ impl<'a> SopsCommandBuilder<'a> {
         self
     }
 
+    /// Set an environment variable for sops
+    pub fn env<K: AsRef<std::ffi::OsStr>, V: AsRef<std::ffi::OsStr>>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> Self {
+        self.command.env(key, value);
+        self
+    }
+
     /// Configure with Age key from 1Password (if it exists)
     pub fn with_age_key(mut self) -> Result<Self, String> {
         // Retrieve the Age key from 1Password
         let age_key = get_age_key_from_1password(self.context)?;
-        self.command.env("SOPS_AGE_KEY", age_key);
-        self.has_age_key = true;
+        self.set_age_key(&age_key);
         Ok(self)
     }
 
+    /// Configure with an Age key that was already retrieved, e.g. to reuse it across many commands
+    pub fn with_age_key_value(mut self, age_key: &SecretString) -> Self {
+        self.set_age_key(age_key);
+        self
+    }
+
     /// Try to set the Age key, but don't fail if it's not available
     pub fn _with_optional_age_key(mut self) -> Self {
         if let Ok(age_key) = get_age_key_from_1password(self.context) {
-            self.command.env("SOPS_AGE_KEY", age_key);
-            self.has_age_key = true;
+            self.set_age_key(&age_key);
         }
         self
     }
 
+    /// Hands the key to sops through an inherited file descriptor exposed as
+    /// `SOPS_AGE_KEY_FILE=/dev/fd/N`, so it never shows up in the process environment.
+    /// Falls back to `SOPS_AGE_KEY` with `--age-key-env`, on platforms without
+    /// inheritable descriptors, or if no descriptor can be created.
+    fn set_age_key(&mut self, age_key: &SecretString) {
+        self.has_age_key = true;
+        // sops would try keys from the user's environment as well
+        for var in KEY_VARIABLES {
+            self.command.env_remove(var);
+        }
+        if self.context.age_key_env || cfg!(not(unix)) {
+            self.command.env("SOPS_AGE_KEY", age_key.expose_secret());
+            return;
+        }
+        #[cfg(unix)]
+        self.inherit_age_key(age_key);
+    }
+
+    #[cfg(unix)]
+    fn inherit_age_key(&mut self, age_key: &SecretString) {
+        let fd = match key_file_descriptor(age_key) {
+            Ok(fd) => fd,
+            Err(e) => {
+                super::print_status::print_warning(tr_args(
+                    "key-fd-failed",
+                    &[("error", e.to_string().into())],
+                ));
+                self.command.env("SOPS_AGE_KEY", age_key.expose_secret());
+                return;
+            }
+        };
+
+        let raw = fd.as_raw_fd();
+        self.command
+            .env("SOPS_AGE_KEY_FILE", format!("/dev/fd/{}", raw));
+        // The descriptor is close-on-exec in opsops itself, only clear that flag in the sops child
+        unsafe {
+            self.command.pre_exec(move || {
+                if libc::fcntl(raw, libc::F_SETFD, 0) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        self.key_fd = Some(fd);
+    }
+
     /// Run the command and wait for it to finish
     pub fn status(mut self) -> std::io::Result<std::process::ExitStatus> {
-        self.command.status()
+        self.check_config()?;
+        session_record::status(&mut self.command)
     }
 
-    /// Spawn the command and return the Child process handle
+    /// Spawn the command and return the Child process handle.
+    /// A merged config for includes is removed when this returns, so wait for
+    /// the child only if the project doesn't use includes.
     pub fn _spawn(mut self) -> std::io::Result<Child> {
+        self.check_config()?;
         self.command.spawn()
     }
 
     /// Run the command and capture its output
     pub fn _output(mut self) -> std::io::Result<std::process::Output> {
-        self.command.output()
+        self.check_config()?;
+        session_record::output(&mut self.command)
+    }
+
+    /// Run the command with `input` piped to its stdin and capture its output
+    pub fn output_with_input(mut self, input: &[u8]) -> std::io::Result<std::process::Output> {
+        self.check_config()?;
+        self.command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let started = Instant::now();
+        let result = self.command.spawn().and_then(|mut child| {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(input)?;
+            }
+            child.wait_with_output()
+        });
+        session_record::record(
+            &self.command,
+            started,
+            result.as_ref().map(|o| o.status.code()),
+        );
+        result
     }
 
     /// Check if the Age key was successfully set
@@ -104,6 +309,98 @@ impl<'a> SopsCommandBuilder<'a> {
     }
 }
 
+/// Runs sops on `input` as if it was the contents of the file at `path`, so
+/// the creation rule for that path applies, and returns what sops printed
+pub fn run_sops_on_buffer(
+    action: &str,
+    file_type: &str,
+    path: &Path,
+    input: &str,
+    context: &GlobalContext,
+) -> Result<String, String> {
+    let output = SopsCommandBuilder::new(context)
+        .arg(action)
+        .arg("--input-type")
+        .arg(file_type)
+        .arg("--output-type")
+        .arg(file_type)
+        .arg("--filename-override")
+        .arg_path(path)
+        .arg("/dev/stdin")
+        .with_age_key()?
+        .output_with_input(input.as_bytes())
+        .map_err(|e| format!("Failed to launch sops: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    String::from_utf8(output.stdout).map_err(|e| format!("sops returned invalid UTF-8: {}", e))
+}
+
+/// A failed [`decrypt_in_memory`]
+#[derive(Debug)]
+pub struct DecryptError {
+    /// What sops printed to stderr, or why it couldn't run
+    pub message: String,
+    /// Exit code of sops, 1 if it didn't run
+    pub code: i32,
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Decrypts `path` without the plaintext touching the disk: sops gets no output
+/// file and writes to a pipe, the key is passed through an anonymous file
+/// descriptor and the returned buffer is zeroed when dropped. Uses `age_key`
+/// if given, the key from 1Password otherwise.
+pub fn decrypt_in_memory(
+    path: &Path,
+    age_key: Option<&SecretString>,
+    context: &GlobalContext,
+) -> Result<Zeroizing<Vec<u8>>, DecryptError> {
+    decrypt_in_memory_with_args(path, age_key, &[], context)
+}
+
+/// Like [`decrypt_in_memory`], with `args` for sops before the file, e.g. `--indent`
+pub fn decrypt_in_memory_with_args(
+    path: &Path,
+    age_key: Option<&SecretString>,
+    args: &[String],
+    context: &GlobalContext,
+) -> Result<Zeroizing<Vec<u8>>, DecryptError> {
+    let failed = |message: String| DecryptError { message, code: 1 };
+    let builder = SopsCommandBuilder::new(context)
+        .arg("--decrypt")
+        .args(args)
+        .arg_path(path);
+    let mut builder = match age_key {
+        Some(key) => builder.with_age_key_value(key),
+        None => builder
+            .with_age_key()
+            .map_err(|e| failed(format!("Failed to get Age key: {}", e)))?,
+    };
+    builder.check_config().map_err(|e| failed(e.to_string()))?;
+
+    let output = session_record::output(
+        builder
+            .command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )
+    .map_err(|e| failed(format!("Failed to launch sops: {}", e)))?;
+    let plaintext = Zeroizing::new(output.stdout);
+    if !output.status.success() {
+        return Err(DecryptError {
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            code: output.status.code().unwrap_or(1),
+        });
+    }
+    Ok(plaintext)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -116,6 +413,10 @@ mod tests {
         GlobalContext {
             opitem,
             sops_file: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
         }
     }
 
@@ -168,4 +469,42 @@ mod tests {
             Err(e) => panic!("Command execution failed: {}", e),
         }
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_key_file_descriptor_can_be_reopened() {
+        use super::key_file_descriptor;
+        use age::secrecy::SecretString;
+        use std::io::Read;
+        use std::os::fd::AsRawFd;
+
+        let key = SecretString::from("AGE-SECRET-KEY-1TEST");
+        let fd = key_file_descriptor(&key).unwrap();
+        let path = format!("/proc/self/fd/{}", fd.as_raw_fd());
+
+        // sops may open the key file more than once, each open starts at the beginning
+        for _ in 0..2 {
+            let mut contents = String::new();
+            std::fs::File::open(&path)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, "AGE-SECRET-KEY-1TEST");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_key_pipe_holds_key() {
+        use super::key_pipe;
+        use age::secrecy::SecretString;
+        use std::io::Read;
+
+        let key = SecretString::from("AGE-SECRET-KEY-1TEST");
+        let mut contents = String::new();
+        std::fs::File::from(key_pipe(&key).unwrap())
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "AGE-SECRET-KEY-1TEST");
+    }
 }
@@ -1,33 +1,52 @@
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{Read, Write},
     path::PathBuf,
 };
 
 use super::{
+    config_edit::{opsops_settings, render_config},
+    config_merge::merge_yaml,
+    file_lock::wait_for_lock,
+    i18n::{failed_to, tr},
+    migrations::migrate_project,
+    opsops_config::{apply_opsops_config, read_opsops_config, write_opsops_config},
+    perf::{Category, span},
     print_status::print_error,
-    sops_structs::{CreationRule, SopsConfig},
+    read_only::ensure_writable,
+    sops_structs::SopsConfig,
 };
 use crate::{GlobalContext, util};
 use colored::Colorize;
-use serde::Deserialize;
-use serde_yaml::{from_str, to_string};
+use serde_yaml::from_str;
 
-pub fn get_sops_config(context: &GlobalContext) -> Option<File> {
-    let config_path = if let Some(sops_file_path) = &context.sops_file {
+/// Resolves the path of the .sops.yaml, either from --sops-file or the project root
+pub fn sops_config_path(context: &GlobalContext) -> Option<PathBuf> {
+    if let Some(sops_file_path) = &context.sops_file {
         // Use the explicitly provided path
-        PathBuf::from(sops_file_path)
+        Some(PathBuf::from(sops_file_path))
     } else {
         // Use the default behavior - look for .sops.yaml in project root
-        let file_name = ".sops.yaml";
-
-        if let Some(project_root) = util::find_project_root::find_project_root() {
-            project_root.join(file_name)
-        } else {
-            print_error(format!(
-                "{}",
-                "Could not determine project root.".red().bold()
-            ));
+        util::find_project_root::find_project_root(context).map(|root| root.join(".sops.yaml"))
+    }
+}
+
+/// The directory sops resolves `path_regex` against: the one containing .sops.yaml
+pub fn config_dir(context: &GlobalContext) -> Option<PathBuf> {
+    let path = sops_config_path(context)?;
+    let dir = path.parent()?.to_path_buf();
+    if dir.as_os_str().is_empty() {
+        Some(context.working_dir())
+    } else {
+        Some(dir)
+    }
+}
+
+pub fn get_sops_config(context: &GlobalContext) -> Option<File> {
+    let config_path = match sops_config_path(context) {
+        Some(path) => path,
+        None => {
+            print_error(format!("{}", tr("no-project-root").red().bold()));
             return None;
         }
     };
@@ -36,14 +55,14 @@ pub fn get_sops_config(context: &GlobalContext) -> Option<File> {
         match File::open(&config_path) {
             Ok(file) => return Some(file),
             Err(e) => {
-                print_error(format!("Failed to open {}: {}", config_path.display(), e));
+                print_error(failed_to("open-failed", &config_path, e));
                 return None;
             }
         }
     } else {
         print_error(format!(
             "{} {}",
-            "Config file not found:".red().bold(),
+            tr("config-not-found").red().bold(),
             config_path.display()
         ));
     }
@@ -52,77 +71,55 @@ pub fn get_sops_config(context: &GlobalContext) -> Option<File> {
 }
 
 pub fn read_or_create_config(context: &GlobalContext) -> Result<SopsConfig, String> {
-    match get_sops_config(context) {
+    let _span = span(Category::ConfigIo, "read .sops.yaml");
+    // Bring layouts of older versions up to date before reading anything
+    migrate_project(context)?;
+
+    let mut config = match get_sops_config(context) {
         Some(mut file) => {
             let mut contents = String::new();
             if let Err(e) = file.read_to_string(&mut contents) {
                 return Err(format!("Failed to read config file: {}", e));
             }
-
-            // Try parsing as-is first
-            match from_str::<SopsConfig>(&contents) {
-                Ok(mut config) => {
-                    // Override onepassworditem if provided via command line
-                    if let Some(opitem) = &context.opitem {
-                        config.onepassworditem = opitem.clone();
-                    }
-                    Ok(config)
-                }
-                Err(e) => {
-                    // If parsing fails due to missing onepassworditem field, parse manually
-                    if e.to_string().contains("missing field `onepassworditem`") {
-                        // Use a custom approach to parse the config without the onepassworditem field
-                        #[derive(Deserialize)]
-                        struct PartialConfig {
-                            #[serde(default)]
-                            creation_rules: Vec<CreationRule>,
-                        }
-
-                        // Try to parse the partial config
-                        match from_str::<PartialConfig>(&contents) {
-                            Ok(partial) => {
-                                // Create a complete config with the parsed rules and onepassworditem from context or empty
-                                let onepassworditem = context.opitem.clone().unwrap_or_default();
-                                Ok(SopsConfig {
-                                    creation_rules: partial.creation_rules,
-                                    onepassworditem,
-                                })
-                            }
-                            Err(e) => Err(format!("Failed to parse partial YAML config: {}", e)),
-                        }
-                    } else {
-                        Err(format!("Failed to parse YAML: {}", e))
-                    }
-                }
-            }
-        }
-        None => {
-            // Create a new config with default values
-            let onepassworditem = context.opitem.clone().unwrap_or_default();
-            Ok(SopsConfig {
-                creation_rules: Vec::new(),
-                onepassworditem,
-            })
+            let mut config = from_str::<SopsConfig>(&contents)
+                .map_err(|e| format!("Failed to parse YAML: {}", e))?;
+            config.original = Some(contents);
+            config
         }
-    }
+        // Create a new config with default values
+        None => SopsConfig::default(),
+    };
+
+    // The 1Password reference lives in .opsops.yaml, --op-item overrides it
+    apply_opsops_config(&mut config, context)?;
+    Ok(config)
 }
 
+/// Writes the config to .sops.yaml and the settings only opsops reads to
+/// `.opsops.yaml` next to it. `.opsops.yaml` is only created once there is
+/// something to put in it.
 pub fn write_config(config: &SopsConfig, context: &GlobalContext) -> Result<(), String> {
-    let config_path = if let Some(sops_file_path) = &context.sops_file {
-        // Use the explicitly provided path
-        PathBuf::from(sops_file_path)
-    } else {
-        // Use the default behavior - write to .sops.yaml in project root
-        if let Some(project_root) = util::find_project_root::find_project_root() {
-            project_root.join(".sops.yaml")
-        } else {
-            return Err("Could not determine project root".to_string());
-        }
+    let _span = span(Category::ConfigIo, "write .sops.yaml");
+    ensure_writable(context, "read-only-write-sops-yaml")?;
+    let config_path = match sops_config_path(context) {
+        Some(path) => path,
+        None => return Err("Could not determine project root".to_string()),
     };
 
-    let yaml = match to_string(config) {
-        Ok(y) => y,
-        Err(e) => return Err(format!("Failed to serialize config: {}", e)),
+    // Another opsops process writing meanwhile waits for this one to finish
+    let _lock = wait_for_lock(&config_path)?;
+    let yaml = render_config(config)?;
+    let yaml = match fs::read_to_string(&config_path) {
+        Ok(current) if config.original.as_ref() != Some(&current) => {
+            merge_yaml(config.original.as_deref(), &yaml, &current).map_err(|e| {
+                format!(
+                    "{} changed while opsops was editing it: {}. Run the command again.",
+                    config_path.display(),
+                    e
+                )
+            })?
+        }
+        _ => yaml,
     };
 
     let mut file = match File::create(&config_path) {
@@ -140,6 +137,14 @@ pub fn write_config(config: &SopsConfig, context: &GlobalContext) -> Result<(),
         return Err(format!("Failed to write to config file: {}", e));
     }
 
+    let existing = read_opsops_config(context)?;
+    if existing.is_some() || !config.onepassworditem.is_empty() {
+        write_opsops_config(
+            &opsops_settings(config, existing.unwrap_or_default()),
+            context,
+        )?;
+    }
+
     Ok(())
 }
 
@@ -160,6 +165,10 @@ mod tests {
         let context = GlobalContext {
             sops_file: Some(dir.path().join(".sops.yaml").to_string_lossy().into()),
             opitem: Some("op://Vault/Item/Field".to_string()),
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
         };
 
         let config = read_or_create_config(&context).expect("should create default config");
@@ -180,6 +189,10 @@ mod tests {
         let context = GlobalContext {
             sops_file: Some(file_path.to_string_lossy().into()),
             opitem: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
         };
 
         let config = read_or_create_config(&context).expect("should read valid config");
@@ -195,6 +208,10 @@ mod tests {
         let context = GlobalContext {
             sops_file: Some(file_path.to_string_lossy().into()),
             opitem: Some("op://Vault/Item/Fallback".to_string()),
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
         };
 
         let config = read_or_create_config(&context).expect("should fallback on missing field");
@@ -210,6 +227,10 @@ mod tests {
         let context = GlobalContext {
             sops_file: Some(path.to_string_lossy().into()),
             opitem: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
         };
 
         let config = SopsConfig {
@@ -219,13 +240,68 @@ mod tests {
                 age: Some("AGE-RECIPIENT-KEY".to_string()), // or None
                 encrypted_regex: None,                      // optional
                 key_groups: vec![],
+                ..Default::default()
             }],
+            ..Default::default()
         };
 
         write_config(&config, &context).expect("should write config successfully");
 
         let written = fs::read_to_string(path).unwrap();
-        assert!(written.contains("onepassworditem"));
+        assert!(!written.contains("onepassworditem"));
         assert!(written.contains("creation_rules"));
+
+        let opsops = fs::read_to_string(dir.path().join(".opsops.yaml")).unwrap();
+        assert!(opsops.contains("version: 1"));
+        assert!(opsops.contains("onepassworditem: op://Vault/Item/Field"));
+    }
+
+    #[test]
+    fn test_write_config_merges_concurrent_changes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(".sops.yaml");
+        fs::write(&path, "creation_rules:\n- path_regex: a\n  age: age1a\n").unwrap();
+        let context = GlobalContext {
+            sops_file: Some(path.to_string_lossy().into()),
+            opitem: None,
+            age_key_env: false,
+            chdir: None,
+            read_only: false,
+            key_preference: Default::default(),
+        };
+
+        let mut config = read_or_create_config(&context).unwrap();
+        config.creation_rules.push(CreationRule {
+            path_regex: Some("b".to_string()),
+            age: Some("age1b".to_string()),
+            ..Default::default()
+        });
+        // Someone else adds a rule and a stores section meanwhile
+        fs::write(
+            &path,
+            "creation_rules:\n- path_regex: a\n  age: age1a\n- path_regex: c\n  age: age1c\nstores:\n  yaml:\n    indent: 2\n",
+        )
+        .unwrap();
+        write_config(&config, &context).unwrap();
+
+        let merged: SopsConfig = serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let patterns: Vec<_> = merged
+            .creation_rules
+            .iter()
+            .map(|r| r.path_regex.as_deref().unwrap())
+            .collect();
+        assert_eq!(patterns, vec!["a", "c", "b"]);
+        assert!(merged.extra.contains_key("stores"));
+
+        // Both changing the same key is a conflict and leaves the file alone
+        let mut config = read_or_create_config(&context).unwrap();
+        config.creation_rules[0].age = Some("age1ours".to_string());
+        let theirs = fs::read_to_string(&path)
+            .unwrap()
+            .replace("age1a", "age1theirs");
+        fs::write(&path, &theirs).unwrap();
+        let err = write_config(&config, &context).unwrap_err();
+        assert!(err.contains("creation_rules[0].age"), "{}", err);
+        assert_eq!(fs::read_to_string(&path).unwrap(), theirs);
     }
 }
@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::dirs::PROJECT_STATE_DIR;
+
+/// Directories that never contain project secrets, or only opsops' own copies
+const SKIPPED_DIRS: [&str; 4] = [".git", PROJECT_STATE_DIR, "target", "node_modules"];
+
+/// Checks whether file contents carry sops metadata in any of the supported formats
+pub fn is_sops_encrypted(contents: &str) -> bool {
+    // YAML
+    let yaml = contents.lines().any(|l| l == "sops:") && contents.contains("mac:");
+    // JSON
+    let json = contents.contains("\"sops\":") && contents.contains("\"mac\":");
+    // dotenv
+    let dotenv = contents.lines().any(|l| l.starts_with("sops_mac="));
+    // INI
+    let ini = contents.lines().any(|l| l.trim() == "[sops]") && contents.contains("mac");
+
+    yaml || json || dotenv || ini
+}
+
+/// Checks whether the file at `path` is encrypted with sops
+pub fn is_sops_encrypted_file(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .map(|c| is_sops_encrypted(&c))
+        .unwrap_or(false)
+}
+
+/// The sops `--input-type`/`--output-type` for a file, based on its extension
+pub fn sops_file_type(path: &Path) -> &'static str {
+    if path.file_name().is_some_and(|n| n == ".env") {
+        return "dotenv";
+    }
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "yaml" | "yml" => "yaml",
+        "json" => "json",
+        "env" => "dotenv",
+        "ini" => "ini",
+        _ => "binary",
+    }
+}
+
+/// Recursively collects all files below `root`, skipping VCS and build directories
+pub fn walk_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(t) => t,
+                Err(_) => continue,
+            };
+            if file_type.is_dir() {
+                let name = entry.file_name();
+                if !SKIPPED_DIRS.iter().any(|s| name == *s) {
+                    stack.push(path);
+                }
+            } else if file_type.is_file() {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Finds all sops encrypted files below `root`
+pub fn find_encrypted_files(root: &Path) -> Vec<PathBuf> {
+    walk_files(root)
+        .into_iter()
+        .filter(|p| is_sops_encrypted_file(p))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+
+    use super::{find_encrypted_files, is_sops_encrypted};
+
+    #[test]
+    fn test_is_sops_encrypted() {
+        assert!(is_sops_encrypted(
+            "password: ENC[AES256_GCM,data:abc]\nsops:\n    mac: ENC[...]\n"
+        ));
+        assert!(is_sops_encrypted(
+            r#"{"password": "ENC[...]", "sops": {"mac": "ENC[...]"}}"#
+        ));
+        assert!(is_sops_encrypted("PASSWORD=ENC[...]\nsops_mac=ENC[...]\n"));
+        assert!(!is_sops_encrypted("password: hunter2\n"));
+        assert!(!is_sops_encrypted("{\"sops\": \"is a tool\"}"));
+    }
+
+    #[test]
+    fn test_find_encrypted_files() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("nested")).unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        let encrypted = "a: ENC[...]\nsops:\n    mac: ENC[...]\n";
+        fs::write(dir.path().join("nested/secret.yaml"), encrypted).unwrap();
+        fs::write(dir.path().join(".git/secret.yaml"), encrypted).unwrap();
+        fs::write(dir.path().join("plain.yaml"), "a: b\n").unwrap();
+
+        let found = find_encrypted_files(dir.path());
+        assert_eq!(found, vec![dir.path().join("nested/secret.yaml")]);
+    }
+}
@@ -0,0 +1,373 @@
+//! The `sops` block sops embeds in every encrypted file: who the data key is
+//! encrypted to, when the file was last written, by which sops version and
+//! how values were selected for encryption. All of it is plaintext, so it can
+//! be read without any key. YAML and JSON files carry it as a nested `sops:`
+//! mapping, dotenv and INI files flatten it into keys like
+//! `sops_age__list_0__map_recipient`.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+
+/// Options deciding which values sops encrypted, in the order sops checks them
+pub const SELECTOR_OPTIONS: [&str; 7] = [
+    "encrypted_regex",
+    "unencrypted_regex",
+    "encrypted_suffix",
+    "unencrypted_suffix",
+    "encrypted_comment_regex",
+    "unencrypted_comment_regex",
+    "mac_only_encrypted",
+];
+
+/// Fields identifying a key of the other sources: kms, gcp_kms, azure_kv and
+/// hc_vault
+const OTHER_KEY_IDS: [&str; 4] = ["arn", "resource_id", "vault_url", "vault_address"];
+
+/// An age recipient the data key is encrypted to
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct AgeRecipient {
+    pub recipient: String,
+}
+
+/// A PGP key the data key is encrypted to
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct PgpKey {
+    pub fp: String,
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub created_at: Option<String>,
+}
+
+/// A key group of a file encrypted with Shamir secret sharing
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct KeyGroupMetadata {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub age: Vec<AgeRecipient>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pgp: Vec<PgpKey>,
+    /// kms, gcp_kms, azure_kv, hc_vault, ...
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+/// The `sops` block of an encrypted file
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct SopsMetadata {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub age: Vec<AgeRecipient>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pgp: Vec<PgpKey>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_groups: Vec<KeyGroupMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shamir_threshold: Option<u32>,
+    /// When sops last wrote the file, e.g. `2024-01-31T23:59:59Z`
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub lastmodified: Option<String>,
+    /// The encrypted MAC over all values, missing if the file was tampered with
+    #[serde(default, deserialize_with = "scalar", skip_serializing)]
+    pub mac: Option<String>,
+    /// The sops version that wrote the file
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub version: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub encrypted_regex: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub unencrypted_regex: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub encrypted_suffix: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub unencrypted_suffix: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub encrypted_comment_regex: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub unencrypted_comment_regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac_only_encrypted: Option<bool>,
+    /// Other key sources (kms, gcp_kms, azure_kv, hc_vault) and newer fields
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+/// Any scalar as a string, sops writes e.g. versions unquoted
+fn scalar<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    })
+}
+
+impl SopsMetadata {
+    pub fn has_mac(&self) -> bool {
+        self.mac.as_deref().is_some_and(|mac| !mac.is_empty())
+    }
+
+    /// The age recipients of the file, from `age` and every key group
+    pub fn age_recipients(&self) -> Vec<String> {
+        self.age
+            .iter()
+            .chain(self.key_groups.iter().flat_map(|group| &group.age))
+            .map(|age| age.recipient.clone())
+            .collect()
+    }
+
+    /// Every key the data key is encrypted to, from `sops` and every key
+    /// group: age recipients as they are, PGP fingerprints, and the other
+    /// sources as `<source>:<id>`, e.g. `kms:arn:aws:kms:...`
+    pub fn key_ids(&self) -> Vec<String> {
+        let groups = std::iter::once((&self.pgp, &self.other)).chain(
+            self.key_groups
+                .iter()
+                .map(|group| (&group.pgp, &group.other)),
+        );
+        let mut ids = self.age_recipients();
+        for (pgp, other) in groups {
+            ids.extend(pgp.iter().map(|key| key.fp.clone()));
+            for (source, keys) in other {
+                let Value::Sequence(keys) = keys else {
+                    continue;
+                };
+                ids.extend(keys.iter().filter_map(|key| {
+                    let id = OTHER_KEY_IDS
+                        .iter()
+                        .find_map(|field| key.get(*field)?.as_str())?;
+                    Some(format!("{}:{}", source, id))
+                }));
+            }
+        }
+        ids
+    }
+
+    /// The selector options the file was encrypted with, see [`SELECTOR_OPTIONS`]
+    pub fn selector(&self) -> Vec<(&'static str, String)> {
+        [
+            ("encrypted_regex", &self.encrypted_regex),
+            ("unencrypted_regex", &self.unencrypted_regex),
+            ("encrypted_suffix", &self.encrypted_suffix),
+            ("unencrypted_suffix", &self.unencrypted_suffix),
+            ("encrypted_comment_regex", &self.encrypted_comment_regex),
+            ("unencrypted_comment_regex", &self.unencrypted_comment_regex),
+        ]
+        .into_iter()
+        .filter_map(|(option, value)| Some((option, value.clone()?)))
+        .chain(
+            self.mac_only_encrypted
+                .map(|value| ("mac_only_encrypted", value.to_string())),
+        )
+        .collect()
+    }
+}
+
+/// A flattened value: `true`, `false` and digits are what sops wrote as such
+fn flat_value(value: &str) -> Value {
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        digits if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => digits
+            .parse::<u64>()
+            .map_or_else(|_| value.into(), Value::from),
+        _ => value.into(),
+    }
+}
+
+/// Sets `value` at the flattened `path`, e.g. `age__list_0__map_recipient`
+fn insert_flat(target: &mut Value, path: &[&str], value: Value) {
+    let Some((segment, rest)) = path.split_first() else {
+        *target = value;
+        return;
+    };
+    if let Some(index) = segment
+        .strip_prefix("list_")
+        .and_then(|i| i.parse::<usize>().ok())
+    {
+        if !target.is_sequence() {
+            *target = Value::Sequence(Vec::new());
+        }
+        let Value::Sequence(items) = target else {
+            return;
+        };
+        if items.len() <= index {
+            items.resize(index + 1, Value::Null);
+        }
+        insert_flat(&mut items[index], rest, value);
+        return;
+    }
+    let key = segment.strip_prefix("map_").unwrap_or(segment);
+    if !target.is_mapping() {
+        *target = Value::Mapping(Mapping::new());
+    }
+    if let Value::Mapping(map) = target {
+        let entry = map.entry(key.into()).or_insert(Value::Null);
+        insert_flat(entry, rest, value);
+    }
+}
+
+/// The metadata of dotenv (`sops_` keys) and INI (`[sops]` section) files
+fn flat_metadata(contents: &str) -> Option<Value> {
+    let mut metadata = Value::Mapping(Mapping::new());
+    let mut in_sops_section = false;
+    let mut found = false;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_sops_section = line == "[sops]";
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        let key = match key.strip_prefix("sops_") {
+            Some(key) => key,
+            None if in_sops_section => key,
+            None => continue,
+        };
+        let path: Vec<&str> = key.split("__").collect();
+        insert_flat(&mut metadata, &path, flat_value(value.trim()));
+        found = true;
+    }
+    found.then_some(metadata)
+}
+
+/// Reads the sops metadata of an encrypted file, `None` if it has none
+pub fn read_metadata(contents: &str) -> Option<SopsMetadata> {
+    // YAML and JSON
+    let block = match serde_yaml::from_str::<Value>(contents) {
+        Ok(document) if document.get("sops").is_some_and(Value::is_mapping) => {
+            document.get("sops").cloned()
+        }
+        _ => flat_metadata(contents),
+    }?;
+    serde_yaml::from_value(block).ok()
+}
+
+/// The age recipients an encrypted file's data key was encrypted to, read from
+/// its sops metadata
+pub fn file_recipients(contents: &str) -> Vec<String> {
+    read_metadata(contents)
+        .map(|metadata| metadata.age_recipients())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_recipients, read_metadata};
+
+    #[test]
+    fn test_file_recipients() {
+        let yaml = "a: ENC[...]\nsops:\n    age:\n        - recipient: age1me\n          enc: x\n        - recipient: age1escrow\n          enc: y\n    mac: ENC[...]\n";
+        assert_eq!(file_recipients(yaml), ["age1me", "age1escrow"]);
+
+        let json = r#"{"a": "ENC[...]", "sops": {"key_groups": [{"age": [{"recipient": "age1escrow"}]}], "mac": "x"}}"#;
+        assert_eq!(file_recipients(json), ["age1escrow"]);
+
+        let dotenv = "A=ENC[...]\nsops_age__list_0__map_recipient=age1me\nsops_mac=x\n";
+        assert_eq!(file_recipients(dotenv), ["age1me"]);
+    }
+
+    #[test]
+    fn test_read_metadata() {
+        let yaml = r#"password: ENC[AES256_GCM,data:abc]
+sops:
+    kms: []
+    age:
+        - recipient: age1me
+          enc: |
+            -----BEGIN AGE ENCRYPTED FILE-----
+            -----END AGE ENCRYPTED FILE-----
+    pgp:
+        - created_at: "2024-01-31T23:59:59Z"
+          fp: FINGERPRINT
+    lastmodified: "2024-01-31T23:59:59Z"
+    mac: ENC[AES256_GCM,data:mac]
+    encrypted_regex: ^(data|stringData)$
+    mac_only_encrypted: true
+    version: 3.8.1
+"#;
+        let metadata = read_metadata(yaml).unwrap();
+        assert_eq!(metadata.age_recipients(), ["age1me"]);
+        assert_eq!(metadata.pgp[0].fp, "FINGERPRINT");
+        assert_eq!(
+            metadata.lastmodified.as_deref(),
+            Some("2024-01-31T23:59:59Z")
+        );
+        assert_eq!(metadata.version.as_deref(), Some("3.8.1"));
+        assert!(metadata.has_mac());
+        assert_eq!(
+            metadata.selector(),
+            [
+                ("encrypted_regex", "^(data|stringData)$".to_string()),
+                ("mac_only_encrypted", "true".to_string())
+            ]
+        );
+        assert!(metadata.other.contains_key("kms"));
+        assert_eq!(metadata.key_ids(), ["age1me", "FINGERPRINT"]);
+
+        let json = r#"{"sops": {"kms": [{"arn": "arn:aws:kms:eu-west-1:1:key/a", "enc": "x"}], "key_groups": [{"age": [{"recipient": "age1a"}], "pgp": [{"fp": "F1"}], "hc_vault": [{"vault_address": "https://vault", "enc": "y"}]}]}}"#;
+        assert_eq!(
+            read_metadata(json).unwrap().key_ids(),
+            [
+                "age1a",
+                "kms:arn:aws:kms:eu-west-1:1:key/a",
+                "F1",
+                "hc_vault:https://vault"
+            ]
+        );
+
+        let ini = "[db]\npassword = ENC[...]\n\n[sops]\nage__list_0__map_recipient = age1me\nlastmodified = 2024-02-01T00:00:00Z\nshamir_threshold = 2\nversion = 3.9.0\n";
+        let metadata = read_metadata(ini).unwrap();
+        assert_eq!(metadata.age_recipients(), ["age1me"]);
+        assert_eq!(metadata.shamir_threshold, Some(2));
+        assert_eq!(metadata.version.as_deref(), Some("3.9.0"));
+        assert!(!metadata.has_mac());
+
+        let dotenv =
+            "A=ENC[...]\nsops_lastmodified=2024-02-01T00:00:00Z\nsops_mac_only_encrypted=false\n";
+        let metadata = read_metadata(dotenv).unwrap();
+        assert_eq!(
+            metadata.lastmodified.as_deref(),
+            Some("2024-02-01T00:00:00Z")
+        );
+        assert_eq!(metadata.mac_only_encrypted, Some(false));
+
+        assert_eq!(read_metadata("a: b\n"), None);
+        assert_eq!(read_metadata("{\"sops\": \"is a tool\"}"), None);
+    }
+}
@@ -1,24 +1,148 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct SopsConfig {
+    /// Rule fragments merged in by opsops before sops sees the config
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
     #[serde(default)]
     pub creation_rules: Vec<CreationRule>,
+    /// Where `sops publish` pushes files to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destination_rules: Vec<DestinationRule>,
+    /// Read from `.opsops.yaml`, never written to .sops.yaml
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub onepassworditem: String,
+    /// Top level sections opsops doesn't model (e.g. `stores`), kept for round-tripping
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+    /// The .sops.yaml this config was read from, to merge changes made to the
+    /// file meanwhile when writing it back
+    #[serde(skip)]
+    pub original: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct CreationRule {
     pub path_regex: Option<String>,
     pub age: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub pgp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub encrypted_regex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unencrypted_regex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted_suffix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unencrypted_suffix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted_comment_regex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unencrypted_comment_regex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shamir_threshold: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac_only_encrypted: Option<bool>,
     #[serde(default)]
     pub key_groups: Vec<KeyGroup>,
+    /// Other key sources (kms, gcp_kms, azure_keyvault, hc_vault_transit_uri, ...)
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct KeyGroup {
     #[serde(default)]
     pub age: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pgp: Vec<String>,
+    /// Other key sources (kms, gcp_kms, azure_keyvault, hc_vault, ...)
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct DestinationRule {
+    pub path_regex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3_bucket: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gcs_bucket: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gcs_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vault_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vault_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vault_kv_mount_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vault_kv_version: Option<u32>,
+    /// Creation rule the file is re-encrypted with before uploading it to a
+    /// bucket, published decrypted if unset. Unused for Vault.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recreation_rule: Option<CreationRule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omit_extensions: Option<bool>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SopsConfig;
+
+    #[test]
+    fn test_round_trip_keeps_all_rule_options() {
+        let yaml = r#"
+creation_rules:
+- path_regex: secrets/.*\.yaml$
+  age: age1abc
+  unencrypted_suffix: _plain
+  shamir_threshold: 2
+  kms: arn:aws:kms:us-east-1:1234:key/abc
+  key_groups:
+  - age:
+    - age1def
+    pgp:
+    - FINGERPRINT
+    gcp_kms:
+    - resource_id: projects/p/locations/l/keyRings/r/cryptoKeys/k
+onepassworditem: op://Vault/Item/Field
+destination_rules:
+- path_regex: s3/.*
+  s3_bucket: team-secrets
+  s3_prefix: prod/
+  recreation_rule:
+    age: age1ghi
+stores:
+  yaml:
+    indent: 2
+"#;
+        let config: SopsConfig = serde_yaml::from_str(yaml).unwrap();
+        let rule = &config.creation_rules[0];
+        assert_eq!(rule.unencrypted_suffix.as_deref(), Some("_plain"));
+        assert_eq!(rule.shamir_threshold, Some(2));
+        assert!(rule.extra.contains_key("kms"));
+        assert_eq!(rule.key_groups[0].pgp, vec!["FINGERPRINT"]);
+        assert!(rule.key_groups[0].extra.contains_key("gcp_kms"));
+        assert!(config.extra.contains_key("stores"));
+        let destination = &config.destination_rules[0];
+        assert_eq!(destination.s3_bucket.as_deref(), Some("team-secrets"));
+        assert_eq!(
+            destination.recreation_rule.as_ref().unwrap().age.as_deref(),
+            Some("age1ghi")
+        );
+
+        let written = serde_yaml::to_string(&config).unwrap();
+        let reparsed: SopsConfig = serde_yaml::from_str(&written).unwrap();
+        assert!(reparsed.creation_rules[0].extra.contains_key("kms"));
+        assert!(reparsed.extra.contains_key("stores"));
+        assert!(written.contains("unencrypted_suffix: _plain"));
+        assert!(written.contains("s3_prefix: prod/"));
+    }
 }
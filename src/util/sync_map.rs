@@ -0,0 +1,82 @@
+//! Secrets that live in 1Password and in encrypted files at the same time.
+//! `sync:` in `.opsops.yaml` pairs a 1Password field with a key path in an
+//! encrypted file, `opsops sync` shows where they drifted apart and copies
+//! values in either direction.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::op_reference::OpReference;
+use super::opsops_config::{opsops_config_path, read_opsops_config};
+use crate::GlobalContext;
+
+/// One entry of `sync:` in `.opsops.yaml`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct SyncEntry {
+    /// `op://<vault>/<item>[/<section>]/<field>`
+    pub op: String,
+    /// `<file>#<key path>`, the file relative to `.opsops.yaml`
+    pub target: String,
+}
+
+impl SyncEntry {
+    pub fn reference(&self) -> Result<OpReference, String> {
+        self.op.parse()
+    }
+
+    /// The file, relative to `root`, and the key path of the target
+    pub fn location(&self, root: &Path) -> Result<(PathBuf, String), String> {
+        match self.target.rsplit_once('#') {
+            Some((file, path)) if !file.is_empty() && !path.is_empty() => {
+                Ok((root.join(file), path.to_string()))
+            }
+            _ => Err(format!(
+                "Sync target '{}' must have the form <file>#<key.path>",
+                self.target
+            )),
+        }
+    }
+}
+
+/// The `sync:` entries of the project, validated, with the directory their
+/// targets are relative to
+pub fn sync_entries(context: &GlobalContext) -> Result<(PathBuf, Vec<SyncEntry>), String> {
+    let entries = read_opsops_config(context)?
+        .map(|config| config.sync)
+        .unwrap_or_default();
+    let root = opsops_config_path(context)
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    for entry in &entries {
+        entry
+            .reference()
+            .map_err(|e| format!("Invalid op reference in sync: {}", e))?;
+        entry.location(&root)?;
+    }
+    Ok((root, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SyncEntry;
+    use std::path::Path;
+
+    #[test]
+    fn test_sync_entry() {
+        let entry = SyncEntry {
+            op: "op://Infra/Database/password".to_string(),
+            target: "k8s/db.yaml#stringData.password".to_string(),
+        };
+        assert_eq!(entry.reference().unwrap().item, "Database");
+        let (file, path) = entry.location(Path::new("/repo")).unwrap();
+        assert_eq!(file, Path::new("/repo/k8s/db.yaml"));
+        assert_eq!(path, "stringData.password");
+
+        let invalid = SyncEntry {
+            op: "op://Infra/Database".to_string(),
+            target: "k8s/db.yaml".to_string(),
+        };
+        assert!(invalid.reference().is_err());
+        assert!(invalid.location(Path::new("/repo")).is_err());
+    }
+}
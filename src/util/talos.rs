@@ -0,0 +1,62 @@
+//! Talos machine configs and the secrets bundle from `talosctl gen secrets`/
+//! `talosctl gen config`, checked against the Talos rule template.
+
+use regex::Regex;
+use serde_yaml::Value;
+
+use super::{
+    encrypted_keys::{KeySelector, selected_values},
+    rule_templates::TALOS_REGEX,
+};
+
+/// Whether `document` looks like a machine config (`machine:` and `cluster:`) or
+/// a secrets bundle (`secrets:` and `certs:`)
+pub fn is_talos_config(document: &Value) -> bool {
+    let has = |key: &str| document.get(key).is_some();
+    (has("machine") && has("cluster")) || (has("secrets") && has("certs"))
+}
+
+/// Key paths of values the Talos template would encrypt that are still plaintext
+pub fn plaintext_secrets(document: &Value) -> Vec<String> {
+    let selector = KeySelector::EncryptedRegex(
+        Regex::new(TALOS_REGEX).expect("the Talos template regex compiles"),
+    );
+    selected_values(document, &selector)
+        .into_iter()
+        .filter(|(_, value)| match value {
+            Value::String(s) => !s.starts_with("ENC["),
+            Value::Null => false,
+            _ => true,
+        })
+        .map(|(path, _)| path)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_talos_config, plaintext_secrets};
+
+    #[test]
+    fn test_plaintext_secrets() {
+        let config = serde_yaml::from_str(
+            r#"
+version: v1alpha1
+machine:
+  type: controlplane
+  token: ENC[AES256_GCM,data:abc]
+  ca:
+    crt: LS0tLS1CRUdJTi...
+    key: ENC[AES256_GCM,data:def]
+cluster:
+  secretboxEncryptionSecret: null
+  clusterName: demo
+"#,
+        )
+        .unwrap();
+        assert!(is_talos_config(&config));
+        assert_eq!(plaintext_secrets(&config), ["machine.ca.crt"]);
+
+        let other = serde_yaml::from_str("apiVersion: v1\nkind: Secret\n").unwrap();
+        assert!(!is_talos_config(&other));
+    }
+}
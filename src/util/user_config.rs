@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+use super::{
+    dirs,
+    formatter::Theme,
+    op_rate_limit::OpRateLimit,
+    perf::{Category, span},
+};
+
+/// Per-user settings, independent of any project
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct UserConfig {
+    /// Vault suggested when storing new items in 1Password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_vault: Option<String>,
+    /// Registry of the key each project uses, see [`super::key_registry`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_registry: Option<String>,
+    /// Where `edit` puts plaintext while editing, a directory or `tmpfs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit_tmpdir: Option<String>,
+    /// Run the editor of `edit` without network access
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sandbox_editor: bool,
+    /// Pacing and retries of `op` calls for accounts that throttle them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub op_rate_limit: Option<OpRateLimit>,
+    /// Colors and symbols of the human formatter, see [`super::formatter`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<Theme>,
+    /// Language of messages, e.g. `de`, instead of the one of the locale
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// How `diff` shows changes, see [`super::diff_render`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_renderer: Option<String>,
+}
+
+/// Location of the user config: `config.yaml` in [`dirs::config_dir`]
+pub fn user_config_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("config.yaml"))
+}
+
+/// Reads the user config, returning defaults if it doesn't exist or can't be parsed
+pub fn read_user_config() -> UserConfig {
+    let _span = span(Category::ConfigIo, "read the user config");
+    user_config_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_yaml::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn write_user_config(config: &UserConfig) -> Result<PathBuf, String> {
+    let path = user_config_path().ok_or("Could not determine user config directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let yaml =
+        serde_yaml::to_string(config).map_err(|e| format!("Failed to serialize config: {}", e))?;
+    fs::write(&path, yaml).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    Ok(path)
+}
@@ -0,0 +1,165 @@
+//! Expiry dates of single values, for certificates and tokens that have to be
+//! renewed before they run out. A date is either kept next to the value in
+//! the encrypted file, as a `<key>__expires: YYYY-MM-DD` sibling, or in
+//! `value_expiry:` in `.opsops.yaml`:
+//!
+//! ```yaml
+//! value_expiry:
+//! - file: certs/tls.yaml
+//!   key: tls.crt
+//!   expires: 2025-12-01
+//!   note: wildcard certificate
+//! ```
+//!
+//! `stats` reads both without a key, so annotations in files have to stay in
+//! plaintext: leave `__expires` keys out of `encrypted_regex`, or end them in
+//! the rule's `unencrypted_suffix`. Annotations sops encrypted are reported
+//! as unreadable.
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    backups::civil_from_days,
+    document::{parse_document, scalar_text},
+    recipient_expiry::validate_date,
+    sops_files::sops_file_type,
+};
+
+/// Suffix of the keys holding the expiry date of their sibling
+pub const EXPIRES_SUFFIX: &str = "__expires";
+
+/// How many days ahead values count as expiring soon by default
+pub const DEFAULT_WARNING_DAYS: u64 = 30;
+
+/// One entry of `value_expiry:` in `.opsops.yaml`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ValueExpiry {
+    /// Encrypted file, relative to the project root
+    pub file: String,
+    /// Key path of the value, e.g. `tls.crt`
+    pub key: String,
+    /// Last valid day, `YYYY-MM-DD`
+    pub expires: String,
+    /// What the value is, shown in reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpiryState {
+    Expired,
+    Soon,
+    Valid,
+}
+
+/// An annotation found in a file, `None` if sops encrypted the date
+pub type Annotation = (String, Option<String>);
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn walk(value: &Value, path: &str, found: &mut Vec<Annotation>) {
+    match value {
+        Value::Mapping(map) => {
+            for (key, value) in map {
+                let Some(key) = key.as_str() else {
+                    continue;
+                };
+                if path.is_empty() && key == "sops" {
+                    continue;
+                }
+                match (key.strip_suffix(EXPIRES_SUFFIX), scalar_text(value)) {
+                    (Some(target), Some(date)) if !target.is_empty() => found.push((
+                        join(path, target),
+                        (!date.starts_with("ENC[")).then_some(date),
+                    )),
+                    _ => walk(value, &join(path, key), found),
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            for (i, item) in items.iter().enumerate() {
+                walk(item, &format!("{}[{}]", path, i), found);
+            }
+        }
+        Value::Tagged(tagged) => walk(&tagged.value, path, found),
+        _ => {}
+    }
+}
+
+/// The `__expires` annotations of the encrypted `contents` of `file`, with the
+/// key paths of the values they annotate
+pub fn annotations(contents: &str, file: &Path) -> Vec<Annotation> {
+    let Ok(document) = parse_document(contents.as_bytes(), sops_file_type(file)) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    walk(&document, "", &mut found);
+    found
+}
+
+/// The UTC date `days` days from now as `YYYY-MM-DD`
+pub fn date_in(days: u64) -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86_400 + days) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Whether a value with the last day `expires` ran out before `today` or does
+/// by `cutoff`. Invalid dates count as expired, so a typo doesn't hide one.
+pub fn expiry_state(expires: &str, today: &str, cutoff: &str) -> ExpiryState {
+    if validate_date(expires).is_err() || expires < today {
+        ExpiryState::Expired
+    } else if expires <= cutoff {
+        ExpiryState::Soon
+    } else {
+        ExpiryState::Valid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{ExpiryState, annotations, expiry_state};
+
+    #[test]
+    fn test_annotations() {
+        let yaml = "tls:\n    crt: ENC[AES256_GCM,data:x]\n    crt__expires: 2025-12-01\napi:\n    - token: ENC[AES256_GCM,data:y]\n      token__expires: ENC[AES256_GCM,data:z]\nsops:\n    mac: x\n";
+        assert_eq!(
+            annotations(yaml, Path::new("app.yaml")),
+            vec![
+                ("tls.crt".to_string(), Some("2025-12-01".to_string())),
+                ("api[0].token".to_string(), None),
+            ]
+        );
+        assert_eq!(
+            annotations(
+                "TOKEN=ENC[x]\nTOKEN__expires=2026-01-31\n",
+                Path::new(".env")
+            ),
+            vec![("TOKEN".to_string(), Some("2026-01-31".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_expiry_state() {
+        let state = |expires| expiry_state(expires, "2025-07-01", "2025-07-31");
+        assert_eq!(state("2025-06-30"), ExpiryState::Expired);
+        assert_eq!(state("2025-07-01"), ExpiryState::Soon);
+        assert_eq!(state("2025-07-31"), ExpiryState::Soon);
+        assert_eq!(state("2025-08-01"), ExpiryState::Valid);
+        assert_eq!(state("someday"), ExpiryState::Expired);
+    }
+}
@@ -0,0 +1,3287 @@
+mod common;
+
+use std::os::fd::AsRawFd;
+
+use common::{Harness, stderr, stdout};
+
+#[test]
+fn encrypt_passes_key_via_file_descriptor() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let log = harness.log();
+    assert_eq!(log[0], "op read op://Vault/Item/Key");
+    assert_eq!(log[1], "sops --encrypt --output secrets.yaml secrets.yaml");
+    assert_eq!(
+        log[2],
+        format!(
+            "key-file {}",
+            age::secrecy::ExposeSecret::expose_secret(&harness.key.to_string())
+        )
+    );
+    assert!(!log.iter().any(|l| l.starts_with("key-env")));
+    assert!(harness.read("secrets.yaml").contains("sops:"));
+}
+
+#[test]
+fn encrypt_with_age_key_env_uses_environment() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["--age-key-env", "encrypt", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let log = harness.log();
+    assert!(log.iter().any(|l| l.starts_with("key-env AGE-SECRET-KEY-")));
+    assert!(!log.iter().any(|l| l.starts_with("key-file")));
+}
+
+#[test]
+fn decrypt_strips_enc_extension() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "secrets.yaml.enc",
+        "password: hunter2\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&["decrypt", "secrets.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert!(
+        harness
+            .log()
+            .contains(&"sops --decrypt --output secrets.yaml secrets.yaml.enc".to_string())
+    );
+    assert_eq!(harness.read("secrets.yaml"), "password: hunter2\n");
+}
+
+#[test]
+fn decrypt_restricts_output_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("app.yaml.enc", "password: hunter2\nsops:\n    mac: fake\n");
+    harness.write("ci.yaml.enc", "password: hunter2\nsops:\n    mac: fake\n");
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\noutput_permissions:\n  - path_regex: ^ci\\.\n    mode: \"0640\"\n",
+    );
+    let mode = |name: &str| {
+        std::fs::metadata(harness.project().join(name))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o7777
+    };
+
+    let output = harness.run(&["decrypt", "app.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(mode("app.yaml"), 0o600);
+
+    let output = harness.run(&["decrypt", "ci.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(mode("ci.yaml"), 0o640);
+
+    let output = harness.run(&["decrypt", "app.yaml.enc", "--mode", "0400"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(mode("app.yaml"), 0o400);
+
+    let output = harness.run(&["decrypt", "app.yaml.enc", "--mode", "rw"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Invalid mode 'rw'"));
+}
+
+#[test]
+fn markdown_notes_only_encrypt_secrets() {
+    let harness = Harness::new();
+    harness.write_config();
+    let note = "---\ndb: hunter2\n---\n# Restore\n\n```secret\nadmin / s3cret\n```\n";
+    harness.write("runbook.md", note);
+
+    let output = harness.run(&["encrypt", "runbook.md"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.log().contains(
+        &"sops --encrypt --input-type yaml --output-type yaml --filename-override runbook.md /dev/stdin"
+            .to_string()
+    ));
+    let encrypted = harness.read("runbook.md");
+    assert!(encrypted.contains("# Restore\n"));
+    assert!(encrypted.contains("```secret\nopsops_secret_blocks[0]\n```\n"));
+    assert!(encrypted.contains("sops:\n    mac: fake\n"));
+
+    let output = harness.run(&["read", "runbook.md"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), note);
+
+    // The editor appends a line to the note
+    harness.fake_binary(
+        "append-editor",
+        "#!/bin/sh\necho 'Call the DBA.' >> \"$1\"\n",
+    );
+    let output = harness.run_with_env(&["edit", "runbook.md"], &[("EDITOR", "append-editor")]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.read("runbook.md").ends_with("```\nCall the DBA.\n"));
+
+    let output = harness.run(&["decrypt", "runbook.md"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        harness.read("runbook.md"),
+        format!("{}Call the DBA.\n", note)
+    );
+}
+
+#[test]
+fn encrypt_fails_for_missing_file() {
+    let harness = Harness::new();
+    harness.write_config();
+
+    let output = harness.run(&["encrypt", "missing.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("File not found: missing.yaml"));
+    assert!(harness.log().is_empty());
+}
+
+#[test]
+fn op_item_flag_overrides_config() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&[
+        "--op-item",
+        "op://Other/Item/Key",
+        "encrypt",
+        "secrets.yaml",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(harness.log()[0], "op read op://Other/Item/Key");
+}
+
+#[test]
+fn init_with_existing_config_does_nothing() {
+    let harness = Harness::new();
+    harness.write_config();
+    let before = harness.read(".sops.yaml");
+
+    let output = harness.run(&["init"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("No action needed"));
+    assert_eq!(harness.read(".sops.yaml"), before);
+    assert!(harness.log().is_empty());
+}
+
+#[test]
+fn init_writes_op_item_into_config() {
+    let harness = Harness::new();
+    harness.write("config.yaml", "");
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "creation_rules:\n- path_regex: .*\n  age: {}\n",
+            harness.public_key()
+        ),
+    );
+
+    let output = harness.run(&["--op-item", "op://Vault/Item/Key", "init"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let opsops: serde_yaml::Value = serde_yaml::from_str(&harness.read(".opsops.yaml")).unwrap();
+    assert_eq!(opsops["onepassworditem"], "op://Vault/Item/Key");
+    assert_eq!(opsops["version"], 1);
+
+    let config: serde_yaml::Value = serde_yaml::from_str(&harness.read(".sops.yaml")).unwrap();
+    assert!(config.get("onepassworditem").is_none());
+    assert_eq!(config["creation_rules"][0]["path_regex"], ".*");
+    assert_eq!(
+        config["creation_rules"][0]["age"].as_str(),
+        Some(harness.public_key().as_str())
+    );
+}
+
+#[test]
+fn target_keys_adds_rule_for_file() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("deployment.yaml", "kind: Secret\n");
+
+    let output = harness.run(&[
+        "target-keys",
+        "deployment.yaml",
+        "--encrypted-regex",
+        "^(data|stringData)$",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(harness.log(), vec!["op read op://Vault/Item/Key"]);
+
+    let config: serde_yaml::Value = serde_yaml::from_str(&harness.read(".sops.yaml")).unwrap();
+    let rule = &config["creation_rules"][1];
+    assert_eq!(rule["path_regex"], "^deployment\\.yaml$");
+    assert_eq!(rule["age"].as_str(), Some(harness.public_key().as_str()));
+    assert_eq!(rule["encrypted_regex"], "^(data|stringData)$");
+}
+
+#[test]
+fn target_keys_picks_pattern_by_content() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nrule_conditions:\n- content_kind: kubernetes-secret\n  encrypted_regex: kubernetes\n- has_key: apiKey\n  encrypted_regex: ^apiKey$\n",
+    );
+    harness.write(
+        "moved/around/secret.yaml",
+        "apiVersion: v1\nkind: Secret\nstringData:\n  password: x\n",
+    );
+    harness.write("service.json", r#"{"service": {"apiKey": "x"}}"#);
+
+    let output = harness.run(&["target-keys", "moved/around/secret.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Using encrypted_regex 'kubernetes' from rule_conditions"));
+    let output = harness.run(&["target-keys", "service.json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let config: serde_yaml::Value = serde_yaml::from_str(&harness.read(".sops.yaml")).unwrap();
+    assert_eq!(
+        config["creation_rules"][1]["encrypted_regex"].as_str(),
+        Some("^(data|stringData|password|token|secret|key|cert|ca.crt|tls|ingress|backupTarget)")
+    );
+    assert_eq!(config["creation_rules"][2]["encrypted_regex"], "^apiKey$");
+}
+
+#[test]
+fn target_keys_rejects_unsupported_extension() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("notes.txt", "");
+    let before = harness.read(".sops.yaml");
+
+    let output = harness.run(&["target-keys", "notes.txt", "--encrypted-regex", ".*"]);
+    assert!(stderr(&output).contains("Only YAML and JSON files are supported."));
+    assert_eq!(harness.read(".sops.yaml"), before);
+}
+
+#[test]
+fn doctor_finds_matching_public_key() {
+    let harness = Harness::new();
+    harness.write_config();
+
+    let output = harness.run(&["doctor"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let out = stdout(&output);
+    assert!(out.contains("Found sops:"));
+    assert!(out.contains("sops 3.10.2"));
+    assert!(out.contains("1Password item found in .opsops.yaml: op://Vault/Item/Key"));
+    assert!(out.contains(&format!(
+        "Found matching public key: {}",
+        harness.public_key()
+    )));
+    assert!(out.contains("No private keys found in tracked files or history"));
+    // The private key itself is never printed
+    assert!(!out.contains(age::secrecy::ExposeSecret::expose_secret(
+        &harness.key.to_string()
+    )));
+}
+
+#[test]
+fn doctor_reports_which_identity_of_a_keys_file_matches() {
+    let harness = Harness::new();
+    harness.write_config();
+    let old = age::x25519::Identity::generate();
+    let keys = format!(
+        "# created: 2023-01-01T00:00:00Z\n# public key: {}\n{}\n# created: 2024-01-01T00:00:00Z\n{}\n",
+        old.to_public(),
+        age::secrecy::ExposeSecret::expose_secret(&old.to_string()),
+        harness.private_key()
+    );
+
+    let output = harness.run_with_env(&["doctor"], &[("FAKE_AGE_KEY", &keys)]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains(&format!(
+        "1 of 2 identities match recipients in .sops.yaml: {}",
+        harness.public_key()
+    )));
+
+    // sops gets every identity, without the comments
+    harness.write("secrets.yaml", "password: hunter2\nsops:\n    mac: fake\n");
+    let output = harness.run_with_env(&["read", "secrets.yaml"], &[("FAKE_AGE_KEY", &keys)]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let log = harness.log();
+    assert!(log.contains(&format!(
+        "key-file {}",
+        age::secrecy::ExposeSecret::expose_secret(&old.to_string())
+    )));
+    assert!(log.contains(&harness.private_key()));
+}
+
+#[test]
+fn doctor_reports_mismatched_public_key() {
+    let harness = Harness::new();
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "creation_rules:\n- path_regex: .*\n  age: {}\nonepassworditem: op://Vault/Item/Key\n",
+            age::x25519::Identity::generate().to_public()
+        ),
+    );
+
+    let output = harness.run(&["doctor"]);
+    let err = stderr(&output);
+    assert!(err.contains("No matching public key found in .sops.yaml config."));
+    assert!(stdout(&output).contains(&format!("Your public key is: {}", harness.public_key())));
+}
+
+#[test]
+fn bench_runs_every_section() {
+    let harness = Harness::new();
+    harness.write_config();
+
+    let output = harness.run(&["bench", "--self", "--files", "20", "--rounds", "1"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let out = stdout(&output);
+    assert!(out.contains("match 1 rules"));
+    assert!(out.contains("1Password (uncached)"));
+    assert!(out.contains("files/s"));
+    assert!(
+        harness
+            .log()
+            .iter()
+            .any(|l| l.starts_with("sops --config") && l.contains("--encrypt --output"))
+    );
+}
+
+#[test]
+fn encrypt_fails_while_file_is_locked() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    // Hold the lock the way a concurrent edit would
+    let lock_path = harness
+        .project()
+        .join(".git/opsops-locks/secrets.yaml.lock");
+    std::fs::create_dir_all(lock_path.parent().unwrap()).unwrap();
+    let lock = std::fs::File::create(&lock_path).unwrap();
+    std::fs::write(&lock_path, "4242 build-host\n").unwrap();
+    let locked = unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    assert_eq!(locked, 0);
+
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("secrets.yaml is being edited by PID 4242 on build-host"));
+    assert!(harness.log().is_empty());
+
+    let output = harness.run(&["encrypt", "--force", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("(--force)"));
+}
+
+#[test]
+fn encrypt_refuses_stale_decrypted_copy() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "secrets.yaml.enc",
+        "password: hunter2\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&["decrypt", "secrets.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .read(".opsops/decrypted.json")
+            .contains("secrets.yaml.enc")
+    );
+
+    // A teammate changes the encrypted original
+    harness.write(
+        "secrets.yaml.enc",
+        "password: hunter3\nsops:\n    mac: fake2\n",
+    );
+
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("changed after secrets.yaml was decrypted from it"));
+    assert_eq!(harness.read("secrets.yaml"), "password: hunter2\n");
+}
+
+#[test]
+fn encrypt_forgets_fresh_decrypted_copy() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "secrets.yaml.enc",
+        "password: hunter2\nsops:\n    mac: fake\n",
+    );
+
+    assert!(
+        harness
+            .run(&["decrypt", "secrets.yaml.enc"])
+            .status
+            .success()
+    );
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(harness.read(".opsops/decrypted.json").trim(), "{}");
+}
+
+#[test]
+fn includes_are_merged_for_sops_and_listed_with_provenance() {
+    let harness = Harness::new();
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "include:\n- teams/payments.yaml\ncreation_rules:\n- path_regex: .*\n  age: {}\nonepassworditem: op://Vault/Item/Key\n",
+            harness.public_key()
+        ),
+    );
+    harness.write(
+        "teams/payments.yaml",
+        "creation_rules:\n- path_regex: ^payments/\n  age: age1payments\n",
+    );
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let log = harness.log();
+    let config = log.iter().find(|l| l.starts_with("config ")).unwrap();
+    assert!(!config.contains("include"), "{}", config);
+    assert!(
+        config.find("age1payments").unwrap() < config.find(&harness.public_key()).unwrap(),
+        "{}",
+        config
+    );
+    // The merged config is cleaned up afterwards
+    let leftovers = std::fs::read_dir(harness.project())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(".sops.opsops-"))
+        .count();
+    assert_eq!(leftovers, 0);
+
+    let output = harness.run(&["list-config"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("From: teams/payments.yaml"));
+}
+
+#[test]
+fn config_values_interpolate_environment_variables() {
+    let harness = Harness::new();
+    harness.write(
+        ".sops.yaml",
+        "creation_rules:\n- path_regex: \\.yaml$\n  age: ${OPSOPS_RECIPIENT}\nonepassworditem: op://${OPSOPS_VAULT}/Item/Key\n",
+    );
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let recipient = harness.public_key();
+    let output = harness.run_with_env(
+        &["encrypt", "secrets.yaml"],
+        &[("OPSOPS_VAULT", "Infra"), ("OPSOPS_RECIPIENT", &recipient)],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let log = harness.log();
+    assert_eq!(log[0], "op read op://Infra/Item/Key");
+    let config = log.iter().find(|l| l.starts_with("config ")).unwrap();
+    assert!(config.contains(&recipient), "{}", config);
+    assert!(config.contains("\\.yaml$"), "{}", config);
+
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("is not set"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn complete_lists_config_and_recently_used_references() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&[
+        "--op-item",
+        "op://Other/Item/Key",
+        "encrypt",
+        "secrets.yaml",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = harness.run(&["__complete", "op-item", "op://"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "op://Vault/Item/Key\tfrom .opsops.yaml\nop://Other/Item/Key\trecently used\n"
+    );
+
+    let output = harness.run(&["__complete", "file", "sec"]);
+    assert_eq!(stdout(&output), "secrets.yaml\tmatches a rule\n");
+}
+
+#[test]
+fn missing_path_without_terminal_fails() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["encrypt"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("no terminal to pick a file from"));
+    assert!(harness.log().is_empty());
+}
+
+#[test]
+fn paths_are_resolved_relative_to_the_config() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("k8s/secret.yaml", "kind: Secret\n");
+
+    for spelling in ["./k8s/secret.yaml", "k8s/../k8s/secret.yaml"] {
+        let output = harness.run(&["target-keys", spelling, "--encrypted-regex", "^data$"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    let absolute = harness.project().join("k8s/secret.yaml");
+    let output = harness.run(&[
+        "target-keys",
+        absolute.to_str().unwrap(),
+        "--encrypted-regex",
+        "^data$",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // All three spellings end up in the same rule
+    let config: serde_yaml::Value = serde_yaml::from_str(&harness.read(".sops.yaml")).unwrap();
+    let rules = config["creation_rules"].as_sequence().unwrap();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[1]["path_regex"], "^k8s/secret\\.yaml$");
+
+    let output = harness.run(&["encrypt", absolute.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .log()
+            .contains(&"sops --encrypt --output k8s/secret.yaml k8s/secret.yaml".to_string())
+    );
+}
+
+#[test]
+fn doctor_warns_about_unescaped_path_rules() {
+    let harness = Harness::new();
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "creation_rules:\n- path_regex: config.prod.yaml\n  age: {}\nonepassworditem: op://Vault/Item/Key\n",
+            harness.public_key()
+        ),
+    );
+    harness.write("config.prod.yaml", "");
+    harness.write("config-prod-yaml.bak", "");
+
+    let output = harness.run(&["doctor"]);
+    let out = stdout(&output);
+    assert!(
+        out.contains("Rule #1 path_regex 'config.prod.yaml' is a plain path"),
+        "{}",
+        out
+    );
+    assert!(out.contains("also matches config-prod-yaml.bak"), "{}", out);
+    assert!(out.contains("^config\\.prod\\.yaml$"), "{}", out);
+}
+
+#[test]
+fn paths_honor_xdg_directories() {
+    let harness = Harness::new();
+    harness.write_config();
+
+    let output = harness.run(&["paths", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let paths: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+
+    let base = harness.dir.path();
+    assert_eq!(
+        paths["user_config"],
+        base.join("config/opsops/config.yaml").to_str().unwrap()
+    );
+    assert_eq!(
+        paths["reference_cache"],
+        base.join("cache/opsops/references").to_str().unwrap()
+    );
+    assert_eq!(
+        paths["agent_socket"],
+        base.join("no-agent.sock").to_str().unwrap()
+    );
+    assert!(
+        paths["locks"]
+            .as_str()
+            .unwrap()
+            .ends_with(".git/opsops-locks")
+    );
+}
+
+#[test]
+fn legacy_op_item_is_migrated_to_opsops_yaml() {
+    let harness = Harness::new();
+    let legacy = format!(
+        "creation_rules:\n- path_regex: .*\n  age: {}\nonepassworditem: op://Vault/Item/Key\n",
+        harness.public_key()
+    );
+    harness.write(".sops.yaml", &legacy);
+
+    let output = harness.run(&["doctor"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Migrated project config"));
+
+    let opsops: serde_yaml::Value = serde_yaml::from_str(&harness.read(".opsops.yaml")).unwrap();
+    assert_eq!(opsops["version"], 1);
+    assert_eq!(opsops["onepassworditem"], "op://Vault/Item/Key");
+    assert!(!harness.read(".sops.yaml").contains("onepassworditem"));
+    assert_eq!(harness.read(".opsops/migrations/.sops.yaml.v0.bak"), legacy);
+
+    // Already migrated projects are left alone
+    let output = harness.run(&["doctor"]);
+    assert!(!stdout(&output).contains("Migrated project config"));
+}
+
+#[test]
+fn chdir_resolves_paths_from_the_given_directory() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("services/api/secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["-C", "services/api", "encrypt", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.log().contains(
+        &"sops --encrypt --output services/api/secrets.yaml services/api/secrets.yaml".to_string()
+    ));
+
+    let output = harness.run(&["--chdir", "missing", "encrypt", "secrets.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Cannot change to missing"));
+}
+
+#[test]
+fn read_prints_and_post_processes_decrypted_content() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "secrets.yaml",
+        "db:\n  password: hunter2\n  port: 5432\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&["read", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "db:\n  password: hunter2\n  port: 5432\n");
+
+    let output = harness.run(&["read", "secrets.yaml", "--extract", "db.password"]);
+    assert_eq!(stdout(&output), "hunter2\n");
+
+    let output = harness.run(&["read", "secrets.yaml", "--redact", "--format", "json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["db"]["password"], "<redacted>");
+    assert!(!stdout(&output).contains("hunter2"));
+
+    let output = harness.run(&["read", "secrets.yaml", "--extract", "db.user"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Key path 'db.user' not found"));
+}
+
+#[test]
+fn read_passes_on_sops_errors() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("plain.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["read", "plain.yaml"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(stderr(&output).contains("sops metadata not found"));
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn encrypt_refuses_config_files() {
+    let harness = Harness::new();
+    harness.write_config();
+
+    let output = harness.run(&["encrypt", ".opsops.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Refusing to encrypt .opsops.yaml"));
+    assert!(stderr(&output).contains("--allow-protected"));
+    assert!(!harness.read(".opsops.yaml").contains("sops:"));
+    assert!(harness.log().is_empty());
+
+    let output = harness.run(&["encrypt", ".opsops.yaml", "--allow-protected"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.read(".opsops.yaml").contains("sops:"));
+}
+
+#[test]
+fn restore_rolls_back_to_the_backup_taken_before_edit() {
+    let harness = Harness::new();
+    harness.write_config();
+    let original = "password: ENC[old]\nsops:\n    mac: old\n";
+    harness.write("secrets.yaml", original);
+
+    let output = harness.run(&["edit", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    // Re-encrypted with a key that turns out to be wrong
+    harness.write("secrets.yaml", "password: ENC[new]\nsops:\n    mac: new\n");
+
+    let output = harness.run(&["restore", "secrets.yaml", "--list"]);
+    assert_eq!(stdout(&output).lines().count(), 1, "{}", stdout(&output));
+
+    let output = harness.run(&["restore", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(harness.read("secrets.yaml"), original);
+
+    // The replaced version was kept as well
+    let output = harness.run(&["restore", "secrets.yaml", "--list"]);
+    assert_eq!(stdout(&output).lines().count(), 2, "{}", stdout(&output));
+
+    let output = harness.run(&["restore", "secrets.yaml", "--at", "1999"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn doctor_ci_fails_and_writes_reports() {
+    let harness = Harness::new();
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "creation_rules:\n- path_regex: .*\n  age: {}\n",
+            age::x25519::Identity::generate().to_public()
+        ),
+    );
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\n",
+    );
+
+    // Without --ci doctor only reports
+    assert!(harness.run(&["doctor"]).status.success());
+
+    let output = harness.run(&[
+        "doctor",
+        "--ci",
+        "--json",
+        "report.json",
+        "--junit",
+        "report.xml",
+    ]);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+
+    let report: serde_json::Value = serde_json::from_str(&harness.read("report.json")).unwrap();
+    let public_key = report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == "public_key")
+        .unwrap();
+    assert_eq!(public_key["status"], "error");
+
+    let junit = harness.read("report.xml");
+    assert!(junit.contains("<testcase classname=\"opsops doctor\" name=\"public_key\">"));
+    assert!(junit.contains("<failure message=\"No matching public key"));
+}
+
+#[test]
+fn escrow_recipient_is_required_on_every_rule() {
+    let harness = Harness::new();
+    harness.write_config();
+    let escrow = age::x25519::Identity::generate().to_public().to_string();
+    harness.write(
+        ".opsops.yaml",
+        &format!(
+            "version: 1\nonepassworditem: op://Vault/Item/Key\nescrow_recipient: {}\n",
+            escrow
+        ),
+    );
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("doesn't encrypt to the escrow recipient"));
+    assert!(harness.log().is_empty());
+    assert!(!harness.run(&["escrow", "verify"]).status.success());
+
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "creation_rules:\n- path_regex: .*\n  age: {},{}\n",
+            harness.public_key(),
+            escrow
+        ),
+    );
+    harness.write(
+        "secrets.yaml",
+        &format!(
+            "password: ENC[abc]\nsops:\n    age:\n        - recipient: {}\n    mac: ENC[def]\n",
+            escrow
+        ),
+    );
+    harness.write(
+        "other.yaml",
+        &format!(
+            "password: ENC[abc]\nsops:\n    age:\n        - recipient: {}\n    mac: ENC[def]\n",
+            harness.public_key()
+        ),
+    );
+
+    let output = harness.run(&["escrow", "verify"]);
+    assert!(!output.status.success());
+    let out = stdout(&output);
+    assert!(out.contains("✓ secrets.yaml"), "{}", out);
+    assert!(out.contains("✗ other.yaml"), "{}", out);
+
+    harness.write("other.yaml", &harness.read("secrets.yaml"));
+    let output = harness.run(&["escrow", "verify"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn preview_marks_values_matched_by_encrypted_regex() {
+    let harness = Harness::new();
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "creation_rules:\n- path_regex: ^k8s/\n  age: {}\n  encrypted_regex: ^(data|stringData)$\n",
+            harness.public_key()
+        ),
+    );
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\n",
+    );
+    harness.write(
+        "k8s/secret.yaml",
+        "kind: Secret\nmetadata:\n  name: db\ndata:\n  password: aHVudGVyMg==\n",
+    );
+
+    let output = harness.run(&["preview", "k8s/secret.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("Rule #1 ^k8s/ (encrypted_regex: ^(data|stringData)$)"),
+        "{}",
+        out
+    );
+    assert!(out.contains("  🔒 password"), "{}", out);
+    assert!(!out.contains("🔒 name"), "{}", out);
+    assert!(out.contains("1 of 3 values would be encrypted"), "{}", out);
+    // Nothing was encrypted
+    assert!(harness.log().is_empty());
+}
+
+#[test]
+fn rule_test_lists_matching_keys() {
+    let harness = Harness::new();
+    harness.write(
+        "app.json",
+        r#"{"name": "app", "credentials": {"api_key": "abc", "user": "me"}, "password": "x"}"#,
+    );
+
+    let output = harness.run(&[
+        "rule",
+        "test",
+        "--regex",
+        "^(api_key|password)$",
+        "app.json",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("🔒 api_key"), "{}", out);
+    assert!(out.contains("🔒 password"), "{}", out);
+    assert!(!out.contains("🔒 user"), "{}", out);
+    assert!(out.contains("2 of 4 values would be encrypted"), "{}", out);
+
+    let output = harness.run(&["rule", "test", "--regex", "(", "app.json"]);
+    assert!(stderr(&output).contains("Invalid regex"));
+}
+
+#[test]
+fn talos_verify_finds_plaintext_secrets() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "talos/controlplane.yaml",
+        "version: v1alpha1\nmachine:\n  token: abc.def\n  ca:\n    crt: ENC[AES256_GCM,data:x]\ncluster:\n  clusterName: demo\n",
+    );
+    harness.write(
+        "talos/worker.yaml",
+        "version: v1alpha1\nmachine:\n  token: ENC[AES256_GCM,data:y]\ncluster:\n  clusterName: demo\n",
+    );
+    harness.write("k8s/app.yaml", "token: not-talos\n");
+
+    let output = harness.run(&["talos", "verify"]);
+    assert!(!output.status.success());
+    let out = stdout(&output);
+    assert!(out.contains("✗ talos/controlplane.yaml"), "{}", out);
+    assert!(out.contains("plaintext machine.token"), "{}", out);
+    assert!(out.contains("✓ talos/worker.yaml"), "{}", out);
+    assert!(!out.contains("k8s/app.yaml"), "{}", out);
+
+    let output = harness.run(&["talos", "verify", "talos/worker.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+/// Fake kubectl. `get secret` prints a Secret with `$FAKE_AGE_KEY_B64`, `apply` logs its input.
+const FAKE_KUBECTL: &str = r#"#!/bin/sh
+echo "kubectl $*" >> "$FAKE_LOG"
+case "$1" in
+    get) printf '{"data": {"age.agekey": "%s"}}' "$FAKE_AGE_KEY_B64" ;;
+    apply) cat >> "$FAKE_LOG" ;;
+esac
+"#;
+
+#[test]
+fn flux_check_compares_the_cluster_secret_with_1password() {
+    use base64::Engine;
+
+    let harness = Harness::new();
+    harness.write_config();
+    harness.fake_binary("kubectl", FAKE_KUBECTL);
+    harness.write(
+        "clusters/prod/apps.yaml",
+        "apiVersion: kustomize.toolkit.fluxcd.io/v1\nkind: Kustomization\nmetadata:\n  name: apps\n  namespace: flux-system\nspec:\n  decryption:\n    provider: sops\n    secretRef:\n      name: sops-age\n",
+    );
+    let encode = |key: &str| base64::engine::general_purpose::STANDARD.encode(key);
+
+    let output = harness.run_with_env(
+        &["flux", "check"],
+        &[("FAKE_AGE_KEY_B64", &encode(&harness.private_key()))],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Secret flux-system/sops-age holds the key from 1Password"));
+
+    let other = age::x25519::Identity::generate();
+    let output = harness.run_with_env(
+        &["flux", "check"],
+        &[(
+            "FAKE_AGE_KEY_B64",
+            &encode(age::secrecy::ExposeSecret::expose_secret(
+                &other.to_string(),
+            )),
+        )],
+    );
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("holds a different age key than 1Password"));
+
+    let output = harness.run(&["flux", "create-secret", "--dry-run"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let log = harness.log().join("\n");
+    assert!(log.contains("kubectl apply -f - --dry-run=client"));
+    assert!(log.contains(&format!("  age.agekey: {}", harness.private_key())));
+}
+
+#[test]
+fn argocd_bootstrap_pipes_the_secret_and_writes_patches() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.fake_binary("kubectl", FAKE_KUBECTL);
+
+    let output = harness.run(&["argocd", "bootstrap", "--dry-run"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let log = harness.log().join("\n");
+    assert!(log.contains("kubectl apply -f - --dry-run=client"));
+    assert!(log.contains("  namespace: argocd"));
+    assert!(log.contains(&format!("  keys.txt: {}", harness.private_key())));
+
+    let patch = harness.read("argocd-ksops/argocd-repo-server-patch.yaml");
+    assert!(patch.contains("secretName: sops-age"));
+    assert!(!patch.contains(&harness.private_key()));
+    assert!(
+        harness
+            .read("argocd-ksops/argocd-cm-patch.yaml")
+            .contains("--enable-exec")
+    );
+}
+
+#[test]
+fn doctor_org_warns_about_key_reuse() {
+    let harness = Harness::new();
+    harness.write_config();
+    let registry = harness.dir.path().join("config/opsops/projects.yaml");
+    std::fs::create_dir_all(registry.parent().unwrap()).unwrap();
+    std::fs::write(
+        &registry,
+        format!(
+            "projects:\n  git@github.com:org/other.git: {}\n",
+            harness.public_key()
+        ),
+    )
+    .unwrap();
+
+    let output = harness.run(&["doctor", "--org"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("The age key of this project also protects 1 other project(s)"),
+        "{}",
+        out
+    );
+    assert!(out.contains("- git@github.com:org/other.git"), "{}", out);
+
+    // This project was recorded as well
+    let registry = std::fs::read_to_string(&registry).unwrap();
+    assert!(registry.contains("/project: age1"), "{}", registry);
+}
+
+#[test]
+fn csv_import_and_export_round_trip() {
+    let harness = Harness::new();
+    harness.write_config();
+    let table = "name,username,password\ngithub,bob,\"p,w\"\n";
+    harness.write("passwords.csv", table);
+
+    let output = harness.run(&["import", "csv", "passwords.csv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Imported 1 entries"));
+    assert_eq!(
+        harness.read("passwords.yaml"),
+        "github:\n  username: bob\n  password: p,w\nsops:\n    mac: fake\n"
+    );
+
+    let output = harness.run(&["import", "csv", "passwords.csv"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Refusing to overwrite"));
+
+    let output = harness.run(&["export", "csv", "passwords.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), table);
+
+    let output = harness.run(&["export", "csv", "passwords.yaml", "-o", "out.tsv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        harness.read("out.tsv"),
+        "name\tusername\tpassword\ngithub\tbob\tp,w\n"
+    );
+}
+
+#[test]
+fn schema_attached_to_rule_is_enforced() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nschemas:\n- path_regex: .*\n  schema: schema.json\n",
+    );
+    harness.write(
+        "schema.json",
+        r#"{"type": "object", "required": ["db"], "properties": {"db": {"required": ["password"]}}}"#,
+    );
+    harness.write("secrets.yaml", "db:\n  user: app\n");
+
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("doesn't match"));
+    assert!(stderr(&output).contains("  /db/password: is required"));
+    assert!(harness.log().is_empty());
+
+    harness.write("secrets.yaml", "db:\n  user: app\n  password: hunter2\n");
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = harness.run(&["verify", "--deep", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ secrets.yaml"));
+
+    let output = harness.run(&["verify", "schema.json"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("not encrypted"));
+}
+
+#[test]
+fn edit_keeps_plaintext_in_tmpdir() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("runbook.md", "```secret\ntoken\n```\n");
+    let output = harness.run(&["encrypt", "runbook.md"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let tmpdir = harness.dir.path().join("ram");
+    std::fs::create_dir(&tmpdir).unwrap();
+    let seen = harness.dir.path().join("seen");
+    harness.fake_binary(
+        "recording-editor",
+        &format!("#!/bin/sh\ndirname \"$1\" > {}\n", seen.display()),
+    );
+    let output = harness.run_with_env(
+        &["edit", "runbook.md", "--tmpdir", tmpdir.to_str().unwrap()],
+        &[("EDITOR", "recording-editor")],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("File has not changed."));
+    assert_eq!(
+        std::fs::read_to_string(&seen).unwrap().trim(),
+        tmpdir.to_str().unwrap()
+    );
+    assert_eq!(std::fs::read_dir(&tmpdir).unwrap().count(), 0);
+
+    let output = harness.run(&["edit", "runbook.md", "--tmpdir", "/definitely/missing"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("doesn't exist"));
+}
+
+#[test]
+fn read_only_refuses_writes() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "secrets.yaml.enc",
+        "password: hunter2\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&["--read-only", "decrypt", "secrets.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "password: hunter2\n");
+    assert!(!harness.project().join("secrets.yaml").exists());
+
+    let output = harness.run(&["encrypt", "--read-only", "secrets.yaml.enc"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Refusing to encrypt files in read-only mode"));
+    assert!(harness.log().iter().all(|l| !l.contains("--encrypt")));
+
+    let output = harness.run(&[
+        "--read-only",
+        "export",
+        "csv",
+        "secrets.yaml.enc",
+        "-o",
+        "x.csv",
+    ]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Refusing to write files in read-only mode"));
+
+    harness.write(
+        "kubeconfig.yaml",
+        "clusters:\n- name: prod\nsops:\n    mac: fake\n",
+    );
+    let tmpdir = harness.dir.path().join("tmp");
+    std::fs::create_dir(&tmpdir).unwrap();
+    let output = harness.run(&[
+        "--read-only",
+        "kubeconfig",
+        "use",
+        "kubeconfig.yaml",
+        "--print",
+        "--tmpdir",
+        tmpdir.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Refusing to write decrypted kubeconfigs in read-only mode"));
+    assert!(std::fs::read_dir(&tmpdir).unwrap().next().is_none());
+
+    let written = tmpdir.join("opsops-kube-test");
+    std::fs::create_dir(&written).unwrap();
+    std::fs::write(written.join("config"), "clusters: []\n").unwrap();
+    let output = harness.run(&[
+        "--read-only",
+        "kubeconfig",
+        "wipe",
+        written.join("config").to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Refusing to delete kubeconfigs in read-only mode"));
+    assert!(written.join("config").exists());
+}
+
+#[test]
+fn drift_lists_files_out_of_step_with_their_rule() {
+    let harness = Harness::new();
+    harness.write_config();
+    let metadata = |recipient: &str, selector: &str| {
+        format!(
+            "password: ENC[abc]\nsops:\n    age:\n        - recipient: {}\n    {}\n    mac: ENC[def]\n",
+            recipient, selector
+        )
+    };
+    harness.write(
+        "current.yaml",
+        &metadata(&harness.public_key(), "unencrypted_suffix: _unencrypted"),
+    );
+    harness.write(
+        "stale.yaml",
+        &metadata("age1former", "unencrypted_suffix: _unencrypted"),
+    );
+    harness.write(
+        "narrow.yaml",
+        &metadata(&harness.public_key(), "encrypted_regex: ^password$"),
+    );
+
+    let output = harness.run(&["drift"]);
+    assert!(!output.status.success());
+    let out = stdout(&output);
+    assert!(out.contains("✓ current.yaml"), "{}", out);
+    assert!(out.contains("✗ stale.yaml"), "{}", out);
+    assert!(out.contains("still encrypted to age1former"), "{}", out);
+    assert!(out.contains("sops updatekeys stale.yaml"), "{}", out);
+    assert!(out.contains("✗ narrow.yaml"), "{}", out);
+    assert!(
+        out.contains("encrypted_regex: file has ^password$, rule has none"),
+        "{}",
+        out
+    );
+
+    let output = harness.run(&["drift", "--fix", "stale.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .log()
+            .iter()
+            .any(|l| l.starts_with("sops updatekeys --yes") && l.ends_with("stale.yaml")),
+        "{:?}",
+        harness.log()
+    );
+
+    let output = harness.run(&["--read-only", "drift", "--fix"]);
+    assert!(!output.status.success());
+    assert!(
+        harness
+            .run(&["--read-only", "drift", "current.yaml"])
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn publish_uses_the_matching_destination_rule() {
+    let harness = Harness::new();
+    harness.write_config();
+    let sops_yaml = harness.read(".sops.yaml");
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "{}destination_rules:\n- path_regex: ^prod/\n  s3_bucket: team-secrets\n  s3_prefix: prod/\n",
+            sops_yaml
+        ),
+    );
+    harness.write(
+        "prod/db.yaml",
+        "password: ENC[abc]\nsops:\n    mac: ENC[def]\n",
+    );
+    harness.write(
+        "dev/db.yaml",
+        "password: ENC[abc]\nsops:\n    mac: ENC[def]\n",
+    );
+
+    let output = harness.run(&["publish", "--yes", "prod/db.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("s3://team-secrets/prod/"));
+    assert!(
+        harness
+            .log()
+            .iter()
+            .any(|l| l.starts_with("sops publish --yes") && l.ends_with("prod/db.yaml")),
+        "{:?}",
+        harness.log()
+    );
+    assert!(stdout(&harness.run(&["list-config"])).contains("s3://team-secrets/prod/"));
+
+    let output = harness.run(&["publish", "dev/db.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("No destination rule matches"));
+}
+
+/// Every file below `dir` whose contents include `needle`
+fn files_containing(dir: &std::path::Path, needle: &str) -> Vec<std::path::PathBuf> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            found.extend(files_containing(&path, needle));
+        } else if std::fs::read(&path).is_ok_and(|c| String::from_utf8_lossy(&c).contains(needle)) {
+            found.push(path);
+        }
+    }
+    found
+}
+
+#[test]
+fn in_memory_commands_never_write_plaintext() {
+    let harness = Harness::new();
+    harness.write_config();
+    let secret = "plaintext-canary-3179";
+    let encrypted = harness.write(
+        "table.yaml",
+        &format!("github:\n  password: {}\nsops:\n    mac: fake\n", secret),
+    );
+    let tmpdir = harness.dir.path().join("tmp");
+    std::fs::create_dir(&tmpdir).unwrap();
+    let env = [("TMPDIR", tmpdir.to_str().unwrap())];
+
+    for args in [
+        vec!["read", "table.yaml"],
+        vec!["read", "table.yaml", "--format", "json"],
+        vec!["export", "csv", "table.yaml"],
+    ] {
+        let output = harness.run_with_env(&args, &env);
+        assert!(output.status.success(), "{:?}: {}", args, stderr(&output));
+        assert!(stdout(&output).contains(secret), "{:?}", args);
+    }
+
+    assert!(std::fs::read_dir(&tmpdir).unwrap().next().is_none());
+    assert_eq!(
+        files_containing(harness.dir.path(), secret),
+        vec![encrypted]
+    );
+    assert!(
+        harness
+            .log()
+            .iter()
+            .all(|l| !l.contains("--output") && !l.contains("--in-place")),
+        "{:?}",
+        harness.log()
+    );
+}
+
+#[test]
+fn init_from_key_writes_config_without_prompts() {
+    let harness = Harness::new();
+    let output = harness.run(&[
+        "init",
+        "--from-key",
+        "op://Vault/Item/Key",
+        "--rule",
+        r"path_regex=k8s/.*\.yaml$,encrypted_regex=^(data|stringData)$",
+        "--rule",
+        "path_regex=.*",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .log()
+            .contains(&"op read op://Vault/Item/Key".to_string())
+    );
+
+    let config: serde_yaml::Value = serde_yaml::from_str(&harness.read(".sops.yaml")).unwrap();
+    assert_eq!(config["creation_rules"][0]["path_regex"], r"k8s/.*\.yaml$");
+    assert_eq!(
+        config["creation_rules"][0]["encrypted_regex"],
+        "^(data|stringData)$"
+    );
+    assert_eq!(
+        config["creation_rules"][1]["age"].as_str(),
+        Some(harness.public_key().as_str())
+    );
+    let opsops: serde_yaml::Value = serde_yaml::from_str(&harness.read(".opsops.yaml")).unwrap();
+    assert_eq!(opsops["onepassworditem"], "op://Vault/Item/Key");
+
+    // Never overwrites an existing config
+    let output = harness.run(&["init", "--from-key", &harness.public_key()]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("already exists"));
+}
+
+#[test]
+fn reads_the_key_from_a_document() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.fake_binary(
+        "op",
+        r#"#!/bin/sh
+echo "op $*" >> "$FAKE_LOG"
+case "$1 $2" in
+    "item get") echo '{"id":"abc","category":"DOCUMENT"}' ;;
+    "document get") printf '# created: 2024-01-01T00:00:00Z\n# public key: age1...\n%s\n' "$FAKE_AGE_KEY" ;;
+esac
+"#,
+    );
+    harness.write("secrets.yaml", "password: hunter2\nsops:\n    mac: fake\n");
+
+    let output = harness.run(&["--op-item", "op://Vault/keys.txt", "read", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let log = harness.log();
+    assert!(log.contains(&"op document get keys.txt --vault Vault".to_string()));
+    assert!(log.contains(&format!("key-file {}", harness.private_key())));
+
+    // Items that aren't documents need a field
+    harness.fake_binary("op", "#!/bin/sh\necho '{\"category\":\"LOGIN\"}'\n");
+    let output = harness.run(&["--op-item", "op://Vault/keys.txt", "read", "secrets.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("op://Vault/keys.txt/<field>"));
+}
+
+#[test]
+fn retries_rate_limited_op_calls() {
+    let harness = Harness::new();
+    harness.write_config();
+    // The first `op read` is throttled, later ones succeed
+    harness.fake_binary(
+        "op",
+        r#"#!/bin/sh
+echo "op $*" >> "$FAKE_LOG"
+marker="$(dirname "$FAKE_LOG")/throttled"
+if [ ! -e "$marker" ]; then
+    touch "$marker"
+    echo "[ERROR] Too Many Requests (429)" >&2
+    exit 1
+fi
+echo "$FAKE_AGE_KEY"
+"#,
+    );
+    let config_dir = harness.dir.path().join("config/opsops");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.yaml"),
+        "op_rate_limit:\n  backoff_ms: 10\n",
+    )
+    .unwrap();
+    harness.write("secrets.yaml", "password: hunter2\nsops:\n    mac: fake\n");
+
+    let output = harness.run(&["read", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("rate limited"));
+    let reads = harness
+        .log()
+        .iter()
+        .filter(|line| line.starts_with("op read"))
+        .count();
+    assert_eq!(reads, 2);
+}
+
+#[test]
+fn completions_install_is_idempotent() {
+    let harness = Harness::new();
+    let home = harness.dir.path().join("home");
+    let env = [("HOME", home.to_str().unwrap())];
+
+    let output = harness.run_with_env(&["completions", "fish", "--install"], &env);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let installed = harness
+        .dir
+        .path()
+        .join("config/fish/completions/opsops.fish");
+    let script = std::fs::read_to_string(&installed).unwrap();
+    assert!(script.contains("complete -c opsops"));
+    assert!(script.contains("opsops __complete op-item"));
+
+    let output = harness.run_with_env(&["completions", "fish", "--install"], &env);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("up to date"));
+
+    // zsh needs a line in .zshrc, which isn't added without asking
+    let output = harness.run_with_env(&["completions", "zsh", "--install"], &env);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("add this line to"));
+    assert!(!home.join(".zshrc").exists());
+
+    let output = harness.run(&["completions", "bash"]);
+    assert!(stdout(&output).contains("complete -F _opsops"));
+}
+
+#[test]
+fn info_reports_versions_and_config() {
+    let harness = Harness::new();
+    harness.write_config();
+
+    let output = harness.run(&["info", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let info: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["tools"][0]["name"], "sops");
+    assert_eq!(info["tools"][0]["version"], "sops 3.10.2 (latest)");
+    assert_eq!(info["tools"][1]["version"], "2.30.0");
+    assert_eq!(info["key_reference"], "op://Vault/Item/Key");
+    assert_eq!(info["key_source"], ".opsops.yaml");
+    assert!(!stdout(&output).contains(&harness.private_key()));
+
+    let output = harness.run(&["--version", "--json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let version: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(version["name"], "opsops");
+    assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+
+    let output = harness.run(&["--version"]);
+    assert_eq!(
+        stdout(&output).trim(),
+        format!("opsops {}", env!("CARGO_PKG_VERSION"))
+    );
+}
+
+#[test]
+fn errors_are_followed_by_the_next_step() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "creation_rules:\n- path_regex: \\.yaml$\n  age: {}\n",
+            harness.public_key()
+        ),
+    );
+    harness.fake_binary(
+        "sops",
+        "#!/bin/sh\necho 'Error getting data key: 0 successful groups required, got 0' >&2\nexit 1\n",
+    );
+    harness.write("secrets.yaml", "password: hunter2\nsops:\n    mac: fake\n");
+    harness.write("app.json", "{\"password\": \"hunter2\"}\n");
+
+    // Classified from what sops printed
+    let output = harness.run(&["read", "secrets.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("→ Your key is not a recipient of this file"));
+
+    // Classified from the project when sops wrote to the terminal
+    let output = harness.run(&["encrypt", "app.json"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("→ Add a creation rule for it: opsops target-keys app.json"));
+}
+
+#[test]
+fn findings_as_sarif() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nschemas:\n- path_regex: .*\n  schema: schema.json\n",
+    );
+    harness.write("schema.json", r#"{"type": "object"}"#);
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["verify", "--format", "sarif", "secrets.yaml"]);
+    assert!(!output.status.success());
+    let sarif: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "unencrypted-file");
+    assert_eq!(
+        result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+        "secrets.yaml"
+    );
+
+    // A key staged in git, found on its line
+    harness.write(
+        "keys/backup.txt",
+        &format!("# old key\n{}\n", harness.private_key()),
+    );
+    let repo = git2::Repository::open(harness.project()).unwrap();
+    let mut index = repo.index().unwrap();
+    index
+        .add_path(std::path::Path::new("keys/backup.txt"))
+        .unwrap();
+    index.write().unwrap();
+
+    let output = harness.run(&["scan", "--format", "sarif"]);
+    assert!(!output.status.success());
+    let sarif: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "committed-private-key");
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "keys/backup.txt");
+    assert_eq!(location["region"]["startLine"], 2);
+    assert!(!stdout(&output).contains(&harness.private_key()));
+}
+
+#[test]
+fn passphrase_mode_and_upgrade() {
+    let harness = Harness::new();
+    let passphrase = [("OPSOPS_PASSPHRASE", "correct horse battery staple")];
+    let output = harness.run_with_env(&["init", "--passphrase"], &passphrase);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let opsops = harness.read(".opsops.yaml");
+    assert!(opsops.contains("keyprovider: passphrase"));
+    assert!(!opsops.contains("onepassworditem"));
+    let settings: serde_yaml::Value = serde_yaml::from_str(&opsops).unwrap();
+    let derived = settings["passphrase"]["public_key"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(harness.read(".sops.yaml").contains(&derived));
+
+    // Encrypting derives the key, 1Password is never asked
+    harness.write("secrets.yaml", "password: hunter2\n");
+    let output = harness.run_with_env(&["encrypt", "secrets.yaml"], &passphrase);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!harness.log().iter().any(|line| line.starts_with("op ")));
+    let key_line = harness
+        .log()
+        .into_iter()
+        .find(|line| line.starts_with("key-file AGE-SECRET-KEY-"))
+        .unwrap();
+    assert!(!key_line.contains(&harness.private_key()));
+
+    let output = harness.run_with_env(
+        &["read", "secrets.yaml"],
+        &[("OPSOPS_PASSPHRASE", "wrong horse")],
+    );
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Wrong passphrase"));
+
+    // The upgrade re-encrypts for the key in 1Password and switches to it
+    harness.write(
+        "secrets.yaml",
+        &format!(
+            "password: ENC[...]\nsops:\n    age:\n    - recipient: {derived}\n    mac: fake\n"
+        ),
+    );
+    let output = harness.run_with_env(
+        &["--op-item", "op://Vault/Item/Key", "key", "upgrade"],
+        &passphrase,
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let log = harness.log();
+    let updatekeys = log
+        .iter()
+        .position(|line| line.starts_with("sops updatekeys --yes"))
+        .unwrap();
+    assert_eq!(log[updatekeys + 1], key_line);
+    let sops_yaml = harness.read(".sops.yaml");
+    assert!(sops_yaml.contains(&harness.public_key()));
+    assert!(!sops_yaml.contains(&derived));
+    let opsops = harness.read(".opsops.yaml");
+    assert!(opsops.contains("onepassworditem: op://Vault/Item/Key"));
+    assert!(!opsops.contains("passphrase"));
+}
+
+#[test]
+fn new_scaffolds_a_template() {
+    let harness = Harness::new();
+    let public_key = harness.public_key();
+    let output = harness.run(&["new", "dotenv-app", "app", "--from-key", &public_key]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let sops_yaml = harness.read("app/.sops.yaml");
+    assert!(sops_yaml.contains(r"path_regex: config/.*\.env$"));
+    assert!(sops_yaml.contains(&public_key));
+    for secret in ["app/config/development.env", "app/config/production.env"] {
+        assert!(harness.read(secret).contains("sops:"), "{}", secret);
+    }
+    assert!(!harness.read("app/.env.example").contains("sops:"));
+    assert!(
+        harness
+            .read("app/.github/workflows/secrets.yml")
+            .contains("opsops verify")
+    );
+    // The hook goes to the enclosing repository
+    assert!(harness.read(".git/hooks/pre-commit").contains("opsops"));
+    assert!(stdout(&output).contains("not the repository root"));
+    // Encrypting needs no key
+    assert!(!harness.log().iter().any(|line| line.starts_with("op ")));
+
+    let output = harness.run(&["new", "dotenv-app", "app", "--from-key", &public_key]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("already has a .sops.yaml"));
+}
+
+#[test]
+fn decrypt_to_unique_output() {
+    let harness = Harness::new();
+    harness.write_config();
+    let source = harness.write("app.yaml.enc", "token: abc\nsops:\n    mac: fake\n");
+    let runtime = harness.dir.path().join("run");
+    let env = [("XDG_RUNTIME_DIR", runtime.to_str().unwrap())];
+
+    let first = harness.run_with_env(&["decrypt", "--unique-output", "app.yaml.enc"], &env);
+    let second = harness.run_with_env(&["decrypt", "--unique-output", "app.yaml.enc"], &env);
+    assert!(first.status.success(), "{}", stderr(&first));
+    let paths: Vec<String> = [&first, &second]
+        .iter()
+        .map(|output| stdout(output).trim().to_string())
+        .collect();
+    assert_ne!(paths[0], paths[1]);
+    for path in &paths {
+        assert!(path.starts_with(runtime.join("opsops/decrypted").to_str().unwrap()));
+        assert!(path.ends_with("/app.yaml"));
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "token: abc\n");
+    }
+    assert!(!harness.project().join("app.yaml").exists());
+
+    // A plaintext copy changed after the ciphertext gets a warning
+    harness.write("app.yaml", "token: edited\n");
+    std::fs::File::options()
+        .write(true)
+        .open(&source)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(60))
+        .unwrap();
+    let output = harness.run(&["decrypt", "app.yaml.enc"]);
+    assert!(stdout(&output).contains("app.yaml is newer than app.yaml.enc"));
+}
+
+#[test]
+fn key_fingerprints_match_everywhere() {
+    use sha2::{Digest, Sha256};
+
+    let harness = Harness::new();
+    harness.write_config();
+    let digest = Sha256::digest(harness.public_key().as_bytes());
+    let expected: String = digest[..4].iter().map(|b| format!("{:02x}", b)).collect();
+
+    let own = harness.run(&["key", "fingerprint"]);
+    assert!(own.status.success(), "{}", stderr(&own));
+    assert_eq!(
+        stdout(&own).trim(),
+        format!("{}  {}", expected, harness.public_key())
+    );
+
+    // A pasted secret key is fingerprinted by its public key, and never echoed
+    let pasted = harness.run(&["key", "fingerprint", &harness.private_key()]);
+    assert_eq!(stdout(&pasted), stdout(&own));
+
+    let list = harness.run(&["list-config"]);
+    assert!(stdout(&list).contains(&format!("{} [{}]", harness.public_key(), expected)));
+
+    let invalid = harness.run(&["key", "fingerprint", "age1nope"]);
+    assert!(!invalid.status.success());
+}
+
+#[test]
+fn global_flags_from_the_environment() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run_with_env(
+        &["info", "--format", "json"],
+        &[("OPSOPS_OP_ITEM", "op://CI/Deploy/key")],
+    );
+    let info: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(info["key_reference"], "op://CI/Deploy/key");
+    assert_eq!(info["key_source"], "--op-item");
+
+    let output = harness.run_with_env(&["encrypt", "secrets.yaml"], &[("OPSOPS_READ_ONLY", "1")]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("read-only"));
+    let output = harness.run_with_env(
+        &["encrypt", "secrets.yaml"],
+        &[("OPSOPS_READ_ONLY", "false")],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn encrypt_diff_summarizes_keys() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "user: admin\npassword: hunter2\n");
+    // Encrypts only `password`, like a rule with encrypted_regex: ^password$
+    harness.fake_binary(
+        "sops",
+        r#"#!/bin/sh
+while [ $# -gt 1 ]; do
+  [ "$1" = --output ] && out=$2
+  shift
+done
+sed 's/^password: \(.*\)/password: ENC[AES256_GCM,data:\1]/' "$1" > "$out.tmp"
+printf 'sops:\n  age:\n  - recipient: age1x\n    enc: datakey\n' >> "$out.tmp"
+mv "$out.tmp" "$out"
+"#,
+    );
+
+    let output = harness.run(&["encrypt", "--diff", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("Encrypted 1 value(s):\n  🔒 password\n"),
+        "{}",
+        out
+    );
+    assert!(out.contains("Left in plaintext by the rule (1):\n     user\n"));
+    assert!(out.contains("Data key: freshly generated"));
+    assert!(!out.contains("hunter2"));
+}
+
+#[test]
+fn formatters_and_theme() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["--formatter", "json", "encrypt", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "{\"level\":\"info\",\"message\":\"Encrypting to secrets.yaml\"}\n\
+         {\"level\":\"success\",\"message\":\"Successfully encrypted file to with SOPS\"}\n"
+    );
+
+    let output = harness.run(&["--formatter", "quiet", "encrypt", "missing.yaml"]);
+    assert!(stdout(&output).is_empty());
+    assert!(stderr(&output).contains("⨯ File not found: missing.yaml"));
+
+    let output = harness.run_with_env(
+        &["decrypt", "secrets.yaml"],
+        &[("OPSOPS_FORMATTER", "plain")],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("ok "), "{}", stdout(&output));
+
+    // Terminals that render emoji poorly
+    let config = harness.dir.path().join("config/opsops/config.yaml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, "theme:\n  emoji: false\n  success: magenta\n").unwrap();
+    let output = harness.run(&["encrypt", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Encrypting to secrets.yaml\n"));
+    assert!(stdout(&output).contains("ok Successfully encrypted"));
+    assert!(!stdout(&output).contains("🔐"));
+}
+
+#[test]
+fn messages_in_the_users_language() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run_with_env(&["encrypt", "missing.yaml"], &[("LANG", "de_DE.UTF-8")]);
+    assert!(stderr(&output).contains("Datei nicht gefunden: missing.yaml"));
+
+    let output = harness.run_with_env(&["encrypt", "secrets.yaml"], &[("LC_ALL", "de_AT.UTF-8")]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Datei erfolgreich mit SOPS verschlüsselt"));
+
+    // The user config wins over the locale
+    let config = harness.dir.path().join("config/opsops/config.yaml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, "language: en\n").unwrap();
+    let output = harness.run_with_env(&["decrypt", "secrets.yaml"], &[("LANG", "de_DE.UTF-8")]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Successfully decrypted file with SOPS"));
+}
+
+#[test]
+fn accessible_prompts() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("app.yaml", "password: hunter2\n");
+    harness.write("db.yaml", "password: hunter2\n");
+
+    // Text narrows the numbered list down, a number picks from it
+    let output = harness.run_with_stdin(&["--accessible", "encrypt"], "db\n7\n1\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let err = stderr(&output);
+    assert!(err.contains("Choose a file to encrypt:\n"), "{}", err);
+    assert!(err.contains("or text to narrow down the list, nothing to cancel:"));
+    assert!(err.contains("7 is not a number from 1 to 1."));
+    assert!(err.contains("Chose db.yaml plaintext"));
+    assert!(!err.contains("🔒"));
+    assert!(harness.read("db.yaml").contains("sops:"));
+    assert!(!harness.read("app.yaml").contains("sops:"));
+    // Plain messages, unless a formatter is picked
+    assert!(stdout(&output).contains("ok Successfully encrypted"));
+
+    // Nothing cancels
+    let output = harness.run_with_stdin(&["--accessible", "decrypt"], "\n");
+    assert!(!output.status.success());
+    assert!(harness.read("db.yaml").contains("sops:"));
+    assert!(!harness.log().iter().any(|line| line.contains("--decrypt")));
+}
+
+#[test]
+fn help_guides_and_man_pages() {
+    let harness = Harness::new();
+
+    let output = harness.run(&["help"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Usage: opsops [OPTIONS] [COMMAND]"));
+    assert!(stdout(&output).contains("key-rotation  Adding and removing recipients"));
+
+    let output = harness.run(&["help", "key-rotation"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("KEY ROTATION\n"));
+
+    let output = harness.run(&["help", "key", "upgrade"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Usage: opsops key upgrade"));
+
+    let output = harness.run(&["help", "rotation"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("No command or guide named 'rotation'"));
+
+    let output = harness.run(&["man"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains(".TH opsops 1"));
+
+    let output = harness.run(&["man", "key", "upgrade"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains(".TH opsops-key-upgrade 1"));
+}
+
+#[test]
+fn record_and_replay_a_session() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["--record", "session.json", "encrypt", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let session: serde_json::Value = serde_json::from_str(&harness.read("session.json")).unwrap();
+    assert_eq!(session["exit_code"], 0);
+    let invocations = session["invocations"].as_array().unwrap();
+    let sops = invocations
+        .iter()
+        .find(|i| i["command"][0] == "sops")
+        .expect("sops was recorded");
+    assert_eq!(sops["exit_code"], 0);
+    assert!(
+        sops["command"]
+            .as_array()
+            .unwrap()
+            .contains(&"--encrypt".into())
+    );
+    assert!(!harness.read("session.json").contains("hunter2"));
+
+    let output = harness.run(&["replay", "session.json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("sops --encrypt --output secrets.yaml"),
+        "{}",
+        out
+    );
+    assert!(out.contains("exit 0"));
+    assert!(out.contains("Exited with 0"));
+
+    let output = harness.run(&["--record", "failed.json", "decrypt", "missing.yaml"]);
+    assert!(!output.status.success());
+    let output = harness.run(&["replay", "failed.json"]);
+    let out = stdout(&output);
+    assert!(out.contains("missing.yaml"), "{}", out);
+    assert!(out.contains("Exited early"));
+}
+
+#[test]
+fn age_key_in_the_environment() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: ENC[x]\nsops:\n    mac: fake\n");
+    let env_key = age::x25519::Identity::generate();
+    let env_key = age::secrecy::ExposeSecret::expose_secret(&env_key.to_string()).to_string();
+    let env = [("SOPS_AGE_KEY", env_key.as_str())];
+
+    // The project's key wins, sops only gets that one. The warning stays out
+    // of the decrypted content on stdout.
+    let output = harness.run_with_env(&["read", "secrets.yaml"], &env);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Found SOPS_AGE_KEY in the environment"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(stdout(&output), "password: ENC[x]\n");
+    let log = harness.log().join("\n");
+    assert!(log.contains(&format!("key-file {}", harness.private_key())));
+    assert!(!log.contains(&env_key));
+
+    // Chosen explicitly, nothing to warn about
+    std::fs::remove_file(harness.dir.path().join("invocations.log")).unwrap();
+    let output = harness.run_with_env(&["--prefer-op-key", "read", "secrets.yaml"], &env);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("Found SOPS_AGE_KEY"));
+
+    // The environment's key, without asking 1Password
+    std::fs::remove_file(harness.dir.path().join("invocations.log")).unwrap();
+    let output = harness.run_with_env(&["--prefer-env-key", "read", "secrets.yaml"], &env);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let log = harness.log();
+    assert!(log.contains(&format!("key-file {}", env_key)), "{:?}", log);
+    assert!(!log.iter().any(|line| line.starts_with("op read")));
+
+    let output = harness.run(&[
+        "--prefer-env-key",
+        "--prefer-op-key",
+        "decrypt",
+        "secrets.yaml",
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn profile_perf_reports_where_the_time_went() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("secrets.yaml", "password: ENC[x]\nsops:\n    mac: fake\n");
+
+    let output = harness.run(&["--profile-perf", "read", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let err = stderr(&output);
+    for line in [
+        "Time spent (--profile-perf):",
+        "1Password (op)",
+        "sops",
+        "key retrieval",
+        "config IO",
+        "opsops itself",
+        "op read op://Vault/Item/Key",
+    ] {
+        assert!(err.contains(line), "{} missing from {}", line, err);
+    }
+    assert!(!stdout(&output).contains("Time spent"));
+
+    let output = harness.run(&[
+        "--profile-perf",
+        "--formatter",
+        "json",
+        "read",
+        "secrets.yaml",
+    ]);
+    let err = stderr(&output);
+    let report: serde_json::Value = serde_json::from_str(err.lines().last().unwrap()).unwrap();
+    assert_eq!(report["perf"]["categories"]["op"]["count"], 1);
+    assert_eq!(report["perf"]["categories"]["sops"]["count"], 1);
+
+    // Also when the command fails
+    let output = harness.run(&["--profile-perf", "read", "missing.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Time spent"));
+}
+
+#[test]
+fn indentation_and_key_order() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nformatting:\n  indent: 4\n",
+    );
+    harness.write("plain.yaml", "b: 1\na: 2\n");
+    harness.write(
+        "secrets.yaml",
+        "zeta: 1\nalpha:\n  y: 2\n  x: 3\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&["encrypt", "plain.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .log()
+            .iter()
+            .any(|line| line.starts_with("sops --encrypt --indent 4 --output"))
+    );
+
+    // The flag wins over .opsops.yaml
+    let output = harness.run(&["read", "--indent", "2", "--sort-keys", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "alpha:\n  x: 3\n  y: 2\nzeta: 1\n");
+    assert!(
+        harness
+            .log()
+            .iter()
+            .any(|line| line.starts_with("sops --decrypt --indent 2"))
+    );
+
+    let output = harness.run(&["decrypt", "--sort-keys", "secrets.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        harness.read("secrets.yaml"),
+        "alpha:\n  x: 3\n  y: 2\nzeta: 1\n"
+    );
+}
+
+#[test]
+fn stats_summarizes_encrypted_files() {
+    let harness = Harness::new();
+    harness.write_config();
+    let encrypted = |recipient: &str, date: &str| {
+        format!(
+            "password: ENC[AES256_GCM,data:abc]\nsops:\n    age:\n        - recipient: {}\n          enc: x\n    lastmodified: \"{}\"\n    mac: ENC[...]\n",
+            recipient, date
+        )
+    };
+    harness.write("old.yaml", &encrypted("age1alice", "2023-05-01T10:00:00Z"));
+    harness.write(
+        "new/secret.yaml",
+        &encrypted("age1bob", "2024-06-01T10:00:00Z"),
+    );
+    harness.write("plain.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["stats", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stats: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(stats["encrypted_files"], 2);
+    assert_eq!(stats["rules"], 1);
+    assert_eq!(
+        stats["recipients"],
+        serde_json::json!(["age1alice", "age1bob"])
+    );
+    assert_eq!(stats["files_per_rule"][0]["files"], 2);
+    assert_eq!(stats["oldest_rotation"], "2023-05-01T10:00:00Z");
+    assert_eq!(stats["least_recently_rotated"][0]["path"], "old.yaml");
+    assert!(harness.log().is_empty());
+
+    let output = harness.run(&["stats"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("2024-06-01T10:00:00Z"));
+}
+
+#[test]
+fn stats_reports_expired_values() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "tls.yaml",
+        "tls:\n    crt: ENC[AES256_GCM,data:abc]\n    crt__expires: 2020-01-31\n    key: ENC[AES256_GCM,data:def]\n    key__expires: ENC[AES256_GCM,data:ghi]\nsops:\n    mac: ENC[...]\n",
+    );
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nvalue_expiry:\n- file: api.yaml\n  key: token\n  expires: 2999-12-31\n  note: CI token\n",
+    );
+
+    let output = harness.run(&["stats", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stats: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(
+        stats["value_expiry"],
+        serde_json::json!([
+            {"file": "tls.yaml", "key": "tls.crt", "expires": "2020-01-31", "state": "expired"},
+            {"file": "api.yaml", "key": "token", "expires": "2999-12-31", "state": "valid", "note": "CI token"},
+        ])
+    );
+    assert_eq!(
+        stats["unreadable_expiry"],
+        serde_json::json!(["tls.yaml#tls.key"])
+    );
+
+    let output = harness.run(&["stats", "--fail-on-expired"]);
+    assert_eq!(output.status.code(), Some(1));
+    let out = stdout(&output);
+    assert!(
+        out.contains("2 (1 expired or due within 30 days)"),
+        "{}",
+        out
+    );
+    assert!(out.contains("2020-01-31  tls.yaml#tls.crt"), "{}", out);
+    assert!(!out.contains("api.yaml#token"), "{}", out);
+    assert!(stderr(&output).contains("1 value(s) have expired."));
+    assert!(harness.log().is_empty());
+}
+
+#[test]
+fn meta_reads_metadata_without_a_key() {
+    let harness = Harness::new();
+    harness.write(
+        "secret.yaml",
+        "password: ENC[AES256_GCM,data:abc]\nsops:\n    age:\n        - recipient: age1alice\n          enc: x\n    lastmodified: \"2024-06-01T10:00:00Z\"\n    mac: ENC[AES256_GCM,data:mac]\n    encrypted_regex: ^password$\n    version: 3.9.0\n",
+    );
+    harness.write("plain.yaml", "password: hunter2\n");
+
+    let output = harness.run(&["meta", "secret.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("age1alice"), "{}", out);
+    assert!(out.contains("3.9.0"));
+    assert!(out.contains("present"));
+
+    let output = harness.run(&["meta", "secret.yaml", "--format", "json"]);
+    let meta: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(meta["mac"], true);
+    assert_eq!(meta["age"][0]["recipient"], "age1alice");
+    assert_eq!(meta["encrypted_regex"], "^password$");
+    assert!(harness.log().is_empty());
+
+    let output = harness.run(&["meta", "plain.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("isn't encrypted"));
+}
+
+#[test]
+fn grep_searches_encrypted_files_in_memory() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "monitoring.yaml",
+        "grafana:\n  admin_token: glsa_secret\nsops:\n    mac: fake\n",
+    );
+    harness.write(
+        "db.yaml",
+        "postgres:\n  password: hunter2\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&["grep", "grafana"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("monitoring.yaml: grafana.admin_token = <redacted>"),
+        "{}",
+        out
+    );
+    assert!(!out.contains("glsa_secret"));
+    assert!(!out.contains("db.yaml"));
+    // The key is fetched once for all files
+    let log = harness.log();
+    assert_eq!(log.iter().filter(|l| l.starts_with("op read")).count(), 1);
+
+    let output = harness.run(&["grep", "--values", "--show-values", "^hunter"]);
+    assert!(stdout(&output).contains("db.yaml: postgres.password = hunter2"));
+
+    let output = harness.run(&["grep", "--keys", "hunter"]);
+    assert_eq!(output.status.code(), Some(1));
+    // Nothing was written to disk
+    assert!(harness.read("db.yaml").contains("sops:"));
+}
+
+#[test]
+fn mv_keeps_rules_and_index_in_sync() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("app.yaml", "password: ENC[...]\nsops:\n    mac: fake\n");
+    let output = harness.run(&["target-keys", "app.yaml", "--encrypted-regex", "^password$"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let repo = git2::Repository::open(harness.project()).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("app.yaml")).unwrap();
+    index.write().unwrap();
+
+    let output = harness.run(&["mv", "app.yaml", "config/app.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!harness.project().join("app.yaml").exists());
+    assert!(harness.read("config/app.yaml").contains("sops:"));
+
+    let config: serde_yaml::Value = serde_yaml::from_str(&harness.read(".sops.yaml")).unwrap();
+    assert_eq!(
+        config["creation_rules"][1]["path_regex"],
+        "^config/app\\.yaml$"
+    );
+    let index = git2::Repository::open(harness.project())
+        .unwrap()
+        .index()
+        .unwrap();
+    assert!(
+        index
+            .get_path(std::path::Path::new("app.yaml"), 0)
+            .is_none()
+    );
+    assert!(
+        index
+            .get_path(std::path::Path::new("config/app.yaml"), 0)
+            .is_some()
+    );
+
+    // Plaintext files and existing targets are refused
+    harness.write("plain.yaml", "a: b\n");
+    assert!(
+        !harness
+            .run(&["mv", "plain.yaml", "other.yaml"])
+            .status
+            .success()
+    );
+    harness.write("taken.yaml", "password: ENC[...]\nsops:\n    mac: fake\n");
+    let output = harness.run(&["mv", "config/app.yaml", "taken.yaml"]);
+    assert!(stderr(&output).contains("already exists"));
+}
+
+#[test]
+fn rm_deletes_file_copies_and_rules() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "app.yaml.enc",
+        "password: ENC[...]\nsops:\n    age:\n        - recipient: age1someone\n    mac: fake\n",
+    );
+    let output = harness.run(&[
+        "target-keys",
+        "app.yaml.enc",
+        "--encrypted-regex",
+        "^password$",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = harness.run(&["decrypt", "app.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.project().join("app.yaml").exists());
+    let repo = git2::Repository::open(harness.project()).unwrap();
+    let mut index = repo.index().unwrap();
+    index
+        .add_path(std::path::Path::new("app.yaml.enc"))
+        .unwrap();
+    index.write().unwrap();
+
+    let output = harness.run(&["rm", "app.yaml.enc", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!harness.project().join("app.yaml.enc").exists());
+    assert!(!harness.project().join("app.yaml").exists());
+    assert!(stdout(&output).contains("age1someone"));
+
+    let config: serde_yaml::Value = serde_yaml::from_str(&harness.read(".sops.yaml")).unwrap();
+    assert_eq!(config["creation_rules"].as_sequence().unwrap().len(), 1);
+    let index = git2::Repository::open(harness.project())
+        .unwrap()
+        .index()
+        .unwrap();
+    assert!(
+        index
+            .get_path(std::path::Path::new("app.yaml.enc"), 0)
+            .is_none()
+    );
+    let audit = harness.read(".opsops/audit.jsonl");
+    assert!(audit.contains("\"action\":\"rm\""), "{}", audit);
+    assert!(audit.contains("age1someone"), "{}", audit);
+
+    // The backup brings it back
+    let output = harness.run(&["restore", "app.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.read("app.yaml.enc").contains("sops:"));
+
+    harness.write("plain.yaml", "a: b\n");
+    assert!(!harness.run(&["rm", "plain.yaml"]).status.success());
+    assert!(harness.project().join("plain.yaml").exists());
+}
+
+#[test]
+fn cp_copies_values_between_encrypted_files() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "api.yaml",
+        "db:\n  user: api\n  password: hunter2\nsops:\n    mac: fake\n",
+    );
+    harness.write("worker.yaml", "name: worker\nsops:\n    mac: fake\n");
+
+    let output = harness.run(&[
+        "cp",
+        "api.yaml#db.password",
+        "worker.yaml#database.password",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stdout(&output).contains("hunter2"));
+    let worker = harness.read("worker.yaml");
+    assert!(
+        worker.contains("database:\n  password: hunter2"),
+        "{}",
+        worker
+    );
+    assert!(worker.contains("name: worker"));
+    assert!(worker.contains("sops:"));
+    // The source is left alone
+    assert!(harness.read("api.yaml").starts_with("db:\n  user: api"));
+
+    // Existing values need --force, the key path defaults to the source's
+    let output = harness.run(&["cp", "api.yaml#db", "worker.yaml#database"]);
+    assert!(stderr(&output).contains("--force"));
+    let output = harness.run(&["cp", "api.yaml#db", "worker.yaml", "--force"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.read("worker.yaml").contains("db:\n  user: api"));
+
+    harness.write("plain.yaml", "a: b\n");
+    let output = harness.run(&["cp", "api.yaml#db.user", "plain.yaml#user"]);
+    assert!(!output.status.success());
+    assert_eq!(harness.read("plain.yaml"), "a: b\n");
+}
+
+#[test]
+fn import_op_inserts_a_field_into_an_encrypted_file() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("app.yaml", "name: app\nsops:\n    mac: fake\n");
+
+    let output = harness.run(&[
+        "import",
+        "op",
+        "op://Vault/Database/password",
+        "--into",
+        "app.yaml",
+        "--path",
+        "db.password",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let app = harness.read("app.yaml");
+    assert!(
+        app.contains(&format!("db:\n  password: {}", harness.private_key())),
+        "{}",
+        app
+    );
+    assert!(
+        harness
+            .log()
+            .contains(&"op read op://Vault/Database/password".to_string())
+    );
+    assert!(!stdout(&output).contains(&harness.private_key()));
+
+    // The value exists now
+    let output = harness.run(&[
+        "import",
+        "op",
+        "op://Vault/Database/password",
+        "--into",
+        "app.yaml",
+        "--path",
+        "db.password",
+    ]);
+    assert!(stderr(&output).contains("--force"));
+}
+
+#[test]
+fn export_op_creates_an_item_with_sections() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "app.yaml",
+        "api_key: abc\ndb:\n  password: hunter2\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&[
+        "export", "op", "app.yaml", "--vault", "Infra", "--item", "app-prod",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let create = harness
+        .log()
+        .into_iter()
+        .find(|line| line.starts_with("op item create"))
+        .expect("op item create was run");
+    assert!(
+        create.contains("--vault Infra --title app-prod"),
+        "{}",
+        create
+    );
+    assert!(create.contains("api_key[password]=abc"), "{}", create);
+    assert!(
+        create.contains("db.password[password]=hunter2"),
+        "{}",
+        create
+    );
+}
+
+#[test]
+fn sync_pulls_and_pushes_1password_fields() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nsync:\n- op: op://Infra/Database/password\n  target: db.yaml#stringData.password\n",
+    );
+    harness.write(
+        "db.yaml",
+        "stringData:\n  password: old\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&["sync", "status"]);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert!(stdout(&output).contains("differs"));
+    assert!(!stdout(&output).contains("old"));
+
+    let output = harness.run(&["sync", "pull"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .read("db.yaml")
+            .contains(&format!("password: {}", harness.private_key()))
+    );
+    let output = harness.run(&["sync", "status"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("in sync"));
+
+    harness.write(
+        "db.yaml",
+        "stringData:\n  password: rotated\nsops:\n    mac: fake\n",
+    );
+    let output = harness.run(&["sync", "push"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .log()
+            .contains(&"op item edit Database --vault Infra password=rotated".to_string())
+    );
+}
+
+#[test]
+fn ci_generate_writes_a_pipeline_for_the_project() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "secrets/app.yaml",
+        "password: ENC[...]\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&["ci", "generate", "github"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let workflow = harness.read(".github/workflows/opsops.yml");
+    let parsed: serde_yaml::Value = serde_yaml::from_str(&workflow).unwrap();
+    assert!(parsed["jobs"]["checks"].is_mapping());
+    assert_eq!(
+        parsed["jobs"]["decrypt"]["env"]["OPSOPS_OP_ITEM"].as_str(),
+        Some("op://Vault/Item/Key")
+    );
+    assert!(workflow.contains("opsops read secrets/app.yaml"));
+    assert!(stdout(&output).contains("OP_SERVICE_ACCOUNT_TOKEN"));
+
+    let output = harness.run(&["ci", "generate", "github"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--force"));
+
+    let output = harness.run(&["ci", "generate", "gitlab"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("local: ci/opsops.gitlab-ci.yml"));
+    let pipeline: serde_yaml::Value =
+        serde_yaml::from_str(&harness.read("ci/opsops.gitlab-ci.yml")).unwrap();
+    assert!(pipeline["opsops:decrypt"]["script"].is_sequence());
+}
+
+#[test]
+fn read_and_decrypt_resolve_includes() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "app.yaml",
+        "name: app\ndb: !opsops-include secrets/db.yaml\nsops:\n    mac: fake\n",
+    );
+    harness.write(
+        "secrets/db.yaml",
+        "password: hunter2\nsops:\n    mac: fake\n",
+    );
+
+    let output = harness.run(&["read", "app.yaml", "--resolve-includes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "name: app\ndb:\n  password: hunter2\n");
+    let key_reads = harness
+        .log()
+        .iter()
+        .filter(|line| line.starts_with("op read"))
+        .count();
+    assert_eq!(key_reads, 1);
+
+    let output = harness.run(&["decrypt", "app.yaml", "--resolve-includes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        harness.read("app.yaml"),
+        "name: app\ndb:\n  password: hunter2\n"
+    );
+
+    let output = harness.run(&["decrypt", "app.yaml", "--resolve-includes"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("not encrypted"));
+}
+
+#[test]
+fn encrypt_guardrails_warn_or_refuse() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "app.yaml",
+        "password: hunter2\ntls_key: /etc/ssl/private/app.key\n",
+    );
+
+    let output = harness.run(&["encrypt", "app.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("look like file paths rather than secrets"));
+    assert!(stdout(&output).contains("tls_key"));
+    assert!(!stdout(&output).contains("/etc/ssl"));
+    assert!(harness.read("app.yaml").contains("sops:"));
+
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nguardrails:\n  max_size: 16\n  severity: error\n",
+    );
+    harness.write("big.yaml", "password: correct horse battery staple\n");
+    let output = harness.run(&["encrypt", "big.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Refusing to encrypt big.yaml"));
+    assert!(stderr(&output).contains("guardrails.max_size"));
+    assert!(!harness.read("big.yaml").contains("sops:"));
+}
+
+#[test]
+fn edit_create_starts_new_files_from_the_rule_schema() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nschemas:\n- path_regex: .*\n  schema: schemas/db.json\n",
+    );
+    harness.write(
+        "schemas/db.json",
+        r#"{"required": ["password", "port"], "properties": {"port": {"type": "integer"}}}"#,
+    );
+
+    let output = harness.run(&["edit", "secrets/db.yaml"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--create"));
+
+    let output = harness.run(&["edit", "--create", "secrets/db.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Creating file: secrets/db.yaml"));
+    assert_eq!(
+        harness.read("secrets/db.yaml"),
+        "password: ''\nport: 0\nsops:\n    mac: fake\n"
+    );
+    assert!(
+        harness
+            .log()
+            .iter()
+            .any(|l| l.contains("--filename-override secrets/db.yaml"))
+    );
+
+    // Quitting the editor without saving leaves nothing behind
+    harness.fake_binary(
+        "sops",
+        "#!/bin/sh\ncase \" $* \" in *\" --encrypt \"*) cat /dev/stdin; echo 'sops:' ;; *) exit 200 ;; esac\n",
+    );
+    let output = harness.run(&["edit", "--create", "secrets/other.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Nothing saved, not created"));
+    assert!(!harness.project().join("secrets/other.yaml").exists());
+}
+
+#[test]
+fn decrypt_writes_to_mapped_outputs() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\ndecrypt_outputs:\n- path_regex: ^k8s/(.+)\\.enc\\.yaml$\n  output: .generated/k8s/$1.yaml\n",
+    );
+    harness.write(
+        "k8s/db.enc.yaml",
+        "password: hunter2\nsops:\n    mac: fake\n",
+    );
+    harness.write("app.yaml.enc", "token: abc\nsops:\n    mac: fake\n");
+    harness.write("in-place.yaml", "key: v\nsops:\n    mac: fake\n");
+
+    let output = harness.run(&["decrypt", "k8s/db.enc.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("k8s/db.enc.yaml -> .generated/k8s/db.yaml"));
+    assert!(stdout(&output).contains("not ignored by git"));
+    assert_eq!(
+        harness.read(".generated/k8s/db.yaml"),
+        "password: hunter2\n"
+    );
+
+    harness.write(".gitignore", ".generated/\n");
+    std::fs::remove_dir_all(harness.project().join(".generated")).unwrap();
+    let output = harness.run(&["decrypt", "--all"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stdout(&output).contains("not ignored by git"));
+    assert!(stdout(&output).contains("Skipping 1 file(s)"));
+    assert_eq!(
+        harness.read(".generated/k8s/db.yaml"),
+        "password: hunter2\n"
+    );
+    assert_eq!(harness.read("app.yaml"), "token: abc\n");
+    assert!(harness.read("in-place.yaml").contains("sops:"));
+    let key_reads = harness
+        .log()
+        .iter()
+        .filter(|l| l.starts_with("op read"))
+        .count();
+    assert_eq!(key_reads, 2);
+}
+
+#[test]
+fn host_keys_pick_the_key_of_this_host() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("app.yaml.enc", "token: abc\nsops:\n    mac: fake\n");
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nhost_keys:\n- host: no-such-host-*\n  onepassworditem: op://Fleet/Other/key\n- host: '*'\n  key_file: keys/host.txt\n",
+    );
+    harness.write(
+        "keys/host.txt",
+        &format!("# this host\n{}\n", harness.private_key()),
+    );
+
+    let output = harness.run(&["read", "app.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!harness.log().iter().any(|l| l.starts_with("op ")));
+    assert!(
+        harness
+            .log()
+            .iter()
+            .any(|l| l.contains(&harness.private_key()))
+    );
+
+    harness.write(
+        ".opsops.yaml",
+        "version: 1\nonepassworditem: op://Vault/Item/Key\nhost_keys:\n- host: '*'\n  onepassworditem: op://Fleet/Host/key\n",
+    );
+    let output = harness.run(&["read", "app.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .log()
+            .contains(&"op read op://Fleet/Host/key".to_string())
+    );
+
+    // --op-item still wins
+    let output = harness.run(&["--op-item", "op://Vault/Item/Key", "read", "app.yaml.enc"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .log()
+            .contains(&"op read op://Vault/Item/Key".to_string())
+    );
+}
+
+#[test]
+fn verify_recipients_lists_missing_and_extra_keys() {
+    let harness = Harness::new();
+    let me = harness.public_key();
+    let escrow = age::x25519::Identity::generate().to_public().to_string();
+    let file = |recipients: &[&str]| {
+        let age: String = recipients
+            .iter()
+            .map(|r| format!("        - recipient: {}\n          enc: x\n", r))
+            .collect();
+        format!(
+            "password: ENC[...]\nsops:\n    age:\n{}    mac: fake\n",
+            age
+        )
+    };
+    harness.write("good.yaml", &file(&[&me, &escrow]));
+    harness.write("bad.yaml", &file(&[&me, "age1stale"]));
+
+    let against = format!("{},{}", me, escrow);
+    let output = harness.run(&["verify-recipients", "good.yaml", "--against", &against]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("exactly the 2 given key(s)"));
+
+    let output = harness.run(&[
+        "verify-recipients",
+        "good.yaml",
+        "bad.yaml",
+        "--against",
+        &me,
+        &escrow,
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    let out = stdout(&output);
+    assert!(out.contains("✓ good.yaml"), "{}", out);
+    assert!(out.contains(&format!("- {} [", escrow)), "{}", out);
+    assert!(out.contains("+ age1stale ["), "{}", out);
+    assert!(harness.log().is_empty());
+}
+
+#[test]
+fn diff_renders_the_plaintext_change_since_a_revision() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "app.yaml",
+        "user: admin\npassword: old\nsops:\n    mac: fake\n",
+    );
+    let repo = git2::Repository::open(harness.project()).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("app.yaml")).unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "Add app", &tree, &[])
+        .unwrap();
+
+    let output = harness.run(&["diff", "app.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("app.yaml is unchanged since HEAD"));
+
+    harness.write(
+        "app.yaml",
+        "user: admin\npassword: new\nsops:\n    mac: fake\n",
+    );
+    let output = harness.run(&["diff", "app.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).ends_with(" user: admin\n-password: old\n+password: new\n"),
+        "{}",
+        stdout(&output)
+    );
+    let log = harness.log();
+    assert_eq!(
+        log.iter()
+            .filter(|line| line.starts_with("sops --decrypt"))
+            .count(),
+        2
+    );
+    assert_eq!(
+        log.iter()
+            .filter(|line| line.starts_with("op read"))
+            .count(),
+        1
+    );
+
+    let output = harness.run(&["diff", "app.yaml", "--renderer", "side-by-side"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("password: old"));
+    assert!(stdout(&output).contains("| password: new"));
+
+    // External tools read both versions from named pipes
+    harness.fake_binary(
+        "fakediff",
+        "#!/bin/sh\nshift $(($# - 2))\n[ -p \"$1\" ] && [ -p \"$2\" ] && echo pipes\ncat \"$1\" \"$2\"\nexit 1\n",
+    );
+    let config = harness.dir.path().join("config/opsops/config.yaml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, "diff_renderer: fakediff --color\n").unwrap();
+    let output = harness.run(&["diff", "app.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "pipes\nuser: admin\npassword: old\nuser: admin\npassword: new\n"
+    );
+
+    let output = harness.run(&["diff", "app.yaml", "--rev", "nope"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Unknown revision 'nope'"));
+}
+
+#[test]
+fn set_generates_values_into_encrypted_files() {
+    let harness = Harness::new();
+    harness.write_config();
+
+    let output = harness.run(&[
+        "set",
+        "app.yaml",
+        "db.password",
+        "--generate",
+        "password:24",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Generated app.yaml#db.password"));
+    let content = harness.read("app.yaml");
+    let password = content
+        .lines()
+        .find_map(|line| line.strip_prefix("  password: "))
+        .unwrap()
+        .trim_matches('\'');
+    assert_eq!(password.len(), 24, "{}", content);
+    assert!(content.ends_with("sops:\n    mac: fake\n"));
+    assert!(!harness.log().iter().any(|line| line.contains(password)));
+
+    let output = harness.run(&["set", "app.yaml", "db.password", "--generate", "uuid"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("pass --force to overwrite it"));
+
+    let output = harness.run_with_stdin(&["set", "app.yaml", "db.password", "--force"], "typed\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.read("app.yaml").contains("  password: typed\n"));
+
+    harness.fake_binary(
+        "openssl",
+        "#!/bin/sh\necho \"openssl $*\" >> \"$FAKE_LOG\"\ncat > /dev/null\necho '$apr1$salt$hash'\n",
+    );
+    let output = harness.run(&["set", "app.yaml", "web", "--generate", "htpasswd:admin"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        harness
+            .read("app.yaml")
+            .contains("  htpasswd: admin:$apr1$salt$hash\n")
+    );
+    assert!(
+        harness
+            .log()
+            .contains(&"openssl passwd -apr1 -stdin".to_string())
+    );
+
+    let output = harness.run(&["set", "app.yaml", "x", "--generate", "pin"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Unknown generator 'pin'"));
+}
+
+#[test]
+fn inspect_describes_certificates_and_keys() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.fake_binary(
+        "openssl",
+        r#"#!/bin/sh
+echo "openssl $*" >> "$FAKE_LOG"
+pem=$(cat)
+case "$1" in
+    x509) case "$pem" in *OLD*) after="Jan  5 00:00:00 2020 GMT" ;; *) after="Dec 31 23:59:59 2999 GMT" ;; esac
+        printf 'Certificate:\n    Issuer: CN = Example CA\n    Not After : %s\n    Subject: CN = example.com\n        Public-Key: (2048 bit)\n        Modulus:\n    X509v3 Subject Alternative Name:\n        DNS:example.com, DNS:www.example.com\n' "$after" ;;
+    pkey) printf 'ED25519 Public-Key:\npub:\n    d7:5a\n' ;;
+esac
+"#,
+    );
+    let pem = |label: &str, body: &str| {
+        format!("-----BEGIN {label}-----\n{body}\n-----END {label}-----\n")
+    };
+    let indent = |text: String| text.replace('\n', "\n        ");
+    harness.write(
+        "tls.yaml",
+        &format!(
+            "tls:\n    crt: |\n        {}\n    key: |\n        {}\n    user: admin\nsops:\n    mac: fake\n",
+            indent(pem("CERTIFICATE", "NEW")),
+            indent(pem("PRIVATE KEY", "KEY"))
+        ),
+    );
+    harness.write(
+        "legacy.yaml",
+        &format!(
+            "ca: |\n    {}\nsops:\n    mac: fake\n",
+            pem("CERTIFICATE", "OLD").replace('\n', "\n    ")
+        ),
+    );
+
+    let output = harness.run(&["inspect", "tls.yaml", "tls.crt"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("subject      CN = example.com"), "{}", out);
+    assert!(
+        out.contains("SANs         DNS:example.com, DNS:www.example.com"),
+        "{}",
+        out
+    );
+    assert!(out.contains("expires      2999-12-31"), "{}", out);
+    assert!(out.contains("key          RSA 2048 bit"), "{}", out);
+    assert!(!out.contains("BEGIN"), "{}", out);
+
+    let output = harness.run(&["inspect", "tls.yaml", "tls.key", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let details: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(
+        details,
+        serde_json::json!([{"kind": "private key", "key_type": "ED25519"}])
+    );
+    assert!(
+        harness
+            .log()
+            .iter()
+            .any(|line| line == "openssl pkey -noout -text_pub -passin pass:")
+    );
+
+    let output = harness.run(&["inspect", "tls.yaml", "tls.user"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "admin\n");
+
+    let output = harness.run(&["inspect", "--certs"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    let old = out.find("2020-01-05  legacy.yaml#ca").expect(&out);
+    let new = out.find("2999-12-31  tls.yaml#tls.crt").expect(&out);
+    assert!(old < new, "{}", out);
+    assert!(!out.contains("tls.key"), "{}", out);
+}
+
+#[test]
+fn kubeconfig_use_wipes_the_decrypted_copy() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write(
+        "prod.yaml",
+        "apiVersion: v1\nkind: Config\nclusters:\n- name: prod\nsops:\n    mac: fake\n",
+    );
+    harness.fake_binary(
+        "fakeshell",
+        "#!/bin/sh\necho \"shell $KUBECONFIG $(stat -c %a \"$KUBECONFIG\")\" >> \"$FAKE_LOG\"\ncat \"$KUBECONFIG\" >> \"$FAKE_LOG\"\nexit 3\n",
+    );
+    let tmpdir = harness.dir.path().join("tmp");
+    std::fs::create_dir(&tmpdir).unwrap();
+    let tmpdir = tmpdir.to_str().unwrap();
+
+    let output = harness.run_with_env(
+        &["kubeconfig", "use", "prod.yaml", "--tmpdir", tmpdir],
+        &[("SHELL", "fakeshell")],
+    );
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    let log = harness.log();
+    let shell = log.iter().find(|line| line.starts_with("shell ")).unwrap();
+    assert!(shell.ends_with(" 600"), "{}", shell);
+    assert!(log.contains(&"- name: prod".to_string()));
+    assert!(stdout(&output).contains("Wiped"));
+    assert_eq!(std::fs::read_dir(tmpdir).unwrap().count(), 0);
+
+    let output = harness.run(&[
+        "kubeconfig",
+        "use",
+        "prod.yaml",
+        "--print",
+        "--tmpdir",
+        tmpdir,
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let printed = stdout(&output);
+    let kubeconfig = printed
+        .lines()
+        .find_map(|line| line.strip_prefix("export KUBECONFIG="))
+        .unwrap()
+        .trim_matches('\'')
+        .to_string();
+    assert!(printed.contains(&format!("kubeconfig wipe '\\''{}'\\''' EXIT", kubeconfig)));
+    assert!(
+        std::fs::read_to_string(&kubeconfig)
+            .unwrap()
+            .contains("kind: Config")
+    );
+
+    let output = harness.run(&["kubeconfig", "wipe", "prod.yaml"]);
+    assert!(!output.status.success());
+    assert!(harness.project().join("prod.yaml").exists());
+
+    let output = harness.run(&["kubeconfig", "wipe", &kubeconfig]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(std::fs::read_dir(tmpdir).unwrap().count(), 0);
+
+    harness.write("app.yaml", "password: hunter2\nsops:\n    mac: fake\n");
+    let output = harness.run(&["kubeconfig", "use", "app.yaml", "--tmpdir", tmpdir]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("doesn't look like a kubeconfig"));
+}
+
+#[test]
+fn teardown_keeps_backups_for_restore() {
+    let harness = Harness::new();
+    harness.write_config();
+    harness.write("app.yaml", "password: hunter2\nsops:\n    mac: fake\n");
+    harness.write(".opsops/cache.json", "{}");
+
+    let output = harness.run_with_stdin(&["--accessible", "teardown"], "yes\ndecrypt\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(harness.read("app.yaml"), "password: hunter2\n");
+    assert!(stdout(&output).contains("except the backups in"));
+    assert!(!harness.project().join(".opsops/cache.json").exists());
+    assert!(harness.project().join(".opsops/backups").is_dir());
+
+    let output = harness.run(&["restore", "app.yaml"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        harness.read("app.yaml"),
+        "password: hunter2\nsops:\n    mac: fake\n"
+    );
+}
+
+#[test]
+fn key_recover_from_mnemonic() {
+    let harness = Harness::new();
+    harness.write_config();
+    // The BIP39 phrase of 32 zero bytes
+    let phrase = format!("{}art\n", "abandon ".repeat(23));
+
+    let output = harness.run_with_stdin(&["key", "recover", "--mnemonic"], &phrase);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    let private_key = out
+        .split_whitespace()
+        .find(|word| word.starts_with("AGE-SECRET-KEY-1"))
+        .unwrap();
+    assert!(private_key.starts_with("AGE-SECRET-KEY-1QQQQQQQQQQ"));
+    let identity: age::x25519::Identity = private_key.parse().unwrap();
+    assert!(out.contains(&identity.to_public().to_string()));
+    assert!(out.contains("not a recipient of any rule"));
+
+    let output = harness.run_with_stdin(&["key", "recover", "--mnemonic"], &"abandon ".repeat(24));
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("checksum"));
+}
+
+#[test]
+fn registry_sync_and_add_recipient_by_name() {
+    let harness = Harness::new();
+    harness.write_config();
+    let alice = age::x25519::Identity::generate().to_public().to_string();
+
+    // A git repository holding the registry
+    let keys = harness.dir.path().join("keys");
+    let repo = git2::Repository::init(&keys).unwrap();
+    std::fs::create_dir(keys.join("team")).unwrap();
+    std::fs::write(
+        keys.join("team/members.yaml"),
+        format!("members:\n  alice:\n    age: {}\n", alice),
+    )
+    .unwrap();
+    let mut index = repo.index().unwrap();
+    index
+        .add_path(std::path::Path::new("team/members.yaml"))
+        .unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "Add keys", &tree, &[])
+        .unwrap();
+
+    let source = format!("{}#team/members.yaml", keys.display());
+    let output = harness.run(&["registry", "sync", &source]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("+ alice"));
+    assert!(stdout(&output).contains("Synced 1 member(s)"));
+
+    let output = harness.run(&["key", "add-recipient", "--member", "alice"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config: serde_yaml::Value = serde_yaml::from_str(&harness.read(".sops.yaml")).unwrap();
+    assert_eq!(
+        config["creation_rules"][0]["age"].as_str().unwrap(),
+        format!("{},{}", harness.public_key(), alice)
+    );
+
+    let output = harness.run(&["list-config"]);
+    assert!(stdout(&output).contains(&format!("{} (alice)", alice)));
+
+    let output = harness.run(&["key", "add-recipient", "--member", "mallory"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("No member 'mallory'"));
+}
+
+#[test]
+fn expire_sweep_removes_expired_recipients_and_rotates() {
+    let harness = Harness::new();
+    let contractor = age::x25519::Identity::generate().to_public().to_string();
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "creation_rules:\n- path_regex: .*\n  age: {},{}\n",
+            harness.public_key(),
+            contractor
+        ),
+    );
+    harness.write(
+        ".opsops.yaml",
+        &format!(
+            "version: 1\nonepassworditem: op://Vault/Item/Key\nrecipient_expiry:\n- recipient: {}\n  expires: 2020-01-31\n  note: Acme\n",
+            contractor
+        ),
+    );
+    harness.write(
+        "secrets.yaml",
+        &format!(
+            "password: ENC[AES256_GCM,data:x]\nsops:\n    age:\n        - recipient: {}\n        - recipient: {}\n    mac: fake\n",
+            harness.public_key(),
+            contractor
+        ),
+    );
+
+    let output = harness.run(&["doctor"]);
+    assert!(stdout(&output).contains("Expired recipients can still decrypt secrets"));
+    assert!(stdout(&output).contains(&format!(
+        "{} (Acme) expired after 2020-01-31: 1 rule(s), 1 file(s)",
+        contractor
+    )));
+
+    let output = harness.run(&["key", "expire-sweep", "--dry-run"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.read(".sops.yaml").contains(&contractor));
+
+    let output = harness.run(&["key", "expire-sweep"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!harness.read(".sops.yaml").contains(&contractor));
+    assert!(harness.log().iter().any(|line| line.starts_with("sops")
+        && line.contains(&format!("--rotate --in-place --rm-age {}", contractor))
+        && line.ends_with("secrets.yaml")));
+}
+
+#[test]
+fn expire_sweep_notifies_without_secrets() {
+    let harness = Harness::new();
+    harness.fake_binary(
+        "curl",
+        "#!/bin/sh\necho \"curl $*\" >> \"$FAKE_LOG\"\ncat >> \"$FAKE_LOG\"\n",
+    );
+    let contractor = age::x25519::Identity::generate().to_public().to_string();
+    harness.write(
+        ".sops.yaml",
+        &format!(
+            "creation_rules:\n- path_regex: .*\n  age: {},{}\n",
+            harness.public_key(),
+            contractor
+        ),
+    );
+    harness.write(
+        ".opsops.yaml",
+        &format!(
+            "version: 1\nonepassworditem: op://Vault/Item/Key\nrecipient_expiry:\n- recipient: {}\n  expires: 2020-01-31\nnotifications:\n- webhook: $OPSOPS_TEST_HOOK\n  on: [rotate]\n- webhook: https://unused.example.com\n  on: [add-recipient]\n",
+            contractor
+        ),
+    );
+    harness.write(
+        "secrets.yaml",
+        &format!(
+            "password: ENC[AES256_GCM,data:x]\nsops:\n    age:\n        - recipient: {}\n    mac: fake\n",
+            contractor
+        ),
+    );
+
+    let output = harness.run_with_env(
+        &["key", "expire-sweep"],
+        &[("OPSOPS_TEST_HOOK", "https://hooks.example.com/abc")],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let log = harness.log().join("\n");
+    assert!(log.contains("curl --config -"), "{}", log);
+    assert!(
+        log.contains("url = \"https://hooks.example.com/abc\""),
+        "{}",
+        log
+    );
+    assert!(!log.contains("unused.example.com"), "{}", log);
+    assert!(log.contains("\\\"event\\\":\\\"rotate\\\""), "{}", log);
+    let fingerprint = harness.run(&["key", "fingerprint", &contractor]);
+    assert!(
+        log.contains(stdout(&fingerprint).split_whitespace().next().unwrap()),
+        "{}",
+        log
+    );
+    let sent = &log[log.find("curl --config -").unwrap()..];
+    assert!(!sent.contains(&harness.private_key()), "{}", sent);
+}
+
+#[test]
+fn expire_sweep_resumes_an_interrupted_run() {
+    let harness = Harness::new();
+    harness.write_config();
+    let contractor = age::x25519::Identity::generate().to_public().to_string();
+    harness.write(
+        ".opsops.yaml",
+        &format!(
+            "version: 1\nonepassworditem: op://Vault/Item/Key\nrecipient_expiry:\n- recipient: {}\n  expires: 2020-01-31\n",
+            contractor
+        ),
+    );
+    for name in ["a.yaml", "b.yaml"] {
+        harness.write(
+            name,
+            &format!(
+                "password: ENC[AES256_GCM,data:x]\nsops:\n    age:\n        - recipient: {}\n    mac: fake\n",
+                contractor
+            ),
+        );
+    }
+    // Rotating drops the recipient from the file, b.yaml fails the first time
+    let fake_sops = |fail: &str| {
+        format!(
+            "#!/bin/sh\necho \"sops $*\" >> \"$FAKE_LOG\"\nfor file; do :; done\ncase \"$file\" in *{}) exit 1 ;; esac\ngrep -v recipient \"$file\" > \"$file.new\" && mv \"$file.new\" \"$file\"\n",
+            fail
+        )
+    };
+    harness.fake_binary("sops", &fake_sops("b.yaml"));
+
+    let output = harness.run(&["key", "expire-sweep"]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(
+        harness
+            .project()
+            .join(".opsops/journal/expire-sweep.json")
+            .is_file()
+    );
+
+    harness.fake_binary("sops", &fake_sops("never"));
+    let output = harness.run(&["key", "expire-sweep"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("ok (earlier run)"));
+    assert!(out.contains("2 succeeded, 0 failed, 0 skipped, 1 finished by an earlier run"));
+    // a.yaml is only rotated once
+    let rotations: Vec<_> = harness
+        .log()
+        .into_iter()
+        .filter(|line| line.contains("--rotate"))
+        .collect();
+    assert_eq!(rotations.len(), 3, "{:?}", rotations);
+    assert!(rotations[2].ends_with("b.yaml"));
+    assert!(
+        !harness
+            .project()
+            .join(".opsops/journal/expire-sweep.json")
+            .exists()
+    );
+}
+
+#[test]
+fn init_from_public_key() {
+    let harness = Harness::new();
+    let output = harness.run(&["init", "--from-key", &harness.public_key()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(harness.log().is_empty());
+    let config: serde_yaml::Value = serde_yaml::from_str(&harness.read(".sops.yaml")).unwrap();
+    assert_eq!(config["creation_rules"][0]["path_regex"], ".*");
+    assert!(!harness.project().join(".opsops.yaml").exists());
+
+    let harness = Harness::new();
+    let output = harness.run(&["init", "--from-key", "not-a-key"]);
+    assert!(!output.status.success());
+    let output = harness.run(&["init", "--rule", "path_regex=.*"]);
+    assert!(!output.status.success());
+}
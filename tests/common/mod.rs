@@ -0,0 +1,228 @@
+//! Shared harness for the integration tests: runs the real opsops binary
+//! against fake `op` and `sops` scripts that record how they were invoked.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use age::secrecy::ExposeSecret;
+use age::x25519::Identity;
+use git2::Repository;
+use tempfile::TempDir;
+
+/// Fake 1Password CLI. `op read` prints `$FAKE_AGE_KEY`, everything else succeeds silently.
+const FAKE_OP: &str = r#"#!/bin/sh
+echo "op $*" >> "$FAKE_LOG"
+case "$1" in
+    read) echo "$FAKE_AGE_KEY" ;;
+    --version) echo "2.30.0" ;;
+    whoami) echo "URL: https://my.1password.com" ;;
+esac
+"#;
+
+/// Fake sops. Records its argv, the config and key it was handed, and `--encrypt`
+/// writes the input file followed by a fake `sops:` metadata block to `--output` or
+/// stdout. `--decrypt` and `-d` print the file without the metadata block and fail
+/// for files that don't have one. `--decrypt --in-place` overwrites the file instead.
+const FAKE_SOPS: &str = r#"#!/bin/sh
+echo "sops $*" >> "$FAKE_LOG"
+if [ -n "$SOPS_AGE_KEY_FILE" ]; then
+    echo "key-file $(cat "$SOPS_AGE_KEY_FILE")" >> "$FAKE_LOG"
+fi
+if [ -n "$SOPS_AGE_KEY" ]; then
+    echo "key-env $SOPS_AGE_KEY" >> "$FAKE_LOG"
+fi
+if [ "$1" = "--config" ]; then
+    echo "config $(cat "$2" | tr '\n' ' ')" >> "$FAKE_LOG"
+    shift 2
+fi
+out=""
+prev=""
+for arg in "$@"; do
+    [ "$prev" = "--output" ] && out="$arg"
+    [ "$arg" = "--in-place" ] && in_place=1
+    prev="$arg"
+done
+[ -n "$in_place" ] && out="$prev"
+case "$1" in
+    --version) echo "sops 3.10.2 (latest)" ;;
+    --encrypt) content=$(cat "$prev"); printf '%s\nsops:\n    mac: fake\n' "$content" > "${out:-/dev/stdout}" ;;
+    --decrypt) raw=$(cat "$prev")
+        case "$raw" in *"sops:"*) ;; *) echo "sops metadata not found" >&2; exit 128 ;; esac
+        content=$(printf '%s\n' "$raw" | grep -v -e '^sops:' -e '^    mac:'); printf '%s\n' "$content" > "${out:-/dev/stdout}" ;;
+    -d) grep -q '^sops:' "$2" || { echo "sops metadata not found" >&2; exit 128; }
+        grep -v -e '^sops:' -e '^    mac:' "$2" ;;
+esac
+"#;
+
+pub struct Harness {
+    pub dir: TempDir,
+    pub key: Identity,
+    bin_dir: PathBuf,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let bin_dir = dir.path().join("bin");
+        fs::create_dir(&bin_dir).unwrap();
+        for (name, script) in [("op", FAKE_OP), ("sops", FAKE_SOPS)] {
+            let path = bin_dir.join(name);
+            fs::write(&path, script).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        // A git repository, so the project root is found without falling back
+        Repository::init(dir.path().join("project")).unwrap();
+
+        Harness {
+            dir,
+            key: Identity::generate(),
+            bin_dir,
+        }
+    }
+
+    /// Puts a fake binary on the PATH opsops runs with
+    pub fn fake_binary(&self, name: &str, script: &str) {
+        let path = self.bin_dir.join(name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// The directory opsops runs in, contains the .sops.yaml
+    pub fn project(&self) -> PathBuf {
+        self.dir.path().join("project")
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.project().join(".sops.yaml")
+    }
+
+    pub fn private_key(&self) -> String {
+        self.key.to_string().expose_secret().to_string()
+    }
+
+    pub fn public_key(&self) -> String {
+        self.key.to_public().to_string()
+    }
+
+    pub fn write(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.project().join(name);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    pub fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.project().join(name)).unwrap()
+    }
+
+    /// Writes a .sops.yaml with one rule for `.*` and an .opsops.yaml pointing at
+    /// `op://Vault/Item/Key`
+    pub fn write_config(&self) {
+        self.write(
+            ".sops.yaml",
+            &format!(
+                "creation_rules:\n- path_regex: .*\n  age: {}\n",
+                self.public_key()
+            ),
+        );
+        self.write(
+            ".opsops.yaml",
+            "version: 1\nonepassworditem: op://Vault/Item/Key\n",
+        );
+    }
+
+    /// Every line the fake binaries recorded, in order
+    pub fn log(&self) -> Vec<String> {
+        fs::read_to_string(self.dir.path().join("invocations.log"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Runs opsops in the project directory with the fake binaries first on PATH
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.run_with_env(args, &[])
+    }
+
+    /// Like [`Harness::run`], with additional environment variables
+    pub fn run_with_env(&self, args: &[&str], env: &[(&str, &str)]) -> Output {
+        self.command(args, env).output().unwrap()
+    }
+
+    /// Like [`Harness::run`], piping `input` to stdin
+    pub fn run_with_stdin(&self, args: &[&str], input: &str) -> Output {
+        let mut child = self
+            .command(args, &[])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    fn command(&self, args: &[&str], env: &[(&str, &str)]) -> Command {
+        let path = format!(
+            "{}:{}",
+            self.bin_dir.display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let mut command = Command::new(env!("CARGO_BIN_EXE_opsops"));
+        command
+            .args(args)
+            .current_dir(self.project())
+            .env("PATH", path)
+            .env("FAKE_LOG", self.dir.path().join("invocations.log"))
+            .env("FAKE_AGE_KEY", self.private_key())
+            .env("OPSOPS_AGENT_SOCK", self.dir.path().join("no-agent.sock"))
+            .env("XDG_CONFIG_HOME", self.dir.path().join("config"))
+            .env("XDG_CACHE_HOME", self.dir.path().join("cache"))
+            .env("NO_COLOR", "1")
+            .env("LANG", "C.UTF-8")
+            .env_remove("LC_ALL")
+            .env_remove("LC_MESSAGES")
+            .env_remove("SOPS_AGE_KEY")
+            .env_remove("OPSOPS_CHDIR")
+            .env_remove("OPSOPS_SOPS_FILE")
+            .env_remove("OPSOPS_OP_ITEM")
+            .env_remove("OPSOPS_AGE_KEY_ENV")
+            .env_remove("OPSOPS_READ_ONLY")
+            .env_remove("OPSOPS_FORMATTER")
+            .env_remove("OPSOPS_ACCESSIBLE")
+            .env_remove("OPSOPS_RECORD")
+            .env_remove("OPSOPS_PREFER_ENV_KEY")
+            .env_remove("OPSOPS_PREFER_OP_KEY")
+            .env_remove("OPSOPS_PROFILE_PERF")
+            .env_remove("SOPS_AGE_KEY_CMD")
+            .env_remove("OPSOPS_PASSPHRASE")
+            .env_remove("SOPS_AGE_KEY_FILE")
+            .env_remove("SUDO_USER")
+            .env_remove("DOAS_USER")
+            .env_remove("SUDO_UID")
+            .env_remove("PKEXEC_UID")
+            .envs(env.iter().copied());
+        command
+    }
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}